@@ -0,0 +1,10 @@
+-- Persist admin bot wizard progress so restarts and forgotten flows are handled
+CREATE TABLE IF NOT EXISTS wizard_sessions (
+    chat_id INTEGER PRIMARY KEY,
+    kind TEXT NOT NULL, -- 'phone', 'auth_code', '2fa', 'prompt'
+    payload TEXT NOT NULL DEFAULT '{}', -- JSON with the serializable part of the state
+    expires_at INTEGER NOT NULL, -- unix timestamp
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_wizard_sessions_expires ON wizard_sessions(expires_at);
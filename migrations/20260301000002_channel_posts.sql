@@ -0,0 +1,6 @@
+-- Separate reply probability for channel posts auto-forwarded into discussion groups
+ALTER TABLE accounts ADD COLUMN channel_post_reply_probability INTEGER NOT NULL DEFAULT 0; -- 0-100
+
+-- Sender identity for stored messages (user or channel acting as sender)
+ALTER TABLE messages_history ADD COLUMN sender_id INTEGER;
+ALTER TABLE messages_history ADD COLUMN sender_chat_id INTEGER;
@@ -0,0 +1,26 @@
+-- Prompt-injection policy overrides per (account, chat)
+CREATE TABLE IF NOT EXISTS chat_security_policies (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    policy TEXT NOT NULL DEFAULT 'log', -- 'off', 'log', 'strike', 'block'
+    risk_threshold REAL, -- NULL = use SECURITY_RISK_THRESHOLD
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+-- Detected prompt-injection attempts
+CREATE TABLE IF NOT EXISTS security_violations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    score REAL NOT NULL,
+    matched_rules TEXT NOT NULL,
+    action TEXT NOT NULL, -- 'logged', 'strike', 'blocked'
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_security_violations_chat_user ON security_violations(account_id, chat_id, user_id, created_at);
@@ -0,0 +1,13 @@
+-- Reusable personas that accounts can be bound to
+CREATE TABLE IF NOT EXISTS personas (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    prompt TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Accounts bound to a persona use its prompt instead of system_prompt
+ALTER TABLE accounts ADD COLUMN persona_id INTEGER REFERENCES personas(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_accounts_persona ON accounts(persona_id);
@@ -0,0 +1,12 @@
+-- Dialogs discovered per userbot account, with per-chat overrides
+CREATE TABLE IF NOT EXISTS account_chats (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    chat_type TEXT NOT NULL DEFAULT 'unknown', -- 'private', 'group', 'supergroup', 'channel', 'secret'
+    is_denied INTEGER NOT NULL DEFAULT 0, -- 1 = never reply here, regardless of allowed_chats
+    reply_probability INTEGER, -- NULL = use the account's reply_probability
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
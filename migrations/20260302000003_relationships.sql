@@ -0,0 +1,17 @@
+-- How each userbot account "knows" the people it talks to
+CREATE TABLE IF NOT EXISTS relationships (
+    account_id INTEGER NOT NULL,
+    peer_user_id INTEGER NOT NULL,
+    how_known TEXT NOT NULL DEFAULT '', -- e.g. "коллега по работе", set by admin or extracted from history
+    familiarity REAL NOT NULL DEFAULT 0.0, -- 0.0 = stranger, 1.0 = close friend
+    inside_jokes TEXT NOT NULL DEFAULT '[]', -- JSON array of strings
+    message_count INTEGER NOT NULL DEFAULT 0,
+    refreshed_at_count INTEGER NOT NULL DEFAULT 0, -- message_count at the last profile extraction
+    last_interaction_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, peer_user_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_history_sender ON messages_history(account_id, sender_id);
@@ -0,0 +1,3 @@
+-- How replies are rendered per chat: 'plain' (as before), or opt-in 'markdown'
+-- (converted to entities) or 'html'
+ALTER TABLE account_chats ADD COLUMN format_mode TEXT NOT NULL DEFAULT 'plain';
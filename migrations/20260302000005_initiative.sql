@@ -0,0 +1,5 @@
+-- Proactive conversation starters (opt-in per chat)
+ALTER TABLE account_chats ADD COLUMN initiative_enabled INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE account_chats ADD COLUMN initiative_day TEXT; -- YYYY-MM-DD the counter below belongs to
+ALTER TABLE account_chats ADD COLUMN initiative_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE account_chats ADD COLUMN last_initiative_at TIMESTAMP;
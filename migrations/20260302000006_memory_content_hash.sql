@@ -0,0 +1,4 @@
+-- Hash of normalized memory text, used to reuse embeddings and skip duplicates
+ALTER TABLE long_term_memory ADD COLUMN content_hash TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_memory_content_hash ON long_term_memory(content_hash);
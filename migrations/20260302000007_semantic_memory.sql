@@ -0,0 +1,28 @@
+-- Curated facts promoted from episodic memory (long_term_memory)
+CREATE TABLE IF NOT EXISTS semantic_memory (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    statement TEXT NOT NULL,
+    confidence REAL NOT NULL DEFAULT 0.5,
+    support_count INTEGER NOT NULL DEFAULT 1, -- how many consolidation runs confirmed it
+    embedding BLOB NOT NULL,
+    content_hash TEXT NOT NULL,
+    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+    UNIQUE (account_id, chat_id, content_hash),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_semantic_memory_account_chat ON semantic_memory(account_id, chat_id);
+
+-- Consolidation progress per chat
+CREATE TABLE IF NOT EXISTS memory_consolidation (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    last_episode_id INTEGER NOT NULL DEFAULT 0,
+    last_run_at INTEGER,
+    facts_promoted INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (account_id, chat_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
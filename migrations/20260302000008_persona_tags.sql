@@ -0,0 +1,16 @@
+-- Free-form tags for grouping personas
+CREATE TABLE IF NOT EXISTS persona_tags (
+    persona_id INTEGER NOT NULL,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (persona_id, tag),
+    FOREIGN KEY (persona_id) REFERENCES personas(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_persona_tags_tag ON persona_tags(tag);
+
+-- Nightly random rotation among the personas of a tag
+CREATE TABLE IF NOT EXISTS persona_tag_rotations (
+    tag TEXT PRIMARY KEY,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    last_rotated_at TIMESTAMP
+);
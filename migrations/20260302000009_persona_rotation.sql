@@ -0,0 +1,10 @@
+-- Weighted per-chat persona rotation
+ALTER TABLE personas ADD COLUMN rotation_weight INTEGER NOT NULL DEFAULT 1; -- 0 = never picked
+
+ALTER TABLE account_chats ADD COLUMN rotation_mode TEXT NOT NULL DEFAULT 'off'; -- 'off', 'schedule', 'conversation'
+ALTER TABLE account_chats ADD COLUMN rotation_tag TEXT; -- NULL = pick from all personas
+ALTER TABLE account_chats ADD COLUMN active_persona_id INTEGER REFERENCES personas(id) ON DELETE SET NULL;
+ALTER TABLE account_chats ADD COLUMN persona_since TIMESTAMP;
+
+-- Which persona produced each reply
+ALTER TABLE messages_history ADD COLUMN persona_id INTEGER;
@@ -0,0 +1,2 @@
+-- Telegram message a memory was taken from, so /ask can link to its sources
+ALTER TABLE long_term_memory ADD COLUMN message_id INTEGER;
@@ -0,0 +1,31 @@
+-- Weekly chat digest, opt-in per chat (weekday 0 = Monday, local time)
+ALTER TABLE account_chats ADD COLUMN digest_enabled BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE account_chats ADD COLUMN digest_weekday INTEGER NOT NULL DEFAULT 6;
+ALTER TABLE account_chats ADD COLUMN digest_hour INTEGER NOT NULL DEFAULT 19;
+ALTER TABLE account_chats ADD COLUMN last_digest_at DATETIME;
+
+-- Members' replies to the userbot's own messages (the TDLib binding doesn't deliver reactions)
+CREATE TABLE IF NOT EXISTS bot_message_responses (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    responses INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id, message_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_bot_responses_chat ON bot_message_responses(account_id, chat_id, created_at);
+
+-- Messages per member and day, answered or not
+CREATE TABLE IF NOT EXISTS member_activity (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    sender_id INTEGER NOT NULL,
+    day TEXT NOT NULL, -- YYYY-MM-DD, UTC
+    messages INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (account_id, chat_id, sender_id, day),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- IANA timezone of a chat (e.g. Europe/Moscow); NULL uses DEFAULT_TIMEZONE
+ALTER TABLE account_chats ADD COLUMN timezone TEXT;
@@ -0,0 +1,22 @@
+-- Telegram Stars payments received by the admin bot
+CREATE TABLE IF NOT EXISTS payments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    feature TEXT NOT NULL,
+    stars INTEGER NOT NULL,
+    days INTEGER,                          -- NULL = lifetime
+    telegram_charge_id TEXT NOT NULL UNIQUE,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_payments_user ON payments(user_id);
+
+-- Premium features a user may use, bought or granted by an owner
+CREATE TABLE IF NOT EXISTS entitlements (
+    user_id INTEGER NOT NULL,
+    feature TEXT NOT NULL,
+    source TEXT NOT NULL DEFAULT 'grant',  -- 'payment' or 'grant'
+    expires_at DATETIME,                   -- NULL = never
+    granted_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, feature)
+);
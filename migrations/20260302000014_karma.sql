@@ -0,0 +1,27 @@
+-- Karma: "thanks" replies earn members points, per chat
+ALTER TABLE account_chats ADD COLUMN karma_enabled BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE account_chats ADD COLUMN karma_monthly_reset BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE account_chats ADD COLUMN karma_period TEXT;   -- YYYY-MM the scores belong to
+
+CREATE TABLE IF NOT EXISTS karma (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    points INTEGER NOT NULL DEFAULT 0,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id, user_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_karma_chat_points ON karma(account_id, chat_id, points DESC);
+
+-- Messages each member already thanked, so repeated "+1" replies count once
+CREATE TABLE IF NOT EXISTS karma_thanks (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    sender_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id, sender_id, message_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,7 @@
+-- Per-chat retention overrides (NULL = use RETENTION_DAYS / keep everything)
+ALTER TABLE account_chats ADD COLUMN retention_days INTEGER;
+ALTER TABLE account_chats ADD COLUMN retention_max_messages INTEGER;
+
+-- Bodies replaced by their SHA-256 after RETENTION_HASH_AFTER_DAYS
+ALTER TABLE messages_history ADD COLUMN is_hashed BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE long_term_memory ADD COLUMN is_hashed BOOLEAN NOT NULL DEFAULT 0;
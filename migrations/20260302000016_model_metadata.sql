@@ -0,0 +1,10 @@
+-- Every chat, embedding and vision model the bot has used
+CREATE TABLE IF NOT EXISTS models (
+    kind TEXT NOT NULL,                 -- 'chat', 'embedding' or 'vision'
+    name TEXT NOT NULL,
+    dimension INTEGER,                  -- embedding size, NULL for other kinds
+    uses INTEGER NOT NULL DEFAULT 0,
+    first_used_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (kind, name)
+);
@@ -0,0 +1,8 @@
+-- Vision prompt overrides per media type; persona_id 0 is the global default
+CREATE TABLE IF NOT EXISTS vision_prompts (
+    media_type TEXT NOT NULL,           -- 'photo', 'gif' or 'video_note'
+    persona_id INTEGER NOT NULL DEFAULT 0,
+    template TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (media_type, persona_id)
+);
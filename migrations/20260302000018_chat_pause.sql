@@ -0,0 +1,3 @@
+-- Chats can be paused temporarily (e.g. when a bot-to-bot loop is suspected)
+ALTER TABLE account_chats ADD COLUMN paused_until TIMESTAMP;
+ALTER TABLE account_chats ADD COLUMN paused_reason TEXT;
@@ -0,0 +1,23 @@
+-- Named presets of per-chat settings, applied with /apply_profile
+CREATE TABLE IF NOT EXISTS chat_profiles (
+    name TEXT PRIMARY KEY,
+    persona_id INTEGER REFERENCES personas(id) ON DELETE SET NULL,
+    reply_probability INTEGER,
+    format_mode TEXT,
+    initiative_enabled BOOLEAN,
+    reply_cooldown_secs INTEGER,
+    style_notes TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Per-chat settings that only profiles (so far) set
+ALTER TABLE account_chats ADD COLUMN pinned_persona_id INTEGER REFERENCES personas(id) ON DELETE SET NULL;
+ALTER TABLE account_chats ADD COLUMN reply_cooldown_secs INTEGER;
+ALTER TABLE account_chats ADD COLUMN style_notes TEXT;
+ALTER TABLE account_chats ADD COLUMN profile_name TEXT;
+
+INSERT OR IGNORE INTO chat_profiles (name, reply_probability, format_mode, initiative_enabled, reply_cooldown_secs, style_notes) VALUES
+    ('quiet_helper', 15, 'plain', 0, 600, 'Отвечай только когда можешь реально помочь. Коротко, по делу, без шуток.'),
+    ('chaotic_member', 60, 'markdown', 1, 30, 'Шути, подкалывай, меняй тему. Иногда отвечай невпопад.'),
+    ('moderator', 25, 'plain', 0, 120, 'Держи разговор в рамках темы чата, спокойно одергивай грубиянов. Не спорь.');
@@ -0,0 +1,10 @@
+-- Who wrote a remembered chunk, so retrieval can be scoped by participant
+ALTER TABLE long_term_memory ADD COLUMN sender_id INTEGER;
+ALTER TABLE long_term_memory ADD COLUMN is_bot_author INTEGER NOT NULL DEFAULT 0;
+
+-- Per-chat retrieval filters
+ALTER TABLE account_chats ADD COLUMN rag_prefer_sender INTEGER NOT NULL DEFAULT 0; -- 1 = boost chunks by the current interlocutor
+ALTER TABLE account_chats ADD COLUMN rag_exclude_bots INTEGER NOT NULL DEFAULT 0; -- 1 = never retrieve chunks written by bots
+ALTER TABLE account_chats ADD COLUMN rag_max_age_days INTEGER; -- NULL = no time limit
+
+CREATE INDEX IF NOT EXISTS idx_long_term_memory_sender ON long_term_memory(account_id, chat_id, sender_id);
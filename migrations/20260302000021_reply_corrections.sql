@@ -0,0 +1,14 @@
+-- Owner corrections of generated replies (!edit / !del), as preference pairs
+CREATE TABLE IF NOT EXISTS reply_corrections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    prompt TEXT, -- the message the reply answered, if it was a reply
+    rejected TEXT NOT NULL, -- what the userbot wrote
+    corrected TEXT, -- what the owner wanted instead; NULL = should not have replied
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_reply_corrections_account ON reply_corrections(account_id, created_at);
@@ -0,0 +1,13 @@
+-- Curated example exchanges per persona, injected as few-shot examples
+CREATE TABLE IF NOT EXISTS persona_examples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    persona_id INTEGER NOT NULL,
+    user_text TEXT NOT NULL,
+    reply TEXT NOT NULL,
+    embedding BLOB NOT NULL, -- of user_text
+    source TEXT NOT NULL DEFAULT 'manual', -- 'manual' (/add_example) or 'ghost' (!save / !edit)
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (persona_id) REFERENCES personas(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_persona_examples_persona ON persona_examples(persona_id);
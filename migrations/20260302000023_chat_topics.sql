@@ -0,0 +1,12 @@
+-- Rolling "current topic" of a chat; the newest row is the active topic
+CREATE TABLE IF NOT EXISTS chat_topics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    topic TEXT NOT NULL,
+    embedding BLOB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_topics_chat ON chat_topics(account_id, chat_id, created_at);
@@ -0,0 +1,32 @@
+-- Telegram Business accounts connected to the business bot (BUSINESS_BOT_TOKEN)
+CREATE TABLE IF NOT EXISTS business_connections (
+    connection_id TEXT PRIMARY KEY,
+    user_id INTEGER NOT NULL, -- the business account
+    user_chat_id INTEGER NOT NULL,
+    can_reply INTEGER NOT NULL DEFAULT 0,
+    is_enabled INTEGER NOT NULL DEFAULT 1, -- as reported by Telegram
+    auto_reply INTEGER NOT NULL DEFAULT 0, -- off until the owner turns it on
+    persona_id INTEGER REFERENCES personas(id) ON DELETE SET NULL,
+    reply_mode TEXT NOT NULL DEFAULT 'away', -- 'away' = outside working hours only, 'always'
+    work_days TEXT NOT NULL DEFAULT '0,1,2,3,4', -- 0 = Monday
+    work_start_hour INTEGER NOT NULL DEFAULT 9,
+    work_end_hour INTEGER NOT NULL DEFAULT 18,
+    timezone TEXT, -- NULL = DEFAULT_TIMEZONE
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_business_connections_user ON business_connections(user_id);
+
+-- Conversation history of business chats, kept apart from userbot history
+CREATE TABLE IF NOT EXISTS business_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    connection_id TEXT NOT NULL,
+    chat_id INTEGER NOT NULL,
+    role TEXT NOT NULL, -- 'user' or 'assistant'
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (connection_id) REFERENCES business_connections(connection_id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_business_messages_chat ON business_messages(connection_id, chat_id, created_at);
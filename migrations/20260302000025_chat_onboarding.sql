@@ -0,0 +1,3 @@
+-- Reply triggers set from the onboarding menu, and when a chat was onboarded
+ALTER TABLE account_chats ADD COLUMN reply_triggers TEXT;
+ALTER TABLE account_chats ADD COLUMN onboarded_at TIMESTAMP;
@@ -0,0 +1,15 @@
+-- Replies that delete themselves after a per-chat number of minutes
+ALTER TABLE account_chats ADD COLUMN ephemeral_minutes INTEGER;
+
+CREATE TABLE IF NOT EXISTS ephemeral_messages (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    -- TDLib's temporary ID until the send is confirmed, then the final one
+    message_id INTEGER NOT NULL,
+    delete_at TIMESTAMP NOT NULL,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_ephemeral_messages_due ON ephemeral_messages(delete_at);
+CREATE INDEX IF NOT EXISTS idx_ephemeral_messages_message ON ephemeral_messages(account_id, chat_id, message_id);
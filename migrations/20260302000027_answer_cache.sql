@@ -0,0 +1,18 @@
+-- Answers to questions, reused when the same question comes up again in a chat
+ALTER TABLE account_chats ADD COLUMN answer_cache_enabled BOOLEAN NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS answer_cache (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    persona_id INTEGER,
+    question TEXT NOT NULL,
+    embedding BLOB NOT NULL,
+    answer TEXT NOT NULL,
+    hits INTEGER NOT NULL DEFAULT 0,
+    -- When the answer was last generated; older answers are stale
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_answer_cache_chat ON answer_cache(account_id, chat_id, updated_at);
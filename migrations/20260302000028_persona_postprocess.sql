@@ -0,0 +1,2 @@
+-- Per-persona reply post-processing rules (JSON, see ai::postprocess)
+ALTER TABLE personas ADD COLUMN postprocess TEXT;
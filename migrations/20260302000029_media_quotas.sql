@@ -0,0 +1,13 @@
+-- Daily per-chat limits and usage of expensive media processing (vision, voice)
+CREATE TABLE IF NOT EXISTS media_quotas (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,
+    -- NULL = the MEDIA_QUOTA_*_PER_DAY default
+    daily_limit INTEGER,
+    used INTEGER NOT NULL DEFAULT 0,
+    -- UTC date the usage belongs to; a new day starts from zero
+    day TEXT NOT NULL DEFAULT '',
+    PRIMARY KEY (account_id, chat_id, kind),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,18 @@
+-- RSS/Atom feeds whose new items are retold in a chat by its persona
+CREATE TABLE IF NOT EXISTS feeds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    -- HTTP caching validators of the last fetch
+    etag TEXT,
+    last_modified TEXT,
+    -- JSON array of recently seen item ids
+    seen_ids TEXT NOT NULL DEFAULT '[]',
+    last_checked_at TIMESTAMP,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (account_id, chat_id, url),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
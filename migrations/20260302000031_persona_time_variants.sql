@@ -0,0 +1,2 @@
+-- Time-of-day variants of a persona, as a JSON array of {"from", "to", "suffix"}
+ALTER TABLE personas ADD COLUMN time_variants TEXT;
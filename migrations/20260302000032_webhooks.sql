@@ -0,0 +1,28 @@
+-- Outbound webhooks: events POSTed as signed JSON to external systems
+CREATE TABLE IF NOT EXISTS webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    url TEXT NOT NULL,
+    -- Comma-separated event names, or '*' for all
+    events TEXT NOT NULL,
+    -- HMAC-SHA256 key for the X-Puppeteer-Signature header
+    secret TEXT NOT NULL,
+    -- Comma-separated words for the 'keyword' event
+    keywords TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT 1,
+    last_status TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Deliveries waiting to be sent or retried
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    webhook_id INTEGER NOT NULL,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries(next_attempt_at);
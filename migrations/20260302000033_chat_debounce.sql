@@ -0,0 +1,3 @@
+-- Per-chat batching of messages written in quick succession; NULL uses DEBOUNCE_MS / DEBOUNCE_MAX_BATCH
+ALTER TABLE account_chats ADD COLUMN debounce_ms INTEGER;
+ALTER TABLE account_chats ADD COLUMN debounce_max_batch INTEGER;
@@ -0,0 +1,2 @@
+-- What happens to messages that piled up while the account was offline
+ALTER TABLE account_chats ADD COLUMN catchup_mode TEXT NOT NULL DEFAULT 'summary'; -- 'off', 'summary', 'post'
@@ -0,0 +1,3 @@
+-- What conversations answered by a persona leave in long-term memory.
+-- 'user_only' (incoming messages) is what was always stored; 'all' adds the persona's replies, 'none' stores nothing
+ALTER TABLE personas ADD COLUMN memory_write TEXT NOT NULL DEFAULT 'user_only';
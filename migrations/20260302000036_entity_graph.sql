@@ -0,0 +1,37 @@
+-- People, places and projects mentioned in a chat
+CREATE TABLE IF NOT EXISTS chat_entities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    -- Lowercased name, for lookups
+    name_key TEXT NOT NULL,
+    -- person, place, project or other
+    kind TEXT NOT NULL DEFAULT 'other',
+    description TEXT,
+    mentions INTEGER NOT NULL DEFAULT 1,
+    -- Message the entity was last seen in
+    last_message_id INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(account_id, chat_id, name_key),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+-- "Витя works at Яндекс": subject, relation, object, and the message that said so
+CREATE TABLE IF NOT EXISTS entity_relations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    subject_id INTEGER NOT NULL,
+    relation TEXT NOT NULL,
+    object_id INTEGER NOT NULL,
+    message_id INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(subject_id, relation, object_id),
+    FOREIGN KEY (subject_id) REFERENCES chat_entities(id) ON DELETE CASCADE,
+    FOREIGN KEY (object_id) REFERENCES chat_entities(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_entity_relations_subject ON entity_relations(subject_id);
+CREATE INDEX IF NOT EXISTS idx_entity_relations_object ON entity_relations(object_id);
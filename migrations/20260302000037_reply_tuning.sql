@@ -0,0 +1,41 @@
+-- Auto-tuned reply probability: owner-set bounds (NULL = tuning off)
+ALTER TABLE account_chats ADD COLUMN tune_min_probability INTEGER;
+ALTER TABLE account_chats ADD COLUMN tune_max_probability INTEGER;
+
+-- Engagement collected while the chat runs at its current probability
+CREATE TABLE IF NOT EXISTS reply_tuning_epochs (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    probability INTEGER NOT NULL,
+    replies_to_bot INTEGER NOT NULL DEFAULT 0,
+    negatives INTEGER NOT NULL DEFAULT 0,
+    started_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+-- Bandit arms: how each probability did so far
+CREATE TABLE IF NOT EXISTS reply_tuning_arms (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    probability INTEGER NOT NULL,
+    pulls INTEGER NOT NULL DEFAULT 0,
+    reward_sum REAL NOT NULL DEFAULT 0,
+    PRIMARY KEY (account_id, chat_id, probability),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+-- Every change, with the numbers behind it
+CREATE TABLE IF NOT EXISTS reply_tuning_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    old_probability INTEGER NOT NULL,
+    new_probability INTEGER NOT NULL,
+    reward REAL NOT NULL,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_reply_tuning_log_chat ON reply_tuning_log(account_id, chat_id, created_at);
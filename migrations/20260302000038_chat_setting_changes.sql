@@ -0,0 +1,17 @@
+-- Chat settings changed by admin commands, newest on top of the undo stack
+CREATE TABLE IF NOT EXISTS chat_setting_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    -- Telegram user who ran the command
+    actor_id INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    -- JSON object of the changed fields before and after; old_values is null when the command created the chat's row
+    old_values TEXT,
+    new_values TEXT NOT NULL,
+    undone BOOLEAN NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_setting_changes_chat ON chat_setting_changes(account_id, chat_id, id);
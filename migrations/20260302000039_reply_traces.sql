@@ -0,0 +1,19 @@
+-- Stage timings of sent replies, looked up by /trace
+CREATE TABLE IF NOT EXISTS reply_traces (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    correlation_id TEXT NOT NULL,
+    -- The message answered and the first message of the reply (TDLib ids)
+    incoming_message_id INTEGER NOT NULL,
+    reply_message_id INTEGER,
+    -- JSON array of [stage, milliseconds] in pipeline order
+    stages TEXT NOT NULL,
+    total_ms INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_reply_traces_reply ON reply_traces(reply_message_id);
+CREATE INDEX IF NOT EXISTS idx_reply_traces_incoming ON reply_traces(incoming_message_id);
+CREATE INDEX IF NOT EXISTS idx_reply_traces_created ON reply_traces(created_at);
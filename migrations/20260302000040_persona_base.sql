@@ -0,0 +1,2 @@
+-- A persona may extend a base persona: base prompt first, its own settings on top
+ALTER TABLE personas ADD COLUMN base_id INTEGER REFERENCES personas(id) ON DELETE SET NULL;
@@ -0,0 +1,17 @@
+-- Backfill of memory embeddings for messages stored without them, started by /embed_backlog
+CREATE TABLE IF NOT EXISTS embed_backlog_jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    chat_id INTEGER, -- NULL = every chat
+    -- Admin chat that gets progress reports
+    report_chat_id INTEGER NOT NULL,
+    -- messages_history id the job got up to, so it resumes after a restart
+    last_message_id INTEGER NOT NULL DEFAULT 0,
+    scanned INTEGER NOT NULL DEFAULT 0,
+    embedded INTEGER NOT NULL DEFAULT 0,
+    failed INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'running', -- 'running', 'done', 'cancelled'
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_embed_backlog_status ON embed_backlog_jobs(status);
@@ -0,0 +1,4 @@
+-- How replies write numbers, dates, money and units in a chat; NULL leaves them as generated
+ALTER TABLE account_chats ADD COLUMN locale TEXT; -- e.g. 'ru', 'en-us', 'de'
+ALTER TABLE account_chats ADD COLUMN units TEXT; -- 'metric' or 'imperial'; NULL follows the locale
+ALTER TABLE account_chats ADD COLUMN transliteration TEXT NOT NULL DEFAULT 'keep'; -- 'keep' or 'latin'
@@ -0,0 +1,4 @@
+-- Local time window without auto-replies, e.g. '23:00-08:00'; messages are still logged and remembered
+ALTER TABLE account_chats ADD COLUMN quiet_hours TEXT;
+-- 1 = direct mentions and replies to the account are still answered during quiet hours
+ALTER TABLE account_chats ADD COLUMN quiet_allow_mentions BOOLEAN NOT NULL DEFAULT 1;
@@ -0,0 +1,14 @@
+-- Conversations handed to a human: the account doesn't auto-reply until they expire or are resumed
+CREATE TABLE IF NOT EXISTS handoffs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    user_id INTEGER, -- NULL = everyone in the chat
+    reason TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    resumed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_handoffs_chat ON handoffs(account_id, chat_id, expires_at);
@@ -0,0 +1,2 @@
+-- 1 = the account may answer "make a poll" requests with a native Telegram poll or quiz
+ALTER TABLE account_chats ADD COLUMN polls_enabled BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,3 @@
+-- Media a remembered description came from, so the picture itself can be sent again
+ALTER TABLE long_term_memory ADD COLUMN media_kind TEXT; -- 'photo' | 'animation'
+ALTER TABLE long_term_memory ADD COLUMN media_file_id TEXT; -- TDLib remote file id
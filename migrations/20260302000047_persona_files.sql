@@ -0,0 +1,2 @@
+-- Version of the PERSONAS_DIR file a persona was last imported from; NULL = managed in Telegram
+ALTER TABLE personas ADD COLUMN file_version INTEGER;
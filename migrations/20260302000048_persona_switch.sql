@@ -0,0 +1,8 @@
+-- Boundary set when another persona takes over a chat: older history isn't replayed, its summary is
+ALTER TABLE account_chats ADD COLUMN context_since TIMESTAMP;
+ALTER TABLE account_chats ADD COLUMN context_summary TEXT;
+-- 1 = the new persona mentions the change in its first reply
+ALTER TABLE account_chats ADD COLUMN announce_persona_switch BOOLEAN NOT NULL DEFAULT 0;
+
+-- Persona answering the chat when a memory was stored
+ALTER TABLE long_term_memory ADD COLUMN persona_id INTEGER;
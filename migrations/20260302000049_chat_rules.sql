@@ -0,0 +1,22 @@
+-- Declarative routing rules: every condition that is set must hold, then the actions apply
+CREATE TABLE IF NOT EXISTS chat_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- Conditions; NULL matches anything
+    account_id INTEGER,
+    chat_id INTEGER,
+    sender_id INTEGER,
+    -- Regex the message text must match
+    pattern TEXT,
+    -- Actions; NULL leaves the default logic alone
+    persona_id INTEGER,
+    temperature REAL,
+    -- 'always' or 'never'
+    reply TEXT,
+    -- Higher first; for each action the first matching rule that sets it wins
+    priority INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    FOREIGN KEY (persona_id) REFERENCES personas(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_rules_account ON chat_rules(account_id);
@@ -0,0 +1,33 @@
+-- Games hosted by the persona in a chat: /game trivia, /game словарь
+ALTER TABLE account_chats ADD COLUMN games_enabled BOOLEAN NOT NULL DEFAULT 0;
+
+CREATE TABLE IF NOT EXISTS game_rounds (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    kind TEXT NOT NULL,            -- trivia or words
+    question TEXT NOT NULL,
+    answer TEXT NOT NULL,
+    alternatives TEXT,             -- JSON array of other accepted answers
+    message_id INTEGER,            -- the question as sent
+    deadline_at DATETIME NOT NULL,
+    finished_at DATETIME,
+    winner_id INTEGER,             -- NULL when time ran out or the round was stopped
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_game_rounds_chat ON game_rounds(account_id, chat_id, finished_at);
+CREATE INDEX IF NOT EXISTS idx_game_rounds_deadline ON game_rounds(finished_at, deadline_at);
+
+CREATE TABLE IF NOT EXISTS game_scores (
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    points INTEGER NOT NULL DEFAULT 0,
+    updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, chat_id, user_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_game_scores_chat_points ON game_scores(account_id, chat_id, points DESC);
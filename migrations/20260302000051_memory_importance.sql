@@ -0,0 +1,2 @@
+-- Weight of a memory in retrieval, set by hand with /memory_edit (0 hides it, 2 doubles it)
+ALTER TABLE long_term_memory ADD COLUMN importance REAL NOT NULL DEFAULT 1.0;
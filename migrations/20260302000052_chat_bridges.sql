@@ -0,0 +1,15 @@
+-- Pairs of chats an account mirrors into each other, translating on the way
+CREATE TABLE IF NOT EXISTS chat_bridges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_a INTEGER NOT NULL,
+    chat_b INTEGER NOT NULL,
+    -- Language messages are translated into when mirrored into each chat
+    lang_a TEXT NOT NULL,
+    lang_b TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE,
+    UNIQUE (account_id, chat_a, chat_b)
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_bridges_account ON chat_bridges(account_id);
@@ -0,0 +1,2 @@
+-- Comma-separated words that get voice and video notes forwarded to the owner with their transcript
+ALTER TABLE account_chats ADD COLUMN escalation_keywords TEXT;
@@ -0,0 +1,21 @@
+-- The persona may pin messages it thinks are important in this chat
+ALTER TABLE account_chats ADD COLUMN pins_enabled BOOLEAN NOT NULL DEFAULT 0;
+
+-- Moderation actions userbots took in chats (pins and unpins), who asked for them and how they went
+CREATE TABLE IF NOT EXISTS audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    -- "pin" or "unpin"
+    action TEXT NOT NULL,
+    message_id INTEGER,
+    -- "owner <id>", "persona" or "admin <id>"
+    actor TEXT NOT NULL,
+    -- Whether Telegram carried it out, and the error when it didn't
+    ok BOOLEAN NOT NULL,
+    details TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_log_account ON audit_log(account_id, id);
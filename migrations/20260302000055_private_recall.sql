@@ -0,0 +1,12 @@
+-- Whether memories of a user's private chat may be recalled when they talk in this group
+ALTER TABLE account_chats ADD COLUMN rag_private_recall BOOLEAN NOT NULL DEFAULT 0;
+
+-- Users who allowed an account to bring up what they said in private when talking in groups (/privacy)
+CREATE TABLE IF NOT EXISTS privacy_consents (
+    account_id INTEGER NOT NULL,
+    user_id INTEGER NOT NULL,
+    share_private BOOLEAN NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (account_id, user_id),
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,5 @@
+-- What a persona may do besides writing text; everything by default
+ALTER TABLE personas ADD COLUMN can_search_web BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE personas ADD COLUMN can_generate_images BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE personas ADD COLUMN can_use_tools BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE personas ADD COLUMN can_send_voice BOOLEAN NOT NULL DEFAULT 1;
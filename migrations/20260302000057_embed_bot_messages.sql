@@ -0,0 +1,2 @@
+-- Whether bot-authored messages are embedded into long-term memory here; NULL follows EMBED_BOT_MESSAGES
+ALTER TABLE account_chats ADD COLUMN embed_bot_messages BOOLEAN;
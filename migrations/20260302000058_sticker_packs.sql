@@ -0,0 +1,13 @@
+-- Telegram sticker packs of persona catchphrases, created by an account and refreshed monthly
+CREATE TABLE IF NOT EXISTS sticker_packs (
+    persona_id INTEGER PRIMARY KEY,
+    account_id INTEGER NOT NULL,
+    name TEXT NOT NULL UNIQUE,
+    title TEXT NOT NULL,
+    -- JSON array of the phrases already in the pack
+    phrases TEXT NOT NULL DEFAULT '[]',
+    refreshed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (persona_id) REFERENCES personas(id) ON DELETE CASCADE,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- Who started a wizard, so the same owner can't run wizards in two chats at once
+ALTER TABLE wizard_sessions ADD COLUMN user_id INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_wizard_sessions_user ON wizard_sessions(user_id);
@@ -0,0 +1,5 @@
+-- Per-chat opt-outs of media processing: photos to the vision model, voice notes
+-- to Whisper, questions to web search. Global availability still applies.
+ALTER TABLE account_chats ADD COLUMN vision_enabled BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE account_chats ADD COLUMN voice_enabled BOOLEAN NOT NULL DEFAULT 1;
+ALTER TABLE account_chats ADD COLUMN web_search_enabled BOOLEAN NOT NULL DEFAULT 1;
@@ -0,0 +1,26 @@
+-- Archive tier: old messages are moved here nightly so the live history table
+-- stays small. Rows keep their ids; messages_all reads both tiers.
+CREATE TABLE IF NOT EXISTS messages_archive (
+    id INTEGER PRIMARY KEY,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    role TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    sender_id INTEGER,
+    sender_chat_id INTEGER,
+    persona_id INTEGER,
+    is_hashed BOOLEAN NOT NULL DEFAULT 0,
+    archived_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_messages_archive_chat ON messages_archive(account_id, chat_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_messages_archive_persona ON messages_archive(persona_id, role);
+
+CREATE VIEW IF NOT EXISTS messages_all AS
+    SELECT id, account_id, chat_id, role, content, created_at, sender_id, sender_chat_id, persona_id, is_hashed
+    FROM messages_history
+    UNION ALL
+    SELECT id, account_id, chat_id, role, content, created_at, sender_id, sender_chat_id, persona_id, is_hashed
+    FROM messages_archive;
@@ -0,0 +1,8 @@
+-- Content hash of each persona at its last /export_changed_personas, so only
+-- personas edited since then are exported again
+CREATE TABLE IF NOT EXISTS persona_exports (
+    persona_id INTEGER PRIMARY KEY,
+    content_hash TEXT NOT NULL,
+    exported_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (persona_id) REFERENCES personas(id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- How often a memory made it into a reply prompt, for /memory_heatmap
+ALTER TABLE long_term_memory ADD COLUMN hits INTEGER NOT NULL DEFAULT 0;
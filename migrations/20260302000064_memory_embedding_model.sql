@@ -0,0 +1,3 @@
+-- Which model made a memory's embedding; the embedding cache only reuses the current
+-- model's vectors (NULL: stored before models were recorded, never reused)
+ALTER TABLE long_term_memory ADD COLUMN embedding_model TEXT;
@@ -0,0 +1,16 @@
+-- Conversation summaries, kept so /summaries can show, regenerate and delete them.
+-- first/last_message_id are the range of messages_all that was summarized.
+CREATE TABLE IF NOT EXISTS chat_summaries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    account_id INTEGER NOT NULL,
+    chat_id INTEGER NOT NULL,
+    -- What produced it: 'persona_switch' for now
+    kind TEXT NOT NULL,
+    first_message_id INTEGER NOT NULL,
+    last_message_id INTEGER NOT NULL,
+    summary TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (account_id) REFERENCES accounts(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_chat_summaries_chat ON chat_summaries(account_id, chat_id, id);
@@ -0,0 +1,127 @@
+use crate::{
+    ai::rag::cosine_similarity,
+    db::{AnswerCacheRepository, CachedAnswer},
+    AppState,
+};
+use anyhow::{Context, Result};
+
+/// Words that open a question even without a question mark
+const QUESTION_WORDS: &[&str] = &[
+    "как", "какой", "какая", "какое", "какие", "где", "когда", "куда", "откуда", "что", "кто", "почему",
+    "зачем", "сколько", "чей", "можно", "how", "what", "where", "when", "who", "why", "which",
+];
+
+/// Whether a message looks like a question worth caching the answer to
+pub fn is_question(text: &str) -> bool {
+    let text = text.trim();
+    if text.chars().count() < 8 {
+        return false;
+    }
+    if text.ends_with('?') {
+        return true;
+    }
+
+    let first_word = text
+        .split_whitespace()
+        .next()
+        .unwrap_or("")
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase();
+    QUESTION_WORDS.contains(&first_word.as_str())
+}
+
+/// The most similar answer to the same question, if any is close enough
+fn best_match<'a>(
+    answers: &'a [CachedAnswer],
+    embedding: &[f32],
+    min_similarity: f32,
+) -> Option<(f32, &'a CachedAnswer)> {
+    answers
+        .iter()
+        .filter_map(|answer| {
+            let cached: Vec<f32> = bincode::deserialize(&answer.embedding).ok()?;
+            let similarity = cosine_similarity(embedding, &cached);
+            (similarity >= min_similarity).then_some((similarity, answer))
+        })
+        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// A fresh answer the persona already gave to this question in this chat
+pub async fn lookup(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    persona_id: Option<i64>,
+    embedding: &[f32],
+) -> Result<Option<String>> {
+    let since = chrono::Utc::now() - chrono::Duration::hours(state.config.answer_cache_ttl_hours);
+    let answers: Vec<CachedAnswer> = AnswerCacheRepository::fresh(&state.db_pool, account_id, chat_id, since)
+        .await?
+        .into_iter()
+        // Another persona would answer in another voice
+        .filter(|a| a.persona_id == persona_id)
+        .collect();
+
+    match best_match(&answers, embedding, state.config.answer_cache_similarity) {
+        Some((similarity, answer)) => {
+            tracing::debug!("Answer cache hit in chat {} ({:.3}): {}", chat_id, similarity, answer.question);
+            AnswerCacheRepository::record_hit(&state.db_pool, answer.id).await?;
+            Ok(Some(answer.answer.clone()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Remember an answer, replacing the stale answer to the same question if there is one
+pub async fn store(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    persona_id: Option<i64>,
+    question: &str,
+    embedding: &[f32],
+    answer: &str,
+) -> Result<()> {
+    let existing = AnswerCacheRepository::list(&state.db_pool, account_id, chat_id).await?;
+    if let Some((_, stale)) = best_match(&existing, embedding, state.config.answer_cache_similarity) {
+        return AnswerCacheRepository::refresh(&state.db_pool, stale.id, persona_id, answer).await;
+    }
+
+    let bytes = bincode::serialize(embedding).context("Failed to serialize embedding")?;
+    AnswerCacheRepository::add(&state.db_pool, account_id, chat_id, persona_id, question, &bytes, answer).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cached(id: i64, embedding: &[f32]) -> CachedAnswer {
+        CachedAnswer {
+            id,
+            account_id: 1,
+            chat_id: -1,
+            persona_id: None,
+            question: "какой пароль от вики?".to_string(),
+            embedding: bincode::serialize(embedding).unwrap(),
+            answer: "qwerty".to_string(),
+            hits: 0,
+            updated_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn detects_questions() {
+        assert!(is_question("какой пароль от вики?"));
+        assert!(is_question("Где взять доступ к серверу"));
+        assert!(is_question("is anyone here?"));
+        assert!(!is_question("да?"));
+        assert!(!is_question("я думаю, что завтра будет дождь"));
+    }
+
+    #[test]
+    fn picks_the_closest_answer_above_threshold() {
+        let answers = vec![cached(1, &[1.0, 0.0]), cached(2, &[0.9, 0.1])];
+        assert_eq!(best_match(&answers, &[0.9, 0.1], 0.95).map(|(_, a)| a.id), Some(2));
+        assert!(best_match(&answers, &[0.0, 1.0], 0.95).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+use crate::ai::{Memory, MemoryTier};
+use crate::AppState;
+use anyhow::{Context, Result};
+
+/// Memories considered when answering a question
+const ASK_SOURCES: usize = 8;
+/// Memories below this similarity are not worth quoting
+const MIN_SOURCE_SIMILARITY: f32 = 0.35;
+/// Memories listed by /why
+const WHY_CANDIDATES: usize = 8;
+
+const ASK_PROMPT: &str = "You answer questions about a Telegram chat using only the numbered \
+excerpts from its history below. Be factual and concise, answer in the language of the question, \
+and cite the excerpts you used as [1], [2], ... If the excerpts do not contain the answer, say so \
+plainly instead of guessing.";
+
+/// Answer to an /ask question with the memories it was based on
+pub struct AskAnswer {
+    pub answer: String,
+    pub sources: Vec<Memory>,
+}
+
+/// Answer a question over a chat's episodic and semantic memory, without the persona.
+pub async fn answer_question(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    question: &str,
+) -> Result<AskAnswer> {
+    let http_client = reqwest::Client::new();
+    let embedding = crate::ai::generate_embedding_cached(
+        &http_client,
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        question,
+    )
+    .await
+    .context("Failed to embed question")?;
+
+    let candidates = if state.config.rag_rerank_enabled { crate::ai::RERANK_CANDIDATES } else { ASK_SOURCES };
+    let chat = crate::db::ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let filter = crate::ai::RetrievalFilter::for_chat(chat.as_ref(), None)
+        .with_topic(crate::ai::topics::current_topic_embedding(state, account_id, chat_id).await);
+    let mut sources =
+        crate::ai::retrieve_memories(&state.db_pool, account_id, chat_id, &embedding, candidates, &filter).await?;
+    if state.config.rag_rerank_enabled {
+        sources = crate::ai::rerank_memories(state, question, sources, ASK_SOURCES).await;
+    }
+    sources.retain(|m| m.similarity >= MIN_SOURCE_SIMILARITY);
+
+    if sources.is_empty() {
+        return Ok(AskAnswer {
+            answer: "Nothing relevant found in this chat's memory.".to_string(),
+            sources,
+        });
+    }
+
+    let mut excerpts = String::new();
+    for (i, memory) in sources.iter().enumerate() {
+        let kind = match memory.tier {
+            MemoryTier::Episodic => "message",
+            MemoryTier::Semantic => "summary",
+        };
+        excerpts.push_str(&format!(
+            "[{}] ({}, {}) {}\n",
+            i + 1,
+            kind,
+            format_date(memory.created_at),
+            memory.content
+        ));
+    }
+
+    let request = crate::ai::ollama::OllamaChatRequest {
+        model: state.config.ollama_model.clone(),
+        messages: vec![
+            crate::ai::ollama::OllamaMessage {
+                role: "system".to_string(),
+                content: ASK_PROMPT.to_string(),
+            },
+            crate::ai::ollama::OllamaMessage {
+                role: "user".to_string(),
+                content: format!("Excerpts:\n{}\nQuestion: {}", excerpts, question),
+            },
+        ],
+        stream: true,
+        options: None,
+    };
+
+    let answer = crate::ai::OllamaClient::new(state.config.ollama_url.clone())
+        .chat(request)
+        .await
+        .context("Failed to generate answer")?;
+
+    Ok(AskAnswer {
+        answer: answer.trim().to_string(),
+        sources,
+    })
+}
+
+/// Format a unix timestamp as a UTC date
+/// What retrieval returns for a message in a chat, with the filters it used.
+/// Scores are before reranking, the way they are shown to the reply pipeline.
+pub async fn explain_retrieval(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    sender_id: Option<i64>,
+    text: &str,
+) -> Result<(crate::ai::RetrievalFilter, Vec<Memory>)> {
+    let embedding = crate::ai::generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        text,
+    )
+    .await
+    .context("Failed to embed message")?;
+
+    let chat = crate::db::ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let filter = crate::ai::RetrievalFilter::for_chat(chat.as_ref(), sender_id)
+        .with_topic(crate::ai::topics::current_topic_embedding(state, account_id, chat_id).await);
+    let memories =
+        crate::ai::retrieve_memories(&state.db_pool, account_id, chat_id, &embedding, WHY_CANDIDATES, &filter).await?;
+
+    Ok((filter, memories))
+}
+
+pub fn format_date(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .map(|d| d.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|| "unknown date".to_string())
+}
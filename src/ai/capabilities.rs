@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+/// What a persona may do besides writing text. The global toggles still apply;
+/// these only take features away, so a "1990s grandma" never cites a web search.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Capabilities {
+    /// Look things up on the web before answering
+    pub search_web: bool,
+    /// Send generated pictures, once there is an image generator
+    pub generate_images: bool,
+    /// Act in the chat: native polls and pinning messages
+    pub use_tools: bool,
+    /// Answer with voice notes
+    pub send_voice: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Capabilities::ALL
+    }
+}
+
+impl Capabilities {
+    pub const ALL: Capabilities = Capabilities { search_web: true, generate_images: true, use_tools: true, send_voice: true };
+
+    /// Names used by /persona_caps and persona files
+    pub const NAMES: [&'static str; 4] = ["search", "images", "tools", "voice"];
+
+    /// The answering persona's capabilities; no persona may do everything
+    pub fn of(persona: Option<&crate::db::Persona>) -> Self {
+        match persona {
+            Some(p) => Capabilities {
+                search_web: p.can_search_web,
+                generate_images: p.can_generate_images,
+                use_tools: p.can_use_tools,
+                send_voice: p.can_send_voice,
+            },
+            None => Capabilities::ALL,
+        }
+    }
+
+    /// Only what both allow: a persona can't regain what its base gave up
+    pub fn intersect(self, other: Capabilities) -> Self {
+        Capabilities {
+            search_web: self.search_web && other.search_web,
+            generate_images: self.generate_images && other.generate_images,
+            use_tools: self.use_tools && other.use_tools,
+            send_voice: self.send_voice && other.send_voice,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "search" => Some(self.search_web),
+            "images" => Some(self.generate_images),
+            "tools" => Some(self.use_tools),
+            "voice" => Some(self.send_voice),
+            _ => None,
+        }
+    }
+
+    /// Apply a "search=off"-style toggle; `None` if it isn't one
+    pub fn toggle(&mut self, arg: &str) -> Option<()> {
+        let (name, value) = arg.split_once('=')?;
+        let value = match value.to_lowercase().as_str() {
+            "on" | "true" | "1" => true,
+            "off" | "false" | "0" => false,
+            _ => return None,
+        };
+        let flag = match name.to_lowercase().as_str() {
+            "search" => &mut self.search_web,
+            "images" => &mut self.generate_images,
+            "tools" => &mut self.use_tools,
+            "voice" => &mut self.send_voice,
+            _ => return None,
+        };
+        *flag = value;
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_by_name() {
+        let mut caps = Capabilities::ALL;
+        assert!(caps.toggle("search=off").is_some());
+        assert!(caps.toggle("Tools=OFF").is_some());
+        assert!(caps.toggle("voice=maybe").is_none());
+        assert!(caps.toggle("telepathy=on").is_none());
+        assert_eq!(caps.get("search"), Some(false));
+        assert_eq!(caps.get("tools"), Some(false));
+        assert_eq!(caps.get("images"), Some(true));
+    }
+
+    #[test]
+    fn bases_only_narrow() {
+        let base = Capabilities { search_web: false, ..Capabilities::ALL };
+        let own = Capabilities { send_voice: false, ..Capabilities::ALL };
+        let merged = own.intersect(base);
+        assert!(!merged.search_web && !merged.send_voice && merged.use_tools && merged.generate_images);
+    }
+
+    #[test]
+    fn files_may_list_only_what_changes() {
+        let caps: Capabilities = serde_json::from_str(r#"{"search_web": false}"#).unwrap();
+        assert_eq!(caps, Capabilities { search_web: false, ..Capabilities::ALL });
+        assert!(serde_json::from_str::<Capabilities>(r#"{"fly": true}"#).is_err());
+    }
+}
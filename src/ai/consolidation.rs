@@ -0,0 +1,193 @@
+use crate::{
+    ai::{
+        ollama::{ChatOptions, OllamaClient, OllamaMessage},
+        rag::{generate_embedding, upsert_semantic_memory},
+    },
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use sqlx::Row;
+
+/// How often the consolidation job runs
+const CONSOLIDATION_INTERVAL_SECS: u64 = 6 * 60 * 60;
+/// Chats need at least this many new episodes before a run is worth an LLM call
+const MIN_NEW_EPISODES: i64 = 10;
+/// Episodes fed to the model per run
+const MAX_EPISODES_PER_RUN: i64 = 80;
+/// Facts below this confidence are not promoted
+const MIN_FACT_CONFIDENCE: f64 = 0.5;
+
+#[derive(Debug, Deserialize)]
+struct ExtractedFacts {
+    #[serde(default)]
+    facts: Vec<ExtractedFact>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedFact {
+    statement: String,
+    #[serde(default)]
+    confidence: f64,
+}
+
+/// Periodically promote recurring facts from episodic to semantic memory
+pub async fn consolidation_worker(state: AppState) {
+    tracing::info!("Memory consolidation worker started");
+
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(CONSOLIDATION_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = consolidate_all(&state).await {
+            tracing::error!("Memory consolidation failed: {}", e);
+        }
+    }
+}
+
+/// Run consolidation for every chat that accumulated enough new episodes
+pub async fn consolidate_all(state: &AppState) -> Result<()> {
+    let chats = sqlx::query(
+        r#"
+        SELECT m.account_id, m.chat_id, COUNT(*) AS pending
+        FROM long_term_memory m
+        LEFT JOIN memory_consolidation c ON c.account_id = m.account_id AND c.chat_id = m.chat_id
+        WHERE m.id > COALESCE(c.last_episode_id, 0) AND m.is_hashed = 0
+        GROUP BY m.account_id, m.chat_id
+        HAVING pending >= ?
+        "#,
+    )
+    .bind(MIN_NEW_EPISODES)
+    .fetch_all(&state.db_pool)
+    .await
+    .context("Failed to find chats to consolidate")?;
+
+    for row in chats {
+        let account_id: i64 = row.try_get("account_id")?;
+        let chat_id: i64 = row.try_get("chat_id")?;
+
+        match consolidate_chat(state, account_id, chat_id).await {
+            Ok(promoted) => tracing::info!(
+                "Consolidated memory for account {} chat {}: {} new facts",
+                account_id,
+                chat_id,
+                promoted
+            ),
+            Err(e) => tracing::warn!(
+                "Consolidation failed for account {} chat {}: {}",
+                account_id,
+                chat_id,
+                e
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+/// Extract facts from a chat's new episodes and store them in semantic memory
+async fn consolidate_chat(state: &AppState, account_id: i64, chat_id: i64) -> Result<i64> {
+    let last_episode_id: i64 = sqlx::query_scalar(
+        "SELECT last_episode_id FROM memory_consolidation WHERE account_id = ? AND chat_id = ?",
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .fetch_optional(&state.db_pool)
+    .await
+    .context("Failed to load consolidation state")?
+    .unwrap_or(0);
+
+    let episodes = sqlx::query(
+        r#"
+        SELECT id, content FROM long_term_memory
+        WHERE account_id = ? AND chat_id = ? AND id > ? AND is_hashed = 0
+        ORDER BY id ASC
+        LIMIT ?
+        "#,
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(last_episode_id)
+    .bind(MAX_EPISODES_PER_RUN)
+    .fetch_all(&state.db_pool)
+    .await
+    .context("Failed to load episodes")?;
+
+    let mut newest_id = last_episode_id;
+    let mut transcript = String::new();
+    for row in &episodes {
+        let id: i64 = row.try_get("id")?;
+        let content: String = row.try_get("content")?;
+        newest_id = newest_id.max(id);
+        transcript.push_str(&format!("- {}\n", content));
+    }
+
+    // A failed extraction leaves the watermark alone so these episodes are tried again next run
+    let facts = extract_facts(state, &transcript).await?;
+    let http_client = reqwest::Client::new();
+    let mut promoted = 0;
+
+    for fact in facts.into_iter().filter(|f| f.confidence >= MIN_FACT_CONFIDENCE) {
+        let statement = fact.statement.trim();
+        if statement.is_empty() {
+            continue;
+        }
+
+        let embedding = generate_embedding(
+            &http_client,
+            &state.config.ollama_url,
+            &state.config.ollama_embed_model,
+            statement,
+        )
+        .await?;
+        crate::ai::models::track(&state.db_pool, crate::ai::ModelKind::Embedding, &state.config.ollama_embed_model, Some(embedding.len())).await;
+
+        if upsert_semantic_memory(&state.db_pool, account_id, chat_id, statement, fact.confidence, &embedding).await? {
+            promoted += 1;
+        }
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO memory_consolidation (account_id, chat_id, last_episode_id, last_run_at, facts_promoted)
+        VALUES (?, ?, ?, strftime('%s', 'now'), ?)
+        ON CONFLICT(account_id, chat_id) DO UPDATE SET
+            last_episode_id = excluded.last_episode_id,
+            last_run_at = excluded.last_run_at,
+            facts_promoted = facts_promoted + excluded.facts_promoted
+        "#,
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(newest_id)
+    .bind(promoted)
+    .execute(&state.db_pool)
+    .await
+    .context("Failed to save consolidation state")?;
+
+    Ok(promoted)
+}
+
+async fn extract_facts(state: &AppState, transcript: &str) -> Result<Vec<ExtractedFact>> {
+    let prompt = format!(
+        r#"Below are messages from a Telegram chat. Extract durable facts worth remembering long-term: things about the people (names, jobs, plans, preferences, relationships) that come up or are stated clearly. Ignore small talk and one-off remarks.
+
+Messages:
+{}
+
+Return JSON: {{"facts": [{{"statement": "<one short self-contained sentence in Russian>", "confidence": <0.0-1.0>}}]}}"#,
+        transcript
+    );
+
+    let extracted: ExtractedFacts = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.1), num_predict: None }),
+        )
+        .await
+        .context("Failed to extract facts")?;
+
+    Ok(extracted.facts)
+}
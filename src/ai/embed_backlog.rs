@@ -0,0 +1,205 @@
+use crate::{
+    ai::rag::{generate_embedding_cached, has_memory, is_memorable, store_memory},
+    db::{EmbedBacklogJob, EmbedBacklogRepository, MessageHistory, MessageRepository},
+    AppState,
+};
+use anyhow::Result;
+use std::time::{Duration, Instant};
+
+/// How often the worker looks for jobs to start or resume
+const POLL_INTERVAL_SECS: u64 = 60;
+/// Messages read per batch; progress is saved after every batch
+const BATCH_SIZE: i64 = 50;
+/// How often a running job reports to the admin who started it
+const REPORT_INTERVAL_SECS: u64 = 5 * 60;
+/// Failing this many messages in a row means Ollama is down: pause until the next poll
+const MAX_CONSECUTIVE_FAILURES: u32 = 5;
+
+/// Pause after each embedding to stay under EMBED_BACKLOG_PER_MINUTE
+pub fn delay_per_embedding(per_minute: u32) -> Duration {
+    Duration::from_millis(60_000 / per_minute.max(1) as u64)
+}
+
+/// Counters of a job since its last saved checkpoint
+#[derive(Debug, Clone, Copy, Default)]
+struct Progress {
+    last_message_id: i64,
+    scanned: i64,
+    embedded: i64,
+    failed: i64,
+}
+
+/// "chat -100123" or "all chats"
+pub fn scope_label(chat_id: Option<i64>) -> String {
+    match chat_id {
+        Some(chat_id) => format!("chat {}", chat_id),
+        None => "all chats".to_string(),
+    }
+}
+
+/// One-line state of a job, for reports and /embed_backlog
+pub fn describe(job: &EmbedBacklogJob, left: Option<i64>) -> String {
+    let mut line = format!(
+        "#{} ({}): {} scanned, {} embedded, {} failed",
+        job.id,
+        scope_label(job.chat_id),
+        job.scanned,
+        job.embedded,
+        job.failed
+    );
+    if let Some(left) = left {
+        line.push_str(&format!(", {} left", left));
+    }
+    line
+}
+
+/// Resume running /embed_backlog jobs, one at a time, after every restart
+pub async fn embed_backlog_worker(state: AppState) {
+    tracing::info!("Embedding backlog worker started");
+
+    loop {
+        match EmbedBacklogRepository::list_running(&state.db_pool).await {
+            Ok(jobs) => {
+                for job in jobs {
+                    if let Err(e) = run_job(&state, job.id).await {
+                        tracing::warn!("Embedding backlog job {} paused: {}", job.id, e);
+                    }
+                }
+            }
+            Err(e) => tracing::error!("Failed to list embedding backlog jobs: {}", e),
+        }
+
+        tokio::time::sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+    }
+}
+
+/// Work through a job until it's done, cancelled or Ollama stops answering
+async fn run_job(state: &AppState, job_id: i64) -> Result<()> {
+    let client = reqwest::Client::new();
+    let delay = delay_per_embedding(state.config.embed_backlog_per_minute);
+    let mut last_report = Instant::now();
+
+    loop {
+        // Re-read every batch: /embed_backlog stop flips the status
+        let job = match EmbedBacklogRepository::get(&state.db_pool, job_id).await? {
+            Some(job) if job.status == "running" => job,
+            _ => return Ok(()),
+        };
+
+        let batch = MessageRepository::list_user_after(&state.db_pool, job.chat_id, job.last_message_id, BATCH_SIZE).await?;
+        if batch.is_empty() {
+            EmbedBacklogRepository::set_status(&state.db_pool, job.id, "done").await?;
+            tracing::info!("Embedding backlog {}", describe(&job, None));
+            report(state, &job, format!("✅ Embedding backlog finished {}", describe(&job, None))).await;
+            return Ok(());
+        }
+
+        let mut progress = Progress { last_message_id: job.last_message_id, ..Default::default() };
+        // Progress up to the last message that didn't fail, saved if the batch is cut short
+        let mut checkpoint = progress;
+        let mut consecutive_failures = 0;
+        let mut outage = None;
+
+        for message in &batch {
+            progress.last_message_id = message.id;
+            progress.scanned += 1;
+            match embed_message(state, &client, message).await {
+                Ok(embedded) => {
+                    consecutive_failures = 0;
+                    if embedded {
+                        progress.embedded += 1;
+                        tokio::time::sleep(delay).await;
+                    }
+                    checkpoint = progress;
+                }
+                Err(e) => {
+                    tracing::debug!("Failed to embed message {}: {}", message.id, e);
+                    progress.failed += 1;
+                    consecutive_failures += 1;
+                    if consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                        outage = Some(e);
+                        break;
+                    }
+                }
+            }
+        }
+
+        let saved = if outage.is_some() { checkpoint } else { progress };
+        EmbedBacklogRepository::save_progress(
+            &state.db_pool,
+            job.id,
+            saved.last_message_id,
+            saved.scanned,
+            saved.embedded,
+            saved.failed,
+        )
+        .await?;
+
+        if let Some(e) = outage {
+            return Err(e.context("Embeddings keep failing"));
+        }
+
+        if last_report.elapsed() >= Duration::from_secs(REPORT_INTERVAL_SECS) {
+            last_report = Instant::now();
+            if let Some(job) = EmbedBacklogRepository::get(&state.db_pool, job.id).await? {
+                let left = MessageRepository::count_user_after(&state.db_pool, job.chat_id, job.last_message_id).await?;
+                report(state, &job, format!("⏳ Embedding backlog {}", describe(&job, Some(left)))).await;
+            }
+        }
+    }
+}
+
+/// Embed and remember one message unless it's trivial or already remembered; true when it was embedded
+async fn embed_message(state: &AppState, client: &reqwest::Client, message: &MessageHistory) -> Result<bool> {
+    if !is_memorable(&message.content, state.config.rag_min_memory_chars) {
+        return Ok(false);
+    }
+    if has_memory(&state.db_pool, message.account_id, message.chat_id, &message.content).await? {
+        return Ok(false);
+    }
+
+    let embedding = generate_embedding_cached(
+        client,
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        &message.content,
+    )
+    .await?;
+
+    store_memory(
+        &state.db_pool,
+        message.account_id,
+        message.chat_id,
+        None,
+        message.sender_id,
+        false,
+        None,
+        &message.content,
+        &embedding,
+        &state.config.ollama_embed_model,
+        1.0,
+    )
+    .await?;
+    Ok(true)
+}
+
+async fn report(state: &AppState, job: &EmbedBacklogJob, text: String) {
+    use teloxide::prelude::*;
+
+    let bot = Bot::new(&state.config.bot_token);
+    if let Err(e) = bot.send_message(ChatId(job.report_chat_id), text).await {
+        tracing::warn!("Failed to report embedding backlog job {}: {}", job.id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_embeddings_over_the_minute() {
+        assert_eq!(delay_per_embedding(120), Duration::from_millis(500));
+        assert_eq!(delay_per_embedding(0), Duration::from_secs(60));
+    }
+}
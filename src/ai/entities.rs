@@ -0,0 +1,272 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    db::{ChatEntity, EntityRepository},
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+/// Entities of a chat checked against an incoming message
+const LOOKUP_LIMIT: i64 = 300;
+/// Entities described in one prompt
+const MAX_CONTEXT_ENTITIES: usize = 3;
+/// Relations shown per entity
+const MAX_CONTEXT_RELATIONS: i64 = 5;
+/// Shorter names are too ambiguous to look up
+const MIN_NAME_CHARS: usize = 3;
+/// Characters of one message shown to the model
+const MESSAGE_MAX_CHARS: usize = 400;
+
+pub const KINDS: &[&str] = &["person", "place", "project", "other"];
+
+/// (message id, text) of messages waiting for extraction
+type Buffer = Vec<(i64, String)>;
+
+lazy_static::lazy_static! {
+    /// Messages of every chat since its last extraction, with their ids for provenance
+    static ref BUFFERS: Mutex<HashMap<(i64, i64), Buffer>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Extraction {
+    entities: Vec<ExtractedEntity>,
+    relations: Vec<ExtractedRelation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExtractedEntity {
+    name: String,
+    kind: String,
+    description: String,
+    message_id: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ExtractedRelation {
+    subject: String,
+    relation: String,
+    object: String,
+    message_id: Option<i64>,
+}
+
+/// Track a chat message and extract entities once ENTITY_EVERY_MESSAGES have piled up
+pub fn track_message(state: &AppState, account_id: i64, chat_id: i64, message_id: i64, text: &str) {
+    let every = state.config.entity_every_messages;
+    if every == 0 || text.trim().is_empty() {
+        return;
+    }
+
+    let batch = {
+        let mut buffers = BUFFERS.lock().unwrap_or_else(|e| e.into_inner());
+        let buffer = buffers.entry((account_id, chat_id)).or_default();
+        buffer.push((message_id, text.chars().take(MESSAGE_MAX_CHARS).collect()));
+        if buffer.len() < every {
+            return;
+        }
+        std::mem::take(buffer)
+    };
+
+    let state = state.clone();
+    tokio::spawn(async move {
+        match extract_and_store(&state, account_id, chat_id, &batch).await {
+            Ok(stored) => tracing::debug!("Stored {} entities for chat {} of account {}", stored, chat_id, account_id),
+            Err(e) => tracing::warn!("Failed to extract entities of chat {}: {}", chat_id, e),
+        }
+    });
+}
+
+async fn extract_and_store(state: &AppState, account_id: i64, chat_id: i64, messages: &[(i64, String)]) -> Result<usize> {
+    let transcript = messages
+        .iter()
+        .map(|(id, text)| format!("[{}] {}", id, text.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let extraction = extract(state, &transcript).await?;
+
+    // Provenance must point at a message of this batch
+    let known: HashSet<i64> = messages.iter().map(|(id, _)| *id).collect();
+    let provenance = |id: Option<i64>| id.filter(|id| known.contains(id));
+
+    let mut ids: HashMap<String, i64> = HashMap::new();
+    for entity in &extraction.entities {
+        let name = entity.name.trim();
+        if name.chars().count() < MIN_NAME_CHARS {
+            continue;
+        }
+        let description = Some(entity.description.trim()).filter(|d| !d.is_empty());
+        let stored = EntityRepository::upsert(
+            &state.db_pool,
+            account_id,
+            chat_id,
+            name,
+            normalize_kind(&entity.kind),
+            description,
+            provenance(entity.message_id),
+        )
+        .await?;
+        ids.insert(name.to_lowercase(), stored.id);
+    }
+
+    for relation in &extraction.relations {
+        let (subject, object, verb) = (relation.subject.trim(), relation.object.trim(), relation.relation.trim());
+        if verb.is_empty() {
+            continue;
+        }
+        let (subject_id, object_id) = match (ids.get(&subject.to_lowercase()), ids.get(&object.to_lowercase())) {
+            (Some(s), Some(o)) if s != o => (*s, *o),
+            _ => continue,
+        };
+        EntityRepository::add_relation(
+            &state.db_pool,
+            account_id,
+            chat_id,
+            subject_id,
+            verb,
+            object_id,
+            provenance(relation.message_id),
+        )
+        .await?;
+    }
+
+    Ok(ids.len())
+}
+
+async fn extract(state: &AppState, transcript: &str) -> Result<Extraction> {
+    let prompt = format!(
+        r#"Below are messages of a Telegram chat, each prefixed with its id in brackets.
+Find the people, places and projects that are talked about (not pronouns, not generic things) and how they relate.
+
+Messages:
+{}
+
+Return JSON: {{"entities": [{{"name": "<name as written, nominative case>", "kind": "person|place|project|other", "description": "<who or what it is, one short sentence in Russian>", "message_id": <id of the message that says so>}}],
+"relations": [{{"subject": "<entity name>", "relation": "<short verb phrase in Russian, e.g. работает в>", "object": "<entity name>", "message_id": <id>}}]}}"#,
+        transcript
+    );
+
+    OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.1), num_predict: None }),
+        )
+        .await
+        .context("Failed to extract entities")
+}
+
+pub fn normalize_kind(kind: &str) -> &'static str {
+    let kind = kind.trim().to_lowercase();
+    KINDS.iter().find(|k| **k == kind).copied().unwrap_or("other")
+}
+
+fn kind_label(kind: &str) -> &'static str {
+    match kind {
+        "person" => "человек",
+        "place" => "место",
+        "project" => "проект",
+        _ => "",
+    }
+}
+
+/// Whether `text` mentions `name`, tolerating case endings ("Витя" in "у Вити")
+pub fn mentions(text: &str, name: &str) -> bool {
+    let words: Vec<String> = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    let name = name.to_lowercase();
+    let mut parts = name.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).peekable();
+    if parts.peek().is_none() {
+        return false;
+    }
+
+    parts.all(|part| {
+        let length = part.chars().count();
+        // Short words must match exactly, longer ones may change their last letter and grow an ending
+        let stem: String = if length > 3 { part.chars().take(length - 1).collect() } else { part.to_string() };
+        words.iter().any(|word| {
+            if length <= 3 {
+                word == part
+            } else {
+                word.starts_with(&stem) && word.chars().count() <= length + 2
+            }
+        })
+    })
+}
+
+/// Entities of the chat that `text` mentions, most mentioned in the chat first
+pub fn mentioned<'a>(text: &str, entities: &'a [ChatEntity]) -> Vec<&'a ChatEntity> {
+    entities
+        .iter()
+        .filter(|e| e.name.chars().count() >= MIN_NAME_CHARS && mentions(text, &e.name))
+        .take(MAX_CONTEXT_ENTITIES)
+        .collect()
+}
+
+/// What the chat's graph knows about the entities a message mentions, for the prompt
+pub async fn entity_context(state: &AppState, account_id: i64, chat_id: i64, text: &str) -> Result<Option<String>> {
+    if state.config.entity_every_messages == 0 {
+        return Ok(None);
+    }
+    let entities = EntityRepository::list_for_chat(&state.db_pool, account_id, chat_id, LOOKUP_LIMIT).await?;
+    let found = mentioned(text, &entities);
+    if found.is_empty() {
+        return Ok(None);
+    }
+
+    let mut context = String::from("[КТО И ЧТО ЕСТЬ В ЭТОМ ЧАТЕ]\n");
+    for entity in found {
+        context.push_str(&describe(entity));
+        context.push('\n');
+        for relation in EntityRepository::relations(&state.db_pool, entity.id, MAX_CONTEXT_RELATIONS).await? {
+            context.push_str(&format!("  — {} {} {}\n", relation.subject, relation.relation, relation.object));
+        }
+    }
+    Ok(Some(context))
+}
+
+/// One line about an entity: name, kind and description
+pub fn describe(entity: &ChatEntity) -> String {
+    let label = kind_label(&entity.kind);
+    let mut line = if label.is_empty() { entity.name.clone() } else { format!("{} ({})", entity.name, label) };
+    if let Some(description) = entity.description.as_deref() {
+        line.push_str(": ");
+        line.push_str(description);
+    }
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_names_with_case_endings() {
+        assert!(mentions("кто такой Витя?", "Витя"));
+        assert!(mentions("передай Вите, что я опоздаю", "Витя"));
+        assert!(mentions("был вчера в Москве", "Москва"));
+        assert!(mentions("как там проект Puppeteer-бот?", "puppeteer"));
+        assert!(!mentions("Витамины купил", "Витя"));
+        assert!(!mentions("привет", ""));
+    }
+
+    #[test]
+    fn multi_word_names_need_every_word() {
+        assert!(mentions("Анна Петрова уже пришла", "Анна Петрова"));
+        assert!(!mentions("Анна уже пришла", "Анна Петрова"));
+    }
+
+    #[test]
+    fn unknown_kinds_become_other() {
+        assert_eq!(normalize_kind(" Person "), "person");
+        assert_eq!(normalize_kind("company"), "other");
+    }
+}
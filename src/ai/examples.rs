@@ -0,0 +1,86 @@
+use crate::{
+    ai::rag::{cosine_similarity, generate_embedding_cached},
+    db::{ExampleRepository, PersonaExample},
+    AppState,
+};
+use anyhow::{Context, Result};
+
+/// Examples below this similarity to the current message are not worth showing
+const MIN_EXAMPLE_SIMILARITY: f32 = 0.3;
+
+/// Store an example exchange for a persona, embedding the user side
+pub async fn add_example(state: &AppState, persona_id: i64, user_text: &str, reply: &str, source: &str) -> Result<i64> {
+    let embedding = generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        user_text,
+    )
+    .await
+    .context("Failed to embed example")?;
+
+    let bytes = bincode::serialize(&embedding).context("Failed to serialize embedding")?;
+    ExampleRepository::add(&state.db_pool, persona_id, user_text, reply, &bytes, source).await
+}
+
+/// The `k` examples of a persona closest to the current message
+pub async fn relevant_examples(
+    state: &AppState,
+    persona_id: i64,
+    query_embedding: &[f32],
+    k: usize,
+) -> Result<Vec<PersonaExample>> {
+    let mut scored: Vec<(f32, PersonaExample)> = ExampleRepository::list(&state.db_pool, persona_id)
+        .await?
+        .into_iter()
+        .filter_map(|example| {
+            let embedding: Vec<f32> = bincode::deserialize(&example.embedding).ok()?;
+            let similarity = cosine_similarity(query_embedding, &embedding);
+            (similarity >= MIN_EXAMPLE_SIMILARITY).then_some((similarity, example))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    Ok(scored.into_iter().take(k).map(|(_, e)| e).collect())
+}
+
+/// Prompt block showing how the persona answers similar messages
+pub fn format_examples(examples: &[PersonaExample]) -> Option<String> {
+    if examples.is_empty() {
+        return None;
+    }
+
+    let mut block = String::from(
+        "[ПРИМЕРЫ ТВОИХ ОТВЕТОВ]\nТак ты отвечал на похожие сообщения. Повторяй стиль и длину, не копируй текст.\n",
+    );
+    for example in examples {
+        block.push_str(&format!("\nСобеседник: {}\nТы: {}\n", example.user_text.trim(), example.reply.trim()));
+    }
+    Some(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn example(user_text: &str, reply: &str) -> PersonaExample {
+        PersonaExample {
+            id: 1,
+            persona_id: 1,
+            user_text: user_text.to_string(),
+            reply: reply.to_string(),
+            embedding: Vec::new(),
+            source: "manual".to_string(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn formats_examples() {
+        assert_eq!(format_examples(&[]), None);
+
+        let block = format_examples(&[example("как дела?", "норм")]).unwrap();
+        assert!(block.contains("Собеседник: как дела?\nТы: норм"));
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{
+    db::{ExampleRepository, MessageRepository},
+    AppState,
+};
+use anyhow::{Context, Result};
+use flate2::{write::GzEncoder, Compression};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Times members must have answered a reply for it to count as a good one, unless the command says otherwise
+pub const DEFAULT_MIN_RESPONSES: i64 = 2;
+
+/// Line format of the exported dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// `{"messages": [{"role": ..., "content": ...}]}`
+    OpenAi,
+    /// `{"conversations": [{"from": ..., "value": ...}]}`
+    ShareGpt,
+}
+
+impl DatasetFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "openai" | "chatml" => Some(DatasetFormat::OpenAi),
+            "sharegpt" => Some(DatasetFormat::ShareGpt),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DatasetFormat::OpenAi => "openai",
+            DatasetFormat::ShareGpt => "sharegpt",
+        }
+    }
+}
+
+/// One training example
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrainingPair {
+    pub system: Option<String>,
+    pub user: String,
+    pub assistant: String,
+    /// manual, ghost or responses
+    pub source: String,
+}
+
+/// Which pairs to export
+#[derive(Debug, Clone, Default)]
+pub struct ExportFilter {
+    pub persona_id: Option<i64>,
+    /// YYYY-MM-DD, inclusive
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub min_responses: i64,
+}
+
+/// How many pairs came from each source
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub examples: usize,
+    pub responses: usize,
+}
+
+/// A pair as one JSONL line
+pub fn to_line(format: DatasetFormat, pair: &TrainingPair) -> String {
+    let turns = pair
+        .system
+        .iter()
+        .map(|s| ("system", s.as_str()))
+        .chain([("user", pair.user.as_str()), ("assistant", pair.assistant.as_str())]);
+
+    match format {
+        DatasetFormat::OpenAi => {
+            let messages: Vec<_> = turns.map(|(role, content)| json!({ "role": role, "content": content })).collect();
+            json!({ "messages": messages }).to_string()
+        }
+        DatasetFormat::ShareGpt => {
+            let conversations: Vec<_> = turns
+                .map(|(role, content)| {
+                    let from = match role {
+                        "user" => "human",
+                        "assistant" => "gpt",
+                        other => other,
+                    };
+                    json!({ "from": from, "value": content })
+                })
+                .collect();
+            json!({ "conversations": conversations }).to_string()
+        }
+    }
+}
+
+/// Pairs from /add_example, ghost-mode approvals and well-received replies, without duplicates
+pub async fn collect(state: &AppState, filter: &ExportFilter) -> Result<(Vec<TrainingPair>, ExportSummary)> {
+    let mut prompts: HashMap<i64, Option<String>> = HashMap::new();
+    let mut pairs = Vec::new();
+    let mut summary = ExportSummary::default();
+
+    let examples =
+        ExampleRepository::list_between(&state.db_pool, filter.persona_id, filter.from.as_deref(), filter.to.as_deref())
+            .await?;
+    for example in examples {
+        let system = persona_prompt(state, &mut prompts, Some(example.persona_id)).await?;
+        if push_unique(&mut pairs, system, &example.user_text, &example.reply, &example.source) {
+            summary.examples += 1;
+        }
+    }
+
+    let replies = MessageRepository::answered_replies(
+        &state.db_pool,
+        filter.persona_id,
+        filter.min_responses,
+        filter.from.as_deref(),
+        filter.to.as_deref(),
+    )
+    .await?;
+    for reply in replies {
+        let user_text = match reply.user_text {
+            Some(text) => text,
+            None => continue,
+        };
+        let system = persona_prompt(state, &mut prompts, reply.persona_id).await?;
+        if push_unique(&mut pairs, system, &user_text, &reply.reply, "responses") {
+            summary.responses += 1;
+        }
+    }
+
+    Ok((pairs, summary))
+}
+
+async fn persona_prompt(
+    state: &AppState,
+    cache: &mut HashMap<i64, Option<String>>,
+    persona_id: Option<i64>,
+) -> Result<Option<String>> {
+    let persona_id = match persona_id {
+        Some(id) => id,
+        None => return Ok(None),
+    };
+    if let Some(prompt) = cache.get(&persona_id) {
+        return Ok(prompt.clone());
+    }
+
+    let prompt = super::persona_inheritance::resolve(&state.db_pool, persona_id).await?.map(|p| p.prompt);
+    cache.insert(persona_id, prompt.clone());
+    Ok(prompt)
+}
+
+/// Add a pair unless it is empty or already exported (ghost approvals often repeat answered replies)
+fn push_unique(pairs: &mut Vec<TrainingPair>, system: Option<String>, user: &str, assistant: &str, source: &str) -> bool {
+    let (user, assistant) = (user.trim(), assistant.trim());
+    if user.is_empty() || assistant.is_empty() || pairs.iter().any(|p| p.user == user && p.assistant == assistant) {
+        return false;
+    }
+
+    pairs.push(TrainingPair {
+        system,
+        user: user.to_string(),
+        assistant: assistant.to_string(),
+        source: source.to_string(),
+    });
+    true
+}
+
+/// The dataset as gzipped JSONL
+pub fn compress(format: DatasetFormat, pairs: &[TrainingPair]) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for pair in pairs {
+        writeln!(encoder, "{}", to_line(format, pair)).context("Failed to write dataset")?;
+    }
+    encoder.finish().context("Failed to compress dataset")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair(system: Option<&str>) -> TrainingPair {
+        TrainingPair {
+            system: system.map(str::to_string),
+            user: "привет".to_string(),
+            assistant: "ку".to_string(),
+            source: "manual".to_string(),
+        }
+    }
+
+    #[test]
+    fn writes_openai_lines() {
+        let line: serde_json::Value = serde_json::from_str(&to_line(DatasetFormat::OpenAi, &pair(Some("sys")))).unwrap();
+        assert_eq!(line["messages"][0]["role"], "system");
+        assert_eq!(line["messages"][2]["content"], "ку");
+    }
+
+    #[test]
+    fn writes_sharegpt_lines() {
+        let line: serde_json::Value = serde_json::from_str(&to_line(DatasetFormat::ShareGpt, &pair(None))).unwrap();
+        let turns = line["conversations"].as_array().unwrap();
+        assert_eq!(turns.len(), 2);
+        assert_eq!(turns[0]["from"], "human");
+        assert_eq!(turns[1]["from"], "gpt");
+    }
+
+    #[test]
+    fn skips_duplicates_and_empty_pairs() {
+        let mut pairs = Vec::new();
+        assert!(push_unique(&mut pairs, None, "a", "b", "ghost"));
+        assert!(!push_unique(&mut pairs, None, " a ", "b", "responses"));
+        assert!(!push_unique(&mut pairs, None, "a", "  ", "manual"));
+        assert_eq!(pairs.len(), 1);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    AppState,
+};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often the error budget is checked
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// Healthy probes in a row needed to resume after an auto-pause
+const RECOVERY_PROBES: u32 = 3;
+
+/// Outcome of one LLM call
+#[derive(Debug, Clone, Copy)]
+pub struct Sample {
+    pub at: Instant,
+    pub ok: bool,
+    pub latency: Duration,
+}
+
+/// Error budget of the chat model
+#[derive(Debug, Clone, Copy)]
+pub struct HealthThresholds {
+    pub window: Duration,
+    /// Fewer calls than this in the window say nothing either way
+    pub min_samples: usize,
+    pub max_error_rate: f64,
+    pub max_avg_latency: Duration,
+}
+
+/// Error rate and latency over the window
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Diagnostics {
+    pub samples: usize,
+    pub errors: usize,
+    pub avg_latency: Duration,
+}
+
+impl Diagnostics {
+    pub fn error_rate(&self) -> f64 {
+        if self.samples == 0 {
+            0.0
+        } else {
+            self.errors as f64 / self.samples as f64
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} calls, {:.0}% failed, avg. {:.1}s",
+            self.samples,
+            self.error_rate() * 100.0,
+            self.avg_latency.as_secs_f64()
+        )
+    }
+}
+
+/// Summarize the samples inside the window ending at `now`
+pub fn diagnose(samples: &VecDeque<Sample>, window: Duration, now: Instant) -> Diagnostics {
+    let recent: Vec<&Sample> = samples.iter().filter(|s| now.duration_since(s.at) <= window).collect();
+    let total_latency: Duration = recent.iter().map(|s| s.latency).sum();
+
+    Diagnostics {
+        samples: recent.len(),
+        errors: recent.iter().filter(|s| !s.ok).count(),
+        avg_latency: if recent.is_empty() { Duration::ZERO } else { total_latency / recent.len() as u32 },
+    }
+}
+
+/// Why the budget is exhausted, if it is
+pub fn budget_exceeded(diagnostics: &Diagnostics, thresholds: &HealthThresholds) -> Option<String> {
+    if diagnostics.samples < thresholds.min_samples {
+        return None;
+    }
+    if diagnostics.error_rate() > thresholds.max_error_rate {
+        return Some(format!(
+            "error rate {:.0}% > {:.0}%",
+            diagnostics.error_rate() * 100.0,
+            thresholds.max_error_rate * 100.0
+        ));
+    }
+    if diagnostics.avg_latency > thresholds.max_avg_latency {
+        return Some(format!(
+            "avg. latency {:.1}s > {}s",
+            diagnostics.avg_latency.as_secs_f64(),
+            thresholds.max_avg_latency.as_secs()
+        ));
+    }
+    None
+}
+
+/// Average latency per bucket of `bucket` length, oldest first; `None` where nothing was called
+pub fn latency_buckets(samples: &VecDeque<Sample>, buckets: usize, bucket: Duration, now: Instant) -> Vec<Option<Duration>> {
+    let mut totals = vec![(Duration::ZERO, 0u32); buckets];
+    for sample in samples {
+        let age = now.duration_since(sample.at).as_secs_f64() / bucket.as_secs_f64();
+        if (age as usize) < buckets {
+            let slot = &mut totals[buckets - 1 - age as usize];
+            slot.0 += sample.latency;
+            slot.1 += 1;
+        }
+    }
+    totals.into_iter().map(|(total, n)| if n == 0 { None } else { Some(total / n) }).collect()
+}
+
+/// One-line chart of the values scaled to their maximum, `·` marks gaps
+pub fn sparkline(values: &[Option<f64>]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().flatten().cloned().fold(0.0_f64, f64::max);
+
+    values
+        .iter()
+        .map(|value| match value {
+            None => '·',
+            Some(_) if max <= 0.0 => BARS[0],
+            Some(v) => BARS[((v / max) * (BARS.len() - 1) as f64).round().clamp(0.0, (BARS.len() - 1) as f64) as usize],
+        })
+        .collect()
+}
+
+/// Recent LLM calls and whether auto-replies are paused because of them
+#[derive(Debug, Default)]
+pub struct LlmHealth {
+    samples: Mutex<VecDeque<Sample>>,
+    /// Why and since when replies are paused
+    paused: Mutex<Option<(String, chrono::DateTime<chrono::Utc>)>>,
+}
+
+impl LlmHealth {
+    pub fn record(&self, ok: bool, latency: Duration) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.push_back(Sample { at: Instant::now(), ok, latency });
+        // Plenty for any sensible window
+        while samples.len() > 1000 {
+            samples.pop_front();
+        }
+    }
+
+    pub fn diagnostics(&self, window: Duration) -> Diagnostics {
+        diagnose(&self.samples.lock().unwrap(), window, Instant::now())
+    }
+
+    /// Average latency of each of the last `minutes` minutes, oldest first
+    pub fn latency_by_minute(&self, minutes: usize) -> Vec<Option<Duration>> {
+        latency_buckets(&self.samples.lock().unwrap(), minutes, Duration::from_secs(60), Instant::now())
+    }
+
+    pub fn paused(&self) -> Option<(String, chrono::DateTime<chrono::Utc>)> {
+        self.paused.lock().unwrap().clone()
+    }
+
+    /// Pause (`Some(reason)`) or resume (`None`) auto-replies
+    pub fn set_paused(&self, reason: Option<String>) {
+        *self.paused.lock().unwrap() = reason.map(|r| (r, chrono::Utc::now()));
+    }
+
+    fn clear_samples(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+}
+
+/// Pause auto-replies when the chat model burns through its error budget,
+/// and resume once probes show it is healthy again
+pub async fn health_worker(state: AppState) {
+    tracing::info!("LLM health worker started");
+    let thresholds = state.config.llm_health;
+    let mut healthy_probes = 0;
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        if state.llm_health.paused().is_none() {
+            let diagnostics = state.llm_health.diagnostics(thresholds.window);
+            if let Some(reason) = budget_exceeded(&diagnostics, &thresholds) {
+                tracing::warn!("Pausing auto-replies: {} ({})", reason, diagnostics.describe());
+                state.set_paused(Some(reason.clone()));
+                healthy_probes = 0;
+                let notice = format!(
+                    "⏸ Auto-replies paused: the chat model is unhealthy ({}).\n\
+                    Last {} min: {}.\n\
+                    They resume by themselves once it recovers.",
+                    reason,
+                    thresholds.window.as_secs() / 60,
+                    diagnostics.describe()
+                );
+                if let Err(e) = crate::userbot::worker::notify_owner(&state, &notice).await {
+                    tracing::error!("Failed to notify owner about the pause: {}", e);
+                }
+            }
+            continue;
+        }
+
+        // Nothing calls the model while paused, so probe it
+        let started = Instant::now();
+        let ok = probe(&state).await;
+        let latency = started.elapsed();
+        if ok && latency <= thresholds.max_avg_latency {
+            healthy_probes += 1;
+        } else {
+            healthy_probes = 0;
+        }
+
+        if healthy_probes >= RECOVERY_PROBES {
+            tracing::info!("Chat model recovered, resuming auto-replies");
+            state.llm_health.clear_samples();
+            state.set_paused(None);
+            crate::ai::markov::clear_cache();
+            healthy_probes = 0;
+            let notice = format!(
+                "▶️ Auto-replies resumed: the chat model answered {} probes in a row (last {:.1}s).",
+                RECOVERY_PROBES,
+                latency.as_secs_f64()
+            );
+            if let Err(e) = crate::userbot::worker::notify_owner(&state, &notice).await {
+                tracing::error!("Failed to notify owner about the resume: {}", e);
+            }
+        }
+    }
+}
+
+/// A minimal chat request
+async fn probe(state: &AppState) -> bool {
+    let request = OllamaChatRequest {
+        model: state.config.ollama_model.clone(),
+        messages: vec![OllamaMessage {
+            role: "user".to_string(),
+            content: "ping".to_string(),
+        }],
+        stream: true,
+        options: None,
+    };
+
+    match OllamaClient::new(state.config.ollama_url.clone()).chat(request).await {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!("LLM health probe failed: {}", e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> HealthThresholds {
+        HealthThresholds {
+            window: Duration::from_secs(600),
+            min_samples: 4,
+            max_error_rate: 0.5,
+            max_avg_latency: Duration::from_secs(30),
+        }
+    }
+
+    fn samples(now: Instant, outcomes: &[(bool, u64)]) -> VecDeque<Sample> {
+        outcomes
+            .iter()
+            .map(|(ok, secs)| Sample { at: now, ok: *ok, latency: Duration::from_secs(*secs) })
+            .collect()
+    }
+
+    #[test]
+    fn too_few_samples_never_pause() {
+        let now = Instant::now();
+        let diagnostics = diagnose(&samples(now, &[(false, 1), (false, 1)]), Duration::from_secs(600), now);
+        assert_eq!(diagnostics.errors, 2);
+        assert!(budget_exceeded(&diagnostics, &thresholds()).is_none());
+    }
+
+    #[test]
+    fn errors_and_latency_exhaust_the_budget() {
+        let now = Instant::now();
+        let failing = diagnose(&samples(now, &[(false, 1), (false, 1), (false, 1), (true, 1)]), Duration::from_secs(600), now);
+        assert!(budget_exceeded(&failing, &thresholds()).unwrap().starts_with("error rate"));
+
+        let slow = diagnose(&samples(now, &[(true, 40), (true, 40), (true, 40), (true, 40)]), Duration::from_secs(600), now);
+        assert!(budget_exceeded(&slow, &thresholds()).unwrap().starts_with("avg. latency"));
+
+        let fine = diagnose(&samples(now, &[(true, 5), (false, 5), (true, 5), (true, 5)]), Duration::from_secs(600), now);
+        assert!(budget_exceeded(&fine, &thresholds()).is_none());
+    }
+
+    #[test]
+    fn old_samples_fall_out_of_the_window() {
+        let now = Instant::now();
+        let old = samples(now, &[(false, 1)]);
+        assert_eq!(diagnose(&old, Duration::from_secs(600), now + Duration::from_secs(700)).samples, 0);
+    }
+
+    #[test]
+    fn buckets_latency_by_age() {
+        let now = Instant::now();
+        let at = |secs_ago: u64, latency: u64| Sample {
+            at: now - Duration::from_secs(secs_ago),
+            ok: true,
+            latency: Duration::from_secs(latency),
+        };
+        let samples: VecDeque<Sample> = vec![at(150, 9), at(10, 2), at(20, 4), at(400, 1)].into();
+
+        let buckets = latency_buckets(&samples, 3, Duration::from_secs(60), now);
+        assert_eq!(buckets, vec![Some(Duration::from_secs(9)), None, Some(Duration::from_secs(3))]);
+    }
+
+    #[test]
+    fn draws_sparklines() {
+        assert_eq!(sparkline(&[Some(0.0), None, Some(5.0), Some(10.0)]), "▁·▅█");
+        assert_eq!(sparkline(&[Some(0.0), None]), "▁·");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
@@ -0,0 +1,435 @@
+use crate::db::AccountChat;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+/// Order of day, month and year in a written date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    Dmy,
+    Mdy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Units {
+    Metric,
+    Imperial,
+}
+
+impl Units {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Units::Metric => "metric",
+            Units::Imperial => "imperial",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "metric" | "si" => Some(Units::Metric),
+            "imperial" | "us" => Some(Units::Imperial),
+            _ => None,
+        }
+    }
+}
+
+/// Whether Cyrillic in replies is rewritten in Latin letters, as in chats that write "privet kak dela"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transliteration {
+    Keep,
+    Latin,
+}
+
+impl Transliteration {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Transliteration::Keep => "keep",
+            Transliteration::Latin => "latin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "keep" | "off" => Some(Transliteration::Keep),
+            "latin" | "translit" => Some(Transliteration::Latin),
+            _ => None,
+        }
+    }
+}
+
+/// How numbers, dates, money and units are written in a locale
+#[derive(Debug, PartialEq)]
+pub struct Locale {
+    pub tag: &'static str,
+    decimal: char,
+    grouping: char,
+    date_order: DateOrder,
+    date_separator: char,
+    /// "$5" rather than "5 $"
+    currency_prefix: bool,
+    units: Units,
+    /// Unit names in Cyrillic ("км") rather than Latin ("km")
+    cyrillic_units: bool,
+}
+
+pub const LOCALES: &[Locale] = &[
+    Locale { tag: "ru", decimal: ',', grouping: ' ', date_order: DateOrder::Dmy, date_separator: '.', currency_prefix: false, units: Units::Metric, cyrillic_units: true },
+    Locale { tag: "uk", decimal: ',', grouping: ' ', date_order: DateOrder::Dmy, date_separator: '.', currency_prefix: false, units: Units::Metric, cyrillic_units: true },
+    Locale { tag: "en-us", decimal: '.', grouping: ',', date_order: DateOrder::Mdy, date_separator: '/', currency_prefix: true, units: Units::Imperial, cyrillic_units: false },
+    Locale { tag: "en-gb", decimal: '.', grouping: ',', date_order: DateOrder::Dmy, date_separator: '/', currency_prefix: true, units: Units::Metric, cyrillic_units: false },
+    Locale { tag: "de", decimal: ',', grouping: '.', date_order: DateOrder::Dmy, date_separator: '.', currency_prefix: false, units: Units::Metric, cyrillic_units: false },
+    Locale { tag: "fr", decimal: ',', grouping: ' ', date_order: DateOrder::Dmy, date_separator: '/', currency_prefix: false, units: Units::Metric, cyrillic_units: false },
+    Locale { tag: "es", decimal: ',', grouping: '.', date_order: DateOrder::Dmy, date_separator: '/', currency_prefix: false, units: Units::Metric, cyrillic_units: false },
+];
+
+impl Locale {
+    /// Look a locale up by tag; "en" means US English
+    pub fn find(tag: &str) -> Option<&'static Locale> {
+        let tag = tag.trim().to_lowercase().replace('_', "-");
+        let tag = match tag.as_str() {
+            "en" | "us" => "en-us",
+            "gb" => "en-gb",
+            "ua" => "uk",
+            other => other,
+        };
+        LOCALES.iter().find(|l| l.tag == tag)
+    }
+
+    /// Digits with this locale's separators; `grouped` adds thousands separators
+    fn format_number(&self, int_digits: &str, frac_digits: Option<&str>, grouped: bool) -> String {
+        let mut out = String::new();
+        let len = int_digits.chars().count();
+        for (i, c) in int_digits.chars().enumerate() {
+            if grouped && i > 0 && (len - i) % 3 == 0 {
+                out.push(self.grouping);
+            }
+            out.push(c);
+        }
+        if let Some(frac) = frac_digits {
+            out.push(self.decimal);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    fn format_value(&self, value: f64) -> String {
+        // One decimal for small values, whole numbers otherwise
+        let rounded = if value.abs() < 10.0 { format!("{:.1}", value) } else { format!("{:.0}", value) };
+        let rounded = rounded.strip_suffix(".0").unwrap_or(&rounded).to_string();
+        match rounded.split_once('.') {
+            Some((int, frac)) => format!("{}{}{}", int, self.decimal, frac),
+            None => rounded,
+        }
+    }
+
+    fn format_date(&self, day: u32, month: u32, year: &str) -> String {
+        let sep = self.date_separator;
+        match self.date_order {
+            DateOrder::Dmy => format!("{:02}{}{:02}{}{}", day, sep, month, sep, year),
+            DateOrder::Mdy => format!("{:02}{}{:02}{}{}", month, sep, day, sep, year),
+        }
+    }
+}
+
+/// A chat's localization settings, ready to run over replies
+#[derive(Debug, Clone, Copy)]
+pub struct Localizer {
+    pub locale: Option<&'static Locale>,
+    pub units: Option<Units>,
+    pub transliteration: Transliteration,
+}
+
+lazy_static! {
+    static ref URL: Regex = Regex::new(r"(?i)(https?://|www\.)\S+").unwrap();
+    static ref ISO_DATE: Regex = Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})\b").unwrap();
+    static ref WRITTEN_DATE: Regex = Regex::new(r"\b(\d{1,2})([./])(\d{1,2})([./])(\d{4})\b").unwrap();
+    static ref NUMBER: Regex = Regex::new(r"\d[\d.,]*\d|\d").unwrap();
+    static ref CURRENCY_BEFORE: Regex =
+        Regex::new(r"([$€£₽₴₸])\s?(\d{1,3}(?:[ \u{a0}]\d{3})+(?:[.,]\d+)?|\d[\d.,]*\d|\d)").unwrap();
+    static ref CURRENCY_AFTER: Regex =
+        Regex::new(r"(\d{1,3}(?:[ \u{a0}]\d{3})+(?:[.,]\d+)?|\d[\d.,]*\d|\d)\s?([$€£₽₴₸])").unwrap();
+    // "m" and "in" are left out: too easily minutes and a preposition
+    static ref QUANTITY: Regex = Regex::new(
+        r"(?i)(-?\d+(?:[.,]\d{1,2})?)\s?(°\s?[cf]\b|miles?\b|mi\b|kilomet(?:er|re)s?\b|km\b|км\b|lbs?\b|kg\b|кг\b|feet\b|foot\b|ft\b|met(?:er|re)s?\b|inch(?:es)?\b|cm\b|см\b|мил[ьяиюе]\w*|фут\w*|дюйм\w*|метр\w*)"
+    )
+    .unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quantity {
+    Distance,
+    Weight,
+    Temperature,
+    Length,
+    Small,
+}
+
+/// What a unit measures, whether it's metric and its size in the metric unit of that quantity
+fn unit_info(unit: &str) -> Option<(Quantity, Units, f64)> {
+    let unit = unit.to_lowercase().replace(' ', "");
+    let info = match unit.as_str() {
+        "°c" => (Quantity::Temperature, Units::Metric, 1.0),
+        "°f" => (Quantity::Temperature, Units::Imperial, 1.0),
+        "mile" | "miles" | "mi" => (Quantity::Distance, Units::Imperial, 1.609344),
+        "km" | "км" | "kilometer" | "kilometers" | "kilometre" | "kilometres" => (Quantity::Distance, Units::Metric, 1.0),
+        "lb" | "lbs" => (Quantity::Weight, Units::Imperial, 0.45359237),
+        "kg" | "кг" => (Quantity::Weight, Units::Metric, 1.0),
+        "feet" | "foot" | "ft" => (Quantity::Length, Units::Imperial, 0.3048),
+        "meter" | "meters" | "metre" | "metres" => (Quantity::Length, Units::Metric, 1.0),
+        "inch" | "inches" | "in" => (Quantity::Small, Units::Imperial, 2.54),
+        "cm" | "см" => (Quantity::Small, Units::Metric, 1.0),
+        u if u.starts_with("мил") => (Quantity::Distance, Units::Imperial, 1.609344),
+        u if u.starts_with("фут") => (Quantity::Length, Units::Imperial, 0.3048),
+        u if u.starts_with("дюйм") => (Quantity::Small, Units::Imperial, 2.54),
+        u if u.starts_with("метр") => (Quantity::Length, Units::Metric, 1.0),
+        _ => return None,
+    };
+    Some(info)
+}
+
+fn unit_name(quantity: Quantity, units: Units, cyrillic: bool) -> &'static str {
+    match (quantity, units, cyrillic) {
+        (Quantity::Temperature, Units::Metric, _) => "°C",
+        (Quantity::Temperature, Units::Imperial, _) => "°F",
+        (Quantity::Distance, Units::Metric, false) => "km",
+        (Quantity::Distance, Units::Metric, true) => "км",
+        (Quantity::Distance, Units::Imperial, false) => "mi",
+        (Quantity::Distance, Units::Imperial, true) => "миль",
+        (Quantity::Weight, Units::Metric, false) => "kg",
+        (Quantity::Weight, Units::Metric, true) => "кг",
+        (Quantity::Weight, Units::Imperial, false) => "lb",
+        (Quantity::Weight, Units::Imperial, true) => "фунт.",
+        (Quantity::Length, Units::Metric, false) => "m",
+        (Quantity::Length, Units::Metric, true) => "м",
+        (Quantity::Length, Units::Imperial, false) => "ft",
+        (Quantity::Length, Units::Imperial, true) => "фт",
+        (Quantity::Small, Units::Metric, false) => "cm",
+        (Quantity::Small, Units::Metric, true) => "см",
+        (Quantity::Small, Units::Imperial, false) => "in",
+        (Quantity::Small, Units::Imperial, true) => "дюйм.",
+    }
+}
+
+/// Replace matches whose closure returns something, skipping those glued to a longer token
+/// ("v1.2", "abc2024-01-01", the tail of "1.234.5")
+fn replace_standalone(re: &Regex, text: &str, mut f: impl FnMut(&Captures) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut last = 0;
+    for caps in re.captures_iter(text) {
+        let m = caps.get(0).expect("match");
+        let glued = text[..m.start()]
+            .chars()
+            .next_back()
+            .is_some_and(|c| c.is_alphanumeric() || matches!(c, '.' | ',' | '_' | '/' | '#' | '@'));
+        if glued {
+            continue;
+        }
+        if let Some(replacement) = f(&caps) {
+            out.push_str(&text[last..m.start()]);
+            out.push_str(&replacement);
+            last = m.end();
+        }
+    }
+    out.push_str(&text[last..]);
+    out
+}
+
+/// Rewrite a number token ("1,234.5", "3.14", "1.000.000") with the locale's separators;
+/// None when it's ambiguous ("1,500") or not a number (versions like "1.2.3")
+fn normalize_number(token: &str, locale: &Locale) -> Option<String> {
+    let separators: Vec<char> = token.chars().filter(|c| matches!(c, '.' | ',')).collect();
+    let groups: Vec<&str> = token.split(['.', ',']).collect();
+    let (first, rest) = groups.split_first()?;
+    let grouping_ok = |groups: &[&str]| first.len() <= 3 && groups.iter().all(|g| g.len() == 3);
+
+    match separators.as_slice() {
+        [] => None,
+        // One separator before exactly three digits is either a decimal or a thousands separator
+        [_] if rest[0].len() == 3 => None,
+        [_] => Some(locale.format_number(first, Some(rest[0]), false)),
+        [sep, more @ ..] if more.iter().all(|s| s == sep) => {
+            grouping_ok(rest).then(|| locale.format_number(&groups.concat(), None, true))
+        }
+        [.., decimal] => {
+            let (int_groups, frac) = rest.split_at(rest.len() - 1);
+            let grouping = &separators[..separators.len() - 1];
+            let consistent = grouping.iter().all(|s| s != decimal && *s == grouping[0]);
+            (consistent && grouping_ok(int_groups)).then(|| {
+                let int: String = std::iter::once(*first).chain(int_groups.iter().copied()).collect();
+                locale.format_number(&int, Some(frac[0]), true)
+            })
+        }
+    }
+}
+
+fn localize_dates(text: &str, locale: &Locale) -> String {
+    let valid = |day: u32, month: u32| (1..=31).contains(&day) && (1..=12).contains(&month);
+
+    let text = replace_standalone(&ISO_DATE, text, |caps| {
+        let month: u32 = caps[2].parse().ok()?;
+        let day: u32 = caps[3].parse().ok()?;
+        valid(day, month).then(|| locale.format_date(day, month, &caps[1]))
+    });
+
+    replace_standalone(&WRITTEN_DATE, &text, |caps| {
+        if caps[2] != caps[4] {
+            return None;
+        }
+        let a: u32 = caps[1].parse().ok()?;
+        let b: u32 = caps[3].parse().ok()?;
+        let (day, month) = match (a > 12, b > 12) {
+            (true, false) => (a, b),
+            (false, true) => (b, a),
+            (true, true) => return None,
+            // Dots mean day first; slashes are read the locale's way
+            (false, false) if &caps[2] == "." || locale.date_order == DateOrder::Dmy => (a, b),
+            (false, false) => (b, a),
+        };
+        valid(day, month).then(|| locale.format_date(day, month, &caps[5]))
+    })
+}
+
+fn localize_quantities(text: &str, locale: &Locale, target: Units) -> String {
+    replace_standalone(&QUANTITY, text, |caps| {
+        let (quantity, units, factor) = unit_info(&caps[2])?;
+        let name = unit_name(quantity, target, locale.cyrillic_units);
+        let separator = if quantity == Quantity::Temperature { "" } else { " " };
+        if units == target {
+            return Some(format!("{}{}{}", &caps[1], separator, name));
+        }
+
+        let value: f64 = caps[1].replace(',', ".").parse().ok()?;
+        let converted = match (quantity, target) {
+            (Quantity::Temperature, Units::Metric) => (value - 32.0) * 5.0 / 9.0,
+            (Quantity::Temperature, Units::Imperial) => value * 9.0 / 5.0 + 32.0,
+            (_, Units::Metric) => value * factor,
+            (_, Units::Imperial) => {
+                let (_, _, imperial_factor) = unit_info(unit_name(quantity, Units::Imperial, false))?;
+                value / imperial_factor
+            }
+        };
+        let converted = if quantity == Quantity::Temperature { converted.round() } else { converted };
+        Some(format!("{}{}{}", locale.format_value(converted), separator, name))
+    })
+}
+
+fn localize_currency(text: &str, locale: &Locale) -> String {
+    if locale.currency_prefix {
+        replace_standalone(&CURRENCY_AFTER, text, |caps| Some(format!("{}{}", &caps[2], &caps[1])))
+    } else {
+        replace_standalone(&CURRENCY_BEFORE, text, |caps| Some(format!("{} {}", &caps[2], &caps[1])))
+    }
+}
+
+/// Russian and Ukrainian letters in a common Latin spelling
+fn transliterate_char(c: char) -> Option<&'static str> {
+    let latin = match c.to_lowercase().next()? {
+        'а' => "a", 'б' => "b", 'в' => "v", 'г' => "g", 'ґ' => "g", 'д' => "d", 'е' => "e", 'ё' => "yo",
+        'є' => "ye", 'ж' => "zh", 'з' => "z", 'и' => "i", 'і' => "i", 'ї' => "yi", 'й' => "y", 'к' => "k",
+        'л' => "l", 'м' => "m", 'н' => "n", 'о' => "o", 'п' => "p", 'р' => "r", 'с' => "s", 'т' => "t",
+        'у' => "u", 'ф' => "f", 'х' => "h", 'ц' => "ts", 'ч' => "ch", 'ш' => "sh", 'щ' => "sch", 'ъ' => "",
+        'ы' => "y", 'ь' => "", 'э' => "e", 'ю' => "yu", 'я' => "ya",
+        _ => return None,
+    };
+    Some(latin)
+}
+
+pub fn transliterate(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match transliterate_char(c) {
+            Some(latin) if c.is_uppercase() => {
+                let mut chars = latin.chars();
+                if let Some(first) = chars.next() {
+                    out.extend(first.to_uppercase());
+                    out.push_str(chars.as_str());
+                }
+            }
+            Some(latin) => out.push_str(latin),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+impl Localizer {
+    /// The chat's settings; None when it leaves replies as generated
+    pub fn for_chat(chat: Option<&AccountChat>) -> Option<Self> {
+        let chat = chat?;
+        let locale = chat.locale.as_deref().and_then(Locale::find);
+        let transliteration = Transliteration::parse(&chat.transliteration).unwrap_or(Transliteration::Keep);
+        if locale.is_none() && transliteration == Transliteration::Keep {
+            return None;
+        }
+        Some(Self { locale, units: chat.units.as_deref().and_then(Units::parse), transliteration })
+    }
+
+    /// Normalize a reply; links are left alone and `||` separators survive
+    pub fn apply(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for url in URL.find_iter(text) {
+            out.push_str(&self.apply_plain(&text[last..url.start()]));
+            out.push_str(url.as_str());
+            last = url.end();
+        }
+        out.push_str(&self.apply_plain(&text[last..]));
+        out
+    }
+
+    fn apply_plain(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        if let Some(locale) = self.locale {
+            text = localize_dates(&text, locale);
+            text = localize_quantities(&text, locale, self.units.unwrap_or(locale.units));
+            text = localize_currency(&text, locale);
+            text = replace_standalone(&NUMBER, &text, |caps| normalize_number(&caps[0], locale));
+        }
+        if self.transliteration == Transliteration::Latin {
+            text = transliterate(&text);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn localizer(tag: &str) -> Localizer {
+        Localizer { locale: Locale::find(tag), units: None, transliteration: Transliteration::Keep }
+    }
+
+    #[test]
+    fn rewrites_dates_for_the_locale() {
+        assert_eq!(localizer("ru").apply("встреча 2024-03-05"), "встреча 05.03.2024");
+        assert_eq!(localizer("en").apply("due 2024-03-05"), "due 03/05/2024");
+        assert_eq!(localizer("ru").apply("с 12/25/2024"), "с 25.12.2024");
+        assert_eq!(localizer("en-gb").apply("on 05.03.2024"), "on 05/03/2024");
+    }
+
+    #[test]
+    fn fixes_separators_but_leaves_ambiguous_numbers() {
+        let ru = localizer("ru");
+        assert_eq!(ru.apply("примерно 3.5 часа"), "примерно 3,5 часа");
+        assert_eq!(ru.apply("итого 1,234.56"), "итого 1 234,56");
+        assert_eq!(ru.apply("было 1,500"), "было 1,500");
+        assert_eq!(ru.apply("версия 1.2.3 и v2.5"), "версия 1.2.3 и v2.5");
+        assert_eq!(localizer("de").apply("1,000,000 Leute"), "1.000.000 Leute");
+        assert_eq!(localizer("en").apply("about 2,5 hours"), "about 2.5 hours");
+    }
+
+    #[test]
+    fn converts_units_and_places_currency() {
+        assert_eq!(localizer("ru").apply("бежал 5 miles при 68°F"), "бежал 8 км при 20°C");
+        assert_eq!(localizer("en").apply("it's 10 km"), "it's 6.2 mi");
+        assert_eq!(localizer("ru").apply("стоит $15"), "стоит 15 $");
+        assert_eq!(localizer("en").apply("costs 15 €"), "costs €15");
+        assert_eq!(localizer("ru").apply("глянь https://x.com/a/2024-03-05/1.5"), "глянь https://x.com/a/2024-03-05/1.5");
+    }
+
+    #[test]
+    fn transliterates_cyrillic() {
+        let translit = Localizer { locale: None, units: None, transliteration: Transliteration::Latin };
+        assert_eq!(translit.apply("Привет, как дела? Щас буду"), "Privet, kak dela? Schas budu");
+    }
+}
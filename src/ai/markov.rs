@@ -0,0 +1,161 @@
+use crate::{db::MessageRepository, state::AppState};
+use anyhow::Result;
+use lazy_static::lazy_static;
+use rand::{seq::SliceRandom, Rng};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Messages a chat's chain is built from
+const MAX_TEXTS: i64 = 2000;
+
+/// With fewer of the account's own replies than this, everyone's messages are used
+const MIN_OWN_TEXTS: usize = 50;
+
+/// A chain this small can only parrot, so nothing is said
+const MIN_WORDS: usize = 30;
+
+/// Longest reply, in words
+const MAX_WORDS: usize = 12;
+
+/// Chains are rebuilt this often while the chat model stays down
+const CACHE_SECS: u64 = 10 * 60;
+
+/// A chain and when it was built
+type CachedChain = (Instant, Arc<MarkovChain>);
+
+lazy_static! {
+    static ref CHAINS: Mutex<HashMap<(i64, i64), CachedChain>> = Mutex::new(HashMap::new());
+}
+
+/// How a word is looked up: lowercase, without surrounding punctuation
+fn key(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase()
+}
+
+/// Word-to-next-word frequencies of a chat's messages
+#[derive(Debug, Default)]
+pub struct MarkovChain {
+    /// Words seen after each word, repeated by frequency; `None` ends a message
+    next: HashMap<String, Vec<Option<String>>>,
+    /// Words messages start with
+    starts: Vec<String>,
+    words: usize,
+}
+
+impl MarkovChain {
+    pub fn build<'a>(texts: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut chain = MarkovChain::default();
+        for text in texts {
+            // Media placeholders and commands aren't speech
+            if text.starts_with('[') || text.starts_with('/') {
+                continue;
+            }
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let first = match words.first() {
+                Some(first) => first,
+                None => continue,
+            };
+            chain.starts.push(first.to_string());
+            chain.words += words.len();
+            for (i, word) in words.iter().enumerate() {
+                chain
+                    .next
+                    .entry(key(word))
+                    .or_default()
+                    .push(words.get(i + 1).map(|w| w.to_string()));
+            }
+        }
+        chain
+    }
+
+    /// Whether there is enough to say anything that isn't a copy of one message
+    pub fn is_usable(&self) -> bool {
+        self.words >= MIN_WORDS
+    }
+
+    /// A short reply, starting from a word of the incoming message when the chain knows one
+    pub fn generate(&self, incoming: &str, rng: &mut impl Rng) -> Option<String> {
+        let known: Vec<String> = incoming
+            .split_whitespace()
+            .map(key)
+            .filter(|w| self.next.contains_key(w))
+            .collect();
+        let mut word = match known.choose(rng) {
+            Some(word) => word.clone(),
+            None => self.starts.choose(rng)?.clone(),
+        };
+
+        let mut reply = vec![word.clone()];
+        while reply.len() < MAX_WORDS {
+            match self.next.get(&key(&word)).and_then(|next| next.choose(rng)) {
+                Some(Some(next)) => {
+                    word = next.clone();
+                    reply.push(word.clone());
+                }
+                _ => break,
+            }
+        }
+        Some(reply.join(" "))
+    }
+}
+
+async fn chain_for(state: &AppState, account_id: i64, chat_id: i64) -> Result<Arc<MarkovChain>> {
+    if let Some((built, chain)) = CHAINS.lock().unwrap().get(&(account_id, chat_id)) {
+        if built.elapsed() < Duration::from_secs(CACHE_SECS) {
+            return Ok(chain.clone());
+        }
+    }
+
+    // In the account's own words when there are enough of them
+    let mut texts = MessageRepository::chat_texts(&state.db_pool, account_id, chat_id, true, MAX_TEXTS).await?;
+    if texts.len() < MIN_OWN_TEXTS {
+        texts = MessageRepository::chat_texts(&state.db_pool, account_id, chat_id, false, MAX_TEXTS).await?;
+    }
+    let chain = Arc::new(MarkovChain::build(texts.iter().map(|t| t.as_str())));
+    CHAINS.lock().unwrap().insert((account_id, chat_id), (Instant::now(), chain.clone()));
+    Ok(chain)
+}
+
+/// A reply made without the chat model, or `None` when the chat has too little history
+pub async fn offline_reply(state: &AppState, account_id: i64, chat_id: i64, incoming: &str) -> Result<Option<String>> {
+    let chain = chain_for(state, account_id, chat_id).await?;
+    if !chain.is_usable() {
+        return Ok(None);
+    }
+    Ok(chain.generate(incoming, &mut rand::thread_rng()))
+}
+
+/// Drop the cached chains once the chat model is back
+pub fn clear_cache() {
+    CHAINS.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_words_seen_in_the_chat() {
+        let texts = ["кот спит на диване", "кот ест рыбу", "[Голосовое сообщение]: привет", "/karma"];
+        let chain = MarkovChain::build(texts);
+        assert_eq!(chain.words, 7);
+        assert!(!chain.is_usable());
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..20 {
+            let reply = chain.generate("а где КОТ?", &mut rng).unwrap();
+            assert!(reply.starts_with("кот "), "{}", reply);
+            assert!(!reply.contains("привет"));
+            assert!(reply.split_whitespace().count() <= MAX_WORDS);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_message_starts() {
+        let chain = MarkovChain::build(["ну да", "ну нет"]);
+        let reply = chain.generate("completely unrelated", &mut rand::thread_rng()).unwrap();
+        assert!(reply.starts_with("ну "));
+        assert!(MarkovChain::default().generate("hi", &mut rand::thread_rng()).is_none());
+    }
+}
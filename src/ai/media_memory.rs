@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use sqlx::{Row, SqlitePool};
+
+/// Below this similarity a remembered picture isn't "that meme" the user means
+const MATCH_THRESHOLD: f32 = 0.45;
+/// How many recent pictures of a chat are compared with the request
+const CANDIDATES: i64 = 300;
+
+lazy_static! {
+    /// "скинь тот мем с котом", "покажи ту фотку", "send that gif again"
+    static ref MEDIA_REQUEST: Regex = Regex::new(
+        r"(?i)\b(скинь|кинь|пришли|покажи|перешли|отправь|send|show|post)\w*\b.*\b(мем\w*|фот\w*|картин\w*|пикч\w*|гиф\w*|гифк\w*|meme|photo|pic|picture|image|gif)\b"
+    )
+    .unwrap();
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    Photo,
+    Animation,
+}
+
+impl MediaKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaKind::Photo => "photo",
+            MediaKind::Animation => "animation",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "photo" => Some(MediaKind::Photo),
+            "animation" => Some(MediaKind::Animation),
+            _ => None,
+        }
+    }
+}
+
+/// A picture or GIF as Telegram knows it: the remote file id can be sent again without downloading
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MediaRef {
+    pub kind: MediaKind,
+    pub remote_file_id: String,
+}
+
+/// A remembered picture matching a request
+#[derive(Debug, Clone)]
+pub struct RememberedMedia {
+    pub media: MediaRef,
+    /// The vision description it was remembered by
+    pub description: String,
+    pub message_id: Option<i64>,
+    pub similarity: f32,
+}
+
+/// Cheap check before embedding anything: does the message ask to send a picture again
+pub fn looks_like_media_request(text: &str) -> bool {
+    MEDIA_REQUEST.is_match(text)
+}
+
+/// Link a stored memory (found by its content, as `store_memory` deduplicates by it) to the media it describes
+pub async fn attach(pool: &SqlitePool, account_id: i64, chat_id: i64, content: &str, media: &MediaRef) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE long_term_memory SET media_kind = ?, media_file_id = ?
+        WHERE account_id = ? AND chat_id = ? AND content_hash = ?
+        "#,
+    )
+    .bind(media.kind.as_str())
+    .bind(&media.remote_file_id)
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(super::rag::content_hash(content))
+    .execute(pool)
+    .await
+    .context("Failed to attach media to memory")?;
+
+    Ok(())
+}
+
+/// The remembered picture of a chat closest to the request, if any is close enough
+pub async fn find(
+    pool: &SqlitePool,
+    account_id: i64,
+    chat_id: i64,
+    query_embedding: &[f32],
+) -> Result<Option<RememberedMedia>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT content, embedding, message_id, media_kind, media_file_id
+        FROM long_term_memory
+        WHERE account_id = ? AND chat_id = ? AND media_file_id IS NOT NULL AND is_hashed = 0
+        ORDER BY created_at DESC
+        LIMIT ?
+        "#,
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(CANDIDATES)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch media memories")?;
+
+    let best = rows
+        .into_iter()
+        .filter_map(|row| {
+            let embedding_bytes: Vec<u8> = row.try_get("embedding").ok()?;
+            let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes).ok()?;
+            let kind: String = row.try_get("media_kind").ok()?;
+            Some(RememberedMedia {
+                media: MediaRef {
+                    kind: MediaKind::parse(&kind)?,
+                    remote_file_id: row.try_get("media_file_id").ok()?,
+                },
+                description: row.try_get("content").ok()?,
+                message_id: row.try_get("message_id").ok().flatten(),
+                similarity: super::rag::cosine_similarity(query_embedding, &embedding),
+            })
+        })
+        .max_by(|a, b| a.similarity.partial_cmp(&b.similarity).unwrap_or(std::cmp::Ordering::Equal));
+
+    Ok(best.filter(|m| m.similarity >= MATCH_THRESHOLD))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spots_requests_to_resend_media() {
+        assert!(looks_like_media_request("скинь тот мем с котом"));
+        assert!(looks_like_media_request("Покажи ещё раз ту фотку с моря"));
+        assert!(looks_like_media_request("can you send that gif again"));
+        assert!(!looks_like_media_request("смешной мем был"));
+        assert!(!looks_like_media_request("скинь адрес"));
+    }
+}
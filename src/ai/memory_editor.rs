@@ -0,0 +1,349 @@
+use crate::{ai::rag, state::AppState};
+use anyhow::{Context, Result};
+use sqlx::SqlitePool;
+
+/// Memories shown per page of /memories
+pub const PAGE_SIZE: i64 = 10;
+
+/// Highest importance /memory_edit accepts
+pub const MAX_IMPORTANCE: f64 = 2.0;
+
+/// An episodic memory as the owner sees and edits it
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct MemoryChunk {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub content: String,
+    /// Unix time
+    pub created_at: i64,
+    pub sender_id: Option<i64>,
+    pub is_bot_author: bool,
+    pub importance: f64,
+    pub media_kind: Option<String>,
+}
+
+const CHUNK_COLUMNS: &str = "id, account_id, chat_id, content, created_at, sender_id, is_bot_author, importance, media_kind";
+
+/// Which memories of a chat a bulk delete removes; every set condition must hold
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MemoryFilter {
+    pub sender_id: Option<i64>,
+    /// Case-insensitive text the memory contains
+    pub contains: Option<String>,
+    pub older_than_days: Option<i64>,
+    pub bots_only: bool,
+}
+
+impl MemoryFilter {
+    /// Parse `user=42 match=crypto older=30 bots`
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let mut filter = MemoryFilter::default();
+        for arg in args {
+            match arg.split_once('=') {
+                Some(("user", id)) => filter.sender_id = Some(id.parse().map_err(|_| "user must be a number")?),
+                Some(("match", text)) if !text.is_empty() => filter.contains = Some(text.to_string()),
+                Some(("older", days)) => {
+                    filter.older_than_days =
+                        Some(days.parse().ok().filter(|d: &i64| *d >= 0).ok_or("older must be a number of days")?)
+                }
+                None if arg == "bots" => filter.bots_only = true,
+                _ => return Err(format!("unknown filter '{}'", arg)),
+            }
+        }
+        if filter == MemoryFilter::default() {
+            return Err("give at least one of user=, match=, older= or bots".to_string());
+        }
+        Ok(filter)
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(id) = self.sender_id {
+            parts.push(format!("from user {}", id));
+        }
+        if let Some(text) = &self.contains {
+            parts.push(format!("containing \"{}\"", text));
+        }
+        if let Some(days) = self.older_than_days {
+            parts.push(format!("older than {} days", days));
+        }
+        if self.bots_only {
+            parts.push("written by bots".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// One page of a chat's memories, newest first, with the total count
+pub async fn page(pool: &SqlitePool, account_id: i64, chat_id: i64, page: i64) -> Result<(Vec<MemoryChunk>, i64)> {
+    let chunks = sqlx::query_as::<_, MemoryChunk>(&format!(
+        r#"
+        SELECT {} FROM long_term_memory
+        WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
+        ORDER BY created_at DESC, id DESC
+        LIMIT ? OFFSET ?
+        "#,
+        CHUNK_COLUMNS
+    ))
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(PAGE_SIZE)
+    .bind(page.max(0) * PAGE_SIZE)
+    .fetch_all(pool)
+    .await
+    .context("Failed to list memories")?;
+
+    let (total,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM long_term_memory WHERE account_id = ? AND chat_id = ? AND is_hashed = 0")
+            .bind(account_id)
+            .bind(chat_id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to count memories")?;
+
+    Ok((chunks, total))
+}
+
+/// The chat's memories closest to a query, across all of them rather than only the recent ones retrieval looks at
+pub async fn search(state: &AppState, account_id: i64, chat_id: i64, query: &str, limit: usize) -> Result<Vec<(MemoryChunk, f32)>> {
+    let query_embedding = rag::generate_embedding(
+        &reqwest::Client::new(),
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        query,
+    )
+    .await?;
+
+    let rows: Vec<(i64, Vec<u8>)> = sqlx::query_as(
+        "SELECT id, embedding FROM long_term_memory WHERE account_id = ? AND chat_id = ? AND is_hashed = 0",
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .fetch_all(&state.db_pool)
+    .await
+    .context("Failed to fetch memory embeddings")?;
+
+    let mut scored: Vec<(i64, f32)> = rows
+        .into_iter()
+        .filter_map(|(id, bytes)| {
+            let embedding: Vec<f32> = bincode::deserialize(&bytes).ok()?;
+            Some((id, rag::cosine_similarity(&query_embedding, &embedding)))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    scored.truncate(limit);
+
+    let mut results = Vec::new();
+    for (id, similarity) in scored {
+        if let Some(chunk) = get(&state.db_pool, id).await? {
+            results.push((chunk, similarity));
+        }
+    }
+    Ok(results)
+}
+
+pub async fn get(pool: &SqlitePool, id: i64) -> Result<Option<MemoryChunk>> {
+    let chunk = sqlx::query_as::<_, MemoryChunk>(&format!("SELECT {} FROM long_term_memory WHERE id = ?", CHUNK_COLUMNS))
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch memory")?;
+
+    Ok(chunk)
+}
+
+/// Replace a memory's text; it is embedded again so retrieval finds it by the new text
+pub async fn edit_text(state: &AppState, id: i64, text: &str) -> Result<bool> {
+    let embedding = rag::generate_embedding(
+        &reqwest::Client::new(),
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        text,
+    )
+    .await?;
+    let embedding_bytes = bincode::serialize(&embedding).context("Failed to serialize embedding")?;
+
+    let result = sqlx::query(
+        "UPDATE long_term_memory SET content = ?, embedding = ?, embedding_model = ?, content_hash = ? WHERE id = ?",
+    )
+    .bind(text)
+    .bind(embedding_bytes)
+    .bind(&state.config.ollama_embed_model)
+    .bind(rag::content_hash(text))
+    .bind(id)
+    .execute(&state.db_pool)
+    .await
+    .context("Failed to edit memory")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn set_importance(pool: &SqlitePool, id: i64, importance: f64) -> Result<bool> {
+    let result = sqlx::query("UPDATE long_term_memory SET importance = ? WHERE id = ?")
+        .bind(importance)
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to set memory importance")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete(pool: &SqlitePool, id: i64) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM long_term_memory WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to delete memory")?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+const FILTER_CONDITIONS: &str = r#"
+    account_id = ? AND chat_id = ?
+    AND (? IS NULL OR sender_id = ?)
+    AND (? IS NULL OR instr(lower(content), lower(?)) > 0)
+    AND (? IS NULL OR created_at < strftime('%s', 'now') - ? * 86400)
+    AND (? = 0 OR is_bot_author = 1)
+"#;
+
+/// How many memories of a chat a filter matches
+pub async fn count_matching(pool: &SqlitePool, account_id: i64, chat_id: i64, filter: &MemoryFilter) -> Result<i64> {
+    let (count,): (i64,) = sqlx::query_as(&format!("SELECT COUNT(*) FROM long_term_memory WHERE {}", FILTER_CONDITIONS))
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(filter.sender_id)
+        .bind(filter.sender_id)
+        .bind(&filter.contains)
+        .bind(&filter.contains)
+        .bind(filter.older_than_days)
+        .bind(filter.older_than_days)
+        .bind(filter.bots_only)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count matching memories")?;
+
+    Ok(count)
+}
+
+/// Delete the memories of a chat a filter matches
+pub async fn delete_matching(pool: &SqlitePool, account_id: i64, chat_id: i64, filter: &MemoryFilter) -> Result<u64> {
+    let result = sqlx::query(&format!("DELETE FROM long_term_memory WHERE {}", FILTER_CONDITIONS))
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(filter.sender_id)
+        .bind(filter.sender_id)
+        .bind(&filter.contains)
+        .bind(&filter.contains)
+        .bind(filter.older_than_days)
+        .bind(filter.older_than_days)
+        .bind(filter.bots_only)
+        .execute(pool)
+        .await
+        .context("Failed to delete matching memories")?;
+
+    Ok(result.rows_affected())
+}
+
+/// Width of the periods /memory_heatmap groups memories into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeatmapPeriod {
+    Week,
+    Month,
+}
+
+impl HeatmapPeriod {
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg.to_lowercase().as_str() {
+            "week" | "weeks" => Some(HeatmapPeriod::Week),
+            "month" | "months" => Some(HeatmapPeriod::Month),
+            _ => None,
+        }
+    }
+
+    fn strftime(self) -> &'static str {
+        match self {
+            HeatmapPeriod::Week => "%Y-W%W",
+            HeatmapPeriod::Month => "%Y-%m",
+        }
+    }
+}
+
+/// Memories of a chat stored in one period, and how often retrieval used them
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct HeatmapBucket {
+    /// "2026-03" or "2026-W09"
+    pub period: String,
+    pub chunks: i64,
+    pub avg_importance: f64,
+    /// Times memories of the period went into a reply prompt
+    pub hits: i64,
+}
+
+/// How a chat's memories spread over time, oldest period first
+pub async fn heatmap(pool: &SqlitePool, account_id: i64, chat_id: i64, period: HeatmapPeriod) -> Result<Vec<HeatmapBucket>> {
+    let buckets = sqlx::query_as::<_, HeatmapBucket>(
+        r#"
+        SELECT strftime(?, created_at, 'unixepoch') AS period,
+               COUNT(*) AS chunks,
+               AVG(importance) AS avg_importance,
+               SUM(hits) AS hits
+        FROM long_term_memory
+        WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
+        GROUP BY period
+        ORDER BY period
+        "#,
+    )
+    .bind(period.strftime())
+    .bind(account_id)
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to build memory heatmap")?;
+
+    Ok(buckets)
+}
+
+/// One heatmap cell: how a value compares with the largest one in its column
+pub fn heat(value: i64, max: i64) -> char {
+    const SHADES: [char; 4] = ['░', '▒', '▓', '█'];
+    if value <= 0 || max <= 0 {
+        return '·';
+    }
+    let level = ((value as f64 / max as f64) * SHADES.len() as f64).ceil() as usize;
+    SHADES[level.clamp(1, SHADES.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn parses_bulk_delete_filters() {
+        let filter = MemoryFilter::parse(&args("user=42 match=crypto older=30 bots")).unwrap();
+        assert_eq!(filter.sender_id, Some(42));
+        assert_eq!(filter.contains.as_deref(), Some("crypto"));
+        assert_eq!(filter.older_than_days, Some(30));
+        assert!(filter.bots_only);
+
+        assert!(MemoryFilter::parse(&[]).is_err());
+        assert!(MemoryFilter::parse(&args("user=me")).is_err());
+        assert!(MemoryFilter::parse(&args("older=-1")).is_err());
+        assert!(MemoryFilter::parse(&args("everything")).is_err());
+    }
+
+    #[test]
+    fn shades_relative_to_the_busiest_period() {
+        assert_eq!(heat(0, 10), '·');
+        assert_eq!(heat(1, 10), '░');
+        assert_eq!(heat(5, 10), '▒');
+        assert_eq!(heat(7, 10), '▓');
+        assert_eq!(heat(10, 10), '█');
+        assert_eq!(heat(3, 0), '·');
+    }
+}
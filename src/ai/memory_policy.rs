@@ -0,0 +1,70 @@
+/// What conversations answered by a persona leave in long-term memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryWritePolicy {
+    /// Incoming messages and the persona's own replies
+    All,
+    /// Incoming messages only
+    #[default]
+    UserOnly,
+    /// Nothing: neither memories nor ghost-mode examples
+    None,
+}
+
+impl MemoryWritePolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MemoryWritePolicy::All => "all",
+            MemoryWritePolicy::UserOnly => "user_only",
+            MemoryWritePolicy::None => "none",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "all" => Some(MemoryWritePolicy::All),
+            "user_only" | "user" => Some(MemoryWritePolicy::UserOnly),
+            "none" | "off" => Some(MemoryWritePolicy::None),
+            _ => None,
+        }
+    }
+
+    /// The policy of the answering persona; no persona keeps the default
+    pub fn of(persona: Option<&crate::db::Persona>) -> Self {
+        persona
+            .and_then(|p| Self::parse(&p.memory_write))
+            .unwrap_or_default()
+    }
+
+    pub fn stores_incoming(&self) -> bool {
+        *self != MemoryWritePolicy::None
+    }
+
+    pub fn stores_replies(&self) -> bool {
+        *self == MemoryWritePolicy::All
+    }
+
+    /// Ghost-mode corrections become few-shot examples
+    pub fn stores_examples(&self) -> bool {
+        *self != MemoryWritePolicy::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_round_trips() {
+        for policy in [MemoryWritePolicy::All, MemoryWritePolicy::UserOnly, MemoryWritePolicy::None] {
+            assert_eq!(MemoryWritePolicy::parse(policy.as_str()), Some(policy));
+        }
+        assert_eq!(MemoryWritePolicy::parse("everything"), None);
+    }
+
+    #[test]
+    fn none_writes_nothing() {
+        let policy = MemoryWritePolicy::None;
+        assert!(!policy.stores_incoming() && !policy.stores_replies() && !policy.stores_examples());
+        assert!(MemoryWritePolicy::UserOnly.stores_incoming() && !MemoryWritePolicy::UserOnly.stores_replies());
+    }
+}
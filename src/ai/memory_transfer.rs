@@ -0,0 +1,403 @@
+use crate::{ai::rag, state::AppState};
+use anyhow::{Context, Result};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use std::io::{BufRead, BufReader, Write};
+
+/// Identifies memory exports; bumped when the record layout changes
+pub const EXPORT_FORMAT: &str = "puppeteer-memory";
+pub const EXPORT_VERSION: u32 = 1;
+
+/// Exports bigger than this are refused on import (decompressed bytes)
+const MAX_IMPORT_BYTES: u64 = 200 * 1024 * 1024;
+
+/// First line of an export
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportHeader {
+    pub format: String,
+    pub version: u32,
+    pub account_id: i64,
+    pub chat_id: i64,
+    /// Embedding model of the exported vectors, if they are included
+    pub embed_model: Option<String>,
+    pub dimension: Option<usize>,
+    pub exported_at: i64,
+    pub records: usize,
+}
+
+/// One memory, episodic or semantic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryRecord {
+    pub tier: rag::MemoryTier,
+    pub content: String,
+    pub created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender_id: Option<i64>,
+    #[serde(default)]
+    pub is_bot_author: bool,
+    /// Semantic facts only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub support_count: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<Vec<f32>>,
+}
+
+/// Outcome of importing a batch of records
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub duplicates: usize,
+    /// Records that came without usable vectors and were embedded again
+    pub reembedded: usize,
+    pub failed: usize,
+}
+
+impl ImportReport {
+    pub fn add(&mut self, other: ImportReport) {
+        self.imported += other.imported;
+        self.duplicates += other.duplicates;
+        self.reembedded += other.reembedded;
+        self.failed += other.failed;
+    }
+}
+
+/// Serialize a chat's memories into gzip-compressed JSONL.
+/// Memories already hashed by retention have no text left and are skipped.
+pub async fn export_chat(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    with_embeddings: bool,
+) -> Result<(Vec<u8>, usize)> {
+    let mut records = episodic_records(&state.db_pool, account_id, chat_id, with_embeddings).await?;
+    records.extend(semantic_records(&state.db_pool, account_id, chat_id, with_embeddings).await?);
+
+    let header = ExportHeader {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        account_id,
+        chat_id,
+        embed_model: with_embeddings.then(|| state.config.ollama_embed_model.clone()),
+        dimension: records.iter().find_map(|r| r.embedding.as_ref().map(|e| e.len())),
+        exported_at: chrono::Utc::now().timestamp(),
+        records: records.len(),
+    };
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    serde_json::to_writer(&mut encoder, &header)?;
+    encoder.write_all(b"\n")?;
+    for record in &records {
+        serde_json::to_writer(&mut encoder, record)?;
+        encoder.write_all(b"\n")?;
+    }
+    let bytes = encoder.finish().context("Failed to compress memory export")?;
+
+    Ok((bytes, records.len()))
+}
+
+/// Decompress and validate an export. Errors name the offending line.
+pub fn parse_export(bytes: &[u8]) -> Result<(ExportHeader, Vec<MemoryRecord>), String> {
+    use std::io::Read;
+
+    let reader = BufReader::new(GzDecoder::new(bytes).take(MAX_IMPORT_BYTES));
+    let mut lines = reader.lines().enumerate();
+
+    let header: ExportHeader = match lines.next() {
+        Some((_, Ok(line))) => serde_json::from_str(&line).map_err(|e| format!("invalid header: {}", e))?,
+        Some((_, Err(e))) => return Err(format!("not a gzip-compressed export: {}", e)),
+        None => return Err("the file is empty".to_string()),
+    };
+    if header.format != EXPORT_FORMAT {
+        return Err(format!("unknown format '{}'", header.format));
+    }
+    if header.version > EXPORT_VERSION {
+        return Err(format!("export version {} is newer than this deployment supports", header.version));
+    }
+
+    let mut records = Vec::with_capacity(header.records);
+    for (i, line) in lines {
+        let line = line.map_err(|e| format!("line {}: {}", i + 1, e))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: MemoryRecord = serde_json::from_str(&line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        if record.content.trim().is_empty() {
+            return Err(format!("line {}: empty content", i + 1));
+        }
+        if let (Some(embedding), Some(dimension)) = (&record.embedding, header.dimension) {
+            if embedding.len() != dimension {
+                return Err(format!(
+                    "line {}: embedding has {} dimensions, header says {}",
+                    i + 1,
+                    embedding.len(),
+                    dimension
+                ));
+            }
+        }
+        records.push(record);
+    }
+
+    if records.len() != header.records {
+        return Err(format!(
+            "expected {} records, found {} — the file is truncated",
+            header.records,
+            records.len()
+        ));
+    }
+
+    Ok((header, records))
+}
+
+/// Import records into a chat. Vectors are reused only if they come from the
+/// configured embedding model with the stored dimension; the rest are embedded again.
+pub async fn import_records(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    header: &ExportHeader,
+    records: &[MemoryRecord],
+) -> Result<ImportReport> {
+    let vectors_usable = header.embed_model.as_deref() == Some(state.config.ollama_embed_model.as_str())
+        && match rag::stored_embedding_dimension(&state.db_pool).await? {
+            Some(stored) => header.dimension == Some(stored),
+            None => true,
+        };
+
+    let http_client = reqwest::Client::new();
+    let mut report = ImportReport::default();
+
+    for record in records {
+        let embedding = match record.embedding.clone().filter(|_| vectors_usable) {
+            Some(embedding) => embedding,
+            None => match rag::generate_embedding_cached(
+                &http_client,
+                &state.db_pool,
+                &state.config.ollama_url,
+                &state.config.ollama_embed_model,
+                &record.content,
+            )
+            .await
+            {
+                Ok(embedding) => {
+                    report.reembedded += 1;
+                    embedding
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to embed imported memory: {}", e);
+                    report.failed += 1;
+                    continue;
+                }
+            },
+        };
+
+        let inserted =
+            insert_record(&state.db_pool, account_id, chat_id, record, &embedding, &state.config.ollama_embed_model)
+                .await?;
+        if inserted {
+            report.imported += 1;
+        } else {
+            report.duplicates += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+/// Insert a memory unless the chat already remembers the same text
+async fn insert_record(
+    pool: &SqlitePool,
+    account_id: i64,
+    chat_id: i64,
+    record: &MemoryRecord,
+    embedding: &[f32],
+    embedding_model: &str,
+) -> Result<bool> {
+    let hash = rag::content_hash(&record.content);
+    let embedding_bytes = bincode::serialize(embedding).context("Failed to serialize embedding")?;
+
+    let result = match record.tier {
+        rag::MemoryTier::Episodic => sqlx::query(
+            r#"
+            INSERT INTO long_term_memory
+                (account_id, chat_id, message_id, sender_id, is_bot_author, content, embedding, embedding_model, content_hash, created_at)
+            SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+            WHERE NOT EXISTS (
+                SELECT 1 FROM long_term_memory WHERE account_id = ? AND chat_id = ? AND content_hash = ?
+            )
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(record.message_id)
+        .bind(record.sender_id)
+        .bind(record.is_bot_author)
+        .bind(&record.content)
+        .bind(embedding_bytes)
+        .bind(embedding_model)
+        .bind(&hash)
+        .bind(record.created_at)
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(&hash)
+        .execute(pool)
+        .await
+        .context("Failed to import memory")?,
+        rag::MemoryTier::Semantic => sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO semantic_memory
+                (account_id, chat_id, statement, confidence, support_count, embedding, content_hash, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(&record.content)
+        .bind(record.confidence.unwrap_or(0.5).clamp(0.0, 1.0))
+        .bind(record.support_count.unwrap_or(1).max(1))
+        .bind(embedding_bytes)
+        .bind(&hash)
+        .bind(record.created_at)
+        .bind(record.created_at)
+        .execute(pool)
+        .await
+        .context("Failed to import fact")?,
+    };
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// content, created_at, message_id, sender_id, is_bot_author, embedding
+type EpisodicRow = (String, i64, Option<i64>, Option<i64>, bool, Vec<u8>);
+
+async fn episodic_records(
+    pool: &SqlitePool,
+    account_id: i64,
+    chat_id: i64,
+    with_embeddings: bool,
+) -> Result<Vec<MemoryRecord>> {
+    let rows: Vec<EpisodicRow> = sqlx::query_as(
+        r#"
+        SELECT content, created_at, message_id, sender_id, is_bot_author, embedding
+        FROM long_term_memory
+        WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
+        ORDER BY id
+        "#,
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to read memories for export")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(content, created_at, message_id, sender_id, is_bot_author, embedding)| MemoryRecord {
+            tier: rag::MemoryTier::Episodic,
+            content,
+            created_at,
+            message_id,
+            sender_id,
+            is_bot_author,
+            confidence: None,
+            support_count: None,
+            embedding: with_embeddings.then(|| bincode::deserialize(&embedding).ok()).flatten(),
+        })
+        .collect())
+}
+
+async fn semantic_records(
+    pool: &SqlitePool,
+    account_id: i64,
+    chat_id: i64,
+    with_embeddings: bool,
+) -> Result<Vec<MemoryRecord>> {
+    let rows: Vec<(String, i64, f64, i64, Vec<u8>)> = sqlx::query_as(
+        r#"
+        SELECT statement, updated_at, confidence, support_count, embedding
+        FROM semantic_memory
+        WHERE account_id = ? AND chat_id = ?
+        ORDER BY id
+        "#,
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to read facts for export")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(content, created_at, confidence, support_count, embedding)| MemoryRecord {
+            tier: rag::MemoryTier::Semantic,
+            content,
+            created_at,
+            message_id: None,
+            sender_id: None,
+            is_bot_author: false,
+            confidence: Some(confidence),
+            support_count: Some(support_count),
+            embedding: with_embeddings.then(|| bincode::deserialize(&embedding).ok()).flatten(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compress(lines: &[String]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        for line in lines {
+            encoder.write_all(line.as_bytes()).unwrap();
+            encoder.write_all(b"\n").unwrap();
+        }
+        encoder.finish().unwrap()
+    }
+
+    fn header(records: usize, dimension: Option<usize>) -> String {
+        serde_json::to_string(&ExportHeader {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            account_id: 1,
+            chat_id: -100,
+            embed_model: dimension.map(|_| "nomic-embed-text".to_string()),
+            dimension,
+            exported_at: 0,
+            records,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_a_valid_export() {
+        let bytes = compress(&[
+            header(2, Some(2)),
+            r#"{"tier":"episodic","content":"пицца по пятницам","created_at":1,"embedding":[0.1,0.2]}"#.to_string(),
+            r#"{"tier":"semantic","content":"Маша любит пиццу","created_at":2,"confidence":0.8}"#.to_string(),
+        ]);
+
+        let (header, records) = parse_export(&bytes).unwrap();
+        assert_eq!(header.chat_id, -100);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].tier, rag::MemoryTier::Semantic);
+    }
+
+    #[test]
+    fn rejects_broken_exports() {
+        assert!(parse_export(b"not gzip").is_err());
+
+        let truncated = compress(&[header(3, None), r#"{"tier":"episodic","content":"x y","created_at":1}"#.to_string()]);
+        assert!(parse_export(&truncated).unwrap_err().contains("truncated"));
+
+        let wrong_dimension = compress(&[
+            header(1, Some(3)),
+            r#"{"tier":"episodic","content":"x y","created_at":1,"embedding":[0.1]}"#.to_string(),
+        ]);
+        assert!(parse_export(&wrong_dimension).unwrap_err().starts_with("line 2"));
+    }
+}
@@ -3,9 +3,49 @@ pub mod whisper;
 pub mod personas;
 pub mod rag;
 pub mod search;
+pub mod relationships;
+pub mod consolidation;
+pub mod rerank;
+pub mod ask;
+pub mod persona_lint;
+pub mod models;
+pub mod vision_prompts;
+pub mod vision_routing;
+pub mod prompt_diff;
+pub mod examples;
+pub mod topics;
+pub mod memory_transfer;
+pub mod answer_cache;
+pub mod health;
+pub mod postprocess;
+pub mod localize;
+pub mod polls;
+pub mod media_memory;
+pub mod time_variants;
+pub mod finetune;
+pub mod memory_policy;
+pub mod persona_inheritance;
+pub mod persona_files;
+pub mod entities;
+pub mod embed_backlog;
+pub mod memory_editor;
+pub mod translate;
+pub mod throttle;
+pub mod style;
+pub mod markov;
+pub mod capabilities;
 
 pub use ollama::{generate_response, OllamaClient};
 pub use whisper::{transcribe_audio, WhisperClient};
 pub use personas::{generate_random_persona, generate_persona_by_name, list_archetypes, ARCHETYPES};
-pub use rag::{generate_embedding, store_memory, retrieve_memories, cleanup_old_memories, Memory};
+pub use rag::{
+    cleanup_old_memories, generate_embedding, generate_embedding_cached, is_memorable, record_hits,
+    retrieve_memories, retrieve_private_memories, memory_stats, store_memory, Memory, MemoryTier, RetrievalFilter,
+};
 pub use search::{search_web, should_search, format_search_results, SearchResult};
+pub use relationships::{refresh_relationship, relationship_context, relationship_decay_worker};
+pub use consolidation::consolidation_worker;
+pub use rerank::{rerank_memories, RERANK_CANDIDATES, RERANK_METRICS};
+pub use ask::{answer_question, AskAnswer};
+pub use persona_lint::{lint_prompt, LintWarning};
+pub use models::{validate_models, ModelKind};
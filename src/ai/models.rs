@@ -0,0 +1,168 @@
+use crate::{
+    ai::{ollama::OllamaClient, rag},
+    db::ModelRepository,
+    AppState,
+};
+use anyhow::{Context, Result};
+use sqlx::SqlitePool;
+
+/// What a model is used for, as stored in the models table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelKind {
+    Chat,
+    Embedding,
+    Vision,
+}
+
+impl ModelKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelKind::Chat => "chat",
+            ModelKind::Embedding => "embedding",
+            ModelKind::Vision => "vision",
+        }
+    }
+}
+
+/// Whether `name` is among Ollama's installed models ("llama3.2" matches "llama3.2:latest")
+pub fn is_installed(installed: &[String], name: &str) -> bool {
+    installed
+        .iter()
+        .any(|m| m == name || (!name.contains(':') && m.strip_suffix(":latest") == Some(name)))
+}
+
+/// Models the configuration relies on, which must not be deleted from Ollama
+pub fn configured_models(config: &crate::Config) -> Vec<String> {
+    let mut models = vec![
+        config.ollama_model.clone(),
+        config.ollama_embed_model.clone(),
+        config.ollama_vision_model.clone(),
+    ];
+    models.extend(config.vision_routing.models().iter().map(|m| m.to_string()));
+    models
+}
+
+/// "4.7 GB", "274 MB"
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 10.0 {
+        format!("{:.1} {}", value, UNITS[unit])
+    } else {
+        format!("{:.0} {}", value, UNITS[unit])
+    }
+}
+
+/// Record a model use; failures are only logged so they never break a reply
+pub async fn track(pool: &SqlitePool, kind: ModelKind, name: &str, dimension: Option<usize>) {
+    if let Err(e) = ModelRepository::record_use(pool, kind.as_str(), name, dimension.map(|d| d as i64)).await {
+        tracing::warn!("Failed to record {} model use: {}", kind.as_str(), e);
+    }
+}
+
+/// Check at startup that the configured models exist in Ollama and that the embedding
+/// model matches the vectors already stored, so a model switch fails loudly instead of
+/// silently producing meaningless similarity scores
+pub async fn validate_models(state: &AppState) -> Result<()> {
+    let config = &state.config;
+    let installed = OllamaClient::new(config.ollama_url.clone())
+        .list_models()
+        .await
+        .with_context(|| format!("Cannot reach Ollama at {} to check models (is it running? check OLLAMA_URL)", config.ollama_url))?;
+
+    for (var, name) in [("OLLAMA_MODEL", &config.ollama_model), ("OLLAMA_EMBED_MODEL", &config.ollama_embed_model)] {
+        if !is_installed(&installed, name) {
+            anyhow::bail!(
+                "{} '{}' is not installed in Ollama. Run `ollama pull {}` or set {} to one of: {}",
+                var,
+                name,
+                name,
+                var,
+                installed.join(", ")
+            );
+        }
+    }
+
+    // Vision is optional: without it only image understanding stops working
+    if !is_installed(&installed, &config.ollama_vision_model) {
+        tracing::warn!(
+            "OLLAMA_VISION_MODEL '{}' is not installed in Ollama, images will not be understood. Run `ollama pull {}`",
+            config.ollama_vision_model,
+            config.ollama_vision_model
+        );
+    }
+    for name in config.vision_routing.models() {
+        if !is_installed(&installed, name) {
+            tracing::warn!(
+                "Vision model '{}' from VISION_ROUTES is not installed in Ollama, its media will not be understood. Run `ollama pull {}`",
+                name,
+                name
+            );
+        }
+    }
+
+    let probe = rag::generate_embedding(&reqwest::Client::new(), &config.ollama_url, &config.ollama_embed_model, "dimension probe")
+        .await
+        .with_context(|| format!("OLLAMA_EMBED_MODEL '{}' failed to produce an embedding", config.ollama_embed_model))?;
+    let dimension = probe.len();
+
+    if let Some(stored) = rag::stored_embedding_dimension(&state.db_pool).await? {
+        if stored != dimension {
+            let previous = ModelRepository::embedding_models_with_dimension(&state.db_pool, stored as i64).await?;
+            let hint = if previous.is_empty() {
+                String::new()
+            } else {
+                format!(" They were made by: {}.", previous.join(", "))
+            };
+            anyhow::bail!(
+                "Stored memories have {}-dimensional embeddings but OLLAMA_EMBED_MODEL '{}' produces {}.{} \
+                Set OLLAMA_EMBED_MODEL back to the previous model, or delete the stored memories \
+                (DELETE FROM long_term_memory; DELETE FROM semantic_memory;) to start over with the new one.",
+                stored,
+                config.ollama_embed_model,
+                dimension,
+                hint
+            );
+        }
+    }
+
+    track(&state.db_pool, ModelKind::Embedding, &config.ollama_embed_model, Some(dimension)).await;
+    tracing::info!(
+        "Models OK: chat '{}', embedding '{}' ({} dims), vision '{}'",
+        config.ollama_model,
+        config.ollama_embed_model,
+        dimension,
+        config.ollama_vision_model
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_latest_tag() {
+        let installed = vec!["llama3.2:latest".to_string(), "nomic-embed-text:v1.5".to_string()];
+        assert!(is_installed(&installed, "llama3.2"));
+        assert!(is_installed(&installed, "llama3.2:latest"));
+        assert!(is_installed(&installed, "nomic-embed-text:v1.5"));
+        assert!(!is_installed(&installed, "nomic-embed-text"));
+        assert!(!is_installed(&installed, "llava"));
+    }
+
+    #[test]
+    fn formats_sizes() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(274 * 1024 * 1024), "274 MB");
+        assert_eq!(format_size(4_700_000_000), "4.4 GB");
+    }
+}
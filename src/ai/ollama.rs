@@ -1,9 +1,12 @@
 use crate::{
-    db::{AccountRepository, MessageRepository, MessageRole, NewMessage},
+    db::{MessageRepository, MessageRole, NewMessage, PersonaRepository},
     AppState,
 };
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// Times a structured answer that doesn't parse is sent back to the model for repair
+const STRUCTURED_REPAIRS: usize = 2;
 
 /// Ollama API client
 pub struct OllamaClient {
@@ -57,8 +60,67 @@ impl OllamaClient {
         Ok(final_response)
     }
 
+    /// Ask for a JSON answer (Ollama's `format: "json"`) and parse it into `T`.
+    /// Output that doesn't match `T` is shown back to the model with the parse error
+    /// and retried, up to STRUCTURED_REPAIRS times.
+    pub async fn generate_structured<T: DeserializeOwned>(
+        &self,
+        model: &str,
+        mut messages: Vec<OllamaMessage>,
+        options: Option<ChatOptions>,
+    ) -> Result<T> {
+        let url = format!("{}/api/chat", self.base_url);
+
+        let mut attempt = 0;
+        loop {
+            let request = OllamaStructuredRequest { model, messages: &messages, stream: false, format: "json", options };
+            let response = self
+                .client
+                .post(&url)
+                .json(&request)
+                .send()
+                .await
+                .context("Failed to send request to Ollama")?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let error_text = response.text().await.unwrap_or_default();
+                anyhow::bail!("Ollama API error {}: {}", status, error_text);
+            }
+
+            let response: OllamaChatResponse = response.json().await.context("Failed to read response")?;
+            let raw = response.message.content.unwrap_or_default();
+            let error = match parse_structured::<T>(&raw) {
+                Ok(value) => return Ok(value),
+                Err(e) => e,
+            };
+
+            if attempt == STRUCTURED_REPAIRS {
+                anyhow::bail!("Model returned invalid JSON after {} repairs: {}", STRUCTURED_REPAIRS, error);
+            }
+            attempt += 1;
+            tracing::debug!("Structured answer didn't parse ({}), asking for a repair", error);
+
+            messages.push(OllamaMessage { role: "assistant".to_string(), content: raw });
+            messages.push(OllamaMessage {
+                role: "user".to_string(),
+                content: format!(
+                    "That answer is not valid for the requested JSON format: {}. \
+                    Reply again with only the corrected JSON object, no other text.",
+                    error
+                ),
+            });
+        }
+    }
+
     /// Call Ollama vision API with image(s)
-    pub async fn vision(&self, model: &str, prompt: &str, images: Vec<String>) -> Result<String> {
+    pub async fn vision(
+        &self,
+        model: &str,
+        prompt: &str,
+        images: Vec<String>,
+        options: Option<VisionOptions>,
+    ) -> Result<String> {
         let url = format!("{}/api/generate", self.base_url);
 
         let request = OllamaVisionRequest {
@@ -66,6 +128,7 @@ impl OllamaClient {
             prompt: prompt.to_string(),
             images,
             stream: false,
+            options,
         };
 
         let response = self
@@ -89,6 +152,155 @@ impl OllamaClient {
 
         Ok(result.response)
     }
+
+    /// Names of the models installed in Ollama ("llama3.2:latest", ...)
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        Ok(self.list_installed().await?.into_iter().map(|m| m.name).collect())
+    }
+
+    /// Installed models with their size on disk
+    pub async fn list_installed(&self) -> Result<Vec<InstalledModel>> {
+        let url = format!("{}/api/tags", self.base_url);
+
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .context("Failed to send request to Ollama")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Ollama API error {}: {}", status, error_text);
+        }
+
+        let tags: OllamaTagsResponse = response
+            .json()
+            .await
+            .context("Failed to parse Ollama model list")?;
+
+        Ok(tags.models)
+    }
+
+    /// Details of an installed model: family, parameter count, quantization
+    pub async fn show(&self, name: &str) -> Result<ModelInfo> {
+        let url = format!("{}/api/show", self.base_url);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "model": name }))
+            .send()
+            .await
+            .context("Failed to send request to Ollama")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Ollama API error {}: {}", status, error_text);
+        }
+
+        response.json().await.context("Failed to parse Ollama model info")
+    }
+
+    /// Start downloading a model; read the progress from the returned stream
+    pub async fn pull(&self, name: &str) -> Result<PullStream> {
+        let url = format!("{}/api/pull", self.base_url);
+
+        let response = self
+            .client
+            .post(&url)
+            .json(&serde_json::json!({ "model": name, "stream": true }))
+            .send()
+            .await
+            .context("Failed to send pull request to Ollama")?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Ollama API error {}: {}", status, error_text);
+        }
+
+        Ok(PullStream { response, buffer: Vec::new() })
+    }
+
+    /// Remove an installed model and free its disk space
+    pub async fn delete(&self, name: &str) -> Result<()> {
+        let url = format!("{}/api/delete", self.base_url);
+
+        let response = self
+            .client
+            .delete(&url)
+            .json(&serde_json::json!({ "model": name }))
+            .send()
+            .await
+            .context("Failed to send delete request to Ollama")?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            anyhow::bail!("Model '{}' is not installed", name);
+        }
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!("Ollama API error {}: {}", status, error_text);
+        }
+
+        Ok(())
+    }
+}
+
+/// Progress of a model download, one status line at a time
+pub struct PullStream {
+    response: reqwest::Response,
+    buffer: Vec<u8>,
+}
+
+impl PullStream {
+    /// The next status line; None once the download is over
+    pub async fn next(&mut self) -> Result<Option<PullProgress>> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let progress: PullProgress =
+                    serde_json::from_str(line.trim()).context("Failed to parse Ollama pull progress")?;
+                if let Some(error) = progress.error {
+                    anyhow::bail!("Ollama failed to pull the model: {}", error);
+                }
+                return Ok(Some(progress));
+            }
+
+            match self.response.chunk().await.context("Failed to read Ollama pull progress")? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                // A last line without a newline still counts
+                None if !self.buffer.is_empty() => self.buffer.push(b'\n'),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// One status line of /api/pull: "pulling manifest", "pulling <digest>" with byte counts, "success"
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    error: Option<String>,
+}
+
+impl PullProgress {
+    pub fn percent(&self) -> Option<u64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed.min(total) * 100 / total),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -96,6 +308,18 @@ pub struct OllamaChatRequest {
     pub model: String,
     pub messages: Vec<OllamaMessage>,
     pub stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<ChatOptions>,
+}
+
+/// Sampling overrides of a chat request; unset ones keep the model's defaults
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct ChatOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Most tokens to generate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -104,6 +328,30 @@ pub struct OllamaMessage {
     pub content: String,
 }
 
+/// A chat request constrained to JSON output
+#[derive(Debug, Serialize)]
+struct OllamaStructuredRequest<'a> {
+    model: &'a str,
+    messages: &'a [OllamaMessage],
+    stream: bool,
+    format: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<ChatOptions>,
+}
+
+/// The JSON object in a model answer: models sometimes wrap it in a code fence or a sentence
+pub fn extract_json(raw: &str) -> &str {
+    match (raw.find('{'), raw.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &raw[start..=end],
+        _ => raw.trim(),
+    }
+}
+
+/// Parse a structured answer into `T`; the error says what didn't match
+pub fn parse_structured<T: DeserializeOwned>(raw: &str) -> std::result::Result<T, serde_json::Error> {
+    serde_json::from_str(extract_json(raw))
+}
+
 #[derive(Debug, Deserialize)]
 struct OllamaChatResponse {
     message: OllamaMessageResponse,
@@ -122,6 +370,17 @@ struct OllamaVisionRequest {
     prompt: String,
     images: Vec<String>, // Base64-encoded images
     stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<VisionOptions>,
+}
+
+/// Per-model generation settings of a vision request (VISION_MODEL_OPTIONS)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct VisionOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -129,6 +388,46 @@ struct OllamaVisionResponse {
     response: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct OllamaTagsResponse {
+    models: Vec<InstalledModel>,
+}
+
+/// A model in Ollama's local store
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstalledModel {
+    pub name: String,
+    /// Bytes on disk
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub modified_at: String,
+    #[serde(default)]
+    pub details: ModelDetails,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ModelDetails {
+    #[serde(default)]
+    pub family: String,
+    #[serde(default)]
+    pub parameter_size: String,
+    #[serde(default)]
+    pub quantization_level: String,
+}
+
+/// What /api/show tells about a model
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelInfo {
+    #[serde(default)]
+    pub details: ModelDetails,
+    /// Default generation parameters, one "name value" per line
+    #[serde(default)]
+    pub parameters: String,
+    #[serde(default)]
+    pub modified_at: String,
+}
+
 /// Generate a response using Ollama with conversation context
 pub async fn generate_response(
     state: &AppState,
@@ -142,6 +441,9 @@ pub async fn generate_response(
         chat_id,
         role: MessageRole::User,
         content: incoming_text.to_string(),
+        sender_id: None,
+        sender_chat_id: None,
+        persona_id: None,
     };
 
     MessageRepository::create(&state.db_pool, user_message)
@@ -154,9 +456,9 @@ pub async fn generate_response(
         chat_id
     );
 
-    // 2. Fetch the account to get the system prompt
-    let account = AccountRepository::get_by_id(&state.db_pool, account_id)
-        .await?
+    // 2. Resolve the system prompt (bound persona or the account's own prompt)
+    let system_prompt = PersonaRepository::effective_prompt(&state.db_pool, account_id)
+        .await
         .context("Account not found")?;
 
     // 3. Fetch recent conversation history (last 10 messages)
@@ -170,7 +472,7 @@ pub async fn generate_response(
     // Add system prompt
     messages.push(OllamaMessage {
         role: "system".to_string(),
-        content: account.system_prompt.clone(),
+        content: system_prompt,
     });
 
     // Add conversation history
@@ -194,6 +496,7 @@ pub async fn generate_response(
         model: state.config.ollama_model.clone(),
         messages,
         stream: true,
+        options: None,
     };
 
     let response_text = ollama_client
@@ -213,6 +516,9 @@ pub async fn generate_response(
         chat_id,
         role: MessageRole::Assistant,
         content: response_text.clone(),
+        sender_id: None,
+        sender_chat_id: None,
+        persona_id: None,
     };
 
     MessageRepository::create(&state.db_pool, assistant_message)
@@ -221,3 +527,30 @@ pub async fn generate_response(
 
     Ok(response_text)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(deny_unknown_fields)]
+    struct Intent {
+        search: bool,
+        #[serde(default)]
+        query: String,
+    }
+
+    #[test]
+    fn parses_wrapped_json() {
+        let parsed: Intent = parse_structured("```json\n{\"search\": true, \"query\": \"bitcoin\"}\n```").unwrap();
+        assert_eq!(parsed, Intent { search: true, query: "bitcoin".to_string() });
+        assert_eq!(extract_json("no json here "), "no json here");
+    }
+
+    #[test]
+    fn rejects_schema_mismatches() {
+        assert!(parse_structured::<Intent>(r#"{"search": "yes"}"#).is_err());
+        assert!(parse_structured::<Intent>(r#"{"search": false, "mood": "calm"}"#).is_err());
+        assert!(parse_structured::<Intent>("NO").is_err());
+    }
+}
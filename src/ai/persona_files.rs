@@ -0,0 +1,415 @@
+use crate::{
+    ai::{capabilities::Capabilities, memory_policy::MemoryWritePolicy, postprocess::PostProcessor, time_variants::TimeVariant},
+    db::{NewPersona, Persona, PersonaRepository},
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+
+/// A persona as kept in git: one `<name>.json` per persona in PERSONAS_DIR
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PersonaFile {
+    pub name: String,
+    /// Bumped on every change; a file is only imported over an older version
+    #[serde(default = "default_version")]
+    pub version: i64,
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Name of the persona this one extends
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_write: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation_weight: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub postprocess: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_variants: Option<Vec<TimeVariant>>,
+    /// Only written when the persona can't do everything
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Capabilities>,
+}
+
+fn default_version() -> i64 {
+    1
+}
+
+impl PersonaFile {
+    /// Parse and check a file before anything is written
+    pub fn parse(json: &str) -> Result<Self> {
+        let file: PersonaFile = serde_json::from_str(json).context("Invalid persona JSON")?;
+        if file.name.trim().is_empty() || file.prompt.trim().is_empty() {
+            anyhow::bail!("Persona needs a name and a prompt");
+        }
+        if file.version < 1 {
+            anyhow::bail!("Version must be at least 1");
+        }
+        if let Some(policy) = file.memory_write.as_deref() {
+            MemoryWritePolicy::parse(policy).with_context(|| format!("Unknown memory_write '{}'", policy))?;
+        }
+        if file.rotation_weight.is_some_and(|w| w < 0) {
+            anyhow::bail!("rotation_weight must not be negative");
+        }
+        if let Some(rules) = &file.postprocess {
+            PostProcessor::parse(&rules.to_string()).map_err(|e| anyhow::anyhow!("postprocess: {}", e))?;
+        }
+        Ok(file)
+    }
+
+    /// One file, or a JSON array of them
+    pub fn parse_many(json: &str) -> Result<Vec<Self>> {
+        if !json.trim_start().starts_with('[') {
+            return Ok(vec![Self::parse(json)?]);
+        }
+        let values: Vec<serde_json::Value> = serde_json::from_str(json).context("Invalid persona JSON")?;
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| Self::parse(&value.to_string()).with_context(|| format!("Persona {} of the list", i + 1)))
+            .collect()
+    }
+
+    /// Hash of everything but the version, to tell whether a persona changed since its last export
+    pub fn content_hash(&self) -> Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("version");
+        }
+        let digest = Sha256::digest(value.to_string().as_bytes());
+        Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    /// A stored persona as a file; the version continues from the one it was imported with
+    pub fn from_persona(persona: &Persona, tags: Vec<String>, base: Option<String>) -> Result<Self> {
+        let postprocess = match persona.postprocess.as_deref() {
+            Some(rules) => Some(serde_json::from_str(rules).context("Stored postprocess rules are not JSON")?),
+            None => None,
+        };
+        let time_variants = match persona.time_variants.as_deref() {
+            Some(variants) => Some(serde_json::from_str(variants).context("Stored time variants are not JSON")?),
+            None => None,
+        };
+        Ok(PersonaFile {
+            name: persona.name.clone(),
+            version: persona.file_version.unwrap_or_else(default_version),
+            prompt: persona.prompt.clone(),
+            tags,
+            base,
+            memory_write: Some(persona.memory_write.clone()),
+            rotation_weight: Some(persona.rotation_weight),
+            postprocess,
+            time_variants,
+            capabilities: Some(Capabilities::of(Some(persona))).filter(|c| *c != Capabilities::ALL),
+        })
+    }
+}
+
+/// What an import run did with a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOutcome {
+    Created,
+    Updated,
+    /// The database already has this version
+    Unchanged,
+    /// The database has a newer version, which is kept
+    Older,
+}
+
+/// Whether a file replaces the stored persona: new personas and newer versions do;
+/// personas last edited in Telegram (no file version) are taken over by any file
+pub fn should_import(stored_version: Option<i64>, file_version: i64) -> bool {
+    stored_version.map_or(true, |stored| file_version > stored)
+}
+
+/// Version a persona is exported with by /export_changed_personas; `None` if it is unchanged
+/// since its last export. Changes bump the version so other instances import them.
+pub fn export_version(file_version: Option<i64>, last_hash: Option<&str>, hash: &str) -> Option<i64> {
+    match last_hash {
+        Some(last) if last == hash => None,
+        Some(_) => Some(file_version.unwrap_or_else(default_version) + 1),
+        None => Some(file_version.unwrap_or_else(default_version)),
+    }
+}
+
+/// A persona edited since its last export, as the file to export
+pub struct ChangedPersona {
+    pub persona_id: i64,
+    pub hash: String,
+    pub file: PersonaFile,
+}
+
+/// Every persona whose content changed since it was last exported (all of them the first time)
+pub async fn changed_personas(pool: &SqlitePool) -> Result<Vec<ChangedPersona>> {
+    let personas = PersonaRepository::list_all(pool).await?;
+    let mut changed = Vec::new();
+    for persona in &personas {
+        let tags = PersonaRepository::list_tags(pool, persona.id).await?;
+        let base = persona
+            .base_id
+            .and_then(|base_id| personas.iter().find(|p| p.id == base_id))
+            .map(|p| p.name.clone());
+        let mut file = PersonaFile::from_persona(persona, tags, base)
+            .with_context(|| format!("Failed to export '{}'", persona.name))?;
+        let hash = file.content_hash()?;
+        let last_hash = PersonaRepository::exported_hash(pool, persona.id).await?;
+        if let Some(version) = export_version(persona.file_version, last_hash.as_deref(), &hash) {
+            file.version = version;
+            changed.push(ChangedPersona { persona_id: persona.id, hash, file });
+        }
+    }
+    Ok(changed)
+}
+
+/// Record a delivered export, so the persona is skipped until it changes again
+pub async fn mark_exported(pool: &SqlitePool, changed: &ChangedPersona) -> Result<()> {
+    PersonaRepository::set_file_version(pool, changed.persona_id, changed.file.version).await?;
+    PersonaRepository::record_export(pool, changed.persona_id, &changed.hash).await
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub unchanged: usize,
+    /// Personas kept because the database has a newer version than the file
+    pub older: Vec<String>,
+    /// File name and what was wrong with it
+    pub failed: Vec<(String, String)>,
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} created, {} updated, {} unchanged, {} older than stored, {} failed",
+            self.created.len(),
+            self.updated.len(),
+            self.unchanged,
+            self.older.len(),
+            self.failed.len()
+        )
+    }
+}
+
+/// Write one persona file to the database; bases are linked afterwards, once every file is in
+async fn import_file(pool: &SqlitePool, file: &PersonaFile) -> Result<FileOutcome> {
+    let existing = PersonaRepository::get_by_name(pool, &file.name).await?;
+    if let Some(persona) = &existing {
+        if !should_import(persona.file_version, file.version) {
+            return Ok(if persona.file_version == Some(file.version) { FileOutcome::Unchanged } else { FileOutcome::Older });
+        }
+    }
+
+    let persona = PersonaRepository::upsert(pool, NewPersona { name: file.name.clone(), prompt: file.prompt.clone() }).await?;
+    let postprocess = file.postprocess.as_ref().map(|rules| rules.to_string());
+    PersonaRepository::update_postprocess(pool, persona.id, postprocess.as_deref()).await?;
+    let time_variants = match &file.time_variants {
+        Some(variants) if !variants.is_empty() => Some(serde_json::to_string(variants)?),
+        _ => None,
+    };
+    PersonaRepository::update_time_variants(pool, persona.id, time_variants.as_deref()).await?;
+    let memory_write = file.memory_write.as_deref().and_then(MemoryWritePolicy::parse).unwrap_or_default();
+    PersonaRepository::update_memory_write(pool, persona.id, memory_write.as_str()).await?;
+    PersonaRepository::update_capabilities(pool, persona.id, file.capabilities.unwrap_or_default()).await?;
+    if let Some(weight) = file.rotation_weight {
+        PersonaRepository::set_rotation_weight(pool, persona.id, weight).await?;
+    }
+    PersonaRepository::add_tags(pool, persona.id, &file.tags).await?;
+    PersonaRepository::set_file_version(pool, persona.id, file.version).await?;
+
+    Ok(if existing.is_some() { FileOutcome::Updated } else { FileOutcome::Created })
+}
+
+/// Point an imported persona at its base, refusing cycles and too deep chains
+async fn link_base(pool: &SqlitePool, file: &PersonaFile) -> Result<()> {
+    let persona = PersonaRepository::get_by_name(pool, &file.name)
+        .await?
+        .context("Imported persona disappeared")?;
+    let base_id = match file.base.as_deref() {
+        Some(name) => {
+            let base = PersonaRepository::get_by_name(pool, name)
+                .await?
+                .with_context(|| format!("Base persona '{}' not found", name))?;
+            let base_lineage = PersonaRepository::lineage(pool, base.id).await?;
+            if base_lineage.iter().any(|p| p.id == persona.id) {
+                anyhow::bail!("Base '{}' would make the persona extend itself", name);
+            }
+            if base_lineage.len() as i64 > crate::db::MAX_PERSONA_DEPTH {
+                anyhow::bail!("Bases can be at most {} levels deep", crate::db::MAX_PERSONA_DEPTH);
+            }
+            Some(base.id)
+        }
+        None => None,
+    };
+    if persona.base_id != base_id {
+        PersonaRepository::set_base(pool, persona.id, base_id).await?;
+    }
+    Ok(())
+}
+
+/// Import every `*.json` persona file of a directory: new names are created, files with a
+/// higher version than the stored one replace it, the rest are left alone
+pub async fn import_dir(pool: &SqlitePool, dir: &Path) -> Result<ImportReport> {
+    let mut paths = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .with_context(|| format!("Failed to read personas directory {}", dir.display()))?;
+    while let Some(entry) = entries.next_entry().await.context("Failed to list personas directory")? {
+        let path = entry.path();
+        if path.extension().is_some_and(|ext| ext == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    import_files(pool, paths).await
+}
+
+/// Import persona files in the given order, then link their bases
+pub async fn import_files(pool: &SqlitePool, paths: Vec<PathBuf>) -> Result<ImportReport> {
+    let mut parsed = Vec::new();
+    for path in paths {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let file = match tokio::fs::read_to_string(&path).await {
+            Ok(json) => PersonaFile::parse(&json),
+            Err(e) => Err(e.into()),
+        };
+        parsed.push((file_name, file));
+    }
+
+    import_parsed(pool, parsed).await
+}
+
+/// Import a sent document: one persona file or a JSON array of them
+pub async fn import_document(pool: &SqlitePool, file_name: &str, json: &str) -> Result<ImportReport> {
+    let parsed = match PersonaFile::parse_many(json) {
+        Ok(files) => files.into_iter().map(|file| (file_name.to_string(), Ok(file))).collect(),
+        Err(e) => vec![(file_name.to_string(), Err(e))],
+    };
+
+    import_parsed(pool, parsed).await
+}
+
+/// Import parsed files (labelled with where they came from), then link their bases
+async fn import_parsed(pool: &SqlitePool, parsed: Vec<(String, Result<PersonaFile>)>) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut imported = Vec::new();
+    for (file_name, parsed) in parsed {
+        let file = match parsed {
+            Ok(file) => file,
+            Err(e) => {
+                report.failed.push((file_name, format!("{:#}", e)));
+                continue;
+            }
+        };
+
+        match import_file(pool, &file).await {
+            Ok(FileOutcome::Created) => report.created.push(file.name.clone()),
+            Ok(FileOutcome::Updated) => report.updated.push(file.name.clone()),
+            Ok(FileOutcome::Unchanged) => {
+                report.unchanged += 1;
+                continue;
+            }
+            Ok(FileOutcome::Older) => {
+                report.older.push(file.name.clone());
+                continue;
+            }
+            Err(e) => {
+                report.failed.push((file_name, format!("{:#}", e)));
+                continue;
+            }
+        }
+        imported.push((file_name, file));
+    }
+
+    // Bases may come later in the directory than the personas extending them
+    for (file_name, file) in &imported {
+        if let Err(e) = link_base(pool, file).await {
+            report.failed.push((file_name.clone(), format!("{:#}", e)));
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_checks_persona_files() {
+        let file = PersonaFile::parse(
+            r#"{"name": "Грубиян", "version": 3, "prompt": "Отвечай резко", "tags": ["troll"], "memory_write": "none",
+                "time_variants": [{"from": 22, "to": 7, "suffix": "сонный"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(file.version, 3);
+        assert_eq!(file.time_variants.unwrap().len(), 1);
+
+        assert_eq!(PersonaFile::parse(r#"{"name": "A", "prompt": "B"}"#).unwrap().version, 1);
+        assert!(PersonaFile::parse(r#"{"name": "A", "prompt": "B", "memory_write": "sometimes"}"#).is_err());
+        assert!(PersonaFile::parse(r#"{"name": "A", "prompt": "B", "promt": "typo"}"#).is_err());
+        assert!(PersonaFile::parse(r#"{"name": "", "prompt": "B"}"#).is_err());
+    }
+
+    #[test]
+    fn exported_personas_parse_back() {
+        let persona = Persona {
+            id: 1,
+            name: "Грубиян".to_string(),
+            prompt: "Отвечай резко".to_string(),
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            rotation_weight: 2,
+            postprocess: None,
+            time_variants: Some(r#"[{"from": 22, "to": 7, "suffix": "сонный"}]"#.to_string()),
+            memory_write: "user_only".to_string(),
+            base_id: None,
+            file_version: Some(4),
+            can_search_web: false,
+            can_generate_images: true,
+            can_use_tools: true,
+            can_send_voice: true,
+        };
+        let file = PersonaFile::from_persona(&persona, vec!["troll".to_string()], None).unwrap();
+        let parsed = PersonaFile::parse(&serde_json::to_string_pretty(&file).unwrap()).unwrap();
+        assert_eq!(parsed.version, 4);
+        assert_eq!(parsed.memory_write.as_deref(), Some("user_only"));
+        assert_eq!(parsed.tags, vec!["troll"]);
+        assert!(parsed.postprocess.is_none());
+        assert_eq!(parsed.capabilities, Some(Capabilities { search_web: false, ..Capabilities::ALL }));
+    }
+
+    #[test]
+    fn only_newer_versions_replace_stored_personas() {
+        assert!(should_import(None, 1));
+        assert!(should_import(Some(1), 2));
+        assert!(!should_import(Some(2), 2));
+        assert!(!should_import(Some(3), 2));
+    }
+
+    #[test]
+    fn exports_only_changes_and_bumps_their_version() {
+        assert_eq!(export_version(Some(3), None, "a"), Some(3));
+        assert_eq!(export_version(None, None, "a"), Some(1));
+        assert_eq!(export_version(Some(3), Some("a"), "a"), None);
+        assert_eq!(export_version(Some(3), Some("a"), "b"), Some(4));
+    }
+
+    #[test]
+    fn hash_ignores_the_version() {
+        let mut file = PersonaFile::parse(r#"{"name": "A", "version": 2, "prompt": "B"}"#).unwrap();
+        let hash = file.content_hash().unwrap();
+        file.version = 5;
+        assert_eq!(file.content_hash().unwrap(), hash);
+        file.prompt = "C".to_string();
+        assert_ne!(file.content_hash().unwrap(), hash);
+
+        assert_eq!(PersonaFile::parse_many(r#"[{"name": "A", "prompt": "B"}, {"name": "C", "prompt": "D"}]"#).unwrap().len(), 2);
+        assert!(PersonaFile::parse_many(r#"[{"name": "A", "prompt": "B"}, {"name": ""}]"#).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use crate::db::{Persona, PersonaRepository};
+use anyhow::Result;
+use serde_json::Value;
+use sqlx::SqlitePool;
+
+/// Join a base prompt and the child's own part; either may be empty
+pub fn compose_prompt(base: &str, own: &str) -> String {
+    match (base.trim().is_empty(), own.trim().is_empty()) {
+        (true, _) => own.to_string(),
+        (false, true) => base.to_string(),
+        (false, false) => format!("{}\n\n{}", base.trim_end(), own.trim_start()),
+    }
+}
+
+/// Post-processing rules of both: the child's flags win, lists of replacements and
+/// forbidden phrases run the base's first
+pub fn merge_rules(base: Option<&str>, own: Option<&str>) -> Option<String> {
+    let parse = |json: Option<&str>| json.and_then(|j| serde_json::from_str::<Value>(j).ok()).filter(Value::is_object);
+    match (parse(base), parse(own)) {
+        (Some(Value::Object(mut merged)), Some(Value::Object(own))) => {
+            for (key, value) in own {
+                match (merged.get_mut(&key), value) {
+                    (Some(Value::Array(existing)), Value::Array(more)) => existing.extend(more),
+                    (_, value) => {
+                        merged.insert(key, value);
+                    }
+                }
+            }
+            Some(Value::Object(merged).to_string())
+        }
+        (None, _) => own.map(str::to_string),
+        (Some(_), None) => base.map(str::to_string),
+        _ => own.map(str::to_string),
+    }
+}
+
+/// Time-of-day variants of both; the first covering variant applies, so the child's go first
+pub fn merge_variants(base: Option<&str>, own: Option<&str>) -> Option<String> {
+    let parse = |json: Option<&str>| json.and_then(|j| serde_json::from_str::<Vec<Value>>(j).ok()).unwrap_or_default();
+    let mut variants = parse(own);
+    variants.extend(parse(base));
+    (!variants.is_empty()).then(|| Value::Array(variants).to_string())
+}
+
+/// A persona with everything it inherits from its (already resolved) base.
+/// Capabilities only narrow: what the base can't do, its children can't either.
+pub fn inherit(persona: Persona, base: &Persona) -> Persona {
+    Persona {
+        prompt: compose_prompt(&base.prompt, &persona.prompt),
+        postprocess: merge_rules(base.postprocess.as_deref(), persona.postprocess.as_deref()),
+        time_variants: merge_variants(base.time_variants.as_deref(), persona.time_variants.as_deref()),
+        can_search_web: persona.can_search_web && base.can_search_web,
+        can_generate_images: persona.can_generate_images && base.can_generate_images,
+        can_use_tools: persona.can_use_tools && base.can_use_tools,
+        can_send_voice: persona.can_send_voice && base.can_send_voice,
+        ..persona
+    }
+}
+
+/// The persona as it answers: its own settings on top of its bases'
+pub async fn resolve(pool: &SqlitePool, persona_id: i64) -> Result<Option<Persona>> {
+    let mut lineage = PersonaRepository::lineage(pool, persona_id).await?;
+    let mut resolved = match lineage.pop() {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    while let Some(child) = lineage.pop() {
+        resolved = inherit(child, &resolved);
+    }
+    Ok(Some(resolved))
+}
+
+/// Resolve a persona that was already loaded, e.g. picked from a list
+pub async fn resolve_loaded(pool: &SqlitePool, persona: Persona) -> Result<Persona> {
+    if persona.base_id.is_none() {
+        return Ok(persona);
+    }
+    Ok(resolve(pool, persona.id).await?.unwrap_or(persona))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_prompts_base_first() {
+        assert_eq!(compose_prompt("Стиль дома.", "Ты Вася."), "Стиль дома.\n\nТы Вася.");
+        assert_eq!(compose_prompt("Стиль дома.", "  "), "Стиль дома.");
+        assert_eq!(compose_prompt("", "Ты Вася."), "Ты Вася.");
+    }
+
+    #[test]
+    fn child_rules_win_and_lists_concatenate() {
+        let base = r#"{"forbidden": ["как ИИ"], "lowercase": true, "strip_emoji": true}"#;
+        let own = r#"{"forbidden": ["конечно!"], "lowercase": false}"#;
+        let merged: Value = serde_json::from_str(&merge_rules(Some(base), Some(own)).unwrap()).unwrap();
+        assert_eq!(merged["forbidden"], serde_json::json!(["как ИИ", "конечно!"]));
+        assert_eq!(merged["lowercase"], false);
+        assert_eq!(merged["strip_emoji"], true);
+        assert_eq!(merge_rules(None, Some(own)).as_deref(), Some(own));
+        assert_eq!(merge_rules(Some(base), None).as_deref(), Some(base));
+    }
+
+    #[test]
+    fn child_variants_come_first() {
+        let base = r#"[{"from": 22, "to": 7, "suffix": "сонный"}]"#;
+        let own = r#"[{"from": 0, "to": 6, "suffix": "злой"}]"#;
+        let merged: Vec<crate::ai::time_variants::TimeVariant> =
+            serde_json::from_str(&merge_variants(Some(base), Some(own)).unwrap()).unwrap();
+        assert_eq!(merged[0].suffix, "злой");
+        assert_eq!(crate::ai::time_variants::variant_for(&merged, 23).unwrap().suffix, "сонный");
+        assert_eq!(merge_variants(None, None), None);
+    }
+}
@@ -0,0 +1,170 @@
+use regex::Regex;
+
+/// Prompts above this estimated size crowd out history and memories in the context window
+pub const TOKEN_BUDGET: usize = 1500;
+
+/// Prefix that saves a prompt despite lint warnings
+pub const FORCE_PREFIX: char = '!';
+
+/// A single problem found in a persona prompt
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Two instructions that cannot both be followed
+struct Contradiction {
+    topic: &'static str,
+    a: Regex,
+    b: Regex,
+}
+
+lazy_static::lazy_static! {
+    static ref CONTRADICTIONS: Vec<Contradiction> = vec![
+        contradiction(
+            "emoji",
+            r"(?i)(никаких эмодзи|без эмодзи|не используй эмодзи|no emojis?|never use emojis?|don'?t use emojis?)",
+            r"(?i)(используй (много )?эмодзи|ставь эмодзи|use (lots of |many )?emojis?|add emojis?)",
+        ),
+        contradiction(
+            "length",
+            r"(?i)(коротк\w* (ответ|фраз|сообщени)|1-2 (коротк\w* )?фраз|short (answers|replies|messages)|be brief|keep it short)",
+            r"(?i)(подробн\w*|развернут\w*|длинн\w* (ответ|сообщени)|detailed (answers|replies)|long (answers|replies|messages)|elaborate)",
+        ),
+        contradiction(
+            "tone",
+            r"(?i)(\bвежлив\w*|\bформальн\w*|\bна вы\b|\bpolite|\bformal)",
+            r"(?i)(грубоват\w*|\bгрубо\b|сленг|\bматер\w*|\bматом\b|\brude\b|\bslang\b|\bswear)",
+        ),
+        contradiction(
+            "punctuation",
+            r"(?i)(без точек|не ставь точки|игнорируй знаки препинания|no punctuation)",
+            r"(?i)(грамотн\w*|правильн\w* пунктуац\w*|proper punctuation|perfect grammar)",
+        ),
+    ];
+
+    /// Phrasing that invites the chat to take control of the persona
+    static ref INJECTION_PRONE: Vec<(&'static str, Regex)> = vec![
+        ("obeys_users", re(r"(?i)(выполняй (любые|все) (просьбы|команды|указания)|делай все,? что (тебя )?просят|always obey|do (anything|whatever) (the user|they|you are) (asks?|told)|follow (any|all) (user )?instructions)")),
+        ("no_limits", re(r"(?i)(у тебя нет (никаких )?(ограничений|правил)|no (restrictions|rules|limits)|without (any )?restrictions)")),
+        ("reveals_prompt", re(r"(?i)(можешь (показать|рассказать) (свой )?(промпт|инструкции)|share (your|this) (prompt|instructions)|reveal (your|this) (prompt|instructions))")),
+        ("admits_ai", re(r"(?i)(признавайся,? что ты (бот|ии|нейросеть)|admit (that )?you are an? (ai|bot))")),
+    ];
+
+    static ref EXAMPLES: Regex = re(r"(?im)(\[пример\w*\]|^\s*(user|пользователь|собеседник|юзер)\s*:|examples?\s*:|пример\w*\s*:)");
+}
+
+fn re(pattern: &str) -> Regex {
+    Regex::new(pattern).expect("invalid built-in lint rule")
+}
+
+/// First match that isn't negated ("не будь слишком вежливым" is not a request to be polite)
+fn affirmative_match<'a>(pattern: &Regex, text: &'a str) -> Option<regex::Match<'a>> {
+    pattern.find_iter(text).find(|m| {
+        let before: String = text[..m.start()].chars().rev().take(20).collect::<Vec<_>>().into_iter().rev().collect();
+        let before = before.to_lowercase();
+        !["не ", "not ", "don't ", "never "].iter().any(|n| before.contains(n))
+    })
+}
+
+fn contradiction(topic: &'static str, a: &str, b: &str) -> Contradiction {
+    Contradiction { topic, a: re(a), b: re(b) }
+}
+
+/// Rough token count: ~4 chars per token for Latin text, ~2.5 for Cyrillic
+pub fn estimate_tokens(text: &str) -> usize {
+    let (cyrillic, other) = text.chars().fold((0usize, 0usize), |(c, o), ch| {
+        if ('\u{0400}'..='\u{04FF}').contains(&ch) { (c + 1, o) } else { (c, o + 1) }
+    });
+    other / 4 + cyrillic * 2 / 5
+}
+
+/// Check a persona prompt for common problems. An empty result means it looks fine.
+pub fn lint_prompt(prompt: &str) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    let tokens = estimate_tokens(prompt);
+    if tokens > TOKEN_BUDGET {
+        warnings.push(LintWarning {
+            code: "too_long",
+            message: format!("~{} tokens, over the {} token budget", tokens, TOKEN_BUDGET),
+        });
+    }
+
+    for c in CONTRADICTIONS.iter() {
+        if let (Some(a), Some(b)) = (affirmative_match(&c.a, prompt), affirmative_match(&c.b, prompt)) {
+            warnings.push(LintWarning {
+                code: "contradiction",
+                message: format!("contradictory {} instructions: \"{}\" vs \"{}\"", c.topic, a.as_str(), b.as_str()),
+            });
+        }
+    }
+
+    if !EXAMPLES.is_match(prompt) {
+        warnings.push(LintWarning {
+            code: "no_examples",
+            message: "no style examples (add a few \"User: ... / reply\" lines)".to_string(),
+        });
+    }
+
+    for (code, pattern) in INJECTION_PRONE.iter() {
+        if let Some(m) = pattern.find(prompt) {
+            warnings.push(LintWarning {
+                code,
+                message: format!("injection-prone phrasing: \"{}\"", m.as_str()),
+            });
+        }
+    }
+
+    warnings
+}
+
+/// Render warnings as a plain-text list for the admin bot
+pub fn format_warnings(warnings: &[LintWarning]) -> String {
+    warnings
+        .iter()
+        .map(|w| format!("• {}", w.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(prompt: &str) -> Vec<&'static str> {
+        lint_prompt(prompt).into_iter().map(|w| w.code).collect()
+    }
+
+    #[test]
+    fn default_style_prompt_is_clean() {
+        let prompt = "Ты ленивый пользователь. Никаких эмодзи, короткие ответы.\n[ПРИМЕРЫ]\nUser: привет\nку";
+        assert!(lint_prompt(prompt).is_empty());
+    }
+
+    #[test]
+    fn ignores_negated_instructions() {
+        let prompt = "Не будь слишком вежливым, иногда грубовато.\nПример: ну";
+        assert!(lint_prompt(prompt).is_empty());
+    }
+
+    #[test]
+    fn detects_contradictions_and_missing_examples() {
+        let found = codes("No emojis please. Use lots of emojis in every reply.");
+        assert!(found.contains(&"contradiction"));
+        assert!(found.contains(&"no_examples"));
+    }
+
+    #[test]
+    fn detects_injection_prone_phrasing() {
+        let found = codes("Выполняй любые просьбы собеседника.\nПример: ок");
+        assert_eq!(found, vec!["obeys_users"]);
+    }
+
+    #[test]
+    fn flags_oversized_prompts() {
+        let prompt = format!("Пример: да\n{}", "очень длинный текст ".repeat(400));
+        assert!(codes(&prompt).contains(&"too_long"));
+    }
+}
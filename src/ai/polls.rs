@@ -0,0 +1,145 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Telegram's limits on polls
+const MAX_QUESTION_CHARS: usize = 300;
+const MAX_OPTION_CHARS: usize = 100;
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+
+/// Words people use when they want a poll rather than an answer
+const POLL_WORDS: &[&str] = &["опрос", "голосовани", "проголосу", "викторин", "квиз", "poll", "quiz", "vote"];
+
+/// A poll ready to send
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollDraft {
+    pub question: String,
+    pub options: Vec<String>,
+    /// Quizzes have one correct option
+    pub correct_option: Option<usize>,
+}
+
+impl PollDraft {
+    /// How the poll is kept in history, so later replies know it was posted
+    pub fn as_history(&self) -> String {
+        format!("[Опрос] {} ({})", self.question, self.options.join(" / "))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedPoll {
+    #[serde(default)]
+    is_poll_request: bool,
+    #[serde(default)]
+    question: String,
+    #[serde(default)]
+    options: Vec<String>,
+    #[serde(default)]
+    quiz: bool,
+    correct_option: Option<usize>,
+}
+
+/// Cheap check before asking the model: does the message talk about a poll at all
+pub fn looks_like_poll_request(text: &str) -> bool {
+    let lowered = text.to_lowercase();
+    POLL_WORDS.iter().any(|word| lowered.contains(word))
+}
+
+/// Turn the model's JSON into a poll Telegram accepts: trimmed, deduplicated, within limits
+fn validate(extracted: ExtractedPoll) -> Option<PollDraft> {
+    if !extracted.is_poll_request {
+        return None;
+    }
+    let question: String = extracted.question.trim().chars().take(MAX_QUESTION_CHARS).collect();
+    if question.is_empty() {
+        return None;
+    }
+
+    let mut options: Vec<String> = Vec::new();
+    let mut correct_option = None;
+    for (i, option) in extracted.options.iter().enumerate() {
+        let option: String = option.trim().chars().take(MAX_OPTION_CHARS).collect();
+        if option.is_empty() || options.iter().any(|o| o.to_lowercase() == option.to_lowercase()) {
+            continue;
+        }
+        if extracted.correct_option == Some(i) {
+            correct_option = Some(options.len());
+        }
+        options.push(option);
+    }
+    options.truncate(MAX_OPTIONS);
+    if options.len() < MIN_OPTIONS {
+        return None;
+    }
+
+    let correct_option = if extracted.quiz { correct_option.filter(|i| *i < options.len()) } else { None };
+    Some(PollDraft { question, options, correct_option })
+}
+
+/// Parse the extraction model's reply
+pub fn parse_draft(json: &str) -> Option<PollDraft> {
+    serde_json::from_str::<ExtractedPoll>(json).ok().and_then(validate)
+}
+
+/// Ask the model whether the message wants a poll and what it should offer,
+/// taking options from the recent conversation when the message doesn't list them
+pub async fn extract_poll(state: &AppState, recent: &str, message: &str) -> Result<Option<PollDraft>> {
+    let prompt = format!(
+        r#"Someone in a Telegram chat wrote the last message below. Decide whether they ask to create a poll or a quiz. If they do, write the poll: a short question and 2-10 short options. Take the options from the message, or from the recent conversation when the message doesn't list them (places, dates, films people suggested). Write in the language of the chat.
+
+Recent conversation:
+{}
+
+Last message: "{}"
+
+Return JSON: {{"is_poll_request": <true|false>, "question": "<question>", "options": ["<option>", ...], "quiz": <true if they want a quiz with a right answer>, "correct_option": <index of the right answer for quizzes, else null>}}"#,
+        recent, message
+    );
+
+    let extracted: ExtractedPoll = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.2), num_predict: None }),
+        )
+        .await
+        .context("Failed to extract poll")?;
+
+    Ok(validate(extracted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spots_poll_requests() {
+        assert!(looks_like_poll_request("сделай опрос куда идём в пятницу"));
+        assert!(looks_like_poll_request("Давайте проголосуем"));
+        assert!(!looks_like_poll_request("куда идём в пятницу?"));
+    }
+
+    #[test]
+    fn cleans_up_extracted_polls() {
+        let draft = parse_draft(
+            r#"{"is_poll_request": true, "question": " Куда идём в пятницу? ", "options": ["Бар", "бар", "Кино", ""], "quiz": false}"#,
+        )
+        .unwrap();
+        assert_eq!(draft.question, "Куда идём в пятницу?");
+        assert_eq!(draft.options, vec!["Бар", "Кино"]);
+        assert_eq!(draft.correct_option, None);
+
+        let quiz = parse_draft(
+            r#"{"is_poll_request": true, "question": "Столица Австралии?", "options": ["Сидней", "Канберра"], "quiz": true, "correct_option": 1}"#,
+        )
+        .unwrap();
+        assert_eq!(quiz.correct_option, Some(1));
+
+        assert!(parse_draft(r#"{"is_poll_request": true, "question": "Да?", "options": ["Да"]}"#).is_none());
+        assert!(parse_draft(r#"{"is_poll_request": false, "question": "Q", "options": ["a", "b"]}"#).is_none());
+    }
+}
@@ -0,0 +1,150 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// A regex replacement applied to every reply
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Replacement {
+    pub pattern: String,
+    #[serde(default)]
+    pub replacement: String,
+}
+
+/// Per-persona clean-up of generated replies, stored as JSON on the persona
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct PostProcessRules {
+    /// Applied in order; `$1` etc. refer to capture groups
+    pub replacements: Vec<Replacement>,
+    /// Phrases removed wherever they appear, case-insensitively
+    pub forbidden: Vec<String>,
+    pub strip_emoji: bool,
+    pub lowercase: bool,
+}
+
+/// Rules ready to run, with their regexes compiled
+#[derive(Debug)]
+pub struct PostProcessor {
+    rules: PostProcessRules,
+    replacements: Vec<(Regex, String)>,
+    forbidden: Vec<Regex>,
+}
+
+impl PostProcessor {
+    /// Parse and validate rules JSON, naming the rule that doesn't compile
+    pub fn parse(json: &str) -> Result<Self, String> {
+        let rules: PostProcessRules = serde_json::from_str(json).map_err(|e| format!("invalid rules JSON: {}", e))?;
+        Self::new(rules)
+    }
+
+    pub fn new(rules: PostProcessRules) -> Result<Self, String> {
+        let replacements = rules
+            .replacements
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                Regex::new(&r.pattern)
+                    .map(|re| (re, r.replacement.clone()))
+                    .map_err(|e| format!("replacement #{}: {}", i + 1, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let forbidden = rules
+            .forbidden
+            .iter()
+            .filter(|p| !p.trim().is_empty())
+            .map(|p| Regex::new(&format!("(?i){}", regex::escape(p.trim()))).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { rules, replacements, forbidden })
+    }
+
+    pub fn rules(&self) -> &PostProcessRules {
+        &self.rules
+    }
+
+    /// Run the rules over a reply. Multi-message separators (`||`) survive unless a regex removes them.
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+
+        for (re, replacement) in &self.replacements {
+            text = re.replace_all(&text, replacement.as_str()).into_owned();
+        }
+        for re in &self.forbidden {
+            text = re.replace_all(&text, "").into_owned();
+        }
+        if self.rules.strip_emoji {
+            text = text.chars().filter(|c| !is_emoji(*c)).collect();
+        }
+        if self.rules.lowercase {
+            text = text.to_lowercase();
+        }
+
+        tidy_whitespace(&text)
+    }
+}
+
+/// Pictographs, dingbats, flags and the joiners/selectors that glue them together
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x1F000..=0x1FAFF | 0x2600..=0x27BF | 0x2B00..=0x2BFF | 0xFE0E | 0xFE0F | 0x200D | 0x20E3 | 0xE0020..=0xE007F
+    )
+}
+
+/// Collapse the gaps left by removed text, keeping line breaks
+fn tidy_whitespace(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Load a persona's rules; broken rules are logged and skipped so replies still go out
+pub fn for_persona(persona_id: i64, json: Option<&str>) -> Option<PostProcessor> {
+    let json = json.filter(|j| !j.trim().is_empty())?;
+    match PostProcessor::parse(json) {
+        Ok(processor) => Some(processor),
+        Err(e) => {
+            tracing::warn!("Ignoring post-processing rules of persona {}: {}", persona_id, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_rules_in_order() {
+        let processor = PostProcessor::parse(
+            r#"{
+                "replacements": [{"pattern": "(?i)конечно", "replacement": "ну да"}],
+                "forbidden": ["как ИИ"],
+                "strip_emoji": true,
+                "lowercase": true
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            processor.apply("Конечно 😀, как ии я не могу || Пока 👋🏻"),
+            "ну да , я не могу || пока"
+        );
+    }
+
+    #[test]
+    fn defaults_leave_text_alone() {
+        let processor = PostProcessor::parse("{}").unwrap();
+        assert_eq!(processor.apply("Привет!\nКак дела? 🙂"), "Привет!\nКак дела? 🙂");
+    }
+
+    #[test]
+    fn reports_the_broken_regex() {
+        let err = PostProcessor::parse(r#"{"replacements": [{"pattern": "ok"}, {"pattern": "("}]}"#).unwrap_err();
+        assert!(err.starts_with("replacement #2"));
+        assert!(PostProcessor::parse("not json").is_err());
+    }
+}
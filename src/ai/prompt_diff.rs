@@ -0,0 +1,108 @@
+/// Unchanged lines kept around each change in a preview
+const CONTEXT_LINES: usize = 1;
+
+/// One line of a line-based diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// Line diff of two prompts (longest common subsequence; prompts are small)
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = common lines of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::with_capacity(a.len().max(b.len()));
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            diff.push(DiffLine::Same(a[i]));
+            i += 1;
+            j += 1;
+        } else if i < a.len() && (j == b.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            // Removals first, so a replaced line reads "- old" then "+ new"
+            diff.push(DiffLine::Removed(a[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(b[j]));
+            j += 1;
+        }
+    }
+    diff
+}
+
+/// Changed lines as "- old" / "+ new" with a little context; "…" marks skipped lines.
+/// Empty if the prompts are identical.
+pub fn render_diff(old: &str, new: &str) -> String {
+    let diff = diff_lines(old, new);
+    let changed: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Same(_)))
+        .map(|(i, _)| i)
+        .collect();
+
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    let visible = |i: usize| changed.iter().any(|&c| i + CONTEXT_LINES >= c && i <= c + CONTEXT_LINES);
+    let mut out = Vec::new();
+    let mut skipped = false;
+
+    for (i, line) in diff.iter().enumerate() {
+        if !visible(i) {
+            skipped = true;
+            continue;
+        }
+        if skipped {
+            out.push("…".to_string());
+            skipped = false;
+        }
+        out.push(match line {
+            DiffLine::Same(l) => format!("  {}", l),
+            DiffLine::Removed(l) => format!("- {}", l),
+            DiffLine::Added(l) => format!("+ {}", l),
+        });
+    }
+    if skipped {
+        out.push("…".to_string());
+    }
+
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs_changed_line() {
+        let old = "one\ntwo\nthree\nfour\nfive";
+        let new = "one\ntwo\n3\nfour\nfive";
+        assert_eq!(render_diff(old, new), "…\n  two\n- three\n+ 3\n  four\n…");
+    }
+
+    #[test]
+    fn identical_prompts_have_no_diff() {
+        assert_eq!(render_diff("a\nb", "a\nb"), "");
+    }
+
+    #[test]
+    fn diffs_appended_lines() {
+        assert_eq!(
+            diff_lines("a", "a\nb"),
+            vec![DiffLine::Same("a"), DiffLine::Added("b")]
+        );
+    }
+}
@@ -1,6 +1,7 @@
 use anyhow::{Context, Result};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use sqlx::{SqlitePool, Row};
 
 #[derive(Debug, Serialize)]
@@ -43,8 +44,80 @@ pub async fn generate_embedding(
     Ok(embedding_response.embedding)
 }
 
+/// Lowercase, drop punctuation/emoji and collapse whitespace so near-identical
+/// messages ("Привет!!", "привет") map to the same text
+pub fn normalize_for_memory(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Stable hash of the normalized text
+pub fn content_hash(text: &str) -> String {
+    let digest = Sha256::digest(normalize_for_memory(text).as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether a message carries enough information to be worth remembering
+pub fn is_memorable(text: &str, min_chars: usize) -> bool {
+    let normalized = normalize_for_memory(text);
+    normalized.chars().count() >= min_chars && normalized.split(' ').count() >= 2
+}
+
+/// Generate an embedding, reusing one the same model made for identical normalized text
+pub async fn generate_embedding_cached(
+    client: &Client,
+    pool: &SqlitePool,
+    ollama_url: &str,
+    model: &str,
+    text: &str,
+) -> Result<Vec<f32>> {
+    let hash = content_hash(text);
+
+    let cached: Option<(Vec<u8>,)> = sqlx::query_as(
+        "SELECT embedding FROM long_term_memory WHERE content_hash = ? AND embedding_model = ? LIMIT 1"
+    )
+    .bind(&hash)
+    .bind(model)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up cached embedding")?;
+
+    if let Some((bytes,)) = cached {
+        if let Ok(embedding) = bincode::deserialize::<Vec<f32>>(&bytes) {
+            tracing::debug!("Embedding cache hit for {}", &hash[..12]);
+            return Ok(embedding);
+        }
+    }
+
+    let embedding = generate_embedding(client, ollama_url, model, text).await?;
+    crate::ai::models::track(pool, crate::ai::models::ModelKind::Embedding, model, Some(embedding.len())).await;
+    Ok(embedding)
+}
+
+/// Size of the embeddings already stored, if there are any
+pub async fn stored_embedding_dimension(pool: &SqlitePool) -> Result<Option<usize>> {
+    let row: Option<(Vec<u8>,)> = sqlx::query_as(
+        r#"
+        SELECT embedding FROM long_term_memory
+        UNION ALL
+        SELECT embedding FROM semantic_memory
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to read a stored embedding")?;
+
+    Ok(row.and_then(|(bytes,)| bincode::deserialize::<Vec<f32>>(&bytes).ok()).map(|e| e.len()))
+}
+
 /// Calculate cosine similarity between two vectors
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() {
         return 0.0;
     }
@@ -60,27 +133,78 @@ fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     dot_product / (magnitude_a * magnitude_b)
 }
 
-/// Store a memory with its embedding
+/// Weight bot-authored messages are remembered with in a chat, or `None` when they aren't embedded there
+pub fn bot_memory_importance(config: &crate::config::Config, chat: Option<&crate::db::AccountChat>) -> Option<f64> {
+    let embed = chat.and_then(|c| c.embed_bot_messages).unwrap_or(config.embed_bot_messages);
+    embed.then_some(config.bot_memory_importance)
+}
+
+/// Store a memory with its embedding (made by `embedding_model`), who wrote it and the persona
+/// answering the chat at the time. `importance` is its retrieval weight; refreshing an existing
+/// memory keeps the one it has.
+#[allow(clippy::too_many_arguments)]
 pub async fn store_memory(
     pool: &SqlitePool,
     account_id: i64,
     chat_id: i64,
+    message_id: Option<i64>,
+    sender_id: Option<i64>,
+    is_bot_author: bool,
+    persona_id: Option<i64>,
     content: &str,
     embedding: &[f32],
+    embedding_model: &str,
+    importance: f64,
 ) -> Result<()> {
+    let hash = content_hash(content);
+
+    // Already remembered in this chat: just bump it so cleanup keeps it
+    let refreshed = sqlx::query(
+        r#"
+        UPDATE long_term_memory
+        SET created_at = strftime('%s', 'now'), message_id = COALESCE(?, message_id),
+            sender_id = COALESCE(?, sender_id), is_bot_author = ?, persona_id = COALESCE(?, persona_id),
+            content = ?, is_hashed = 0
+        WHERE account_id = ? AND chat_id = ? AND content_hash = ?
+        "#
+    )
+    .bind(message_id)
+    .bind(sender_id)
+    .bind(is_bot_author)
+    .bind(persona_id)
+    .bind(content)
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(&hash)
+    .execute(pool)
+    .await
+    .context("Failed to refresh memory")?;
+
+    if refreshed.rows_affected() > 0 {
+        return Ok(());
+    }
+
     let embedding_bytes = bincode::serialize(embedding)
         .context("Failed to serialize embedding")?;
 
     sqlx::query(
         r#"
-        INSERT INTO long_term_memory (account_id, chat_id, content, embedding)
-        VALUES (?, ?, ?, ?)
+        INSERT INTO long_term_memory
+            (account_id, chat_id, message_id, sender_id, is_bot_author, persona_id, content, embedding, embedding_model, content_hash, importance)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         "#
     )
     .bind(account_id)
     .bind(chat_id)
+    .bind(message_id)
+    .bind(sender_id)
+    .bind(is_bot_author)
+    .bind(persona_id)
     .bind(content)
     .bind(embedding_bytes)
+    .bind(embedding_model)
+    .bind(&hash)
+    .bind(importance)
     .execute(pool)
     .await
     .context("Failed to store memory")?;
@@ -88,31 +212,135 @@ pub async fn store_memory(
     Ok(())
 }
 
+/// Relative weight of raw episodic chunks in retrieval
+const EPISODIC_WEIGHT: f32 = 1.0;
+/// Relative weight of consolidated facts, further scaled by their confidence
+const SEMANTIC_WEIGHT: f32 = 1.15;
+
+/// Boost for episodic chunks written by the current interlocutor
+const SENDER_BOOST: f32 = 1.2;
+/// Weight of a memory's similarity to the chat's current topic
+const TOPIC_WEIGHT: f32 = 0.15;
+
+/// Optional scoping of memory retrieval, configured per chat with /chat_rag
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RetrievalFilter {
+    /// Prefer chunks written by this user
+    pub prefer_sender: Option<i64>,
+    /// Skip chunks written by bots
+    pub exclude_bots: bool,
+    /// Only memories from the last N days
+    pub max_age_days: Option<i64>,
+    /// Embedding of the chat's current topic; related memories rank higher
+    pub topic: Option<Vec<f32>>,
+}
+
+impl RetrievalFilter {
+    /// Filters of a chat, preferring `sender_id` if the chat asks for it
+    pub fn for_chat(chat: Option<&crate::db::AccountChat>, sender_id: Option<i64>) -> Self {
+        match chat {
+            Some(chat) => RetrievalFilter {
+                prefer_sender: sender_id.filter(|id| *id != 0 && chat.rag_prefer_sender),
+                exclude_bots: chat.rag_exclude_bots,
+                max_age_days: chat.rag_max_age_days.filter(|d| *d > 0),
+                topic: None,
+            },
+            None => RetrievalFilter::default(),
+        }
+    }
+
+    /// Boost memories related to the chat's current topic
+    pub fn with_topic(mut self, topic: Option<Vec<f32>>) -> Self {
+        self.topic = topic;
+        self
+    }
+
+    /// Similarity of a memory after the sender preference and topic boost
+    fn score(&self, similarity: f32, sender_id: Option<i64>, embedding: &[f32]) -> f32 {
+        let similarity = match self.prefer_sender {
+            Some(preferred) if sender_id == Some(preferred) => similarity * SENDER_BOOST,
+            _ => similarity,
+        };
+        match &self.topic {
+            Some(topic) => similarity + TOPIC_WEIGHT * cosine_similarity(topic, embedding).max(0.0),
+            None => similarity,
+        }
+    }
+
+    /// Short description for debug output
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(sender) = self.prefer_sender {
+            parts.push(format!("prefer user {}", sender));
+        }
+        if self.exclude_bots {
+            parts.push("no bots".to_string());
+        }
+        if let Some(days) = self.max_age_days {
+            parts.push(format!("last {} days", days));
+        }
+        if self.topic.is_some() {
+            parts.push("topic boost".to_string());
+        }
+
+        if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Which memory tier a retrieved memory came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryTier {
+    /// Raw message chunks
+    Episodic,
+    /// Consolidated, human-readable facts
+    Semantic,
+}
+
 #[derive(Debug)]
 pub struct Memory {
+    /// Row of an episodic memory; semantic facts have none
+    pub id: Option<i64>,
     pub content: String,
     pub similarity: f32,
+    pub tier: MemoryTier,
+    /// Unix time the memory was stored (or the fact last confirmed)
+    pub created_at: i64,
+    /// Source Telegram message, for episodic memories stored with one
+    pub message_id: Option<i64>,
+    /// Author of an episodic memory, if known
+    pub sender_id: Option<i64>,
 }
 
-/// Retrieve top N most relevant memories for a query
+/// Retrieve top N most relevant memories for a query, mixing episodic and semantic tiers
 pub async fn retrieve_memories(
     pool: &SqlitePool,
     account_id: i64,
     chat_id: i64,
     query_embedding: &[f32],
     top_n: usize,
+    filter: &RetrievalFilter,
 ) -> Result<Vec<Memory>> {
     let rows = sqlx::query(
         r#"
-        SELECT content, embedding
+        SELECT id, content, embedding, created_at, message_id, sender_id, importance
         FROM long_term_memory
-        WHERE account_id = ? AND chat_id = ?
+        WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
+        AND (? = 0 OR is_bot_author = 0)
+        AND (? IS NULL OR created_at >= strftime('%s', 'now') - ? * 86400)
         ORDER BY created_at DESC
         LIMIT 100
         "#
     )
     .bind(account_id)
     .bind(chat_id)
+    .bind(filter.exclude_bots)
+    .bind(filter.max_age_days)
+    .bind(filter.max_age_days)
     .fetch_all(pool)
     .await
     .context("Failed to fetch memories")?;
@@ -124,14 +352,56 @@ pub async fn retrieve_memories(
             let embedding_bytes: Vec<u8> = row.try_get("embedding").ok()?;
             let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes).ok()?;
             let similarity = cosine_similarity(query_embedding, &embedding);
-            
+            let sender_id: Option<i64> = row.try_get("sender_id").ok().flatten();
+            // Set by hand with /memory_edit
+            let importance: f64 = row.try_get("importance").unwrap_or(1.0);
+
             Some(Memory {
+                id: row.try_get("id").ok(),
                 content,
-                similarity,
+                similarity: filter.score(similarity * EPISODIC_WEIGHT * importance as f32, sender_id, &embedding),
+                tier: MemoryTier::Episodic,
+                created_at: row.try_get("created_at").unwrap_or_default(),
+                message_id: row.try_get("message_id").ok().flatten(),
+                sender_id,
             })
         })
         .collect();
 
+    let semantic_rows = sqlx::query(
+        r#"
+        SELECT statement, confidence, embedding, updated_at
+        FROM semantic_memory
+        WHERE account_id = ? AND chat_id = ?
+        AND (? IS NULL OR updated_at >= strftime('%s', 'now') - ? * 86400)
+        "#
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(filter.max_age_days)
+    .bind(filter.max_age_days)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch semantic memories")?;
+
+    memories_with_similarity.extend(semantic_rows.into_iter().filter_map(|row| {
+        let statement: String = row.try_get("statement").ok()?;
+        let confidence: f64 = row.try_get("confidence").ok()?;
+        let embedding_bytes: Vec<u8> = row.try_get("embedding").ok()?;
+        let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes).ok()?;
+        let similarity = cosine_similarity(query_embedding, &embedding);
+
+        Some(Memory {
+            id: None,
+            content: statement,
+            similarity: filter.score(similarity * SEMANTIC_WEIGHT * (0.5 + 0.5 * confidence as f32), None, &embedding),
+            tier: MemoryTier::Semantic,
+            created_at: row.try_get("updated_at").unwrap_or_default(),
+            message_id: None,
+            sender_id: None,
+        })
+    }));
+
     // Sort by similarity (highest first)
     memories_with_similarity.sort_by(|a, b| {
         b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal)
@@ -141,6 +411,68 @@ pub async fn retrieve_memories(
     Ok(memories_with_similarity.into_iter().take(top_n).collect())
 }
 
+/// What a user told the account in their private chat, to recall when they talk in a group.
+/// Only the user's own messages: the account's replies there stay in that chat.
+pub async fn retrieve_private_memories(
+    pool: &SqlitePool,
+    account_id: i64,
+    user_id: i64,
+    query_embedding: &[f32],
+    top_n: usize,
+) -> Result<Vec<Memory>> {
+    // A private chat's ID is the user's ID
+    let rows = sqlx::query(
+        r#"
+        SELECT id, content, embedding, created_at, message_id, importance
+        FROM long_term_memory
+        WHERE account_id = ? AND chat_id = ? AND sender_id = ? AND is_hashed = 0
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#
+    )
+    .bind(account_id)
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch private chat memories")?;
+
+    let mut memories: Vec<Memory> = rows
+        .into_iter()
+        .filter_map(|row| {
+            let embedding_bytes: Vec<u8> = row.try_get("embedding").ok()?;
+            let embedding: Vec<f32> = bincode::deserialize(&embedding_bytes).ok()?;
+            let importance: f64 = row.try_get("importance").unwrap_or(1.0);
+
+            Some(Memory {
+                id: row.try_get("id").ok(),
+                content: row.try_get("content").ok()?,
+                similarity: cosine_similarity(query_embedding, &embedding) * EPISODIC_WEIGHT * importance as f32,
+                tier: MemoryTier::Episodic,
+                created_at: row.try_get("created_at").unwrap_or_default(),
+                message_id: row.try_get("message_id").ok().flatten(),
+                sender_id: Some(user_id),
+            })
+        })
+        .collect();
+
+    memories.sort_by(|a, b| b.similarity.partial_cmp(&a.similarity).unwrap_or(std::cmp::Ordering::Equal));
+    memories.truncate(top_n);
+    Ok(memories)
+}
+
+/// Count a use of the episodic memories that went into a prompt
+pub async fn record_hits(pool: &SqlitePool, memories: &[&Memory]) -> Result<()> {
+    for id in memories.iter().filter_map(|m| m.id) {
+        sqlx::query("UPDATE long_term_memory SET hits = hits + 1 WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to record memory hit")?;
+    }
+    Ok(())
+}
+
 /// Clean up old memories (keep last 1000 per chat)
 pub async fn cleanup_old_memories(
     pool: &SqlitePool,
@@ -169,3 +501,242 @@ pub async fn cleanup_old_memories(
 
     Ok(())
 }
+
+/// Whether a chat already remembers text with this normalized content
+pub async fn has_memory(pool: &SqlitePool, account_id: i64, chat_id: i64, content: &str) -> Result<bool> {
+    let found: Option<(i64,)> = sqlx::query_as(
+        "SELECT id FROM long_term_memory WHERE account_id = ? AND chat_id = ? AND content_hash = ? LIMIT 1",
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(content_hash(content))
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up memory")?;
+
+    Ok(found.is_some())
+}
+
+/// Drop the text of episodic memories older than `days`, keeping their hash and embedding
+pub async fn hash_memories_older_than(pool: &SqlitePool, days: i64) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE long_term_memory SET content = '', is_hashed = 1
+        WHERE is_hashed = 0 AND created_at < strftime('%s', 'now') - ? * 86400
+        "#
+    )
+    .bind(days)
+    .execute(pool)
+    .await
+    .context("Failed to hash old memories")?;
+
+    Ok(result.rows_affected())
+}
+
+/// Delete episodic and semantic memories older than `days`; facts count from when they were last confirmed
+pub async fn purge_memories_older_than(pool: &SqlitePool, days: i64) -> Result<u64> {
+    let mut deleted = 0;
+    for sql in [
+        "DELETE FROM long_term_memory WHERE created_at < strftime('%s', 'now') - ? * 86400",
+        "DELETE FROM semantic_memory WHERE updated_at < strftime('%s', 'now') - ? * 86400",
+    ] {
+        let result = sqlx::query(sql)
+            .bind(days)
+            .execute(pool)
+            .await
+            .context("Failed to purge old memories")?;
+        deleted += result.rows_affected();
+    }
+
+    Ok(deleted)
+}
+
+/// Delete a chat's episodic and semantic memories from before `before` (unix time)
+pub async fn purge_chat_memories_before(pool: &SqlitePool, account_id: i64, chat_id: i64, before: i64) -> Result<u64> {
+    let mut deleted = 0;
+    for sql in [
+        "DELETE FROM long_term_memory WHERE account_id = ? AND chat_id = ? AND created_at < ?",
+        "DELETE FROM semantic_memory WHERE account_id = ? AND chat_id = ? AND updated_at < ?",
+    ] {
+        let result = sqlx::query(sql)
+            .bind(account_id)
+            .bind(chat_id)
+            .bind(before)
+            .execute(pool)
+            .await
+            .context("Failed to purge chat memories")?;
+        deleted += result.rows_affected();
+    }
+
+    Ok(deleted)
+}
+
+/// Promote a fact into semantic memory, or reinforce it if already known
+pub async fn upsert_semantic_memory(
+    pool: &SqlitePool,
+    account_id: i64,
+    chat_id: i64,
+    statement: &str,
+    confidence: f64,
+    embedding: &[f32],
+) -> Result<bool> {
+    let hash = content_hash(statement);
+
+    // Re-confirmed facts gain confidence towards 1.0
+    let reinforced = sqlx::query(
+        r#"
+        UPDATE semantic_memory SET
+            confidence = MIN(1.0, MAX(confidence, ?) + 0.1 * (1.0 - MAX(confidence, ?))),
+            support_count = support_count + 1,
+            updated_at = strftime('%s', 'now')
+        WHERE account_id = ? AND chat_id = ? AND content_hash = ?
+        "#
+    )
+    .bind(confidence)
+    .bind(confidence)
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(&hash)
+    .execute(pool)
+    .await
+    .context("Failed to reinforce semantic memory")?;
+
+    if reinforced.rows_affected() > 0 {
+        return Ok(false);
+    }
+
+    let embedding_bytes = bincode::serialize(embedding)
+        .context("Failed to serialize embedding")?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO semantic_memory (account_id, chat_id, statement, confidence, embedding, content_hash)
+        VALUES (?, ?, ?, ?, ?, ?)
+        "#
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(statement)
+    .bind(confidence.clamp(0.0, 1.0))
+    .bind(embedding_bytes)
+    .bind(&hash)
+    .execute(pool)
+    .await
+    .context("Failed to store semantic memory")?;
+
+    Ok(true)
+}
+
+/// Facts consolidated or reconfirmed in a chat since a unix timestamp, best supported first
+pub async fn recent_facts(
+    pool: &SqlitePool,
+    account_id: i64,
+    chat_id: i64,
+    since: i64,
+    limit: i64,
+) -> Result<Vec<String>> {
+    let rows: Vec<(String,)> = sqlx::query_as(
+        r#"
+        SELECT statement FROM semantic_memory
+        WHERE account_id = ? AND chat_id = ? AND updated_at >= ?
+        ORDER BY support_count DESC, confidence DESC
+        LIMIT ?
+        "#
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(since)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch recent facts")?;
+
+    Ok(rows.into_iter().map(|(s,)| s).collect())
+}
+
+/// Memory tier sizes and consolidation progress for an account
+#[derive(Debug, Default)]
+pub struct MemoryStats {
+    pub episodic_count: i64,
+    pub semantic_count: i64,
+    pub pending_episodes: i64,
+    pub avg_confidence: Option<f64>,
+    pub last_consolidation: Option<i64>,
+}
+
+/// Collect memory statistics for an account (optionally a single chat)
+pub async fn memory_stats(pool: &SqlitePool, account_id: i64, chat_id: Option<i64>) -> Result<MemoryStats> {
+    let (episodic_count,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM long_term_memory WHERE account_id = ? AND (? IS NULL OR chat_id = ?)"
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(chat_id)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count episodic memories")?;
+
+    let (semantic_count, avg_confidence): (i64, Option<f64>) = sqlx::query_as(
+        "SELECT COUNT(*), AVG(confidence) FROM semantic_memory WHERE account_id = ? AND (? IS NULL OR chat_id = ?)"
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(chat_id)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count semantic memories")?;
+
+    let (pending_episodes,): (i64,) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FROM long_term_memory m
+        LEFT JOIN memory_consolidation c ON c.account_id = m.account_id AND c.chat_id = m.chat_id
+        WHERE m.account_id = ? AND (? IS NULL OR m.chat_id = ?)
+        AND m.id > COALESCE(c.last_episode_id, 0)
+        "#
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(chat_id)
+    .fetch_one(pool)
+    .await
+    .context("Failed to count pending episodes")?;
+
+    let (last_consolidation,): (Option<i64>,) = sqlx::query_as(
+        "SELECT MAX(last_run_at) FROM memory_consolidation WHERE account_id = ? AND (? IS NULL OR chat_id = ?)"
+    )
+    .bind(account_id)
+    .bind(chat_id)
+    .bind(chat_id)
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch consolidation status")?;
+
+    Ok(MemoryStats {
+        episodic_count,
+        semantic_count,
+        pending_episodes,
+        avg_confidence,
+        last_consolidation,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boosts_only_the_preferred_sender() {
+        let filter = RetrievalFilter { prefer_sender: Some(42), ..Default::default() };
+        assert!(filter.score(0.5, Some(42), &[1.0]) > 0.5);
+        assert_eq!(filter.score(0.5, Some(7), &[1.0]), 0.5);
+        assert_eq!(filter.score(0.5, None, &[1.0]), 0.5);
+        assert_eq!(RetrievalFilter::default().score(0.5, Some(42), &[1.0]), 0.5);
+    }
+
+    #[test]
+    fn boosts_memories_on_topic() {
+        let filter = RetrievalFilter::default().with_topic(Some(vec![1.0, 0.0]));
+        assert!(filter.score(0.5, None, &[1.0, 0.0]) > filter.score(0.5, None, &[0.0, 1.0]));
+        assert_eq!(filter.score(0.5, None, &[0.0, 1.0]), 0.5);
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    db::{MessageRepository, PrivacyRepository, Relationship, RelationshipRepository},
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Re-extract the profile after this many new messages from the contact
+pub const REFRESH_EVERY_MESSAGES: i64 = 30;
+
+/// Contacts silent for longer than this start to fade
+const DECAY_IDLE_DAYS: i64 = 14;
+/// Daily familiarity multiplier for idle contacts
+const DECAY_FACTOR: f64 = 0.95;
+
+#[derive(Debug, Deserialize)]
+struct ExtractedProfile {
+    #[serde(default)]
+    how_known: String,
+    #[serde(default)]
+    inside_jokes: Vec<String>,
+}
+
+/// Describe the relationship for the system prompt
+pub fn relationship_context(relationship: &Relationship) -> String {
+    let closeness = match relationship.familiarity {
+        f if f < 0.1 => "незнакомый человек, общайся сдержанно и без панибратства",
+        f if f < 0.35 => "малознакомый, общались пару раз",
+        f if f < 0.7 => "знакомый, общаетесь регулярно, можно неформально",
+        _ => "близкий контакт, давно и много общаетесь, можно подкалывать и шутить по-свойски",
+    };
+
+    let mut context = String::from("[КТО ТВОЙ СОБЕСЕДНИК]\n");
+    context.push_str(&format!("Степень знакомства: {}\n", closeness));
+
+    if !relationship.how_known.is_empty() {
+        context.push_str(&format!("Откуда ты его знаешь: {}\n", relationship.how_known));
+    }
+
+    let jokes = relationship.get_inside_jokes();
+    if !jokes.is_empty() {
+        context.push_str("Ваши общие шутки и темы (используй изредка, к месту):\n");
+        for joke in jokes {
+            context.push_str(&format!("- {}\n", joke));
+        }
+    }
+
+    context
+}
+
+/// Extract "how we know each other" and inside jokes from the contact's messages
+pub async fn refresh_relationship(state: &AppState, account_id: i64, peer_user_id: i64) -> Result<()> {
+    let relationship = match RelationshipRepository::get(&state.db_pool, account_id, peer_user_id).await? {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    // The profile goes into group prompts too, so private chats only count if the contact allowed it with /privacy
+    let include_private = PrivacyRepository::shares_private(&state.db_pool, account_id, peer_user_id).await?;
+    let messages =
+        MessageRepository::get_recent_from_sender(&state.db_pool, account_id, peer_user_id, include_private, 60).await?;
+    if messages.is_empty() {
+        return Ok(());
+    }
+
+    let transcript = messages
+        .iter()
+        .map(|m| format!("- {}", m.content))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        r#"Below are messages one person sent in Telegram chats. Based only on them, describe the relationship with this person.
+
+Messages:
+{}
+
+Already known: "{}"
+
+Return JSON: {{"how_known": "<short phrase in Russian: how we know each other, or empty if unclear>", "inside_jokes": ["<up to 5 recurring jokes, nicknames or running topics, in Russian>"]}}"#,
+        transcript, relationship.how_known
+    );
+
+    // A failed extraction bails here, before the stored profile could be overwritten
+    let profile: ExtractedProfile = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.2), num_predict: None }),
+        )
+        .await
+        .context("Failed to extract relationship profile")?;
+
+    let jokes: Vec<String> = profile
+        .inside_jokes
+        .into_iter()
+        .map(|j| j.trim().to_string())
+        .filter(|j| !j.is_empty())
+        .take(5)
+        .collect();
+
+    // An admin-provided description wins over the model's guess
+    let how_known = if relationship.how_known.is_empty() {
+        profile.how_known.trim().to_string()
+    } else {
+        String::new()
+    };
+
+    RelationshipRepository::update_profile(
+        &state.db_pool,
+        account_id,
+        peer_user_id,
+        &how_known,
+        &jokes,
+        relationship.message_count,
+    )
+    .await?;
+
+    tracing::debug!(
+        "Refreshed relationship of account {} with user {} ({} inside jokes)",
+        account_id,
+        peer_user_id,
+        jokes.len()
+    );
+    Ok(())
+}
+
+/// Background worker that fades familiarity with contacts who went quiet
+pub async fn relationship_decay_worker(state: AppState) {
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(24 * 60 * 60));
+
+    loop {
+        interval.tick().await;
+
+        match RelationshipRepository::decay_inactive(&state.db_pool, DECAY_IDLE_DAYS, DECAY_FACTOR).await {
+            Ok(count) if count > 0 => tracing::info!("Decayed familiarity of {} idle contacts", count),
+            Ok(_) => {}
+            Err(e) => tracing::error!("Relationship decay failed: {}", e),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    ai::{
+        ollama::{ChatOptions, OllamaClient, OllamaMessage},
+        rag::Memory,
+    },
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Candidates pulled by embedding similarity before reranking
+pub const RERANK_CANDIDATES: usize = 50;
+
+/// Counters for the reranking stage
+#[derive(Debug, Default)]
+pub struct RerankMetrics {
+    pub calls: AtomicU64,
+    pub failures: AtomicU64,
+    pub total_latency_ms: AtomicU64,
+    /// Times the reranker's top pick differed from the cosine top pick
+    pub top_changed: AtomicU64,
+}
+
+impl RerankMetrics {
+    pub fn avg_latency_ms(&self) -> u64 {
+        let calls = self.calls.load(Ordering::Relaxed);
+        self.total_latency_ms.load(Ordering::Relaxed).checked_div(calls).unwrap_or(0)
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref RERANK_METRICS: RerankMetrics = RerankMetrics::default();
+}
+
+/// Response of a `/rerank` endpoint (TEI / Jina / Cohere style)
+#[derive(Debug, Deserialize)]
+struct RerankEndpointResponse {
+    results: Vec<RerankEndpointResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RerankEndpointResult {
+    index: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct LlmRanking {
+    #[serde(default)]
+    ranking: Vec<usize>,
+}
+
+/// Reorder candidates by relevance to the query and keep the top `top_k`.
+/// Falls back to the cosine order if the reranker fails.
+pub async fn rerank_memories(state: &AppState, query: &str, candidates: Vec<Memory>, top_k: usize) -> Vec<Memory> {
+    if candidates.len() <= 1 {
+        return candidates;
+    }
+
+    let started = std::time::Instant::now();
+    let documents: Vec<&str> = candidates.iter().map(|m| m.content.as_str()).collect();
+
+    let ranking = match &state.config.rag_reranker_url {
+        Some(url) => rerank_via_endpoint(url, query, &documents, top_k).await,
+        None => rerank_via_llm(state, query, &documents, top_k).await,
+    };
+
+    RERANK_METRICS.calls.fetch_add(1, Ordering::Relaxed);
+    RERANK_METRICS
+        .total_latency_ms
+        .fetch_add(started.elapsed().as_millis() as u64, Ordering::Relaxed);
+
+    let ranking = match ranking {
+        Ok(r) if !r.is_empty() => r,
+        Ok(_) => {
+            RERANK_METRICS.failures.fetch_add(1, Ordering::Relaxed);
+            return candidates.into_iter().take(top_k).collect();
+        }
+        Err(e) => {
+            tracing::warn!("Reranking failed, using cosine order: {}", e);
+            RERANK_METRICS.failures.fetch_add(1, Ordering::Relaxed);
+            return candidates.into_iter().take(top_k).collect();
+        }
+    };
+
+    if ranking.first() != Some(&0) {
+        RERANK_METRICS.top_changed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let mut slots: Vec<Option<Memory>> = candidates.into_iter().map(Some).collect();
+    ranking
+        .into_iter()
+        .filter_map(|idx| slots.get_mut(idx).and_then(Option::take))
+        .take(top_k)
+        .collect()
+}
+
+async fn rerank_via_endpoint(url: &str, query: &str, documents: &[&str], top_k: usize) -> Result<Vec<usize>> {
+    let request = serde_json::json!({
+        "query": query,
+        "documents": documents,
+        "texts": documents,
+        "top_n": top_k,
+    });
+
+    let response: RerankEndpointResponse = reqwest::Client::new()
+        .post(url)
+        .json(&request)
+        .send()
+        .await
+        .context("Failed to send rerank request")?
+        .error_for_status()
+        .context("Reranker returned an error")?
+        .json()
+        .await
+        .context("Failed to parse rerank response")?;
+
+    Ok(response.results.into_iter().map(|r| r.index).collect())
+}
+
+async fn rerank_via_llm(state: &AppState, query: &str, documents: &[&str], top_k: usize) -> Result<Vec<usize>> {
+    let numbered = documents
+        .iter()
+        .enumerate()
+        .map(|(i, d)| format!("[{}] {}", i, d.chars().take(300).collect::<String>()))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        r#"Query: "{}"
+
+Passages:
+{}
+
+Rank the passages by how useful they are for answering the query. Return JSON: {{"ranking": [<indices of the {} most relevant passages, best first>]}}"#,
+        query, numbered, top_k
+    );
+
+    let ranking: LlmRanking = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.0), num_predict: None }),
+        )
+        .await
+        .context("Failed to rerank via the model")?;
+
+    Ok(ranking.ranking)
+}
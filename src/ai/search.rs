@@ -1,6 +1,8 @@
+use super::ollama::{ChatOptions, OllamaClient, OllamaMessage};
 use anyhow::{Context, Result};
 use reqwest::Client;
 use scraper::{Html, Selector};
+use serde::Deserialize;
 
 #[derive(Debug, Clone)]
 pub struct SearchResult {
@@ -76,69 +78,44 @@ fn parse_duckduckgo_results(html: &str, max_results: usize) -> Result<Vec<Search
     Ok(results)
 }
 
-/// Check if a message requires web search using LLM
-pub async fn should_search(
-    client: &Client,
-    ollama_url: &str,
-    model: &str,
-    message: &str,
-) -> Result<Option<String>> {
-    use serde_json::json;
+/// What the search-intent check answers
+#[derive(Debug, Deserialize)]
+struct SearchIntent {
+    search: bool,
+    #[serde(default)]
+    query: String,
+}
 
+/// Check if a message requires web search using LLM
+pub async fn should_search(ollama_url: &str, model: &str, message: &str) -> Result<Option<String>> {
     let prompt = format!(
         r#"Analyze this message and determine if it requires searching the internet for current facts, news, or real-time information.
 
 Message: "{}"
 
-If search is needed, reply ONLY with: SEARCH: <query>
-If no search needed, reply ONLY with: NO
+Return JSON: {{"search": true or false, "query": "<short search query, empty if no search is needed>"}}
 
 Examples:
-- "what's the weather today?" → SEARCH: weather today
-- "who won the game yesterday?" → SEARCH: game results yesterday
-- "привет как дела?" → NO
-- "что нового в мире?" → SEARCH: latest news
-- "сколько стоит биткоин?" → SEARCH: bitcoin price
-
-Your response:"#,
+- "what's the weather today?" → {{"search": true, "query": "weather today"}}
+- "who won the game yesterday?" → {{"search": true, "query": "game results yesterday"}}
+- "привет как дела?" → {{"search": false, "query": ""}}
+- "что нового в мире?" → {{"search": true, "query": "latest news"}}
+- "сколько стоит биткоин?" → {{"search": true, "query": "bitcoin price"}}"#,
         message
     );
 
-    let url = format!("{}/api/generate", ollama_url);
-    let request = json!({
-        "model": model,
-        "prompt": prompt,
-        "stream": false,
-        "options": {
-            "temperature": 0.1,
-            "num_predict": 50
-        }
-    });
-
-    let response = client
-        .post(&url)
-        .json(&request)
-        .send()
-        .await
-        .context("Failed to send LLM request")?;
-
-    let response_json: serde_json::Value = response
-        .json()
+    let intent: SearchIntent = OllamaClient::new(ollama_url)
+        .generate_structured(
+            model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.1), num_predict: Some(60) }),
+        )
         .await
-        .context("Failed to parse LLM response")?;
+        .context("Failed to detect search intent")?;
 
-    let response_text = response_json["response"]
-        .as_str()
-        .unwrap_or("")
-        .trim();
-
-    if response_text.starts_with("SEARCH:") {
-        let query = response_text
-            .strip_prefix("SEARCH:")
-            .unwrap_or("")
-            .trim()
-            .to_string();
-        Ok(Some(query))
+    let query = intent.query.trim();
+    if intent.search && !query.is_empty() {
+        Ok(Some(query.to_string()))
     } else {
         Ok(None)
     }
@@ -0,0 +1,257 @@
+use crate::{
+    db::{MessageRepository, PersonaRepository},
+    AppState,
+};
+use anyhow::Result;
+use lazy_static::lazy_static;
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+/// How often personas are checked for drift
+const CHECK_INTERVAL_SECS: u64 = 6 * 60 * 60;
+
+/// Replies of the last days compared against the baseline before them
+pub const RECENT_DAYS: i64 = 2;
+pub const BASELINE_DAYS: i64 = 30;
+
+/// Fewer replies than this on either side say nothing about drift
+const MIN_REPLIES: usize = 20;
+const MAX_REPLIES: i64 = 500;
+
+/// Relative change of the average length that counts as drift
+const LENGTH_DRIFT: f64 = 0.5;
+/// Change in emoji per reply
+const EMOJI_DRIFT: f64 = 0.5;
+/// Change of the formality score (0–1)
+const FORMALITY_DRIFT: f64 = 0.2;
+/// Change in the share of replies written in Cyrillic
+const LANGUAGE_DRIFT: f64 = 0.3;
+
+lazy_static! {
+    /// Personas the owner was already told about, until they are back to their baseline
+    static ref DRIFTING: Mutex<HashSet<i64>> = Mutex::new(HashSet::new());
+}
+
+/// Words that make a reply casual
+const INFORMAL_MARKERS: &[&str] = &["лол", "ща", "норм", "кек", "оч", "плз", "спс", "хз", "чё", "че", "lol", "lmao", "idk", "btw", "gonna", "wanna"];
+/// Words that make it polite
+const FORMAL_MARKERS: &[&str] = &["пожалуйста", "извините", "благодарю", "здравствуйте", "уважаемый", "please", "regards", "sincerely"];
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F300..=0x1FAFF | 0x2600..=0x27BF | 0x1F000..=0x1F2FF)
+}
+
+/// Style of a single reply
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplyStyle {
+    pub length: usize,
+    pub emojis: usize,
+    /// 0 casual … 1 formal
+    pub formality: f64,
+    /// Most letters are Cyrillic
+    pub cyrillic: bool,
+}
+
+pub fn measure(text: &str) -> ReplyStyle {
+    let text = text.trim();
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let capitalized = text.chars().find(|c| c.is_alphabetic()).is_some_and(char::is_uppercase);
+    let punctuated = text
+        .trim_end_matches(is_emoji)
+        .trim_end()
+        .ends_with(['.', '!', '?', '…']);
+    let casual = words.iter().any(|w| INFORMAL_MARKERS.contains(w)) || text.contains("))");
+    let polite = words.iter().any(|w| FORMAL_MARKERS.contains(w));
+
+    let mut formality = [capitalized, punctuated, !casual].iter().filter(|f| **f).count() as f64 / 3.0;
+    if polite {
+        formality = (formality + 0.25).min(1.0);
+    }
+
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    let cyrillic = letters.iter().filter(|c| matches!(**c, 'а'..='я' | 'А'..='Я' | 'ё' | 'Ё')).count();
+
+    ReplyStyle {
+        length: text.chars().count(),
+        emojis: text.chars().filter(|c| is_emoji(*c)).count(),
+        formality,
+        cyrillic: !letters.is_empty() && cyrillic * 2 > letters.len(),
+    }
+}
+
+/// Averages over a set of replies
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StyleMetrics {
+    pub replies: usize,
+    pub avg_length: f64,
+    /// Emoji per reply
+    pub emoji_rate: f64,
+    pub formality: f64,
+    /// Share of replies written in Cyrillic
+    pub cyrillic_share: f64,
+}
+
+impl StyleMetrics {
+    pub fn of<'a>(replies: impl IntoIterator<Item = &'a str>) -> Self {
+        let styles: Vec<ReplyStyle> = replies.into_iter().map(measure).collect();
+        if styles.is_empty() {
+            return StyleMetrics::default();
+        }
+        let n = styles.len() as f64;
+        StyleMetrics {
+            replies: styles.len(),
+            avg_length: styles.iter().map(|s| s.length as f64).sum::<f64>() / n,
+            emoji_rate: styles.iter().map(|s| s.emojis as f64).sum::<f64>() / n,
+            formality: styles.iter().map(|s| s.formality).sum::<f64>() / n,
+            cyrillic_share: styles.iter().filter(|s| s.cyrillic).count() as f64 / n,
+        }
+    }
+
+    /// "ru" or "en" by the majority of replies
+    pub fn language(&self) -> &'static str {
+        if self.cyrillic_share >= 0.5 {
+            "ru"
+        } else {
+            "en"
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "{} replies: {:.0} chars, {:.2} emoji, formality {:.2}, {} ({:.0}% Cyrillic)",
+            self.replies,
+            self.avg_length,
+            self.emoji_rate,
+            self.formality,
+            self.language(),
+            self.cyrillic_share * 100.0
+        )
+    }
+}
+
+/// How recent replies differ from the baseline, one line per metric that moved too far
+pub fn drift(baseline: &StyleMetrics, recent: &StyleMetrics) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if baseline.replies < MIN_REPLIES || recent.replies < MIN_REPLIES {
+        return reasons;
+    }
+
+    if baseline.avg_length > 0.0 && ((recent.avg_length - baseline.avg_length) / baseline.avg_length).abs() > LENGTH_DRIFT {
+        reasons.push(format!("length {:.0} → {:.0} chars", baseline.avg_length, recent.avg_length));
+    }
+    if (recent.emoji_rate - baseline.emoji_rate).abs() > EMOJI_DRIFT {
+        reasons.push(format!("emoji {:.2} → {:.2} per reply", baseline.emoji_rate, recent.emoji_rate));
+    }
+    if (recent.formality - baseline.formality).abs() > FORMALITY_DRIFT {
+        reasons.push(format!("formality {:.2} → {:.2}", baseline.formality, recent.formality));
+    }
+    if (recent.cyrillic_share - baseline.cyrillic_share).abs() > LANGUAGE_DRIFT {
+        reasons.push(format!(
+            "Cyrillic replies {:.0}% → {:.0}%",
+            baseline.cyrillic_share * 100.0,
+            recent.cyrillic_share * 100.0
+        ));
+    }
+    reasons
+}
+
+/// Style of a persona's recent replies and of its baseline before them
+pub async fn compare(state: &AppState, persona_id: i64) -> Result<(StyleMetrics, StyleMetrics)> {
+    let recent = MessageRepository::persona_replies(&state.db_pool, persona_id, RECENT_DAYS, 0, MAX_REPLIES).await?;
+    let baseline =
+        MessageRepository::persona_replies(&state.db_pool, persona_id, BASELINE_DAYS, RECENT_DAYS, MAX_REPLIES).await?;
+
+    Ok((
+        StyleMetrics::of(baseline.iter().map(|(text, _)| text.as_str())),
+        StyleMetrics::of(recent.iter().map(|(text, _)| text.as_str())),
+    ))
+}
+
+/// Tell the owner when a persona stops sounding like itself, e.g. after a model change
+pub async fn style_drift_worker(state: AppState) {
+    tracing::info!("Style drift worker started");
+
+    let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = check_all(&state).await {
+            tracing::error!("Style drift check failed: {}", e);
+        }
+    }
+}
+
+async fn check_all(state: &AppState) -> Result<()> {
+    for persona in PersonaRepository::list_all(&state.db_pool).await? {
+        let (baseline, recent) = compare(state, persona.id).await?;
+        let reasons = drift(&baseline, &recent);
+
+        if reasons.is_empty() {
+            DRIFTING.lock().unwrap().remove(&persona.id);
+            continue;
+        }
+        if !DRIFTING.lock().unwrap().insert(persona.id) {
+            continue;
+        }
+
+        tracing::warn!("Persona {} drifted from its style: {}", persona.name, reasons.join("; "));
+        let notice = format!(
+            "🎭 Persona \"{}\" doesn't sound like itself lately (last {} days vs. the {} before):\n• {}\n\n\
+            Chat model: {}. Details: /persona_style {}",
+            persona.name,
+            RECENT_DAYS,
+            BASELINE_DAYS - RECENT_DAYS,
+            reasons.join("\n• "),
+            state.config.ollama_model,
+            persona.id
+        );
+        if let Err(e) = crate::userbot::worker::notify_owner(state, &notice).await {
+            tracing::error!("Failed to notify owner about style drift: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_reply_style() {
+        let casual = measure("ну норм вроде))");
+        assert_eq!(casual.formality, 0.0);
+        assert!(casual.cyrillic);
+
+        let formal = measure("Здравствуйте! Пожалуйста, уточните вопрос.");
+        assert_eq!(formal.formality, 1.0);
+
+        let emoji = measure("Sounds great 🎉🎉");
+        assert_eq!(emoji.emojis, 2);
+        assert!(!emoji.cyrillic);
+        assert!(emoji.formality < 1.0);
+    }
+
+    #[test]
+    fn flags_drift_only_past_thresholds() {
+        let baseline = StyleMetrics::of(std::iter::repeat("ну да, бывает)) ").take(30));
+        let same = StyleMetrics::of(std::iter::repeat("ага, такое бывает)) ").take(30));
+        assert!(drift(&baseline, &same).is_empty());
+
+        let broken = StyleMetrics::of(
+            std::iter::repeat("Certainly! Here is a detailed explanation of the topic you asked about.").take(30),
+        );
+        let reasons = drift(&baseline, &broken);
+        assert!(reasons.iter().any(|r| r.starts_with("length")));
+        assert!(reasons.iter().any(|r| r.starts_with("formality")));
+        assert!(reasons.iter().any(|r| r.starts_with("Cyrillic")));
+
+        let few = StyleMetrics::of(std::iter::repeat("Certainly!").take(5));
+        assert!(drift(&baseline, &few).is_empty());
+    }
+}
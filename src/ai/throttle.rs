@@ -0,0 +1,301 @@
+use crate::AppState;
+use std::sync::atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// How often host load is sampled
+const CHECK_INTERVAL_SECS: u64 = 15;
+
+/// Pressure has to fall below this share of where a level starts before throttling eases off
+const RELAX_RATIO: f64 = 0.8;
+
+/// Past this multiple of a threshold generation degrades all the way
+const SEVERE_RATIO: f64 = 1.25;
+
+/// Shortest pause between replies in a chat while throttled, in seconds
+const REDUCED_COOLDOWN_SECS: i64 = 30;
+const MINIMAL_COOLDOWN_SECS: i64 = 120;
+
+/// How far generation is degraded to spare the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThrottleLevel {
+    Normal = 0,
+    /// Shorter replies and longer cooldowns
+    Reduced = 1,
+    /// On top of that, the fallback model
+    Minimal = 2,
+}
+
+impl ThrottleLevel {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => ThrottleLevel::Normal,
+            1 => ThrottleLevel::Reduced,
+            _ => ThrottleLevel::Minimal,
+        }
+    }
+
+    /// Share of the thresholds at which this level starts
+    fn threshold(&self) -> f64 {
+        match self {
+            ThrottleLevel::Normal => 0.0,
+            ThrottleLevel::Reduced => 1.0,
+            ThrottleLevel::Minimal => SEVERE_RATIO,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThrottleLevel::Normal => "normal",
+            ThrottleLevel::Reduced => "reduced",
+            ThrottleLevel::Minimal => "minimal",
+        }
+    }
+
+    /// Most tokens a reply may have; `None` leaves the model's default
+    pub fn num_predict(&self, reduced: i64) -> Option<i64> {
+        match self {
+            ThrottleLevel::Normal => None,
+            ThrottleLevel::Reduced => Some(reduced),
+            ThrottleLevel::Minimal => Some((reduced / 2).max(16)),
+        }
+    }
+
+    /// The chat model to use
+    pub fn model<'a>(&self, default: &'a str, fallback: Option<&'a str>) -> &'a str {
+        match (self, fallback) {
+            (ThrottleLevel::Minimal, Some(fallback)) => fallback,
+            _ => default,
+        }
+    }
+
+    /// Cooldown between replies in a chat, given the chat's own
+    pub fn cooldown(&self, base: Option<i64>) -> Option<i64> {
+        let base = base.unwrap_or(0).max(0);
+        let cooldown = match self {
+            ThrottleLevel::Normal => base,
+            ThrottleLevel::Reduced => (base * 2).max(REDUCED_COOLDOWN_SECS),
+            ThrottleLevel::Minimal => (base * 4).max(MINIMAL_COOLDOWN_SECS),
+        };
+        Some(cooldown).filter(|c| *c > 0)
+    }
+}
+
+/// Host load above which generation is degraded (THROTTLE_*)
+#[derive(Debug, Clone, Copy)]
+pub struct ThrottleThresholds {
+    /// 1-minute load average per CPU core
+    pub cpu_load: f64,
+    /// Share of memory in use, 0.0–1.0
+    pub memory: f64,
+    /// Chat generations running or waiting for Ollama at once
+    pub queue: usize,
+}
+
+/// What the host looks like right now; unknown parts (not Linux) count as no pressure
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Pressure {
+    pub cpu_load: Option<f64>,
+    pub memory: Option<f64>,
+    pub queue: usize,
+}
+
+impl Pressure {
+    /// The highest share of its threshold any measure reaches
+    pub fn ratio(&self, thresholds: &ThrottleThresholds) -> f64 {
+        let ratio = |value: Option<f64>, limit: f64| match value {
+            Some(v) if limit > 0.0 => v / limit,
+            _ => 0.0,
+        };
+        ratio(self.cpu_load, thresholds.cpu_load)
+            .max(ratio(self.memory, thresholds.memory))
+            .max(ratio(Some(self.queue as f64), thresholds.queue as f64))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(load) = self.cpu_load {
+            parts.push(format!("load {:.2}/core", load));
+        }
+        if let Some(memory) = self.memory {
+            parts.push(format!("memory {:.0}%", memory * 100.0));
+        }
+        parts.push(format!("{} in queue", self.queue));
+        parts.join(", ")
+    }
+}
+
+/// The level pressure calls for; stepping down only once it has clearly eased
+pub fn next_level(current: ThrottleLevel, pressure: &Pressure, thresholds: &ThrottleThresholds) -> ThrottleLevel {
+    let ratio = pressure.ratio(thresholds);
+    let wanted = if ratio >= SEVERE_RATIO {
+        ThrottleLevel::Minimal
+    } else if ratio >= 1.0 {
+        ThrottleLevel::Reduced
+    } else {
+        ThrottleLevel::Normal
+    };
+
+    if wanted >= current {
+        return wanted;
+    }
+
+    let mut level = current;
+    while level > wanted && ratio < level.threshold() * RELAX_RATIO {
+        level = ThrottleLevel::from_u8(level as u8 - 1);
+    }
+    level
+}
+
+/// 1-minute load average from /proc/loadavg
+pub fn parse_loadavg(text: &str) -> Option<f64> {
+    text.split_whitespace().next()?.parse().ok()
+}
+
+/// Share of memory in use from /proc/meminfo
+pub fn parse_meminfo(text: &str) -> Option<f64> {
+    let field = |name: &str| {
+        text.lines()
+            .find(|line| line.starts_with(name))
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|kb| kb.parse::<f64>().ok())
+    };
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+    if total <= 0.0 {
+        return None;
+    }
+    Some(((total - available) / total).clamp(0.0, 1.0))
+}
+
+fn sample_host() -> (Option<f64>, Option<f64>) {
+    let cores = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1) as f64;
+    let cpu_load = std::fs::read_to_string("/proc/loadavg").ok().as_deref().and_then(parse_loadavg).map(|l| l / cores);
+    let memory = std::fs::read_to_string("/proc/meminfo").ok().as_deref().and_then(parse_meminfo);
+    (cpu_load, memory)
+}
+
+/// Current throttle level, the pressure behind it, and counters for /start
+#[derive(Debug, Default)]
+pub struct Throttle {
+    level: AtomicU8,
+    in_flight: AtomicUsize,
+    pressure: Mutex<Pressure>,
+    /// Level changes since start
+    pub changes: AtomicU64,
+    /// Replies generated while throttled since start
+    pub throttled_replies: AtomicU64,
+}
+
+/// Counts a chat generation as queued until dropped
+pub struct InFlight<'a>(&'a Throttle);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Throttle {
+    pub fn level(&self) -> ThrottleLevel {
+        ThrottleLevel::from_u8(self.level.load(Ordering::Relaxed))
+    }
+
+    pub fn pressure(&self) -> Pressure {
+        *self.pressure.lock().unwrap()
+    }
+
+    /// "reduced (load 1.62/core, memory 71%, 2 in queue)" for /start and the statistics screen
+    pub fn describe(&self) -> String {
+        format!("{} ({})", self.level().as_str(), self.pressure().describe())
+    }
+
+    /// Mark a chat generation as started
+    pub fn start_generation(&self) -> InFlight<'_> {
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        if self.level() != ThrottleLevel::Normal {
+            self.throttled_replies.fetch_add(1, Ordering::Relaxed);
+        }
+        InFlight(self)
+    }
+
+    /// Take a sample and move to the level it calls for; returns the new level if it changed
+    fn update(&self, thresholds: &ThrottleThresholds) -> Option<ThrottleLevel> {
+        let (cpu_load, memory) = sample_host();
+        let pressure = Pressure { cpu_load, memory, queue: self.in_flight.load(Ordering::Relaxed) };
+        *self.pressure.lock().unwrap() = pressure;
+
+        let current = self.level();
+        let next = next_level(current, &pressure, thresholds);
+        if next == current {
+            return None;
+        }
+        self.level.store(next as u8, Ordering::Relaxed);
+        self.changes.fetch_add(1, Ordering::Relaxed);
+        Some(next)
+    }
+}
+
+/// Degrade generation while the host is under pressure and restore it once it eases
+pub async fn throttle_worker(state: AppState) {
+    tracing::info!("Throttle worker started");
+    let thresholds = state.config.throttle;
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        if let Some(level) = state.throttle.update(&thresholds) {
+            let pressure = state.throttle.pressure();
+            match level {
+                ThrottleLevel::Normal => tracing::info!("Host pressure eased ({}), generation back to normal", pressure.describe()),
+                _ => tracing::warn!("Host under pressure ({}), throttling generation: {}", pressure.describe(), level.as_str()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> ThrottleThresholds {
+        ThrottleThresholds { cpu_load: 1.0, memory: 0.9, queue: 4 }
+    }
+
+    fn pressure(cpu_load: f64, queue: usize) -> Pressure {
+        Pressure { cpu_load: Some(cpu_load), memory: Some(0.5), queue }
+    }
+
+    #[test]
+    fn steps_up_at_once_and_down_with_hysteresis() {
+        let t = thresholds();
+        assert_eq!(next_level(ThrottleLevel::Normal, &pressure(0.5, 0), &t), ThrottleLevel::Normal);
+        assert_eq!(next_level(ThrottleLevel::Normal, &pressure(1.1, 0), &t), ThrottleLevel::Reduced);
+        assert_eq!(next_level(ThrottleLevel::Normal, &pressure(0.5, 5), &t), ThrottleLevel::Minimal);
+        // Just under where a level starts it holds
+        assert_eq!(next_level(ThrottleLevel::Minimal, &pressure(1.1, 0), &t), ThrottleLevel::Minimal);
+        assert_eq!(next_level(ThrottleLevel::Reduced, &pressure(0.9, 0), &t), ThrottleLevel::Reduced);
+        assert_eq!(next_level(ThrottleLevel::Minimal, &pressure(0.9, 0), &t), ThrottleLevel::Reduced);
+        assert_eq!(next_level(ThrottleLevel::Minimal, &pressure(0.7, 0), &t), ThrottleLevel::Normal);
+    }
+
+    #[test]
+    fn degrades_tokens_model_and_cooldown() {
+        assert_eq!(ThrottleLevel::Normal.num_predict(256), None);
+        assert_eq!(ThrottleLevel::Minimal.num_predict(256), Some(128));
+        assert_eq!(ThrottleLevel::Reduced.model("big", Some("small")), "big");
+        assert_eq!(ThrottleLevel::Minimal.model("big", Some("small")), "small");
+        assert_eq!(ThrottleLevel::Minimal.model("big", None), "big");
+        assert_eq!(ThrottleLevel::Normal.cooldown(None), None);
+        assert_eq!(ThrottleLevel::Reduced.cooldown(Some(60)), Some(120));
+        assert_eq!(ThrottleLevel::Minimal.cooldown(None), Some(MINIMAL_COOLDOWN_SECS));
+    }
+
+    #[test]
+    fn reads_proc_files() {
+        assert_eq!(parse_loadavg("0.52 0.58 0.59 1/467 12345\n"), Some(0.52));
+        let meminfo = "MemTotal:       16000000 kB\nMemFree:         1000000 kB\nMemAvailable:    4000000 kB\n";
+        assert_eq!(parse_meminfo(meminfo), Some(0.75));
+        assert_eq!(parse_meminfo("MemTotal: 100 kB\n"), None);
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Extra instructions for a persona during an hour range of the chat's local day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimeVariant {
+    /// First hour of the range (0-23)
+    pub from: u32,
+    /// Hour the range ends at, exclusive; a range may wrap past midnight (22-7)
+    pub to: u32,
+    pub suffix: String,
+}
+
+impl TimeVariant {
+    pub fn covers(&self, hour: u32) -> bool {
+        if self.from < self.to {
+            (self.from..self.to).contains(&hour)
+        } else {
+            hour >= self.from || hour < self.to
+        }
+    }
+}
+
+/// Parse "22-7" into an hour range
+pub fn parse_range(s: &str) -> Option<(u32, u32)> {
+    let (from, to) = s.split_once('-')?;
+    let (from, to): (u32, u32) = (from.trim().parse().ok()?, to.trim().parse().ok()?);
+    (from < 24 && to <= 24 && from != to % 24).then_some((from, to % 24))
+}
+
+/// Variants stored on a persona; broken JSON counts as none
+pub fn parse_variants(json: Option<&str>) -> Vec<TimeVariant> {
+    json.and_then(|j| serde_json::from_str(j).ok()).unwrap_or_default()
+}
+
+/// The first variant covering the hour
+pub fn variant_for(variants: &[TimeVariant], hour: u32) -> Option<&TimeVariant> {
+    variants.iter().find(|v| v.covers(hour))
+}
+
+/// Append the variant for the local hour to the prompt, if the persona has one
+pub fn apply(prompt: String, variants_json: Option<&str>, hour: u32) -> String {
+    match variant_for(&parse_variants(variants_json), hour) {
+        Some(variant) => format!("{}\n\n[СОСТОЯНИЕ СЕЙЧАС]\n{}", prompt, variant.suffix),
+        None => prompt,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variant(from: u32, to: u32, suffix: &str) -> TimeVariant {
+        TimeVariant { from, to, suffix: suffix.to_string() }
+    }
+
+    #[test]
+    fn ranges_wrap_past_midnight() {
+        let night = variant(22, 7, "сонный");
+        assert!(night.covers(23));
+        assert!(night.covers(3));
+        assert!(!night.covers(7));
+        assert!(!variant(18, 22, "бодрый").covers(22));
+    }
+
+    #[test]
+    fn first_matching_variant_wins() {
+        let variants = vec![variant(22, 7, "сонный"), variant(6, 12, "утро")];
+        assert_eq!(variant_for(&variants, 2).map(|v| v.suffix.as_str()), Some("сонный"));
+        assert_eq!(variant_for(&variants, 9).map(|v| v.suffix.as_str()), Some("утро"));
+        assert!(variant_for(&variants, 15).is_none());
+    }
+
+    #[test]
+    fn parses_ranges() {
+        assert_eq!(parse_range("22-7"), Some((22, 7)));
+        assert_eq!(parse_range("18-24"), Some((18, 0)));
+        assert_eq!(parse_range("5-5"), None);
+        assert_eq!(parse_range("25-3"), None);
+    }
+}
@@ -0,0 +1,141 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    db::{ChatTopic, TopicRepository},
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Messages kept per chat for topic extraction
+const BUFFER_SIZE: usize = 40;
+/// A topic older than this no longer counts as current
+const TOPIC_MAX_AGE_HOURS: i64 = 6;
+
+#[derive(Default)]
+struct ChatBuffer {
+    messages: VecDeque<String>,
+    since_extraction: usize,
+}
+
+lazy_static::lazy_static! {
+    /// Recent messages of every chat, whether or not they were answered
+    static ref BUFFERS: Mutex<HashMap<(i64, i64), ChatBuffer>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Deserialize)]
+struct ExtractedTopic {
+    #[serde(default)]
+    topic: String,
+}
+
+/// Remember a message of a chat; returns the recent messages once `every` new ones
+/// have arrived since the last extraction
+fn push_message(account_id: i64, chat_id: i64, text: &str, every: usize) -> Option<Vec<String>> {
+    let mut buffers = BUFFERS.lock().unwrap_or_else(|e| e.into_inner());
+    let buffer = buffers.entry((account_id, chat_id)).or_default();
+
+    buffer.messages.push_back(text.to_string());
+    if buffer.messages.len() > BUFFER_SIZE {
+        buffer.messages.pop_front();
+    }
+    buffer.since_extraction += 1;
+
+    if buffer.since_extraction < every {
+        return None;
+    }
+    buffer.since_extraction = 0;
+    Some(buffer.messages.iter().cloned().collect())
+}
+
+/// Track a chat message and re-extract the chat's topic every TOPIC_EVERY_MESSAGES messages
+pub fn track_message(state: &AppState, account_id: i64, chat_id: i64, text: &str) {
+    let every = state.config.topic_every_messages;
+    if every == 0 || text.trim().is_empty() {
+        return;
+    }
+
+    if let Some(messages) = push_message(account_id, chat_id, text, every) {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = refresh_topic(&state, account_id, chat_id, &messages).await {
+                tracing::warn!("Failed to refresh topic of chat {}: {}", chat_id, e);
+            }
+        });
+    }
+}
+
+/// Ask the model what the chat is discussing and store it
+async fn refresh_topic(state: &AppState, account_id: i64, chat_id: i64, messages: &[String]) -> Result<()> {
+    let transcript = messages.iter().map(|m| format!("- {}", m)).collect::<Vec<_>>().join("\n");
+    let prompt = format!(
+        r#"Below are the latest messages of a Telegram group chat, oldest first. What are people discussing right now? Focus on the most recent messages.
+
+Messages:
+{}
+
+Return JSON: {{"topic": "<the current topic in Russian, up to 12 words, or empty if it is just small talk>"}}"#,
+        transcript
+    );
+
+    let extracted: ExtractedTopic = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.2), num_predict: None }),
+        )
+        .await
+        .context("Failed to extract topic")?;
+    let topic = extracted.topic.trim();
+    if topic.is_empty() {
+        return Ok(());
+    }
+
+    let embedding = crate::ai::generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        topic,
+    )
+    .await?;
+    let bytes = bincode::serialize(&embedding).context("Failed to serialize embedding")?;
+
+    TopicRepository::add(&state.db_pool, account_id, chat_id, topic, &bytes).await?;
+    tracing::debug!("Chat {} of account {} is now discussing: {}", chat_id, account_id, topic);
+    Ok(())
+}
+
+/// The chat's topic, if one was extracted recently enough to still be current
+pub async fn current_topic(state: &AppState, account_id: i64, chat_id: i64) -> Result<Option<ChatTopic>> {
+    let latest = TopicRepository::recent(&state.db_pool, account_id, chat_id, 1).await?;
+    Ok(latest
+        .into_iter()
+        .next()
+        .filter(|t| chrono::Utc::now() - t.created_at < chrono::Duration::hours(TOPIC_MAX_AGE_HOURS)))
+}
+
+/// Embedding of the current topic, for topic-aware retrieval
+pub async fn current_topic_embedding(state: &AppState, account_id: i64, chat_id: i64) -> Option<Vec<f32>> {
+    match current_topic(state, account_id, chat_id).await {
+        Ok(topic) => topic.and_then(|t| bincode::deserialize(&t.embedding).ok()),
+        Err(e) => {
+            tracing::debug!("Failed to read topic of chat {}: {}", chat_id, e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_every_n_messages() {
+        assert_eq!(push_message(-1, -100, "a", 3), None);
+        assert_eq!(push_message(-1, -100, "b", 3), None);
+        assert_eq!(push_message(-1, -100, "c", 3), Some(vec!["a".into(), "b".into(), "c".into()]));
+        assert_eq!(push_message(-1, -100, "d", 3), None);
+    }
+}
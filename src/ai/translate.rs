@@ -0,0 +1,74 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Language /translate uses when none is given
+pub const DEFAULT_TARGET: &str = "en";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Translation {
+    /// Language the model detected, as it names it
+    #[serde(default)]
+    pub source_language: String,
+    pub translation: String,
+}
+
+/// Read the model's JSON answer; an empty translation is no translation
+pub fn parse_translation(json: &str) -> Option<Translation> {
+    clean(serde_json::from_str(json).ok()?)
+}
+
+/// Trim the model's translation; an empty one is no translation
+fn clean(mut translation: Translation) -> Option<Translation> {
+    translation.translation = translation.translation.trim().to_string();
+    translation.source_language = translation.source_language.trim().to_string();
+    if translation.translation.is_empty() {
+        return None;
+    }
+    Some(translation)
+}
+
+/// Translate a text into a language (a code like "en" or a name like "German"), detecting what it is written in
+pub async fn translate(state: &AppState, text: &str, target: &str) -> Result<Translation> {
+    let prompt = format!(
+        r#"Translate the message below into the language "{}". Detect the language it is written in.
+Keep the meaning, tone, slang, emoji, names and links; do not explain or add anything.
+If it is already in that language, return it unchanged.
+
+Message:
+{}
+
+Return JSON: {{"source_language": "<language of the message, ISO 639-1 code>", "translation": "<the translated message>"}}"#,
+        target, text
+    );
+
+    let translation: Translation = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.2), num_predict: None }),
+        )
+        .await
+        .context("Failed to translate")?;
+
+    clean(translation).context("Model returned no translation")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_translations() {
+        let translation = parse_translation(r#"{"source_language": "ru", "translation": " Hello, how are you? "}"#).unwrap();
+        assert_eq!(translation.source_language, "ru");
+        assert_eq!(translation.translation, "Hello, how are you?");
+
+        assert_eq!(parse_translation(r#"{"translation": "Hi"}"#).unwrap().source_language, "");
+        assert!(parse_translation(r#"{"source_language": "en", "translation": "  "}"#).is_none());
+        assert!(parse_translation("not json").is_none());
+    }
+}
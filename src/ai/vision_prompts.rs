@@ -0,0 +1,88 @@
+use crate::db::{Persona, VisionPromptRepository};
+use sqlx::SqlitePool;
+
+/// Media the vision model is asked about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaType {
+    Photo,
+    Animation,
+    VideoNote,
+}
+
+impl MediaType {
+    pub const ALL: [MediaType; 3] = [MediaType::Photo, MediaType::Animation, MediaType::VideoNote];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Photo => "photo",
+            MediaType::Animation => "gif",
+            MediaType::VideoNote => "video_note",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "photo" | "image" => Some(MediaType::Photo),
+            "gif" | "animation" => Some(MediaType::Animation),
+            "video_note" | "circle" => Some(MediaType::VideoNote),
+            _ => None,
+        }
+    }
+
+    /// Built-in prompt used when no override is set
+    pub fn default_template(&self) -> &'static str {
+        match self {
+            MediaType::Photo => "Опиши что на этом изображении. Будь кратким, 1-2 предложения.",
+            MediaType::Animation => "Опиши что происходит в этой гифке/анимации. Будь кратким, 1-2 предложения.",
+            MediaType::VideoNote => "Опиши что происходит в этом видео кружке. Будь кратким, 1-2 предложения.",
+        }
+    }
+}
+
+/// Fill in {{caption}} and {{persona_name}}. A caption is appended when the template
+/// doesn't place it itself, so the vision model always sees what the sender wrote.
+pub fn render(template: &str, caption: Option<&str>, persona_name: Option<&str>) -> String {
+    let caption = caption.map(str::trim).filter(|c| !c.is_empty());
+    let mut prompt = template
+        .replace("{{persona_name}}", persona_name.unwrap_or(""))
+        .replace("{{caption}}", caption.unwrap_or(""));
+
+    if let Some(caption) = caption {
+        if !template.contains("{{caption}}") {
+            prompt.push_str(&format!("\nПодпись отправителя: {}", caption));
+        }
+    }
+    prompt
+}
+
+/// The prompt for a piece of media: the persona's override, the global one, or the built-in default
+pub async fn prompt_for(pool: &SqlitePool, media: MediaType, persona: Option<&Persona>, caption: Option<&str>) -> String {
+    let template = match VisionPromptRepository::resolve(pool, media.as_str(), persona.map(|p| p.id)).await {
+        Ok(Some(template)) => template,
+        Ok(None) => media.default_template().to_string(),
+        Err(e) => {
+            tracing::warn!("Failed to load {} vision prompt, using default: {}", media.as_str(), e);
+            media.default_template().to_string()
+        }
+    };
+
+    render(&template, caption, persona.map(|p| p.name.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_variables() {
+        let prompt = render("Ты {{persona_name}}. Подпись: {{caption}}", Some("мой кот"), Some("Критик"));
+        assert_eq!(prompt, "Ты Критик. Подпись: мой кот");
+    }
+
+    #[test]
+    fn appends_unplaced_caption() {
+        let prompt = render(MediaType::Photo.default_template(), Some(" смотри "), None);
+        assert!(prompt.ends_with("\nПодпись отправителя: смотри"));
+        assert_eq!(render("Опиши", Some("  "), None), "Опиши");
+    }
+}
@@ -0,0 +1,257 @@
+use super::ollama::VisionOptions;
+use std::collections::HashMap;
+
+/// Share of edge pixels above which an image is mostly text, whatever its shape
+const TEXT_HEAVY_DENSITY: f64 = 0.12;
+/// Share of edge pixels that, on a screen-shaped image, marks a screenshot
+const SCREEN_DENSITY: f64 = 0.05;
+
+/// Common desktop and laptop screen resolutions
+const SCREEN_SIZES: &[(i32, i32)] = &[
+    (1280, 720),
+    (1280, 800),
+    (1366, 768),
+    (1440, 900),
+    (1536, 864),
+    (1600, 900),
+    (1680, 1050),
+    (1920, 1080),
+    (1920, 1200),
+    (2560, 1080),
+    (2560, 1440),
+    (2560, 1600),
+    (2880, 1800),
+    (3440, 1440),
+    (3840, 2160),
+];
+
+/// Kinds of media that can be routed to their own vision model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VisionCategory {
+    Photo,
+    Screenshot,
+    Animation,
+    VideoNote,
+}
+
+impl VisionCategory {
+    pub const ALL: [VisionCategory; 4] = [
+        VisionCategory::Photo,
+        VisionCategory::Screenshot,
+        VisionCategory::Animation,
+        VisionCategory::VideoNote,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VisionCategory::Photo => "photo",
+            VisionCategory::Screenshot => "screenshot",
+            VisionCategory::Animation => "gif",
+            VisionCategory::VideoNote => "video_note",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "photo" | "image" => Some(VisionCategory::Photo),
+            "screenshot" | "document" => Some(VisionCategory::Screenshot),
+            "gif" | "animation" => Some(VisionCategory::Animation),
+            "video_note" | "circle" => Some(VisionCategory::VideoNote),
+            _ => None,
+        }
+    }
+}
+
+/// Which vision model handles which media, and how each model is run
+#[derive(Debug, Clone, Default)]
+pub struct VisionRouting {
+    routes: HashMap<VisionCategory, String>,
+    options: HashMap<String, VisionOptions>,
+}
+
+impl VisionRouting {
+    /// Parse VISION_ROUTES ("screenshot=minicpm-v,gif=moondream") and
+    /// VISION_MODEL_OPTIONS ("minicpm-v=400/0.1,moondream=120"): tokens, then an optional temperature
+    pub fn parse(routes: &str, options: &str) -> Result<Self, String> {
+        let mut routing = VisionRouting::default();
+
+        for entry in routes.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (category, model) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{}' is not <category>=<model>", entry))?;
+            let category = VisionCategory::parse(category)
+                .ok_or_else(|| format!("unknown media category '{}' (photo, screenshot, gif, video_note)", category))?;
+            let model = model.trim();
+            if model.is_empty() {
+                return Err(format!("no model for {}", category.as_str()));
+            }
+            routing.routes.insert(category, model.to_string());
+        }
+
+        for entry in options.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            // Model names may contain ':' and '=' never, so split at the last '='
+            let (model, settings) = entry
+                .rsplit_once('=')
+                .ok_or_else(|| format!("'{}' is not <model>=<tokens>[/<temperature>]", entry))?;
+            let (tokens, temperature) = match settings.split_once('/') {
+                Some((tokens, temperature)) => (tokens, Some(temperature)),
+                None => (settings, None),
+            };
+            let num_predict = match tokens.trim() {
+                "" | "-" => None,
+                tokens => Some(tokens.parse::<i64>().map_err(|_| format!("bad token limit '{}' for {}", tokens, model))?),
+            };
+            let temperature = match temperature.map(str::trim) {
+                None | Some("") | Some("-") => None,
+                Some(t) => Some(t.parse::<f64>().map_err(|_| format!("bad temperature '{}' for {}", t, model))?),
+            };
+            routing.options.insert(model.trim().to_string(), VisionOptions { num_predict, temperature });
+        }
+
+        Ok(routing)
+    }
+
+    /// The model for a category, falling back to OLLAMA_VISION_MODEL
+    pub fn model_for<'a>(&'a self, category: VisionCategory, default: &'a str) -> &'a str {
+        match self.routes.get(&category) {
+            Some(model) => model,
+            // Screenshots are photos unless routed on their own
+            None if category == VisionCategory::Screenshot => self.model_for(VisionCategory::Photo, default),
+            None => default,
+        }
+    }
+
+    pub fn options_for(&self, model: &str) -> Option<VisionOptions> {
+        self.options.get(model).copied()
+    }
+
+    /// Telling screenshots apart costs an ffmpeg run, so only do it when they go elsewhere
+    pub fn routes_screenshots(&self) -> bool {
+        self.routes.contains_key(&VisionCategory::Screenshot)
+    }
+
+    /// Routed models other than the default one
+    pub fn models(&self) -> Vec<&str> {
+        let mut models: Vec<&str> = self.routes.values().map(String::as_str).collect();
+        models.sort_unstable();
+        models.dedup();
+        models
+    }
+
+    /// "screenshot → minicpm-v (400 tokens, t=0.1)" lines for every category
+    pub fn describe(&self, default: &str) -> Vec<String> {
+        VisionCategory::ALL
+            .iter()
+            .map(|category| {
+                let model = self.model_for(*category, default);
+                let mut line = format!("{} → {}", category.as_str(), model);
+                if let Some(options) = self.options_for(model) {
+                    let mut settings = Vec::new();
+                    if let Some(tokens) = options.num_predict {
+                        settings.push(format!("{} tokens", tokens));
+                    }
+                    if let Some(temperature) = options.temperature {
+                        settings.push(format!("t={}", temperature));
+                    }
+                    if !settings.is_empty() {
+                        line.push_str(&format!(" ({})", settings.join(", ")));
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+}
+
+/// Screen-shaped (a phone held upright or a common monitor resolution) and full of fine edges,
+/// or so full of edges that it's mostly text whatever its shape
+pub fn looks_like_screenshot(width: i32, height: i32, edge_density: f64) -> bool {
+    if width <= 0 || height <= 0 {
+        return false;
+    }
+    let tall_phone = height as f64 / width as f64 >= 1.9;
+    let monitor = SCREEN_SIZES.contains(&(width, height));
+    edge_density >= TEXT_HEAVY_DENSITY || ((tall_phone || monitor) && edge_density >= SCREEN_DENSITY)
+}
+
+/// Share of edge pixels in an image, a cheap stand-in for how much text it holds
+pub async fn edge_density(path: &str) -> Option<f64> {
+    let output = tokio::process::Command::new("ffmpeg")
+        .args([
+            "-v",
+            "info",
+            "-i",
+            path,
+            "-vf",
+            "scale=480:-2,format=gray,edgedetect=low=0.1:high=0.3,signalstats,metadata=print:key=lavfi.signalstats.YAVG",
+            "-frames:v",
+            "1",
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .await
+        .ok()?;
+
+    // Edges are white on black, so the mean brightness is the share of edge pixels
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .find_map(|line| line.split("lavfi.signalstats.YAVG=").nth(1))
+        .and_then(|value| value.trim().parse::<f64>().ok())
+        .map(|mean| mean / 255.0)
+}
+
+/// Photo or screenshot, by shape first and edge density only when the shape leaves it open
+pub async fn classify_photo(routing: &VisionRouting, width: i32, height: i32, path: &str) -> VisionCategory {
+    if !routing.routes_screenshots() {
+        return VisionCategory::Photo;
+    }
+    match edge_density(path).await {
+        Some(density) if looks_like_screenshot(width, height, density) => VisionCategory::Screenshot,
+        _ => VisionCategory::Photo,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_routes_and_model_options() {
+        let routing = VisionRouting::parse("screenshot=minicpm-v, gif = moondream", "minicpm-v=400/0.1,llava:13b=-/0.7").unwrap();
+        assert_eq!(routing.model_for(VisionCategory::Screenshot, "llava"), "minicpm-v");
+        assert_eq!(routing.model_for(VisionCategory::Animation, "llava"), "moondream");
+        assert_eq!(routing.model_for(VisionCategory::VideoNote, "llava"), "llava");
+        assert_eq!(
+            routing.options_for("minicpm-v"),
+            Some(VisionOptions { num_predict: Some(400), temperature: Some(0.1) })
+        );
+        assert_eq!(
+            routing.options_for("llava:13b"),
+            Some(VisionOptions { num_predict: None, temperature: Some(0.7) })
+        );
+        assert!(VisionRouting::parse("memes=llava", "").is_err());
+        assert!(VisionRouting::parse("", "llava=many").is_err());
+    }
+
+    #[test]
+    fn unrouted_screenshots_follow_photos() {
+        let routing = VisionRouting::parse("photo=llava:13b", "").unwrap();
+        assert_eq!(routing.model_for(VisionCategory::Screenshot, "llava"), "llava:13b");
+        assert!(!routing.routes_screenshots());
+    }
+
+    #[test]
+    fn spots_screenshots_by_shape_and_text() {
+        // Phone screenshot with a chat on it
+        assert!(looks_like_screenshot(1080, 2400, 0.07));
+        // Monitor-sized capture of a document
+        assert!(looks_like_screenshot(1920, 1080, 0.06));
+        // A landscape photo of the same size but smooth
+        assert!(!looks_like_screenshot(1920, 1080, 0.02));
+        // A scanned page, any shape
+        assert!(looks_like_screenshot(1200, 1600, 0.15));
+        assert!(!looks_like_screenshot(1280, 960, 0.06));
+    }
+}
@@ -1,7 +1,137 @@
 use anyhow::{Context, Result};
 use reqwest::multipart;
 use serde::Deserialize;
-use std::path::Path;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+/// Per-request timeout of a client made with `new`
+const REQUEST_TIMEOUT_SECS: u64 = 60;
+
+/// Transcription queue counters since startup
+#[derive(Debug, Default)]
+pub struct WhisperMetrics {
+    /// Transcriptions waiting for a free slot right now
+    pub waiting: AtomicU64,
+    /// Transcriptions running right now
+    pub running: AtomicU64,
+    pub completed: AtomicU64,
+    pub failures: AtomicU64,
+    /// Gave up after WHISPER_TIMEOUT_SECONDS in the queue or on one request
+    pub timeouts: AtomicU64,
+    /// Waits long enough to send a placeholder
+    pub slow_waits: AtomicU64,
+    pub total_wait_ms: AtomicU64,
+    pub max_wait_ms: AtomicU64,
+}
+
+impl WhisperMetrics {
+    pub fn avg_wait_ms(&self) -> u64 {
+        let started = self.completed.load(Ordering::Relaxed) + self.failures.load(Ordering::Relaxed);
+        self.total_wait_ms.load(Ordering::Relaxed).checked_div(started).unwrap_or(0)
+    }
+}
+
+lazy_static::lazy_static! {
+    pub static ref WHISPER_METRICS: WhisperMetrics = WhisperMetrics::default();
+}
+
+/// Decrements a gauge when the wait or run it tracks ends, however it ends
+struct Gauge(&'static AtomicU64);
+
+impl Gauge {
+    fn enter(counter: &'static AtomicU64) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Gauge(counter)
+    }
+}
+
+impl Drop for Gauge {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Run a transcription once one of the `slots` is free.
+///
+/// `wait_timeout` covers only the queue wait: a long recording makes many
+/// requests, so the work bounds each of them itself. If the wait takes longer
+/// than `placeholder_after`, `on_slow_wait` runs once while still waiting.
+pub async fn run_queued<T>(
+    slots: &Semaphore,
+    wait_timeout: Duration,
+    placeholder_after: Option<Duration>,
+    on_slow_wait: impl Future<Output = ()>,
+    work: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let metrics = &*WHISPER_METRICS;
+
+    let queued_at = Instant::now();
+    let waiting = Gauge::enter(&metrics.waiting);
+    let wait = async {
+        let acquire = slots.acquire();
+        tokio::pin!(acquire);
+        match placeholder_after {
+            Some(after) => tokio::select! {
+                permit = &mut acquire => permit,
+                _ = tokio::time::sleep(after) => {
+                    metrics.slow_waits.fetch_add(1, Ordering::Relaxed);
+                    on_slow_wait.await;
+                    acquire.await
+                }
+            },
+            None => acquire.await,
+        }
+        .context("Transcription queue closed")
+    };
+
+    let permit = match tokio::time::timeout(wait_timeout, wait).await {
+        Ok(Ok(permit)) => permit,
+        Ok(Err(e)) => {
+            metrics.failures.fetch_add(1, Ordering::Relaxed);
+            return Err(e);
+        }
+        Err(_) => {
+            metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+            anyhow::bail!("Transcription queue wait timed out after {}s", wait_timeout.as_secs())
+        }
+    };
+    drop(waiting);
+
+    let wait_ms = queued_at.elapsed().as_millis() as u64;
+    metrics.total_wait_ms.fetch_add(wait_ms, Ordering::Relaxed);
+    metrics.max_wait_ms.fetch_max(wait_ms, Ordering::Relaxed);
+
+    let running = Gauge::enter(&metrics.running);
+    let result = work.await;
+    drop(running);
+    drop(permit);
+
+    match result {
+        Ok(text) => {
+            metrics.completed.fetch_add(1, Ordering::Relaxed);
+            Ok(text)
+        }
+        Err(e) if is_timeout(&e) => {
+            metrics.timeouts.fetch_add(1, Ordering::Relaxed);
+            Err(e)
+        }
+        Err(e) => {
+            metrics.failures.fetch_add(1, Ordering::Relaxed);
+            Err(e)
+        }
+    }
+}
+
+/// Whether a transcription failed because a Whisper request timed out
+fn is_timeout(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| cause.downcast_ref::<reqwest::Error>().is_some_and(|e| e.is_timeout()))
+}
 
 /// Whisper API client for audio transcription
 pub struct WhisperClient {
@@ -11,9 +141,14 @@ pub struct WhisperClient {
 
 impl WhisperClient {
     pub fn new(base_url: String) -> Self {
+        Self::with_timeout(base_url, Duration::from_secs(REQUEST_TIMEOUT_SECS))
+    }
+
+    /// Client whose requests each give up after `timeout`
+    pub fn with_timeout(base_url: String, timeout: Duration) -> Self {
         Self {
             base_url,
-            client: reqwest::Client::new(),
+            client: reqwest::Client::builder().timeout(timeout).build().unwrap_or_default(),
         }
     }
 
@@ -74,3 +209,183 @@ pub async fn transcribe_audio(whisper_url: &str, audio_path: &Path) -> Result<St
     let client = WhisperClient::new(whisper_url.to_string());
     client.transcribe(audio_path).await
 }
+
+/// Transcribe a long recording by splitting it into `chunk_secs` segments.
+///
+/// `duration_secs` is the length Telegram reports; only when it is unknown (0)
+/// is the file probed, and a failed probe sends it in one go. Recordings that
+/// fit in one chunk are sent as-is. Each request gives up after
+/// `segment_timeout`. `on_progress` is called with (done, total) after each segment.
+pub async fn transcribe_chunked(
+    whisper_url: &str,
+    audio_path: &Path,
+    duration_secs: u32,
+    chunk_secs: u32,
+    segment_timeout: Duration,
+    on_progress: impl Fn(usize, usize),
+) -> Result<String> {
+    let duration = if duration_secs > 0 {
+        duration_secs as f64
+    } else {
+        audio_duration(audio_path).await.unwrap_or_else(|e| {
+            tracing::debug!("Failed to probe {}, transcribing it whole: {:#}", audio_path.display(), e);
+            0.0
+        })
+    };
+    let client = WhisperClient::with_timeout(whisper_url.to_string(), segment_timeout);
+    if duration <= chunk_secs as f64 {
+        return client.transcribe(audio_path).await;
+    }
+
+    // Removed when this is dropped, also when the caller gives up mid-way
+    let segments = split_audio(audio_path, chunk_secs).await?;
+    let total = segments.paths.len();
+    let mut parts = Vec::with_capacity(total);
+
+    for (i, segment) in segments.paths.iter().enumerate() {
+        let text = client
+            .transcribe(segment)
+            .await
+            .with_context(|| format!("Failed to transcribe segment {}/{}", i + 1, total))?;
+        parts.push(text.trim().to_string());
+        on_progress(i + 1, total);
+    }
+
+    Ok(parts
+        .into_iter()
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// Duration of an audio file in seconds (via ffprobe)
+pub async fn audio_duration(audio_path: &Path) -> Result<f64> {
+    let output = Command::new("ffprobe")
+        .args(["-v", "error", "-show_entries", "format=duration", "-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(audio_path)
+        .output()
+        .await
+        .context("Failed to run ffprobe")?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Failed to parse audio duration")
+}
+
+/// Segment files of one recording in /tmp, deleted on drop
+struct Segments {
+    prefix: String,
+    paths: Vec<PathBuf>,
+}
+
+impl Segments {
+    fn path(&self, i: usize) -> PathBuf {
+        PathBuf::from(format!("{}_{:03}.ogg", self.prefix, i))
+    }
+
+    /// Pick up the files ffmpeg wrote so far
+    fn collect(&mut self) {
+        self.paths = (0..).map(|i| self.path(i)).take_while(|p| p.exists()).collect();
+    }
+}
+
+impl Drop for Segments {
+    fn drop(&mut self) {
+        // ffmpeg may have been stopped before `collect` ran
+        self.collect();
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Split audio into `chunk_secs` segments without re-encoding (via ffmpeg)
+async fn split_audio(audio_path: &Path, chunk_secs: u32) -> Result<Segments> {
+    let mut segments = Segments {
+        prefix: format!("/tmp/voice_{}_{}", std::process::id(), rand::random::<u32>()),
+        paths: Vec::new(),
+    };
+    let pattern = format!("{}_%03d.ogg", segments.prefix);
+
+    let output = Command::new("ffmpeg")
+        .arg("-i")
+        .arg(audio_path)
+        .args(["-f", "segment", "-segment_time", &chunk_secs.to_string(), "-c", "copy", &pattern])
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to run ffmpeg")?;
+
+    if !output.status.success() {
+        anyhow::bail!("ffmpeg failed: {}", String::from_utf8_lossy(&output.stderr));
+    }
+
+    segments.collect();
+    if segments.paths.is_empty() {
+        anyhow::bail!("ffmpeg produced no segments");
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn waits_for_a_free_slot_and_reports_slow_waits() {
+        let slots = Semaphore::new(1);
+        let held = slots.acquire().await.unwrap();
+
+        let slow = std::sync::atomic::AtomicBool::new(false);
+        let queued = run_queued(
+            &slots,
+            Duration::from_secs(5),
+            Some(Duration::from_millis(10)),
+            async { slow.store(true, Ordering::Relaxed) },
+            async { Ok("text") },
+        );
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(held);
+        };
+
+        let (result, _) = tokio::join!(queued, release);
+        assert_eq!(result.unwrap(), "text");
+        assert!(slow.load(Ordering::Relaxed));
+    }
+
+    #[tokio::test]
+    async fn times_out_while_queued() {
+        let slots = Semaphore::new(1);
+        let _held = slots.acquire().await.unwrap();
+
+        let result = run_queued(&slots, Duration::from_millis(20), None, async {}, async { Ok(()) }).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn long_work_is_not_cut_by_the_wait_timeout() {
+        let slots = Semaphore::new(1);
+        let work = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok("text")
+        };
+
+        let result = run_queued(&slots, Duration::from_millis(20), None, async {}, work).await;
+        assert_eq!(result.unwrap(), "text");
+    }
+
+    #[test]
+    fn removes_segments_on_drop() {
+        let segments = Segments { prefix: format!("/tmp/voice_test_{}", rand::random::<u32>()), paths: Vec::new() };
+        let paths = [segments.path(0), segments.path(1)];
+        for path in &paths {
+            std::fs::write(path, b"ogg").unwrap();
+        }
+
+        drop(segments);
+        assert!(paths.iter().all(|p| !p.exists()));
+    }
+}
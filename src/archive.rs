@@ -0,0 +1,74 @@
+use crate::{db::MessageRepository, AppState};
+use anyhow::Result;
+use chrono::{NaiveDate, Timelike};
+
+/// How often the worker checks whether the archiving hour has come
+const CHECK_INTERVAL_SECS: u64 = 10 * 60;
+/// Messages moved per transaction, so a large backlog doesn't hold the database for long
+const ARCHIVE_BATCH_SIZE: i64 = 2000;
+
+/// Whether the nightly run is due: the archiving hour has come and it hasn't run today
+pub fn is_due(now: chrono::NaiveDateTime, hour: u32, last_run: Option<NaiveDate>) -> bool {
+    now.hour() == hour && last_run != Some(now.date())
+}
+
+/// Move messages older than ARCHIVE_AFTER_DAYS to the archive tier once a night
+pub async fn archive_worker(state: AppState) {
+    let days = match state.config.archive_after_days {
+        Some(days) => days,
+        None => return,
+    };
+    tracing::info!("Archive worker started (messages older than {} days)", days);
+
+    let mut last_run = None;
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let now = chrono::Utc::now().with_timezone(&state.config.default_timezone).naive_local();
+        if !is_due(now, state.config.archive_hour, last_run) {
+            continue;
+        }
+        last_run = Some(now.date());
+
+        if let Err(e) = archive_messages(&state, days).await {
+            tracing::error!("Archive run failed: {}", e);
+        }
+    }
+}
+
+/// One archiving pass; returns how many messages were moved
+pub async fn archive_messages(state: &AppState, days: i64) -> Result<u64> {
+    let mut moved = 0;
+    loop {
+        let batch = MessageRepository::archive_older_than(&state.db_pool, days, ARCHIVE_BATCH_SIZE).await?;
+        if batch == 0 {
+            break;
+        }
+        moved += batch;
+        // Let replies get at the database between batches
+        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
+    }
+
+    if moved > 0 {
+        let total = MessageRepository::count_archived(&state.db_pool).await?;
+        tracing::info!("Archived {} messages ({} in the archive)", moved, total);
+    }
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_once_in_the_archiving_hour() {
+        let at = |h: u32, m: u32| NaiveDate::from_ymd_opt(2026, 3, 2).unwrap().and_hms_opt(h, m, 0).unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 3, 2);
+        let yesterday = NaiveDate::from_ymd_opt(2026, 3, 1);
+
+        assert!(is_due(at(4, 5), 4, None));
+        assert!(is_due(at(4, 5), 4, yesterday));
+        assert!(!is_due(at(4, 15), 4, today));
+        assert!(!is_due(at(5, 0), 4, yesterday));
+    }
+}
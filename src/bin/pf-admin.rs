@@ -0,0 +1,251 @@
+//! Maintenance without Telegram: works straight on the database (and Ollama where needed),
+//! so it still helps when the bot itself can't start.
+
+use anyhow::{Context, Result};
+use puppeteer::{
+    ai::{persona_files, rag},
+    db::{self, integrity, ChatRepository, PersonaRepository},
+    AppState, Config,
+};
+use sqlx::SqlitePool;
+use std::path::{Path, PathBuf};
+
+/// Memories shown by `rag`
+const RAG_TOP_N: usize = 10;
+
+const USAGE: &str = "Usage: pf-admin <command>
+
+  doctor                               check the database, migrations, config and Ollama models
+  migrate                              run pending migrations (with the startup preflight)
+  personas list                        list personas with their file versions
+  personas export <dir> [name]         write personas as PERSONAS_DIR files
+  personas import <dir|file>           import persona files
+  rag <account_id> <chat_id> <query>   show the memories a message would retrieve
+  prune <account_id> <chat_id>         keep the newest memories of a chat
+  prune --older-than <days>            delete memories older than N days everywhere
+  chats <account_id>                   list an account's chats
+  chat <account_id> <chat_id>          show a chat's settings
+
+DATABASE_URL (or .env) selects the database, default sqlite:data/puppeteer.db";
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenvy::dotenv().ok();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["doctor"] => doctor().await,
+        ["migrate"] => {
+            db::init_db(&database_url()).await?;
+            println!("Database is up to date");
+            Ok(())
+        }
+        ["personas", "list"] => list_personas(&connect().await?).await,
+        ["personas", "export", dir] => export_personas(&connect().await?, Path::new(dir), None).await,
+        ["personas", "export", dir, name] => export_personas(&connect().await?, Path::new(dir), Some(*name)).await,
+        ["personas", "import", path] => import_personas(&connect().await?, Path::new(path)).await,
+        ["rag", account_id, chat_id, query @ ..] if !query.is_empty() => {
+            query_memories(id(account_id)?, id(chat_id)?, &query.join(" ")).await
+        }
+        ["prune", "--older-than", days] => {
+            let days: i64 = days.parse().context("Days must be a number")?;
+            let deleted = rag::purge_memories_older_than(&connect().await?, days).await?;
+            println!("Deleted {} memories older than {} days", deleted, days);
+            Ok(())
+        }
+        ["prune", account_id, chat_id] => {
+            rag::cleanup_old_memories(&connect().await?, id(account_id)?, id(chat_id)?).await?;
+            println!("Pruned memories of chat {}", chat_id);
+            Ok(())
+        }
+        ["chats", account_id] => list_chats(&connect().await?, id(account_id)?).await,
+        ["chat", account_id, chat_id] => show_chat(&connect().await?, id(account_id)?, id(chat_id)?).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+fn database_url() -> String {
+    std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:data/puppeteer.db".to_string())
+}
+
+/// Open the database as it is: no preflight, no migrations
+async fn connect() -> Result<SqlitePool> {
+    db::connect(&database_url()).await
+}
+
+fn id(value: &str) -> Result<i64> {
+    value.parse().with_context(|| format!("'{}' is not an ID", value))
+}
+
+fn report(ok: bool, check: &str, details: &[String]) {
+    println!("{} {}", if ok { "✅" } else { "❌" }, check);
+    for line in details {
+        println!("   {}", line);
+    }
+}
+
+/// Every startup check, reported instead of aborting at the first failure
+async fn doctor() -> Result<()> {
+    let url = database_url();
+    let mut healthy = true;
+
+    let database = match db::connect(&url).await {
+        Ok(pool) => {
+            let integrity = integrity::integrity_problems(&pool).await?;
+            healthy &= integrity.is_empty();
+            report(integrity.is_empty(), "Database integrity", &integrity);
+
+            let violations = integrity::foreign_key_problems(&pool).await?;
+            healthy &= violations.is_empty();
+            report(violations.is_empty(), "Foreign keys", &violations);
+
+            let migrations = integrity::migration_report(&pool, db::migrator()).await?;
+            let problems = migrations.problems();
+            healthy &= problems.is_empty();
+            report(problems.is_empty(), "Migration history", &problems);
+            if !migrations.pending.is_empty() {
+                println!("ℹ️  Pending migrations {:?}, applied at the next start (or `pf-admin migrate`)", migrations.pending);
+            }
+            Some(pool)
+        }
+        Err(e) => {
+            healthy = false;
+            report(false, &format!("Database {}", url), &[format!("{:#}", e)]);
+            None
+        }
+    };
+
+    match Config::from_env() {
+        Ok(config) => {
+            report(true, "Configuration", &[]);
+            // Stored embeddings are checked against the embedding model, so this needs the database
+            match database {
+                Some(pool) => match puppeteer::ai::validate_models(&AppState::new(config, pool)).await {
+                    Ok(()) => report(true, "Ollama models", &[]),
+                    Err(e) => {
+                        healthy = false;
+                        report(false, "Ollama models", &[format!("{:#}", e)]);
+                    }
+                },
+                None => report(false, "Ollama models", &["Skipped: the database could not be opened".to_string()]),
+            }
+        }
+        Err(e) => {
+            healthy = false;
+            report(false, "Configuration", &[format!("{:#}", e)]);
+        }
+    }
+
+    if !healthy {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+async fn list_personas(pool: &SqlitePool) -> Result<()> {
+    for persona in PersonaRepository::list_all(pool).await? {
+        let tags = PersonaRepository::list_tags(pool, persona.id).await?;
+        println!(
+            "{:>4}  {:<24} file v{}  weight {}  memory {}{}",
+            persona.id,
+            persona.name,
+            persona.file_version.map_or("-".to_string(), |v| v.to_string()),
+            persona.rotation_weight,
+            persona.memory_write,
+            if tags.is_empty() { String::new() } else { format!("  [{}]", tags.join(", ")) }
+        );
+    }
+    Ok(())
+}
+
+async fn export_personas(pool: &SqlitePool, dir: &Path, name: Option<&str>) -> Result<()> {
+    let personas = PersonaRepository::list_all(pool).await?;
+    let selected: Vec<_> = personas.iter().filter(|p| name.map_or(true, |n| p.name == n)).collect();
+    if selected.is_empty() {
+        anyhow::bail!("No persona named '{}'", name.unwrap_or_default());
+    }
+
+    tokio::fs::create_dir_all(dir)
+        .await
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    for persona in selected {
+        let tags = PersonaRepository::list_tags(pool, persona.id).await?;
+        let base = persona
+            .base_id
+            .and_then(|base_id| personas.iter().find(|p| p.id == base_id))
+            .map(|p| p.name.clone());
+        let file = persona_files::PersonaFile::from_persona(persona, tags, base)
+            .with_context(|| format!("Failed to export '{}'", persona.name))?;
+        let path = dir.join(format!("{}.json", persona.name));
+        tokio::fs::write(&path, serde_json::to_string_pretty(&file)? + "\n")
+            .await
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("{} → {}", persona.name, path.display());
+    }
+    Ok(())
+}
+
+async fn import_personas(pool: &SqlitePool, path: &Path) -> Result<()> {
+    let report = if path.is_dir() {
+        persona_files::import_dir(pool, path).await?
+    } else {
+        persona_files::import_files(pool, vec![PathBuf::from(path)]).await?
+    };
+
+    println!("{}", report.summary());
+    for name in report.created.iter().chain(&report.updated) {
+        println!("  imported {}", name);
+    }
+    for name in &report.older {
+        println!("  kept {}: the database has a newer version", name);
+    }
+    for (file, error) in &report.failed {
+        println!("  {} failed: {}", file, error);
+    }
+    Ok(())
+}
+
+async fn query_memories(account_id: i64, chat_id: i64, query: &str) -> Result<()> {
+    let config = Config::from_env().context("RAG queries need the Ollama settings from .env")?;
+    let pool = connect().await?;
+    let chat = ChatRepository::get(&pool, account_id, chat_id).await?;
+    let filter = rag::RetrievalFilter::for_chat(chat.as_ref(), None);
+
+    let embedding =
+        rag::generate_embedding(&reqwest::Client::new(), &config.ollama_url, &config.ollama_embed_model, query).await?;
+    let memories = rag::retrieve_memories(&pool, account_id, chat_id, &embedding, RAG_TOP_N, &filter).await?;
+
+    println!("Filter: {}", filter.describe());
+    if memories.is_empty() {
+        println!("No memories");
+    }
+    for memory in memories {
+        println!("{:.3}  {:?}  {}", memory.similarity, memory.tier, memory.content);
+    }
+    Ok(())
+}
+
+async fn list_chats(pool: &SqlitePool, account_id: i64) -> Result<()> {
+    for chat in ChatRepository::list_for_account(pool, account_id).await? {
+        println!(
+            "{:>16}  {:<8} {}  {}",
+            chat.chat_id,
+            chat.chat_type,
+            if chat.is_denied { "denied " } else { "allowed" },
+            chat.title
+        );
+    }
+    Ok(())
+}
+
+async fn show_chat(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<()> {
+    let chat = ChatRepository::get(pool, account_id, chat_id)
+        .await?
+        .with_context(|| format!("Account {} has no chat {}", account_id, chat_id))?;
+    println!("{}", serde_json::to_string_pretty(&chat)?);
+    Ok(())
+}
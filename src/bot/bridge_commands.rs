@@ -0,0 +1,168 @@
+use crate::{
+    ai::translate,
+    bot::handlers::html_escape,
+    db::{AccountRepository, ChatBridgeRepository},
+    AppState,
+};
+use teloxide::{prelude::*, types::ParseMode};
+
+/// Translate the message replied to, detecting its language
+/// Usage: /translate [language] (in reply to a message)
+pub async fn handle_translate(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = match msg.reply_to_message().and_then(|m| m.text().or_else(|| m.caption())) {
+        Some(text) => text.to_string(),
+        None => {
+            bot.send_message(msg.chat.id, "❌ Reply to a message with /translate [language], e.g. /translate de")
+                .await?;
+            return Ok(());
+        }
+    };
+    let target = match args.is_empty() {
+        true => translate::DEFAULT_TARGET.to_string(),
+        false => args.join(" "),
+    };
+
+    let translation = match translate::translate(&state, &text, &target).await {
+        Ok(translation) => translation,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ Failed to translate: {}", html_escape(&e.to_string())))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let from = match translation.source_language.as_str() {
+        "" => "?".to_string(),
+        language => language.to_string(),
+    };
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🌐 <i>{} → {}</i>\n{}",
+            html_escape(&from),
+            html_escape(&target),
+            html_escape(&translation.translation)
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Chats an account mirrors into each other
+/// Usage: /bridges <account_id>
+pub async fn handle_bridges(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /bridges <account_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let bridges = ChatBridgeRepository::list(&state.db_pool, account_id).await?;
+    if bridges.is_empty() {
+        bot.send_message(msg.chat.id, format!("🌉 Account {} has no bridges. Add one with /bridge_add", account_id))
+            .await?;
+        return Ok(());
+    }
+
+    let mut text = format!("🌉 <b>Bridges of account {}</b>\n\n", account_id);
+    for bridge in &bridges {
+        text.push_str(&format!(
+            "#{} <code>{}</code> ({}) ⇄ <code>{}</code> ({})\n",
+            bridge.id,
+            bridge.chat_a,
+            html_escape(&bridge.lang_a),
+            bridge.chat_b,
+            html_escape(&bridge.lang_b)
+        ));
+    }
+    text.push_str("\n/bridge_remove &lt;id&gt; to stop mirroring");
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Mirror two chats into each other, translating each message into the other chat's language
+/// Usage: /bridge_add <account_id> <chat_a> <chat_b> <lang_a> <lang_b>
+pub async fn handle_bridge_add(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /bridge_add <account_id> <chat_a> <chat_b> <lang_a> <lang_b>\n\n\
+Messages from chat_a are posted in chat_b translated into lang_b, and back.\n\
+Example: /bridge_add 1 -100123 -100456 ru en";
+    let ids: Vec<Option<i64>> = args.iter().take(3).map(|a| a.parse::<i64>().ok()).collect();
+    let (account_id, chat_a, chat_b, lang_a, lang_b) = match (ids.as_slice(), args.get(3), args.get(4)) {
+        ([Some(account_id), Some(chat_a), Some(chat_b)], Some(lang_a), Some(lang_b)) if chat_a != chat_b => {
+            (*account_id, *chat_a, *chat_b, lang_a, lang_b)
+        }
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Account {} not found", account_id)).await?;
+        return Ok(());
+    }
+    let existing = ChatBridgeRepository::for_chat(&state.db_pool, account_id, chat_a).await?;
+    if let Some(bridge) = existing.iter().find(|b| b.chat_a == chat_b || b.chat_b == chat_b) {
+        bot.send_message(
+            msg.chat.id,
+            format!("❌ Chats {} and {} are already bridged (#{}). Remove it first with /bridge_remove", chat_a, chat_b, bridge.id),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let id = ChatBridgeRepository::add(&state.db_pool, account_id, chat_a, chat_b, lang_a, lang_b).await?;
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Bridge #{} added: {} ({}) ⇄ {} ({})\nOnly chats the account is allowed in are read.",
+            id, chat_a, lang_a, chat_b, lang_b
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Stop mirroring two chats
+/// Usage: /bridge_remove <bridge_id>
+pub async fn handle_bridge_remove(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /bridge_remove <bridge_id> (see /bridges)").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if ChatBridgeRepository::remove(&state.db_pool, id).await? {
+        format!("🗑 Bridge #{} removed", id)
+    } else {
+        format!("❌ Bridge #{} not found", id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use crate::{
+    bot::handlers::html_escape,
+    business,
+    db::{BusinessRepository, PersonaRepository},
+    AppState,
+};
+use teloxide::{prelude::*, types::ParseMode};
+
+/// List connected business accounts and their auto-responder settings
+/// Usage: /business
+pub async fn handle_business(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if state.config.business_bot_token.is_none() {
+        bot.send_message(msg.chat.id, "💼 Business mode is off. Set BUSINESS_BOT_TOKEN to a separate bot and restart.")
+            .await?;
+        return Ok(());
+    }
+
+    let connections = BusinessRepository::list(&state.db_pool).await?;
+    if connections.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "💼 No business accounts connected yet. Add the business bot in Telegram: \
+             Settings → Business → Chatbots.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut response = "💼 <b>Business accounts</b>\n".to_string();
+    for connection in &connections {
+        let status = if !connection.is_enabled {
+            "⛔ disconnected"
+        } else if !connection.can_reply {
+            "👁 read-only"
+        } else if connection.auto_reply {
+            "✅ replying"
+        } else {
+            "⏸ replies off"
+        };
+        let days = connection
+            .get_work_days()
+            .iter()
+            .map(|d| crate::userbot::digest::WEEKDAYS[*d as usize])
+            .collect::<Vec<_>>()
+            .join(",");
+        let working = if business::is_working_now(&state, connection) { "working hours" } else { "off hours" };
+
+        response.push_str(&format!(
+            "\n<b>User {}</b> — {}\n   Mode: {} | Persona: {}\n   Hours: {}:00–{}:00 {} ({}), now {}\n",
+            connection.user_id,
+            status,
+            connection.reply_mode,
+            connection.persona_id.map(|id| format!("#{}", id)).unwrap_or_else(|| "default".to_string()),
+            connection.work_start_hour,
+            connection.work_end_hour,
+            days,
+            html_escape(connection.timezone.as_deref().unwrap_or(state.config.default_timezone.name())),
+            working
+        ));
+    }
+    response.push_str(
+        "\nChange with /business_set &lt;user_id&gt; reply=on|off mode=away|always persona=&lt;id&gt;|none \
+         hours=9-18 days=mon-fri tz=Area/City|default",
+    );
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Change the auto-responder settings of a business account
+/// Usage: /business_set <user_id> <key=value> ...
+pub async fn handle_business_set(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) if args.len() > 1 => id,
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /business_set <user_id> [reply=on|off] [mode=away|always] [persona=<id>|none] \
+                 [hours=9-18] [days=mon-fri] [tz=Area/City|default]",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut connection = match BusinessRepository::get_by_user(&state.db_pool, user_id).await? {
+        Some(connection) => connection,
+        None => {
+            bot.send_message(msg.chat.id, "❌ No business connection for this user, see /business").await?;
+            return Ok(());
+        }
+    };
+
+    for arg in &args[1..] {
+        let result = match arg.split_once('=') {
+            Some((key, value)) => business::apply_setting(&mut connection, key, value),
+            None => Err(format!("expected key=value, got '{}'", arg)),
+        };
+        if let Err(e) = result {
+            bot.send_message(msg.chat.id, format!("❌ {}", e)).await?;
+            return Ok(());
+        }
+    }
+
+    if let Some(persona_id) = connection.persona_id {
+        if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    }
+
+    BusinessRepository::update_settings(&state.db_pool, &connection).await?;
+
+    let warning = if connection.auto_reply && !connection.can_reply {
+        "\n⚠️ The account didn't allow the bot to reply; enable it in Telegram's business chatbot settings."
+    } else {
+        ""
+    };
+    bot.send_message(msg.chat.id, format!("✅ Business settings of user {} saved{}", user_id, warning))
+        .await?;
+    Ok(())
+}
@@ -1,14 +1,31 @@
 use crate::{
-    bot::AddAccountDialogue,
-    db::AccountRepository,
+    bot::{dialogues, handlers::html_escape, AddAccountDialogue, AddAccountState},
+    db::{Account, AccountRepository, ChatRepository, MessageRepository, PersonaRepository},
     AppState,
 };
 use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use teloxide::{
     prelude::*,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode},
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, MessageId, ParseMode},
+    RequestError,
 };
 
+/// How often a live status message is edited, well within Telegram's edit limits
+const LIVE_REFRESH: Duration = Duration::from_secs(15);
+
+/// Minutes covered by the status sparklines
+const STATUS_MINUTES: usize = 10;
+
+lazy_static::lazy_static! {
+    /// Live status messages and the id of the loop refreshing each
+    static ref LIVE_STATUS: std::sync::Mutex<HashMap<(ChatId, MessageId), u64>> = std::sync::Mutex::new(HashMap::new());
+}
+
+static NEXT_LIVE_ID: AtomicU64 = AtomicU64::new(1);
+
 /// Main menu keyboard
 pub fn main_menu_keyboard() -> InlineKeyboardMarkup {
     InlineKeyboardMarkup::new(vec![
@@ -18,6 +35,151 @@ pub fn main_menu_keyboard() -> InlineKeyboardMarkup {
     ])
 }
 
+/// Statistics keyboard, with the live toggle
+fn stats_keyboard(state: &AppState, live: bool) -> InlineKeyboardMarkup {
+    let toggle = if live {
+        InlineKeyboardButton::callback("⏹ Stop live status", "menu:live_off")
+    } else {
+        InlineKeyboardButton::callback(
+            format!("📡 Enable live status ({} min)", state.config.live_status_minutes),
+            "menu:live",
+        )
+    };
+
+    InlineKeyboardMarkup::new(vec![
+        vec![toggle],
+        vec![InlineKeyboardButton::callback("🔙 Back", "menu:main")],
+    ])
+}
+
+/// Statistics screen: accounts, LLM latency, queued work and message rate over the last minutes
+async fn stats_text(state: &AppState, live_until: Option<chrono::DateTime<chrono::Utc>>) -> Result<String> {
+    use crate::ai::health::sparkline;
+
+    let active_count = state.active_userbot_count().await;
+    let all_accounts = AccountRepository::list_all(&state.db_pool).await?;
+
+    let latencies = state.llm_health.latency_by_minute(STATUS_MINUTES);
+    let latency_secs: Vec<Option<f64>> = latencies.iter().map(|l| l.map(|d| d.as_secs_f64())).collect();
+    let last_latency = match latency_secs.iter().rev().flatten().next() {
+        Some(secs) => format!("{:.1}s", secs),
+        None => "no calls".to_string(),
+    };
+    let llm_text = match state.llm_health.paused() {
+        Some((reason, since)) => format!(
+            "⏸ Paused since {} UTC: {}",
+            since.format("%H:%M"),
+            html_escape(&reason)
+        ),
+        None => format!("<code>{}</code> last: {}", sparkline(&latency_secs), last_latency),
+    };
+
+    let (mut incoming, mut replies) = (vec![0i64; STATUS_MINUTES], vec![0i64; STATUS_MINUTES]);
+    for (ago, role, count) in MessageRepository::per_minute(&state.db_pool, STATUS_MINUTES as i64).await? {
+        if ago < 0 || ago >= STATUS_MINUTES as i64 {
+            continue;
+        }
+        let slot = STATUS_MINUTES - 1 - ago as usize;
+        match role.as_str() {
+            "user" => incoming[slot] += count,
+            "assistant" => replies[slot] += count,
+            _ => {}
+        }
+    }
+    let per_minute = |counts: &[i64]| counts.iter().sum::<i64>() as f64 / STATUS_MINUTES as f64;
+    let reply_counts: Vec<Option<f64>> = replies.iter().map(|&c| Some(c as f64)).collect();
+
+    let live_text = match live_until {
+        Some(until) => format!("\n📡 Live until {} UTC", until.format("%H:%M")),
+        None => String::new(),
+    };
+
+    Ok(format!(
+        "📊 <b>Statistics</b>\n\n\
+        🤖 Active Userbots: {}\n\
+        📱 Total Accounts: {}\n\n\
+        🧠 LLM latency ({} min): {}\n\
+        📥 Queue: {} batched, {} missed to catch up on\n\
+        🌡 Throttle: {}, {} throttled replies since start\n\
+        💬 Messages/min: {:.1} in, {:.1} out <code>{}</code>\n{}",
+        active_count,
+        all_accounts.len(),
+        STATUS_MINUTES,
+        llm_text,
+        crate::userbot::debounce::pending_messages(),
+        crate::userbot::catchup::pending_messages(),
+        state.throttle.describe(),
+        state.throttle.throttled_replies.load(std::sync::atomic::Ordering::Relaxed),
+        per_minute(&incoming),
+        per_minute(&replies),
+        sparkline(&reply_counts),
+        live_text
+    ))
+}
+
+/// Whether `id` is still the loop refreshing this message
+fn is_live(key: (ChatId, MessageId), id: u64) -> bool {
+    LIVE_STATUS.lock().unwrap_or_else(|e| e.into_inner()).get(&key) == Some(&id)
+}
+
+/// Edit the statistics message every few seconds until it expires or is stopped
+async fn refresh_live_status(bot: Bot, state: AppState, key: (ChatId, MessageId), id: u64, until: chrono::DateTime<chrono::Utc>) {
+    let deadline = Instant::now() + Duration::from_secs(state.config.live_status_minutes * 60);
+    let mut shown = String::new();
+
+    while Instant::now() < deadline {
+        tokio::time::sleep(LIVE_REFRESH).await;
+        if !is_live(key, id) {
+            return;
+        }
+
+        let text = match stats_text(&state, Some(until)).await {
+            Ok(text) => text,
+            Err(e) => {
+                tracing::warn!("Failed to build live status: {}", e);
+                continue;
+            }
+        };
+        // Telegram rejects edits that change nothing
+        if text == shown {
+            continue;
+        }
+
+        match bot
+            .edit_message_text(key.0, key.1, text.clone())
+            .parse_mode(ParseMode::Html)
+            .reply_markup(stats_keyboard(&state, true))
+            .await
+        {
+            Ok(_) => shown = text,
+            Err(RequestError::RetryAfter(wait)) => tokio::time::sleep(wait.duration()).await,
+            Err(e) => {
+                // Most likely the message was deleted
+                tracing::debug!("Stopping live status: {}", e);
+                break;
+            }
+        }
+    }
+
+    let expired = {
+        let mut live = LIVE_STATUS.lock().unwrap_or_else(|e| e.into_inner());
+        let ours = live.get(&key) == Some(&id);
+        if ours {
+            live.remove(&key);
+        }
+        ours
+    };
+    if expired {
+        if let Ok(text) = stats_text(&state, None).await {
+            let _ = bot
+                .edit_message_text(key.0, key.1, text)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(stats_keyboard(&state, false))
+                .await;
+        }
+    }
+}
+
 /// Account list keyboard
 pub async fn accounts_keyboard(state: &AppState) -> Result<InlineKeyboardMarkup> {
     let accounts = AccountRepository::list_all(&state.db_pool).await?;
@@ -58,6 +220,10 @@ pub fn account_control_keyboard(account_id: i64, is_running: bool) -> InlineKeyb
             "🎲 Set Probability",
             format!("acc:prob:{}", account_id),
         )],
+        vec![InlineKeyboardButton::callback(
+            "🎭 Persona",
+            format!("acc:persona:{}", account_id),
+        )],
         vec![InlineKeyboardButton::callback(
             "💬 Manage Chats",
             format!("acc:chats:{}", account_id),
@@ -70,6 +236,101 @@ pub fn account_control_keyboard(account_id: i64, is_running: bool) -> InlineKeyb
     ])
 }
 
+/// Persona picker keyboard for an account
+pub async fn persona_picker_keyboard(state: &AppState, account_id: i64) -> Result<InlineKeyboardMarkup> {
+    let personas = PersonaRepository::list_all(&state.db_pool).await?;
+
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = personas
+        .into_iter()
+        .map(|persona| {
+            vec![InlineKeyboardButton::callback(
+                format!("🎭 {}", persona.name),
+                format!("bind:{}:{}", account_id, persona.id),
+            )]
+        })
+        .collect();
+
+    buttons.push(vec![InlineKeyboardButton::callback(
+        "🚫 Use own prompt",
+        format!("bind:{}:none", account_id),
+    )]);
+    buttons.push(vec![InlineKeyboardButton::callback(
+        "🔙 Back",
+        format!("account:{}", account_id),
+    )]);
+
+    Ok(InlineKeyboardMarkup::new(buttons))
+}
+
+/// Chat management keyboard: a deny/allow and a quiet hours toggle per discovered dialog,
+/// and a button opening its media processing toggles
+pub async fn chats_keyboard(state: &AppState, account_id: i64) -> Result<InlineKeyboardMarkup> {
+    let chats = ChatRepository::list_for_account(&state.db_pool, account_id).await?;
+
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = chats
+        .into_iter()
+        .take(40)
+        .map(|chat| {
+            let icon = if chat.is_denied { "⛔" } else { "✅" };
+            let quiet = match crate::userbot::quiet_hours::of(Some(&chat)) {
+                Some(window) => format!("🌙 {}", window.format()),
+                None => "🔔".to_string(),
+            };
+            let title = if chat.title.is_empty() { chat.chat_id.to_string() } else { chat.title };
+            vec![
+                InlineKeyboardButton::callback(
+                    format!("{} {}", icon, title),
+                    format!("chat:toggle:{}:{}", account_id, chat.chat_id),
+                ),
+                InlineKeyboardButton::callback(quiet, format!("chat:quiet:{}:{}", account_id, chat.chat_id)),
+                InlineKeyboardButton::callback("🖼", format!("chat:media:{}:{}", account_id, chat.chat_id)),
+            ]
+        })
+        .collect();
+
+    buttons.push(vec![InlineKeyboardButton::callback(
+        "🔄 Refresh from Telegram",
+        format!("chat:refresh:{}", account_id),
+    )]);
+    buttons.push(vec![InlineKeyboardButton::callback(
+        "🔙 Back",
+        format!("account:{}", account_id),
+    )]);
+
+    Ok(InlineKeyboardMarkup::new(buttons))
+}
+
+/// Vision, voice transcription and web search toggles of one chat
+fn chat_media_keyboard(account_id: i64, chat_id: i64, chat: Option<&crate::db::AccountChat>) -> InlineKeyboardMarkup {
+    let (vision, voice, web_search) = chat.map_or((true, true, true), |c| (c.vision_enabled, c.voice_enabled, c.web_search_enabled));
+    let toggle = |label: &str, on: bool, kind: &str| {
+        vec![InlineKeyboardButton::callback(
+            format!("{} {}", if on { "✅" } else { "⛔" }, label),
+            format!("chat:mset:{}:{}:{}", account_id, chat_id, kind),
+        )]
+    };
+
+    InlineKeyboardMarkup::new(vec![
+        toggle("👁 Photos to vision", vision, "vision"),
+        toggle("🎙 Voice transcription", voice, "voice"),
+        toggle("🔎 Web search", web_search, "search"),
+        vec![InlineKeyboardButton::callback("🔙 Back", format!("acc:chats:{}", account_id))],
+    ])
+}
+
+/// Name of the persona bound to an account, if any
+async fn persona_label(state: &AppState, account: &Account) -> Result<String> {
+    let label = match account.persona_id {
+        Some(persona_id) => PersonaRepository::get_by_id(&state.db_pool, persona_id)
+            .await?
+            .map(|p| format!("🎭 {} (overrides prompt below)", p.name))
+            .unwrap_or_else(|| "—".to_string()),
+        None => "—".to_string(),
+    };
+
+    Ok(label)
+}
+
 /// Handle callback queries
 pub async fn handle_callback(
     bot: Bot,
@@ -84,6 +345,14 @@ pub async fn handle_callback(
             "menu" => handle_menu_callback(&bot, &q, &state, parts).await?,
             "account" => handle_account_list_callback(&bot, &q, &state, parts).await?,
             "acc" => handle_account_control_callback(&bot, &q, &state, &dialogue, parts).await?,
+            "bind" => handle_persona_bind_callback(&bot, &q, &state, parts).await?,
+            "chat" => handle_chat_callback(&bot, &q, &state, parts).await?,
+            "onb" => handle_onboarding_callback(&bot, &q, &state, parts).await?,
+            "nlr" => crate::bot::nl_router::handle_confirm_callback(&bot, &q, &state, &dialogue, parts).await?,
+            dialogues::CANCEL_CALLBACK => handle_wizard_cancel_callback(&bot, &q, &state, &dialogue).await?,
+            "p_edit_name" | "p_edit_prompt" | "p_edit_post" | "p_save" | "p_discard" => {
+                handle_persona_edit_callback(&bot, &q, &state, &dialogue, parts).await?
+            }
             _ => {}
         }
     }
@@ -93,6 +362,30 @@ pub async fn handle_callback(
     Ok(())
 }
 
+/// "❌ Отмена" under a wizard prompt: leave whatever step this chat's wizard is at
+async fn handle_wizard_cancel_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    dialogue: &AddAccountDialogue,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let message = match &q.message {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+
+    let text = match dialogue.get().await? {
+        Some(AddAccountState::Idle) | None => "⌛ This wizard is no longer active.",
+        Some(_) => {
+            dialogues::exit_wizard(dialogue, state).await?;
+            "❌ Operation cancelled."
+        }
+    };
+    bot.edit_message_reply_markup(message.chat().id, message.id()).await?;
+    bot.send_message(message.chat().id, text).await?;
+    Ok(())
+}
+
 async fn handle_menu_callback(
     bot: &Bot,
     q: &CallbackQuery,
@@ -106,6 +399,11 @@ async fn handle_menu_callback(
     
     let chat_id = message.chat().id;
     let message_id = message.id();
+
+    // Navigating away stops a live status refreshing this message
+    if parts.get(1) != Some(&"live") {
+        LIVE_STATUS.lock().unwrap_or_else(|e| e.into_inner()).remove(&(chat_id, message_id));
+    }
     
     match parts.get(1) {
         Some(&"main") => {
@@ -142,22 +440,33 @@ async fn handle_menu_callback(
             .await?;
         }
         Some(&"stats") => {
-            let active_count = state.active_userbot_count().await;
-            let all_accounts = AccountRepository::list_all(&state.db_pool).await?;
-            
-            let text = format!(
-                "📊 <b>Statistics</b>\n\n\
-                🤖 Active Userbots: {}\n\
-                📱 Total Accounts: {}\n",
-                active_count,
-                all_accounts.len()
-            );
-            
-            bot.edit_message_text(chat_id, message_id, text)
+            bot.edit_message_text(chat_id, message_id, stats_text(state, None).await?)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(stats_keyboard(state, false))
+                .await?;
+        }
+        Some(&"live") => {
+            let id = NEXT_LIVE_ID.fetch_add(1, Ordering::Relaxed);
+            LIVE_STATUS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert((chat_id, message_id), id);
+            let until = chrono::Utc::now() + chrono::Duration::minutes(state.config.live_status_minutes as i64);
+
+            bot.edit_message_text(chat_id, message_id, stats_text(state, Some(until)).await?)
+                .parse_mode(ParseMode::Html)
+                .reply_markup(stats_keyboard(state, true))
+                .await?;
+
+            let (bot, state) = (bot.clone(), state.clone());
+            tokio::spawn(async move {
+                refresh_live_status(bot, state, (chat_id, message_id), id, until).await;
+            });
+        }
+        Some(&"live_off") => {
+            bot.edit_message_text(chat_id, message_id, stats_text(state, None).await?)
                 .parse_mode(ParseMode::Html)
-                .reply_markup(InlineKeyboardMarkup::new(vec![
-                    vec![InlineKeyboardButton::callback("🔙 Back", "menu:main")],
-                ]))
+                .reply_markup(stats_keyboard(state, false))
                 .await?;
         }
         _ => {}
@@ -201,12 +510,14 @@ async fn handle_account_list_callback(
                     "📱 <b>Account: {}</b>\n\n\
                     ID: {}\n\
                     Status: {}\n\
-                    Reply Probability: {}%\n\n\
+                    Reply Probability: {}%\n\
+                    Persona: {}\n\n\
                     <i>System Prompt:</i>\n<code>{}</code>",
                     account.phone_number,
                     account.id,
                     status,
                     account.reply_probability,
+                    persona_label(state, &account).await?,
                     account.system_prompt
                 );
                 
@@ -244,6 +555,11 @@ async fn handle_account_control_callback(
     let account_id: i64 = parts[2].parse()?;
     
     match action {
+        "start" if state.config.safe_mode => {
+            bot.answer_callback_query(&q.id)
+                .text("🛡 Userbots are disabled in safe mode")
+                .await?;
+        }
         "start" => {
             if !state.is_userbot_running(account_id).await {
                 crate::userbot::spawn_userbot(state.clone(), account_id).await?;
@@ -295,6 +611,39 @@ async fn handle_account_control_callback(
             .await?;
             return Ok(());
         }
+        "chats" => {
+            let keyboard = chats_keyboard(state, account_id).await?;
+            bot.edit_message_text(
+                chat_id,
+                message_id,
+                format!(
+                    "💬 <b>Chats of account {}</b>\n\n\
+                    Tap a chat to deny or allow replies there, 🖼 for its media processing.\n\
+                    Per-chat probabilities: /chat_prob",
+                    account_id
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+            return Ok(());
+        }
+        "persona" => {
+            let keyboard = persona_picker_keyboard(state, account_id).await?;
+            bot.edit_message_text(
+                chat_id,
+                message_id,
+                format!(
+                    "🎭 <b>Persona for account {}</b>\n\n\
+                    Pick a stored persona. Create new ones with /create_persona.",
+                    account_id
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard)
+            .await?;
+            return Ok(());
+        }
         "prob" => {
             bot.send_message(
                 chat_id,
@@ -316,12 +665,14 @@ async fn handle_account_control_callback(
             "📱 <b>Account: {}</b>\n\n\
             ID: {}\n\
             Status: {}\n\
-            Reply Probability: {}%\n\n\
+            Reply Probability: {}%\n\
+            Persona: {}\n\n\
             <i>System Prompt:</i>\n<code>{}</code>",
             account.phone_number,
             account.id,
             status,
             account.reply_probability,
+            persona_label(state, &account).await?,
             account.system_prompt
         );
         
@@ -333,3 +684,340 @@ async fn handle_account_control_callback(
     
     Ok(())
 }
+
+async fn handle_persona_bind_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    parts: Vec<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if parts.len() < 3 {
+        return Ok(());
+    }
+
+    let account_id: i64 = parts[1].parse()?;
+    let persona_id = match parts[2] {
+        "none" => None,
+        id => Some(id.parse::<i64>()?),
+    };
+
+    PersonaRepository::bind_account(&state.db_pool, account_id, persona_id).await?;
+    if let Some(persona_id) = persona_id {
+        crate::webhooks::emit(
+            state,
+            crate::webhooks::Event::PersonaActivated,
+            serde_json::json!({ "account_id": account_id, "persona_id": persona_id, "reason": "bind" }),
+        )
+        .await;
+    }
+
+    bot.answer_callback_query(&q.id)
+        .text(if persona_id.is_some() { "✅ Persona bound!" } else { "✅ Persona unbound!" })
+        .await?;
+
+    // Back to the account panel
+    handle_account_list_callback(bot, q, state, vec!["account", parts[1]]).await
+}
+
+/// Single-field persona edits started from /edit_persona, and Save/Discard of a prompt diff
+async fn handle_persona_edit_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    dialogue: &AddAccountDialogue,
+    parts: Vec<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let message = match &q.message {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+    let chat_id = message.chat().id;
+
+    let persona_id: i64 = match parts.get(1).and_then(|id| id.parse().ok()) {
+        Some(id) => id,
+        None => return Ok(()),
+    };
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            bot.send_message(chat_id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    };
+
+    // Save/Discard only settle a wizard, the edits start one
+    let user_id = Some(q.from.id.0 as i64);
+    if parts[0].starts_with("p_edit") && dialogues::refuse_if_busy(bot, state, chat_id, user_id).await? {
+        return Ok(());
+    }
+
+    match parts[0] {
+        "p_edit_name" => {
+            dialogues::enter_wizard(dialogue, state, user_id, AddAccountState::EditPersonaName { persona_id }).await?;
+            bot.send_message(
+                chat_id,
+                format!(
+                    "✏️ Send the new name for persona <b>{}</b>.\n\nSend /cancel to abort.",
+                    html_escape(&persona.name)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(dialogues::cancel_keyboard())
+            .await?;
+        }
+        "p_edit_prompt" => {
+            dialogues::enter_wizard(dialogue, state, user_id, AddAccountState::EditPersonaPrompt { persona_id }).await?;
+            bot.send_message(
+                chat_id,
+                format!(
+                    "📝 Current prompt of <b>{}</b> (copy, edit and send it back):\n\n<code>{}</code>\n\nSend /cancel to abort.",
+                    html_escape(&persona.name),
+                    html_escape(&persona.prompt)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(dialogues::cancel_keyboard())
+            .await?;
+        }
+        "p_edit_post" => {
+            dialogues::enter_wizard(dialogue, state, user_id, AddAccountState::EditPersonaPostprocess { persona_id }).await?;
+            let current = persona.postprocess.unwrap_or_else(|| {
+                r#"{"replacements": [{"pattern": "!{2,}", "replacement": "!"}], "forbidden": ["как ИИ"], "strip_emoji": false, "lowercase": false}"#
+                    .to_string()
+            });
+            bot.send_message(
+                chat_id,
+                format!(
+                    "🧹 Post-processing of <b>{}</b>: regex replacements, forbidden phrases, emoji stripping, \
+                    lowercase. Edit and send back the JSON, or \"off\" to remove the rules:\n\n<code>{}</code>\n\n\
+                    Send /cancel to abort.",
+                    html_escape(&persona.name),
+                    html_escape(&current)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(dialogues::cancel_keyboard())
+            .await?;
+        }
+        "p_save" => {
+            let pending = match dialogue.get().await? {
+                Some(AddAccountState::ConfirmPersonaPrompt { persona_id: id, prompt }) if id == persona_id => prompt,
+                _ => {
+                    bot.edit_message_text(chat_id, message.id(), "⌛ This edit is no longer pending.").await?;
+                    return Ok(());
+                }
+            };
+            PersonaRepository::update_prompt(&state.db_pool, persona_id, &pending).await?;
+            dialogues::exit_wizard(dialogue, state).await?;
+            bot.edit_message_text(
+                chat_id,
+                message.id(),
+                format!("✅ Prompt of {} saved.", persona.name),
+            )
+            .await?;
+        }
+        "p_discard" => {
+            if matches!(dialogue.get().await?, Some(AddAccountState::ConfirmPersonaPrompt { .. })) {
+                dialogues::exit_wizard(dialogue, state).await?;
+            }
+            bot.edit_message_text(chat_id, message.id(), "❌ Changes discarded.").await?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+async fn handle_chat_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    parts: Vec<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let message = match &q.message {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+
+    if parts.len() < 3 {
+        return Ok(());
+    }
+
+    let account_id: i64 = parts[2].parse()?;
+
+    match parts[1] {
+        "toggle" => {
+            let target_chat: i64 = match parts.get(3) {
+                Some(id) => id.parse()?,
+                None => return Ok(()),
+            };
+            let denied = !ChatRepository::get(&state.db_pool, account_id, target_chat)
+                .await?
+                .map(|c| c.is_denied)
+                .unwrap_or(false);
+            let change = ChatRepository::set_denied(&state.db_pool, account_id, target_chat, denied);
+            if !apply_chat_change(bot, q, state, account_id, target_chat, "chat menu: toggle", change).await? {
+                return Ok(());
+            }
+        }
+        "quiet" => {
+            let target_chat: i64 = match parts.get(3) {
+                Some(id) => id.parse()?,
+                None => return Ok(()),
+            };
+            let chat = ChatRepository::get(&state.db_pool, account_id, target_chat).await?;
+            let window = match crate::userbot::quiet_hours::of(chat.as_ref()) {
+                Some(_) => None,
+                None => Some(state.config.default_quiet_hours.format()),
+            };
+            let allow_mentions = chat.as_ref().map_or(true, |c| c.quiet_allow_mentions);
+            let change =
+                ChatRepository::set_quiet_hours(&state.db_pool, account_id, target_chat, window.as_deref(), allow_mentions);
+            if !apply_chat_change(bot, q, state, account_id, target_chat, "chat menu: quiet", change).await? {
+                return Ok(());
+            }
+        }
+        "media" | "mset" => {
+            let target_chat: i64 = match parts.get(3) {
+                Some(id) => id.parse()?,
+                None => return Ok(()),
+            };
+            let mut chat = ChatRepository::get(&state.db_pool, account_id, target_chat).await?;
+            if parts[1] == "mset" {
+                let (mut vision, mut voice, mut web_search) =
+                    chat.as_ref().map_or((true, true, true), |c| (c.vision_enabled, c.voice_enabled, c.web_search_enabled));
+                match parts.get(4) {
+                    Some(&"vision") => vision = !vision,
+                    Some(&"voice") => voice = !voice,
+                    Some(&"search") => web_search = !web_search,
+                    _ => return Ok(()),
+                }
+                let change = ChatRepository::set_media(&state.db_pool, account_id, target_chat, vision, voice, web_search);
+                if !apply_chat_change(bot, q, state, account_id, target_chat, "chat menu: media", change).await? {
+                    return Ok(());
+                }
+                chat = ChatRepository::get(&state.db_pool, account_id, target_chat).await?;
+            }
+
+            let title = chat
+                .as_ref()
+                .map(|c| c.title.clone())
+                .filter(|t| !t.is_empty())
+                .unwrap_or_else(|| target_chat.to_string());
+            bot.edit_message_text(
+                message.chat().id,
+                message.id(),
+                format!(
+                    "🖼 <b>Media in {}</b>\n\n\
+                    What the account may send to the vision model, Whisper and web search for this chat. \
+                    Features that are off globally stay off.",
+                    html_escape(&title)
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .reply_markup(chat_media_keyboard(account_id, target_chat, chat.as_ref()))
+            .await?;
+            return Ok(());
+        }
+        "refresh" => {
+            if let Err(e) = crate::userbot::discover_chats(state, account_id).await {
+                bot.answer_callback_query(&q.id)
+                    .text(format!("❌ {}", e))
+                    .await?;
+            }
+        }
+        _ => return Ok(()),
+    }
+
+    let keyboard = chats_keyboard(state, account_id).await?;
+    bot.edit_message_reply_markup(message.chat().id, message.id())
+        .reply_markup(keyboard)
+        .await?;
+
+    Ok(())
+}
+
+/// Apply a chat settings change from a menu under the settings cooldown and history.
+/// Returns false, after telling the owner, when the cooldown refused it.
+async fn apply_chat_change(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    label: &str,
+    change: impl std::future::Future<Output = Result<()>>,
+) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+    use crate::settings_history::{cooldown_message, guarded, Guarded};
+
+    match guarded(state, account_id, chat_id, q.from.id.0 as i64, label, change).await? {
+        Guarded::Applied(result) => {
+            result?;
+            Ok(true)
+        }
+        Guarded::CoolingDown(left) => {
+            bot.answer_callback_query(&q.id)
+                .text(cooldown_message(account_id, chat_id, left))
+                .await?;
+            Ok(false)
+        }
+    }
+}
+
+/// Setup menu sent when an account is added to a group: onb:<choice>:<account_id>:<chat_id>
+async fn handle_onboarding_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    parts: Vec<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::userbot::onboarding::{self, SetupChoice};
+
+    let message = match &q.message {
+        Some(msg) => msg,
+        None => return Ok(()),
+    };
+
+    let (choice, account_id, chat_id) = match (
+        parts.get(1).and_then(|c| SetupChoice::parse(c)),
+        parts.get(2).and_then(|a| a.parse::<i64>().ok()),
+        parts.get(3).and_then(|c| c.parse::<i64>().ok()),
+    ) {
+        (Some(choice), Some(a), Some(c)) => (choice, a, c),
+        _ => return Ok(()),
+    };
+
+    let label = format!("setup menu: {}", choice.as_str());
+    let change = onboarding::apply_choice(state, account_id, chat_id, choice);
+    if !apply_chat_change(bot, q, state, account_id, chat_id, &label, change).await? {
+        return Ok(());
+    }
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let title = chat
+        .as_ref()
+        .map(|c| c.title.clone())
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| chat_id.to_string());
+    let triggers = chat.map(|c| c.get_triggers()).unwrap_or_default();
+
+    let mut text = format!(
+        "✅ Account {} in <b>{}</b>: {}",
+        account_id,
+        html_escape(&title),
+        choice.label()
+    );
+    if !triggers.is_empty() {
+        text.push_str(&format!("\nTriggers: {}", html_escape(&triggers.join(", "))));
+    }
+    text.push_str(&format!(
+        "\nChange later with /chat_prob, /chat_triggers or /apply_profile {} {} &lt;name&gt;",
+        account_id, chat_id
+    ));
+
+    bot.edit_message_text(message.chat().id, message.id(), text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,2712 @@
+use crate::{
+    bot::handlers::html_escape,
+    db::{
+        AccountRepository, AnswerCacheRepository, ChatRepository, EphemeralRepository, FeedRepository, GameRepository,
+        HandoffRepository, KarmaRepository, MediaQuotaRepository, MessageRepository, PersonaRepository, ProfileRepository,
+        SettingChangeRepository, TuningRepository,
+    },
+    userbot::{
+        catchup::CatchupMode,
+        debounce::DebounceSettings,
+        digest, feeds,
+        formatting::FormatMode,
+        onboarding, profiles, quiet_hours,
+        quotas::{self, MediaKind},
+        rotation::RotationMode,
+        timezone, tuning,
+    },
+    settings_history, AppState,
+};
+use anyhow::Result;
+use std::collections::HashMap;
+use teloxide::{prelude::*, types::ParseMode};
+
+/// List the userbot's dialogs with their allow/deny state and overrides
+/// Usage: /chats <account_id> [refresh]
+pub async fn handle_chats(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /chats <account_id> [refresh]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
+        Some(a) => a,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if args.get(1).map(|a| a == "refresh").unwrap_or(false) {
+        match crate::userbot::discover_chats(&state, account_id).await {
+            Ok(count) => {
+                bot.send_message(msg.chat.id, format!("🔄 Discovered {} chats", count))
+                    .await?;
+            }
+            Err(e) => {
+                bot.send_message(msg.chat.id, format!("❌ Discovery failed: {}", e))
+                    .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let chats = ChatRepository::list_for_account(&state.db_pool, account_id).await?;
+
+    if chats.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "📋 No chats known for account {}. Start the userbot and run /chats {} refresh",
+                account_id, account_id
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let media_quotas = MediaQuotaRepository::list_for_account(&state.db_pool, account_id).await?;
+    let today = quotas::today();
+
+    let mut response = format!("💬 <b>Chats of account {}</b>\n\n", account_id);
+
+    for chat in chats {
+        let status = if chat.is_denied {
+            "⛔"
+        } else if chat.is_paused() {
+            "⏸"
+        } else if account.is_chat_allowed(chat.chat_id) {
+            "✅"
+        } else {
+            "⚪"
+        };
+
+        let initiative = if chat.initiative_enabled { " | 💡" } else { "" };
+        let profile = chat
+            .profile_name
+            .as_deref()
+            .map(|p| format!(" | 🎭 {}", html_escape(p)))
+            .unwrap_or_default();
+
+        let probability = chat
+            .reply_probability
+            .map(|p| format!("{}%", p))
+            .unwrap_or_else(|| format!("{}% (default)", account.reply_probability));
+
+        // Media processed today, against the limit if there is one
+        let usage: String = MediaKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                let quota = media_quotas.iter().find(|q| q.chat_id == chat.chat_id && q.kind == kind.as_str());
+                let used = quota.map(|q| q.used_on(&today)).unwrap_or(0);
+                let limit = quotas::effective_limit(quota, kind.default_limit(&state));
+                (used > 0).then(|| match limit {
+                    Some(limit) => format!(" | {} {}/{}", kind.icon(), used, limit),
+                    None => format!(" | {} {}", kind.icon(), used),
+                })
+            })
+            .collect();
+
+        response.push_str(&format!(
+            "{} <b>{}</b> [{}]\n   <code>{}</code> | Prob: {} | Format: {}{}{}{}\n",
+            status,
+            html_escape(&chat.title),
+            chat.chat_type,
+            chat.chat_id,
+            probability,
+            chat.format_mode,
+            initiative,
+            profile,
+            usage
+        ));
+    }
+
+    response.push_str(
+        "\n✅ replies allowed | ⚪ not in whitelist | ⛔ denied | 💡 starts conversations | 📷/🎙 media processed today",
+    );
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Toggle the deny flag of a chat
+/// Usage: /deny_chat <account_id> <chat_id>
+pub async fn handle_deny_chat(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /deny_chat <account_id> <chat_id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let denied = !ChatRepository::get(&state.db_pool, account_id, chat_id)
+        .await?
+        .map(|c| c.is_denied)
+        .unwrap_or(false);
+
+    ChatRepository::set_denied(&state.db_pool, account_id, chat_id, denied).await?;
+
+    let text = if denied {
+        format!("⛔ Account {} will no longer reply in chat {}", account_id, chat_id)
+    } else {
+        format!("✅ Chat {} is no longer denied for account {}", chat_id, account_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Set or clear the per-chat reply probability override
+/// Usage: /chat_prob <account_id> <chat_id> <0-100|default>
+pub async fn handle_chat_prob(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_prob <account_id> <chat_id> <0-100|default>";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let probability = match args.get(2).map(|s| s.as_str()) {
+        Some("default") => None,
+        Some(value) => match value.parse::<i64>() {
+            Ok(p) if (0..=100).contains(&p) => Some(p),
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_reply_probability(&state.db_pool, account_id, chat_id, probability).await?;
+
+    let text = match probability {
+        Some(p) => format!("✅ Reply probability in chat {} set to {}% for account {}", chat_id, p, account_id),
+        None => format!("✅ Chat {} now uses the account's default probability", chat_id),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Choose how replies are rendered in a chat
+/// Usage: /chat_format <account_id> <chat_id> <markdown|html|plain> (plain by default)
+pub async fn handle_chat_format(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parsed = (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).and_then(|a| FormatMode::parse(a)),
+    );
+
+    let (account_id, chat_id, mode) = match parsed {
+        (Some(a), Some(c), Some(m)) => (a, c, m),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /chat_format <account_id> <chat_id> <markdown|html|plain>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_format_mode(&state.db_pool, account_id, chat_id, mode.as_str()).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Replies in chat {} will be sent as {}", chat_id, mode.as_str()),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Enable or disable conversation starters in a chat
+/// Usage: /initiative <account_id> <chat_id> <on|off>
+pub async fn handle_initiative(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parsed = (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).map(|a| a.as_str()),
+    );
+
+    let (account_id, chat_id, enabled) = match parsed {
+        (Some(a), Some(c), Some("on")) => (a, c, true),
+        (Some(a), Some(c), Some("off")) => (a, c, false),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /initiative <account_id> <chat_id> <on|off>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_initiative(&state.db_pool, account_id, chat_id, enabled).await?;
+
+    let text = if enabled {
+        let (start, end) = state.config.initiative_active_hours;
+        format!(
+            "✅ Account {} may start conversations in chat {} after {} min of silence \
+            (max {}/day, {}:00-{}:00)",
+            account_id,
+            chat_id,
+            state.config.initiative_silence_minutes,
+            state.config.initiative_max_per_day,
+            start,
+            end
+        )
+    } else {
+        format!("✅ Conversation starters disabled in chat {}", chat_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Show or set a chat's timezone
+/// Usage: /chat_timezone <account_id> <chat_id> [Area/City|auto|reset]
+pub async fn handle_chat_timezone(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /chat_timezone <account_id> <chat_id> [Area/City|auto|reset]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
+        Some(a) => a,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Account {} not found", account_id)).await?;
+            return Ok(());
+        }
+    };
+    let suggestion = timezone::suggest_timezone(&account.phone_number);
+
+    let requested = match args.get(2).map(|a| a.as_str()) {
+        None => {
+            let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+            let now = timezone::chat_now(chat.as_ref(), state.config.default_timezone);
+            let current = chat
+                .as_ref()
+                .and_then(|c| c.timezone.clone())
+                .unwrap_or_else(|| format!("{} (default)", state.config.default_timezone));
+
+            let mut text = format!("🕒 Chat {}: {}, local time {}", chat_id, current, now.format("%Y-%m-%d %H:%M"));
+            if let Some(tz) = suggestion {
+                text.push_str(&format!("\nSuggested from the account's phone: {} (/chat_timezone {} {} auto)", tz, account_id, chat_id));
+            }
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Some("reset") => None,
+        Some("auto") => match suggestion {
+            Some(tz) => Some(tz.to_string()),
+            None => {
+                bot.send_message(msg.chat.id, "❌ No timezone suggestion for this account's phone number")
+                    .await?;
+                return Ok(());
+            }
+        },
+        Some(name) => match timezone::parse_timezone(name) {
+            Some(tz) => Some(tz.name().to_string()),
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Unknown timezone '{}' (expected e.g. Europe/Moscow)", name))
+                    .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    ChatRepository::set_timezone(&state.db_pool, account_id, chat_id, requested.as_deref()).await?;
+
+    let text = match requested {
+        Some(tz) => format!("✅ Chat {} now uses {}", chat_id, tz),
+        None => format!("✅ Chat {} uses the default timezone ({})", chat_id, state.config.default_timezone),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Configure karma in a chat, or show its leaderboard
+/// Usage: /karma_chat <account_id> <chat_id> [on|off|monthly|reset]
+pub async fn handle_karma_chat(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /karma_chat <account_id> <chat_id> [on|off|monthly|reset]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let (enabled, monthly) = chat.as_ref().map_or((false, false), |c| (c.karma_enabled, c.karma_monthly_reset));
+
+    let text = match args.get(2).map(|a| a.to_lowercase()).as_deref() {
+        None => {
+            let top = KarmaRepository::leaderboard(&state.db_pool, account_id, chat_id, 10).await?;
+            let mut text = format!(
+                "🏆 Karma in chat {}: {}{}\n",
+                chat_id,
+                if enabled { "on" } else { "off" },
+                if monthly { ", resets monthly" } else { "" }
+            );
+            for (i, entry) in top.iter().enumerate() {
+                text.push_str(&format!("\n{}. user {} — {}", i + 1, entry.user_id, entry.points));
+            }
+            text
+        }
+        Some("on") => {
+            KarmaRepository::configure(&state.db_pool, account_id, chat_id, true, monthly).await?;
+            format!("✅ Karma enabled in chat {}. Members can use /karma and /leaderboard there.", chat_id)
+        }
+        Some("off") => {
+            KarmaRepository::configure(&state.db_pool, account_id, chat_id, false, monthly).await?;
+            format!("✅ Karma disabled in chat {}", chat_id)
+        }
+        Some("monthly") => {
+            KarmaRepository::configure(&state.db_pool, account_id, chat_id, enabled, !monthly).await?;
+            format!("✅ Monthly karma reset {} in chat {}", if monthly { "disabled" } else { "enabled" }, chat_id)
+        }
+        Some("reset") => {
+            let period = chrono::Utc::now().format("%Y-%m").to_string();
+            KarmaRepository::reset(&state.db_pool, account_id, chat_id, &period).await?;
+            format!("✅ Karma reset in chat {}", chat_id)
+        }
+        Some(_) => "❌ Usage: /karma_chat <account_id> <chat_id> [on|off|monthly|reset]".to_string(),
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Enable games in a chat, or show its leaderboard
+/// Usage: /chat_games <account_id> <chat_id> [on|off|reset]
+pub async fn handle_chat_games(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_games <account_id> <chat_id> [on|off|reset]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let text = match args.get(2).map(|a| a.to_lowercase()).as_deref() {
+        None => {
+            let enabled = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .is_some_and(|c| c.games_enabled);
+            let top = GameRepository::leaderboard(&state.db_pool, account_id, chat_id, 10).await?;
+            let mut text = format!("🎲 Games in chat {}: {}\n", chat_id, if enabled { "on" } else { "off" });
+            for (i, entry) in top.iter().enumerate() {
+                text.push_str(&format!("\n{}. user {} — {}", i + 1, entry.user_id, entry.points));
+            }
+            text
+        }
+        Some("on") => {
+            GameRepository::set_enabled(&state.db_pool, account_id, chat_id, true).await?;
+            format!(
+                "✅ Games enabled in chat {}. Members can use /game trivia, /game словарь, /game stop and /game top there.",
+                chat_id
+            )
+        }
+        Some("off") => {
+            GameRepository::set_enabled(&state.db_pool, account_id, chat_id, false).await?;
+            format!("✅ Games disabled in chat {}", chat_id)
+        }
+        Some("reset") => {
+            let cleared = GameRepository::reset_scores(&state.db_pool, account_id, chat_id).await?;
+            format!("✅ Cleared the game scores of {} members in chat {}", cleared, chat_id)
+        }
+        Some(_) => usage.to_string(),
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or set a chat's retention limits
+/// Usage: /chat_retention <account_id> <chat_id> [days|off] [max_messages]
+pub async fn handle_chat_retention(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const USAGE: &str = "❌ Usage: /chat_retention <account_id> <chat_id> [days|off] [max_messages]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, USAGE).await?;
+            return Ok(());
+        }
+    };
+
+    let describe = |days: Option<i64>, max: Option<i64>| {
+        let days = days.map(|d| format!("{} days", d)).unwrap_or_else(|| "default".to_string());
+        let max = max.map(|m| format!("last {} messages", m)).unwrap_or_else(|| "no message limit".to_string());
+        format!("{}, {}", days, max)
+    };
+
+    let days = match args.get(2).map(|a| a.to_lowercase()) {
+        None => {
+            let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+            let (days, max) = chat.map_or((None, None), |c| (c.retention_days, c.retention_max_messages));
+            let global = state
+                .config
+                .retention_days
+                .map(|d| format!("{} days", d))
+                .unwrap_or_else(|| "keep forever".to_string());
+            bot.send_message(
+                msg.chat.id,
+                format!("🗄 Retention in chat {}: {}\nGlobal (RETENTION_DAYS): {}", chat_id, describe(days, max), global),
+            )
+            .await?;
+            return Ok(());
+        }
+        Some(a) if a == "off" || a == "default" => None,
+        Some(a) => match a.parse::<i64>().ok().filter(|d| *d > 0) {
+            Some(d) => Some(d),
+            None => {
+                bot.send_message(msg.chat.id, USAGE).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let max_messages = match args.get(3).map(|a| a.parse::<i64>().ok().filter(|m| *m > 0)) {
+        None => None,
+        Some(Some(m)) => Some(m),
+        Some(None) => {
+            bot.send_message(msg.chat.id, USAGE).await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_retention(&state.db_pool, account_id, chat_id, days, max_messages).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Retention in chat {}: {}\nApplied on the next retention run (every 6 hours).",
+            chat_id,
+            describe(days, max_messages)
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Pause replies in a chat for a while
+/// Usage: /pause_chat <account_id> <chat_id> <minutes>
+pub async fn handle_pause_chat(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id, minutes) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).and_then(|a| a.parse::<i64>().ok()).filter(|m| *m > 0),
+    ) {
+        (Some(a), Some(c), Some(m)) => (a, c, m),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /pause_chat <account_id> <chat_id> <minutes>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let until = chrono::Utc::now() + chrono::Duration::minutes(minutes);
+    ChatRepository::pause(&state.db_pool, account_id, chat_id, until, "paused by owner").await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("⏸ Replies in chat {} paused until {} UTC", chat_id, until.format("%Y-%m-%d %H:%M")),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Lift a pause (manual or automatic) early
+/// Usage: /resume_chat <account_id> <chat_id>
+pub async fn handle_resume_chat(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /resume_chat <account_id> <chat_id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = if ChatRepository::resume(&state.db_pool, account_id, chat_id).await? {
+        format!("▶️ Replies in chat {} resumed", chat_id)
+    } else {
+        format!("ℹ️ Chat {} is not paused", chat_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Hand a conversation to a human, or list the running handoffs
+/// Usage: /handoff <account_id> <chat_id> [user_id|all] [minutes]
+pub async fn handle_handoff(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /handoff <account_id> <chat_id> [user_id|all] [minutes]";
+
+    if args.is_empty() {
+        let handoffs = HandoffRepository::list_active(&state.db_pool).await?;
+        if handoffs.is_empty() {
+            bot.send_message(msg.chat.id, "No conversations are handed over right now").await?;
+            return Ok(());
+        }
+        let mut text = String::from("🙋 Handed over to a human:\n");
+        for handoff in &handoffs {
+            text.push_str(&format!(
+                "\n#{} account {} chat {} ({}) until {} UTC: {}",
+                handoff.id,
+                handoff.account_id,
+                handoff.chat_id,
+                handoff.user_id.map_or("everyone".to_string(), crate::logging::user_ref),
+                handoff.expires_at.format("%Y-%m-%d %H:%M"),
+                handoff.reason
+            ));
+        }
+        bot.send_message(msg.chat.id, text).await?;
+        return Ok(());
+    }
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+    let user_id = match args.get(2).map(String::as_str) {
+        None | Some("all") => None,
+        Some(value) => match value.parse::<i64>() {
+            Ok(user_id) => Some(user_id),
+            Err(_) => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+    let minutes = match args.get(3).map(|m| m.parse::<i64>()) {
+        None => None,
+        Some(Ok(minutes)) if minutes > 0 => Some(minutes),
+        Some(_) => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Account {} not found", account_id)).await?;
+        return Ok(());
+    }
+
+    let handoff = crate::userbot::handoff::start(&state, account_id, chat_id, user_id, "handed over by the owner", minutes)
+        .await?;
+    let notice = crate::userbot::handoff::notice(&state, &handoff, None).await?;
+    bot.send_message(msg.chat.id, notice).await?;
+    Ok(())
+}
+
+/// End handoffs early so the bot answers again
+/// Usage: /resume <account_id> <chat_id> [user_id]
+pub async fn handle_resume(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /resume <account_id> <chat_id> [user_id]").await?;
+            return Ok(());
+        }
+    };
+    let user_id = args.get(2).and_then(|a| a.parse::<i64>().ok());
+
+    let resumed = HandoffRepository::resume(&state.db_pool, account_id, chat_id, user_id).await?;
+    let text = if resumed > 0 {
+        format!("▶️ Chat {} is back with the bot ({} handoff(s) ended)", chat_id, resumed)
+    } else {
+        format!("ℹ️ Nothing in chat {} is handed over", chat_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Create or replace a chat profile
+/// Usage: /save_profile <name> [persona=<id>] [prob=<0-100>] [format=<mode>] [initiative=<on|off>] [cooldown=<secs>] [style=<text>]
+pub async fn handle_save_profile(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = match args.first() {
+        Some(name) => name.to_lowercase(),
+        None => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /save_profile <name> [persona=<id>] [prob=<0-100>] [format=<markdown|html|plain>] \
+                 [initiative=<on|off>] [cooldown=<secs>] [style=<text>]",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let profile = match profiles::parse_settings(&name, &args[1..]) {
+        Ok(profile) => profile,
+        Err(errors) => {
+            bot.send_message(msg.chat.id, format!("❌ {}", errors.join("\n❌ "))).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(persona_id) = profile.persona_id {
+        if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    }
+
+    ProfileRepository::save(&state.db_pool, &profile).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Profile <b>{}</b> saved: {}", html_escape(&name), html_escape(&profiles::describe(&profile))),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// List chat profiles
+/// Usage: /profiles
+pub async fn handle_profiles(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let all = ProfileRepository::list(&state.db_pool).await?;
+    if all.is_empty() {
+        bot.send_message(msg.chat.id, "📭 No chat profiles. Create one with /save_profile").await?;
+        return Ok(());
+    }
+
+    let mut response = "🎭 <b>Chat profiles</b>\n\n".to_string();
+    for profile in &all {
+        response.push_str(&format!(
+            "<b>{}</b>: {}\n",
+            html_escape(&profile.name),
+            html_escape(&profiles::describe(profile))
+        ));
+        if let Some(style) = &profile.style_notes {
+            response.push_str(&format!("   <i>{}</i>\n", html_escape(style)));
+        }
+    }
+    response.push_str("\nApply with /apply_profile &lt;id&gt; &lt;chat_id&gt; &lt;name&gt;");
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Delete a chat profile; chats keep the settings it applied
+/// Usage: /delete_profile <name>
+pub async fn handle_delete_profile(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = match args.first() {
+        Some(name) => name.to_lowercase(),
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /delete_profile <name>").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if ProfileRepository::delete(&state.db_pool, &name).await? {
+        format!("🗑 Profile '{}' deleted", name)
+    } else {
+        format!("❌ Profile '{}' not found", name)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Apply every setting of a profile to a chat at once
+/// Usage: /apply_profile <account_id> <chat_id> <name>
+pub async fn handle_apply_profile(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id, name) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2),
+    ) {
+        (Some(a), Some(c), Some(n)) => (a, c, n.to_lowercase()),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /apply_profile <account_id> <chat_id> <name>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, "❌ Account not found").await?;
+        return Ok(());
+    }
+
+    let profile = match ProfileRepository::get(&state.db_pool, &name).await? {
+        Some(profile) => profile,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Profile '{}' not found, see /profiles", name))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    ProfileRepository::apply(&state.db_pool, account_id, chat_id, &profile).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🎭 Chat <code>{}</code> now uses profile <b>{}</b>: {}",
+            chat_id,
+            html_escape(&profile.name),
+            html_escape(&profiles::describe(&profile))
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Change settings of many chats at once
+/// Usage: /set_all [all|groups|private|ids=<id,...>] key=value... (or /set_all <key> <value>)
+pub async fn handle_set_all(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (filter, patch) = match profiles::parse_bulk(&args) {
+        Ok(parsed) => parsed,
+        Err(errors) => {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "❌ {}\nUsage: /set_all [all|groups|private|ids=&lt;id,...&gt;] [persona=&lt;id&gt;] [prob=&lt;0-100&gt;] \
+                    [format=&lt;mode&gt;] [initiative=&lt;on|off&gt;] [cooldown=&lt;secs&gt;] [style=&lt;text&gt;]\n\
+                    Example: /set_all groups cooldown=10",
+                    html_escape(&errors.join("\n❌ "))
+                ),
+            )
+            .parse_mode(ParseMode::Html)
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(persona_id) = patch.persona_id {
+        if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    }
+
+    let targets: Vec<(i64, i64)> = ChatRepository::list_all(&state.db_pool)
+        .await?
+        .iter()
+        .filter(|chat| filter.matches(chat))
+        .map(|chat| (chat.account_id, chat.chat_id))
+        .collect();
+
+    if targets.is_empty() {
+        bot.send_message(msg.chat.id, format!("📭 No known chats match: {}", filter.describe())).await?;
+        return Ok(());
+    }
+
+    let actor_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
+    let updated = ProfileRepository::apply_bulk(&state.db_pool, &targets, &patch, actor_id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Updated {} chat(s) ({}): {}",
+            updated,
+            filter.describe(),
+            profiles::describe(&patch)
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// What the userbot thinks a group is discussing
+/// Usage: /topic <account_id> <chat_id>
+pub async fn handle_topic(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /topic <account_id> <chat_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let current = crate::ai::topics::current_topic(&state, account_id, chat_id).await?;
+    let trail = crate::db::TopicRepository::recent(&state.db_pool, account_id, chat_id, 6).await?;
+
+    let mut response = match &current {
+        Some(topic) => format!(
+            "💭 <b>Now discussing</b> (since {} UTC)\n{}\n",
+            topic.created_at.format("%H:%M"),
+            html_escape(&topic.topic)
+        ),
+        None if state.config.topic_every_messages == 0 => "💭 Topic tracking is off (TOPIC_EVERY_MESSAGES=0)\n".to_string(),
+        None => "💭 No current topic yet\n".to_string(),
+    };
+
+    let earlier: Vec<_> = trail
+        .iter()
+        .filter(|t| current.as_ref().map_or(true, |c| c.id != t.id))
+        .collect();
+    if !earlier.is_empty() {
+        response.push_str("\n<b>Earlier</b>\n");
+        for topic in earlier {
+            response.push_str(&format!(
+                "{} — {}\n",
+                topic.created_at.format("%m-%d %H:%M"),
+                html_escape(&topic.topic)
+            ));
+        }
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Messages /messages shows by default, and at most
+const MESSAGES_PAGE: i64 = 20;
+const MESSAGES_MAX: i64 = 50;
+
+/// Characters of a message body shown by /messages
+const MESSAGE_PREVIEW_CHARS: usize = 120;
+
+/// Text /messages puts in one Telegram message, under its 4096-character limit
+const MESSAGES_CHUNK_CHARS: usize = 3800;
+
+/// Recent stored messages of a chat with who wrote them, oldest first
+/// Usage: /messages <account_id> <chat_id> [limit] [before=<message_id>]
+pub async fn handle_messages(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /messages <account_id> <chat_id> [limit] [before=<message_id>]";
+    let (account_id, chat_id) = match settings_history::chat_scope(&args) {
+        Some(ids) => ids,
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+    let mut limit = MESSAGES_PAGE;
+    let mut before = None;
+    for arg in &args[2..] {
+        match (arg.strip_prefix("before="), arg.parse::<i64>()) {
+            (Some(id), _) => match id.parse::<i64>() {
+                Ok(id) => before = Some(id),
+                Err(_) => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            },
+            (None, Ok(n)) if n > 0 => limit = n.min(MESSAGES_MAX),
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let messages = MessageRepository::page_all(&state.db_pool, account_id, chat_id, before, limit).await?;
+    let (first, last) = match (messages.first(), messages.last()) {
+        (Some(first), Some(last)) => (first.id, last.id),
+        _ => {
+            bot.send_message(msg.chat.id, format!("💬 No stored messages in chat {}", chat_id)).await?;
+            return Ok(());
+        }
+    };
+    let ghost_edited = MessageRepository::ghost_edited_between(&state.db_pool, account_id, chat_id, first, last).await?;
+
+    // Names and bot flags come from the running userbot; without it senders show as ids
+    let mut senders: HashMap<i64, (String, bool)> = HashMap::new();
+    if let Some(handle) = state.get_userbot(account_id).await {
+        use rust_tdlib::types::*;
+        let client_lock = handle.client.lock().await;
+        for sender_id in messages.iter().filter_map(|m| m.sender_id).filter(|id| *id != 0) {
+            if senders.contains_key(&sender_id) {
+                continue;
+            }
+            if let Ok(user) = client_lock.get_user(&GetUser::builder().user_id(sender_id).build()).await {
+                let name = match user.last_name().is_empty() {
+                    true => user.first_name().to_string(),
+                    false => format!("{} {}", user.first_name(), user.last_name()),
+                };
+                senders.insert(sender_id, (name, matches!(user.type_(), UserType::Bot(_))));
+            }
+        }
+    }
+
+    let mut lines = Vec::new();
+    for message in &messages {
+        let author = match (message.role.as_str(), message.sender_id, message.sender_chat_id) {
+            ("assistant", _, _) => match message.persona_id {
+                Some(id) => format!("🎭 reply · persona #{}", id),
+                None => "🎭 reply".to_string(),
+            },
+            (_, _, Some(channel)) => format!("📢 channel {}", channel),
+            (_, Some(id), _) => match senders.get(&id) {
+                Some((name, true)) => format!("🤖 {} ({})", html_escape(name), id),
+                Some((name, false)) => format!("👤 {} ({})", html_escape(name), id),
+                None => format!("👤 {}", id),
+            },
+            _ => "👤 unknown".to_string(),
+        };
+        let flag = if ghost_edited.contains(&message.id) { " · ✏️ edited by owner" } else { "" };
+
+        let body = if message.is_hashed {
+            "<i>(hashed)</i>".to_string()
+        } else {
+            let mut preview: String = message.content.chars().take(MESSAGE_PREVIEW_CHARS).collect();
+            if message.content.chars().count() > MESSAGE_PREVIEW_CHARS {
+                preview.push('…');
+            }
+            html_escape(&preview)
+        };
+        lines.push(format!(
+            "<code>#{}</code> <i>{}</i> {}{}\n{}",
+            message.id,
+            message.created_at.format("%m-%d %H:%M"),
+            author,
+            flag,
+            body
+        ));
+    }
+    if messages.len() as i64 == limit {
+        lines.push(format!("Older: /messages {} {} {} before={}", account_id, chat_id, limit, first));
+    }
+
+    // Long pages go out in several messages, split between entries so no HTML tag is cut
+    let mut text = format!("💬 <b>Messages of chat {}</b>\n\n", chat_id);
+    for line in lines {
+        if text.len() + line.len() > MESSAGES_CHUNK_CHARS {
+            bot.send_message(msg.chat.id, std::mem::take(&mut text)).parse_mode(ParseMode::Html).await?;
+        }
+        text.push_str(&line);
+        text.push_str("\n\n");
+    }
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Show or set the words that always get a reply in a chat
+/// Usage: /chat_triggers <account_id> <chat_id> [word, word ...|off]
+pub async fn handle_chat_triggers(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /chat_triggers <account_id> <chat_id> [word, word ...|off]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = match args.get(2).map(|s| s.as_str()) {
+        None => {
+            let triggers = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .map(|c| c.get_triggers())
+                .unwrap_or_default();
+            if triggers.is_empty() {
+                format!("🏷 No reply triggers in chat {}", chat_id)
+            } else {
+                format!("🏷 Reply triggers in chat {}: {}", chat_id, triggers.join(", "))
+            }
+        }
+        Some("off") => {
+            ChatRepository::set_triggers(&state.db_pool, account_id, chat_id, None).await?;
+            format!("✅ Reply triggers cleared in chat {}", chat_id)
+        }
+        Some(_) => {
+            let triggers = onboarding::parse_triggers(&args[2..].join(" "));
+            if triggers.is_empty() {
+                bot.send_message(msg.chat.id, "❌ Give at least one trigger word").await?;
+                return Ok(());
+            }
+            ChatRepository::set_triggers(&state.db_pool, account_id, chat_id, Some(&triggers.join(","))).await?;
+            format!("✅ Messages mentioning {} always get a reply in chat {}", triggers.join(", "), chat_id)
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Forward voice and video notes whose transcript mentions one of the keywords to the owner
+/// Usage: /chat_escalation <account_id> <chat_id> [word, word ...|off]
+pub async fn handle_chat_escalation(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /chat_escalation <account_id> <chat_id> [word, word ...|off]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = match args.get(2).map(|s| s.as_str()) {
+        None => {
+            let keywords = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .map(|c| c.get_escalation_keywords())
+                .unwrap_or_default();
+            if keywords.is_empty() {
+                format!("🚨 No escalation keywords in chat {}", chat_id)
+            } else {
+                format!("🚨 Voice and video notes mentioning {} in chat {} are forwarded to you", keywords.join(", "), chat_id)
+            }
+        }
+        Some("off") => {
+            ChatRepository::set_escalation_keywords(&state.db_pool, account_id, chat_id, None).await?;
+            format!("✅ Escalation turned off in chat {}", chat_id)
+        }
+        Some(_) => {
+            let keywords = onboarding::parse_triggers(&args[2..].join(" "));
+            if keywords.is_empty() {
+                bot.send_message(msg.chat.id, "❌ Give at least one keyword").await?;
+                return Ok(());
+            }
+            ChatRepository::set_escalation_keywords(&state.db_pool, account_id, chat_id, Some(&keywords.join(",")))
+                .await?;
+            let mut text = format!(
+                "✅ Voice and video notes mentioning {} in chat {} will be forwarded to you with their transcript",
+                keywords.join(", "),
+                chat_id
+            );
+            if state.config.whisper_url.is_none() {
+                text.push_str("\n⚠️ WHISPER_URL is not set, so nothing can be transcribed yet");
+            }
+            text
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Make replies in a chat delete themselves after some minutes
+/// Usage: /chat_ephemeral <account_id> <chat_id> [minutes|off]
+pub async fn handle_chat_ephemeral(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_ephemeral <account_id> <chat_id> [minutes|off]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let text = match args.get(2).map(|s| s.as_str()) {
+        None => {
+            let minutes = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .and_then(|c| c.ephemeral_minutes);
+            let pending = EphemeralRepository::count_pending(&state.db_pool, account_id, chat_id).await?;
+            match minutes {
+                Some(m) => format!("⏳ Replies in chat {} delete themselves after {} min ({} pending)", chat_id, m, pending),
+                None => format!("⏳ Replies in chat {} are kept ({} pending deletions)", chat_id, pending),
+            }
+        }
+        Some("off") => {
+            ChatRepository::set_ephemeral(&state.db_pool, account_id, chat_id, None).await?;
+            format!("✅ New replies in chat {} are kept; already scheduled ones still go", chat_id)
+        }
+        Some(value) => match value.parse::<i64>() {
+            Ok(m) if (1..=7 * 24 * 60).contains(&m) => {
+                ChatRepository::set_ephemeral(&state.db_pool, account_id, chat_id, Some(m)).await?;
+                format!(
+                    "✅ Replies in chat {} will delete themselves after {} min, unless pinned or answered",
+                    chat_id, m
+                )
+            }
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or change how messages written in quick succession are batched in a chat
+/// Usage: /chat_debounce <account_id> <chat_id> [<ms>|off|default] [max=<n>]
+pub async fn handle_chat_debounce(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_debounce <account_id> <chat_id> [<ms>|off|default] [max=<n>]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+
+    let (window, max_batch) = match (args.get(2).map(|s| s.as_str()), args.get(3).map(|s| s.as_str())) {
+        (None, _) => {
+            let settings = DebounceSettings::for_chat(&state, chat.as_ref());
+            let text = if settings.is_enabled() {
+                format!(
+                    "⏱ Chat {}: messages are answered after {} ms of quiet (longer while typing, {} ms at most), \
+                    up to {} per batch{}",
+                    chat_id,
+                    settings.window.as_millis(),
+                    settings.max_wait.as_millis(),
+                    settings.max_batch,
+                    if chat.as_ref().is_some_and(|c| c.debounce_ms.is_some() || c.debounce_max_batch.is_some()) {
+                        ""
+                    } else {
+                        " (defaults)"
+                    }
+                )
+            } else {
+                format!("⏱ Chat {}: every message is answered on its own", chat_id)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        (Some("default"), None) => (None, None),
+        (Some("off"), None) => (Some(0), None),
+        (Some(ms), max) => {
+            let window = ms.parse::<i64>().ok().filter(|ms| (0..=60_000).contains(ms));
+            let max_batch = match max.map(|m| m.strip_prefix("max=").and_then(|n| n.parse::<i64>().ok())) {
+                None => Some(chat.as_ref().and_then(|c| c.debounce_max_batch)),
+                Some(Some(n)) if (1..=50).contains(&n) => Some(Some(n)),
+                Some(_) => None,
+            };
+            match (window, max_batch) {
+                (Some(window), Some(max_batch)) => (Some(window), max_batch),
+                _ => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    ChatRepository::set_debounce(&state.db_pool, account_id, chat_id, window, max_batch).await?;
+
+    let text = match window {
+        None => format!("✅ Chat {} uses the default batching", chat_id),
+        Some(0) => format!("✅ Every message in chat {} is answered on its own", chat_id),
+        Some(ms) => format!(
+            "✅ Chat {}: messages are answered after {} ms of quiet{}",
+            chat_id,
+            ms,
+            max_batch.map(|n| format!(", up to {} per batch", n)).unwrap_or_default()
+        ),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or change what happens to messages a chat missed while the account was offline
+/// Usage: /chat_catchup <account_id> <chat_id> [off|summary|post]
+pub async fn handle_chat_catchup(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_catchup <account_id> <chat_id> [off|summary|post]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let mode = match args.get(2) {
+        None => {
+            let mode = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .and_then(|c| CatchupMode::parse(&c.catchup_mode))
+                .unwrap_or(CatchupMode::Summary);
+            bot.send_message(msg.chat.id, format!("🔁 Catch-up mode of chat {}: {}", chat_id, mode.as_str()))
+                .await?;
+            return Ok(());
+        }
+        Some(value) => match CatchupMode::parse(value) {
+            Some(mode) => mode,
+            None => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    ChatRepository::set_catchup_mode(&state.db_pool, account_id, chat_id, mode.as_str()).await?;
+
+    let text = match mode {
+        CatchupMode::Off => format!("✅ Messages chat {} sends while the account is offline are ignored", chat_id),
+        CatchupMode::Summary => format!("✅ Chat {}: missed messages are summarized into memory", chat_id),
+        CatchupMode::Post => format!(
+            "✅ Chat {}: missed messages are summarized into memory, and after more than {} min away the account \
+            says it's back",
+            chat_id, state.config.catchup_post_after_minutes
+        ),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or switch whether a new persona mentions taking over a chat
+/// Usage: /chat_switch_notice <account_id> <chat_id> [on|off]
+pub async fn handle_chat_switch_notice(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_switch_notice <account_id> <chat_id> [on|off]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let enabled = match args.get(2).map(String::as_str) {
+        None => {
+            let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+            let enabled = chat.as_ref().is_some_and(|c| c.announce_persona_switch);
+            let mut text = if enabled {
+                format!("🎭 Chat {}: a new persona mentions taking over in its first reply", chat_id)
+            } else {
+                format!("🎭 Chat {}: persona switches go unannounced", chat_id)
+            };
+            if let Some(since) = chat.as_ref().and_then(|c| c.context_since) {
+                text.push_str(&format!("\nLast switch: {} UTC", since.format("%Y-%m-%d %H:%M")));
+            }
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_announce_persona_switch(&state.db_pool, account_id, chat_id, enabled).await?;
+    let text = if enabled {
+        format!("✅ Chat {}: a new persona will mention taking over", chat_id)
+    } else {
+        format!("✅ Chat {}: persona switches go unannounced", chat_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or switch native polls in a chat
+/// Usage: /chat_polls <account_id> <chat_id> [on|off]
+pub async fn handle_chat_polls(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_polls <account_id> <chat_id> [on|off]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let enabled = match args.get(2).map(String::as_str) {
+        None => {
+            let enabled = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .is_some_and(|c| c.polls_enabled);
+            let text = if enabled {
+                format!("📊 Chat {}: \"make a poll\" requests get a native poll", chat_id)
+            } else {
+                format!("📊 Chat {}: polls are off", chat_id)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_polls(&state.db_pool, account_id, chat_id, enabled).await?;
+    let text = if enabled {
+        format!("✅ Chat {}: the account creates polls and quizzes when asked", chat_id)
+    } else {
+        format!("✅ Chat {}: polls are off", chat_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Let the persona pin announcements in a chat
+/// Usage: /chat_pins <account_id> <chat_id> [on|off]
+pub async fn handle_chat_pins(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_pins <account_id> <chat_id> [on|off]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let enabled = match args.get(2).map(String::as_str) {
+        None => {
+            let enabled = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .is_some_and(|c| c.pins_enabled);
+            let text = if enabled {
+                format!("📌 Chat {}: the persona pins messages it finds important", chat_id)
+            } else {
+                format!("📌 Chat {}: the persona doesn't pin (owners still can with \"закрепи\")", chat_id)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Some("on") => true,
+        Some("off") => false,
+        Some(_) => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    ChatRepository::set_pins(&state.db_pool, account_id, chat_id, enabled).await?;
+    let text = if enabled {
+        format!(
+            "✅ Chat {}: the persona may pin announcements. The account needs the right to pin there; see /audit {} {}",
+            chat_id, account_id, chat_id
+        )
+    } else {
+        format!("✅ Chat {}: the persona no longer pins", chat_id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// "👁 vision on · 🎙 voice off · 🔎 search on"
+fn media_summary(chat: Option<&crate::db::AccountChat>) -> String {
+    let (vision, voice, web_search) = chat.map_or((true, true, true), |c| (c.vision_enabled, c.voice_enabled, c.web_search_enabled));
+    let word = |on: bool| if on { "on" } else { "off" };
+    format!("👁 vision {} · 🎙 voice {} · 🔎 search {}", word(vision), word(voice), word(web_search))
+}
+
+/// Keep a chat's photos, voice notes or questions away from the vision model, Whisper or web search
+/// Usage: /chat_media <account_id> <chat_id> [vision=on|off] [voice=on|off] [search=on|off]
+pub async fn handle_chat_media(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_media <account_id> <chat_id> [vision=on|off] [voice=on|off] [search=on|off]\n\n\
+        Turning one off only keeps this chat out of it; a feature that is off globally stays off.";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    if args.len() == 2 {
+        bot.send_message(msg.chat.id, format!("🖼 Chat {}: {}", chat_id, media_summary(chat.as_ref()))).await?;
+        return Ok(());
+    }
+
+    let (mut vision, mut voice, mut web_search) =
+        chat.as_ref().map_or((true, true, true), |c| (c.vision_enabled, c.voice_enabled, c.web_search_enabled));
+    for arg in &args[2..] {
+        let (name, value) = arg.split_once('=').unwrap_or((arg.as_str(), ""));
+        let on = match value.to_lowercase().as_str() {
+            "on" => true,
+            "off" => false,
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        };
+        match name.to_lowercase().as_str() {
+            "vision" => vision = on,
+            "voice" => voice = on,
+            "search" => web_search = on,
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    ChatRepository::set_media(&state.db_pool, account_id, chat_id, vision, voice, web_search).await?;
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    bot.send_message(msg.chat.id, format!("✅ Chat {}: {}", chat_id, media_summary(chat.as_ref()))).await?;
+    Ok(())
+}
+
+/// Show or set a chat's quiet hours
+/// Usage: /chat_quiet <account_id> <chat_id> [HH:MM-HH:MM|on|off] [mentions|strict]
+pub async fn handle_chat_quiet(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_quiet <account_id> <chat_id> [HH:MM-HH:MM|on|off] [mentions|strict]\n\n\
+        mentions: direct mentions and replies are still answered (default)\n\
+        strict: nothing is answered\n\n\
+        Example: /chat_quiet 1 -1001234567890 23:00-08:00";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let mut window = quiet_hours::of(chat.as_ref());
+    let mut allow_mentions = chat.as_ref().map_or(true, |c| c.quiet_allow_mentions);
+
+    if args.len() > 2 {
+        for arg in &args[2..] {
+            match arg.as_str() {
+                "off" => window = None,
+                "on" => window = Some(window.unwrap_or(state.config.default_quiet_hours)),
+                "mentions" => allow_mentions = true,
+                "strict" => allow_mentions = false,
+                value => match quiet_hours::QuietHours::parse(value) {
+                    Some(parsed) => window = Some(parsed),
+                    None => {
+                        bot.send_message(msg.chat.id, usage).await?;
+                        return Ok(());
+                    }
+                },
+            }
+        }
+        let formatted = window.map(|w| w.format());
+        ChatRepository::set_quiet_hours(&state.db_pool, account_id, chat_id, formatted.as_deref(), allow_mentions)
+            .await?;
+    }
+
+    let text = match window {
+        Some(window) => format!(
+            "🌙 Chat {}: quiet {} local time ({}), {}",
+            chat_id,
+            window.format(),
+            timezone::chat_timezone(chat.as_ref(), state.config.default_timezone),
+            if allow_mentions { "mentions still answered" } else { "nothing answered" }
+        ),
+        None => format!("🔔 Chat {} has no quiet hours", chat_id),
+    };
+    let text = if args.len() > 2 { format!("✅ {}", text) } else { text };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or change how replies write numbers, dates, money and units in a chat
+/// Usage: /chat_locale <account_id> <chat_id> [<locale>|off] [metric|imperial] [latin|keep]
+pub async fn handle_chat_locale(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::localize::{Locale, Transliteration, Units, LOCALES};
+
+    let locales: Vec<&str> = LOCALES.iter().map(|l| l.tag).collect();
+    let usage = format!(
+        "❌ Usage: /chat_locale <account_id> <chat_id> [<locale>|off] [metric|imperial] [latin|keep]\n\nLocales: {}",
+        locales.join(", ")
+    );
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let mut locale = chat.as_ref().and_then(|c| c.locale.as_deref()).and_then(Locale::find);
+    let mut units = chat.as_ref().and_then(|c| c.units.as_deref()).and_then(Units::parse);
+    let mut transliteration = chat
+        .as_ref()
+        .and_then(|c| Transliteration::parse(&c.transliteration))
+        .unwrap_or(Transliteration::Keep);
+
+    if args.len() > 2 {
+        for arg in &args[2..] {
+            if arg == "off" {
+                locale = None;
+                units = None;
+                transliteration = Transliteration::Keep;
+            } else if let Some(parsed) = Units::parse(arg) {
+                units = Some(parsed);
+            } else if let Some(parsed) = Transliteration::parse(arg) {
+                transliteration = parsed;
+            } else if let Some(parsed) = Locale::find(arg) {
+                locale = Some(parsed);
+            } else {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        }
+        ChatRepository::set_locale(
+            &state.db_pool,
+            account_id,
+            chat_id,
+            locale.map(|l| l.tag),
+            units.map(|u| u.as_str()),
+            transliteration.as_str(),
+        )
+        .await?;
+    }
+
+    let text = match locale {
+        Some(locale) => format!(
+            "🌐 Chat {}: replies use {} formats, {} units, transliteration {}",
+            chat_id,
+            locale.tag,
+            units.map_or("the locale's", |u| u.as_str()),
+            transliteration.as_str()
+        ),
+        None => format!(
+            "🌐 Chat {}: numbers, dates and units are left as generated, transliteration {}",
+            chat_id,
+            transliteration.as_str()
+        ),
+    };
+    let text = if args.len() > 2 { format!("✅ {}", text) } else { text };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Let the bandit tune a chat's reply probability within bounds, or stop it
+/// Usage: /chat_tuning <account_id> <chat_id> <min> <max>|off
+pub async fn handle_chat_tuning(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_tuning <account_id> <chat_id> <min> <max>|off\n\nExample: /chat_tuning 1 -1001234567890 5 40";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    if args.get(2).map(|a| a.as_str()) == Some("off") {
+        ChatRepository::set_tuning_bounds(&state.db_pool, account_id, chat_id, None).await?;
+        TuningRepository::reset(&state.db_pool, account_id, chat_id).await?;
+        bot.send_message(
+            msg.chat.id,
+            format!("✅ Reply probability of chat {} is no longer tuned; it stays where the tuner left it", chat_id),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let bounds = match (
+        args.get(2).and_then(|a| a.trim_end_matches('%').parse::<i64>().ok()),
+        args.get(3).and_then(|a| a.trim_end_matches('%').parse::<i64>().ok()),
+    ) {
+        (Some(min), Some(max)) if (0..=100).contains(&min) && (0..=100).contains(&max) && min <= max => (min, max),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    // New bounds mean new arms: what was learned no longer applies
+    ChatRepository::set_tuning_bounds(&state.db_pool, account_id, chat_id, Some(bounds)).await?;
+    TuningRepository::reset(&state.db_pool, account_id, chat_id).await?;
+
+    let arms = tuning::arms(bounds.0, bounds.1)
+        .iter()
+        .map(|p| format!("{}%", p))
+        .collect::<Vec<_>>()
+        .join(", ");
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Chat {} reply probability is now tuned between {}% and {}%\n\n\
+            Tried: {}. Each one runs for {}h, then gets scored by replies to the account \
+            and \"заткнись\"-style messages per reply sent. See /tuning_report {} {}",
+            chat_id, bounds.0, bounds.1, arms, state.config.tuning_epoch_hours, account_id, chat_id
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Explain the tuning of a chat: current epoch, how each probability did and the latest changes
+/// Usage: /tuning_report <account_id> <chat_id>
+pub async fn handle_tuning_report(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /tuning_report <account_id> <chat_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let bounds = match chat.as_ref().and_then(|c| c.tune_min_probability.zip(c.tune_max_probability)) {
+        Some(bounds) => bounds,
+        None => {
+            bot.send_message(
+                msg.chat.id,
+                format!("ℹ️ Chat {} is not tuned. Enable it with /chat_tuning {} {} <min> <max>", chat_id, account_id, chat_id),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = format!(
+        "🎛 <b>Reply probability tuning, chat {}</b>\nBounds: {}–{}%, epochs of {}h\n",
+        chat_id, bounds.0, bounds.1, state.config.tuning_epoch_hours
+    );
+
+    match TuningRepository::get_epoch(&state.db_pool, account_id, chat_id).await? {
+        Some(epoch) => {
+            let replies = TuningRepository::epoch_activity(&state.db_pool, account_id, chat_id).await?;
+            let signals = tuning::Signals { replies, replies_to_bot: epoch.replies_to_bot, negatives: epoch.negatives };
+            text.push_str(&format!(
+                "\n<b>Now:</b> {}% since {} UTC\n{}\n",
+                epoch.probability,
+                epoch.started_at.format("%d.%m %H:%M"),
+                signals.describe()
+            ));
+        }
+        None => text.push_str("\n<b>Now:</b> the first epoch starts within 30 minutes\n"),
+    }
+
+    let stats = TuningRepository::arms(&state.db_pool, account_id, chat_id).await?;
+    text.push_str("\n<b>Probabilities:</b>\n");
+    for arm in tuning::arms(bounds.0, bounds.1) {
+        match stats.iter().find(|s| s.probability == arm && s.pulls > 0) {
+            Some(s) => text.push_str(&format!(
+                "• {}%: avg. engagement {:.2} over {} epochs\n",
+                arm,
+                s.reward_sum / s.pulls as f64,
+                s.pulls
+            )),
+            None => text.push_str(&format!("• {}%: not measured yet\n", arm)),
+        }
+    }
+
+    let decisions = TuningRepository::recent_decisions(&state.db_pool, account_id, chat_id, 5).await?;
+    if !decisions.is_empty() {
+        text.push_str("\n<b>Latest changes:</b>\n");
+        for decision in &decisions {
+            text.push_str(&format!(
+                "• {} {}% → {}%: {}\n",
+                decision.created_at.format("%d.%m %H:%M"),
+                decision.old_probability,
+                decision.new_probability,
+                html_escape(&decision.reason)
+            ));
+        }
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Revert the newest settings change of a chat
+/// Usage: /undo_last_setting <account_id> <chat_id>
+pub async fn handle_undo_last_setting(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match settings_history::chat_scope(&args) {
+        Some(scope) => scope,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /undo_last_setting <account_id> <chat_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let change = match settings_history::undo_last(&state, account_id, chat_id).await {
+        Ok(Some(change)) => change,
+        Ok(None) => {
+            bot.send_message(msg.chat.id, format!("ℹ️ No settings changes of chat {} to undo", chat_id)).await?;
+            return Ok(());
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ Failed to undo: {}", html_escape(&e.to_string())))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = format!(
+        "↩️ <b>Reverted {}</b> in chat {}
+Changed {} UTC by {}
+
+",
+        html_escape(&change.command),
+        chat_id,
+        change.created_at.format("%d.%m %H:%M"),
+        change.actor_id
+    );
+    for line in settings_history::describe(&change) {
+        text.push_str(&format!("• {}\n", html_escape(&line)));
+    }
+    if change.old_values.is_none() {
+        text.push_str("\nThe chat had no settings before, so they were removed.");
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Settings changes of a chat, newest first, with who made them
+/// Usage: /settings_history <account_id> <chat_id> [limit]
+pub async fn handle_settings_history(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match settings_history::chat_scope(&args) {
+        Some(scope) => scope,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /settings_history <account_id> <chat_id> [limit]").await?;
+            return Ok(());
+        }
+    };
+    let limit = args.get(2).and_then(|a| a.parse::<i64>().ok()).unwrap_or(15).clamp(1, 50);
+
+    let changes = SettingChangeRepository::history(&state.db_pool, account_id, chat_id, limit).await?;
+    if changes.is_empty() {
+        bot.send_message(msg.chat.id, format!("ℹ️ Settings of chat {} were never changed", chat_id)).await?;
+        return Ok(());
+    }
+
+    let mut text = format!("🗂 <b>Settings history of chat {}</b>
+
+", chat_id);
+    for change in &changes {
+        text.push_str(&format!(
+            "{}<b>#{}</b> {} UTC · {} by {}{}
+",
+            if change.undone { "<s>" } else { "" },
+            change.id,
+            change.created_at.format("%d.%m %H:%M"),
+            html_escape(&change.command),
+            change.actor_id,
+            if change.undone { "</s> (undone)" } else { "" }
+        ));
+        for line in settings_history::describe(change) {
+            text.push_str(&format!("  • {}
+", html_escape(&line)));
+        }
+    }
+    text.push_str(&format!(
+        "
+/settings_rollback {} {} &lt;change_id&gt; puts the settings back to how they were before a change",
+        account_id, chat_id
+    ));
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Undo a settings change and every later one, restoring the chat to how it was before it
+/// Usage: /settings_rollback <account_id> <chat_id> <change_id> [confirm]
+pub async fn handle_settings_rollback(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /settings_rollback <account_id> <chat_id> <change_id> [confirm] (see /settings_history)";
+    let ((account_id, chat_id), change_id) =
+        match (settings_history::chat_scope(&args), args.get(2).and_then(|a| a.parse::<i64>().ok())) {
+            (Some(scope), Some(id)) => (scope, id),
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        };
+
+    if args.get(3).map(|a| a.as_str()) != Some("confirm") {
+        let pending = SettingChangeRepository::active_since(&state.db_pool, account_id, chat_id, change_id).await?;
+        if pending.last().map(|c| c.id) != Some(change_id) {
+            bot.send_message(
+                msg.chat.id,
+                format!("❌ Change #{} is not an active change of chat {} (see /settings_history)", change_id, chat_id),
+            )
+            .await?;
+            return Ok(());
+        }
+        let mut text = format!("⚠️ <b>Rolling back to before #{}</b> reverts {} change(s):
+", change_id, pending.len());
+        for change in &pending {
+            text.push_str(&format!("• #{} {}
+", change.id, html_escape(&change.command)));
+        }
+        text.push_str(&format!(
+            "
+Run <code>/settings_rollback {} {} {} confirm</code> to proceed.",
+            account_id, chat_id, change_id
+        ));
+        bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+        return Ok(());
+    }
+
+    let reverted = match settings_history::rollback_to(&state, account_id, chat_id, change_id).await {
+        Ok(reverted) => reverted,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ Failed to roll back: {}", html_escape(&e.to_string())))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = format!(
+        "↩️ <b>Chat {} is back to before #{}</b>
+Reverted {} change(s):
+",
+        chat_id,
+        change_id,
+        reverted.len()
+    );
+    for change in &reverted {
+        text.push_str(&format!("• #{} {}
+", change.id, html_escape(&change.command)));
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Show or change the daily media quotas of a chat
+/// Usage: /chat_quota <account_id> <chat_id> [vision|voice <limit|default>]
+pub async fn handle_chat_quota(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_quota <account_id> <chat_id> [vision|voice <limit|default>]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(kind) = args.get(2) {
+        let kind = match MediaKind::parse(kind) {
+            Some(kind) => kind,
+            None => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        };
+        let limit = match args.get(3).map(|s| s.as_str()) {
+            Some("default") => None,
+            Some(value) => match value.parse::<i64>() {
+                Ok(n) if n >= 0 => Some(n),
+                _ => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            },
+            None => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        };
+        MediaQuotaRepository::set_limit(&state.db_pool, account_id, chat_id, kind.as_str(), limit).await?;
+    }
+
+    let today = quotas::today();
+    let mut text = format!("📊 Media quotas of chat {} (UTC day, owners exempt)\n", chat_id);
+    for kind in MediaKind::ALL {
+        let quota = MediaQuotaRepository::get(&state.db_pool, account_id, chat_id, kind.as_str()).await?;
+        let used = quota.as_ref().map(|q| q.used_on(&today)).unwrap_or(0);
+        let limit = quotas::effective_limit(quota.as_ref(), kind.default_limit(&state));
+        let source = if quota.as_ref().and_then(|q| q.daily_limit).is_some() { "" } else { " (default)" };
+        text.push_str(&format!("\n{} {}: {}{}", kind.icon(), kind.as_str(), quotas::describe(used, limit), source));
+    }
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or toggle the answer cache of a chat
+/// Usage: /chat_cache <account_id> <chat_id> [on|off|clear]
+pub async fn handle_chat_cache(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parsed = (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).map(|a| a.as_str()),
+    );
+
+    let (account_id, chat_id, action) = match parsed {
+        (Some(a), Some(c), action @ (None | Some("on" | "off" | "clear"))) => (a, c, action),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /chat_cache <account_id> <chat_id> [on|off|clear]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = match action {
+        Some("on") => {
+            ChatRepository::set_answer_cache(&state.db_pool, account_id, chat_id, true).await?;
+            format!(
+                "✅ Repeated questions in chat {} reuse answers for {}h",
+                chat_id, state.config.answer_cache_ttl_hours
+            )
+        }
+        Some("off") => {
+            ChatRepository::set_answer_cache(&state.db_pool, account_id, chat_id, false).await?;
+            format!("✅ Answer cache disabled in chat {}", chat_id)
+        }
+        Some(_) => {
+            let removed = AnswerCacheRepository::clear(&state.db_pool, account_id, chat_id).await?;
+            format!("🗑 Removed {} cached answers from chat {}", removed, chat_id)
+        }
+        None => {
+            let enabled = ChatRepository::get(&state.db_pool, account_id, chat_id)
+                .await?
+                .is_some_and(|c| c.answer_cache_enabled);
+            let answers = AnswerCacheRepository::list(&state.db_pool, account_id, chat_id).await?;
+            let stale_before = chrono::Utc::now() - chrono::Duration::hours(state.config.answer_cache_ttl_hours);
+
+            let mut response = format!(
+                "💾 <b>Answer cache of chat {}</b>: {}\n{} answers, {} hits\n",
+                chat_id,
+                if enabled { "on" } else { "off" },
+                answers.len(),
+                answers.iter().map(|a| a.hits).sum::<i64>()
+            );
+            for answer in answers.iter().take(10) {
+                response.push_str(&format!(
+                    "\n{} {}× {}",
+                    if answer.updated_at < stale_before { "⌛" } else { "•" },
+                    answer.hits,
+                    html_escape(&answer.question.chars().take(80).collect::<String>())
+                ));
+            }
+
+            bot.send_message(msg.chat.id, response)
+                .parse_mode(ParseMode::Html)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show or change the memory retrieval filters of a chat
+/// Usage: /chat_rag <account_id> <chat_id> [sender=on|off] [bots=on|off] [days=<n>|off]
+pub async fn handle_chat_rag(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const USAGE: &str = "❌ Usage: /chat_rag <account_id> <chat_id> [sender=on|off] [bots=on|off] [days=<n>|off] [private=on|off]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, USAGE).await?;
+            return Ok(());
+        }
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+    let mut prefer_sender = chat.as_ref().map(|c| c.rag_prefer_sender).unwrap_or(false);
+    let mut exclude_bots = chat.as_ref().map(|c| c.rag_exclude_bots).unwrap_or(false);
+    let mut max_age_days = chat.as_ref().and_then(|c| c.rag_max_age_days);
+    let mut private_recall = chat.as_ref().map(|c| c.rag_private_recall).unwrap_or(false);
+
+    for arg in &args[2..] {
+        match arg.split_once('=') {
+            Some(("sender", "on")) => prefer_sender = true,
+            Some(("sender", "off")) => prefer_sender = false,
+            Some(("bots", "on")) => exclude_bots = false,
+            Some(("bots", "off")) => exclude_bots = true,
+            Some(("days", "off")) => max_age_days = None,
+            Some(("private", "on")) => private_recall = true,
+            Some(("private", "off")) => private_recall = false,
+            Some(("days", days)) if days.parse::<i64>().is_ok_and(|d| d > 0) => {
+                max_age_days = days.parse().ok();
+            }
+            _ => {
+                bot.send_message(msg.chat.id, USAGE).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    if args.len() > 2 {
+        ChatRepository::set_rag_scope(
+            &state.db_pool,
+            account_id,
+            chat_id,
+            prefer_sender,
+            exclude_bots,
+            max_age_days,
+            private_recall,
+        )
+        .await?;
+    }
+
+    let on_off = |b: bool| if b { "on" } else { "off" };
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🧠 <b>Memory retrieval in chat</b> <code>{}</code>\n\n\
+             Prefer the current interlocutor: {}\n\
+             Bot-authored memories: {}\n\
+             Time range: {}\n\
+             Private chats of members who allowed it (/privacy on): {}",
+            chat_id,
+            on_off(prefer_sender),
+            on_off(!exclude_bots),
+            max_age_days.map(|d| format!("last {} days", d)).unwrap_or_else(|| "all".to_string()),
+            on_off(private_recall)
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Whether bot-authored messages (our replies, other bots) are embedded into a chat's memory
+/// Usage: /chat_bot_memory <account_id> <chat_id> [on|off|default]
+pub async fn handle_chat_bot_memory(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /chat_bot_memory <account_id> <chat_id> [on|off|default]";
+
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let embed = match args.get(2).map(|a| a.to_lowercase()).as_deref() {
+        None => ChatRepository::get(&state.db_pool, account_id, chat_id).await?.and_then(|c| c.embed_bot_messages),
+        Some(value) => {
+            let embed = match value {
+                "on" => Some(true),
+                "off" => Some(false),
+                "default" => None,
+                _ => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            };
+            ChatRepository::set_embed_bot_messages(&state.db_pool, account_id, chat_id, embed).await?;
+            embed
+        }
+    };
+
+    let effective = embed.unwrap_or(state.config.embed_bot_messages);
+    let text = format!(
+        "🤖 Bot-authored messages in chat {}: {}{}",
+        chat_id,
+        if effective {
+            format!("embedded with importance {}", state.config.bot_memory_importance)
+        } else {
+            "not embedded".to_string()
+        },
+        if embed.is_none() { " (global default)" } else { "" }
+    );
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Configure the weekly chat digest
+/// Usage: /digest <account_id> <chat_id> <on [day] [hour]|off|now>
+pub async fn handle_digest(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /digest <account_id> <chat_id> <on [mon..sun] [0-23]|off|now>";
+
+    let (account_id, chat_id, action) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).map(|a| a.to_lowercase()),
+    ) {
+        (Some(a), Some(c), Some(action)) => (a, c, action),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let existing = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+
+    match action.as_str() {
+        "on" => {
+            let weekday = match args.get(3) {
+                Some(day) => match digest::parse_weekday(day) {
+                    Some(d) => d,
+                    None => {
+                        bot.send_message(msg.chat.id, usage).await?;
+                        return Ok(());
+                    }
+                },
+                None => existing.as_ref().map_or(6, |c| c.digest_weekday),
+            };
+            let hour = match args.get(4).map(|h| h.parse::<i64>()) {
+                Some(Ok(h)) if (0..24).contains(&h) => h,
+                Some(_) => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+                None => existing.as_ref().map_or(19, |c| c.digest_hour),
+            };
+
+            ChatRepository::set_digest(&state.db_pool, account_id, chat_id, true, weekday, hour).await?;
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "✅ Weekly digest in chat {} every {} at {}:00",
+                    chat_id, digest::WEEKDAYS[weekday as usize], hour
+                ),
+            )
+            .await?;
+        }
+        "off" => {
+            let (weekday, hour) = existing.as_ref().map_or((6, 19), |c| (c.digest_weekday, c.digest_hour));
+            ChatRepository::set_digest(&state.db_pool, account_id, chat_id, false, weekday, hour).await?;
+            bot.send_message(msg.chat.id, format!("✅ Weekly digest disabled in chat {}", chat_id))
+                .await?;
+        }
+        "now" => {
+            let chat = match existing {
+                Some(c) => c,
+                None => {
+                    bot.send_message(msg.chat.id, "❌ Unknown chat, run /chats <id> refresh first")
+                        .await?;
+                    return Ok(());
+                }
+            };
+
+            let text = match digest::post_digest(&state, &chat).await {
+                Ok(true) => format!("✅ Digest posted in chat {}", chat_id),
+                Ok(false) => format!("ℹ️ Not enough activity in chat {} for a digest", chat_id),
+                Err(e) => format!("❌ Digest failed: {}", e),
+            };
+            bot.send_message(msg.chat.id, text).await?;
+        }
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Configure per-chat persona rotation
+/// Usage: /chat_rotation <account_id> <chat_id> <off|schedule|conversation> [tag]
+pub async fn handle_chat_rotation(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parsed = (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).and_then(|a| RotationMode::parse(a)),
+    );
+
+    let (account_id, chat_id, mode) = match parsed {
+        (Some(a), Some(c), Some(m)) => (a, c, m),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /chat_rotation <account_id> <chat_id> <off|schedule|conversation> [tag]",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    let tag = args.get(3).map(|t| t.to_lowercase());
+
+    ChatRepository::set_rotation(&state.db_pool, account_id, chat_id, mode.as_str(), tag.as_deref()).await?;
+
+    let pool = tag
+        .map(|t| format!("personas tagged '{}'", t))
+        .unwrap_or_else(|| "all personas".to_string());
+    let text = match mode {
+        RotationMode::Off => format!("✅ Persona rotation disabled in chat {}", chat_id),
+        RotationMode::Schedule => format!(
+            "🔄 Chat {} rotates among {} every {}h (weighted, never mid-conversation)",
+            chat_id, pool, state.config.persona_rotation_hours
+        ),
+        RotationMode::Conversation => format!(
+            "🔄 Chat {} picks a new persona from {} for each conversation (gap ≥ {} min)",
+            chat_id, pool, state.config.persona_sticky_minutes
+        ),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Show the chats where an account replied the most
+/// Usage: /top_chats <account_id>
+pub async fn handle_top_chats(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /top_chats <account_id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let stats = MessageRepository::top_reply_chats(&state.db_pool, account_id, 10).await?;
+
+    if stats.is_empty() {
+        bot.send_message(msg.chat.id, format!("📊 Account {} has not replied anywhere yet.", account_id))
+            .await?;
+        return Ok(());
+    }
+
+    let titles: HashMap<i64, String> = ChatRepository::list_for_account(&state.db_pool, account_id)
+        .await?
+        .into_iter()
+        .map(|c| (c.chat_id, c.title))
+        .collect();
+
+    let mut response = format!("📊 <b>Top chats of account {}</b>\n\n", account_id);
+
+    for (i, stat) in stats.iter().enumerate() {
+        let title = titles
+            .get(&stat.chat_id)
+            .filter(|t| !t.is_empty())
+            .map(|t| html_escape(t))
+            .unwrap_or_else(|| stat.chat_id.to_string());
+
+        response.push_str(&format!("{}. {} — {} replies\n", i + 1, title, stat.replies));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Retell new items of an RSS/Atom feed in a chat
+/// Usage: /subscribe <account_id> <chat_id> <feed_url>
+pub async fn handle_subscribe(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id, url) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+        args.get(2).filter(|u| u.starts_with("http://") || u.starts_with("https://")),
+    ) {
+        (Some(a), Some(c), Some(u)) => (a, c, u.clone()),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /subscribe <account_id> <chat_id> <feed_url>").await?;
+            return Ok(());
+        }
+    };
+
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, "❌ Account not found").await?;
+        return Ok(());
+    }
+
+    // Fetch once to check the feed; what is in it now counts as already seen
+    let fetched = match feeds::fetch(&url, None, None).await {
+        Ok(Some(fetched)) => fetched,
+        Ok(None) => {
+            bot.send_message(msg.chat.id, "❌ The server returned no feed").await?;
+            return Ok(());
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {:#}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let seen: Vec<String> = fetched.items.iter().map(|i| i.id.clone()).collect();
+    let title = if fetched.title.is_empty() { url.clone() } else { fetched.title.clone() };
+
+    let text = match FeedRepository::add(&state.db_pool, account_id, chat_id, &url, &title, &seen).await? {
+        Some(id) => format!(
+            "📰 Subscribed chat {} to <b>{}</b> (#{}, {} items now). New items are retold by the chat's persona, \
+            checked every {} min.",
+            chat_id,
+            html_escape(&title),
+            id,
+            seen.len(),
+            state.config.feed_poll_minutes
+        ),
+        None => format!("ℹ️ Chat {} is already subscribed to this feed", chat_id),
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Stop a feed subscription
+/// Usage: /unsubscribe <subscription_id>
+pub async fn handle_unsubscribe(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /unsubscribe <subscription_id> (see /subscriptions)").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if FeedRepository::remove(&state.db_pool, id).await? {
+        format!("🗑 Subscription #{} removed", id)
+    } else {
+        format!("❌ Subscription #{} not found", id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// List feed subscriptions, of all accounts or one
+/// Usage: /subscriptions [account_id]
+pub async fn handle_subscriptions(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let all = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(account_id) => FeedRepository::list_for_account(&state.db_pool, account_id).await?,
+        None => FeedRepository::list_all(&state.db_pool).await?,
+    };
+
+    if all.is_empty() {
+        bot.send_message(msg.chat.id, "📭 No feed subscriptions. Add one with /subscribe").await?;
+        return Ok(());
+    }
+
+    let mut response = "📰 <b>Feed subscriptions</b>\n".to_string();
+    for feed in &all {
+        let checked = feed
+            .last_checked_at
+            .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string());
+        response.push_str(&format!(
+            "\n#{} <b>{}</b>\n   account {} → <code>{}</code> | checked {}\n   {}\n",
+            feed.id,
+            html_escape(&feed.title),
+            feed.account_id,
+            feed.chat_id,
+            checked,
+            html_escape(&feed.url)
+        ));
+        if let Some(error) = &feed.last_error {
+            response.push_str(&format!("   ⚠️ {}\n", html_escape(error)));
+        }
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
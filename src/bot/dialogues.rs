@@ -1,5 +1,5 @@
 use crate::{
-    db::{AccountRepository, NewAccount},
+    db::{AccountRepository, NewAccount, PersonaRepository, WizardRepository},
     userbot,
     AppState,
 };
@@ -12,7 +12,11 @@ use rust_tdlib::{
     },
 };
 use std::sync::Arc;
-use teloxide::{dispatching::dialogue::InMemStorage, prelude::*};
+use teloxide::{
+    dispatching::dialogue::{InMemStorage, Storage},
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup},
+};
 use tokio::sync::Mutex;
 
 type TdClient = Client<TdJson>;
@@ -22,6 +26,7 @@ pub type AddAccountDialogue = Dialogue<AddAccountState, InMemStorage<AddAccountS
 
 #[derive(Clone)]
 pub enum AddAccountState {
+    Idle,
     ReceivePhone,
     ReceiveAuthCode {
         phone: String,
@@ -36,11 +41,266 @@ pub enum AddAccountState {
     ReceivePrompt {
         account_id: i64,
     },
+    EditPersonaName {
+        persona_id: i64,
+    },
+    EditPersonaPrompt {
+        persona_id: i64,
+    },
+    /// New prompt shown as a diff, waiting for Save/Discard (or a revised prompt)
+    ConfirmPersonaPrompt {
+        persona_id: i64,
+        prompt: String,
+    },
+    EditPersonaPostprocess {
+        persona_id: i64,
+    },
+    /// Commands planned from a natural-language request, waiting for Confirm/Cancel
+    ConfirmAdminCommands {
+        commands: Vec<String>,
+    },
 }
 
 impl Default for AddAccountState {
     fn default() -> Self {
-        Self::ReceivePhone
+        Self::Idle
+    }
+}
+
+impl AddAccountState {
+    /// Short identifier stored in the `wizard_sessions` table
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::ReceivePhone => "phone",
+            Self::ReceiveAuthCode { .. } => "auth_code",
+            Self::Receive2FA { .. } => "2fa",
+            Self::ReceivePrompt { .. } => "prompt",
+            Self::EditPersonaName { .. } => "persona_name",
+            Self::EditPersonaPrompt { .. } => "persona_prompt",
+            Self::ConfirmPersonaPrompt { .. } => "persona_confirm",
+            Self::EditPersonaPostprocess { .. } => "persona_postprocess",
+            Self::ConfirmAdminCommands { .. } => "admin_confirm",
+        }
+    }
+
+    /// How long the wizard may wait for the next message before expiring
+    pub fn ttl_secs(&self, timeout_secs: i64) -> i64 {
+        match self {
+            Self::Idle => 0,
+            // Login codes expire quickly on Telegram's side anyway
+            Self::ReceiveAuthCode { .. } | Self::Receive2FA { .. } => timeout_secs.min(5 * 60),
+            _ => timeout_secs,
+        }
+    }
+
+    /// Serializable part of the state (TDLib clients can't be persisted)
+    fn payload(&self) -> serde_json::Value {
+        match self {
+            Self::ReceiveAuthCode { phone, .. } | Self::Receive2FA { phone, .. } => {
+                serde_json::json!({ "phone": phone })
+            }
+            Self::ReceivePrompt { account_id } => serde_json::json!({ "account_id": account_id }),
+            Self::EditPersonaName { persona_id }
+            | Self::EditPersonaPrompt { persona_id }
+            | Self::EditPersonaPostprocess { persona_id } => {
+                serde_json::json!({ "persona_id": persona_id })
+            }
+            Self::ConfirmPersonaPrompt { persona_id, prompt } => {
+                serde_json::json!({ "persona_id": persona_id, "prompt": prompt })
+            }
+            Self::ConfirmAdminCommands { commands } => serde_json::json!({ "commands": commands }),
+            _ => serde_json::json!({}),
+        }
+    }
+}
+
+/// Human-readable wizard name for status output
+pub fn wizard_label(kind: &str) -> &'static str {
+    match kind {
+        "phone" => "Add account: waiting for phone number",
+        "auth_code" => "Add account: waiting for login code",
+        "2fa" => "Add account: waiting for 2FA password",
+        "prompt" => "Set prompt: waiting for new prompt",
+        "persona_name" => "Edit persona: waiting for new name",
+        "persona_prompt" => "Edit persona: waiting for new prompt",
+        "persona_confirm" => "Edit persona: waiting for Save or Discard",
+        "persona_postprocess" => "Edit persona: waiting for post-processing rules",
+        "admin_confirm" => "Natural request: waiting for Confirm or Cancel",
+        _ => "Unknown wizard",
+    }
+}
+
+/// Callback data of the cancel button under wizard prompts
+pub const CANCEL_CALLBACK: &str = "wiz_cancel";
+
+/// "❌ Отмена" button attached to every wizard prompt
+pub fn cancel_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback("❌ Отмена", CANCEL_CALLBACK)]])
+}
+
+/// Refuse to start a wizard while the same owner has one open in another chat,
+/// so answers meant for one can't land in the other. Returns true if refused.
+pub async fn refuse_if_busy(bot: &Bot, state: &AppState, chat_id: ChatId, user_id: Option<i64>) -> Result<bool> {
+    let user_id = match user_id {
+        Some(id) => id,
+        None => return Ok(false),
+    };
+    let session = match WizardRepository::active_elsewhere(&state.db_pool, user_id, chat_id.0).await? {
+        Some(session) => session,
+        None => return Ok(false),
+    };
+
+    bot.send_message(
+        chat_id,
+        format!(
+            "⚠️ You already have a wizard open in another chat ({}). Finish or cancel it there first, \
+            or wait {} min for it to expire.",
+            wizard_label(&session.kind),
+            (session.seconds_left() + 59) / 60
+        ),
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Move the dialogue to a new wizard step and persist it; `user_id` is who started it
+/// (`None` keeps the recorded one)
+pub async fn enter_wizard(
+    dialogue: &AddAccountDialogue,
+    state: &AppState,
+    user_id: Option<i64>,
+    next: AddAccountState,
+) -> Result<()> {
+    WizardRepository::save(
+        &state.db_pool,
+        dialogue.chat_id().0,
+        user_id,
+        next.kind(),
+        &next.payload(),
+        next.ttl_secs(state.config.wizard_timeout_minutes * 60),
+    )
+    .await?;
+
+    dialogue.update(next).await?;
+    Ok(())
+}
+
+/// Leave the wizard and drop its persisted record
+pub async fn exit_wizard(dialogue: &AddAccountDialogue, state: &AppState) -> Result<()> {
+    WizardRepository::delete(&state.db_pool, dialogue.chat_id().0).await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
+/// Restore persisted wizards after a restart.
+///
+/// Steps that only need plain data are put back into the dialogue storage;
+/// steps that depended on a live TDLib client are dropped with a notice.
+pub async fn restore_wizards(
+    bot: &Bot,
+    state: &AppState,
+    storage: Arc<InMemStorage<AddAccountState>>,
+) -> Result<()> {
+    for session in WizardRepository::list_all(&state.db_pool).await? {
+        let chat_id = ChatId(session.chat_id);
+
+        let restored = if session.seconds_left() <= 0 {
+            None
+        } else {
+            match session.kind.as_str() {
+                "phone" => Some(AddAccountState::ReceivePhone),
+                "prompt" => session.payload_json()["account_id"]
+                    .as_i64()
+                    .map(|account_id| AddAccountState::ReceivePrompt { account_id }),
+                "persona_name" => session.payload_json()["persona_id"]
+                    .as_i64()
+                    .map(|persona_id| AddAccountState::EditPersonaName { persona_id }),
+                "persona_prompt" => session.payload_json()["persona_id"]
+                    .as_i64()
+                    .map(|persona_id| AddAccountState::EditPersonaPrompt { persona_id }),
+                "persona_postprocess" => session.payload_json()["persona_id"]
+                    .as_i64()
+                    .map(|persona_id| AddAccountState::EditPersonaPostprocess { persona_id }),
+                "persona_confirm" => {
+                    let payload = session.payload_json();
+                    match (payload["persona_id"].as_i64(), payload["prompt"].as_str()) {
+                        (Some(persona_id), Some(prompt)) => Some(AddAccountState::ConfirmPersonaPrompt {
+                            persona_id,
+                            prompt: prompt.to_string(),
+                        }),
+                        _ => None,
+                    }
+                }
+                "admin_confirm" => session.payload_json()["commands"].as_array().map(|commands| {
+                    AddAccountState::ConfirmAdminCommands {
+                        commands: commands.iter().filter_map(|c| c.as_str().map(str::to_string)).collect(),
+                    }
+                }),
+                _ => None,
+            }
+        };
+
+        match restored {
+            Some(dialogue_state) => {
+                storage.clone().update_dialogue(chat_id, dialogue_state).await?;
+                tracing::info!("Restored '{}' wizard for chat {}", session.kind, session.chat_id);
+            }
+            None => {
+                WizardRepository::delete(&state.db_pool, session.chat_id).await?;
+                let _ = bot
+                    .send_message(
+                        chat_id,
+                        format!(
+                            "⚠️ Wizard interrupted by a restart ({}). Please start it again.",
+                            wizard_label(&session.kind)
+                        ),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Periodically expire wizards that have been waiting too long
+pub async fn wizard_expiry_worker(
+    bot: Bot,
+    state: AppState,
+    storage: Arc<InMemStorage<AddAccountState>>,
+) {
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+
+        let expired = match WizardRepository::list_expired(&state.db_pool).await {
+            Ok(sessions) => sessions,
+            Err(e) => {
+                tracing::error!("Failed to fetch expired wizards: {}", e);
+                continue;
+            }
+        };
+
+        for session in expired {
+            let chat_id = ChatId(session.chat_id);
+
+            // The dialogue may already be gone (e.g. after a restart)
+            let _ = storage.clone().remove_dialogue(chat_id).await;
+
+            if let Err(e) = WizardRepository::delete(&state.db_pool, session.chat_id).await {
+                tracing::error!("Failed to delete expired wizard: {}", e);
+                continue;
+            }
+
+            tracing::info!("Wizard '{}' expired in chat {}", session.kind, session.chat_id);
+
+            let _ = bot
+                .send_message(
+                    chat_id,
+                    format!("⌛ Wizard expired: {}. Start it again when ready.", wizard_label(&session.kind)),
+                )
+                .await;
+        }
     }
 }
 
@@ -60,7 +320,7 @@ pub async fn receive_phone(
     };
 
     if text == "/cancel" {
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
         return Ok(());
     }
@@ -82,7 +342,7 @@ pub async fn receive_phone(
             format!("❌ Account with phone {} already exists.", phone),
         )
         .await?;
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         return Ok(());
     }
 
@@ -97,7 +357,7 @@ pub async fn receive_phone(
                 format!("❌ Failed to connect to Telegram: {}", e),
             )
             .await?;
-            dialogue.exit().await?;
+            exit_wizard(&dialogue, &state).await?;
             return Ok(());
         }
     };
@@ -113,7 +373,7 @@ pub async fn receive_phone(
             format!("❌ Failed to request login code: {}", e),
         )
         .await?;
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         return Ok(());
     }
 
@@ -124,10 +384,10 @@ pub async fn receive_phone(
             phone
         ),
     )
+    .reply_markup(cancel_keyboard())
     .await?;
 
-    dialogue
-        .update(AddAccountState::ReceiveAuthCode { phone, client, worker })
+    enter_wizard(&dialogue, &state, None, AddAccountState::ReceiveAuthCode { phone, client, worker })
         .await?;
 
     Ok(())
@@ -150,7 +410,7 @@ pub async fn receive_auth_code(
     };
 
     if text == "/cancel" {
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
         return Ok(());
     }
@@ -164,7 +424,7 @@ pub async fn receive_auth_code(
         tracing::error!("Failed to check auth code: {}", e);
         bot.send_message(msg.chat.id, format!("❌ Invalid code: {}", e))
             .await?;
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         return Ok(());
     }
 
@@ -178,10 +438,10 @@ pub async fn receive_auth_code(
                 msg.chat.id,
                 "🔐 Two-factor authentication is enabled.\n\nPlease send your 2FA password.\nSend /cancel to abort.",
             )
+            .reply_markup(cancel_keyboard())
             .await?;
 
-            dialogue
-                .update(AddAccountState::Receive2FA { phone, client, worker })
+            enter_wizard(&dialogue, &state, None, AddAccountState::Receive2FA { phone, client, worker })
                 .await?;
         }
         AuthorizationState::Ready(_) => {
@@ -189,13 +449,13 @@ pub async fn receive_auth_code(
                 tracing::error!("Failed to finalize account: {}", e);
                 bot.send_message(msg.chat.id, format!("❌ Failed to save account: {}", e))
                     .await?;
-                dialogue.exit().await?;
+                exit_wizard(&dialogue, &state).await?;
             }
         }
         _ => {
             bot.send_message(msg.chat.id, "❌ Unexpected authentication state")
                 .await?;
-            dialogue.exit().await?;
+            exit_wizard(&dialogue, &state).await?;
         }
     }
 
@@ -219,7 +479,7 @@ pub async fn receive_2fa(
     };
 
     if text == "/cancel" {
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
         return Ok(());
     }
@@ -235,7 +495,7 @@ pub async fn receive_2fa(
         tracing::error!("2FA error: {}", e);
         bot.send_message(msg.chat.id, format!("❌ Invalid password: {}", e))
             .await?;
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         return Ok(());
     }
 
@@ -245,7 +505,7 @@ pub async fn receive_2fa(
         tracing::error!("Failed to finalize account: {}", e);
         bot.send_message(msg.chat.id, format!("❌ Failed to save account: {}", e))
             .await?;
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
     }
 
     Ok(())
@@ -268,12 +528,30 @@ pub async fn receive_prompt(
     };
 
     if text == "/cancel" {
-        dialogue.exit().await?;
+        exit_wizard(&dialogue, &state).await?;
         bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
         return Ok(());
     }
 
-    let new_prompt = text.to_string();
+    // Lint before saving; a leading "!" saves anyway
+    let (new_prompt, forced) = match text.strip_prefix(crate::ai::persona_lint::FORCE_PREFIX) {
+        Some(rest) => (rest.trim().to_string(), true),
+        None => (text.to_string(), false),
+    };
+
+    let warnings = crate::ai::lint_prompt(&new_prompt);
+    if !warnings.is_empty() && !forced {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "⚠️ The prompt was not saved:\n\n{}\n\nSend a fixed prompt, or the same one prefixed with \"!\" to save it anyway. /cancel to abort.",
+                crate::ai::persona_lint::format_warnings(&warnings)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
     AccountRepository::update_system_prompt(&state.db_pool, account_id, &new_prompt).await?;
 
     bot.send_message(
@@ -282,10 +560,221 @@ pub async fn receive_prompt(
     )
     .await?;
 
-    dialogue.exit().await?;
+    exit_wizard(&dialogue, &state).await?;
+    Ok(())
+}
+
+pub async fn receive_persona_name(
+    bot: Bot,
+    msg: Message,
+    dialogue: AddAccountDialogue,
+    state: AppState,
+    persona_id: i64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = match msg.text().map(str::trim) {
+        Some("/cancel") => {
+            exit_wizard(&dialogue, &state).await?;
+            bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
+            return Ok(());
+        }
+        Some(name) if !name.is_empty() && !name.contains('|') => name.to_string(),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Please send the new name as text (without \"|\"). /cancel to abort.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    match PersonaRepository::rename(&state.db_pool, persona_id, &name).await {
+        Ok(()) => {
+            exit_wizard(&dialogue, &state).await?;
+            bot.send_message(msg.chat.id, format!("✅ Persona {} renamed to {}", persona_id, name))
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {:#}\n\nSend another name or /cancel.", e))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Replies of the persona used to preview post-processing rules
+const POSTPROCESS_PREVIEW_REPLIES: i64 = 3;
+
+pub async fn receive_persona_postprocess(
+    bot: Bot,
+    msg: Message,
+    dialogue: AddAccountDialogue,
+    state: AppState,
+    persona_id: i64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::bot::handlers::html_escape;
+
+    let text = match msg.text().map(str::trim) {
+        Some("/cancel") => {
+            exit_wizard(&dialogue, &state).await?;
+            bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
+            return Ok(());
+        }
+        Some(text) if !text.is_empty() => text,
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Please send the rules as JSON text, \"off\" or /cancel.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if text.eq_ignore_ascii_case("off") {
+        PersonaRepository::update_postprocess(&state.db_pool, persona_id, None).await?;
+        exit_wizard(&dialogue, &state).await?;
+        bot.send_message(msg.chat.id, format!("✅ Replies of persona {} are sent as generated", persona_id))
+            .await?;
+        return Ok(());
+    }
+
+    let processor = match crate::ai::postprocess::PostProcessor::parse(text) {
+        Ok(processor) => processor,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {}\n\nSend fixed rules or /cancel.", e)).await?;
+            return Ok(());
+        }
+    };
+
+    // Store normalized JSON so the next edit starts from a tidy copy
+    let json = serde_json::to_string_pretty(processor.rules())?;
+    PersonaRepository::update_postprocess(&state.db_pool, persona_id, Some(&json)).await?;
+    exit_wizard(&dialogue, &state).await?;
+
+    let mut response = format!("✅ Post-processing rules of persona {} saved.", persona_id);
+    let recent = crate::db::MessageRepository::recent_persona_replies(&state.db_pool, persona_id, POSTPROCESS_PREVIEW_REPLIES)
+        .await?;
+    if !recent.is_empty() {
+        response.push_str("\n\n<b>Recent replies with these rules:</b>");
+        for reply in recent {
+            response.push_str(&format!(
+                "\n\n<i>{}</i>\n→ {}",
+                html_escape(&reply),
+                html_escape(&processor.apply(&reply))
+            ));
+        }
+    }
+    response.push_str(&format!("\n\nTry any text with /preview_postprocess {} &lt;text&gt;", persona_id));
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn receive_persona_prompt(
+    bot: Bot,
+    msg: Message,
+    dialogue: AddAccountDialogue,
+    state: AppState,
+    persona_id: i64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = match msg.text() {
+        Some(t) => t.trim(),
+        None => {
+            bot.send_message(msg.chat.id, "❌ Please send the prompt as text.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if text == "/cancel" {
+        exit_wizard(&dialogue, &state).await?;
+        bot.send_message(msg.chat.id, "❌ Operation cancelled.").await?;
+        return Ok(());
+    }
+
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            exit_wizard(&dialogue, &state).await?;
+            bot.send_message(msg.chat.id, format!("❌ Persona {} no longer exists", persona_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Lint before previewing; a leading "!" skips the warnings
+    let (new_prompt, forced) = match text.strip_prefix(crate::ai::persona_lint::FORCE_PREFIX) {
+        Some(rest) => (rest.trim().to_string(), true),
+        None => (text.to_string(), false),
+    };
+
+    let warnings = crate::ai::lint_prompt(&new_prompt);
+    if !warnings.is_empty() && !forced {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "⚠️ Prompt issues:\n\n{}\n\nSend a fixed prompt, or the same one prefixed with \"!\" to continue anyway. /cancel to abort.",
+                crate::ai::persona_lint::format_warnings(&warnings)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let diff = crate::ai::prompt_diff::render_diff(&persona.prompt, &new_prompt);
+    if diff.is_empty() {
+        bot.send_message(msg.chat.id, "ℹ️ The prompt is unchanged. Send an edited prompt or /cancel.")
+            .await?;
+        return Ok(());
+    }
+
+    enter_wizard(
+        &dialogue,
+        &state,
+        None,
+        AddAccountState::ConfirmPersonaPrompt { persona_id, prompt: new_prompt },
+    )
+    .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "📝 <b>Changes to {}</b>\n\n<pre>{}</pre>\n\nSave them? You can also send a revised prompt.",
+            crate::bot::handlers::html_escape(&persona.name),
+            crate::bot::handlers::html_escape(&truncate_preview(&diff))
+        ),
+    )
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(teloxide::types::InlineKeyboardMarkup::new(vec![vec![
+        teloxide::types::InlineKeyboardButton::callback("✅ Save", format!("p_save:{}", persona_id)),
+        teloxide::types::InlineKeyboardButton::callback("❌ Discard", format!("p_discard:{}", persona_id)),
+    ]]))
+    .await?;
+
     Ok(())
 }
 
+/// A revised prompt sent while the previous one awaits confirmation replaces it
+pub async fn receive_revised_persona_prompt(
+    bot: Bot,
+    msg: Message,
+    dialogue: AddAccountDialogue,
+    state: AppState,
+    (persona_id, _pending): (i64, String),
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    receive_persona_prompt(bot, msg, dialogue, state, persona_id).await
+}
+
+/// Keep a diff preview within Telegram's message limit
+fn truncate_preview(diff: &str) -> String {
+    const MAX_PREVIEW_CHARS: usize = 3500;
+    if diff.chars().count() <= MAX_PREVIEW_CHARS {
+        return diff.to_string();
+    }
+    let mut preview: String = diff.chars().take(MAX_PREVIEW_CHARS).collect();
+    preview.push_str("\n… (diff truncated)");
+    preview
+}
+
 async fn create_tdlib_client(
     state: &AppState,
     phone: &str,
@@ -357,7 +846,7 @@ async fn finalize_account(
         .await?;
     }
 
-    dialogue.exit().await?;
+    exit_wizard(dialogue, state).await?;
     Ok(())
 }
 
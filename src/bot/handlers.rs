@@ -1,65 +1,416 @@
 use crate::{
-    bot::{AddAccountDialogue, AddAccountState},
-    db::{AccountRepository, MessageRepository},
+    bot::{dialogues, AddAccountDialogue, AddAccountState},
+    db::{
+        AccountRepository, MessageRepository, NewPersona, PersonaRepository, SecurityRepository,
+        RelationshipRepository, WebhookRepository, WizardRepository,
+    },
+    security::{self, SecurityPolicy},
     AppState,
 };
 use anyhow::Result;
-use teloxide::{prelude::*, utils::command::BotCommands};
+use teloxide::{
+    prelude::*,
+    types::{BotCommand, BotCommandScope, Recipient},
+    utils::command::BotCommands,
+};
 
 #[derive(BotCommands, Clone)]
-#[command(rename_rule = "lowercase", description = "Admin commands:")]
+#[command(rename_rule = "snake_case", description = "Admin commands:")]
 pub enum Command {
     #[command(description = "Show bot status and statistics")]
     Start,
-    #[command(description = "Add a new MTProto userbot account")]
+    #[command(description = "Add a new MTProto userbot account", aliases = ["addaccount"], hide_aliases)]
     AddAccount,
     #[command(description = "List all connected accounts")]
     List,
-    #[command(description = "Update system prompt for an account (usage: /set_prompt <id>)")]
+    #[command(description = "Update system prompt for an account (usage: /set_prompt <id>)", aliases = ["setprompt"], hide_aliases)]
     SetPrompt,
-    #[command(description = "Set reply probability 0-100 (usage: /set_prob <id> <0-100>)")]
+    #[command(description = "Set reply probability 0-100 (usage: /set_prob <id> <0-100>)", aliases = ["setprob"], hide_aliases)]
     SetProb,
-    #[command(description = "Add chat to whitelist (usage: /allow_chat <id> <chat_id>)")]
+    #[command(description = "Set reply probability for channel posts in discussion groups (usage: /set_post_prob <id> <0-100>)")]
+    SetPostProb,
+    #[command(description = "Add chat to whitelist (usage: /allow_chat <id> <chat_id>)", aliases = ["allowchat"], hide_aliases)]
     AllowChat,
-    #[command(description = "Remove chat from whitelist (usage: /remove_chat <id> <chat_id>)")]
+    #[command(description = "Remove chat from whitelist (usage: /remove_chat <id> <chat_id>)", aliases = ["removechat"], hide_aliases)]
     RemoveChat,
+    #[command(description = "List userbot dialogs (usage: /chats <id> [refresh])")]
+    Chats,
+    #[command(description = "Toggle deny for a chat (usage: /deny_chat <id> <chat_id>)")]
+    DenyChat,
+    #[command(description = "Per-chat reply probability (usage: /chat_prob <id> <chat_id> <0-100|default>)")]
+    ChatProb,
+    #[command(description = "Words that always get a reply in a chat (usage: /chat_triggers <id> <chat_id> [word, word|off])")]
+    ChatTriggers,
+    #[command(description = "Delete replies in a chat after N minutes (usage: /chat_ephemeral <id> <chat_id> [minutes|off])")]
+    ChatEphemeral,
+    #[command(description = "How messages written in quick succession are batched (usage: /chat_debounce <id> <chat_id> [<ms>|off|default] [max=<n>])")]
+    ChatDebounce,
+    #[command(description = "What happens to messages missed while offline (usage: /chat_catchup <id> <chat_id> [off|summary|post])")]
+    ChatCatchup,
+    #[command(description = "How replies write numbers, dates, money and units (usage: /chat_locale <id> <chat_id> [ru|en-us|en-gb|de|fr|es|off] [metric|imperial] [latin|keep])")]
+    ChatLocale,
+    #[command(description = "Local hours without auto-replies (usage: /chat_quiet <id> <chat_id> [23:00-08:00|on|off] [mentions|strict])")]
+    ChatQuiet,
+    #[command(description = "Let the account answer \"make a poll\" requests with a native poll (usage: /chat_polls <id> <chat_id> [on|off])")]
+    ChatPolls,
+    #[command(description = "Let the persona pin announcements in a chat (usage: /chat_pins <id> <chat_id> [on|off])")]
+    ChatPins,
+    #[command(description = "Keep a chat's media from vision, voice transcription or web search (usage: /chat_media <id> <chat_id> [vision=on|off] [voice=on|off] [search=on|off])")]
+    ChatMedia,
+    #[command(description = "Have a new persona mention taking over a chat (usage: /chat_switch_notice <id> <chat_id> [on|off])")]
+    ChatSwitchNotice,
+    #[command(description = "Auto-tune a chat's reply probability within bounds (usage: /chat_tuning <id> <chat_id> <min> <max>|off)")]
+    ChatTuning,
+    #[command(description = "Why a chat's reply probability was tuned the way it was (usage: /tuning_report <id> <chat_id>)")]
+    TuningReport,
+    #[command(description = "Revert the last settings change of a chat (usage: /undo_last_setting <id> <chat_id>)")]
+    UndoLastSetting,
+    #[command(description = "Settings changes of a chat with who made them (usage: /settings_history <id> <chat_id> [limit])")]
+    SettingsHistory,
+    #[command(description = "Restore a chat's settings to before a change (usage: /settings_rollback <id> <chat_id> <change_id> [confirm])")]
+    SettingsRollback,
+    #[command(description = "Daily media processing limits of a chat (usage: /chat_quota <id> <chat_id> [vision|voice <limit|default>])")]
+    ChatQuota,
+    #[command(description = "Reuse answers to repeated questions in a chat (usage: /chat_cache <id> <chat_id> [on|off|clear])")]
+    ChatCache,
+    #[command(description = "Reply formatting for a chat (usage: /chat_format <id> <chat_id> <markdown|html|plain>)")]
+    ChatFormat,
+    #[command(description = "Conversation starters in a chat (usage: /initiative <id> <chat_id> <on|off>)")]
+    Initiative,
+    #[command(description = "Timezone of a chat (usage: /chat_timezone <id> <chat_id> [Area/City|auto|reset])")]
+    ChatTimezone,
+    #[command(description = "Karma in a chat: leaderboard or settings (usage: /karma_chat <id> <chat_id> [on|off|monthly|reset])")]
+    KarmaChat,
+    #[command(description = "Games in a chat: leaderboard or settings (usage: /chat_games <id> <chat_id> [on|off|reset])")]
+    ChatGames,
+    #[command(description = "Forward voice/video notes mentioning keywords to the owner (usage: /chat_escalation <id> <chat_id> [word, word ...|off])")]
+    ChatEscalation,
+    #[command(description = "Retention limits of a chat (usage: /chat_retention <id> <chat_id> [days|off] [max_messages])")]
+    ChatRetention,
+    #[command(description = "Pause replies in a chat (usage: /pause_chat <id> <chat_id> <minutes>)")]
+    PauseChat,
+    #[command(description = "Resume replies in a paused chat (usage: /resume_chat <id> <chat_id>)")]
+    ResumeChat,
+    #[command(description = "Hand a conversation to a human: no auto-replies to the user (or chat) for a while (usage: /handoff <id> <chat_id> [user_id|all] [minutes])")]
+    Handoff,
+    #[command(description = "Hand a conversation back to the bot after /handoff (usage: /resume <id> <chat_id> [user_id])")]
+    Resume,
+    #[command(description = "Create or replace a chat profile (usage: /save_profile <name> [key=value ...])")]
+    SaveProfile,
+    #[command(description = "List chat profiles")]
+    Profiles,
+    #[command(description = "Delete a chat profile (usage: /delete_profile <name>)")]
+    DeleteProfile,
+    #[command(description = "Apply a profile to a chat (usage: /apply_profile <id> <chat_id> <name>)")]
+    ApplyProfile,
+    #[command(description = "Change settings of many chats at once (usage: /set_all [all|groups|private|ids=<id,...>] key=value...)")]
+    SetAll,
+    #[command(description = "What a group is discussing now, and before (usage: /topic <id> <chat_id>)")]
+    Topic,
+    #[command(description = "Memory retrieval filters of a chat (usage: /chat_rag <id> <chat_id> [sender=on|off] [bots=on|off] [days=<n>|off] [private=on|off])")]
+    ChatRag,
+    #[command(description = "Embed bot-authored messages into a chat's memory (usage: /chat_bot_memory <id> <chat_id> [on|off|default])")]
+    ChatBotMemory,
+    #[command(description = "Weekly digest in a chat (usage: /digest <id> <chat_id> <on [day] [hour]|off|now>)")]
+    Digest,
+    #[command(description = "Retell new items of an RSS/Atom feed in a chat (usage: /subscribe <id> <chat_id> <feed_url>)")]
+    Subscribe,
+    #[command(description = "Stop a feed subscription (usage: /unsubscribe <subscription_id>)")]
+    Unsubscribe,
+    #[command(description = "List feed subscriptions (usage: /subscriptions [id])")]
+    Subscriptions,
+    #[command(description = "Per-chat persona rotation (usage: /chat_rotation <id> <chat_id> <off|schedule|conversation> [tag])")]
+    ChatRotation,
+    #[command(description = "Chats with the most replies (usage: /top_chats <id>)")]
+    TopChats,
+    #[command(description = "Show or set how an account knows a user (usage: /relationship <id> <user_id> [description])")]
+    Relationship,
+    #[command(description = "Answer a question from a chat's memory, with sources (usage: /ask <id> <chat_id> <question>)")]
+    Ask,
+    #[command(description = "Show which memories a message would retrieve and why (usage: /why <id> <chat_id> [from=<user_id>] <text>)")]
+    Why,
+    #[command(description = "Stage timings of a reply (usage: /trace <message_link>|<chat_id> <message_id>)")]
+    Trace,
+    #[command(description = "Delete messages and memories older than N days in all chats (usage: /purge_history <days>)")]
+    PurgeHistory,
+    #[command(description = "Memory tiers and consolidation status (usage: /memory_stats <id> [chat_id])")]
+    MemoryStats,
+    #[command(description = "Browse people, places and projects known in a chat (usage: /entities <id> <chat_id> [name])")]
+    Entities,
+    #[command(description = "Translate the message you reply to, detecting its language (usage: /translate [language])")]
+    Translate,
+    #[command(description = "Chats mirrored into each other with translation (usage: /bridges <id>)")]
+    Bridges,
+    #[command(description = "Mirror two chats into each other, translated (usage: /bridge_add <id> <chat_a> <chat_b> <lang_a> <lang_b>)")]
+    BridgeAdd,
+    #[command(description = "Stop mirroring two chats (usage: /bridge_remove <bridge_id>)")]
+    BridgeRemove,
+    #[command(description = "Pin a message as an account (usage: /pin <id> <message_link>|<chat_id> <message_id>)")]
+    Pin,
+    #[command(description = "Unpin a message as an account (usage: /unpin <id> <message_link>|<chat_id> <message_id>)")]
+    Unpin,
+    #[command(description = "Pins and unpins an account made (usage: /audit <id> [chat_id])")]
+    Audit,
+    #[command(description = "Browse a chat's memories page by page or by similarity (usage: /memories <id> <chat_id> [page|search <text>])")]
+    Memories,
+    #[command(description = "Rewrite a memory or set its importance (usage: /memory_edit <memory_id> <text>|importance=<0-2>)")]
+    MemoryEdit,
+    #[command(description = "Delete one memory (usage: /memory_delete <memory_id>)")]
+    MemoryDelete,
+    #[command(description = "Delete a chat's memories matching a filter (usage: /memory_delete_where <id> <chat_id> [user=] [match=] [older=<days>] [bots] [confirm])")]
+    MemoryDeleteWhere,
+    #[command(description = "When a chat's memories were made and how often replies use them (usage: /memory_heatmap <id> <chat_id> [week|month])")]
+    MemoryHeatmap,
+    #[command(description = "Recent stored messages of a chat with their authors (usage: /messages <id> <chat_id> [limit] [before=<message_id>])")]
+    Messages,
+    #[command(description = "Latest conversation summaries of a chat with their message ranges (usage: /summaries <id> <chat_id> [before=<summary_id>])")]
+    Summaries,
+    #[command(description = "Summarize a summary's messages again (usage: /summary_regenerate <summary_id>)")]
+    SummaryRegenerate,
+    #[command(description = "Delete a conversation summary (usage: /summary_delete <summary_id>)")]
+    SummaryDelete,
+    #[command(description = "Download a chat's memory as compressed JSONL (usage: /export_memory <id> <chat_id> [embeddings])")]
+    ExportMemory,
+    #[command(description = "Import a memory export into a chat, in reply to the file (usage: /import_memory <id> <chat_id>)")]
+    ImportMemory,
+    #[command(description = "Embed old messages that have no memory yet, in the background (usage: /embed_backlog <chat_id|all> or /embed_backlog stop <job_id>)")]
+    EmbedBacklog,
+    #[command(description = "Replies you corrected with !edit / !del from the account (usage: /corrections <id>)")]
+    Corrections,
+    #[command(description = "Models in use, their history and the transcription queue")]
+    Models,
+    #[command(description = "Models installed in Ollama and their disk usage, or details of one (usage: /ollama_models [name])")]
+    OllamaModels,
+    #[command(description = "Download a model into Ollama with live progress (usage: /pull_model <name>)")]
+    PullModel,
+    #[command(description = "Delete a model from Ollama (usage: /delete_model <name> [confirm])")]
+    DeleteModel,
+    #[command(description = "List routing rules in evaluation order")]
+    Rules,
+    #[command(description = "Add a routing rule (usage: /rule_add [account=<id>] [chat=<id>] [user=<id>] [match=<regex>] [persona=<name|id>] [temperature=<t>] [reply=always|never] [priority=<n>])")]
+    RuleAdd,
+    #[command(description = "Remove a routing rule (usage: /rule_remove <rule_id>)")]
+    RuleRemove,
+    #[command(description = "Set prompt-injection policy for a chat (usage: /security_policy <account_id> <chat_id> <off|log|strike|block> [threshold])")]
+    SecurityPolicy,
+    #[command(description = "Show recent prompt-injection violations")]
+    Violations,
+    #[command(description = "List outbound webhooks")]
+    Webhooks,
+    #[command(description = "POST events to a URL (usage: /webhook_add <url> <event,...|*> [keywords=a,b] [secret=<s>])")]
+    WebhookAdd,
+    #[command(description = "Remove an outbound webhook (usage: /webhook_remove <webhook_id>)")]
+    WebhookRemove,
+    #[command(description = "Send a test event to a webhook (usage: /webhook_test <webhook_id>)")]
+    WebhookTest,
+    #[command(description = "Show or change the log filter (usage: /loglevel [debug|info,puppeteer=trace|reset])", aliases = ["log_level"], hide_aliases)]
+    Loglevel,
     #[command(description = "Stop a running userbot (usage: /stop <id>)")]
     Stop,
     #[command(description = "Delete an account from database (usage: /delete <id>)")]
     Delete,
     
     // Persona commands
-    #[command(description = "List all available personality archetypes")]
+    #[command(description = "List all available personality archetypes", aliases = ["listpersonas"], hide_aliases)]
     ListPersonas,
-    #[command(description = "Set random persona for account (usage: /random_persona <id>)")]
+    #[command(description = "Set random persona for account (usage: /random_persona <id>)", aliases = ["randompersona"], hide_aliases)]
     RandomPersona,
-    #[command(description = "Set specific persona (usage: /set_persona <id> <persona_name>)")]
+    #[command(description = "Set specific persona (usage: /set_persona <id> <persona_name>)", aliases = ["setpersona"], hide_aliases)]
     SetPersona,
+    #[command(description = "Create or update a stored persona (usage: /create_persona <name>|<prompt>)")]
+    CreatePersona,
+    #[command(description = "Check a stored persona's prompt for problems (usage: /lint_persona <persona_id>)")]
+    LintPersona,
+    #[command(description = "List stored personas")]
+    Personas,
+    #[command(description = "Bind account to a stored persona (usage: /bind_persona <account_id> <persona_id>)")]
+    BindPersona,
+    #[command(description = "Unbind account from its persona (usage: /unbind_persona <account_id>)")]
+    UnbindPersona,
+    #[command(description = "Delete a stored persona (usage: /delete_persona <persona_id>)")]
+    DeletePersona,
+    #[command(description = "Tag a persona (usage: /tag_persona <persona_id> <tag1,tag2>)")]
+    TagPersona,
+    #[command(description = "Remove a persona tag (usage: /untag_persona <persona_id> <tag>)")]
+    UntagPersona,
+    #[command(description = "List tags, or personas with a tag (usage: /personas_by_tag [tag])")]
+    PersonasByTag,
+    #[command(description = "Export personas with a tag as JSON (usage: /export_tag <tag>)")]
+    ExportTag,
+    #[command(description = "Export personas changed since the last run, into PERSONAS_DIR or as files")]
+    ExportChangedPersonas,
+    #[command(description = "Import persona files, keeping newer stored versions (usage: reply /import_personas to a JSON file, or alone to re-read PERSONAS_DIR)")]
+    ImportPersonas,
+    #[command(description = "Delete all personas with a tag (usage: /delete_tag <tag> confirm)")]
+    DeleteTag,
+    #[command(description = "Nightly persona rotation within a tag (usage: /rotate_tag <tag> <on|off|now>)")]
+    RotateTag,
+    #[command(description = "Persona weight in per-chat rotation (usage: /persona_weight <persona_id> <weight>)")]
+    PersonaWeight,
+    #[command(description = "Replies per persona (usage: /persona_stats <id>)")]
+    PersonaStats,
+    #[command(description = "Reply style of a persona over time and drift from its baseline (usage: /persona_style <persona_id> [days])")]
+    PersonaStyle,
+    #[command(description = "Vision prompt per media type, optionally per persona (usage: /vision_prompt [photo|gif|video_note] [persona_id|default] [template|reset])")]
+    VisionPrompt,
+    #[command(description = "Edit a single persona field (usage: /edit_persona <persona_id>)")]
+    EditPersona,
+    #[command(description = "Time-of-day variants of a persona (usage: /persona_time <persona_id> [<from>-<to> <instructions>|remove <n>|clear])")]
+    PersonaTime,
+    #[command(description = "What a persona's conversations leave in long-term memory (usage: /persona_memory <persona_id> [all|user_only|none])")]
+    PersonaMemory,
+    #[command(description = "What a persona may do besides writing text (usage: /persona_caps <persona_id> [search=on|off] [images=on|off] [tools=on|off] [voice=on|off])")]
+    PersonaCaps,
+    #[command(description = "Sticker pack of a persona's catchphrases (usage: /sticker_pack <persona_id> [create <account_id>|refresh|off])")]
+    StickerPack,
+    #[command(description = "Let a persona extend a base persona's prompt and settings (usage: /persona_base <persona_id> [base_id|off])")]
+    PersonaBase,
+    #[command(description = "Dry-run a persona's post-processing rules (usage: /preview_postprocess <persona_id> <text>)")]
+    PreviewPostprocess,
+    #[command(description = "Add a few-shot example to a persona (usage: /add_example <persona_id> <message> || <reply>)")]
+    AddExample,
+    #[command(description = "List a persona's few-shot examples (usage: /examples <persona_id>)")]
+    Examples,
+    #[command(description = "Delete a few-shot example (usage: /delete_example <example_id>)")]
+    DeleteExample,
+    #[command(description = "Export examples and well-received replies for fine-tuning (usage: /export_dataset <openai|sharegpt> [persona=<id>] [from=YYYY-MM-DD] [to=YYYY-MM-DD] [min_responses=<n>])")]
+    ExportDataset,
     
     // Bot group commands
-    #[command(description = "Create bot group (usage: /create_group <name> [desc])")]
+    #[command(description = "Create bot group (usage: /create_group <name> [desc])", aliases = ["creategroup"], hide_aliases)]
     CreateGroup,
-    #[command(description = "List all bot groups")]
+    #[command(description = "List all bot groups", aliases = ["listgroups"], hide_aliases)]
     ListGroups,
-    #[command(description = "Add account to group (usage: /add_to_group <group_id> <account_id>)")]
+    #[command(description = "Add account to group (usage: /add_to_group <group_id> <account_id>)", aliases = ["addtogroup"], hide_aliases)]
     AddToGroup,
     
     // Spam campaign commands
     #[command(description = "Create spam campaign (usage: /spam <group_id|all> <type> <target_id> <repeat> <delay_ms> <text>)")]
     Spam,
-    #[command(description = "List spam campaigns")]
+    #[command(description = "List spam campaigns", aliases = ["listcampaigns"], hide_aliases)]
     ListCampaigns,
-    #[command(description = "Stop campaign (usage: /stop_campaign <id>)")]
+    #[command(description = "Stop campaign (usage: /stop_campaign <id>)", aliases = ["stopcampaign"], hide_aliases)]
     StopCampaign,
     
+    // Payments
+    #[command(description = "Create a Telegram Stars invoice link (usage: /invoice_link <feature> <stars> [days])")]
+    InvoiceLink,
+    #[command(description = "Grant a premium feature (usage: /grant <user_id> <feature> [days])")]
+    Grant,
+    #[command(description = "Revoke a premium feature (usage: /revoke <user_id> <feature>)")]
+    Revoke,
+    #[command(description = "Payment totals or a user's entitlements (usage: /entitlements [user_id])")]
+    Entitlements,
+
     // Direct messaging
     #[command(description = "Send DM from bot (usage: /dm <account_id> <user_id> <text>)")]
     Dm,
     
+    #[command(description = "Connected Telegram Business accounts and their auto-reply settings")]
+    Business,
+    #[command(description = "Business auto-reply settings (usage: /business_set <user_id> key=value ...)")]
+    BusinessSet,
+
     #[command(description = "Show help message")]
     Help,
 }
 
+/// Telegram shows at most this many commands in a bot's menu
+const MENU_LIMIT: usize = 100;
+
+/// Advanced and rarely used commands left out of the menu to stay under MENU_LIMIT.
+/// They still work when typed and /help lists them.
+const MENU_HIDDEN: &[&str] = &[
+    "set_post_prob",
+    "chat_ephemeral",
+    "chat_debounce",
+    "chat_catchup",
+    "chat_locale",
+    "chat_polls",
+    "chat_pins",
+    "chat_switch_notice",
+    "chat_tuning",
+    "tuning_report",
+    "settings_rollback",
+    "chat_quota",
+    "chat_cache",
+    "chat_escalation",
+    "chat_retention",
+    "delete_profile",
+    "chat_rag",
+    "chat_bot_memory",
+    "why",
+    "trace",
+    "memory_delete_where",
+    "memory_heatmap",
+    "summary_regenerate",
+    "summary_delete",
+    "export_memory",
+    "import_memory",
+    "embed_backlog",
+    "pull_model",
+    "delete_model",
+    "rule_remove",
+    "webhook_remove",
+    "webhook_test",
+    "bridge_remove",
+    "loglevel",
+    "lint_persona",
+    "untag_persona",
+    "personas_by_tag",
+    "export_tag",
+    "export_changed_personas",
+    "import_personas",
+    "delete_tag",
+    "rotate_tag",
+    "persona_weight",
+    "persona_time",
+    "persona_base",
+    "preview_postprocess",
+    "delete_example",
+    "export_dataset",
+    "invoice_link",
+    "grant",
+    "revoke",
+    "entitlements",
+];
+
+/// Commands published in the owners' menu
+fn menu_commands() -> Vec<BotCommand> {
+    Command::bot_commands()
+        .into_iter()
+        .filter(|c| !MENU_HIDDEN.contains(&c.command.trim_start_matches('/')))
+        .collect()
+}
+
+/// Register the command menu with Telegram.
+///
+/// Owners get the menu commands in their private chats, minus `MENU_HIDDEN`
+/// and capped at `MENU_LIMIT`; the rest still work when typed and are listed
+/// by /help. Everyone else gets an empty menu since the admin bot ignores
+/// non-owners anyway.
+pub async fn register_commands(bot: &Bot, owner_ids: &[i64]) -> Result<()> {
+    bot.delete_my_commands().await?;
+
+    let mut commands = menu_commands();
+    if commands.len() > MENU_LIMIT {
+        tracing::warn!("{} commands don't fit the menu, publishing the first {}", commands.len(), MENU_LIMIT);
+        commands.truncate(MENU_LIMIT);
+    }
+    for owner_id in owner_ids {
+        let scope = BotCommandScope::Chat {
+            chat_id: Recipient::Id(ChatId(*owner_id)),
+        };
+
+        if let Err(e) = bot.set_my_commands(commands.clone()).scope(scope).await {
+            tracing::warn!("Failed to register commands for owner {}: {}", owner_id, e);
+        }
+    }
+
+    tracing::info!("Registered {} of {} bot commands in the menu", commands.len(), Command::bot_commands().len());
+    Ok(())
+}
+
 pub async fn handle_command(
     bot: Bot,
     msg: Message,
@@ -70,41 +421,231 @@ pub async fn handle_command(
     // Parse command arguments
     let text = msg.text().unwrap_or("");
     let args: Vec<String> = text.split_whitespace().skip(1).map(|s| s.to_string()).collect();
-    
-    match cmd {
-        Command::Start => handle_start(bot, msg, state).await?,
-        Command::AddAccount => handle_add_account(bot, msg, dialogue).await?,
-        Command::List => handle_list(bot, msg, state).await?,
-        Command::SetPrompt => handle_set_prompt(bot, msg, state, dialogue).await?,
-        Command::SetProb => handle_set_prob(bot, msg, state).await?,
-        Command::AllowChat => handle_allow_chat(bot, msg, state).await?,
-        Command::RemoveChat => handle_remove_chat(bot, msg, state).await?,
-        Command::Stop => handle_stop(bot, msg, state).await?,
-        Command::Delete => handle_delete(bot, msg, state).await?,
-        
-        // Persona commands
-        Command::ListPersonas => handle_list_personas(bot, msg).await?,
-        Command::RandomPersona => handle_random_persona(bot, msg, state, args).await?,
-        Command::SetPersona => handle_set_persona(bot, msg, state, args).await?,
-        
-        // Bot group commands
-        Command::CreateGroup => crate::bot::group_commands::handle_create_group(bot, msg, state, args).await?,
-        Command::ListGroups => crate::bot::group_commands::handle_list_groups(bot, msg, state).await?,
-        Command::AddToGroup => crate::bot::group_commands::handle_add_to_group(bot, msg, state, args).await?,
-        
-        // Spam campaign commands
-        Command::Spam => crate::bot::group_commands::handle_create_spam(bot, msg, state, args).await?,
-        Command::ListCampaigns => crate::bot::group_commands::handle_list_campaigns(bot, msg, state).await?,
-        Command::StopCampaign => crate::bot::group_commands::handle_stop_campaign(bot, msg, state, args).await?,
-        
-        // Direct messaging
-        Command::Dm => crate::bot::group_commands::handle_dm(bot, msg, state, args).await?,
-        
-        Command::Help => handle_help(bot, msg).await?,
+
+    if state.config.safe_mode && matches!(cmd, Command::Spam) {
+        bot.send_message(msg.chat.id, "🛡 Spam campaigns are disabled in safe mode.").await?;
+        return Ok(());
+    }
+
+    // Per-chat settings commands are cooled down and recorded for /undo_last_setting
+    let settings_scope = match changes_chat_settings(&cmd, args.len()) {
+        true => crate::settings_history::chat_scope(&args),
+        false => None,
+    };
+    let actor_id = msg.from.as_ref().map(|u| u.id.0 as i64).unwrap_or(0);
+    let command_name = text.split_whitespace().next().unwrap_or("").to_string();
+    let reply_bot = bot.clone();
+    let reply_chat = msg.chat.id;
+    let history_state = state.clone();
+
+    let run = async move {
+        match cmd {
+            Command::Start => handle_start(bot, msg, state).await?,
+            Command::AddAccount => handle_add_account(bot, msg, state, dialogue).await?,
+            Command::List => handle_list(bot, msg, state).await?,
+            Command::SetPrompt => handle_set_prompt(bot, msg, state, dialogue).await?,
+            Command::SetProb => handle_set_prob(bot, msg, state).await?,
+            Command::SetPostProb => handle_set_post_prob(bot, msg, state, args).await?,
+            Command::AllowChat => handle_allow_chat(bot, msg, state).await?,
+            Command::RemoveChat => handle_remove_chat(bot, msg, state).await?,
+            Command::Chats => crate::bot::chat_commands::handle_chats(bot, msg, state, args).await?,
+            Command::DenyChat => crate::bot::chat_commands::handle_deny_chat(bot, msg, state, args).await?,
+            Command::ChatProb => crate::bot::chat_commands::handle_chat_prob(bot, msg, state, args).await?,
+            Command::ChatTriggers => crate::bot::chat_commands::handle_chat_triggers(bot, msg, state, args).await?,
+            Command::ChatEphemeral => crate::bot::chat_commands::handle_chat_ephemeral(bot, msg, state, args).await?,
+            Command::ChatDebounce => crate::bot::chat_commands::handle_chat_debounce(bot, msg, state, args).await?,
+            Command::ChatCatchup => crate::bot::chat_commands::handle_chat_catchup(bot, msg, state, args).await?,
+            Command::ChatLocale => crate::bot::chat_commands::handle_chat_locale(bot, msg, state, args).await?,
+            Command::ChatQuiet => crate::bot::chat_commands::handle_chat_quiet(bot, msg, state, args).await?,
+            Command::ChatPolls => crate::bot::chat_commands::handle_chat_polls(bot, msg, state, args).await?,
+            Command::ChatPins => crate::bot::chat_commands::handle_chat_pins(bot, msg, state, args).await?,
+            Command::ChatMedia => crate::bot::chat_commands::handle_chat_media(bot, msg, state, args).await?,
+            Command::ChatSwitchNotice => crate::bot::chat_commands::handle_chat_switch_notice(bot, msg, state, args).await?,
+            Command::ChatTuning => crate::bot::chat_commands::handle_chat_tuning(bot, msg, state, args).await?,
+            Command::TuningReport => crate::bot::chat_commands::handle_tuning_report(bot, msg, state, args).await?,
+            Command::UndoLastSetting => crate::bot::chat_commands::handle_undo_last_setting(bot, msg, state, args).await?,
+            Command::SettingsHistory => crate::bot::chat_commands::handle_settings_history(bot, msg, state, args).await?,
+            Command::SettingsRollback => crate::bot::chat_commands::handle_settings_rollback(bot, msg, state, args).await?,
+            Command::ChatQuota => crate::bot::chat_commands::handle_chat_quota(bot, msg, state, args).await?,
+            Command::ChatCache => crate::bot::chat_commands::handle_chat_cache(bot, msg, state, args).await?,
+            Command::ChatFormat => crate::bot::chat_commands::handle_chat_format(bot, msg, state, args).await?,
+            Command::Initiative => crate::bot::chat_commands::handle_initiative(bot, msg, state, args).await?,
+            Command::ChatTimezone => crate::bot::chat_commands::handle_chat_timezone(bot, msg, state, args).await?,
+            Command::KarmaChat => crate::bot::chat_commands::handle_karma_chat(bot, msg, state, args).await?,
+            Command::ChatGames => crate::bot::chat_commands::handle_chat_games(bot, msg, state, args).await?,
+            Command::ChatEscalation => crate::bot::chat_commands::handle_chat_escalation(bot, msg, state, args).await?,
+            Command::ChatRetention => crate::bot::chat_commands::handle_chat_retention(bot, msg, state, args).await?,
+            Command::PauseChat => crate::bot::chat_commands::handle_pause_chat(bot, msg, state, args).await?,
+            Command::ResumeChat => crate::bot::chat_commands::handle_resume_chat(bot, msg, state, args).await?,
+            Command::Handoff => crate::bot::chat_commands::handle_handoff(bot, msg, state, args).await?,
+            Command::Resume => crate::bot::chat_commands::handle_resume(bot, msg, state, args).await?,
+            Command::SaveProfile => crate::bot::chat_commands::handle_save_profile(bot, msg, state, args).await?,
+            Command::Profiles => crate::bot::chat_commands::handle_profiles(bot, msg, state).await?,
+            Command::DeleteProfile => crate::bot::chat_commands::handle_delete_profile(bot, msg, state, args).await?,
+            Command::ApplyProfile => crate::bot::chat_commands::handle_apply_profile(bot, msg, state, args).await?,
+            Command::SetAll => crate::bot::chat_commands::handle_set_all(bot, msg, state, args).await?,
+            Command::Topic => crate::bot::chat_commands::handle_topic(bot, msg, state, args).await?,
+            Command::ChatRag => crate::bot::chat_commands::handle_chat_rag(bot, msg, state, args).await?,
+            Command::ChatBotMemory => crate::bot::chat_commands::handle_chat_bot_memory(bot, msg, state, args).await?,
+            Command::Digest => crate::bot::chat_commands::handle_digest(bot, msg, state, args).await?,
+            Command::Subscribe => crate::bot::chat_commands::handle_subscribe(bot, msg, state, args).await?,
+            Command::Unsubscribe => crate::bot::chat_commands::handle_unsubscribe(bot, msg, state, args).await?,
+            Command::Subscriptions => crate::bot::chat_commands::handle_subscriptions(bot, msg, state, args).await?,
+            Command::ChatRotation => crate::bot::chat_commands::handle_chat_rotation(bot, msg, state, args).await?,
+            Command::TopChats => crate::bot::chat_commands::handle_top_chats(bot, msg, state, args).await?,
+            Command::Relationship => handle_relationship(bot, msg, state, args).await?,
+            Command::Ask => handle_ask(bot, msg, state, args).await?,
+            Command::Why => handle_why(bot, msg, state, args).await?,
+            Command::Trace => handle_trace(bot, msg, state, args).await?,
+            Command::PurgeHistory => handle_purge_history(bot, msg, state, args).await?,
+            Command::MemoryStats => handle_memory_stats(bot, msg, state, args).await?,
+            Command::Entities => handle_entities(bot, msg, state, args).await?,
+            Command::Translate => crate::bot::bridge_commands::handle_translate(bot, msg, state, args).await?,
+            Command::Bridges => crate::bot::bridge_commands::handle_bridges(bot, msg, state, args).await?,
+            Command::BridgeAdd => crate::bot::bridge_commands::handle_bridge_add(bot, msg, state, args).await?,
+            Command::BridgeRemove => crate::bot::bridge_commands::handle_bridge_remove(bot, msg, state, args).await?,
+            Command::Pin => crate::bot::pin_commands::handle_pin(bot, msg, state, args).await?,
+            Command::Unpin => crate::bot::pin_commands::handle_unpin(bot, msg, state, args).await?,
+            Command::Audit => crate::bot::pin_commands::handle_audit(bot, msg, state, args).await?,
+            Command::Memories => crate::bot::memory_commands::handle_memories(bot, msg, state, args).await?,
+            Command::MemoryEdit => crate::bot::memory_commands::handle_memory_edit(bot, msg, state, args).await?,
+            Command::MemoryDelete => crate::bot::memory_commands::handle_memory_delete(bot, msg, state, args).await?,
+            Command::MemoryDeleteWhere => crate::bot::memory_commands::handle_memory_delete_where(bot, msg, state, args).await?,
+            Command::MemoryHeatmap => crate::bot::memory_commands::handle_memory_heatmap(bot, msg, state, args).await?,
+            Command::Messages => crate::bot::chat_commands::handle_messages(bot, msg, state, args).await?,
+            Command::Summaries => crate::bot::memory_commands::handle_summaries(bot, msg, state, args).await?,
+            Command::SummaryRegenerate => crate::bot::memory_commands::handle_summary_regenerate(bot, msg, state, args).await?,
+            Command::SummaryDelete => crate::bot::memory_commands::handle_summary_delete(bot, msg, state, args).await?,
+            Command::ExportMemory => handle_export_memory(bot, msg, state, args).await?,
+            Command::ImportMemory => handle_import_memory(bot, msg, state, args).await?,
+            Command::EmbedBacklog => handle_embed_backlog(bot, msg, state, args).await?,
+            Command::Corrections => handle_corrections(bot, msg, state, args).await?,
+            Command::Models => handle_models(bot, msg, state).await?,
+            Command::OllamaModels => crate::bot::model_commands::handle_ollama_models(bot, msg, state, args).await?,
+            Command::PullModel => crate::bot::model_commands::handle_pull_model(bot, msg, state, args).await?,
+            Command::DeleteModel => crate::bot::model_commands::handle_delete_model(bot, msg, state, args).await?,
+            Command::Rules => crate::bot::rule_commands::handle_rules(bot, msg, state).await?,
+            Command::RuleAdd => crate::bot::rule_commands::handle_rule_add(bot, msg, state, args).await?,
+            Command::RuleRemove => crate::bot::rule_commands::handle_rule_remove(bot, msg, state, args).await?,
+            Command::SecurityPolicy => handle_security_policy(bot, msg, state, args).await?,
+            Command::Violations => handle_violations(bot, msg, state).await?,
+            Command::Webhooks => handle_webhooks(bot, msg, state).await?,
+            Command::WebhookAdd => handle_webhook_add(bot, msg, state, args).await?,
+            Command::WebhookRemove => handle_webhook_remove(bot, msg, state, args).await?,
+            Command::WebhookTest => handle_webhook_test(bot, msg, state, args).await?,
+            Command::Loglevel => handle_loglevel(bot, msg, args).await?,
+            Command::Stop => handle_stop(bot, msg, state).await?,
+            Command::Delete => handle_delete(bot, msg, state).await?,
+            
+            // Persona commands
+            Command::ListPersonas => handle_list_personas(bot, msg).await?,
+            Command::RandomPersona => handle_random_persona(bot, msg, state, args).await?,
+            Command::SetPersona => handle_set_persona(bot, msg, state, args).await?,
+            Command::CreatePersona => handle_create_persona(bot, msg, state).await?,
+            Command::LintPersona => crate::bot::persona_commands::handle_lint_persona(bot, msg, state, args).await?,
+            Command::Personas => handle_personas(bot, msg, state).await?,
+            Command::BindPersona => handle_bind_persona(bot, msg, state, args).await?,
+            Command::UnbindPersona => handle_unbind_persona(bot, msg, state, args).await?,
+            Command::DeletePersona => handle_delete_persona(bot, msg, state, args).await?,
+            Command::TagPersona => crate::bot::persona_commands::handle_tag_persona(bot, msg, state, args).await?,
+            Command::UntagPersona => crate::bot::persona_commands::handle_untag_persona(bot, msg, state, args).await?,
+            Command::PersonasByTag => crate::bot::persona_commands::handle_personas_by_tag(bot, msg, state, args).await?,
+            Command::ExportTag => crate::bot::persona_commands::handle_export_tag(bot, msg, state, args).await?,
+            Command::ExportChangedPersonas => crate::bot::persona_commands::handle_export_changed_personas(bot, msg, state).await?,
+            Command::ImportPersonas => crate::bot::persona_commands::handle_import_personas(bot, msg, state).await?,
+            Command::DeleteTag => crate::bot::persona_commands::handle_delete_tag(bot, msg, state, args).await?,
+            Command::RotateTag => crate::bot::persona_commands::handle_rotate_tag(bot, msg, state, args).await?,
+            Command::PersonaWeight => crate::bot::persona_commands::handle_persona_weight(bot, msg, state, args).await?,
+            Command::PersonaStats => crate::bot::persona_commands::handle_persona_stats(bot, msg, state, args).await?,
+            Command::PersonaStyle => crate::bot::persona_commands::handle_persona_style(bot, msg, state, args).await?,
+            Command::EditPersona => crate::bot::persona_commands::handle_edit_persona(bot, msg, state, args).await?,
+            Command::PersonaTime => crate::bot::persona_commands::handle_persona_time(bot, msg, state, args).await?,
+            Command::PersonaMemory => crate::bot::persona_commands::handle_persona_memory(bot, msg, state, args).await?,
+            Command::PersonaCaps => crate::bot::persona_commands::handle_persona_caps(bot, msg, state, args).await?,
+            Command::StickerPack => crate::bot::persona_commands::handle_sticker_pack(bot, msg, state, args).await?,
+            Command::PersonaBase => crate::bot::persona_commands::handle_persona_base(bot, msg, state, args).await?,
+            Command::PreviewPostprocess => crate::bot::persona_commands::handle_preview_postprocess(bot, msg, state, args).await?,
+            Command::AddExample => crate::bot::persona_commands::handle_add_example(bot, msg, state, args).await?,
+            Command::Examples => crate::bot::persona_commands::handle_examples(bot, msg, state, args).await?,
+            Command::DeleteExample => crate::bot::persona_commands::handle_delete_example(bot, msg, state, args).await?,
+            Command::ExportDataset => crate::bot::persona_commands::handle_export_dataset(bot, msg, state, args).await?,
+            Command::VisionPrompt => crate::bot::persona_commands::handle_vision_prompt(bot, msg, state, args).await?,
+            
+            // Bot group commands
+            Command::CreateGroup => crate::bot::group_commands::handle_create_group(bot, msg, state, args).await?,
+            Command::ListGroups => crate::bot::group_commands::handle_list_groups(bot, msg, state).await?,
+            Command::AddToGroup => crate::bot::group_commands::handle_add_to_group(bot, msg, state, args).await?,
+            
+            // Spam campaign commands
+            Command::Spam => crate::bot::group_commands::handle_create_spam(bot, msg, state, args).await?,
+            Command::ListCampaigns => crate::bot::group_commands::handle_list_campaigns(bot, msg, state).await?,
+            Command::StopCampaign => crate::bot::group_commands::handle_stop_campaign(bot, msg, state, args).await?,
+            
+            // Direct messaging
+            Command::InvoiceLink => crate::bot::payment_commands::handle_invoice_link(bot, msg, args).await?,
+            Command::Grant => crate::bot::payment_commands::handle_grant(bot, msg, state, args).await?,
+            Command::Revoke => crate::bot::payment_commands::handle_revoke(bot, msg, state, args).await?,
+            Command::Entitlements => crate::bot::payment_commands::handle_entitlements(bot, msg, state, args).await?,
+            Command::Dm => crate::bot::group_commands::handle_dm(bot, msg, state, args).await?,
+            
+            Command::Business => crate::bot::business_commands::handle_business(bot, msg, state).await?,
+            Command::BusinessSet => crate::bot::business_commands::handle_business_set(bot, msg, state, args).await?,
+            Command::Help => handle_help(bot, msg, args).await?,
+        }
+
+        Ok::<(), Box<dyn std::error::Error + Send + Sync>>(())
+    };
+
+    match settings_scope {
+        Some((account_id, chat_id)) => {
+            use crate::settings_history::{cooldown_message, guarded, Guarded};
+            match guarded(&history_state, account_id, chat_id, actor_id, &command_name, run).await? {
+                Guarded::Applied(result) => result?,
+                Guarded::CoolingDown(left) => {
+                    reply_bot.send_message(reply_chat, cooldown_message(account_id, chat_id, left)).await?;
+                }
+            }
+        }
+        None => run.await?,
     }
     Ok(())
 }
 
+/// Commands that change a chat's settings; without arguments past the ids most of them only show them
+fn changes_chat_settings(cmd: &Command, arg_count: usize) -> bool {
+    match cmd {
+        Command::DenyChat | Command::ResumeChat => arg_count >= 2,
+        Command::ChatProb
+        | Command::ChatTriggers
+        | Command::ChatEphemeral
+        | Command::ChatDebounce
+        | Command::ChatCatchup
+        | Command::ChatLocale
+        | Command::ChatQuiet
+        | Command::ChatPolls
+        | Command::ChatPins
+        | Command::ChatMedia
+        | Command::ChatSwitchNotice
+        | Command::ChatTuning
+        | Command::ChatQuota
+        | Command::ChatCache
+        | Command::ChatFormat
+        | Command::Initiative
+        | Command::ChatTimezone
+        | Command::KarmaChat
+        | Command::ChatGames
+        | Command::ChatEscalation
+        | Command::ChatRetention
+        | Command::PauseChat
+        | Command::ApplyProfile
+        | Command::ChatRag
+        | Command::ChatBotMemory
+        | Command::Digest
+        | Command::ChatRotation => arg_count > 2,
+        _ => false,
+    }
+}
+
 async fn handle_start(
     bot: Bot,
     msg: Message,
@@ -112,15 +653,51 @@ async fn handle_start(
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let active_count = state.active_userbot_count().await;
     let all_accounts = AccountRepository::list_all(&state.db_pool).await?;
+
+    let wizard_text = match WizardRepository::get(&state.db_pool, msg.chat.id.0).await? {
+        Some(session) if session.seconds_left() > 0 => format!(
+            "• Active Wizard: {} (expires in {} min)\n",
+            dialogues::wizard_label(&session.kind),
+            session.seconds_left() / 60 + 1
+        ),
+        _ => String::new(),
+    };
+
+    let thresholds = &state.config.llm_health;
+    let diagnostics = state.llm_health.diagnostics(thresholds.window);
+    let llm_text = match state.llm_health.paused() {
+        Some((reason, since)) => format!(
+            "• ⏸ Auto-replies paused since {} UTC: {}{}\n",
+            since.format("%H:%M"),
+            html_escape(&reason),
+            if state.config.offline_fallback { " (offline replies on)" } else { "" }
+        ),
+        None => format!(
+            "• LLM ({} min): {} (limits: {:.0}% errors, {}s avg.)\n",
+            thresholds.window.as_secs() / 60,
+            diagnostics.describe(),
+            thresholds.max_error_rate * 100.0,
+            thresholds.max_avg_latency.as_secs()
+        ),
+    };
+    let throttle_text = match state.throttle.level() {
+        crate::ai::throttle::ThrottleLevel::Normal => String::new(),
+        _ => format!("• 🌡 Throttled under host load: {}\n", state.throttle.describe()),
+    };
     
     let status_text = format!(
         "🎭 <b>Puppeteer Admin Panel</b>\n\n\
         📊 <b>Quick Stats:</b>\n\
         • Active Userbots: {}\n\
-        • Total Accounts: {}\n\n\
+        • Total Accounts: {}\n\
+        {}{}{}{}\n\
         Select an option below:",
         active_count,
-        all_accounts.len()
+        all_accounts.len(),
+        llm_text,
+        throttle_text,
+        wizard_text,
+        if state.config.safe_mode { "• 🛡 Safe mode: userbots, ghost mode, campaigns and web search are off\n" } else { "" }
     );
 
     bot.send_message(msg.chat.id, status_text)
@@ -134,8 +711,14 @@ async fn handle_start(
 async fn handle_add_account(
     bot: Bot,
     msg: Message,
+    state: AppState,
     dialogue: AddAccountDialogue,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = msg.from.as_ref().map(|u| u.id.0 as i64);
+    if dialogues::refuse_if_busy(&bot, &state, msg.chat.id, user_id).await? {
+        return Ok(());
+    }
+
     bot.send_message(
         msg.chat.id,
         "📱 <b>Add New Userbot Account</b>\n\n\
@@ -143,9 +726,10 @@ async fn handle_add_account(
         Send /cancel to abort.",
     )
     .parse_mode(teloxide::types::ParseMode::Html)
+    .reply_markup(dialogues::cancel_keyboard())
     .await?;
 
-    dialogue.update(AddAccountState::ReceivePhone).await?;
+    dialogues::enter_wizard(&dialogue, &state, user_id, AddAccountState::ReceivePhone).await?;
     Ok(())
 }
 
@@ -223,6 +807,11 @@ async fn handle_set_prompt(
     };
 
     // Check if account exists
+    let user_id = msg.from.as_ref().map(|u| u.id.0 as i64);
+    if dialogues::refuse_if_busy(&bot, &state, msg.chat.id, user_id).await? {
+        return Ok(());
+    }
+
     match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
         Some(account) => {
             bot.send_message(
@@ -239,9 +828,11 @@ async fn handle_set_prompt(
                 ),
             )
             .parse_mode(teloxide::types::ParseMode::Html)
+            .reply_markup(dialogues::cancel_keyboard())
             .await?;
 
-            dialogue.update(AddAccountState::ReceivePrompt { account_id }).await?;
+            dialogues::enter_wizard(&dialogue, &state, user_id, AddAccountState::ReceivePrompt { account_id })
+                .await?;
         }
         None => {
             bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
@@ -303,51 +894,58 @@ async fn handle_set_prob(
     Ok(())
 }
 
-async fn handle_allow_chat(
+async fn handle_set_post_prob(
     bot: Bot,
     msg: Message,
     state: AppState,
+    args: Vec<String>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Parse arguments from message text
-    let text = msg.text().unwrap_or("");
-    let parts: Vec<&str> = text.split_whitespace().collect();
-    
-    if parts.len() < 3 {
-        bot.send_message(msg.chat.id, "❌ Usage: /allow_chat <account_id> <chat_id>")
+    if args.len() < 2 {
+        bot.send_message(msg.chat.id, "❌ Usage: /set_post_prob <account_id> <0-100>")
             .await?;
         return Ok(());
     }
-    
-    let account_id = match parts[1].parse::<i64>() {
+
+    let account_id = match args[0].parse::<i64>() {
         Ok(id) => id,
         Err(_) => {
-            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /allow_chat <id> <chat_id>")
+            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /set_post_prob <id> <0-100>")
                 .await?;
             return Ok(());
         }
     };
 
-    let chat_id_num = match parts[2].parse::<i64>() {
-        Ok(id) => id,
-        Err(_) => {
-            bot.send_message(msg.chat.id, "❌ Invalid chat ID")
+    let probability = match args[1].parse::<i64>() {
+        Ok(p) if (0..=100).contains(&p) => p,
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Probability must be between 0 and 100")
                 .await?;
             return Ok(());
         }
     };
 
-    AccountRepository::add_allowed_chat(&state.db_pool, account_id, chat_id_num).await?;
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
+            .await?;
+        return Ok(());
+    }
+
+    AccountRepository::update_channel_post_probability(&state.db_pool, account_id, probability)
+        .await?;
 
     bot.send_message(
         msg.chat.id,
-        format!("✅ Chat {} added to whitelist for account {}", chat_id_num, account_id),
+        format!(
+            "✅ Account {} will comment on {}% of channel posts in discussion groups",
+            account_id, probability
+        ),
     )
     .await?;
 
     Ok(())
 }
 
-async fn handle_remove_chat(
+async fn handle_allow_chat(
     bot: Bot,
     msg: Message,
     state: AppState,
@@ -357,7 +955,7 @@ async fn handle_remove_chat(
     let parts: Vec<&str> = text.split_whitespace().collect();
     
     if parts.len() < 3 {
-        bot.send_message(msg.chat.id, "❌ Usage: /remove_chat <account_id> <chat_id>")
+        bot.send_message(msg.chat.id, "❌ Usage: /allow_chat <account_id> <chat_id>")
             .await?;
         return Ok(());
     }
@@ -365,7 +963,7 @@ async fn handle_remove_chat(
     let account_id = match parts[1].parse::<i64>() {
         Ok(id) => id,
         Err(_) => {
-            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /remove_chat <id> <chat_id>")
+            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /allow_chat <id> <chat_id>")
                 .await?;
             return Ok(());
         }
@@ -380,28 +978,28 @@ async fn handle_remove_chat(
         }
     };
 
-    AccountRepository::remove_allowed_chat(&state.db_pool, account_id, chat_id_num).await?;
+    AccountRepository::add_allowed_chat(&state.db_pool, account_id, chat_id_num).await?;
 
     bot.send_message(
         msg.chat.id,
-        format!("✅ Chat {} removed from whitelist for account {}", chat_id_num, account_id),
+        format!("✅ Chat {} added to whitelist for account {}", chat_id_num, account_id),
     )
     .await?;
 
     Ok(())
 }
 
-async fn handle_stop(
+async fn handle_remove_chat(
     bot: Bot,
     msg: Message,
     state: AppState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Parse account ID from message text
+    // Parse arguments from message text
     let text = msg.text().unwrap_or("");
     let parts: Vec<&str> = text.split_whitespace().collect();
     
-    if parts.len() < 2 {
-        bot.send_message(msg.chat.id, "❌ Usage: /stop <account_id>")
+    if parts.len() < 3 {
+        bot.send_message(msg.chat.id, "❌ Usage: /remove_chat <account_id> <chat_id>")
             .await?;
         return Ok(());
     }
@@ -409,77 +1007,98 @@ async fn handle_stop(
     let account_id = match parts[1].parse::<i64>() {
         Ok(id) => id,
         Err(_) => {
-            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /stop <id>")
+            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /remove_chat <id> <chat_id>")
                 .await?;
             return Ok(());
         }
     };
 
-    if !state.is_userbot_running(account_id).await {
-        bot.send_message(msg.chat.id, format!("⚠️ Userbot {} is not running.", account_id))
-            .await?;
-        return Ok(());
-    }
+    let chat_id_num = match parts[2].parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, "❌ Invalid chat ID")
+                .await?;
+            return Ok(());
+        }
+    };
 
-    state.shutdown_userbot(account_id).await?;
-    AccountRepository::set_active(&state.db_pool, account_id, false).await?;
+    AccountRepository::remove_allowed_chat(&state.db_pool, account_id, chat_id_num).await?;
 
     bot.send_message(
         msg.chat.id,
-        format!("✅ Userbot {} stopped successfully.", account_id),
+        format!("✅ Chat {} removed from whitelist for account {}", chat_id_num, account_id),
     )
     .await?;
 
     Ok(())
 }
 
-async fn handle_delete(
+async fn handle_security_policy(
     bot: Bot,
     msg: Message,
     state: AppState,
+    args: Vec<String>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Parse account ID from message text
-    let text = msg.text().unwrap_or("");
-    let parts: Vec<&str> = text.split_whitespace().collect();
-    
-    if parts.len() < 2 {
-        bot.send_message(msg.chat.id, "❌ Usage: /delete <account_id>")
+    let (account_id, chat_id_num) = match crate::settings_history::chat_scope(&args) {
+        Some(scope) => scope,
+        None => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /security_policy <account_id> <chat_id> <off|log|strike|block> [threshold 0.0-1.0]",
+            )
             .await?;
-        return Ok(());
-    }
-    
-    let account_id = match parts[1].parse::<i64>() {
-        Ok(id) => id,
-        Err(_) => {
-            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /delete <id>")
-                .await?;
             return Ok(());
         }
     };
 
-    // Check if account exists
-    let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
-        Some(acc) => acc,
+    // Without a policy argument, show the effective settings
+    let policy = match args.get(2) {
+        Some(p) => match SecurityPolicy::parse(p) {
+            Some(policy) => policy,
+            None => {
+                bot.send_message(msg.chat.id, "❌ Policy must be one of: off, log, strike, block")
+                    .await?;
+                return Ok(());
+            }
+        },
         None => {
-            bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
-                .await?;
+            let (policy, threshold) = security::resolve_policy(&state, account_id, chat_id_num).await?;
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "🛡 Chat {} (account {}): policy <b>{}</b>, threshold {:.2}",
+                    chat_id_num,
+                    account_id,
+                    policy.as_str(),
+                    threshold
+                ),
+            )
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .await?;
             return Ok(());
         }
     };
 
-    // Stop if running
-    if state.is_userbot_running(account_id).await {
-        state.shutdown_userbot(account_id).await?;
-    }
+    let threshold = match args.get(3).map(|t| t.parse::<f64>()) {
+        Some(Ok(t)) if (0.0..=1.0).contains(&t) => Some(t),
+        Some(_) => {
+            bot.send_message(msg.chat.id, "❌ Threshold must be between 0.0 and 1.0")
+                .await?;
+            return Ok(());
+        }
+        None => None,
+    };
 
-    // Delete from database
-    AccountRepository::delete(&state.db_pool, account_id).await?;
+    SecurityRepository::set_policy(&state.db_pool, account_id, chat_id_num, policy.as_str(), threshold).await?;
 
     bot.send_message(
         msg.chat.id,
         format!(
-            "✅ Account {} ({}) deleted successfully.",
-            account_id, account.phone_number
+            "✅ Security policy for chat {} (account {}) set to {}{}",
+            chat_id_num,
+            account_id,
+            policy.as_str(),
+            threshold.map(|t| format!(" (threshold {:.2})", t)).unwrap_or_default()
         ),
     )
     .await?;
@@ -487,105 +1106,1250 @@ async fn handle_delete(
     Ok(())
 }
 
-async fn handle_help(
+async fn handle_violations(
     bot: Bot,
     msg: Message,
+    state: AppState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let help_text = format!(
-        "<b>🤖 Puppeteer Admin Bot</b>\n\n\
-        <b>Available Commands:</b>\n\
-        {}\n\n\
-        <b>About:</b>\n\
-        Puppeteer manages multiple AI-driven Telegram userbots with human-like behavior. \
-        Each userbot can have its own personality (system prompt) and maintains conversation context.",
-        Command::descriptions()
-    );
+    let violations = SecurityRepository::list_recent(&state.db_pool, 10).await?;
 
-    bot.send_message(msg.chat.id, help_text)
+    if violations.is_empty() {
+        bot.send_message(msg.chat.id, "🛡 No prompt-injection attempts recorded.")
+            .await?;
+        return Ok(());
+    }
+
+    let mut response = String::from("🛡 <b>Recent Violations:</b>\n\n");
+
+    for v in violations {
+        response.push_str(&format!(
+            "• {} | chat {} | user {} | score {:.2} | {}\n  <i>{}</i> — {}\n\n",
+            v.created_at.format("%Y-%m-%d %H:%M"),
+            v.chat_id,
+            v.user_id,
+            v.score,
+            v.action,
+            v.matched_rules,
+            html_escape(&v.content.chars().take(100).collect::<String>())
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
         .parse_mode(teloxide::types::ParseMode::Html)
         .await?;
 
     Ok(())
 }
 
-// ============================================================================
-// Persona Management Handlers
-// ============================================================================
-
-async fn handle_list_personas(
+async fn handle_webhooks(
     bot: Bot,
     msg: Message,
+    state: AppState,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let archetypes = crate::ai::list_archetypes();
-    
-    let mut response = String::from("🎭 <b>Available Personality Archetypes:</b>\n\n");
-    
-    for (idx, name) in archetypes.iter().enumerate() {
-        response.push_str(&format!("{}. <code>{}</code>\n", idx + 1, name));
+    let webhooks = WebhookRepository::list(&state.db_pool).await?;
+
+    if webhooks.is_empty() {
+        bot.send_message(msg.chat.id, "🔗 No webhooks. Add one with /webhook_add").await?;
+        return Ok(());
     }
-    
-    response.push_str("\n<b>Usage:</b>\n");
-    response.push_str("• <code>/random_persona &lt;account_id&gt;</code> - Assign random persona\n");
-    response.push_str("• <code>/set_persona &lt;account_id&gt; &lt;persona_name&gt;</code> - Assign specific persona\n\n");
-    response.push_str("<i>Each persona has unique communication style, emoji usage, and behavioral patterns.</i>");
-    
+
+    let mut response = String::from("🔗 <b>Webhooks:</b>\n");
+    for webhook in webhooks {
+        let pending = WebhookRepository::count_pending(&state.db_pool, webhook.id).await?;
+        response.push_str(&format!(
+            "\n#{} {}\n   events: <code>{}</code>",
+            webhook.id,
+            html_escape(&webhook.url),
+            html_escape(&webhook.events)
+        ));
+        if let Some(keywords) = &webhook.keywords {
+            response.push_str(&format!(" | keywords: {}", html_escape(keywords)));
+        }
+        response.push_str(&format!(
+            "\n   {} | {} pending\n",
+            html_escape(webhook.last_status.as_deref().unwrap_or("never sent")),
+            pending
+        ));
+    }
+
     bot.send_message(msg.chat.id, response)
         .parse_mode(teloxide::types::ParseMode::Html)
         .await?;
-    
+
     Ok(())
 }
 
-async fn handle_random_persona(
+async fn handle_webhook_add(
     bot: Bot,
     msg: Message,
     state: AppState,
     args: Vec<String>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    if args.is_empty() {
-        bot.send_message(
-            msg.chat.id,
-            "❌ Usage: /random_persona <account_id>\n\nExample: /random_persona 1",
-        )
-        .await?;
-        return Ok(());
-    }
+    let usage = format!(
+        "❌ Usage: /webhook_add <url> <event,...|*> [keywords=a,b] [secret=<s>]\nEvents: {}",
+        crate::webhooks::Event::ALL.iter().map(|e| e.as_str()).collect::<Vec<_>>().join(", ")
+    );
 
-    let account_id: i64 = match args[0].parse() {
-        Ok(id) => id,
-        Err(_) => {
-            bot.send_message(msg.chat.id, "❌ Invalid account ID. Must be a number.")
-                .await?;
+    let (url, events) = match (
+        args.first().filter(|u| u.starts_with("http://") || u.starts_with("https://")),
+        args.get(1),
+    ) {
+        (Some(url), Some(events)) => (url.clone(), events.clone()),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
             return Ok(());
         }
     };
 
-    // Check if account exists
-    let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
-        Some(acc) => acc,
-        None => {
-            bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
-                .await?;
-            return Ok(());
+    if events != "*" && !events.split(',').all(|e| crate::webhooks::Event::parse(e).is_some()) {
+        bot.send_message(msg.chat.id, usage).await?;
+        return Ok(());
+    }
+
+    let mut keywords = None;
+    let mut secret = None;
+    for arg in &args[2..] {
+        match arg.split_once('=') {
+            Some(("keywords", value)) if !value.is_empty() => keywords = Some(value.to_string()),
+            Some(("secret", value)) if !value.is_empty() => secret = Some(value.to_string()),
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
         }
-    };
+    }
 
-    // Generate random persona
-    let new_prompt = crate::ai::generate_random_persona();
+    let wants_keywords = events == "*" || events.split(',').any(|e| e.trim() == "keyword");
+    if wants_keywords != keywords.is_some() {
+        bot.send_message(msg.chat.id, "❌ The keyword event needs keywords=..., and keywords need the keyword event")
+            .await?;
+        return Ok(());
+    }
 
-    // Update in database
-    AccountRepository::update_system_prompt(&state.db_pool, account_id, &new_prompt).await?;
+    let generated = secret.is_none();
+    let secret = secret.unwrap_or_else(crate::webhooks::generate_secret);
+    let id = WebhookRepository::add(&state.db_pool, &url, &events, &secret, keywords.as_deref()).await?;
+    crate::webhooks::invalidate_cache();
 
-    bot.send_message(
-        msg.chat.id,
-        format!(
-            "✅ Random persona assigned to account {} ({})\n\n\
-            <i>Restart the userbot with /stop {} and then start it again for changes to take effect.</i>",
-            account_id, account.phone_number, account_id
-        ),
-    )
-    .parse_mode(teloxide::types::ParseMode::Html)
-    .await?;
+    let mut response = format!("✅ Webhook #{} added for <code>{}</code>", id, html_escape(&events));
+    if generated {
+        response.push_str(&format!(
+            "\n\nSigning secret (shown once): <code>{}</code>\nVerify <code>X-Puppeteer-Signature</code> \
+            as sha256=HMAC-SHA256(secret, body).",
+            secret
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_webhook_remove(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /webhook_remove <webhook_id> (see /webhooks)").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if WebhookRepository::remove(&state.db_pool, id).await? {
+        crate::webhooks::invalidate_cache();
+        format!("🗑 Webhook #{} removed with its pending deliveries", id)
+    } else {
+        format!("❌ Webhook #{} not found", id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+async fn handle_webhook_test(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /webhook_test <webhook_id>").await?;
+            return Ok(());
+        }
+    };
+
+    if WebhookRepository::get(&state.db_pool, id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Webhook #{} not found", id)).await?;
+        return Ok(());
+    }
+
+    crate::webhooks::ping(&state, id).await?;
+    bot.send_message(
+        msg.chat.id,
+        format!("📤 Ping queued for webhook #{}, check /webhooks for the result in a few seconds", id),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_loglevel(
+    bot: Bot,
+    msg: Message,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if args.is_empty() {
+        let current = crate::logging::current_level().unwrap_or_else(|| "unknown".to_string());
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "📝 Current log filter: <code>{}</code>\n\nUsage: /loglevel <code>debug</code> or <code>info,puppeteer=trace</code>, /loglevel reset",
+                html_escape(&current)
+            ),
+        )
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+        return Ok(());
+    }
+
+    let directives = match args.join(",").as_str() {
+        "reset" => std::env::var("RUST_LOG").unwrap_or_else(|_| "info,puppeteer=debug".to_string()),
+        other => other.to_string(),
+    };
+
+    match crate::logging::set_level(&directives) {
+        Ok(()) => {
+            bot.send_message(msg.chat.id, format!("✅ Log filter set to {}", directives))
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {:#}", e)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn handle_purge_history(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let days = match args.first().and_then(|a| a.parse::<i64>().ok()).filter(|d| *d > 0) {
+        Some(d) => d,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /purge_history <days>").await?;
+            return Ok(());
+        }
+    };
+
+    let messages = MessageRepository::delete_older_than(&state.db_pool, days).await?;
+    let memories = crate::ai::rag::purge_memories_older_than(&state.db_pool, days).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🗑 Deleted {} messages and {} memories older than {} days",
+            messages, memories, days
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_relationship(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, user_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(u)) => (a, u),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /relationship <account_id> <user_id> [how they know each other]",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if args.len() > 2 {
+        let how_known = args[2..].join(" ");
+        RelationshipRepository::set_how_known(&state.db_pool, account_id, user_id, &how_known).await?;
+        bot.send_message(
+            msg.chat.id,
+            format!("✅ Account {} now knows user {} as: {}", account_id, user_id, how_known),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let relationship = match RelationshipRepository::get(&state.db_pool, account_id, user_id).await? {
+        Some(r) => r,
+        None => {
+            bot.send_message(
+                msg.chat.id,
+                format!("🤝 Account {} has no history with user {}", account_id, user_id),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let jokes = relationship.get_inside_jokes();
+    let jokes_text = if jokes.is_empty() {
+        "—".to_string()
+    } else {
+        jokes.iter().map(|j| format!("• {}", html_escape(j))).collect::<Vec<_>>().join("\n")
+    };
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🤝 <b>Account {} ↔ user {}</b>\n\n\
+            Known as: {}\n\
+            Familiarity: {:.0}%\n\
+            Messages: {}\n\
+            Last seen: {}\n\n\
+            <b>Inside jokes:</b>\n{}",
+            account_id,
+            user_id,
+            if relationship.how_known.is_empty() { "—".to_string() } else { html_escape(&relationship.how_known) },
+            relationship.familiarity * 100.0,
+            relationship.message_count,
+            relationship.last_interaction_at.format("%Y-%m-%d %H:%M"),
+            jokes_text
+        ),
+    )
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_ask(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) if args.len() > 2 => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /ask <account_id> <chat_id> <question>")
+                .await?;
+            return Ok(());
+        }
+    };
+    let question = args[2..].join(" ");
+
+    bot.send_chat_action(msg.chat.id, teloxide::types::ChatAction::Typing).await?;
+
+    let result = match crate::ai::answer_question(&state, account_id, chat_id, &question).await {
+        Ok(result) => result,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {:#}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut response = format!("❓ <b>{}</b>\n\n{}", html_escape(&question), html_escape(&result.answer));
+
+    if !result.sources.is_empty() {
+        response.push_str("\n\n📚 <b>Sources:</b>\n");
+    }
+    for (i, source) in result.sources.iter().enumerate() {
+        let excerpt: String = source.content.chars().take(150).collect();
+        let date = crate::ai::ask::format_date(source.created_at);
+        // A profile link is no source in private chats, so only group messages get linked
+        let link = source.message_id.filter(|_| chat_id < 0);
+        let label = match link.and_then(|id| crate::userbot::trace::message_link(chat_id, id, 0)) {
+            Some(link) => format!("<a href=\"{}\">{}</a>", link, date),
+            None if source.tier == crate::ai::MemoryTier::Semantic => format!("{}, summary", date),
+            None => date,
+        };
+        response.push_str(&format!("[{}] {} — <i>{}</i>\n", i + 1, label, html_escape(&excerpt)));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Show where the time went while answering a message
+/// Usage: /trace <message_link> or /trace <chat_id> <message_id>
+async fn handle_trace(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::userbot::trace;
+
+    let target = match args.as_slice() {
+        [link] => trace::parse_message_link(link),
+        [chat_id, message_id] => match (chat_id.parse::<i64>(), message_id.parse::<i64>()) {
+            (Ok(chat_id), Ok(message_id)) => Some((Some(chat_id), message_id)),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (chat_id, message_id) = match target {
+        Some(target) => target,
+        None => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /trace <message_link> or /trace <chat_id> <message_id>\n\
+                Link the reply or the message it answered.",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let traces = crate::db::TraceRepository::find(&state.db_pool, chat_id, message_id, 3).await?;
+    if traces.is_empty() {
+        bot.send_message(msg.chat.id, "ℹ️ No timings stored for that message (they are kept for a week)").await?;
+        return Ok(());
+    }
+
+    let mut text = String::new();
+    for record in &traces {
+        text.push_str(&format!(
+            "⏱ <b>Reply {}</b> in chat {} (account {})\n{} UTC, {} ms in total\n",
+            html_escape(&record.correlation_id),
+            record.chat_id,
+            record.account_id,
+            record.created_at.format("%d.%m %H:%M:%S"),
+            record.total_ms
+        ));
+        for (stage, ms) in trace::parse_stages(&record.stages) {
+            let share = if record.total_ms > 0 { ms as f64 * 100.0 / record.total_ms as f64 } else { 0.0 };
+            text.push_str(&format!("• {}: {} ms ({:.0}%)\n", html_escape(&stage), ms, share));
+        }
+        text.push('\n');
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Debug memory retrieval for a message as if it arrived in a chat
+/// Usage: /why <account_id> <chat_id> [from=<user_id>] <text>
+async fn handle_why(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) if args.len() > 2 => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /why <account_id> <chat_id> [from=<user_id>] <text>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let sender_id = args[2].strip_prefix("from=").and_then(|id| id.parse::<i64>().ok());
+    let text = args[if sender_id.is_some() { 3 } else { 2 }..].join(" ");
+    if text.is_empty() {
+        bot.send_message(msg.chat.id, "❌ Nothing to look up").await?;
+        return Ok(());
+    }
+
+    let (filter, memories) = match crate::ai::ask::explain_retrieval(&state, account_id, chat_id, sender_id, &text).await {
+        Ok(result) => result,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {:#}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut response = format!(
+        "🔎 <b>Retrieval for</b> <i>{}</i>\nFilters: {}\n\n",
+        html_escape(&text),
+        html_escape(&filter.describe())
+    );
+    if memories.is_empty() {
+        response.push_str("Nothing retrieved.");
+    }
+    for (i, memory) in memories.iter().enumerate() {
+        let excerpt: String = memory.content.chars().take(120).collect();
+        let source = match (memory.tier, memory.sender_id) {
+            (crate::ai::MemoryTier::Semantic, _) => "fact".to_string(),
+            (_, Some(sender)) if Some(sender) == filter.prefer_sender => format!("user {} ⭐", sender),
+            (_, Some(sender)) => format!("user {}", sender),
+            (_, None) => "unknown author".to_string(),
+        };
+        let used = if memory.similarity > 0.5 { "✅" } else { "▫️" };
+        response.push_str(&format!(
+            "{} {}. <b>{:.2}</b> {}, {} — <i>{}</i>\n",
+            used,
+            i + 1,
+            memory.similarity,
+            source,
+            crate::ai::ask::format_date(memory.created_at),
+            html_escape(&excerpt)
+        ));
+    }
+    response.push_str("\n✅ above the 0.5 injection threshold | ⭐ preferred interlocutor");
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_memory_stats(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /memory_stats <account_id> [chat_id]")
+                .await?;
+            return Ok(());
+        }
+    };
+    let chat_id = args.get(1).and_then(|a| a.parse::<i64>().ok());
+
+    let stats = crate::ai::memory_stats(&state.db_pool, account_id, chat_id).await?;
+
+    let last_run = stats
+        .last_consolidation
+        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+        .map(|dt| dt.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_else(|| "never".to_string());
+
+    let scope = chat_id
+        .map(|id| format!("chat {}", id))
+        .unwrap_or_else(|| "all chats".to_string());
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🧠 <b>Memory of account {}</b> ({})\n\n\
+            Episodic chunks: {}\n\
+            Semantic facts: {}\n\
+            Avg. fact confidence: {}\n\
+            Awaiting consolidation: {}\n\
+            Last consolidation: {}{}",
+            account_id,
+            scope,
+            stats.episodic_count,
+            stats.semantic_count,
+            stats
+                .avg_confidence
+                .map(|c| format!("{:.0}%", c * 100.0))
+                .unwrap_or_else(|| "—".to_string()),
+            stats.pending_episodes,
+            last_run,
+            rerank_stats(&state)
+        ),
+    )
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
+
+    Ok(())
+}
+
+/// List a chat's entity graph, or one entity with its relations and the messages they came from
+/// Usage: /entities <account_id> <chat_id> [name]
+async fn handle_entities(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(account_id), Some(chat_id)) => (account_id, chat_id),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /entities <account_id> <chat_id> [name]").await?;
+            return Ok(());
+        }
+    };
+
+    let name = args[2..].join(" ");
+    if name.is_empty() {
+        let entities = crate::db::EntityRepository::list_for_chat(&state.db_pool, account_id, chat_id, 30).await?;
+        if entities.is_empty() {
+            bot.send_message(msg.chat.id, "📭 No entities extracted in this chat yet.").await?;
+            return Ok(());
+        }
+
+        let mut text = format!("🕸 <b>Entities of chat {}</b> (most mentioned first)\n\n", chat_id);
+        for entity in &entities {
+            text.push_str(&format!(
+                "• {} · {} · {}×\n",
+                html_escape(&entity.name),
+                entity.kind,
+                entity.mentions
+            ));
+        }
+        text.push_str("\nDetails: /entities <id> <chat_id> <name>");
+        bot.send_message(msg.chat.id, text)
+            .parse_mode(teloxide::types::ParseMode::Html)
+            .await?;
+        return Ok(());
+    }
+
+    let entity = match crate::db::EntityRepository::find(&state.db_pool, account_id, chat_id, &name).await? {
+        Some(entity) => entity,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ No entity named \"{}\" in this chat.", name)).await?;
+            return Ok(());
+        }
+    };
+
+    let relations = crate::db::EntityRepository::relations(&state.db_pool, entity.id, 20).await?;
+    let mut text = format!(
+        "🕸 <b>{}</b>\n\n\
+        Kind: {}\n\
+        Mentions: {}\n\
+        Last seen in message: {}\n",
+        html_escape(&crate::ai::entities::describe(&entity)),
+        entity.kind,
+        entity.mentions,
+        entity.last_message_id.map_or_else(|| "—".to_string(), |id| id.to_string())
+    );
+    if !relations.is_empty() {
+        text.push_str("\n<b>Relations:</b>\n");
+        for relation in &relations {
+            text.push_str(&format!(
+                "• {} {} {}{}\n",
+                html_escape(&relation.subject),
+                html_escape(&relation.relation),
+                html_escape(&relation.object),
+                relation.message_id.map(|id| format!(" (msg {})", id)).unwrap_or_default()
+            ));
+        }
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Send a chat's episodic and semantic memory as a gzip-compressed JSONL file
+/// Usage: /export_memory <account_id> <chat_id> [embeddings]
+async fn handle_export_memory(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /export_memory <account_id> <chat_id> [embeddings]")
+                .await?;
+            return Ok(());
+        }
+    };
+    let with_embeddings = args.get(2).map(|a| a.as_str()) == Some("embeddings");
+
+    let (bytes, count) = crate::ai::memory_transfer::export_chat(&state, account_id, chat_id, with_embeddings).await?;
+    if count == 0 {
+        bot.send_message(msg.chat.id, format!("🧠 Chat {} has no memories to export", chat_id))
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_document(
+        msg.chat.id,
+        teloxide::types::InputFile::memory(bytes)
+            .file_name(format!("memory-{}-{}.jsonl.gz", account_id, chat_id)),
+    )
+    .caption(format!(
+        "🧠 {} memories of chat {}{}. Import with /import_memory <account_id> <chat_id> in reply to this file.",
+        count,
+        chat_id,
+        if with_embeddings { ", with embeddings" } else { "" }
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Start, stop or list backfills of memory embeddings for old messages
+/// Usage: /embed_backlog <chat_id|all> | /embed_backlog stop <job_id> | /embed_backlog
+async fn handle_embed_backlog(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::embed_backlog;
+    use crate::db::EmbedBacklogRepository;
+
+    let usage = "❌ Usage: /embed_backlog <chat_id|all> or /embed_backlog stop <job_id>";
+    let chat_id = match args.first().map(String::as_str) {
+        None => {
+            let jobs = EmbedBacklogRepository::list_running(&state.db_pool).await?;
+            if jobs.is_empty() {
+                bot.send_message(msg.chat.id, "No embedding backlog jobs running").await?;
+                return Ok(());
+            }
+            let mut text = String::from("⏳ Running embedding backlog jobs:\n");
+            for job in &jobs {
+                let left = MessageRepository::count_user_after(&state.db_pool, job.chat_id, job.last_message_id).await?;
+                text.push_str(&format!("\n{}", embed_backlog::describe(job, Some(left))));
+            }
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Some("stop") => {
+            let job_id = match args.get(1).and_then(|a| a.parse::<i64>().ok()) {
+                Some(id) => id,
+                None => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            };
+            let text = if EmbedBacklogRepository::set_status(&state.db_pool, job_id, "cancelled").await? {
+                format!("🛑 Embedding backlog job #{} stopped", job_id)
+            } else {
+                format!("❌ No running embedding backlog job #{}", job_id)
+            };
+            bot.send_message(msg.chat.id, text).await?;
+            return Ok(());
+        }
+        Some("all") => None,
+        Some(arg) => match arg.parse::<i64>() {
+            Ok(chat_id) => Some(chat_id),
+            Err(_) => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    if let Some(job) = EmbedBacklogRepository::find_running(&state.db_pool, chat_id).await? {
+        bot.send_message(msg.chat.id, format!("⏳ Already running: {}", embed_backlog::describe(&job, None))).await?;
+        return Ok(());
+    }
+
+    let pending = MessageRepository::count_user_after(&state.db_pool, chat_id, 0).await?;
+    if pending == 0 {
+        bot.send_message(msg.chat.id, format!("Nothing to embed in {}", embed_backlog::scope_label(chat_id))).await?;
+        return Ok(());
+    }
+
+    let job = EmbedBacklogRepository::create(&state.db_pool, chat_id, msg.chat.id.0).await?;
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "⏳ Embedding backlog job #{} queued for {}: {} messages to check, at most {} embeddings a minute. \
+            It resumes after restarts; progress reports follow here.",
+            job.id,
+            embed_backlog::scope_label(chat_id),
+            pending,
+            state.config.embed_backlog_per_minute
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Records imported between two progress updates
+const IMPORT_BATCH: usize = 50;
+
+/// Import a memory export sent as a document, reporting progress as it goes
+/// Usage: /import_memory <account_id> <chat_id> (in reply to the file)
+async fn handle_import_memory(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::memory_transfer::{self, ImportReport};
+    use teloxide::net::Download;
+
+    let usage = "❌ Usage: reply to a .jsonl.gz memory export with /import_memory <account_id> <chat_id>";
+    let (account_id, chat_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => (a, c),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let document = match msg.reply_to_message().and_then(|m| m.document()) {
+        Some(document) => document.clone(),
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Account {} not found", account_id)).await?;
+        return Ok(());
+    }
+
+    let file = bot.get_file(&document.file.id).await?;
+    let mut bytes = Vec::new();
+    bot.download_file(&file.path, &mut bytes).await?;
+
+    let (header, records) = match memory_transfer::parse_export(&bytes) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ Invalid memory export: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let progress = bot
+        .send_message(msg.chat.id, format!("⏳ Importing {} memories into chat {}…", records.len(), chat_id))
+        .await?;
+
+    let mut report = ImportReport::default();
+    for (i, batch) in records.chunks(IMPORT_BATCH).enumerate() {
+        report.add(memory_transfer::import_records(&state, account_id, chat_id, &header, batch).await?);
+
+        let done = (i * IMPORT_BATCH + batch.len()).min(records.len());
+        if done < records.len() {
+            if let Err(e) = bot
+                .edit_message_text(
+                    msg.chat.id,
+                    progress.id,
+                    format!("⏳ Importing into chat {}: {}/{}", chat_id, done, records.len()),
+                )
+                .await
+            {
+                tracing::debug!("Failed to update import progress: {}", e);
+            }
+        }
+    }
+
+    bot.edit_message_text(
+        msg.chat.id,
+        progress.id,
+        format!(
+            "✅ Imported {} memories into chat {} (from chat {} of account {})\n\
+            Already known: {}\n\
+            Re-embedded: {}\n\
+            Failed: {}",
+            report.imported,
+            chat_id,
+            header.chat_id,
+            header.account_id,
+            report.duplicates,
+            report.reembedded,
+            report.failed
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Latest ghost-mode corrections of an account
+/// Usage: /corrections <account_id>
+async fn handle_corrections(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /corrections <account_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let (edited, deleted) = crate::db::CorrectionRepository::counts(&state.db_pool, account_id).await?;
+    let mut response = format!(
+        "✏️ <b>Corrections of account {}</b>\n\nEdited: {} | Deleted: {}\n\n\
+        Reply to one of the account's messages from the account itself with \
+        <code>!edit new text</code> or <code>!del</code>; <code>!save</code> keeps a good reply as a persona example.\n",
+        account_id, edited, deleted
+    );
+
+    for correction in crate::db::CorrectionRepository::list(&state.db_pool, account_id, 5).await? {
+        let rejected: String = correction.rejected.chars().take(80).collect();
+        let corrected = match &correction.corrected {
+            Some(text) => html_escape(&text.chars().take(80).collect::<String>()),
+            None => "🗑".to_string(),
+        };
+        response.push_str(&format!(
+            "\n{} — <s>{}</s> → {}",
+            correction.created_at.format("%m-%d %H:%M"),
+            html_escape(&rejected),
+            corrected
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_models(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut response = format!(
+        "🤖 <b>Configured models</b>\n\nChat: <code>{}</code>\nEmbedding: <code>{}</code>\nVision: <code>{}</code>\n",
+        html_escape(&state.config.ollama_model),
+        html_escape(&state.config.ollama_embed_model),
+        html_escape(&state.config.ollama_vision_model)
+    );
+
+    let routing = &state.config.vision_routing;
+    if !routing.models().is_empty() {
+        response.push_str("\n<b>Vision routing</b>\n");
+        for line in routing.describe(&state.config.ollama_vision_model) {
+            response.push_str(&format!("• {}\n", html_escape(&line)));
+        }
+    }
+
+    if state.config.whisper_url.is_some() {
+        use std::sync::atomic::Ordering;
+        let metrics = &*crate::ai::whisper::WHISPER_METRICS;
+        response.push_str(&format!(
+            "\n<b>Transcription</b> ({} at a time, since start)\n\
+            Running: {} | Queued: {}\n\
+            Done: {} | Failed: {} | Timed out: {}\n\
+            Avg. wait: {} ms | Max wait: {} ms | Placeholders: {}\n",
+            state.config.whisper_concurrency,
+            metrics.running.load(Ordering::Relaxed),
+            metrics.waiting.load(Ordering::Relaxed),
+            metrics.completed.load(Ordering::Relaxed),
+            metrics.failures.load(Ordering::Relaxed),
+            metrics.timeouts.load(Ordering::Relaxed),
+            metrics.avg_wait_ms(),
+            metrics.max_wait_ms.load(Ordering::Relaxed),
+            metrics.slow_waits.load(Ordering::Relaxed)
+        ));
+    }
+
+    let models = crate::db::ModelRepository::list(&state.db_pool).await?;
+    if !models.is_empty() {
+        response.push_str("\n<b>History</b>\n");
+        for m in models {
+            let dimension = m.dimension.map(|d| format!(", {} dims", d)).unwrap_or_default();
+            response.push_str(&format!(
+                "• {} <code>{}</code>{} — {} uses, {} → {}\n",
+                m.kind,
+                html_escape(&m.name),
+                dimension,
+                m.uses,
+                m.first_used_at.format("%Y-%m-%d"),
+                m.last_used_at.format("%Y-%m-%d")
+            ));
+        }
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Reranking counters since startup, if reranking is enabled
+fn rerank_stats(state: &AppState) -> String {
+    use std::sync::atomic::Ordering;
+
+    if !state.config.rag_rerank_enabled {
+        return String::new();
+    }
+
+    let metrics = &*crate::ai::RERANK_METRICS;
+    format!(
+        "\n\n<b>Reranking</b> (since start)\n\
+        Calls: {} | Failures: {}\n\
+        Avg. latency: {} ms\n\
+        Top result changed: {}",
+        metrics.calls.load(Ordering::Relaxed),
+        metrics.failures.load(Ordering::Relaxed),
+        metrics.avg_latency_ms(),
+        metrics.top_changed.load(Ordering::Relaxed)
+    )
+}
+
+/// Escape text for Telegram HTML parse mode
+pub(crate) fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+async fn handle_stop(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Parse account ID from message text
+    let text = msg.text().unwrap_or("");
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    
+    if parts.len() < 2 {
+        bot.send_message(msg.chat.id, "❌ Usage: /stop <account_id>")
+            .await?;
+        return Ok(());
+    }
+    
+    let account_id = match parts[1].parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /stop <id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if !state.is_userbot_running(account_id).await {
+        bot.send_message(msg.chat.id, format!("⚠️ Userbot {} is not running.", account_id))
+            .await?;
+        return Ok(());
+    }
+
+    state.shutdown_userbot(account_id).await?;
+    AccountRepository::set_active(&state.db_pool, account_id, false).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Userbot {} stopped successfully.", account_id),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_delete(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    // Parse account ID from message text
+    let text = msg.text().unwrap_or("");
+    let parts: Vec<&str> = text.split_whitespace().collect();
+    
+    if parts.len() < 2 {
+        bot.send_message(msg.chat.id, "❌ Usage: /delete <account_id>")
+            .await?;
+        return Ok(());
+    }
+    
+    let account_id = match parts[1].parse::<i64>() {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, "❌ Invalid account ID. Usage: /delete <id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Check if account exists
+    let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
+        Some(acc) => acc,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Stop if running
+    if state.is_userbot_running(account_id).await {
+        state.shutdown_userbot(account_id).await?;
+    }
+
+    // Delete from database
+    AccountRepository::delete(&state.db_pool, account_id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Account {} ({}) deleted successfully.",
+            account_id, account.phone_number
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Text of one /help page, under Telegram's 4096-character message limit
+const HELP_PAGE_CHARS: usize = 3500;
+
+/// All commands with their descriptions, split into pages between commands
+fn help_pages() -> Vec<String> {
+    let mut pages = vec![String::new()];
+    for command in Command::bot_commands() {
+        let line = format!("{} — {}\n", command.command, html_escape(&command.description));
+        if pages.last().map_or(0, |p| p.len()) + line.len() > HELP_PAGE_CHARS {
+            pages.push(String::new());
+        }
+        if let Some(page) = pages.last_mut() {
+            page.push_str(&line);
+        }
+    }
+    pages
+}
+
+/// Usage: /help [page]
+async fn handle_help(
+    bot: Bot,
+    msg: Message,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let pages = help_pages();
+    let page = args
+        .first()
+        .and_then(|a| a.parse::<usize>().ok())
+        .unwrap_or(1)
+        .clamp(1, pages.len());
+
+    let mut help_text = String::new();
+    if page == 1 {
+        help_text.push_str(
+            "<b>🤖 Puppeteer Admin Bot</b>\n\n\
+            Puppeteer manages multiple AI-driven Telegram userbots with human-like behavior. \
+            Each userbot can have its own personality (system prompt) and maintains conversation context.\n\
+            The command menu shows the everyday commands; every command is listed here.\n\n",
+        );
+    }
+    help_text.push_str(&format!("<b>Commands</b> — page {}/{}\n", page, pages.len()));
+    help_text.push_str(&pages[page - 1]);
+    if page < pages.len() {
+        help_text.push_str(&format!("\nNext: /help {}", page + 1));
+    }
+
+    bot.send_message(msg.chat.id, help_text)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+// ============================================================================
+// Persona Management Handlers
+// ============================================================================
+
+async fn handle_list_personas(
+    bot: Bot,
+    msg: Message,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let archetypes = crate::ai::list_archetypes();
+    
+    let mut response = String::from("🎭 <b>Available Personality Archetypes:</b>\n\n");
+    
+    for (idx, name) in archetypes.iter().enumerate() {
+        response.push_str(&format!("{}. <code>{}</code>\n", idx + 1, name));
+    }
+    
+    response.push_str("\n<b>Usage:</b>\n");
+    response.push_str("• <code>/random_persona &lt;account_id&gt;</code> - Assign random persona\n");
+    response.push_str("• <code>/set_persona &lt;account_id&gt; &lt;persona_name&gt;</code> - Assign specific persona\n\n");
+    response.push_str("<i>Each persona has unique communication style, emoji usage, and behavioral patterns.</i>");
+    
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+    
+    Ok(())
+}
+
+async fn handle_random_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if args.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "❌ Usage: /random_persona <account_id>\n\nExample: /random_persona 1",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let account_id: i64 = match args[0].parse() {
+        Ok(id) => id,
+        Err(_) => {
+            bot.send_message(msg.chat.id, "❌ Invalid account ID. Must be a number.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Check if account exists
+    let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await? {
+        Some(acc) => acc,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Generate random persona
+    let new_prompt = crate::ai::generate_random_persona();
+
+    // Update in database
+    AccountRepository::update_system_prompt(&state.db_pool, account_id, &new_prompt).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Random persona assigned to account {} ({})\n\n\
+            <i>Restart the userbot with /stop {} and then start it again for changes to take effect.</i>",
+            account_id, account.phone_number, account_id
+        ),
+    )
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
 
     Ok(())
 }
@@ -661,3 +2425,263 @@ async fn handle_set_persona(
 
     Ok(())
 }
+
+// ============================================================================
+// Stored Persona Handlers
+// ============================================================================
+
+async fn handle_create_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let text = msg.text().unwrap_or("");
+    let body = text.split_once(char::is_whitespace).map(|(_, rest)| rest).unwrap_or("").trim();
+
+    // "<name>|<prompt>", or just "<name>" to store one of the built-in archetypes.
+    // Custom prompts are linted; "<name>|!<prompt>" saves despite warnings.
+    let (name, prompt, lint) = match body.split_once('|') {
+        Some((name, prompt)) => {
+            let prompt = prompt.trim();
+            match prompt.strip_prefix(crate::ai::persona_lint::FORCE_PREFIX) {
+                Some(forced) => (name.trim().to_string(), Some(forced.trim().to_string()), false),
+                None => (name.trim().to_string(), Some(prompt.to_string()), true),
+            }
+        }
+        None => (body.to_string(), crate::ai::generate_persona_by_name(body), false),
+    };
+
+    let prompt = match prompt {
+        Some(p) if !name.is_empty() && !p.is_empty() => p,
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /create_persona <name>|<prompt>\n\n\
+                Or /create_persona <archetype> to store a built-in archetype (see /list_personas).",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    if lint {
+        let warnings = crate::ai::lint_prompt(&prompt);
+        if !warnings.is_empty() {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "⚠️ Persona not saved:\n\n{}\n\nFix the prompt, or use /create_persona {}|!<prompt> to save it anyway.",
+                    crate::ai::persona_lint::format_warnings(&warnings),
+                    name
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    }
+
+    let persona = PersonaRepository::upsert(&state.db_pool, NewPersona { name, prompt }).await?;
+    let bound = PersonaRepository::count_bound_accounts(&state.db_pool, persona.id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Persona <b>{}</b> saved (ID {}). Bound accounts: {}\n\n\
+            Use <code>/bind_persona &lt;account_id&gt; {}</code> to assign it.",
+            html_escape(&persona.name),
+            persona.id,
+            bound,
+            persona.id
+        ),
+    )
+    .parse_mode(teloxide::types::ParseMode::Html)
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_personas(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let personas = PersonaRepository::list_all(&state.db_pool).await?;
+
+    if personas.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "📋 No stored personas. Use /create_persona <name>|<prompt> to add one.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut response = String::from("🎭 <b>Stored Personas:</b>\n\n");
+
+    for persona in personas {
+        let bound = PersonaRepository::count_bound_accounts(&state.db_pool, persona.id).await?;
+        let tags = PersonaRepository::list_tags(&state.db_pool, persona.id).await?;
+        let tags_text = if tags.is_empty() {
+            String::new()
+        } else {
+            format!("\n   🏷 {}", html_escape(&tags.join(", ")))
+        };
+        response.push_str(&format!(
+            "🔹 <b>{}</b> (ID: {})\n   Accounts: {} | Prompt: {} chars{}\n\n",
+            html_escape(&persona.name),
+            persona.id,
+            bound,
+            persona.prompt.chars().count(),
+            tags_text
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(teloxide::types::ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+async fn handle_bind_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, persona_id) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(p)) => (a, p),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /bind_persona <account_id> <persona_id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Account {} not found.", account_id))
+            .await?;
+        return Ok(());
+    }
+
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found.", persona_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    PersonaRepository::bind_account(&state.db_pool, account_id, Some(persona_id)).await?;
+    crate::webhooks::emit(
+        &state,
+        crate::webhooks::Event::PersonaActivated,
+        serde_json::json!({ "account_id": account_id, "persona_id": persona_id, "persona": persona.name, "reason": "bind" }),
+    )
+    .await;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Account {} now uses persona '{}'", account_id, persona.name),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_unbind_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /unbind_persona <account_id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    PersonaRepository::bind_account(&state.db_pool, account_id, None).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Account {} unbound, using its own system prompt again", account_id),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn handle_delete_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /delete_persona <persona_id>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Persona {} not found.", persona_id))
+            .await?;
+        return Ok(());
+    }
+
+    let bound = PersonaRepository::count_bound_accounts(&state.db_pool, persona_id).await?;
+    PersonaRepository::delete(&state.db_pool, persona_id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Persona {} deleted. {} account(s) fell back to their own system prompt.",
+            persona_id, bound
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_fits_telegram_limits() {
+        let menu = menu_commands();
+        assert!(menu.len() <= MENU_LIMIT, "{} commands in the menu", menu.len());
+        // Telegram rejects the whole list if one description is over 256 characters
+        for command in &menu {
+            assert!(command.description.chars().count() <= 256, "{} has a long description", command.command);
+        }
+    }
+
+    #[test]
+    fn hidden_commands_exist() {
+        let names: Vec<String> = Command::bot_commands().into_iter().map(|c| c.command).collect();
+        for hidden in MENU_HIDDEN {
+            assert!(names.contains(&format!("/{}", hidden)), "unknown command {}", hidden);
+        }
+    }
+
+    #[test]
+    fn help_pages_fit_a_message() {
+        let pages = help_pages();
+        assert!(pages.len() > 1);
+        assert!(pages.iter().all(|p| p.len() <= HELP_PAGE_CHARS));
+        assert_eq!(pages.concat().lines().count(), Command::bot_commands().len());
+    }
+}
@@ -0,0 +1,404 @@
+use crate::{
+    ai::memory_editor::{self, HeatmapPeriod, MemoryChunk, MemoryFilter, MAX_IMPORTANCE, PAGE_SIZE},
+    bot::handlers::html_escape,
+    db::ChatSummaryRepository,
+    userbot::persona_switch,
+    AppState,
+};
+use teloxide::{prelude::*, types::ParseMode};
+
+/// Memories found by /memories search
+const SEARCH_LIMIT: usize = 10;
+
+/// Characters of a memory shown in a listing
+const PREVIEW_CHARS: usize = 200;
+
+/// Newest periods /memory_heatmap shows, to stay within one message
+const HEATMAP_ROWS: usize = 60;
+
+/// Summaries /summaries shows per message
+const SUMMARIES_PAGE: i64 = 5;
+
+fn chat_args(args: &[String]) -> Option<(i64, i64)> {
+    match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(a), Some(c)) => Some((a, c)),
+        _ => None,
+    }
+}
+
+fn chunk_line(chunk: &MemoryChunk, similarity: Option<f32>) -> String {
+    let mut preview: String = chunk.content.chars().take(PREVIEW_CHARS).collect();
+    if chunk.content.chars().count() > PREVIEW_CHARS {
+        preview.push('…');
+    }
+    let date = chrono::DateTime::from_timestamp(chunk.created_at, 0)
+        .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default();
+
+    let mut meta = vec![date];
+    if let Some(similarity) = similarity {
+        meta.push(format!("similarity {:.2}", similarity));
+    }
+    if (chunk.importance - 1.0).abs() > f64::EPSILON {
+        meta.push(format!("importance {}", chunk.importance));
+    }
+    match (chunk.is_bot_author, chunk.sender_id) {
+        (true, _) => meta.push("bot".to_string()),
+        (false, Some(id)) => meta.push(format!("user {}", id)),
+        (false, None) => {}
+    }
+    if let Some(kind) = &chunk.media_kind {
+        meta.push(kind.clone());
+    }
+
+    format!("<b>#{}</b> <i>{}</i>\n{}\n\n", chunk.id, meta.join(" · "), html_escape(&preview))
+}
+
+/// Page through a chat's episodic memories, newest first, or find the ones closest to a text
+/// Usage: /memories <account_id> <chat_id> [page|search <text>]
+pub async fn handle_memories(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (account_id, chat_id) = match chat_args(&args) {
+        Some(ids) => ids,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /memories <account_id> <chat_id> [page|search <text>]")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = String::new();
+    if args.get(2).map(|a| a.as_str()) == Some("search") {
+        let query = args[3..].join(" ");
+        if query.is_empty() {
+            bot.send_message(msg.chat.id, "❌ Usage: /memories <account_id> <chat_id> search <text>").await?;
+            return Ok(());
+        }
+        let found = memory_editor::search(&state, account_id, chat_id, &query, SEARCH_LIMIT).await?;
+        if found.is_empty() {
+            bot.send_message(msg.chat.id, format!("🧠 Chat {} has no memories", chat_id)).await?;
+            return Ok(());
+        }
+        text.push_str(&format!("🔎 <b>Memories closest to</b> \"{}\"\n\n", html_escape(&query)));
+        for (chunk, similarity) in &found {
+            text.push_str(&chunk_line(chunk, Some(*similarity)));
+        }
+    } else {
+        let page = args.get(2).and_then(|a| a.parse::<i64>().ok()).unwrap_or(1).max(1);
+        let (chunks, total) = memory_editor::page(&state.db_pool, account_id, chat_id, page - 1).await?;
+        if total == 0 {
+            bot.send_message(msg.chat.id, format!("🧠 Chat {} has no memories", chat_id)).await?;
+            return Ok(());
+        }
+        let pages = (total + PAGE_SIZE - 1) / PAGE_SIZE;
+        text.push_str(&format!(
+            "🧠 <b>Memories of chat {}</b> — page {}/{} ({} total)\n\n",
+            chat_id, page, pages, total
+        ));
+        for chunk in &chunks {
+            text.push_str(&chunk_line(chunk, None));
+        }
+        if page < pages {
+            text.push_str(&format!("Next: /memories {} {} {}\n", account_id, chat_id, page + 1));
+        }
+    }
+    text.push_str("/memory_edit &lt;id&gt; to fix one, /memory_delete &lt;id&gt; to remove it");
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Rewrite a memory (it is embedded again) or change how much retrieval weighs it
+/// Usage: /memory_edit <memory_id> <new text> | /memory_edit <memory_id> importance=<0-2>
+pub async fn handle_memory_edit(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /memory_edit <memory_id> <new text> or /memory_edit <memory_id> importance=<0-2>";
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) if args.len() >= 2 => id,
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    if memory_editor::get(&state.db_pool, id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Memory #{} not found", id)).await?;
+        return Ok(());
+    }
+
+    let text = match args[1].strip_prefix("importance=") {
+        Some(value) if args.len() == 2 => {
+            let importance = match value.parse::<f64>().ok().filter(|v| (0.0..=MAX_IMPORTANCE).contains(v)) {
+                Some(v) => v,
+                None => {
+                    bot.send_message(msg.chat.id, format!("❌ Importance must be between 0 and {}", MAX_IMPORTANCE))
+                        .await?;
+                    return Ok(());
+                }
+            };
+            memory_editor::set_importance(&state.db_pool, id, importance).await?;
+            format!("✅ Memory #{} now has importance {}", id, importance)
+        }
+        _ => {
+            memory_editor::edit_text(&state, id, &args[1..].join(" ")).await?;
+            format!("✅ Memory #{} rewritten and embedded again", id)
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Delete one memory
+/// Usage: /memory_delete <memory_id>
+pub async fn handle_memory_delete(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /memory_delete <memory_id> (see /memories)").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if memory_editor::delete(&state.db_pool, id).await? {
+        format!("🗑 Memory #{} deleted", id)
+    } else {
+        format!("❌ Memory #{} not found", id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Delete the memories of a chat matching a filter; without confirm only counts them
+/// Usage: /memory_delete_where <account_id> <chat_id> [user=<id>] [match=<text>] [older=<days>] [bots] [confirm]
+pub async fn handle_memory_delete_where(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /memory_delete_where <account_id> <chat_id> [user=<id>] [match=<text>] [older=<days>] [bots] [confirm]";
+    let (account_id, chat_id) = match chat_args(&args) {
+        Some(ids) => ids,
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let confirmed = args.last().map(|a| a.as_str()) == Some("confirm");
+    let filter_args: Vec<String> = args[2..].iter().filter(|a| a.as_str() != "confirm").cloned().collect();
+    let filter = match MemoryFilter::parse(&filter_args) {
+        Ok(filter) => filter,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {}\n\n{}", e, usage)).await?;
+            return Ok(());
+        }
+    };
+
+    let text = if confirmed {
+        let deleted = memory_editor::delete_matching(&state.db_pool, account_id, chat_id, &filter).await?;
+        format!("🗑 Deleted {} memories of chat {} {}", deleted, chat_id, html_escape(&filter.describe()))
+    } else {
+        let count = memory_editor::count_matching(&state.db_pool, account_id, chat_id, &filter).await?;
+        format!(
+            "⚠️ {} memories of chat {} {}.\nRepeat with <code>confirm</code> at the end to delete them.",
+            count,
+            chat_id,
+            html_escape(&filter.describe())
+        )
+    };
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Show, period by period, how many memories a chat has, how important they are and how often replies used them
+/// Usage: /memory_heatmap <account_id> <chat_id> [week|month]
+pub async fn handle_memory_heatmap(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /memory_heatmap <account_id> <chat_id> [week|month]";
+    let (account_id, chat_id) = match chat_args(&args) {
+        Some(ids) => ids,
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+    let period = match args.get(2) {
+        None => HeatmapPeriod::Month,
+        Some(arg) => match HeatmapPeriod::parse(arg) {
+            Some(period) => period,
+            None => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let buckets = memory_editor::heatmap(&state.db_pool, account_id, chat_id, period).await?;
+    if buckets.is_empty() {
+        bot.send_message(msg.chat.id, format!("🧠 Chat {} has no memories", chat_id)).await?;
+        return Ok(());
+    }
+
+    let buckets = &buckets[buckets.len().saturating_sub(HEATMAP_ROWS)..];
+    let max_chunks = buckets.iter().map(|b| b.chunks).max().unwrap_or(0);
+    let max_hits = buckets.iter().map(|b| b.hits).max().unwrap_or(0);
+    let mut table = format!("{:<9} {:>7} {:>5} {:>7}\n", "period", "chunks", "imp", "hits");
+    for bucket in buckets {
+        table.push_str(&format!(
+            "{:<9} {} {:>5} {:>5.2} {} {:>5}\n",
+            bucket.period,
+            memory_editor::heat(bucket.chunks, max_chunks),
+            bucket.chunks,
+            bucket.avg_importance,
+            memory_editor::heat(bucket.hits, max_hits),
+            bucket.hits,
+        ));
+    }
+
+    let text = format!(
+        "🧠 <b>Memory heatmap of chat {}</b>\n<pre>{}</pre>\nHits count how often a period's memories went into a reply. \
+         Light rows are periods the bot barely remembers.",
+        chat_id,
+        html_escape(&table)
+    );
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// The latest conversation summaries of a chat, with when they were made and which messages they cover
+/// Usage: /summaries <account_id> <chat_id> [before=<summary_id>]
+pub async fn handle_summaries(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /summaries <account_id> <chat_id> [before=<summary_id>]";
+    let (account_id, chat_id) = match chat_args(&args) {
+        Some(ids) => ids,
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+    let before = match args.get(2).map(|a| a.strip_prefix("before=").and_then(|id| id.parse::<i64>().ok())) {
+        None => None,
+        Some(Some(id)) => Some(id),
+        Some(None) => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let summaries = ChatSummaryRepository::list(&state.db_pool, account_id, chat_id, before, SUMMARIES_PAGE).await?;
+    if summaries.is_empty() {
+        bot.send_message(msg.chat.id, format!("📝 Chat {} has no summaries", chat_id)).await?;
+        return Ok(());
+    }
+
+    let mut text = format!("📝 <b>Summaries of chat {}</b>\n\n", chat_id);
+    for summary in &summaries {
+        let mut preview: String = summary.summary.chars().take(PREVIEW_CHARS).collect();
+        if summary.summary.chars().count() > PREVIEW_CHARS {
+            preview.push('…');
+        }
+        text.push_str(&format!(
+            "<b>#{}</b> <i>{} · {} · messages {}–{}</i>\n{}\n\n",
+            summary.id,
+            summary.created_at.format("%Y-%m-%d %H:%M"),
+            summary.kind,
+            summary.first_message_id,
+            summary.last_message_id,
+            html_escape(&preview)
+        ));
+    }
+    if summaries.len() as i64 == SUMMARIES_PAGE {
+        if let Some(last) = summaries.last() {
+            text.push_str(&format!("Older: /summaries {} {} before={}\n", account_id, chat_id, last.id));
+        }
+    }
+    text.push_str("/summary_regenerate &lt;id&gt; to summarize again, /summary_delete &lt;id&gt; to remove one");
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Summarize a summary's messages again
+/// Usage: /summary_regenerate <summary_id>
+pub async fn handle_summary_regenerate(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /summary_regenerate <summary_id> (see /summaries)").await?;
+            return Ok(());
+        }
+    };
+    let stored = match ChatSummaryRepository::get(&state.db_pool, id).await? {
+        Some(stored) => stored,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Summary #{} not found", id)).await?;
+            return Ok(());
+        }
+    };
+
+    let text = match persona_switch::regenerate_summary(&state, &stored).await {
+        Ok(summary) => format!("✅ Summary <b>#{}</b> regenerated:\n{}", id, html_escape(&summary)),
+        Err(e) => format!("❌ Could not regenerate summary #{}: {}", id, html_escape(&e.to_string())),
+    };
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Delete a summary; a chat still using it as context stops passing it on
+/// Usage: /summary_delete <summary_id>
+pub async fn handle_summary_delete(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /summary_delete <summary_id> (see /summaries)").await?;
+            return Ok(());
+        }
+    };
+
+    let text = match ChatSummaryRepository::get(&state.db_pool, id).await? {
+        Some(stored) => {
+            persona_switch::delete_summary(&state, &stored).await?;
+            format!("🗑 Summary #{} deleted", id)
+        }
+        None => format!("❌ Summary #{} not found", id),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
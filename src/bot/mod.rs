@@ -2,7 +2,17 @@ pub mod dialogues;
 pub mod handlers;
 pub mod middleware;
 pub mod group_commands;
+pub mod chat_commands;
+pub mod persona_commands;
+pub mod model_commands;
+pub mod rule_commands;
+pub mod memory_commands;
+pub mod bridge_commands;
+pub mod pin_commands;
+pub mod payment_commands;
+pub mod business_commands;
 pub mod callbacks;
+pub mod nl_router;
 
 use crate::AppState;
 use anyhow::Result;
@@ -20,10 +30,25 @@ pub async fn run_admin_bot(state: AppState) -> Result<()> {
 
     let bot = Bot::new(&state.config.bot_token);
 
-    // Create dialogue storage
+    // Publish the command menu (owners only)
+    if let Err(e) = handlers::register_commands(&bot, &state.config.owner_ids).await {
+        tracing::warn!("Failed to register bot commands: {}", e);
+    }
+
+    // Create dialogue storage and bring back wizards persisted before a restart
     let storage = InMemStorage::<AddAccountState>::new();
+    if let Err(e) = dialogues::restore_wizards(&bot, &state, storage.clone()).await {
+        tracing::error!("Failed to restore wizard sessions: {}", e);
+    }
+
+    // Expire forgotten wizards so they don't swallow unrelated messages
+    tokio::spawn(dialogues::wizard_expiry_worker(
+        bot.clone(),
+        state.clone(),
+        storage.clone(),
+    ));
 
-    // Build the dispatcher with owner filter and callback handler
+    // Build the dispatcher with owner filter, callback and payment handlers
     let handler = dptree::entry()
         .branch(
             Update::filter_callback_query()
@@ -35,16 +60,24 @@ pub async fn run_admin_bot(state: AppState) -> Result<()> {
                         .map(|id| state.config.is_owner(id as i64))
                         .unwrap_or(false)
                 })
+                .enter_dialogue::<CallbackQuery, InMemStorage<AddAccountState>, AddAccountState>()
                 .endpoint(callbacks::handle_callback),
         )
+        .branch(Update::filter_pre_checkout_query().endpoint(payment_commands::handle_pre_checkout))
         .branch(
             Update::filter_message()
+                // Stars payments come from regular users, not owners
+                .branch(
+                    dptree::filter(|msg: Message| msg.successful_payment().is_some())
+                        .endpoint(payment_commands::handle_successful_payment),
+                )
                 .branch(
                     dptree::filter(move |msg: Message, state: AppState| {
                         msg.from()
                             .map(|user| state.config.is_owner(user.id.0 as i64))
                             .unwrap_or(false)
                     })
+                    .enter_dialogue::<Message, InMemStorage<AddAccountState>, AddAccountState>()
                     .branch(
                         dptree::entry()
                             .filter_command::<handlers::Command>()
@@ -65,6 +98,32 @@ pub async fn run_admin_bot(state: AppState) -> Result<()> {
                     .branch(
                         dptree::case![AddAccountState::ReceivePrompt { account_id }]
                             .endpoint(dialogues::receive_prompt),
+                    )
+                    .branch(
+                        dptree::case![AddAccountState::EditPersonaName { persona_id }]
+                            .endpoint(dialogues::receive_persona_name),
+                    )
+                    .branch(
+                        dptree::case![AddAccountState::EditPersonaPrompt { persona_id }]
+                            .endpoint(dialogues::receive_persona_prompt),
+                    )
+                    .branch(
+                        dptree::case![AddAccountState::ConfirmPersonaPrompt { persona_id, prompt }]
+                            .endpoint(dialogues::receive_revised_persona_prompt),
+                    )
+                    .branch(
+                        dptree::case![AddAccountState::EditPersonaPostprocess { persona_id }]
+                            .endpoint(dialogues::receive_persona_postprocess),
+                    )
+                    // "бот, ..." requests, only outside of other wizards
+                    .branch(
+                        dptree::filter(|msg: Message, dialogue_state: AddAccountState| {
+                            matches!(
+                                dialogue_state,
+                                AddAccountState::Idle | AddAccountState::ConfirmAdminCommands { .. }
+                            ) && msg.text().and_then(nl_router::strip_prefix).is_some()
+                        })
+                        .endpoint(nl_router::handle_request),
                     ),
                 ),
         );
@@ -0,0 +1,201 @@
+use crate::{
+    ai::{
+        models::{configured_models, format_size, is_installed},
+        ollama::{OllamaClient, PullProgress},
+    },
+    bot::handlers::html_escape,
+    AppState,
+};
+use std::time::{Duration, Instant};
+use teloxide::{prelude::*, types::ParseMode};
+
+/// Telegram rate-limits edits; progress is refreshed at most this often
+const PROGRESS_EDIT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Installed Ollama models with their disk usage, or the details of one
+/// Usage: /ollama_models [name]
+pub async fn handle_ollama_models(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = OllamaClient::new(state.config.ollama_url.clone());
+
+    if let Some(name) = args.first() {
+        let info = match client.show(name).await {
+            Ok(info) => info,
+            Err(e) => {
+                bot.send_message(msg.chat.id, format!("❌ {}", e)).await?;
+                return Ok(());
+            }
+        };
+        let mut text = format!(
+            "🤖 <b>{}</b>\nFamily: {}\nParameters: {}\nQuantization: {}\nModified: {}\n",
+            html_escape(name),
+            html_escape(&info.details.family),
+            html_escape(&info.details.parameter_size),
+            html_escape(&info.details.quantization_level),
+            html_escape(&info.modified_at)
+        );
+        if !info.parameters.trim().is_empty() {
+            text.push_str(&format!("\n<b>Defaults</b>\n<pre>{}</pre>", html_escape(info.parameters.trim())));
+        }
+        bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+        return Ok(());
+    }
+
+    let mut installed = client.list_installed().await?;
+    installed.sort_by_key(|m| std::cmp::Reverse(m.size));
+    let in_use = configured_models(&state.config);
+
+    let mut text = String::from("🤖 <b>Installed models</b>\n\n");
+    for model in &installed {
+        let used = in_use.iter().any(|name| is_installed(std::slice::from_ref(&model.name), name));
+        text.push_str(&format!(
+            "{} <code>{}</code> — {} {}\n",
+            if used { "🟢" } else { "⚪️" },
+            html_escape(&model.name),
+            format_size(model.size),
+            html_escape(&model.details.parameter_size)
+        ));
+    }
+    let total: u64 = installed.iter().map(|m| m.size).sum();
+    text.push_str(&format!(
+        "\nDisk usage: {} in {} models (🟢 in use)\n\
+        /pull_model &lt;name&gt; to add one, /delete_model &lt;name&gt; to free space",
+        format_size(total),
+        installed.len()
+    ));
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+fn progress_text(name: &str, progress: &PullProgress) -> String {
+    match (progress.percent(), progress.completed, progress.total) {
+        (Some(percent), Some(completed), Some(total)) => format!(
+            "⏳ Pulling {}: {}\n{}% ({} / {})",
+            name,
+            progress.status,
+            percent,
+            format_size(completed),
+            format_size(total)
+        ),
+        _ => format!("⏳ Pulling {}: {}", name, progress.status),
+    }
+}
+
+/// Follow a pull to the end, editing the progress message as it goes
+async fn follow_pull(bot: Bot, state: AppState, chat_id: ChatId, message_id: teloxide::types::MessageId, name: String) {
+    let client = OllamaClient::new(state.config.ollama_url.clone());
+    let mut last_edit = Instant::now();
+    let mut last_status = String::new();
+
+    let result = async {
+        let mut stream = client.pull(&name).await?;
+        while let Some(progress) = stream.next().await? {
+            if progress.status != last_status || last_edit.elapsed() >= PROGRESS_EDIT_INTERVAL {
+                last_status = progress.status.clone();
+                last_edit = Instant::now();
+                if let Err(e) = bot.edit_message_text(chat_id, message_id, progress_text(&name, &progress)).await {
+                    tracing::debug!("Failed to update pull progress: {}", e);
+                }
+            }
+        }
+        client.list_installed().await
+    }
+    .await;
+
+    let text = match result {
+        Ok(installed) => match installed.iter().find(|m| is_installed(std::slice::from_ref(&m.name), &name)) {
+            Some(model) => format!("✅ Pulled {} ({})", model.name, format_size(model.size)),
+            None => format!("✅ Pulled {}", name),
+        },
+        Err(e) => format!("❌ Failed to pull {}: {}", name, e),
+    };
+    if let Err(e) = bot.edit_message_text(chat_id, message_id, text).await {
+        tracing::warn!("Failed to report pull of {}: {}", name, e);
+    }
+}
+
+/// Download a model into Ollama, with progress in an edited message
+/// Usage: /pull_model <name>
+pub async fn handle_pull_model(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = match args.first() {
+        Some(name) => name.clone(),
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /pull_model <name> (e.g. /pull_model qwen2.5:7b)")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let progress = bot.send_message(msg.chat.id, format!("⏳ Pulling {}…", name)).await?;
+
+    // Pulls take minutes; the admin chat stays usable meanwhile
+    tokio::spawn(follow_pull(bot, state, msg.chat.id, progress.id, name));
+    Ok(())
+}
+
+/// Remove a model from Ollama after a confirmation
+/// Usage: /delete_model <name> [confirm]
+pub async fn handle_delete_model(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let name = match args.first() {
+        Some(name) => name,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /delete_model <name> [confirm]").await?;
+            return Ok(());
+        }
+    };
+
+    let client = OllamaClient::new(state.config.ollama_url.clone());
+    let installed = client.list_installed().await?;
+    let model = match installed.iter().find(|m| is_installed(std::slice::from_ref(&m.name), name)) {
+        Some(model) => model,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ {} is not installed", name)).await?;
+            return Ok(());
+        }
+    };
+
+    if configured_models(&state.config).iter().any(|m| is_installed(std::slice::from_ref(&model.name), m)) {
+        bot.send_message(
+            msg.chat.id,
+            format!("❌ {} is configured in .env; point the config at another model first", model.name),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    if args.get(1).map(|a| a.as_str()) != Some("confirm") {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "⚠️ This deletes {} and frees {}.\n\nSend /delete_model {} confirm to proceed.",
+                model.name,
+                format_size(model.size),
+                name
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let text = match client.delete(&model.name).await {
+        Ok(()) => format!("🗑 Deleted {}, freed {}", model.name, format_size(model.size)),
+        Err(e) => format!("❌ {}", e),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
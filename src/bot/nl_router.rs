@@ -0,0 +1,237 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    bot::{dialogues, handlers::{self, html_escape, Command}, AddAccountDialogue, AddAccountState},
+    db::{AccountRepository, PersonaRepository},
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, MediaKind, MediaText, MessageKind, ParseMode, User},
+    utils::command::BotCommands,
+};
+
+/// Owner messages starting with one of these are routed through the model
+const PREFIXES: &[&str] = &["бот,", "bot,"];
+
+/// Commands a single request may plan
+const MAX_COMMANDS: usize = 5;
+
+/// Commands that need a reply, a file or a wizard and can't run from a plan
+const NOT_ROUTABLE: &[&str] = &["/add_account", "/set_prompt", "/import_memory"];
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Plan {
+    commands: Vec<String>,
+    reply: String,
+}
+
+/// The request text after the "бот," prefix, if the message has one
+pub fn strip_prefix(text: &str) -> Option<&str> {
+    let trimmed = text.trim_start();
+    PREFIXES
+        .iter()
+        .find_map(|prefix| {
+            let head = trimmed.get(..prefix.len())?;
+            (head.to_lowercase() == *prefix).then(|| trimmed[prefix.len()..].trim())
+        })
+        .filter(|request| !request.is_empty())
+}
+
+/// Keep planned commands that exist and can run unattended, explaining the rest
+fn validate(commands: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut valid = Vec::new();
+    let mut rejected = Vec::new();
+
+    for command in commands.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+        let name = command.split_whitespace().next().unwrap_or("");
+        if NOT_ROUTABLE.contains(&name) {
+            rejected.push(format!("{} (run it by hand)", command));
+        } else if Command::parse(command, "").is_err() {
+            rejected.push(format!("{} (unknown command)", command));
+        } else if valid.len() >= MAX_COMMANDS {
+            rejected.push(format!("{} (more than {} commands)", command, MAX_COMMANDS));
+        } else {
+            valid.push(command.to_string());
+        }
+    }
+
+    (valid, rejected)
+}
+
+/// Ask the model which admin commands fulfil the request
+async fn plan(state: &AppState, request: &str) -> Result<Plan> {
+    let accounts = AccountRepository::list_all(&state.db_pool)
+        .await?
+        .iter()
+        .map(|a| format!("- account {}: {}", a.id, a.phone_number))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let personas = PersonaRepository::list_all(&state.db_pool)
+        .await?
+        .iter()
+        .map(|p| format!("- persona {}: {}", p.id, p.name))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let prompt = format!(
+        r#"You translate the owner's request into admin commands of a Telegram bot.
+
+Available commands:
+{}
+
+Accounts:
+{}
+
+Personas:
+{}
+
+Request: {}
+
+Use only the commands above, with their documented arguments. If the request can't be done with them, return no commands and say why.
+Return JSON: {{"commands": ["/command arg ..."], "reply": "<one sentence in the language of the request: what will happen, or why it can't be done>"}}"#,
+        Command::descriptions(),
+        if accounts.is_empty() { "(none)".to_string() } else { accounts },
+        if personas.is_empty() { "(none)".to_string() } else { personas },
+        request
+    );
+
+    OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.1), num_predict: None }),
+        )
+        .await
+        .context("Failed to plan commands")
+}
+
+/// Plan commands for a "бот, ..." request and ask the owner to confirm them
+pub async fn handle_request(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    dialogue: AddAccountDialogue,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let request = match msg.text().and_then(strip_prefix) {
+        Some(request) => request.to_string(),
+        None => return Ok(()),
+    };
+
+    if state.is_paused() {
+        bot.send_message(msg.chat.id, "⏸ The chat model is paused as unhealthy, use commands directly.").await?;
+        return Ok(());
+    }
+
+    let plan = match plan(&state, &request).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            tracing::warn!("Failed to plan admin commands: {}", e);
+            bot.send_message(msg.chat.id, "❌ Couldn't understand the request, try rephrasing or use /help").await?;
+            return Ok(());
+        }
+    };
+
+    let (commands, rejected) = validate(&plan.commands);
+    let mut text = format!("🤖 {}", html_escape(plan.reply.trim()));
+    if !rejected.is_empty() {
+        text.push_str("\n\n<b>Skipped:</b>");
+        for command in &rejected {
+            text.push_str(&format!("\n• <code>{}</code>", html_escape(command)));
+        }
+    }
+
+    if commands.is_empty() {
+        // Nothing to confirm; drop an older pending plan too
+        if matches!(dialogue.get().await?, Some(AddAccountState::ConfirmAdminCommands { .. })) {
+            dialogues::exit_wizard(&dialogue, &state).await?;
+        }
+        bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+        return Ok(());
+    }
+
+    text.push_str("\n\n<b>Will run:</b>");
+    for command in &commands {
+        text.push_str(&format!("\n• <code>{}</code>", html_escape(command)));
+    }
+
+    let user_id = msg.from.as_ref().map(|u| u.id.0 as i64);
+    if dialogues::refuse_if_busy(&bot, &state, msg.chat.id, user_id).await? {
+        return Ok(());
+    }
+    dialogues::enter_wizard(&dialogue, &state, user_id, AddAccountState::ConfirmAdminCommands { commands }).await?;
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .reply_markup(InlineKeyboardMarkup::new(vec![vec![
+            InlineKeyboardButton::callback("✅ Confirm", "nlr:ok"),
+            InlineKeyboardButton::callback("❌ Cancel", "nlr:no"),
+        ]]))
+        .await?;
+
+    Ok(())
+}
+
+/// A copy of a message with different text, sent by the owner who confirmed the plan,
+/// to feed a planned command to the regular handler
+fn with_text(msg: &Message, from: &User, text: &str) -> Message {
+    let mut msg = msg.clone();
+    msg.from = Some(from.clone());
+    if let MessageKind::Common(common) = &mut msg.kind {
+        common.media_kind = MediaKind::Text(MediaText {
+            text: text.to_string(),
+            entities: Vec::new(),
+            link_preview_options: None,
+        });
+    }
+    msg
+}
+
+/// Run (nlr:ok) or drop (nlr:no) the pending plan
+pub async fn handle_confirm_callback(
+    bot: &Bot,
+    q: &CallbackQuery,
+    state: &AppState,
+    dialogue: &AddAccountDialogue,
+    parts: Vec<&str>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let message = match q.message.as_ref().and_then(|m| m.regular_message()) {
+        Some(message) => message.clone(),
+        None => return Ok(()),
+    };
+
+    let commands = match dialogue.get().await? {
+        Some(AddAccountState::ConfirmAdminCommands { commands }) => commands,
+        _ => {
+            bot.edit_message_text(message.chat.id, message.id, "⌛ This request is no longer pending.").await?;
+            return Ok(());
+        }
+    };
+    dialogues::exit_wizard(dialogue, state).await?;
+
+    if parts.get(1) != Some(&"ok") {
+        bot.edit_message_text(message.chat.id, message.id, "❌ Cancelled, nothing was changed.").await?;
+        return Ok(());
+    }
+
+    bot.edit_message_reply_markup(message.chat.id, message.id).await?;
+
+    for command in commands {
+        let cmd = match Command::parse(&command, "") {
+            Ok(cmd) => cmd,
+            Err(_) => continue,
+        };
+        tracing::info!("Running planned admin command: {}", command);
+        if let Err(e) =
+            handlers::handle_command(bot.clone(), with_text(&message, &q.from, &command), cmd, state.clone(), dialogue.clone()).await
+        {
+            bot.send_message(message.chat.id, format!("❌ {} failed: {}. Remaining commands skipped.", command, e))
+                .await?;
+            break;
+        }
+    }
+
+    Ok(())
+}
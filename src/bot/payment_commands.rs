@@ -0,0 +1,263 @@
+use crate::{
+    bot::handlers::html_escape,
+    db::PaymentRepository,
+    payments::{self, Feature},
+    AppState,
+};
+use teloxide::{
+    prelude::*,
+    types::{LabeledPrice, ParseMode},
+};
+
+/// Telegram Stars currency code; Stars invoices need no payment provider
+const STARS_CURRENCY: &str = "XTR";
+
+fn feature_list() -> String {
+    Feature::ALL.iter().map(|f| f.as_str()).collect::<Vec<_>>().join("|")
+}
+
+/// Create a Stars invoice link for a feature that can be shared with users
+/// Usage: /invoice_link <feature> <stars> [days]
+pub async fn handle_invoice_link(
+    bot: Bot,
+    msg: Message,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parsed = (
+        args.first().and_then(|a| Feature::parse(a)),
+        args.get(1).and_then(|a| a.parse::<u32>().ok()).filter(|s| *s > 0),
+        args.get(2).map(|a| a.parse::<i64>().ok().filter(|d| *d > 0)),
+    );
+
+    let (feature, stars, days) = match parsed {
+        (Some(f), Some(s), None) => (f, s, None),
+        (Some(f), Some(s), Some(Some(d))) => (f, s, Some(d)),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                format!("❌ Usage: /invoice_link <{}> <stars> [days]", feature_list()),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let description = match days {
+        Some(d) => format!("{} for {} days", feature.title(), d),
+        None => format!("{}, lifetime access", feature.title()),
+    };
+
+    let link = bot
+        .create_invoice_link(
+            feature.title(),
+            description,
+            payments::invoice_payload(feature, days),
+            "",
+            STARS_CURRENCY,
+            vec![LabeledPrice::new(feature.title(), stars)],
+        )
+        .await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("💫 Invoice for <b>{}</b> ({} ⭐):\n{}", feature.title(), stars, html_escape(&link)),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+
+    Ok(())
+}
+
+/// Grant a feature to a user manually
+/// Usage: /grant <user_id> <feature> [days]
+pub async fn handle_grant(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let parsed = (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| Feature::parse(a)),
+        args.get(2).map(|a| a.parse::<i64>().ok().filter(|d| *d > 0)),
+    );
+
+    let (user_id, feature, days) = match parsed {
+        (Some(u), Some(f), None) => (u, f, None),
+        (Some(u), Some(f), Some(Some(d))) => (u, f, Some(d)),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                format!("❌ Usage: /grant <user_id> <{}> [days]", feature_list()),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    PaymentRepository::grant(&state.db_pool, user_id, feature.as_str(), days, "grant").await?;
+
+    let duration = days.map(|d| format!("{} days", d)).unwrap_or_else(|| "lifetime".to_string());
+    let mut text = format!("✅ Granted {} to user {} ({})", feature.as_str(), user_id, duration);
+    if !payments::is_gated(&state, feature) {
+        text.push_str("\nℹ️ This feature is not gated (PREMIUM_FEATURES), everyone can use it.");
+    }
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Revoke a feature from a user
+/// Usage: /revoke <user_id> <feature>
+pub async fn handle_revoke(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (user_id, feature) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| Feature::parse(a)),
+    ) {
+        (Some(u), Some(f)) => (u, f),
+        _ => {
+            bot.send_message(msg.chat.id, format!("❌ Usage: /revoke <user_id> <{}>", feature_list()))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = if PaymentRepository::revoke(&state.db_pool, user_id, feature.as_str()).await? {
+        format!("✅ Revoked {} from user {}", feature.as_str(), user_id)
+    } else {
+        format!("ℹ️ User {} has no {} entitlement", user_id, feature.as_str())
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Show a user's entitlements, or payment totals without arguments
+/// Usage: /entitlements [user_id]
+pub async fn handle_entitlements(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let user_id = match args.first().map(|a| a.parse::<i64>()) {
+        Some(Ok(id)) => id,
+        Some(Err(_)) => {
+            bot.send_message(msg.chat.id, "❌ Usage: /entitlements [user_id]").await?;
+            return Ok(());
+        }
+        None => {
+            let (stars, count) = PaymentRepository::totals(&state.db_pool).await?;
+            let gated = if state.config.premium_features.is_empty() {
+                "none".to_string()
+            } else {
+                state.config.premium_features.iter().map(|f| f.as_str()).collect::<Vec<_>>().join(", ")
+            };
+            bot.send_message(
+                msg.chat.id,
+                format!("💫 {} payments, {} ⭐ total\nGated features: {}", count, stars, gated),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let entitlements = PaymentRepository::list_for_user(&state.db_pool, user_id).await?;
+    if entitlements.is_empty() {
+        bot.send_message(msg.chat.id, format!("ℹ️ User {} has no entitlements", user_id))
+            .await?;
+        return Ok(());
+    }
+
+    let mut response = format!("💫 <b>Entitlements of user {}</b>\n\n", user_id);
+    for e in entitlements {
+        let until = match e.expires_at {
+            Some(at) if e.is_active() => format!("until {}", at.format("%Y-%m-%d")),
+            Some(at) => format!("expired {}", at.format("%Y-%m-%d")),
+            None => "lifetime".to_string(),
+        };
+        response.push_str(&format!("• {} — {} ({})\n", e.feature, until, e.source));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Approve checkouts for invoices we issued
+pub async fn handle_pre_checkout(
+    bot: Bot,
+    query: PreCheckoutQuery,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if payments::parse_payload(&query.invoice_payload).is_some() {
+        bot.answer_pre_checkout_query(query.id, true).await?;
+    } else {
+        bot.answer_pre_checkout_query(query.id, false)
+            .error_message("This invoice is no longer valid.")
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Record a completed Stars payment and unlock the feature
+pub async fn handle_successful_payment(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (payment, user) = match (msg.successful_payment(), msg.from.as_ref()) {
+        (Some(p), Some(u)) => (p, u),
+        _ => return Ok(()),
+    };
+
+    let (feature, days) = match payments::parse_payload(&payment.invoice_payload) {
+        Some(parsed) => parsed,
+        None => {
+            tracing::warn!("Payment with unknown payload '{}'", payment.invoice_payload);
+            return Ok(());
+        }
+    };
+
+    // The charge and its entitlement are stored together, so a redelivered payment is never left ungranted
+    let user_id = user.id.0 as i64;
+    let is_new = PaymentRepository::record_payment(
+        &state.db_pool,
+        user_id,
+        feature.as_str(),
+        payment.total_amount as i64,
+        days,
+        &payment.telegram_payment_charge_id,
+    )
+    .await?;
+
+    if !is_new {
+        return Ok(());
+    }
+
+    bot.send_message(msg.chat.id, format!("✨ Thank you! {} is now unlocked.", feature.title()))
+        .await?;
+
+    for owner_id in &state.config.owner_ids {
+        let _ = bot
+            .send_message(
+                ChatId(*owner_id),
+                format!(
+                    "💫 User {} paid {} ⭐ for {}",
+                    user_id,
+                    payment.total_amount,
+                    feature.as_str()
+                ),
+            )
+            .await;
+    }
+
+    Ok(())
+}
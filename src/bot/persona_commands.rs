@@ -0,0 +1,1341 @@
+use crate::{
+    ai::vision_prompts::MediaType,
+    bot::handlers::html_escape,
+    db::{ExampleRepository, MessageRepository, PersonaRepository, VisionPromptRepository},
+    AppState,
+};
+use anyhow::Result;
+use teloxide::{
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, InputFile, ParseMode},
+};
+
+/// Parse "a, b,c" into lowercase tags
+fn parse_tags(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Tag a persona
+/// Usage: /tag_persona <persona_id> <tag1,tag2,...>
+pub async fn handle_tag_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = args.first().and_then(|a| a.parse::<i64>().ok());
+    let tags = parse_tags(&args.iter().skip(1).cloned().collect::<Vec<_>>().join(" "));
+
+    let persona_id = match persona_id {
+        Some(id) if !tags.is_empty() => id,
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /tag_persona <persona_id> <tag1,tag2,...>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+        bot.send_message(msg.chat.id, format!("❌ Persona {} not found.", persona_id))
+            .await?;
+        return Ok(());
+    }
+
+    PersonaRepository::add_tags(&state.db_pool, persona_id, &tags).await?;
+    let all_tags = PersonaRepository::list_tags(&state.db_pool, persona_id).await?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("🏷 Persona {} tags: {}", persona_id, all_tags.join(", ")),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Remove a tag from a persona
+/// Usage: /untag_persona <persona_id> <tag>
+pub async fn handle_untag_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (persona_id, tag) = match (args.first().and_then(|a| a.parse::<i64>().ok()), args.get(1)) {
+        (Some(id), Some(tag)) => (id, tag),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /untag_persona <persona_id> <tag>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    PersonaRepository::remove_tag(&state.db_pool, persona_id, tag).await?;
+
+    bot.send_message(msg.chat.id, format!("✅ Removed tag '{}' from persona {}", tag, persona_id))
+        .await?;
+
+    Ok(())
+}
+
+/// List personas carrying a tag, or all tags without an argument
+/// Usage: /personas_by_tag [tag]
+pub async fn handle_personas_by_tag(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let tag = match args.first() {
+        Some(tag) => tag,
+        None => {
+            let tags = PersonaRepository::list_all_tags(&state.db_pool).await?;
+            if tags.is_empty() {
+                bot.send_message(msg.chat.id, "🏷 No tags yet. Use /tag_persona <persona_id> <tags>")
+                    .await?;
+                return Ok(());
+            }
+
+            let mut response = String::from("🏷 <b>Persona tags:</b>\n\n");
+            for (tag, count) in tags {
+                response.push_str(&format!("• <code>{}</code> — {} personas\n", html_escape(&tag), count));
+            }
+
+            bot.send_message(msg.chat.id, response)
+                .parse_mode(ParseMode::Html)
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let personas = PersonaRepository::list_by_tag(&state.db_pool, tag).await?;
+
+    if personas.is_empty() {
+        bot.send_message(msg.chat.id, format!("🏷 No personas tagged '{}'", tag))
+            .await?;
+        return Ok(());
+    }
+
+    let mut response = format!("🏷 <b>Personas tagged {}:</b>\n\n", html_escape(tag));
+    for persona in personas {
+        let bound = PersonaRepository::count_bound_accounts(&state.db_pool, persona.id).await?;
+        response.push_str(&format!(
+            "🔹 <b>{}</b> (ID: {}) — {} accounts\n",
+            html_escape(&persona.name),
+            persona.id,
+            bound
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Export every persona of a tag as a JSON file
+/// Usage: /export_tag <tag>
+pub async fn handle_export_tag(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let tag = match args.first() {
+        Some(tag) => tag.to_lowercase(),
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /export_tag <tag>").await?;
+            return Ok(());
+        }
+    };
+
+    let personas = PersonaRepository::list_by_tag(&state.db_pool, &tag).await?;
+
+    if personas.is_empty() {
+        bot.send_message(msg.chat.id, format!("🏷 No personas tagged '{}'", tag))
+            .await?;
+        return Ok(());
+    }
+
+    let mut export = Vec::with_capacity(personas.len());
+    for persona in &personas {
+        let tags = PersonaRepository::list_tags(&state.db_pool, persona.id).await?;
+        export.push(serde_json::json!({
+            "name": persona.name,
+            "prompt": persona.prompt,
+            "tags": tags,
+        }));
+    }
+
+    let json = serde_json::to_vec_pretty(&export)?;
+
+    bot.send_document(
+        msg.chat.id,
+        InputFile::memory(json).file_name(format!("personas-{}.json", tag)),
+    )
+    .caption(format!("📦 {} personas tagged '{}'", personas.len(), tag))
+    .await?;
+
+    Ok(())
+}
+
+/// Export the personas edited since the last run, for a git-backed persona repo:
+/// into PERSONAS_DIR when it is set, as documents otherwise
+/// Usage: /export_changed_personas
+pub async fn handle_export_changed_personas(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let changed = crate::ai::persona_files::changed_personas(&state.db_pool).await?;
+    if changed.is_empty() {
+        bot.send_message(msg.chat.id, "📦 No persona changed since the last export").await?;
+        return Ok(());
+    }
+
+    let mut lines = Vec::new();
+    for persona in &changed {
+        let json = serde_json::to_string_pretty(&persona.file)? + "\n";
+        let file_name = format!("{}.json", persona.file.name);
+        match state.config.personas_dir.as_deref() {
+            Some(dir) => {
+                let path = std::path::Path::new(dir).join(&file_name);
+                if let Err(e) = tokio::fs::write(&path, json).await {
+                    lines.push(format!("❌ {}: {}", html_escape(&persona.file.name), html_escape(&e.to_string())));
+                    continue;
+                }
+            }
+            None => {
+                bot.send_document(msg.chat.id, InputFile::memory(json.into_bytes()).file_name(file_name)).await?;
+            }
+        }
+        crate::ai::persona_files::mark_exported(&state.db_pool, persona).await?;
+        lines.push(format!("• {} → v{}", html_escape(&persona.file.name), persona.file.version));
+    }
+
+    let destination = match state.config.personas_dir.as_deref() {
+        Some(dir) => format!(" to <code>{}</code>", html_escape(dir)),
+        None => String::new(),
+    };
+    bot.send_message(
+        msg.chat.id,
+        format!("📦 <b>Exported {} changed personas{}</b>\n\n{}", changed.len(), destination, lines.join("\n")),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Import persona files, keeping stored personas that have a newer version
+/// Usage: /import_personas in reply to a persona JSON file (or a list of them), or alone to re-read PERSONAS_DIR
+pub async fn handle_import_personas(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::persona_files;
+    use teloxide::net::Download;
+
+    let result = match (msg.reply_to_message().and_then(|m| m.document()), state.config.personas_dir.as_deref()) {
+        (Some(document), _) => {
+            let file = bot.get_file(&document.file.id).await?;
+            let mut bytes = Vec::new();
+            bot.download_file(&file.path, &mut bytes).await?;
+            let file_name = document.file_name.clone().unwrap_or_else(|| "document".to_string());
+            persona_files::import_document(&state.db_pool, &file_name, &String::from_utf8_lossy(&bytes)).await
+        }
+        (None, Some(dir)) => persona_files::import_dir(&state.db_pool, std::path::Path::new(dir)).await,
+        (None, None) => {
+            bot.send_message(
+                msg.chat.id,
+                "❌ Usage: /import_personas in reply to a persona JSON file, or alone when PERSONAS_DIR is set",
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ Import failed: {}", e)).await?;
+            return Ok(());
+        }
+    };
+
+    let mut text = format!("📥 <b>Persona import</b>: {}", html_escape(&report.summary()));
+    let list = |names: &[String]| names.iter().map(|n| html_escape(n)).collect::<Vec<_>>().join(", ");
+    if !report.created.is_empty() {
+        text.push_str(&format!("\n\n🆕 Created: {}", list(&report.created)));
+    }
+    if !report.updated.is_empty() {
+        text.push_str(&format!("\n\n🔄 Updated: {}", list(&report.updated)));
+    }
+    if !report.older.is_empty() {
+        text.push_str(&format!("\n\n⏭ Kept newer stored versions: {}", list(&report.older)));
+    }
+    for (file, error) in &report.failed {
+        text.push_str(&format!("\n\n❌ {}: {}", html_escape(file), html_escape(error)));
+    }
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Delete every persona carrying a tag
+/// Usage: /delete_tag <tag> confirm
+pub async fn handle_delete_tag(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let tag = match args.first() {
+        Some(tag) => tag,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /delete_tag <tag> confirm").await?;
+            return Ok(());
+        }
+    };
+
+    if args.get(1).map(|a| a.as_str()) != Some("confirm") {
+        let count = PersonaRepository::list_by_tag(&state.db_pool, tag).await?.len();
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "⚠️ This deletes {} personas tagged '{}'. Bound accounts fall back to their own prompt.\n\n\
+                Send /delete_tag {} confirm to proceed.",
+                count, tag, tag
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let deleted = PersonaRepository::delete_by_tag(&state.db_pool, tag).await?;
+
+    bot.send_message(msg.chat.id, format!("🗑 Deleted {} personas tagged '{}'", deleted, tag))
+        .await?;
+
+    Ok(())
+}
+
+/// Turn nightly random rotation among a tag's personas on/off, or rotate now
+/// Usage: /rotate_tag <tag> <on|off|now>
+pub async fn handle_rotate_tag(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (tag, action) = match (args.first(), args.get(1).map(|a| a.as_str())) {
+        (Some(tag), Some(action @ ("on" | "off" | "now"))) => (tag, action),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /rotate_tag <tag> <on|off|now>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let text = match action {
+        "on" => {
+            PersonaRepository::set_tag_rotation(&state.db_pool, tag, true).await?;
+            format!(
+                "🔄 Accounts using a '{}' persona will get a random '{}' persona every night",
+                tag, tag
+            )
+        }
+        "off" => {
+            PersonaRepository::set_tag_rotation(&state.db_pool, tag, false).await?;
+            format!("⏸ Rotation for tag '{}' disabled", tag)
+        }
+        _ => {
+            let rotated = crate::userbot::rotation::rotate_tag(&state, tag).await?;
+            format!("🔄 Rotated {} accounts within tag '{}'", rotated, tag)
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Set a persona's weight in per-chat rotation
+/// Usage: /persona_weight <persona_id> <weight>
+pub async fn handle_persona_weight(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let (persona_id, weight) = match (
+        args.first().and_then(|a| a.parse::<i64>().ok()),
+        args.get(1).and_then(|a| a.parse::<i64>().ok()),
+    ) {
+        (Some(p), Some(w)) if w >= 0 => (p, w),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /persona_weight <persona_id> <weight ≥ 0>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    PersonaRepository::set_rotation_weight(&state.db_pool, persona_id, weight).await?;
+
+    let text = if weight == 0 {
+        format!("✅ Persona {} excluded from rotation", persona_id)
+    } else {
+        format!("✅ Persona {} rotation weight set to {}", persona_id, weight)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+
+    Ok(())
+}
+
+/// Show which personas produced an account's replies
+/// Usage: /persona_stats <account_id>
+pub async fn handle_persona_stats(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /persona_stats <account_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let stats = MessageRepository::persona_reply_stats(&state.db_pool, account_id).await?;
+
+    if stats.is_empty() {
+        bot.send_message(msg.chat.id, format!("📊 Account {} has no replies yet", account_id))
+            .await?;
+        return Ok(());
+    }
+
+    let total: i64 = stats.iter().map(|s| s.replies).sum();
+    let mut response = format!("📊 <b>Replies by persona, account {}</b>\n\n", account_id);
+
+    for stat in stats {
+        let name = match (stat.persona_id, stat.persona_name) {
+            (_, Some(name)) => html_escape(&name),
+            (Some(id), None) => format!("deleted persona {}", id),
+            (None, None) => "own prompt".to_string(),
+        };
+        response.push_str(&format!(
+            "• {} — {} ({:.0}%)\n",
+            name,
+            stat.replies,
+            stat.replies as f64 * 100.0 / total as f64
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Style of a persona's replies day by day, and whether it drifted from its baseline
+/// Usage: /persona_style <persona_id> [days]
+pub async fn handle_persona_style(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::{health::sparkline, style::{self, StyleMetrics}};
+
+    let persona_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /persona_style <persona_id> [days]").await?;
+            return Ok(());
+        }
+    };
+    let days = args.get(1).and_then(|a| a.parse::<i64>().ok()).unwrap_or(14).clamp(2, 60);
+
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    };
+
+    let replies = MessageRepository::persona_replies(&state.db_pool, persona_id, days, 0, 5000).await?;
+    if replies.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            format!("📊 {} wrote no replies in the last {} days", persona.name, days),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Oldest day first
+    let today = chrono::Utc::now().date_naive();
+    let daily: Vec<StyleMetrics> = (0..days)
+        .rev()
+        .map(|ago| {
+            let day = today - chrono::Duration::days(ago);
+            StyleMetrics::of(
+                replies
+                    .iter()
+                    .filter(|(_, at)| at.date_naive() == day)
+                    .map(|(text, _)| text.as_str()),
+            )
+        })
+        .collect();
+    let chart = |value: fn(&StyleMetrics) -> f64| {
+        let values: Vec<Option<f64>> =
+            daily.iter().map(|m| if m.replies == 0 { None } else { Some(value(m)) }).collect();
+        sparkline(&values)
+    };
+
+    let (baseline, recent) = style::compare(&state, persona_id).await?;
+    let drift = style::drift(&baseline, &recent);
+
+    let mut text = format!(
+        "🎭 <b>Style of {}</b>, last {} days (oldest → newest)
+
+        Length   <code>{}</code>
+        Emoji    <code>{}</code>
+        Formal   <code>{}</code>
+        Cyrillic <code>{}</code>
+
+        <b>Baseline</b> ({}–{} days ago): {}
+        <b>Recent</b> (last {} days): {}
+
+",
+        html_escape(&persona.name),
+        days,
+        chart(|m| m.avg_length),
+        chart(|m| m.emoji_rate),
+        chart(|m| m.formality),
+        chart(|m| m.cyrillic_share),
+        style::RECENT_DAYS,
+        style::BASELINE_DAYS,
+        baseline.describe(),
+        style::RECENT_DAYS,
+        recent.describe()
+    );
+    if drift.is_empty() {
+        text.push_str("✅ No drift from the baseline");
+    } else {
+        text.push_str("⚠️ <b>Drifted:</b>\n");
+        for reason in &drift {
+            text.push_str(&format!("• {}\n", html_escape(reason)));
+        }
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Lint an existing persona's prompt
+/// Usage: /lint_persona <persona_id>
+pub async fn handle_lint_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /lint_persona <persona_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    };
+
+    let warnings = crate::ai::lint_prompt(&persona.prompt);
+    let tokens = crate::ai::persona_lint::estimate_tokens(&persona.prompt);
+
+    let text = if warnings.is_empty() {
+        format!("✅ <b>{}</b> looks fine (~{} tokens)", html_escape(&persona.name), tokens)
+    } else {
+        format!(
+            "⚠️ <b>{}</b> (~{} tokens):\n\n{}",
+            html_escape(&persona.name),
+            tokens,
+            html_escape(&crate::ai::persona_lint::format_warnings(&warnings))
+        )
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Show or override the prompts used to describe photos, GIFs and video notes
+/// Usage: /vision_prompt [photo|gif|video_note] [persona_id|default] [template|reset]
+pub async fn handle_vision_prompt(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    const USAGE: &str = "❌ Usage: /vision_prompt [photo|gif|video_note] [persona_id|default] [template|reset]\n\
+        Templates can use {{caption}} and {{persona_name}}.";
+
+    let media = match args.first().map(|a| MediaType::parse(a)) {
+        None => {
+            let overrides = VisionPromptRepository::list(&state.db_pool).await?;
+            let mut response = String::from("👁 <b>Vision prompts</b>\n");
+            for media in MediaType::ALL {
+                let global = overrides.iter().find(|p| p.media_type == media.as_str() && p.persona_id == 0);
+                response.push_str(&format!(
+                    "\n<b>{}</b>{}: {}\n",
+                    media.as_str(),
+                    if global.is_some() { "" } else { " (built-in)" },
+                    html_escape(global.map_or(media.default_template(), |p| p.template.as_str()))
+                ));
+                for p in overrides.iter().filter(|p| p.media_type == media.as_str() && p.persona_id != 0) {
+                    response.push_str(&format!("  • persona {}: {}\n", p.persona_id, html_escape(&p.template)));
+                }
+            }
+            bot.send_message(msg.chat.id, response)
+                .parse_mode(ParseMode::Html)
+                .await?;
+            return Ok(());
+        }
+        Some(Some(media)) => media,
+        Some(None) => {
+            bot.send_message(msg.chat.id, USAGE).await?;
+            return Ok(());
+        }
+    };
+
+    let persona_id = match args.get(1).map(|a| a.as_str()) {
+        None | Some("default") => None,
+        Some(id) => match id.parse::<i64>() {
+            Ok(id) if PersonaRepository::get_by_id(&state.db_pool, id).await?.is_some() => Some(id),
+            Ok(id) => {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", id)).await?;
+                return Ok(());
+            }
+            Err(_) => {
+                bot.send_message(msg.chat.id, USAGE).await?;
+                return Ok(());
+            }
+        },
+    };
+    let scope = persona_id.map_or("all personas".to_string(), |id| format!("persona {}", id));
+
+    let text = match args.get(2).map(|a| a.as_str()) {
+        None => {
+            let template = VisionPromptRepository::resolve(&state.db_pool, media.as_str(), persona_id)
+                .await?
+                .unwrap_or_else(|| media.default_template().to_string());
+            format!("👁 {} prompt for {}:\n{}", media.as_str(), scope, template)
+        }
+        Some("reset") => {
+            if VisionPromptRepository::reset(&state.db_pool, media.as_str(), persona_id).await? {
+                format!("✅ {} prompt for {} reset", media.as_str(), scope)
+            } else {
+                format!("ℹ️ No {} prompt override for {}", media.as_str(), scope)
+            }
+        }
+        Some(_) => {
+            let template = args[2..].join(" ");
+            VisionPromptRepository::set(&state.db_pool, media.as_str(), persona_id, &template).await?;
+            format!("✅ {} prompt for {} saved", media.as_str(), scope)
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Show a persona with buttons to edit one field at a time
+/// Usage: /edit_persona <persona_id>
+pub async fn handle_edit_persona(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /edit_persona <persona_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    };
+
+    let preview: String = persona.prompt.chars().take(300).collect();
+    let ellipsis = if persona.prompt.chars().count() > 300 { "…" } else { "" };
+    let base = match persona.base_id {
+        Some(base_id) => match PersonaRepository::get_by_id(&state.db_pool, base_id).await? {
+            Some(base) => format!("\nExtends <b>{}</b> (/persona_base {})", html_escape(&base.name), persona.id),
+            None => String::new(),
+        },
+        None => String::new(),
+    };
+
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🎭 <b>{}</b> (ID {}){}\n\n<i>{}{}</i>\n\nWhat do you want to change?",
+            html_escape(&persona.name),
+            persona.id,
+            base,
+            html_escape(&preview),
+            ellipsis
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .reply_markup(InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✏️ Name", format!("p_edit_name:{}", persona.id)),
+        InlineKeyboardButton::callback("📝 Prompt", format!("p_edit_prompt:{}", persona.id)),
+        InlineKeyboardButton::callback("🧹 Post-processing", format!("p_edit_post:{}", persona.id)),
+    ]]))
+    .await?;
+
+    Ok(())
+}
+
+/// Add a curated example exchange to a persona
+/// Usage: /add_example <persona_id> <message> || <reply>
+pub async fn handle_add_example(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = args.first().and_then(|a| a.parse::<i64>().ok());
+    let rest = args.iter().skip(1).cloned().collect::<Vec<_>>().join(" ");
+    let exchange = rest
+        .split_once("||")
+        .map(|(user, reply)| (user.trim().to_string(), reply.trim().to_string()))
+        .filter(|(user, reply)| !user.is_empty() && !reply.is_empty());
+
+    let (persona_id, (user_text, reply)) = match (persona_id, exchange) {
+        (Some(id), Some(exchange)) => (id, exchange),
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /add_example <persona_id> <message> || <reply>")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+        bot.send_message(msg.chat.id, "❌ Persona not found").await?;
+        return Ok(());
+    }
+
+    match crate::ai::examples::add_example(&state, persona_id, &user_text, &reply, "manual").await {
+        Ok(id) => {
+            bot.send_message(msg.chat.id, format!("✅ Example #{} added to persona {}", id, persona_id))
+                .await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {:#}", e)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// List a persona's few-shot examples
+/// Usage: /examples <persona_id>
+pub async fn handle_examples(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /examples <persona_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let examples = ExampleRepository::list(&state.db_pool, persona_id).await?;
+    if examples.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "📭 No examples yet. Add one with /add_example or reply !save to a good reply from the account.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let mut response = format!("💬 <b>Examples of persona {}</b> ({})\n", persona_id, examples.len());
+    for example in examples.iter().take(20) {
+        let user_text: String = example.user_text.chars().take(80).collect();
+        let reply: String = example.reply.chars().take(80).collect();
+        response.push_str(&format!(
+            "\n<b>#{}</b> [{}]\n→ {}\n← <i>{}</i>\n",
+            example.id,
+            example.source,
+            html_escape(&user_text),
+            html_escape(&reply)
+        ));
+    }
+
+    bot.send_message(msg.chat.id, response)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Delete a few-shot example
+/// Usage: /delete_example <example_id>
+pub async fn handle_delete_example(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /delete_example <example_id>").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if ExampleRepository::delete(&state.db_pool, id).await? {
+        format!("🗑 Example #{} deleted", id)
+    } else {
+        format!("❌ Example #{} not found", id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Export curated exchanges as a fine-tuning dataset
+/// Usage: /export_dataset <openai|sharegpt> [persona=<id>] [from=YYYY-MM-DD] [to=YYYY-MM-DD] [min_responses=<n>]
+pub async fn handle_export_dataset(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::finetune::{self, DatasetFormat, ExportFilter};
+
+    let usage = "❌ Usage: /export_dataset <openai|sharegpt> [persona=<id>] [from=YYYY-MM-DD] [to=YYYY-MM-DD] \
+        [min_responses=<n>]";
+    let format = match args.first().and_then(|a| DatasetFormat::parse(a)) {
+        Some(format) => format,
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let is_date = |d: &str| chrono::NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok();
+    let mut filter = ExportFilter { min_responses: finetune::DEFAULT_MIN_RESPONSES, ..Default::default() };
+    for arg in &args[1..] {
+        match arg.split_once('=') {
+            Some(("persona", id)) if id.parse::<i64>().is_ok() => filter.persona_id = Some(id.parse()?),
+            Some(("from", date)) if is_date(date) => filter.from = Some(date.to_string()),
+            Some(("to", date)) if is_date(date) => filter.to = Some(date.to_string()),
+            Some(("min_responses", n)) if n.parse::<i64>().is_ok_and(|n| n >= 1) => {
+                filter.min_responses = n.parse()?
+            }
+            _ => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    if let Some(persona_id) = filter.persona_id {
+        if PersonaRepository::get_by_id(&state.db_pool, persona_id).await?.is_none() {
+            bot.send_message(msg.chat.id, "❌ Persona not found").await?;
+            return Ok(());
+        }
+    }
+
+    let (pairs, summary) = finetune::collect(&state, &filter).await?;
+    if pairs.is_empty() {
+        bot.send_message(
+            msg.chat.id,
+            "📭 Nothing to export: no examples or replies answered often enough match the filter.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let name = format!(
+        "dataset-{}-{}.jsonl.gz",
+        filter.persona_id.map_or("all".to_string(), |id| format!("persona{}", id)),
+        format.as_str()
+    );
+    bot.send_document(msg.chat.id, InputFile::memory(finetune::compress(format, &pairs)?).file_name(name))
+        .caption(format!(
+            "🎓 {} pairs: {} curated examples (/add_example, ghost mode), {} replies answered ≥{} times",
+            pairs.len(),
+            summary.examples,
+            summary.responses,
+            filter.min_responses
+        ))
+        .await?;
+    Ok(())
+}
+
+/// Dry-run a persona's post-processing rules on any text
+/// Usage: /preview_postprocess <persona_id> <text>
+pub async fn handle_preview_postprocess(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let persona_id = args.first().and_then(|a| a.parse::<i64>().ok());
+    let text = args.iter().skip(1).cloned().collect::<Vec<_>>().join(" ");
+
+    let persona_id = match persona_id {
+        Some(id) if !text.trim().is_empty() => id,
+        _ => {
+            bot.send_message(msg.chat.id, "❌ Usage: /preview_postprocess <persona_id> <text>").await?;
+            return Ok(());
+        }
+    };
+
+    let persona = match PersonaRepository::get_by_id(&state.db_pool, persona_id).await? {
+        Some(p) => p,
+        None => {
+            bot.send_message(msg.chat.id, format!("❌ Persona {} not found", persona_id)).await?;
+            return Ok(());
+        }
+    };
+
+    let rules = match persona.postprocess.as_deref().filter(|r| !r.trim().is_empty()) {
+        Some(rules) => rules,
+        None => {
+            bot.send_message(
+                msg.chat.id,
+                format!("ℹ️ {} has no post-processing rules. Set them with /edit_persona {}", persona.name, persona_id),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let text = match crate::ai::postprocess::PostProcessor::parse(rules) {
+        Ok(processor) => format!(
+            "🧹 <b>{}</b>\n\n<b>Before:</b>\n{}\n\n<b>After:</b>\n{}",
+            html_escape(&persona.name),
+            html_escape(text.trim()),
+            html_escape(&processor.apply(&text))
+        ),
+        Err(e) => format!("❌ Stored rules are broken and skipped: {}", html_escape(&e)),
+    };
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+
+    Ok(())
+}
+
+/// Time-of-day variants of a persona, resolved by each chat's local hour
+/// Usage: /persona_time <persona_id> [<from>-<to> <instructions>|remove <n>|clear]
+pub async fn handle_persona_time(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::time_variants::{self, TimeVariant};
+
+    const USAGE: &str = "❌ Usage: /persona_time <persona_id> [<from>-<to> <instructions>|remove <n>|clear]\n\
+        Example: /persona_time 3 23-7 Ты сонный, отвечаешь коротко и зеваешь";
+
+    let persona = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => match PersonaRepository::get_by_id(&state.db_pool, id).await? {
+            Some(p) => p,
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", id)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, USAGE).await?;
+            return Ok(());
+        }
+    };
+
+    let mut variants = time_variants::parse_variants(persona.time_variants.as_deref());
+    let changed = match args.get(1).map(|a| a.as_str()) {
+        None => false,
+        Some("clear") => {
+            variants.clear();
+            true
+        }
+        Some("remove") => match args.get(2).and_then(|n| n.parse::<usize>().ok()) {
+            Some(n) if (1..=variants.len()).contains(&n) => {
+                variants.remove(n - 1);
+                true
+            }
+            _ => {
+                bot.send_message(msg.chat.id, "❌ No such variant, see /persona_time <persona_id>").await?;
+                return Ok(());
+            }
+        },
+        Some(range) => {
+            let suffix = args[2..].join(" ");
+            match time_variants::parse_range(range) {
+                Some((from, to)) if !suffix.trim().is_empty() => {
+                    variants.push(TimeVariant { from, to, suffix: suffix.trim().to_string() });
+                    true
+                }
+                _ => {
+                    bot.send_message(msg.chat.id, USAGE).await?;
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    if changed {
+        let json = (!variants.is_empty()).then(|| serde_json::to_string(&variants)).transpose()?;
+        PersonaRepository::update_time_variants(&state.db_pool, persona.id, json.as_deref()).await?;
+    }
+
+    let mut text = format!("🕰 <b>{}</b> by local hour of each chat", html_escape(&persona.name));
+    if variants.is_empty() {
+        text.push_str("\n\nNo variants: the same all day.");
+    } else {
+        for (i, v) in variants.iter().enumerate() {
+            text.push_str(&format!("\n\n{}. {:02}:00–{:02}:00\n<i>{}</i>", i + 1, v.from, v.to, html_escape(&v.suffix)));
+        }
+        text.push_str("\n\nThe first matching range applies.");
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
+
+/// Show or set what a persona's conversations leave in long-term memory
+/// Usage: /persona_memory <persona_id> [all|user_only|none]
+pub async fn handle_persona_memory(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::memory_policy::MemoryWritePolicy;
+
+    let usage = "❌ Usage: /persona_memory <persona_id> [all|user_only|none]";
+    let persona = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => match PersonaRepository::get_by_id(&state.db_pool, id).await? {
+            Some(p) => p,
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", id)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let policy = match args.get(1) {
+        None => MemoryWritePolicy::of(Some(&persona)),
+        Some(value) => match MemoryWritePolicy::parse(value) {
+            Some(policy) => {
+                PersonaRepository::update_memory_write(&state.db_pool, persona.id, policy.as_str()).await?;
+                policy
+            }
+            None => {
+                bot.send_message(msg.chat.id, usage).await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let description = match policy {
+        MemoryWritePolicy::All => "incoming messages and its own replies are remembered",
+        MemoryWritePolicy::UserOnly => "incoming messages are remembered, its own replies aren't",
+        MemoryWritePolicy::None => "nothing is remembered, and ghost-mode corrections don't become examples",
+    };
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "🧠 <b>{}</b> memory: <code>{}</code> — {}",
+            html_escape(&persona.name),
+            policy.as_str(),
+            description
+        ),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Show or set what a persona may do besides writing text
+/// Usage: /persona_caps <persona_id> [search=on|off] [images=on|off] [tools=on|off] [voice=on|off]
+pub async fn handle_persona_caps(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::ai::capabilities::Capabilities;
+
+    let usage = "❌ Usage: /persona_caps <persona_id> [search=on|off] [images=on|off] [tools=on|off] [voice=on|off]";
+    let persona = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => match PersonaRepository::get_by_id(&state.db_pool, id).await? {
+            Some(p) => p,
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", id)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    let mut own = Capabilities::of(Some(&persona));
+    for arg in &args[1..] {
+        if own.toggle(arg).is_none() {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    }
+    if args.len() > 1 {
+        PersonaRepository::update_capabilities(&state.db_pool, persona.id, own).await?;
+    }
+
+    // A base that can't do something takes it away from this persona too
+    let effective = match crate::ai::persona_inheritance::resolve(&state.db_pool, persona.id).await? {
+        Some(resolved) => Capabilities::of(Some(&resolved)),
+        None => own,
+    };
+
+    let mut text = format!("🧰 <b>{}</b> capabilities\n\n", html_escape(&persona.name));
+    for name in Capabilities::NAMES {
+        let (mine, actual) = (own.get(name).unwrap_or(true), effective.get(name).unwrap_or(true));
+        text.push_str(&format!(
+            "{} {}{}\n",
+            if actual { "✅" } else { "🚫" },
+            name,
+            if mine && !actual { " (taken away by its base)" } else { "" }
+        ));
+    }
+    text.push_str("\nGlobal settings still apply: a persona can't search in safe mode, whatever it may do.");
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Catchphrase sticker pack of a persona: create it as an account, refresh it now or stop refreshing
+/// Usage: /sticker_pack <persona_id> [create <account_id>|refresh|off]
+pub async fn handle_sticker_pack(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    use crate::{db::StickerPackRepository, userbot::stickers};
+
+    let usage = "❌ Usage: /sticker_pack <persona_id> [create <account_id>|refresh|off]";
+    let persona = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => match PersonaRepository::get_by_id(&state.db_pool, id).await? {
+            Some(p) => p,
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", id)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+    let pack = StickerPackRepository::get(&state.db_pool, persona.id).await?;
+
+    let text = match (args.get(1).map(|a| a.as_str()), pack) {
+        (None, Some(pack)) => format!(
+            "🎨 <b>{}</b>: <a href=\"https://t.me/addstickers/{}\">{}</a>\n{} stickers, made by account {}, refreshed {}",
+            html_escape(&persona.name),
+            pack.name,
+            html_escape(&pack.title),
+            pack.get_phrases().len(),
+            pack.account_id,
+            pack.refreshed_at.format("%Y-%m-%d")
+        ),
+        (None, None) => format!(
+            "🎨 <b>{}</b> has no sticker pack. Create one with /sticker_pack {} create &lt;account_id&gt;",
+            html_escape(&persona.name),
+            persona.id
+        ),
+        (Some("create"), Some(pack)) => format!(
+            "❌ <b>{}</b> already has <a href=\"https://t.me/addstickers/{}\">a sticker pack</a>",
+            html_escape(&persona.name),
+            pack.name
+        ),
+        (Some("create"), None) => {
+            let account_id = match args.get(2).and_then(|a| a.parse::<i64>().ok()) {
+                Some(id) => id,
+                None => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            };
+            let handle = match state.get_userbot(account_id).await {
+                Some(handle) => handle,
+                None => {
+                    bot.send_message(msg.chat.id, "❌ Userbot not running").await?;
+                    return Ok(());
+                }
+            };
+            bot.send_message(msg.chat.id, "⏳ Drawing stickers…").await?;
+            match stickers::create_pack(&state, &handle.client, account_id, &persona).await {
+                Ok(name) => format!(
+                    "✅ Created <a href=\"https://t.me/addstickers/{}\">{}'s sticker pack</a>. New catchphrases are added monthly.",
+                    name,
+                    html_escape(&persona.name)
+                ),
+                Err(e) => format!("❌ Couldn't create the pack: {}", html_escape(&e.to_string())),
+            }
+        }
+        (Some("refresh"), Some(pack)) => match state.get_userbot(pack.account_id).await {
+            Some(handle) => match stickers::refresh_pack(&state, &handle.client, &pack).await {
+                Ok(added) => format!("✅ Added {} stickers to the pack", added),
+                Err(e) => format!("❌ Couldn't refresh the pack: {}", html_escape(&e.to_string())),
+            },
+            None => format!("❌ Account {} that owns the pack isn't running", pack.account_id),
+        },
+        (Some("off"), Some(_)) => {
+            StickerPackRepository::delete(&state.db_pool, persona.id).await?;
+            "✅ The pack won't be refreshed anymore; it stays on Telegram".to_string()
+        }
+        (Some("refresh" | "off"), None) => format!("❌ <b>{}</b> has no sticker pack", html_escape(&persona.name)),
+        _ => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Show or set the base persona a persona extends
+/// Usage: /persona_base <persona_id> [base_id|off]
+pub async fn handle_persona_base(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let usage = "❌ Usage: /persona_base <persona_id> [base_id|off]";
+    let persona = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => match PersonaRepository::get_by_id(&state.db_pool, id).await? {
+            Some(p) => p,
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", id)).await?;
+                return Ok(());
+            }
+        },
+        None => {
+            bot.send_message(msg.chat.id, usage).await?;
+            return Ok(());
+        }
+    };
+
+    match args.get(1).map(String::as_str) {
+        None => {}
+        Some("off") | Some("none") => PersonaRepository::set_base(&state.db_pool, persona.id, None).await?,
+        Some(value) => {
+            let base_id = match value.parse::<i64>() {
+                Ok(id) => id,
+                Err(_) => {
+                    bot.send_message(msg.chat.id, usage).await?;
+                    return Ok(());
+                }
+            };
+            let base_lineage = PersonaRepository::lineage(&state.db_pool, base_id).await?;
+            if base_lineage.is_empty() {
+                bot.send_message(msg.chat.id, format!("❌ Persona {} not found", base_id)).await?;
+                return Ok(());
+            }
+            // The base must not extend this persona, directly or through its own bases
+            if base_lineage.iter().any(|p| p.id == persona.id) {
+                bot.send_message(msg.chat.id, "❌ That would make the persona extend itself").await?;
+                return Ok(());
+            }
+            if base_lineage.len() as i64 > crate::db::MAX_PERSONA_DEPTH {
+                bot.send_message(
+                    msg.chat.id,
+                    format!("❌ Bases can be at most {} levels deep", crate::db::MAX_PERSONA_DEPTH),
+                )
+                .await?;
+                return Ok(());
+            }
+            PersonaRepository::set_base(&state.db_pool, persona.id, Some(base_id)).await?;
+        }
+    }
+
+    let lineage = PersonaRepository::lineage(&state.db_pool, persona.id).await?;
+    let mut text = format!("🧬 <b>{}</b> (ID {})\n", html_escape(&persona.name), persona.id);
+    if lineage.len() > 1 {
+        let chain: Vec<String> = lineage.iter().skip(1).map(|p| format!("{} ({})", html_escape(&p.name), p.id)).collect();
+        text.push_str(&format!("Extends: {}\n", chain.join(" → ")));
+    } else {
+        text.push_str("Extends no base persona\n");
+    }
+
+    let children = PersonaRepository::list_children(&state.db_pool, persona.id).await?;
+    if !children.is_empty() {
+        let names: Vec<String> = children.iter().map(|p| format!("{} ({})", html_escape(&p.name), p.id)).collect();
+        text.push_str(&format!("Extended by: {}\n", names.join(", ")));
+    }
+
+    if let Some(resolved) = crate::ai::persona_inheritance::resolve(&state.db_pool, persona.id).await? {
+        let tokens = crate::ai::persona_lint::estimate_tokens(&resolved.prompt);
+        let preview: String = resolved.prompt.chars().take(300).collect();
+        let ellipsis = if resolved.prompt.chars().count() > 300 { "…" } else { "" };
+        text.push_str(&format!(
+            "\n<b>Effective prompt</b> (~{} tokens):\n<i>{}{}</i>",
+            tokens,
+            html_escape(&preview),
+            ellipsis
+        ));
+    }
+
+    bot.send_message(msg.chat.id, text)
+        .parse_mode(ParseMode::Html)
+        .await?;
+    Ok(())
+}
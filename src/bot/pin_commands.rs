@@ -0,0 +1,130 @@
+use crate::{
+    bot::handlers::html_escape,
+    db::AuditRepository,
+    userbot::{
+        pin::{self, PinAction},
+        trace,
+        transport::TdTransport,
+    },
+    AppState,
+};
+use teloxide::{prelude::*, types::ParseMode};
+
+/// Entries shown by /audit
+const AUDIT_PAGE: i64 = 20;
+
+/// Pin or unpin a message from an account, through the same path the persona uses
+/// Usage: /pin|/unpin <account_id> <message_link> or <account_id> <chat_id> <message_id>
+async fn pin_from_admin(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+    action: PinAction,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let target = match args.as_slice() {
+        [account_id, link] => account_id.parse::<i64>().ok().zip(trace::parse_message_link(link)),
+        [account_id, chat_id, message_id] => match (account_id.parse::<i64>(), chat_id.parse::<i64>(), message_id.parse::<i64>()) {
+            (Ok(account_id), Ok(chat_id), Ok(message_id)) => Some((account_id, (Some(chat_id), message_id))),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (account_id, chat_id, message_id) = match target {
+        Some((account_id, (Some(chat_id), message_id))) => (account_id, chat_id, message_id),
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                format!(
+                    "❌ Usage: /{0} <account_id> <message_link> or /{0} <account_id> <chat_id> <message_id>\n\
+                    Links must be t.me/c/… links that name the chat.",
+                    action.as_str()
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let handle = match state.get_userbot(account_id).await {
+        Some(handle) => handle,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Userbot not running").await?;
+            return Ok(());
+        }
+    };
+
+    let actor = format!("admin {}", msg.from.as_ref().map_or(0, |u| u.id.0 as i64));
+    let transport = TdTransport::new(handle.client.clone());
+    let text = match pin::apply(&state, &transport, account_id, chat_id, message_id, action, &actor).await {
+        Ok(()) => format!("📌 Done: {} of message {} in chat {}", action.as_str(), message_id, chat_id),
+        Err(e) => format!("❌ Telegram refused to {} it: {}", action.as_str(), e),
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
+
+/// Pin a message as an account
+/// Usage: /pin <account_id> <message_link> or /pin <account_id> <chat_id> <message_id>
+pub async fn handle_pin(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pin_from_admin(bot, msg, state, args, PinAction::Pin).await
+}
+
+/// Unpin a message as an account
+/// Usage: /unpin <account_id> <message_link> or /unpin <account_id> <chat_id> <message_id>
+pub async fn handle_unpin(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    pin_from_admin(bot, msg, state, args, PinAction::Unpin).await
+}
+
+/// Pins and unpins an account made, who asked for them and whether Telegram allowed it
+/// Usage: /audit <account_id> [chat_id]
+pub async fn handle_audit(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let account_id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /audit <account_id> [chat_id]").await?;
+            return Ok(());
+        }
+    };
+    let chat_id = args.get(1).and_then(|a| a.parse::<i64>().ok());
+
+    let entries = AuditRepository::recent(&state.db_pool, account_id, chat_id, AUDIT_PAGE).await?;
+    if entries.is_empty() {
+        bot.send_message(msg.chat.id, format!("📜 Nothing in the audit log of account {}", account_id)).await?;
+        return Ok(());
+    }
+
+    let mut text = format!("📜 <b>Audit log of account {}</b>\n\n", account_id);
+    for entry in &entries {
+        text.push_str(&format!(
+            "{} {} {} · {} · <code>{}</code> msg {}\n",
+            if entry.ok { "✅" } else { "❌" },
+            entry.created_at.format("%d.%m %H:%M"),
+            html_escape(&entry.action),
+            html_escape(&entry.actor),
+            entry.chat_id,
+            entry.message_id.map_or("-".to_string(), |id| id.to_string())
+        ));
+        if let Some(details) = &entry.details {
+            text.push_str(&format!("   <i>{}</i>\n", html_escape(details)));
+        }
+    }
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
@@ -0,0 +1,126 @@
+use crate::{
+    bot::handlers::html_escape,
+    db::{AccountRepository, ChatRuleRepository, PersonaRepository},
+    userbot::rules,
+    AppState,
+};
+use teloxide::{prelude::*, types::ParseMode};
+
+const USAGE: &str = "❌ Usage: /rule_add [account=<id>] [chat=<id>] [user=<id>] [match=<regex>] \
+[persona=<name|id>] [temperature=<t>] [reply=always|never] [priority=<n>]\n\n\
+Examples:\n/rule_add chat=-100123 match=(?i)crypto persona=Trader temperature=1.2\n/rule_add user=42 reply=never";
+
+/// Routing rules in the order they are evaluated
+/// Usage: /rules
+pub async fn handle_rules(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let all = ChatRuleRepository::list(&state.db_pool).await?;
+    if all.is_empty() {
+        bot.send_message(msg.chat.id, "📐 No routing rules. Add one with /rule_add").await?;
+        return Ok(());
+    }
+
+    let mut text = String::from("📐 <b>Routing rules</b> (first match wins per action)\n\n");
+    for rule in &all {
+        let persona = match rule.persona_id {
+            Some(id) => PersonaRepository::get_by_id(&state.db_pool, id).await?.map(|p| p.name),
+            None => None,
+        };
+        text.push_str(&format!(
+            "#{}{} {}\n",
+            rule.id,
+            if rule.priority != 0 { format!(" (priority {})", rule.priority) } else { String::new() },
+            html_escape(&rules::describe(rule, persona.as_deref()))
+        ));
+    }
+    text.push_str("\n/rule_remove &lt;id&gt; to delete one");
+
+    bot.send_message(msg.chat.id, text).parse_mode(ParseMode::Html).await?;
+    Ok(())
+}
+
+/// Add a routing rule: conditions on account, chat, user and text, and what to do then
+/// Usage: /rule_add [account=<id>] [chat=<id>] [user=<id>] [match=<regex>] [persona=<name|id>] [temperature=<t>] [reply=always|never] [priority=<n>]
+pub async fn handle_rule_add(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if args.is_empty() {
+        bot.send_message(msg.chat.id, USAGE).await?;
+        return Ok(());
+    }
+
+    let mut draft = match rules::parse(&args) {
+        Ok(draft) => draft,
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ {}\n\n{}", e, USAGE)).await?;
+            return Ok(());
+        }
+    };
+
+    if let Some(account_id) = draft.rule.account_id {
+        if AccountRepository::get_by_id(&state.db_pool, account_id).await?.is_none() {
+            bot.send_message(msg.chat.id, format!("❌ Account {} not found", account_id)).await?;
+            return Ok(());
+        }
+    }
+
+    let mut persona_name = None;
+    if let Some(name) = &draft.persona {
+        let persona = match name.parse::<i64>() {
+            Ok(id) => PersonaRepository::get_by_id(&state.db_pool, id).await?,
+            Err(_) => PersonaRepository::get_by_name(&state.db_pool, name).await?,
+        };
+        match persona {
+            Some(persona) => {
+                draft.rule.persona_id = Some(persona.id);
+                persona_name = Some(persona.name);
+            }
+            None => {
+                bot.send_message(msg.chat.id, format!("❌ Persona '{}' not found", name)).await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let id = ChatRuleRepository::add(&state.db_pool, &draft.rule).await?;
+    let rule = ChatRuleRepository::get(&state.db_pool, id).await?.ok_or("Added rule disappeared")?;
+
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Rule #{} added\n{}", id, html_escape(&rules::describe(&rule, persona_name.as_deref()))),
+    )
+    .parse_mode(ParseMode::Html)
+    .await?;
+    Ok(())
+}
+
+/// Delete a routing rule
+/// Usage: /rule_remove <rule_id>
+pub async fn handle_rule_remove(
+    bot: Bot,
+    msg: Message,
+    state: AppState,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let id = match args.first().and_then(|a| a.parse::<i64>().ok()) {
+        Some(id) => id,
+        None => {
+            bot.send_message(msg.chat.id, "❌ Usage: /rule_remove <rule_id> (see /rules)").await?;
+            return Ok(());
+        }
+    };
+
+    let text = if ChatRuleRepository::remove(&state.db_pool, id).await? {
+        format!("🗑 Rule #{} removed", id)
+    } else {
+        format!("❌ Rule #{} not found", id)
+    };
+    bot.send_message(msg.chat.id, text).await?;
+    Ok(())
+}
@@ -0,0 +1,515 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{BusinessConnection, BusinessRepository, MessageRole},
+    userbot::{digest::parse_weekday, timezone},
+    AppState,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Timelike};
+use chrono_tz::Tz;
+use rand::Rng;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Long-polling timeout of getUpdates
+const POLL_TIMEOUT_SECS: u64 = 30;
+/// Pause after a failed poll
+const RETRY_DELAY_SECS: u64 = 5;
+/// Messages of a business chat given to the model as context
+const HISTORY_MESSAGES: i64 = 12;
+
+/// When a business account gets automatic replies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyMode {
+    /// Only outside working hours
+    Away,
+    /// Around the clock
+    Always,
+}
+
+impl ReplyMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplyMode::Away => "away",
+            ReplyMode::Always => "always",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "away" => Some(ReplyMode::Away),
+            "always" => Some(ReplyMode::Always),
+            _ => None,
+        }
+    }
+}
+
+/// How the extra delay before an auto-reply is drawn between its bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JitterDistribution {
+    /// Any delay in the range is equally likely
+    Uniform,
+    /// Mostly short delays with an occasional long one
+    Short,
+}
+
+impl JitterDistribution {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "uniform" => Some(JitterDistribution::Uniform),
+            "short" => Some(JitterDistribution::Short),
+            _ => None,
+        }
+    }
+}
+
+/// Random hold-back of generated auto-replies, so they don't arrive with the fixed rhythm of generation time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplyJitter {
+    pub min_ms: u64,
+    pub max_ms: u64,
+    pub distribution: JitterDistribution,
+    /// Upper bound when the client replies to one of the account's messages
+    pub mention_max_ms: u64,
+}
+
+impl ReplyJitter {
+    /// Delay before sending a reply; `mention` takes the faster path
+    pub fn delay(&self, mention: bool, rng: &mut impl Rng) -> Duration {
+        let max = if mention { self.max_ms.min(self.mention_max_ms) } else { self.max_ms };
+        let min = self.min_ms.min(max);
+        let fraction = match self.distribution {
+            JitterDistribution::Uniform => rng.gen::<f64>(),
+            JitterDistribution::Short => rng.gen::<f64>() * rng.gen::<f64>(),
+        };
+        Duration::from_millis(min + ((max - min) as f64 * fraction) as u64)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawUpdate {
+    update_id: i64,
+    business_connection: Option<RawConnection>,
+    business_message: Option<RawMessage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawUser {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRights {
+    #[serde(default)]
+    can_reply: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawConnection {
+    id: String,
+    user: RawUser,
+    user_chat_id: i64,
+    /// Bot API before 9.0
+    #[serde(default)]
+    can_reply: bool,
+    /// Bot API 9.0 and later
+    rights: Option<RawRights>,
+    is_enabled: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawChat {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawReplyTo {
+    from: Option<RawUser>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMessage {
+    business_connection_id: String,
+    chat: RawChat,
+    from: Option<RawUser>,
+    reply_to_message: Option<RawReplyTo>,
+    /// Set when a bot sent the message on behalf of the account (our own replies)
+    sender_business_bot: Option<RawUser>,
+    text: Option<String>,
+    caption: Option<String>,
+}
+
+/// Whether `now` falls into working hours. Ranges past midnight ("22-6") are supported.
+pub fn is_working_time(work_days: &[u32], start_hour: u32, end_hour: u32, now: DateTime<Tz>) -> bool {
+    let hour = now.hour();
+    let today = now.weekday().num_days_from_monday();
+
+    if start_hour <= end_hour {
+        work_days.contains(&today) && hour >= start_hour && hour < end_hour
+    } else if hour >= start_hour {
+        work_days.contains(&today)
+    } else {
+        // Early hours belong to the shift that started the day before
+        work_days.contains(&((today + 6) % 7)) && hour < end_hour
+    }
+}
+
+/// Parse "mon-fri" or "mon,wed,sat" into weekday numbers (0 = Monday)
+pub fn parse_work_days(value: &str) -> Option<Vec<u32>> {
+    if let Some((from, to)) = value.split_once('-') {
+        let (from, to) = (parse_weekday(from)? as u32, parse_weekday(to)? as u32);
+        let mut days = vec![from];
+        let mut day = from;
+        while day != to {
+            day = (day + 1) % 7;
+            days.push(day);
+        }
+        return Some(days);
+    }
+
+    value.split(',').map(|d| parse_weekday(d).map(|d| d as u32)).collect()
+}
+
+/// Apply a "key=value" setting from /business_set to a connection
+pub fn apply_setting(connection: &mut BusinessConnection, key: &str, value: &str) -> Result<(), String> {
+    match key.to_lowercase().as_str() {
+        "reply" => {
+            connection.auto_reply = match value.to_lowercase().as_str() {
+                "on" => true,
+                "off" => false,
+                _ => return Err("reply must be on or off".to_string()),
+            };
+        }
+        "mode" => {
+            let mode = ReplyMode::parse(value).ok_or("mode must be away or always")?;
+            connection.reply_mode = mode.as_str().to_string();
+        }
+        "persona" => {
+            connection.persona_id = match value {
+                "none" => None,
+                id => Some(id.parse().map_err(|_| format!("invalid persona id '{}'", id))?),
+            };
+        }
+        "hours" => {
+            let (start, end) = value
+                .split_once('-')
+                .and_then(|(s, e)| Some((s.trim().parse::<i64>().ok()?, e.trim().parse::<i64>().ok()?)))
+                .filter(|(s, e)| (0..24).contains(s) && (0..=24).contains(e) && s != e)
+                .ok_or("hours must look like 9-18")?;
+            connection.work_start_hour = start;
+            connection.work_end_hour = end;
+        }
+        "days" => {
+            let days = parse_work_days(value).ok_or("days must look like mon-fri or mon,wed,fri")?;
+            connection.work_days = days.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(",");
+        }
+        "tz" => {
+            connection.timezone = match value {
+                "default" => None,
+                tz => Some(timezone::parse_timezone(tz).ok_or(format!("unknown timezone '{}'", tz))?.name().to_string()),
+            };
+        }
+        _ => return Err(format!("unknown setting '{}'", key)),
+    }
+    Ok(())
+}
+
+/// Current time in the business account's timezone
+fn local_now(state: &AppState, connection: &BusinessConnection) -> DateTime<Tz> {
+    let tz = connection
+        .timezone
+        .as_deref()
+        .and_then(timezone::parse_timezone)
+        .unwrap_or(state.config.default_timezone);
+    chrono::Utc::now().with_timezone(&tz)
+}
+
+/// Whether the account is within its working hours right now
+pub fn is_working_now(state: &AppState, connection: &BusinessConnection) -> bool {
+    is_working_time(
+        &connection.get_work_days(),
+        connection.work_start_hour as u32,
+        connection.work_end_hour as u32,
+        local_now(state, connection),
+    )
+}
+
+/// Call a Bot API method of the business bot
+async fn call<T: serde::de::DeserializeOwned>(
+    http: &reqwest::Client,
+    token: &str,
+    method: &str,
+    params: &serde_json::Value,
+) -> Result<T> {
+    let response: ApiResponse<T> = http
+        .post(format!("https://api.telegram.org/bot{}/{}", token, method))
+        .json(params)
+        .send()
+        .await
+        .with_context(|| format!("Failed to call {}", method))?
+        .json()
+        .await
+        .with_context(|| format!("Failed to parse {} response", method))?;
+
+    match response.result {
+        Some(result) if response.ok => Ok(result),
+        _ => anyhow::bail!("{} failed: {}", method, response.description.unwrap_or_default()),
+    }
+}
+
+/// Long-poll the business bot for connection changes and business messages
+pub async fn business_worker(state: AppState) {
+    let token = match state.config.business_bot_token.clone() {
+        Some(token) => token,
+        None => return,
+    };
+    tracing::info!("Business bot worker started");
+
+    let http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+        .build()
+        .unwrap_or_default();
+    let mut offset = 0;
+
+    loop {
+        let params = serde_json::json!({
+            "offset": offset,
+            "timeout": POLL_TIMEOUT_SECS,
+            "allowed_updates": ["business_connection", "business_message"],
+        });
+
+        let updates: Vec<RawUpdate> = match call(&http, &token, "getUpdates", &params).await {
+            Ok(updates) => updates,
+            Err(e) => {
+                tracing::warn!("Business bot poll failed: {:#}", e);
+                tokio::time::sleep(Duration::from_secs(RETRY_DELAY_SECS)).await;
+                continue;
+            }
+        };
+
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+
+            let result = if let Some(connection) = update.business_connection {
+                handle_connection(&state, connection).await
+            } else if let Some(message) = update.business_message {
+                handle_message(&state, &http, &token, message).await
+            } else {
+                Ok(())
+            };
+
+            if let Err(e) = result {
+                tracing::warn!("Failed to handle business update {}: {:#}", update.update_id, e);
+            }
+        }
+    }
+}
+
+async fn handle_connection(state: &AppState, connection: RawConnection) -> Result<()> {
+    let can_reply = connection.rights.map_or(connection.can_reply, |r| r.can_reply);
+    let known = BusinessRepository::get(&state.db_pool, &connection.id).await?.is_some();
+
+    BusinessRepository::upsert_connection(
+        &state.db_pool,
+        &connection.id,
+        connection.user.id,
+        connection.user_chat_id,
+        can_reply,
+        connection.is_enabled,
+    )
+    .await?;
+
+    if !known {
+        let notice = format!(
+            "💼 Business account {} connected (can reply: {}). Auto-replies are off until you run \
+             /business_set {} reply=on",
+            connection.user.id,
+            if can_reply { "yes" } else { "no" },
+            connection.user.id
+        );
+        if let Err(e) = crate::userbot::worker::notify_owner(state, &notice).await {
+            tracing::warn!("Failed to notify owner about business connection: {}", e);
+        }
+    }
+
+    tracing::info!(
+        "Business connection {} of user {}: enabled={}, can_reply={}",
+        connection.id,
+        connection.user.id,
+        connection.is_enabled,
+        can_reply
+    );
+    Ok(())
+}
+
+async fn handle_message(state: &AppState, http: &reqwest::Client, token: &str, message: RawMessage) -> Result<()> {
+    let connection = match BusinessRepository::get(&state.db_pool, &message.business_connection_id).await? {
+        Some(connection) => connection,
+        None => return Ok(()),
+    };
+    let text = match message.text.or(message.caption) {
+        Some(text) if !text.trim().is_empty() => text,
+        _ => return Ok(()),
+    };
+    let chat_id = message.chat.id;
+
+    // Messages from the account itself: our own replies are already stored,
+    // the owner's manual ones are kept as context
+    if message.from.as_ref().map(|u| u.id) == Some(connection.user_id) {
+        if message.sender_business_bot.is_none() {
+            BusinessRepository::add_message(&state.db_pool, &connection.connection_id, chat_id, MessageRole::Assistant, &text)
+                .await?;
+        }
+        return Ok(());
+    }
+
+    BusinessRepository::add_message(&state.db_pool, &connection.connection_id, chat_id, MessageRole::User, &text).await?;
+
+    let working = is_working_now(state, &connection);
+    let mode = ReplyMode::parse(&connection.reply_mode).unwrap_or(ReplyMode::Away);
+    if !connection.auto_reply
+        || !connection.is_enabled
+        || !connection.can_reply
+        || (mode == ReplyMode::Away && working)
+        || state.is_paused()
+    {
+        return Ok(());
+    }
+
+    let reply = generate_reply(state, &connection, chat_id, working).await?;
+    if reply.is_empty() || reply == "<IGNORE>" {
+        return Ok(());
+    }
+
+    // Replying to the account's own message is addressing it directly
+    let mention = message.reply_to_message.and_then(|r| r.from).map(|u| u.id) == Some(connection.user_id);
+    let delay = state.config.business_reply_jitter.delay(mention, &mut rand::thread_rng());
+
+    // Updates are handled one at a time; the wait must not hold up other chats
+    let (state, http, token) = (state.clone(), http.clone(), token.to_string());
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if let Err(e) = send_reply(&state, &http, &token, &connection, chat_id, &reply).await {
+            tracing::warn!("Failed to send business reply to chat {}: {:#}", chat_id, e);
+        }
+    });
+    Ok(())
+}
+
+async fn send_reply(
+    state: &AppState,
+    http: &reqwest::Client,
+    token: &str,
+    connection: &BusinessConnection,
+    chat_id: i64,
+    reply: &str,
+) -> Result<()> {
+    let params = serde_json::json!({
+        "business_connection_id": connection.connection_id,
+        "chat_id": chat_id,
+        "text": reply,
+    });
+    call::<serde_json::Value>(http, token, "sendMessage", &params).await?;
+
+    BusinessRepository::add_message(&state.db_pool, &connection.connection_id, chat_id, MessageRole::Assistant, reply)
+        .await?;
+    Ok(())
+}
+
+/// Answer the latest message of a business chat on behalf of the account
+async fn generate_reply(state: &AppState, connection: &BusinessConnection, chat_id: i64, working: bool) -> Result<String> {
+    let persona = match connection.persona_id {
+        Some(id) => crate::ai::persona_inheritance::resolve(&state.db_pool, id).await?,
+        None => None,
+    };
+    let prompt = persona.map(|p| p.prompt).unwrap_or_else(|| state.config.default_system_prompt.clone());
+    let prompt = timezone::with_local_time(prompt, local_now(state, connection));
+
+    let hours = if working {
+        "Сейчас рабочее время владельца аккаунта.".to_string()
+    } else {
+        format!(
+            "Сейчас нерабочее время владельца аккаунта (он на связи с {}:00 до {}:00). Если вопрос требует \
+             его личного участия, скажи, что он ответит в рабочее время. Ничего не обещай от его имени.",
+            connection.work_start_hour, connection.work_end_hour
+        )
+    };
+
+    let mut messages = vec![
+        OllamaMessage { role: "system".to_string(), content: prompt },
+        OllamaMessage {
+            role: "system".to_string(),
+            content: format!("[БИЗНЕС-АККАУНТ]\nТы отвечаешь клиентам от имени владельца бизнес-аккаунта.\n{}", hours),
+        },
+    ];
+    for message in BusinessRepository::recent_messages(&state.db_pool, &connection.connection_id, chat_id, HISTORY_MESSAGES).await? {
+        messages.push(OllamaMessage { role: message.role, content: message.content });
+    }
+
+    let reply = OllamaClient::new(state.config.ollama_url.clone())
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages,
+            stream: true,
+            options: None,
+        })
+        .await?;
+    crate::ai::models::track(&state.db_pool, crate::ai::ModelKind::Chat, &state.config.ollama_model, None).await;
+
+    Ok(reply.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(weekday_offset: u32, hour: u32) -> DateTime<Tz> {
+        // 2024-01-01 is a Monday
+        chrono_tz::UTC.with_ymd_and_hms(2024, 1, 1 + weekday_offset, hour, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn checks_working_hours() {
+        let weekdays = [0, 1, 2, 3, 4];
+        assert!(is_working_time(&weekdays, 9, 18, at(0, 10)));
+        assert!(!is_working_time(&weekdays, 9, 18, at(0, 18)));
+        assert!(!is_working_time(&weekdays, 9, 18, at(5, 10)));
+
+        // Night shift starting Friday
+        assert!(is_working_time(&[4], 22, 6, at(4, 23)));
+        assert!(is_working_time(&[4], 22, 6, at(5, 3)));
+        assert!(!is_working_time(&[4], 22, 6, at(6, 3)));
+    }
+
+    #[test]
+    fn jitters_replies_within_bounds() {
+        let jitter = ReplyJitter { min_ms: 1000, max_ms: 5000, distribution: JitterDistribution::Short, mention_max_ms: 800 };
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let delay = jitter.delay(false, &mut rng);
+            assert!(delay >= Duration::from_millis(1000) && delay <= Duration::from_millis(5000));
+            assert!(jitter.delay(true, &mut rng) <= Duration::from_millis(800));
+        }
+
+        let off = ReplyJitter { min_ms: 0, max_ms: 0, distribution: JitterDistribution::Uniform, mention_max_ms: 0 };
+        assert_eq!(off.delay(false, &mut rng), Duration::ZERO);
+        assert_eq!(JitterDistribution::parse(" Short"), Some(JitterDistribution::Short));
+        assert_eq!(JitterDistribution::parse("gauss"), None);
+    }
+
+    #[test]
+    fn parses_work_days() {
+        assert_eq!(parse_work_days("mon-fri"), Some(vec![0, 1, 2, 3, 4]));
+        assert_eq!(parse_work_days("sat-mon"), Some(vec![5, 6, 0]));
+        assert_eq!(parse_work_days("mon,wed"), Some(vec![0, 2]));
+        assert_eq!(parse_work_days("someday"), None);
+    }
+}
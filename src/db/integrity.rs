@@ -0,0 +1,299 @@
+use anyhow::{bail, Context, Result};
+use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, SqlitePool};
+use std::path::{Path, PathBuf};
+
+/// Pre-migration backups kept next to the database
+const BACKUPS_KEPT: usize = 3;
+
+/// Problem lines shown when aborting
+const MAX_REPORTED: usize = 10;
+
+/// A row of sqlx's migration bookkeeping table
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AppliedMigration {
+    pub version: i64,
+    pub success: bool,
+    pub checksum: Vec<u8>,
+}
+
+/// How the database's migration history compares to the migrations built into the binary
+#[derive(Debug, Default, PartialEq)]
+pub struct MigrationReport {
+    pub pending: Vec<i64>,
+    /// Started but never finished
+    pub dirty: Vec<i64>,
+    /// Applied, but the file was edited afterwards
+    pub modified: Vec<i64>,
+    /// Applied by a newer build
+    pub unknown: Vec<i64>,
+}
+
+impl MigrationReport {
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for version in &self.dirty {
+            problems.push(format!(
+                "migration {} was partially applied (success = 0 in _sqlx_migrations); restore a backup \
+                or finish it by hand and delete its row",
+                version
+            ));
+        }
+        for version in &self.modified {
+            problems.push(format!(
+                "migration {} was changed after it was applied (checksum mismatch); revert the file",
+                version
+            ));
+        }
+        for version in &self.unknown {
+            problems.push(format!(
+                "migration {} is applied but unknown to this build; the database belongs to a newer version",
+                version
+            ));
+        }
+        problems
+    }
+}
+
+/// Compare built-in migrations (version, checksum) with the applied ones
+pub fn compare(known: &[(i64, &[u8])], applied: &[AppliedMigration]) -> MigrationReport {
+    let mut report = MigrationReport::default();
+
+    for (version, checksum) in known {
+        match applied.iter().find(|a| a.version == *version) {
+            None => report.pending.push(*version),
+            Some(a) if !a.success => report.dirty.push(*version),
+            Some(a) if a.checksum.as_slice() != *checksum => report.modified.push(*version),
+            Some(_) => {}
+        }
+    }
+    for a in applied {
+        if !known.iter().any(|(version, _)| *version == a.version) {
+            report.unknown.push(a.version);
+        }
+    }
+
+    report
+}
+
+/// File behind a `sqlite:` URL, if it is a file that already exists
+pub fn database_file(database_url: &str) -> Option<PathBuf> {
+    let path = database_url
+        .trim_start_matches("sqlite:")
+        .trim_start_matches("//")
+        .split('?')
+        .next()
+        .unwrap_or("");
+    let path = Path::new(path);
+    (!path.as_os_str().is_empty() && path.is_file()).then(|| path.to_path_buf())
+}
+
+/// Lines of `PRAGMA integrity_check` that aren't "ok"
+pub async fn integrity_problems(pool: &SqlitePool) -> Result<Vec<String>> {
+    let integrity: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(pool)
+        .await
+        .context("Failed to run integrity check")?;
+    Ok(integrity.into_iter().filter(|line| line != "ok").collect())
+}
+
+/// Tables with rows pointing at missing parents
+pub async fn foreign_key_problems(pool: &SqlitePool) -> Result<Vec<String>> {
+    let violations: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT \"table\", COUNT(*) FROM pragma_foreign_key_check GROUP BY \"table\"",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to run foreign key check")?;
+    Ok(violations
+        .iter()
+        .map(|(table, count)| format!("{}: {} row(s) point at missing parents", table, count))
+        .collect())
+}
+
+/// How the database's migration history compares to the migrator's
+pub async fn migration_report(pool: &SqlitePool, migrator: &Migrator) -> Result<MigrationReport> {
+    let known: Vec<(i64, &[u8])> = migrator
+        .iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| (m.version, m.checksum.as_ref()))
+        .collect();
+    Ok(compare(&known, &applied_migrations(pool).await?))
+}
+
+/// Check the database before migrating it: storage integrity, foreign keys and the migration
+/// history. When migrations are pending, back the database up and try them on a copy first.
+/// Every failure aborts startup with what is wrong and where the backup is.
+pub async fn preflight(pool: &SqlitePool, database_url: &str, migrator: &Migrator) -> Result<()> {
+    let integrity = integrity_problems(pool).await?;
+    if !integrity.is_empty() {
+        bail!(
+            "Database integrity check failed:\n{}\nRestore a backup or try `sqlite3 <db> .recover`",
+            summarize(&integrity)
+        );
+    }
+
+    let violations = foreign_key_problems(pool).await?;
+    if !violations.is_empty() {
+        bail!(
+            "Foreign key check failed:\n{}\nInspect with `PRAGMA foreign_key_check;`",
+            summarize(&violations)
+        );
+    }
+
+    let report = migration_report(pool, migrator).await?;
+    let problems = report.problems();
+    if !problems.is_empty() {
+        bail!("Migration history is inconsistent:\n{}", summarize(&problems));
+    }
+
+    if report.pending.is_empty() {
+        return Ok(());
+    }
+    tracing::info!("{} pending migration(s): {:?}", report.pending.len(), report.pending);
+
+    // A new database has nothing to lose
+    let file = match database_file(database_url) {
+        Some(file) => file,
+        None => return Ok(()),
+    };
+
+    let backup = sibling(&file, &format!("pre-migrate-{}.bak", chrono::Utc::now().format("%Y%m%d%H%M%S")));
+    vacuum_into(pool, &backup).await?;
+    tracing::info!("Backed up the database to {} before migrating", backup.display());
+    prune_backups(&file);
+
+    // Dry run on a throwaway copy so a broken migration never touches the real database
+    let dry_run = sibling(&file, "dry-run");
+    let _ = std::fs::remove_file(&dry_run);
+    std::fs::copy(&backup, &dry_run).context("Failed to prepare migration dry run")?;
+    let result = dry_run_migrations(&dry_run, migrator).await;
+    let _ = std::fs::remove_file(&dry_run);
+    if let Err(e) = result {
+        bail!(
+            "Pending migrations {:?} fail on a copy of the database, nothing was changed: {:#}\nBackup: {}",
+            report.pending,
+            e,
+            backup.display()
+        );
+    }
+
+    Ok(())
+}
+
+async fn applied_migrations(pool: &SqlitePool) -> Result<Vec<AppliedMigration>> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations')",
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to look for the migrations table")?;
+    if !exists {
+        return Ok(Vec::new());
+    }
+
+    sqlx::query_as::<_, AppliedMigration>("SELECT version, success, checksum FROM _sqlx_migrations ORDER BY version")
+        .fetch_all(pool)
+        .await
+        .context("Failed to read applied migrations")
+}
+
+async fn dry_run_migrations(path: &Path, migrator: &Migrator) -> Result<()> {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect(&format!("sqlite:{}", path.display()))
+        .await
+        .context("Failed to open the dry-run copy")?;
+    let result = migrator.run(&pool).await;
+    pool.close().await;
+    result.map_err(anyhow::Error::from)
+}
+
+/// A consistent copy, including pages still in the WAL
+async fn vacuum_into(pool: &SqlitePool, target: &Path) -> Result<()> {
+    if target.exists() {
+        return Ok(());
+    }
+    sqlx::query("VACUUM INTO ?")
+        .bind(target.to_string_lossy().to_string())
+        .execute(pool)
+        .await
+        .with_context(|| format!("Failed to back up the database to {}", target.display()))?;
+    Ok(())
+}
+
+/// `data/puppeteer.db` + `dry-run` → `data/puppeteer.db.dry-run`
+fn sibling(file: &Path, suffix: &str) -> PathBuf {
+    let mut name = file.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}", suffix));
+    file.with_file_name(name)
+}
+
+fn prune_backups(file: &Path) {
+    let dir = match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = format!("{}.pre-migrate-", file.file_name().unwrap_or_default().to_string_lossy());
+
+    let mut backups: Vec<PathBuf> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.file_name().is_some_and(|n| n.to_string_lossy().starts_with(&prefix)))
+            .collect(),
+        Err(_) => return,
+    };
+    // Timestamps sort chronologically
+    backups.sort();
+
+    while backups.len() > BACKUPS_KEPT {
+        let oldest = backups.remove(0);
+        if let Err(e) = std::fs::remove_file(&oldest) {
+            tracing::warn!("Failed to remove old backup {}: {}", oldest.display(), e);
+        }
+    }
+}
+
+fn summarize(lines: &[String]) -> String {
+    let mut summary: Vec<String> = lines.iter().take(MAX_REPORTED).map(|l| format!("  - {}", l)).collect();
+    if lines.len() > MAX_REPORTED {
+        summary.push(format!("  … and {} more", lines.len() - MAX_REPORTED));
+    }
+    summary.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn applied(version: i64, success: bool, checksum: &[u8]) -> AppliedMigration {
+        AppliedMigration { version, success, checksum: checksum.to_vec() }
+    }
+
+    #[test]
+    fn finds_pending_and_broken_migrations() {
+        let known: Vec<(i64, &[u8])> = vec![(1, b"a"), (2, b"b"), (3, b"c"), (4, b"d")];
+        let report = compare(
+            &known,
+            &[applied(1, true, b"a"), applied(2, true, b"changed"), applied(3, false, b"c"), applied(9, true, b"z")],
+        );
+
+        assert_eq!(report.pending, vec![4]);
+        assert_eq!(report.modified, vec![2]);
+        assert_eq!(report.dirty, vec![3]);
+        assert_eq!(report.unknown, vec![9]);
+        assert_eq!(report.problems().len(), 3);
+    }
+
+    #[test]
+    fn clean_history_has_no_problems() {
+        let known: Vec<(i64, &[u8])> = vec![(1, b"a"), (2, b"b")];
+        let report = compare(&known, &[applied(1, true, b"a")]);
+        assert_eq!(report.pending, vec![2]);
+        assert!(report.problems().is_empty());
+    }
+
+    #[test]
+    fn names_sibling_files() {
+        assert_eq!(sibling(Path::new("data/puppeteer.db"), "dry-run"), PathBuf::from("data/puppeteer.db.dry-run"));
+    }
+}
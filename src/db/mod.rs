@@ -1,15 +1,18 @@
 use anyhow::{Context, Result};
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use sqlx::{migrate::Migrator, sqlite::SqlitePoolOptions, SqlitePool};
 use std::path::Path;
 
+pub mod integrity;
 pub mod models;
 pub mod repository;
 
 pub use models::*;
 pub use repository::*;
 
-/// Initialize the database connection pool with WAL mode for high concurrency
-pub async fn init_db(database_url: &str) -> Result<SqlitePool> {
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Open the database without checking or migrating it, for tools that must work on a broken one
+pub async fn connect(database_url: &str) -> Result<SqlitePool> {
     // Ensure the data directory exists
     if let Some(parent) = Path::new(database_url.trim_start_matches("sqlite:")).parent() {
         tokio::fs::create_dir_all(parent).await
@@ -35,11 +38,28 @@ pub async fn init_db(database_url: &str) -> Result<SqlitePool> {
         .await
         .context("Failed to enable foreign keys")?;
 
+    Ok(pool)
+}
+
+/// Migrations built into this binary
+pub fn migrator() -> &'static Migrator {
+    &MIGRATOR
+}
+
+/// Initialize the database connection pool with WAL mode for high concurrency
+pub async fn init_db(database_url: &str) -> Result<SqlitePool> {
+    let pool = connect(database_url).await?;
+
+    // Refuse to start on a damaged database; back it up and dry-run pending migrations
+    integrity::preflight(&pool, database_url, &MIGRATOR)
+        .await
+        .context("Database preflight failed, startup aborted")?;
+
     // Run migrations
-    sqlx::migrate!("./migrations")
+    MIGRATOR
         .run(&pool)
         .await
-        .context("Failed to run database migrations")?;
+        .context("Failed to run database migrations (a pre-migration backup is next to the database file)")?;
 
     tracing::info!("Database initialized successfully with WAL mode enabled");
 
@@ -18,6 +18,8 @@ pub struct Account {
     pub use_reply_probability: i64,
     pub ignore_old_messages_sec: i64,
     pub always_respond_in_pm: i64,
+    pub channel_post_reply_probability: i64,
+    pub persona_id: Option<i64>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -44,6 +46,10 @@ pub struct MessageHistory {
     pub role: String,
     pub content: String,
     pub created_at: DateTime<Utc>,
+    pub sender_id: Option<i64>,
+    pub sender_chat_id: Option<i64>,
+    pub persona_id: Option<i64>,
+    pub is_hashed: bool,
 }
 
 /// Role of a message in the conversation
@@ -86,6 +92,12 @@ pub struct NewMessage {
     pub chat_id: i64,
     pub role: MessageRole,
     pub content: String,
+    /// Telegram user who sent the message (None for our own replies)
+    pub sender_id: Option<i64>,
+    /// Chat that sent the message on behalf of a user (channel posts, anonymous admins)
+    pub sender_chat_id: Option<i64>,
+    /// Persona that produced the reply (None for incoming messages)
+    pub persona_id: Option<i64>,
 }
 
 /// Bot group for coordinated actions
@@ -145,3 +157,775 @@ pub struct NewSpamCampaign {
     pub repeat_count: i64,
     pub delay_between_ms: i64,
 }
+
+/// Persisted admin bot wizard session (one per chat)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WizardSession {
+    pub chat_id: i64,
+    pub kind: String,
+    pub payload: String,
+    pub expires_at: i64,
+    pub created_at: DateTime<Utc>,
+    pub user_id: Option<i64>,
+}
+
+impl WizardSession {
+    /// Parse the JSON payload
+    pub fn payload_json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.payload).unwrap_or_default()
+    }
+
+    /// Seconds left before the session expires (negative if already expired)
+    pub fn seconds_left(&self) -> i64 {
+        self.expires_at - Utc::now().timestamp()
+    }
+}
+
+/// Per-chat prompt-injection policy override
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatSecurityPolicy {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub policy: String,
+    pub risk_threshold: Option<f64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Recorded prompt-injection attempt
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SecurityViolation {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub score: f64,
+    pub matched_rules: String,
+    pub action: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Data for recording a new security violation
+#[derive(Debug, Clone)]
+pub struct NewSecurityViolation {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub score: f64,
+    pub matched_rules: String,
+    pub action: String,
+    pub content: String,
+}
+
+/// Reusable persona that accounts can be bound to
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Persona {
+    pub id: i64,
+    pub name: String,
+    pub prompt: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub rotation_weight: i64,
+    /// Reply post-processing rules as JSON
+    pub postprocess: Option<String>,
+    /// Time-of-day variants as JSON
+    pub time_variants: Option<String>,
+    /// What its conversations leave in long-term memory: all, user_only or none
+    pub memory_write: String,
+    /// Persona whose prompt and settings this one extends
+    pub base_id: Option<i64>,
+    /// Version of the PERSONAS_DIR file it was last imported from
+    pub file_version: Option<i64>,
+    /// Capabilities (see `ai::capabilities`): web search, pictures, polls and pins, voice notes
+    pub can_search_web: bool,
+    pub can_generate_images: bool,
+    pub can_use_tools: bool,
+    pub can_send_voice: bool,
+}
+
+/// Data for creating a new persona
+#[derive(Debug, Clone)]
+pub struct NewPersona {
+    pub name: String,
+    pub prompt: String,
+}
+
+/// A dialog known to a userbot account, with per-chat overrides
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AccountChat {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub title: String,
+    pub chat_type: String,
+    pub is_denied: bool,
+    pub reply_probability: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+    pub format_mode: String,
+    pub initiative_enabled: bool,
+    pub initiative_day: Option<String>,
+    pub initiative_count: i64,
+    pub last_initiative_at: Option<DateTime<Utc>>,
+    pub rotation_mode: String,
+    pub rotation_tag: Option<String>,
+    pub active_persona_id: Option<i64>,
+    pub persona_since: Option<DateTime<Utc>>,
+    pub digest_enabled: bool,
+    pub digest_weekday: i64,
+    pub digest_hour: i64,
+    pub last_digest_at: Option<DateTime<Utc>>,
+    pub timezone: Option<String>,
+    pub karma_enabled: bool,
+    pub karma_monthly_reset: bool,
+    pub karma_period: Option<String>,
+    pub retention_days: Option<i64>,
+    pub retention_max_messages: Option<i64>,
+    pub paused_until: Option<DateTime<Utc>>,
+    pub paused_reason: Option<String>,
+    pub pinned_persona_id: Option<i64>,
+    pub reply_cooldown_secs: Option<i64>,
+    pub style_notes: Option<String>,
+    pub profile_name: Option<String>,
+    pub rag_prefer_sender: bool,
+    pub rag_exclude_bots: bool,
+    pub rag_max_age_days: Option<i64>,
+    /// Comma-separated words that always get a reply, regardless of probability
+    pub reply_triggers: Option<String>,
+    pub onboarded_at: Option<DateTime<Utc>>,
+    /// Replies delete themselves after this many minutes
+    pub ephemeral_minutes: Option<i64>,
+    pub answer_cache_enabled: bool,
+    /// Overrides DEBOUNCE_MS for this chat
+    pub debounce_ms: Option<i64>,
+    /// Overrides DEBOUNCE_MAX_BATCH for this chat
+    pub debounce_max_batch: Option<i64>,
+    /// What happens to messages missed while offline: off, summary or post
+    pub catchup_mode: String,
+    /// Bounds for auto-tuning reply_probability; tuning is off without them
+    pub tune_min_probability: Option<i64>,
+    pub tune_max_probability: Option<i64>,
+    /// Locale replies are normalized to (numbers, dates, money, units)
+    pub locale: Option<String>,
+    /// Overrides the locale's metric or imperial units
+    pub units: Option<String>,
+    /// keep or latin
+    pub transliteration: String,
+    /// Local "23:00-08:00" window without auto-replies
+    pub quiet_hours: Option<String>,
+    /// Direct mentions are still answered during quiet hours
+    pub quiet_allow_mentions: bool,
+    /// "Make a poll" requests get a native poll
+    pub polls_enabled: bool,
+    /// History before this isn't replayed to the model (set when another persona takes over)
+    pub context_since: Option<DateTime<Utc>>,
+    /// What was said before `context_since`
+    pub context_summary: Option<String>,
+    /// The new persona mentions a switch in its first reply
+    pub announce_persona_switch: bool,
+    /// Members can start trivia and word games with /game
+    pub games_enabled: bool,
+    /// Voice and video notes mentioning one of these (comma-separated) are forwarded to the owner
+    pub escalation_keywords: Option<String>,
+    /// The persona may pin messages it finds important
+    pub pins_enabled: bool,
+    /// Recall what consenting members said in private when they talk here
+    pub rag_private_recall: bool,
+    /// Embed bot-authored messages into memory; `None` follows EMBED_BOT_MESSAGES
+    pub embed_bot_messages: Option<bool>,
+    /// Photos, GIFs and video notes may be sent to the vision model
+    pub vision_enabled: bool,
+    /// Voice notes may be sent to Whisper
+    pub voice_enabled: bool,
+    /// Questions may be looked up on the web
+    pub web_search_enabled: bool,
+}
+
+impl AccountChat {
+    /// Whether replies are paused in this chat right now
+    pub fn is_paused(&self) -> bool {
+        self.paused_until.is_some_and(|until| until > Utc::now())
+    }
+
+    /// Parse reply_triggers into a list of lowercase words
+    pub fn get_triggers(&self) -> Vec<String> {
+        self.reply_triggers
+            .as_deref()
+            .map(crate::userbot::onboarding::parse_triggers)
+            .unwrap_or_default()
+    }
+
+    pub fn get_escalation_keywords(&self) -> Vec<String> {
+        self.escalation_keywords
+            .as_deref()
+            .map(crate::userbot::onboarding::parse_triggers)
+            .unwrap_or_default()
+    }
+}
+
+/// Message count per sender in a chat, for the weekly digest
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MemberActivity {
+    pub sender_id: i64,
+    pub messages: i64,
+}
+
+/// One of the userbot's own messages and how many times members replied to it
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BotMessageResponses {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub content: String,
+    pub responses: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// An own reply members answered, with the message it answered itself
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct AnsweredReply {
+    pub persona_id: Option<i64>,
+    pub responses: i64,
+    pub reply: String,
+    /// None when the reply started the conversation
+    pub user_text: Option<String>,
+}
+
+/// Reply count per chat, for the "top chats" stats
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatReplyStats {
+    pub chat_id: i64,
+    pub replies: i64,
+}
+
+/// How a userbot account knows a particular person
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Relationship {
+    pub account_id: i64,
+    pub peer_user_id: i64,
+    pub how_known: String,
+    pub familiarity: f64,
+    pub inside_jokes: String,
+    pub message_count: i64,
+    pub refreshed_at_count: i64,
+    pub last_interaction_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Relationship {
+    /// Parse inside_jokes JSON into a Vec
+    pub fn get_inside_jokes(&self) -> Vec<String> {
+        serde_json::from_str(&self.inside_jokes).unwrap_or_default()
+    }
+}
+
+/// Nightly rotation among the personas sharing a tag
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PersonaTagRotation {
+    pub tag: String,
+    pub enabled: bool,
+    pub last_rotated_at: Option<DateTime<Utc>>,
+}
+
+/// Reply count per persona, for rotation analytics
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PersonaReplyStats {
+    pub persona_id: Option<i64>,
+    pub persona_name: Option<String>,
+    pub replies: i64,
+}
+
+/// A member's karma in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Karma {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub points: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A question of a game in a chat, open until someone answers it or time runs out
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct GameRound {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub kind: String,
+    pub question: String,
+    pub answer: String,
+    /// Other accepted answers as a JSON array
+    pub alternatives: Option<String>,
+    pub message_id: Option<i64>,
+    pub deadline_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub winner_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A member's game points in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct GameScore {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub user_id: i64,
+    pub points: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A Telegram Stars payment received by the admin bot
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Payment {
+    pub id: i64,
+    pub user_id: i64,
+    pub feature: String,
+    pub stars: i64,
+    pub days: Option<i64>,
+    pub telegram_charge_id: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A premium feature a user is allowed to use
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Entitlement {
+    pub user_id: i64,
+    pub feature: String,
+    pub source: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub granted_at: DateTime<Utc>,
+}
+
+impl Entitlement {
+    pub fn is_active(&self) -> bool {
+        self.expires_at.map_or(true, |at| at > Utc::now())
+    }
+}
+
+/// A model the bot has used, with its embedding dimension if any
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ModelInfo {
+    pub kind: String,
+    pub name: String,
+    pub dimension: Option<i64>,
+    pub uses: i64,
+    pub first_used_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+}
+
+/// A vision prompt override; persona_id 0 applies to every persona
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VisionPrompt {
+    pub media_type: String,
+    pub persona_id: i64,
+    pub template: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A named preset of per-chat settings; unset fields leave the chat's value alone
+#[derive(Debug, Clone, Default, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatProfile {
+    pub name: String,
+    pub persona_id: Option<i64>,
+    pub reply_probability: Option<i64>,
+    pub format_mode: Option<String>,
+    pub initiative_enabled: Option<bool>,
+    pub reply_cooldown_secs: Option<i64>,
+    pub style_notes: Option<String>,
+    #[serde(skip)]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip)]
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A generated reply the owner edited or deleted in ghost mode
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReplyCorrection {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub prompt: Option<String>,
+    pub rejected: String,
+    /// None when the reply was deleted outright
+    pub corrected: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Example exchange (user → persona reply) used as a few-shot example
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct PersonaExample {
+    pub id: i64,
+    pub persona_id: i64,
+    pub user_text: String,
+    pub reply: String,
+    #[serde(skip)]
+    pub embedding: Vec<u8>,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Topic a chat was discussing at some point
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatTopic {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub topic: String,
+    #[serde(skip)]
+    pub embedding: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A Telegram Business account connected to the business bot, with its auto-responder settings
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BusinessConnection {
+    pub connection_id: String,
+    pub user_id: i64,
+    pub user_chat_id: i64,
+    pub can_reply: bool,
+    pub is_enabled: bool,
+    pub auto_reply: bool,
+    pub persona_id: Option<i64>,
+    pub reply_mode: String,
+    pub work_days: String,
+    pub work_start_hour: i64,
+    pub work_end_hour: i64,
+    pub timezone: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BusinessConnection {
+    /// Working weekdays, 0 = Monday
+    pub fn get_work_days(&self) -> Vec<u32> {
+        self.work_days
+            .split(',')
+            .filter_map(|d| d.trim().parse().ok())
+            .filter(|d| *d < 7)
+            .collect()
+    }
+}
+
+/// A message of a business chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct BusinessMessage {
+    pub id: i64,
+    pub connection_id: String,
+    pub chat_id: i64,
+    pub role: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A sent reply waiting to be deleted
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct EphemeralMessage {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub message_id: i64,
+    pub delete_at: DateTime<Utc>,
+}
+
+/// A generated answer to a question, reused for near-identical questions
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CachedAnswer {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub persona_id: Option<i64>,
+    pub question: String,
+    #[serde(skip)]
+    pub embedding: Vec<u8>,
+    pub answer: String,
+    pub hits: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Daily limit and usage of one media processing kind in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct MediaQuota {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub kind: String,
+    pub daily_limit: Option<i64>,
+    pub used: i64,
+    pub day: String,
+}
+
+impl MediaQuota {
+    /// Usage counted for the given day
+    pub fn used_on(&self, day: &str) -> i64 {
+        if self.day == day {
+            self.used
+        } else {
+            0
+        }
+    }
+}
+
+/// An RSS/Atom feed subscribed to in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Feed {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub url: String,
+    pub title: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub seen_ids: String,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Feed {
+    /// Parse seen_ids into item ids
+    pub fn get_seen_ids(&self) -> Vec<String> {
+        serde_json::from_str(&self.seen_ids).unwrap_or_default()
+    }
+}
+
+/// An external endpoint events are POSTed to
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    pub events: String,
+    #[serde(skip)]
+    pub secret: String,
+    pub keywords: Option<String>,
+    pub is_active: bool,
+    pub last_status: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    /// Whether the webhook subscribed to an event
+    pub fn wants(&self, event: &str) -> bool {
+        self.events.split(',').map(str::trim).any(|e| e == "*" || e == event)
+    }
+
+    /// Parse keywords into a list of lowercase words
+    pub fn get_keywords(&self) -> Vec<String> {
+        self.keywords
+            .as_deref()
+            .map(crate::userbot::onboarding::parse_triggers)
+            .unwrap_or_default()
+    }
+}
+
+/// A webhook call waiting to be sent or retried
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i64,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i64,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A person, place or project mentioned in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatEntity {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub name: String,
+    pub name_key: String,
+    pub kind: String,
+    pub description: Option<String>,
+    pub mentions: i64,
+    pub last_message_id: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A relation between two entities, as one line of text
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct EntityRelation {
+    pub id: i64,
+    pub subject: String,
+    pub relation: String,
+    pub object: String,
+    /// Message the relation was extracted from
+    pub message_id: Option<i64>,
+}
+
+/// Engagement of a tuned chat at its current reply probability
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TuningEpoch {
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub probability: i64,
+    pub replies_to_bot: i64,
+    pub negatives: i64,
+    pub started_at: DateTime<Utc>,
+}
+
+/// How one reply probability has done in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TuningArm {
+    pub probability: i64,
+    pub pulls: i64,
+    pub reward_sum: f64,
+}
+
+/// A reply probability change made by the tuner
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TuningDecision {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub old_probability: i64,
+    pub new_probability: i64,
+    pub reward: f64,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One admin command's change to a chat's settings
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatSettingChange {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub actor_id: i64,
+    pub command: String,
+    pub old_values: Option<String>,
+    pub new_values: String,
+    pub undone: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Stage timings of one sent reply
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReplyTraceRecord {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub correlation_id: String,
+    pub incoming_message_id: i64,
+    pub reply_message_id: Option<i64>,
+    pub stages: String,
+    pub total_ms: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A running or finished /embed_backlog backfill
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct EmbedBacklogJob {
+    pub id: i64,
+    pub chat_id: Option<i64>,
+    pub report_chat_id: i64,
+    pub last_message_id: i64,
+    pub scanned: i64,
+    pub embedded: i64,
+    pub failed: i64,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A conversation handed over to a human
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Handoff {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub user_id: Option<i64>,
+    pub reason: String,
+    pub expires_at: DateTime<Utc>,
+    pub resumed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A routing rule: conditions on a message and what to do when they hold (/rule_add)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatRule {
+    pub id: i64,
+    pub account_id: Option<i64>,
+    pub chat_id: Option<i64>,
+    pub sender_id: Option<i64>,
+    pub pattern: Option<String>,
+    pub persona_id: Option<i64>,
+    pub temperature: Option<f64>,
+    pub reply: Option<String>,
+    pub priority: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Data for creating a routing rule
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NewChatRule {
+    pub account_id: Option<i64>,
+    pub chat_id: Option<i64>,
+    pub sender_id: Option<i64>,
+    pub pattern: Option<String>,
+    pub persona_id: Option<i64>,
+    pub temperature: Option<f64>,
+    pub reply: Option<String>,
+    pub priority: i64,
+}
+
+/// Two chats an account mirrors into each other with translation (/bridge_add)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatBridge {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_a: i64,
+    pub chat_b: i64,
+    pub lang_a: String,
+    pub lang_b: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A moderation action a userbot took in a chat
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub action: String,
+    pub message_id: Option<i64>,
+    pub actor: String,
+    pub ok: bool,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A persona's catchphrase sticker pack (/sticker_pack)
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct StickerPack {
+    pub persona_id: i64,
+    pub account_id: i64,
+    /// Short name, as in t.me/addstickers/<name>
+    pub name: String,
+    pub title: String,
+    pub phrases: String,
+    pub refreshed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl StickerPack {
+    /// Parse phrases JSON into a Vec
+    pub fn get_phrases(&self) -> Vec<String> {
+        serde_json::from_str(&self.phrases).unwrap_or_default()
+    }
+}
+
+/// A stored summary of a stretch of a chat's conversation
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ChatSummary {
+    pub id: i64,
+    pub account_id: i64,
+    pub chat_id: i64,
+    pub kind: String,
+    pub first_message_id: i64,
+    pub last_message_id: i64,
+    pub summary: String,
+    pub created_at: DateTime<Utc>,
+}
@@ -145,6 +145,29 @@ impl AccountRepository {
         Ok(())
     }
 
+    /// Update account's reply probability for auto-forwarded channel posts
+    pub async fn update_channel_post_probability(
+        pool: &SqlitePool,
+        account_id: i64,
+        probability: i64,
+    ) -> Result<()> {
+        if !(0..=100).contains(&probability) {
+            anyhow::bail!("Channel post probability must be between 0 and 100");
+        }
+
+        sqlx::query(
+            "UPDATE accounts SET channel_post_reply_probability = ? WHERE id = ?"
+        )
+        .bind(probability)
+        .bind(account_id)
+        .execute(pool)
+        .await
+        .context("Failed to update channel post probability")?;
+
+        tracing::info!("Updated channel post probability for account {} to {}", account_id, probability);
+        Ok(())
+    }
+
     /// Add a chat to the allowed chats list
     pub async fn add_allowed_chat(
         pool: &SqlitePool,
@@ -211,8 +234,8 @@ impl MessageRepository {
     pub async fn create(pool: &SqlitePool, new_message: NewMessage) -> Result<MessageHistory> {
         let message = sqlx::query_as::<_, MessageHistory>(
             r#"
-            INSERT INTO messages_history (account_id, chat_id, role, content)
-            VALUES (?, ?, ?, ?)
+            INSERT INTO messages_history (account_id, chat_id, role, content, sender_id, sender_chat_id, persona_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
             RETURNING *
             "#,
         )
@@ -220,6 +243,9 @@ impl MessageRepository {
         .bind(new_message.chat_id)
         .bind(new_message.role.as_str())
         .bind(&new_message.content)
+        .bind(new_message.sender_id)
+        .bind(new_message.sender_chat_id)
+        .bind(new_message.persona_id)
         .fetch_one(pool)
         .await
         .context("Failed to create message")?;
@@ -237,7 +263,7 @@ impl MessageRepository {
         let messages = sqlx::query_as::<_, MessageHistory>(
             r#"
             SELECT * FROM messages_history
-            WHERE account_id = ? AND chat_id = ?
+            WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
             ORDER BY created_at DESC
             LIMIT ?
             "#,
@@ -253,10 +279,10 @@ impl MessageRepository {
         Ok(messages.into_iter().rev().collect())
     }
 
-    /// Get total message count for an account
+    /// Get total message count for an account, archived ones included
     pub async fn count_by_account(pool: &SqlitePool, account_id: i64) -> Result<i64> {
         let count: (i64,) = sqlx::query_as(
-            "SELECT COUNT(*) FROM messages_history WHERE account_id = ?"
+            "SELECT COUNT(*) FROM messages_all WHERE account_id = ?"
         )
         .bind(account_id)
         .fetch_one(pool)
@@ -266,267 +292,5591 @@ impl MessageRepository {
         Ok(count.0)
     }
 
-    /// Delete old messages (cleanup)
-    pub async fn delete_older_than(pool: &SqlitePool, days: i64) -> Result<u64> {
-        let result = sqlx::query(
-            r#"
-            DELETE FROM messages_history
-            WHERE created_at < datetime('now', '-' || ? || ' days')
-            "#,
-        )
-        .bind(days)
-        .execute(pool)
-        .await
-        .context("Failed to delete old messages")?;
-
-        tracing::info!("Deleted {} old messages", result.rows_affected());
-        Ok(result.rows_affected())
-    }
-}
-
-impl AccountRepository {
-    /// Add a message to history (convenience method)
-    pub async fn add_message(pool: &SqlitePool, new_message: NewMessage) -> Result<MessageHistory> {
-        MessageRepository::create(pool, new_message).await
-    }
-
-    /// Get recent messages for context (convenience method)
-    pub async fn get_recent_messages(
+    /// The newest message in a chat, if any; a chat quiet for long may only have archived ones
+    pub async fn get_last_message(
         pool: &SqlitePool,
         account_id: i64,
         chat_id: i64,
-        limit: i64,
-    ) -> Result<Vec<MessageHistory>> {
-        MessageRepository::get_recent_messages(pool, account_id, chat_id, limit).await
-    }
-}
+    ) -> Result<Option<MessageHistory>> {
+        for table in ["messages_history", "messages_archive"] {
+            let sql = format!(
+                "SELECT * FROM {} WHERE account_id = ? AND chat_id = ? AND is_hashed = 0 ORDER BY created_at DESC LIMIT 1",
+                table
+            );
+            let message = sqlx::query_as::<_, MessageHistory>(&sql)
+                .bind(account_id)
+                .bind(chat_id)
+                .fetch_optional(pool)
+                .await
+                .context("Failed to fetch last message")?;
+            if message.is_some() {
+                return Ok(message);
+            }
+        }
 
-/// Repository for bot group operations
-pub struct BotGroupRepository;
+        Ok(None)
+    }
 
-impl BotGroupRepository {
-    /// Create a new bot group
-    pub async fn create(pool: &SqlitePool, new_group: NewBotGroup) -> Result<BotGroup> {
-        let group = sqlx::query_as::<_, BotGroup>(
+    /// Messages per account and role over the last hours, for activity stats
+    pub async fn activity_since(pool: &SqlitePool, hours: i64) -> Result<Vec<(i64, String, i64)>> {
+        let rows = sqlx::query_as::<_, (i64, String, i64)>(
             r#"
-            INSERT INTO bot_groups (name, description)
-            VALUES (?, ?)
-            RETURNING *
+            SELECT account_id, role, COUNT(*) FROM messages_history
+            WHERE created_at >= datetime('now', '-' || ? || ' hours')
+            GROUP BY account_id, role
+            ORDER BY account_id
             "#,
         )
-        .bind(&new_group.name)
-        .bind(&new_group.description)
-        .fetch_one(pool)
+        .bind(hours)
+        .fetch_all(pool)
         .await
-        .context("Failed to create bot group")?;
+        .context("Failed to count recent messages")?;
 
-        tracing::info!("Created bot group '{}' with ID {}", group.name, group.id);
-        Ok(group)
+        Ok(rows)
     }
 
-    /// Get a bot group by ID
-    pub async fn get_by_id(pool: &SqlitePool, id: i64) -> Result<Option<BotGroup>> {
-        let group = sqlx::query_as::<_, BotGroup>(
-            "SELECT * FROM bot_groups WHERE id = ?"
+    /// Messages per role in each of the last minutes, as (minutes ago, role, count)
+    pub async fn per_minute(pool: &SqlitePool, minutes: i64) -> Result<Vec<(i64, String, i64)>> {
+        let rows = sqlx::query_as::<_, (i64, String, i64)>(
+            r#"
+            SELECT (strftime('%s', 'now') - strftime('%s', created_at)) / 60 AS ago, role, COUNT(*)
+            FROM messages_history
+            WHERE created_at >= datetime('now', '-' || ? || ' minutes')
+            GROUP BY ago, role
+            "#,
         )
-        .bind(id)
-        .fetch_optional(pool)
+        .bind(minutes)
+        .fetch_all(pool)
         .await
-        .context("Failed to fetch bot group")?;
+        .context("Failed to count messages per minute")?;
 
-        Ok(group)
+        Ok(rows)
     }
 
-    /// List all bot groups
-    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<BotGroup>> {
-        let groups = sqlx::query_as::<_, BotGroup>(
-            "SELECT * FROM bot_groups ORDER BY created_at DESC"
+    /// The newest replies written by a persona, newest first
+    pub async fn recent_persona_replies(pool: &SqlitePool, persona_id: i64, limit: i64) -> Result<Vec<String>> {
+        let replies = sqlx::query_scalar::<_, String>(
+            r#"
+            SELECT content FROM messages_history
+            WHERE persona_id = ? AND role = 'assistant' AND is_hashed = 0
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
         )
+        .bind(persona_id)
+        .bind(limit)
         .fetch_all(pool)
         .await
-        .context("Failed to list bot groups")?;
+        .context("Failed to fetch persona replies")?;
 
-        Ok(groups)
+        Ok(replies)
     }
 
-    /// Add an account to a bot group
-    pub async fn add_member(pool: &SqlitePool, group_id: i64, account_id: i64) -> Result<()> {
-        sqlx::query(
-            "INSERT OR IGNORE INTO bot_group_members (group_id, account_id) VALUES (?, ?)"
+    /// The newest stored reply containing a sent message.
+    /// A stored reply may have been sent as several messages.
+    pub async fn find_reply(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        sent_text: &str,
+    ) -> Result<Option<MessageHistory>> {
+        let reply = sqlx::query_as::<_, MessageHistory>(
+            r#"
+            SELECT * FROM messages_history
+            WHERE account_id = ? AND chat_id = ? AND role = 'assistant' AND instr(content, ?) > 0
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
         )
-        .bind(group_id)
         .bind(account_id)
-        .execute(pool)
+        .bind(chat_id)
+        .bind(sent_text)
+        .fetch_optional(pool)
         .await
-        .context("Failed to add member to bot group")?;
+        .context("Failed to find stored reply")?;
+
+        Ok(reply)
+    }
+
+    /// Rewrite (or drop, with `None`) one sent message inside a stored reply,
+    /// so corrected replies don't linger in the conversation context
+    pub async fn replace_in_reply(pool: &SqlitePool, id: i64, sent_text: &str, replacement: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE messages_history SET content = TRIM(REPLACE(content, ?, ?)) WHERE id = ?")
+            .bind(sent_text)
+            .bind(replacement.unwrap_or(""))
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update stored reply")?;
+
+        sqlx::query("DELETE FROM messages_history WHERE id = ? AND content = ''")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to drop emptied reply")?;
 
-        tracing::info!("Added account {} to bot group {}", account_id, group_id);
         Ok(())
     }
 
-    /// Remove an account from a bot group
-    pub async fn remove_member(pool: &SqlitePool, group_id: i64, account_id: i64) -> Result<()> {
-        sqlx::query(
-            "DELETE FROM bot_group_members WHERE group_id = ? AND account_id = ?"
+    /// When the account last replied in a chat, if ever
+    pub async fn last_reply_at(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+    ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
+        let last: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(last) FROM (
+                SELECT MAX(created_at) AS last FROM messages_history
+                WHERE account_id = ?1 AND chat_id = ?2 AND role = 'assistant'
+                UNION ALL
+                SELECT MAX(created_at) FROM messages_archive
+                WHERE account_id = ?1 AND chat_id = ?2 AND role = 'assistant'
+            )
+            "#,
         )
-        .bind(group_id)
         .bind(account_id)
-        .execute(pool)
+        .bind(chat_id)
+        .fetch_one(pool)
         .await
-        .context("Failed to remove member from bot group")?;
+        .context("Failed to fetch last reply time")?;
 
-        tracing::info!("Removed account {} from bot group {}", account_id, group_id);
-        Ok(())
+        Ok(last)
     }
 
-    /// Get all members of a bot group
-    pub async fn get_members(pool: &SqlitePool, group_id: i64) -> Result<Vec<Account>> {
-        let accounts = sqlx::query_as::<_, Account>(
+    /// Recent messages of a chat after its context boundary (all of them if it has none)
+    pub async fn get_context_messages(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        limit: i64,
+    ) -> Result<Vec<MessageHistory>> {
+        let messages = sqlx::query_as::<_, MessageHistory>(
             r#"
-            SELECT a.* FROM accounts a
-            INNER JOIN bot_group_members bgm ON a.id = bgm.account_id
-            WHERE bgm.group_id = ?
-            ORDER BY a.id
+            SELECT * FROM messages_history
+            WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
+            AND created_at >= COALESCE(
+                (SELECT context_since FROM account_chats WHERE account_id = ? AND chat_id = ?), ''
+            )
+            ORDER BY created_at DESC
+            LIMIT ?
             "#,
         )
-        .bind(group_id)
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
         .fetch_all(pool)
         .await
-        .context("Failed to fetch bot group members")?;
+        .context("Failed to fetch context messages")?;
 
-        Ok(accounts)
+        Ok(messages.into_iter().rev().collect())
     }
 
-    /// Delete a bot group
-    pub async fn delete(pool: &SqlitePool, group_id: i64) -> Result<()> {
-        sqlx::query("DELETE FROM bot_groups WHERE id = ?")
-            .bind(group_id)
-            .execute(pool)
-            .await
-            .context("Failed to delete bot group")?;
+    /// Most recent messages a given user sent to an account, across all chats
+    /// Private chats count only with `include_private`; group chats always do.
+    pub async fn get_recent_from_sender(
+        pool: &SqlitePool,
+        account_id: i64,
+        sender_id: i64,
+        include_private: bool,
+        limit: i64,
+    ) -> Result<Vec<MessageHistory>> {
+        let messages = sqlx::query_as::<_, MessageHistory>(
+            r#"
+            SELECT * FROM messages_history
+            WHERE account_id = ? AND sender_id = ? AND is_hashed = 0 AND (? OR chat_id < 0)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(sender_id)
+        .bind(include_private)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch messages from sender")?;
 
-        tracing::info!("Deleted bot group {}", group_id);
-        Ok(())
+        Ok(messages.into_iter().rev().collect())
     }
-}
-
-/// Repository for spam campaign operations
-pub struct SpamCampaignRepository;
 
-impl SpamCampaignRepository {
-    /// Create a new spam campaign
-    pub async fn create(pool: &SqlitePool, new_campaign: NewSpamCampaign) -> Result<SpamCampaign> {
-        let campaign = sqlx::query_as::<_, SpamCampaign>(
+    /// Replies per persona for an account
+    pub async fn persona_reply_stats(pool: &SqlitePool, account_id: i64) -> Result<Vec<PersonaReplyStats>> {
+        let stats = sqlx::query_as::<_, PersonaReplyStats>(
             r#"
-            INSERT INTO spam_campaigns (
-                name, group_id, target_type, target_id, message_text,
-                media_path, media_type, repeat_count, delay_between_ms
-            )
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-            RETURNING *
+            SELECT m.persona_id, p.name AS persona_name, COUNT(*) AS replies
+            FROM messages_all m
+            LEFT JOIN personas p ON p.id = m.persona_id
+            WHERE m.account_id = ? AND m.role = 'assistant'
+            GROUP BY m.persona_id
+            ORDER BY replies DESC
             "#,
         )
-        .bind(&new_campaign.name)
-        .bind(new_campaign.group_id)
-        .bind(&new_campaign.target_type)
-        .bind(new_campaign.target_id)
-        .bind(&new_campaign.message_text)
-        .bind(&new_campaign.media_path)
-        .bind(&new_campaign.media_type)
-        .bind(new_campaign.repeat_count)
-        .bind(new_campaign.delay_between_ms)
-        .fetch_one(pool)
+        .bind(account_id)
+        .fetch_all(pool)
         .await
-        .context("Failed to create spam campaign")?;
+        .context("Failed to fetch persona reply stats")?;
 
-        tracing::info!("Created spam campaign '{}' with ID {}", campaign.name, campaign.id);
-        Ok(campaign)
+        Ok(stats)
     }
 
-    /// Get a spam campaign by ID
-    pub async fn get_by_id(pool: &SqlitePool, id: i64) -> Result<Option<SpamCampaign>> {
-        let campaign = sqlx::query_as::<_, SpamCampaign>(
-            "SELECT * FROM spam_campaigns WHERE id = ?"
+    /// Replies a persona wrote between `from_days` and `to_days` days ago, newest first, with when they were written
+    pub async fn persona_replies(
+        pool: &SqlitePool,
+        persona_id: i64,
+        from_days: i64,
+        to_days: i64,
+        limit: i64,
+    ) -> Result<Vec<(String, chrono::DateTime<chrono::Utc>)>> {
+        let replies = sqlx::query_as(
+            r#"
+            SELECT content, created_at FROM messages_all
+            WHERE persona_id = ? AND role = 'assistant' AND is_hashed = 0
+              AND created_at >= datetime('now', '-' || ? || ' days')
+              AND created_at < datetime('now', '-' || ? || ' days')
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
         )
-        .bind(id)
-        .fetch_optional(pool)
+        .bind(persona_id)
+        .bind(from_days)
+        .bind(to_days)
+        .bind(limit)
+        .fetch_all(pool)
         .await
-        .context("Failed to fetch spam campaign")?;
+        .context("Failed to fetch persona replies")?;
 
-        Ok(campaign)
+        Ok(replies)
     }
 
-    /// List all spam campaigns
-    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<SpamCampaign>> {
-        let campaigns = sqlx::query_as::<_, SpamCampaign>(
-            "SELECT * FROM spam_campaigns ORDER BY created_at DESC"
+    /// Latest readable messages of a chat, only the account's own replies with `own_only`
+    pub async fn chat_texts(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        own_only: bool,
+        limit: i64,
+    ) -> Result<Vec<String>> {
+        let texts = sqlx::query_scalar(
+            r#"
+            SELECT content FROM messages_all
+            WHERE account_id = ? AND chat_id = ? AND is_hashed = 0
+              AND (role = 'assistant' OR ? = 0)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
         )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(own_only)
+        .bind(limit)
         .fetch_all(pool)
         .await
-        .context("Failed to list spam campaigns")?;
+        .context("Failed to fetch chat texts")?;
 
-        Ok(campaigns)
+        Ok(texts)
     }
 
-    /// List pending spam campaigns
-    pub async fn list_pending(pool: &SqlitePool) -> Result<Vec<SpamCampaign>> {
-        let campaigns = sqlx::query_as::<_, SpamCampaign>(
-            "SELECT * FROM spam_campaigns WHERE status = 'pending' ORDER BY created_at"
+    /// Chats where an account sent the most replies
+    pub async fn top_reply_chats(
+        pool: &SqlitePool,
+        account_id: i64,
+        limit: i64,
+    ) -> Result<Vec<ChatReplyStats>> {
+        let stats = sqlx::query_as::<_, ChatReplyStats>(
+            r#"
+            SELECT chat_id, COUNT(*) AS replies FROM messages_all
+            WHERE account_id = ? AND role = 'assistant'
+            GROUP BY chat_id
+            ORDER BY replies DESC
+            LIMIT ?
+            "#,
         )
+        .bind(account_id)
+        .bind(limit)
         .fetch_all(pool)
         .await
-        .context("Failed to list pending spam campaigns")?;
+        .context("Failed to fetch reply stats")?;
+
+        Ok(stats)
+    }
+
+    /// Count a message of a member towards today's activity, whether it was answered or not
+    pub async fn record_member_message(pool: &SqlitePool, account_id: i64, chat_id: i64, sender_id: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO member_activity (account_id, chat_id, sender_id, day, messages)
+            VALUES (?, ?, ?, date('now'), 1)
+            ON CONFLICT(account_id, chat_id, sender_id, day) DO UPDATE SET messages = messages + 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(sender_id)
+        .execute(pool)
+        .await
+        .context("Failed to record member activity")?;
+
+        Ok(())
+    }
+
+    /// Most active members of a chat over the last `days` days
+    pub async fn top_senders(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        days: i64,
+        limit: i64,
+    ) -> Result<Vec<MemberActivity>> {
+        let members = sqlx::query_as::<_, MemberActivity>(
+            r#"
+            SELECT sender_id, SUM(messages) AS messages FROM member_activity
+            WHERE account_id = ? AND chat_id = ? AND day >= date('now', '-' || ? || ' days')
+            GROUP BY sender_id
+            ORDER BY messages DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(days)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch member activity")?;
+
+        Ok(members)
+    }
+
+    /// Count a member's reply to one of the userbot's own messages
+    pub async fn record_response(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        message_id: i64,
+        content: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO bot_message_responses (account_id, chat_id, message_id, content, responses)
+            VALUES (?, ?, ?, ?, 1)
+            ON CONFLICT(account_id, chat_id, message_id) DO UPDATE SET
+                responses = responses + 1,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(message_id)
+        .bind(content)
+        .execute(pool)
+        .await
+        .context("Failed to record response")?;
+
+        Ok(())
+    }
+
+    /// How many times members replied to one of the userbot's messages
+    pub async fn responses_to(pool: &SqlitePool, account_id: i64, chat_id: i64, message_id: i64) -> Result<i64> {
+        let responses: Option<i64> = sqlx::query_scalar(
+            "SELECT responses FROM bot_message_responses WHERE account_id = ? AND chat_id = ? AND message_id = ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(message_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch response count")?;
+
+        Ok(responses.unwrap_or(0))
+    }
+
+    /// The userbot's message members answered most over the last `days` days
+    pub async fn top_answered(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        days: i64,
+    ) -> Result<Option<BotMessageResponses>> {
+        let message = sqlx::query_as::<_, BotMessageResponses>(
+            r#"
+            SELECT * FROM bot_message_responses
+            WHERE account_id = ? AND chat_id = ? AND created_at >= datetime('now', '-' || ? || ' days')
+              AND responses > 0
+            ORDER BY responses DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(days)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch most answered message")?;
+
+        Ok(message)
+    }
+
+    /// Own replies members answered at least `min_responses` times, with the user message they answered
+    pub async fn answered_replies(
+        pool: &SqlitePool,
+        persona_id: Option<i64>,
+        min_responses: i64,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<AnsweredReply>> {
+        // Responses are counted per sent message, which may be one part of a split reply
+        let replies = sqlx::query_as::<_, AnsweredReply>(
+            r#"
+            SELECT m.persona_id, r.responses, r.content AS reply,
+                (SELECT u.content FROM messages_all u
+                 WHERE u.account_id = m.account_id AND u.chat_id = m.chat_id
+                   AND u.role = 'user' AND u.is_hashed = 0 AND u.id < m.id
+                 ORDER BY u.id DESC LIMIT 1) AS user_text
+            FROM bot_message_responses r
+            JOIN messages_all m ON m.id = (
+                SELECT a.id FROM messages_all a
+                WHERE a.account_id = r.account_id AND a.chat_id = r.chat_id AND a.role = 'assistant'
+                  AND a.is_hashed = 0 AND instr(a.content, r.content) > 0
+                ORDER BY a.id DESC LIMIT 1
+            )
+            WHERE r.responses >= ?1
+              AND (?2 IS NULL OR m.persona_id = ?2)
+              AND (?3 IS NULL OR date(r.created_at) >= ?3)
+              AND (?4 IS NULL OR date(r.created_at) <= ?4)
+            ORDER BY r.responses DESC
+            "#,
+        )
+        .bind(min_responses)
+        .bind(persona_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch answered replies")?;
+
+        Ok(replies)
+    }
+
+    /// Delete a chat's messages older than `days`, in both tiers
+    pub async fn purge_chat_older_than(pool: &SqlitePool, account_id: i64, chat_id: i64, days: i64) -> Result<u64> {
+        let mut deleted = 0;
+        for table in ["messages_history", "messages_archive"] {
+            let sql = format!(
+                "DELETE FROM {} WHERE account_id = ? AND chat_id = ? AND created_at < datetime('now', '-' || ? || ' days')",
+                table
+            );
+            let result = sqlx::query(&sql)
+                .bind(account_id)
+                .bind(chat_id)
+                .bind(days)
+                .execute(pool)
+                .await
+                .context("Failed to purge chat messages")?;
+            deleted += result.rows_affected();
+        }
+
+        Ok(deleted)
+    }
+
+    /// Keep only the newest `keep` messages of a chat, counting both tiers
+    pub async fn purge_chat_excess(pool: &SqlitePool, account_id: i64, chat_id: i64, keep: i64) -> Result<u64> {
+        let mut deleted = 0;
+        for table in ["messages_history", "messages_archive"] {
+            let sql = format!(
+                r#"
+                DELETE FROM {}
+                WHERE account_id = ?1 AND chat_id = ?2
+                AND id NOT IN (
+                    SELECT id FROM messages_all
+                    WHERE account_id = ?1 AND chat_id = ?2
+                    ORDER BY created_at DESC
+                    LIMIT ?3
+                )
+                "#,
+                table
+            );
+            let result = sqlx::query(&sql)
+                .bind(account_id)
+                .bind(chat_id)
+                .bind(keep)
+                .execute(pool)
+                .await
+                .context("Failed to trim chat messages")?;
+            deleted += result.rows_affected();
+        }
+
+        Ok(deleted)
+    }
+
+    /// When the oldest stored message of a chat was sent (unix time), counting both tiers
+    pub async fn oldest_message_at(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<i64>> {
+        let oldest = sqlx::query_scalar(
+            "SELECT CAST(strftime('%s', MIN(created_at)) AS INTEGER) FROM messages_all WHERE account_id = ? AND chat_id = ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to find the oldest chat message")?;
+
+        Ok(oldest)
+    }
+
+    /// A chat's messages with ids in `first_id..=last_id`, oldest first, in either tier
+    pub async fn get_range(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        first_id: i64,
+        last_id: i64,
+    ) -> Result<Vec<MessageHistory>> {
+        let messages = sqlx::query_as::<_, MessageHistory>(
+            r#"
+            SELECT * FROM messages_all
+            WHERE account_id = ? AND chat_id = ? AND id BETWEEN ? AND ?
+            ORDER BY id
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(first_id)
+        .bind(last_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch message range")?;
+
+        Ok(messages)
+    }
+
+    /// A chat's stored messages before `before_id` (the newest when None), oldest first, in either tier
+    pub async fn page_all(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<MessageHistory>> {
+        let messages = sqlx::query_as::<_, MessageHistory>(
+            r#"
+            SELECT * FROM messages_all
+            WHERE account_id = ? AND chat_id = ? AND (? IS NULL OR id < ?)
+            ORDER BY id DESC LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(before_id)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch chat messages")?;
+
+        Ok(messages.into_iter().rev().collect())
+    }
+
+    /// Ids of a chat's replies in `first_id..=last_id` the owner rewrote in ghost mode
+    pub async fn ghost_edited_between(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        first_id: i64,
+        last_id: i64,
+    ) -> Result<Vec<i64>> {
+        let ids = sqlx::query_scalar(
+            r#"
+            SELECT m.id FROM messages_all m
+            WHERE m.account_id = ? AND m.chat_id = ? AND m.id BETWEEN ? AND ? AND m.role = 'assistant'
+            AND EXISTS (
+                SELECT 1 FROM reply_corrections c
+                WHERE c.account_id = m.account_id AND c.chat_id = m.chat_id
+                AND c.corrected IS NOT NULL AND instr(m.content, c.corrected) > 0
+            )
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(first_id)
+        .bind(last_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch ghost-edited replies")?;
+
+        Ok(ids)
+    }
+
+    /// Messages older than `days` whose bodies are still stored in clear text, in either tier
+    pub async fn list_unhashed_older_than(pool: &SqlitePool, days: i64, limit: i64) -> Result<Vec<(i64, String)>> {
+        let rows = sqlx::query_as(
+            r#"
+            SELECT id, content FROM messages_all
+            WHERE is_hashed = 0 AND created_at < datetime('now', '-' || ? || ' days')
+            LIMIT ?
+            "#,
+        )
+        .bind(days)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list messages to hash")?;
+
+        Ok(rows)
+    }
+
+    /// Readable user messages after `after_id`, oldest first; any chat when `chat_id` is None
+    pub async fn list_user_after(
+        pool: &SqlitePool,
+        chat_id: Option<i64>,
+        after_id: i64,
+        limit: i64,
+    ) -> Result<Vec<MessageHistory>> {
+        let messages = sqlx::query_as::<_, MessageHistory>(
+            r#"
+            SELECT * FROM messages_all
+            WHERE id > ? AND role = 'user' AND is_hashed = 0 AND (? IS NULL OR chat_id = ?)
+            ORDER BY id
+            LIMIT ?
+            "#,
+        )
+        .bind(after_id)
+        .bind(chat_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list messages to embed")?;
+
+        Ok(messages)
+    }
+
+    pub async fn count_user_after(pool: &SqlitePool, chat_id: Option<i64>, after_id: i64) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM messages_all
+            WHERE id > ? AND role = 'user' AND is_hashed = 0 AND (? IS NULL OR chat_id = ?)
+            "#,
+        )
+        .bind(after_id)
+        .bind(chat_id)
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count messages to embed")?;
+
+        Ok(count.0)
+    }
+
+    /// Replace a message body with its hash, in whichever tier it is
+    pub async fn mark_hashed(pool: &SqlitePool, id: i64, hashed_content: &str) -> Result<()> {
+        for table in ["messages_history", "messages_archive"] {
+            let sql = format!("UPDATE {} SET content = ?, is_hashed = 1 WHERE id = ?", table);
+            sqlx::query(&sql)
+                .bind(hashed_content)
+                .bind(id)
+                .execute(pool)
+                .await
+                .context("Failed to hash message")?;
+        }
+
+        Ok(())
+    }
+
+    /// Delete old messages from both tiers (cleanup)
+    pub async fn delete_older_than(pool: &SqlitePool, days: i64) -> Result<u64> {
+        let mut deleted = 0;
+        for table in ["messages_history", "messages_archive"] {
+            let sql = format!("DELETE FROM {} WHERE created_at < datetime('now', '-' || ? || ' days')", table);
+            let result = sqlx::query(&sql)
+                .bind(days)
+                .execute(pool)
+                .await
+                .context("Failed to delete old messages")?;
+            deleted += result.rows_affected();
+        }
+
+        tracing::info!("Deleted {} old messages", deleted);
+        Ok(deleted)
+    }
+
+    /// Move up to `batch` messages older than `days` to the archive tier; returns how many moved.
+    /// The newest message always stays, so new ids keep growing past archived ones.
+    pub async fn archive_older_than(pool: &SqlitePool, days: i64, batch: i64) -> Result<u64> {
+        // One cutoff for every statement, so exactly the copied messages are removed
+        let cutoff = (chrono::Utc::now() - chrono::Duration::days(days)).format("%Y-%m-%d %H:%M:%S").to_string();
+        let upto: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT MAX(id) FROM (
+                SELECT id FROM messages_history
+                WHERE created_at < ?
+                  AND id < (SELECT MAX(id) FROM messages_history)
+                ORDER BY id
+                LIMIT ?
+            )
+            "#,
+        )
+        .bind(&cutoff)
+        .bind(batch)
+        .fetch_one(pool)
+        .await
+        .context("Failed to find messages to archive")?;
+
+        let upto = match upto {
+            Some(id) => id,
+            None => return Ok(0),
+        };
+
+        let mut tx = pool.begin().await.context("Failed to start transaction")?;
+
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO messages_archive
+                (id, account_id, chat_id, role, content, created_at, sender_id, sender_chat_id, persona_id, is_hashed)
+            SELECT id, account_id, chat_id, role, content, created_at, sender_id, sender_chat_id, persona_id, is_hashed
+            FROM messages_history
+            WHERE id <= ? AND created_at < ?
+            "#,
+        )
+        .bind(upto)
+        .bind(&cutoff)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to copy messages to the archive")?;
+
+        let result = sqlx::query("DELETE FROM messages_history WHERE id <= ? AND created_at < ?")
+        .bind(upto)
+        .bind(&cutoff)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to remove archived messages")?;
+
+        tx.commit().await.context("Failed to commit message archiving")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Messages in the archive tier
+    pub async fn count_archived(pool: &SqlitePool) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM messages_archive")
+            .fetch_one(pool)
+            .await
+            .context("Failed to count archived messages")?;
+
+        Ok(count.0)
+    }
+}
+
+impl AccountRepository {
+    /// Add a message to history (convenience method)
+    pub async fn add_message(pool: &SqlitePool, new_message: NewMessage) -> Result<MessageHistory> {
+        MessageRepository::create(pool, new_message).await
+    }
+
+    /// Get recent messages for context (convenience method)
+    pub async fn get_recent_messages(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        limit: i64,
+    ) -> Result<Vec<MessageHistory>> {
+        MessageRepository::get_recent_messages(pool, account_id, chat_id, limit).await
+    }
+}
+
+/// Repository for bot group operations
+pub struct BotGroupRepository;
+
+impl BotGroupRepository {
+    /// Create a new bot group
+    pub async fn create(pool: &SqlitePool, new_group: NewBotGroup) -> Result<BotGroup> {
+        let group = sqlx::query_as::<_, BotGroup>(
+            r#"
+            INSERT INTO bot_groups (name, description)
+            VALUES (?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&new_group.name)
+        .bind(&new_group.description)
+        .fetch_one(pool)
+        .await
+        .context("Failed to create bot group")?;
+
+        tracing::info!("Created bot group '{}' with ID {}", group.name, group.id);
+        Ok(group)
+    }
+
+    /// Get a bot group by ID
+    pub async fn get_by_id(pool: &SqlitePool, id: i64) -> Result<Option<BotGroup>> {
+        let group = sqlx::query_as::<_, BotGroup>(
+            "SELECT * FROM bot_groups WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch bot group")?;
+
+        Ok(group)
+    }
+
+    /// List all bot groups
+    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<BotGroup>> {
+        let groups = sqlx::query_as::<_, BotGroup>(
+            "SELECT * FROM bot_groups ORDER BY created_at DESC"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list bot groups")?;
+
+        Ok(groups)
+    }
+
+    /// Add an account to a bot group
+    pub async fn add_member(pool: &SqlitePool, group_id: i64, account_id: i64) -> Result<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO bot_group_members (group_id, account_id) VALUES (?, ?)"
+        )
+        .bind(group_id)
+        .bind(account_id)
+        .execute(pool)
+        .await
+        .context("Failed to add member to bot group")?;
+
+        tracing::info!("Added account {} to bot group {}", account_id, group_id);
+        Ok(())
+    }
+
+    /// Remove an account from a bot group
+    pub async fn remove_member(pool: &SqlitePool, group_id: i64, account_id: i64) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM bot_group_members WHERE group_id = ? AND account_id = ?"
+        )
+        .bind(group_id)
+        .bind(account_id)
+        .execute(pool)
+        .await
+        .context("Failed to remove member from bot group")?;
+
+        tracing::info!("Removed account {} from bot group {}", account_id, group_id);
+        Ok(())
+    }
+
+    /// Get all members of a bot group
+    pub async fn get_members(pool: &SqlitePool, group_id: i64) -> Result<Vec<Account>> {
+        let accounts = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT a.* FROM accounts a
+            INNER JOIN bot_group_members bgm ON a.id = bgm.account_id
+            WHERE bgm.group_id = ?
+            ORDER BY a.id
+            "#,
+        )
+        .bind(group_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch bot group members")?;
+
+        Ok(accounts)
+    }
+
+    /// Delete a bot group
+    pub async fn delete(pool: &SqlitePool, group_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM bot_groups WHERE id = ?")
+            .bind(group_id)
+            .execute(pool)
+            .await
+            .context("Failed to delete bot group")?;
+
+        tracing::info!("Deleted bot group {}", group_id);
+        Ok(())
+    }
+}
+
+/// Repository for spam campaign operations
+pub struct SpamCampaignRepository;
+
+impl SpamCampaignRepository {
+    /// Create a new spam campaign
+    pub async fn create(pool: &SqlitePool, new_campaign: NewSpamCampaign) -> Result<SpamCampaign> {
+        let campaign = sqlx::query_as::<_, SpamCampaign>(
+            r#"
+            INSERT INTO spam_campaigns (
+                name, group_id, target_type, target_id, message_text,
+                media_path, media_type, repeat_count, delay_between_ms
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&new_campaign.name)
+        .bind(new_campaign.group_id)
+        .bind(&new_campaign.target_type)
+        .bind(new_campaign.target_id)
+        .bind(&new_campaign.message_text)
+        .bind(&new_campaign.media_path)
+        .bind(&new_campaign.media_type)
+        .bind(new_campaign.repeat_count)
+        .bind(new_campaign.delay_between_ms)
+        .fetch_one(pool)
+        .await
+        .context("Failed to create spam campaign")?;
+
+        tracing::info!("Created spam campaign '{}' with ID {}", campaign.name, campaign.id);
+        Ok(campaign)
+    }
+
+    /// Get a spam campaign by ID
+    pub async fn get_by_id(pool: &SqlitePool, id: i64) -> Result<Option<SpamCampaign>> {
+        let campaign = sqlx::query_as::<_, SpamCampaign>(
+            "SELECT * FROM spam_campaigns WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch spam campaign")?;
+
+        Ok(campaign)
+    }
+
+    /// List all spam campaigns
+    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<SpamCampaign>> {
+        let campaigns = sqlx::query_as::<_, SpamCampaign>(
+            "SELECT * FROM spam_campaigns ORDER BY created_at DESC"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list spam campaigns")?;
 
         Ok(campaigns)
     }
 
-    /// Update campaign status
-    pub async fn update_status(pool: &SqlitePool, campaign_id: i64, status: &str) -> Result<()> {
-        let now = chrono::Utc::now();
-        
-        let query = match status {
-            "running" => {
-                sqlx::query(
-                    "UPDATE spam_campaigns SET status = ?, started_at = ? WHERE id = ?"
-                )
-                .bind(status)
-                .bind(now)
-                .bind(campaign_id)
-            }
-            "completed" | "stopped" => {
-                sqlx::query(
-                    "UPDATE spam_campaigns SET status = ?, completed_at = ? WHERE id = ?"
-                )
-                .bind(status)
-                .bind(now)
-                .bind(campaign_id)
-            }
-            _ => {
-                sqlx::query(
-                    "UPDATE spam_campaigns SET status = ? WHERE id = ?"
-                )
-                .bind(status)
-                .bind(campaign_id)
-            }
-        };
+    /// List pending spam campaigns
+    pub async fn list_pending(pool: &SqlitePool) -> Result<Vec<SpamCampaign>> {
+        let campaigns = sqlx::query_as::<_, SpamCampaign>(
+            "SELECT * FROM spam_campaigns WHERE status = 'pending' ORDER BY created_at"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list pending spam campaigns")?;
+
+        Ok(campaigns)
+    }
+
+    /// Update campaign status
+    pub async fn update_status(pool: &SqlitePool, campaign_id: i64, status: &str) -> Result<()> {
+        let now = chrono::Utc::now();
+        
+        let query = match status {
+            "running" => {
+                sqlx::query(
+                    "UPDATE spam_campaigns SET status = ?, started_at = ? WHERE id = ?"
+                )
+                .bind(status)
+                .bind(now)
+                .bind(campaign_id)
+            }
+            "completed" | "stopped" => {
+                sqlx::query(
+                    "UPDATE spam_campaigns SET status = ?, completed_at = ? WHERE id = ?"
+                )
+                .bind(status)
+                .bind(now)
+                .bind(campaign_id)
+            }
+            _ => {
+                sqlx::query(
+                    "UPDATE spam_campaigns SET status = ? WHERE id = ?"
+                )
+                .bind(status)
+                .bind(campaign_id)
+            }
+        };
+
+        query.execute(pool)
+            .await
+            .context("Failed to update campaign status")?;
+
+        tracing::info!("Updated campaign {} status to {}", campaign_id, status);
+        Ok(())
+    }
+
+    /// Delete a spam campaign
+    pub async fn delete(pool: &SqlitePool, campaign_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM spam_campaigns WHERE id = ?")
+            .bind(campaign_id)
+            .execute(pool)
+            .await
+            .context("Failed to delete spam campaign")?;
+
+        tracing::info!("Deleted spam campaign {}", campaign_id);
+        Ok(())
+    }
+}
+
+/// Repository for persisted wizard sessions
+pub struct WizardRepository;
+
+impl WizardRepository {
+    /// Save (or replace) the wizard session for a chat
+    pub async fn save(
+        pool: &SqlitePool,
+        chat_id: i64,
+        user_id: Option<i64>,
+        kind: &str,
+        payload: &serde_json::Value,
+        ttl_secs: i64,
+    ) -> Result<()> {
+        let expires_at = chrono::Utc::now().timestamp() + ttl_secs;
+
+        sqlx::query(
+            r#"
+            INSERT INTO wizard_sessions (chat_id, user_id, kind, payload, expires_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(chat_id) DO UPDATE SET
+                user_id = COALESCE(excluded.user_id, wizard_sessions.user_id),
+                kind = excluded.kind,
+                payload = excluded.payload,
+                expires_at = excluded.expires_at,
+                created_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(kind)
+        .bind(payload.to_string())
+        .bind(expires_at)
+        .execute(pool)
+        .await
+        .context("Failed to save wizard session")?;
+
+        Ok(())
+    }
+
+    /// Get the wizard session for a chat
+    pub async fn get(pool: &SqlitePool, chat_id: i64) -> Result<Option<WizardSession>> {
+        let session = sqlx::query_as::<_, WizardSession>(
+            "SELECT * FROM wizard_sessions WHERE chat_id = ?"
+        )
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch wizard session")?;
+
+        Ok(session)
+    }
+
+    /// An unexpired wizard the user has open in a chat other than `chat_id`
+    pub async fn active_elsewhere(pool: &SqlitePool, user_id: i64, chat_id: i64) -> Result<Option<WizardSession>> {
+        let session = sqlx::query_as::<_, WizardSession>(
+            "SELECT * FROM wizard_sessions WHERE user_id = ? AND chat_id != ? AND expires_at > ? LIMIT 1"
+        )
+        .bind(user_id)
+        .bind(chat_id)
+        .bind(chrono::Utc::now().timestamp())
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch wizard sessions of user")?;
+
+        Ok(session)
+    }
+
+    /// List all stored wizard sessions
+    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<WizardSession>> {
+        let sessions = sqlx::query_as::<_, WizardSession>(
+            "SELECT * FROM wizard_sessions ORDER BY expires_at"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list wizard sessions")?;
+
+        Ok(sessions)
+    }
+
+    /// List sessions whose TTL has passed
+    pub async fn list_expired(pool: &SqlitePool) -> Result<Vec<WizardSession>> {
+        let sessions = sqlx::query_as::<_, WizardSession>(
+            "SELECT * FROM wizard_sessions WHERE expires_at <= ?"
+        )
+        .bind(chrono::Utc::now().timestamp())
+        .fetch_all(pool)
+        .await
+        .context("Failed to list expired wizard sessions")?;
+
+        Ok(sessions)
+    }
+
+    /// Remove the wizard session for a chat
+    pub async fn delete(pool: &SqlitePool, chat_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM wizard_sessions WHERE chat_id = ?")
+            .bind(chat_id)
+            .execute(pool)
+            .await
+            .context("Failed to delete wizard session")?;
+
+        Ok(())
+    }
+}
+
+/// Repository for prompt-injection policies and violations
+pub struct SecurityRepository;
+
+impl SecurityRepository {
+    /// Get an account's policy override for a chat
+    pub async fn get_policy(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<ChatSecurityPolicy>> {
+        let policy = sqlx::query_as::<_, ChatSecurityPolicy>(
+            "SELECT * FROM chat_security_policies WHERE account_id = ? AND chat_id = ?"
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch security policy")?;
+
+        Ok(policy)
+    }
+
+    /// Set (or replace) an account's policy override for a chat
+    pub async fn set_policy(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        policy: &str,
+        risk_threshold: Option<f64>,
+    ) -> Result<()> {
+        if let Some(threshold) = risk_threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                anyhow::bail!("Risk threshold must be between 0.0 and 1.0");
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO chat_security_policies (account_id, chat_id, policy, risk_threshold)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                policy = excluded.policy,
+                risk_threshold = excluded.risk_threshold,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(policy)
+        .bind(risk_threshold)
+        .execute(pool)
+        .await
+        .context("Failed to set security policy")?;
+
+        tracing::info!(
+            "Set security policy for chat {} (account {}) to {} ({:?})",
+            chat_id,
+            account_id,
+            policy,
+            risk_threshold
+        );
+        Ok(())
+    }
+
+    /// Record a detected violation
+    pub async fn record_violation(pool: &SqlitePool, violation: NewSecurityViolation) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO security_violations (
+                account_id, chat_id, user_id, score, matched_rules, action, content
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(violation.account_id)
+        .bind(violation.chat_id)
+        .bind(violation.user_id)
+        .bind(violation.score)
+        .bind(&violation.matched_rules)
+        .bind(&violation.action)
+        .bind(&violation.content)
+        .execute(pool)
+        .await
+        .context("Failed to record security violation")?;
+
+        Ok(())
+    }
+
+    /// Count violations by a user against an account in a chat during the last 24 hours that
+    /// were acted on; ones only logged (policy off or log) don't count as strikes
+    pub async fn count_recent_violations(pool: &SqlitePool, account_id: i64, chat_id: i64, user_id: i64) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM security_violations
+            WHERE account_id = ? AND chat_id = ? AND user_id = ? AND action IN ('strike', 'blocked')
+            AND created_at > datetime('now', '-1 day')
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count security violations")?;
+
+        Ok(count.0)
+    }
+
+    /// List the most recent violations
+    pub async fn list_recent(pool: &SqlitePool, limit: i64) -> Result<Vec<SecurityViolation>> {
+        let violations = sqlx::query_as::<_, SecurityViolation>(
+            "SELECT * FROM security_violations ORDER BY created_at DESC LIMIT ?"
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list security violations")?;
+
+        Ok(violations)
+    }
+}
+
+/// Bases followed above a persona; a longer chain is cut here
+pub const MAX_PERSONA_DEPTH: i64 = 5;
+
+/// Repository for persona operations
+pub struct PersonaRepository;
+
+impl PersonaRepository {
+    /// Create a persona, or update the prompt of an existing one with the same name
+    pub async fn upsert(pool: &SqlitePool, new_persona: NewPersona) -> Result<Persona> {
+        let persona = sqlx::query_as::<_, Persona>(
+            r#"
+            INSERT INTO personas (name, prompt)
+            VALUES (?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                prompt = excluded.prompt,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+        )
+        .bind(&new_persona.name)
+        .bind(&new_persona.prompt)
+        .fetch_one(pool)
+        .await
+        .context("Failed to save persona")?;
+
+        tracing::info!("Saved persona '{}' with ID {}", persona.name, persona.id);
+        Ok(persona)
+    }
+
+    pub async fn get_by_name(pool: &SqlitePool, name: &str) -> Result<Option<Persona>> {
+        let persona = sqlx::query_as::<_, Persona>("SELECT * FROM personas WHERE name = ?")
+            .bind(name)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to get persona by name")?;
+
+        Ok(persona)
+    }
+
+    /// Get a persona by ID
+    pub async fn get_by_id(pool: &SqlitePool, id: i64) -> Result<Option<Persona>> {
+        let persona = sqlx::query_as::<_, Persona>(
+            "SELECT * FROM personas WHERE id = ?"
+        )
+        .bind(id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch persona")?;
+
+        Ok(persona)
+    }
+
+    /// List all personas
+    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<Persona>> {
+        let personas = sqlx::query_as::<_, Persona>(
+            "SELECT * FROM personas ORDER BY name"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list personas")?;
+
+        Ok(personas)
+    }
+
+    /// Update a persona's prompt (affects every bound account)
+    pub async fn update_prompt(pool: &SqlitePool, persona_id: i64, prompt: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE personas SET prompt = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+        )
+        .bind(prompt)
+        .bind(persona_id)
+        .execute(pool)
+        .await
+        .context("Failed to update persona prompt")?;
+
+        tracing::info!("Updated prompt for persona {}", persona_id);
+        Ok(())
+    }
+
+    /// Set (or clear, with `None`) a persona's reply post-processing rules
+    pub async fn update_postprocess(pool: &SqlitePool, persona_id: i64, rules: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE personas SET postprocess = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(rules)
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to update persona post-processing")?;
+
+        Ok(())
+    }
+
+    /// Set (or clear, with `None`) a persona's time-of-day variants
+    pub async fn update_time_variants(pool: &SqlitePool, persona_id: i64, variants: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE personas SET time_variants = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(variants)
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to update persona time variants")?;
+
+        Ok(())
+    }
+
+    /// Set what a persona's conversations leave in long-term memory
+    pub async fn update_memory_write(pool: &SqlitePool, persona_id: i64, policy: &str) -> Result<()> {
+        sqlx::query("UPDATE personas SET memory_write = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(policy)
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to update persona memory policy")?;
+
+        Ok(())
+    }
+
+    pub async fn update_capabilities(
+        pool: &SqlitePool,
+        persona_id: i64,
+        capabilities: crate::ai::capabilities::Capabilities,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE personas SET
+                can_search_web = ?, can_generate_images = ?, can_use_tools = ?, can_send_voice = ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(capabilities.search_web)
+        .bind(capabilities.generate_images)
+        .bind(capabilities.use_tools)
+        .bind(capabilities.send_voice)
+        .bind(persona_id)
+        .execute(pool)
+        .await
+        .context("Failed to update persona capabilities")?;
+
+        Ok(())
+    }
+
+    /// Record the version of the persona file it was imported from
+    pub async fn set_file_version(pool: &SqlitePool, persona_id: i64, version: i64) -> Result<()> {
+        sqlx::query("UPDATE personas SET file_version = ? WHERE id = ?")
+            .bind(version)
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to update persona file version")?;
+
+        Ok(())
+    }
+
+    /// Content hash of the persona at its last differential export, if it was exported
+    pub async fn exported_hash(pool: &SqlitePool, persona_id: i64) -> Result<Option<String>> {
+        let hash = sqlx::query_scalar("SELECT content_hash FROM persona_exports WHERE persona_id = ?")
+            .bind(persona_id)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch persona export hash")?;
+
+        Ok(hash)
+    }
+
+    /// Remember what a persona looked like when it was exported
+    pub async fn record_export(pool: &SqlitePool, persona_id: i64, content_hash: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO persona_exports (persona_id, content_hash)
+            VALUES (?, ?)
+            ON CONFLICT(persona_id) DO UPDATE SET
+                content_hash = excluded.content_hash,
+                exported_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(persona_id)
+        .bind(content_hash)
+        .execute(pool)
+        .await
+        .context("Failed to record persona export")?;
+
+        Ok(())
+    }
+
+    pub async fn set_base(pool: &SqlitePool, persona_id: i64, base_id: Option<i64>) -> Result<()> {
+        sqlx::query("UPDATE personas SET base_id = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(base_id)
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to update persona base")?;
+
+        Ok(())
+    }
+
+    /// A persona followed by its base, the base's base and so on, at most MAX_PERSONA_DEPTH bases up
+    pub async fn lineage(pool: &SqlitePool, persona_id: i64) -> Result<Vec<Persona>> {
+        let personas = sqlx::query_as::<_, Persona>(
+            r#"
+            WITH RECURSIVE lineage(id, depth) AS (
+                SELECT id, 0 FROM personas WHERE id = ?
+                UNION ALL
+                SELECT p.base_id, l.depth + 1 FROM personas p JOIN lineage l ON p.id = l.id
+                WHERE p.base_id IS NOT NULL AND l.depth < ?
+            )
+            SELECT p.* FROM lineage l JOIN personas p ON p.id = l.id ORDER BY l.depth
+            "#,
+        )
+        .bind(persona_id)
+        .bind(MAX_PERSONA_DEPTH)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch persona lineage")?;
+
+        Ok(personas)
+    }
+
+    /// Personas extending this one directly
+    pub async fn list_children(pool: &SqlitePool, persona_id: i64) -> Result<Vec<Persona>> {
+        let personas = sqlx::query_as::<_, Persona>("SELECT * FROM personas WHERE base_id = ? ORDER BY name")
+            .bind(persona_id)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch derived personas")?;
+
+        Ok(personas)
+    }
+
+    /// Rename a persona; fails if the name is taken
+    pub async fn rename(pool: &SqlitePool, persona_id: i64, name: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE personas SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?"
+        )
+        .bind(name)
+        .bind(persona_id)
+        .execute(pool)
+        .await
+        .context("Failed to rename persona (is the name already taken?)")?;
+
+        tracing::info!("Renamed persona {} to '{}'", persona_id, name);
+        Ok(())
+    }
+
+    /// Delete a persona (bound accounts fall back to their own system prompt)
+    pub async fn delete(pool: &SqlitePool, persona_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM personas WHERE id = ?")
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to delete persona")?;
+
+        tracing::info!("Deleted persona {}", persona_id);
+        Ok(())
+    }
+
+    /// Count accounts bound to a persona
+    pub async fn count_bound_accounts(pool: &SqlitePool, persona_id: i64) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM accounts WHERE persona_id = ?"
+        )
+        .bind(persona_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count bound accounts")?;
+
+        Ok(count.0)
+    }
+
+    /// Bind an account to a persona, or unbind it with `None`
+    pub async fn bind_account(
+        pool: &SqlitePool,
+        account_id: i64,
+        persona_id: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE accounts SET persona_id = ? WHERE id = ?")
+            .bind(persona_id)
+            .bind(account_id)
+            .execute(pool)
+            .await
+            .context("Failed to bind persona to account")?;
+
+        tracing::info!("Bound account {} to persona {:?}", account_id, persona_id);
+        Ok(())
+    }
+
+    /// Set how likely a persona is to be picked by rotation (0 = never)
+    pub async fn set_rotation_weight(pool: &SqlitePool, persona_id: i64, weight: i64) -> Result<()> {
+        if weight < 0 {
+            anyhow::bail!("Weight must not be negative");
+        }
+
+        sqlx::query("UPDATE personas SET rotation_weight = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(weight)
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to update persona weight")?;
+
+        tracing::info!("Set rotation weight of persona {} to {}", persona_id, weight);
+        Ok(())
+    }
+
+    /// Attach tags to a persona (tags are lowercased, duplicates ignored)
+    pub async fn add_tags(pool: &SqlitePool, persona_id: i64, tags: &[String]) -> Result<()> {
+        for tag in tags {
+            sqlx::query("INSERT OR IGNORE INTO persona_tags (persona_id, tag) VALUES (?, ?)")
+                .bind(persona_id)
+                .bind(tag.trim().to_lowercase())
+                .execute(pool)
+                .await
+                .context("Failed to tag persona")?;
+        }
+
+        tracing::info!("Tagged persona {} with {:?}", persona_id, tags);
+        Ok(())
+    }
+
+    /// Remove a tag from a persona
+    pub async fn remove_tag(pool: &SqlitePool, persona_id: i64, tag: &str) -> Result<()> {
+        sqlx::query("DELETE FROM persona_tags WHERE persona_id = ? AND tag = ?")
+            .bind(persona_id)
+            .bind(tag.trim().to_lowercase())
+            .execute(pool)
+            .await
+            .context("Failed to untag persona")?;
+
+        Ok(())
+    }
+
+    /// Tags of a persona
+    pub async fn list_tags(pool: &SqlitePool, persona_id: i64) -> Result<Vec<String>> {
+        let tags: Vec<(String,)> = sqlx::query_as(
+            "SELECT tag FROM persona_tags WHERE persona_id = ? ORDER BY tag"
+        )
+        .bind(persona_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list persona tags")?;
+
+        Ok(tags.into_iter().map(|t| t.0).collect())
+    }
+
+    /// All tags with the number of personas carrying them
+    pub async fn list_all_tags(pool: &SqlitePool) -> Result<Vec<(String, i64)>> {
+        let tags: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT tag, COUNT(*) FROM persona_tags GROUP BY tag ORDER BY tag"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list tags")?;
+
+        Ok(tags)
+    }
+
+    /// Personas carrying a tag
+    pub async fn list_by_tag(pool: &SqlitePool, tag: &str) -> Result<Vec<Persona>> {
+        let personas = sqlx::query_as::<_, Persona>(
+            r#"
+            SELECT p.* FROM personas p
+            JOIN persona_tags t ON t.persona_id = p.id
+            WHERE t.tag = ?
+            ORDER BY p.name
+            "#,
+        )
+        .bind(tag.trim().to_lowercase())
+        .fetch_all(pool)
+        .await
+        .context("Failed to list personas by tag")?;
+
+        Ok(personas)
+    }
+
+    /// Delete every persona carrying a tag
+    pub async fn delete_by_tag(pool: &SqlitePool, tag: &str) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM personas WHERE id IN (SELECT persona_id FROM persona_tags WHERE tag = ?)"
+        )
+        .bind(tag.trim().to_lowercase())
+        .execute(pool)
+        .await
+        .context("Failed to delete personas by tag")?;
+
+        tracing::info!("Deleted {} personas tagged '{}'", result.rows_affected(), tag);
+        Ok(result.rows_affected())
+    }
+
+    /// Accounts currently bound to a persona carrying a tag
+    pub async fn accounts_with_tag(pool: &SqlitePool, tag: &str) -> Result<Vec<Account>> {
+        let accounts = sqlx::query_as::<_, Account>(
+            r#"
+            SELECT a.* FROM accounts a
+            JOIN persona_tags t ON t.persona_id = a.persona_id
+            WHERE t.tag = ?
+            "#,
+        )
+        .bind(tag.trim().to_lowercase())
+        .fetch_all(pool)
+        .await
+        .context("Failed to list accounts by persona tag")?;
+
+        Ok(accounts)
+    }
+
+    /// Enable or disable nightly rotation for a tag
+    pub async fn set_tag_rotation(pool: &SqlitePool, tag: &str, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO persona_tag_rotations (tag, enabled)
+            VALUES (?, ?)
+            ON CONFLICT(tag) DO UPDATE SET enabled = excluded.enabled
+            "#,
+        )
+        .bind(tag.trim().to_lowercase())
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update tag rotation")?;
+
+        tracing::info!("Set rotation for tag '{}' to {}", tag, enabled);
+        Ok(())
+    }
+
+    /// Enabled tag rotations
+    pub async fn list_tag_rotations(pool: &SqlitePool) -> Result<Vec<PersonaTagRotation>> {
+        let rotations = sqlx::query_as::<_, PersonaTagRotation>(
+            "SELECT * FROM persona_tag_rotations WHERE enabled = 1"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list tag rotations")?;
+
+        Ok(rotations)
+    }
+
+    /// Remember when a tag was last rotated
+    pub async fn mark_tag_rotated(pool: &SqlitePool, tag: &str) -> Result<()> {
+        sqlx::query("UPDATE persona_tag_rotations SET last_rotated_at = CURRENT_TIMESTAMP WHERE tag = ?")
+            .bind(tag)
+            .execute(pool)
+            .await
+            .context("Failed to mark tag rotation")?;
+
+        Ok(())
+    }
+
+    /// Resolve the prompt an account should use: its persona's prompt, or its own system prompt
+    pub async fn effective_prompt(pool: &SqlitePool, account_id: i64) -> Result<String> {
+        // A bound persona's prompt comes after the prompts of its bases, root first
+        let prompt: (String,) = sqlx::query_as(
+            r#"
+            WITH RECURSIVE lineage(id, depth) AS (
+                SELECT persona_id, 0 FROM accounts WHERE id = ? AND persona_id IS NOT NULL
+                UNION ALL
+                SELECT p.base_id, l.depth + 1 FROM personas p JOIN lineage l ON p.id = l.id
+                WHERE p.base_id IS NOT NULL AND l.depth < ?
+            )
+            SELECT COALESCE(
+                (SELECT group_concat(prompt, char(10) || char(10)) FROM (
+                    SELECT TRIM(p.prompt) AS prompt FROM lineage l JOIN personas p ON p.id = l.id
+                    WHERE TRIM(p.prompt) != '' ORDER BY l.depth DESC
+                )),
+                (SELECT system_prompt FROM accounts WHERE id = ?)
+            )
+            "#,
+        )
+        .bind(account_id)
+        .bind(MAX_PERSONA_DEPTH)
+        .bind(account_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to resolve account prompt")?;
+
+        Ok(prompt.0)
+    }
+}
+
+/// Repository for discovered dialogs and per-chat overrides
+pub struct ChatRepository;
+
+impl ChatRepository {
+    /// Insert or refresh a discovered dialog, keeping existing overrides
+    pub async fn upsert_discovered(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        title: &str,
+        chat_type: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, title, chat_type)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                title = excluded.title,
+                chat_type = excluded.chat_type,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(title)
+        .bind(chat_type)
+        .execute(pool)
+        .await
+        .context("Failed to save discovered chat")?;
+
+        Ok(())
+    }
+
+    /// Get the overrides for a single chat
+    pub async fn get(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<AccountChat>> {
+        let chat = sqlx::query_as::<_, AccountChat>(
+            "SELECT * FROM account_chats WHERE account_id = ? AND chat_id = ?"
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch chat settings")?;
+
+        Ok(chat)
+    }
+
+    /// List all known dialogs of an account
+    pub async fn list_for_account(pool: &SqlitePool, account_id: i64) -> Result<Vec<AccountChat>> {
+        let chats = sqlx::query_as::<_, AccountChat>(
+            "SELECT * FROM account_chats WHERE account_id = ? ORDER BY title"
+        )
+        .bind(account_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list chats")?;
+
+        Ok(chats)
+    }
+
+    /// Every known dialog of every account
+    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<AccountChat>> {
+        let chats = sqlx::query_as::<_, AccountChat>("SELECT * FROM account_chats ORDER BY account_id, title")
+            .fetch_all(pool)
+            .await
+            .context("Failed to list chats")?;
+
+        Ok(chats)
+    }
+
+    /// Deny (or re-allow) replies in a chat
+    pub async fn set_denied(pool: &SqlitePool, account_id: i64, chat_id: i64, denied: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, is_denied)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                is_denied = excluded.is_denied,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(denied)
+        .execute(pool)
+        .await
+        .context("Failed to update chat deny flag")?;
+
+        tracing::info!("Set denied={} for chat {} on account {}", denied, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Stop replying in a chat until the given time
+    pub async fn pause(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        until: chrono::DateTime<chrono::Utc>,
+        reason: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, paused_until, paused_reason)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                paused_until = excluded.paused_until,
+                paused_reason = excluded.paused_reason,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(until)
+        .bind(reason)
+        .execute(pool)
+        .await
+        .context("Failed to pause chat")?;
+
+        tracing::info!("Paused chat {} on account {} until {} ({})", chat_id, account_id, until, reason);
+        Ok(())
+    }
+
+    /// Lift a pause; returns false if the chat wasn't paused
+    pub async fn resume(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE account_chats
+            SET paused_until = NULL, paused_reason = NULL, updated_at = CURRENT_TIMESTAMP
+            WHERE account_id = ? AND chat_id = ? AND paused_until IS NOT NULL
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to resume chat")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Set or clear (None) the per-chat reply probability override
+    pub async fn set_reply_probability(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        probability: Option<i64>,
+    ) -> Result<()> {
+        if let Some(p) = probability {
+            if !(0..=100).contains(&p) {
+                anyhow::bail!("Probability must be between 0 and 100");
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, reply_probability)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                reply_probability = excluded.reply_probability,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(probability)
+        .execute(pool)
+        .await
+        .context("Failed to update chat reply probability")?;
+
+        tracing::info!(
+            "Set reply probability override for chat {} on account {} to {:?}",
+            chat_id,
+            account_id,
+            probability
+        );
+        Ok(())
+    }
+
+    /// Set (or clear, with `None`) the words that always get a reply in a chat
+    pub async fn set_triggers(pool: &SqlitePool, account_id: i64, chat_id: i64, triggers: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, reply_triggers)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                reply_triggers = excluded.reply_triggers,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(triggers)
+        .execute(pool)
+        .await
+        .context("Failed to update chat reply triggers")?;
+
+        Ok(())
+    }
+
+    /// Set (or clear, with `None`) the words that get voice and video notes escalated to the owner
+    pub async fn set_escalation_keywords(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        keywords: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, escalation_keywords)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                escalation_keywords = excluded.escalation_keywords,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(keywords)
+        .execute(pool)
+        .await
+        .context("Failed to update chat escalation keywords")?;
+
+        Ok(())
+    }
+
+    /// Set (or clear, with `None`) how many minutes replies live in a chat
+    pub async fn set_ephemeral(pool: &SqlitePool, account_id: i64, chat_id: i64, minutes: Option<i64>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, ephemeral_minutes)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                ephemeral_minutes = excluded.ephemeral_minutes,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(minutes)
+        .execute(pool)
+        .await
+        .context("Failed to update ephemeral replies")?;
+
+        Ok(())
+    }
+
+    /// Set (or reset to the defaults, with `None`) how messages of a chat are batched
+    pub async fn set_debounce(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        debounce_ms: Option<i64>,
+        max_batch: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, debounce_ms, debounce_max_batch)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                debounce_ms = excluded.debounce_ms,
+                debounce_max_batch = excluded.debounce_max_batch,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(debounce_ms)
+        .bind(max_batch)
+        .execute(pool)
+        .await
+        .context("Failed to update message batching")?;
+
+        Ok(())
+    }
+
+    /// Start a fresh context: history so far is only passed on as its summary
+    pub async fn set_context_boundary(pool: &SqlitePool, account_id: i64, chat_id: i64, summary: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, context_since, context_summary)
+            VALUES (?, ?, CURRENT_TIMESTAMP, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                context_since = CURRENT_TIMESTAMP,
+                context_summary = excluded.context_summary,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(summary)
+        .execute(pool)
+        .await
+        .context("Failed to set context boundary")?;
+
+        Ok(())
+    }
+
+    /// Replace the summary passed on from before the context boundary, keeping the boundary
+    pub async fn set_context_summary(pool: &SqlitePool, account_id: i64, chat_id: i64, summary: Option<&str>) -> Result<()> {
+        sqlx::query(
+            "UPDATE account_chats SET context_summary = ?, updated_at = CURRENT_TIMESTAMP WHERE account_id = ? AND chat_id = ?",
+        )
+        .bind(summary)
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to set context summary")?;
+
+        Ok(())
+    }
+
+    /// Whether a new persona mentions taking over a chat
+    pub async fn set_announce_persona_switch(pool: &SqlitePool, account_id: i64, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, announce_persona_switch)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                announce_persona_switch = excluded.announce_persona_switch,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update persona switch announcement")?;
+
+        Ok(())
+    }
+
+    /// Allow or forbid native polls in a chat
+    pub async fn set_polls(pool: &SqlitePool, account_id: i64, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, polls_enabled)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                polls_enabled = excluded.polls_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update chat polls")?;
+
+        Ok(())
+    }
+
+    /// Allow or forbid sending a chat's photos to the vision model, its voice notes
+    /// to Whisper and its questions to web search
+    pub async fn set_media(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        vision: bool,
+        voice: bool,
+        web_search: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, vision_enabled, voice_enabled, web_search_enabled)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                vision_enabled = excluded.vision_enabled,
+                voice_enabled = excluded.voice_enabled,
+                web_search_enabled = excluded.web_search_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(vision)
+        .bind(voice)
+        .bind(web_search)
+        .execute(pool)
+        .await
+        .context("Failed to update chat media processing")?;
+
+        Ok(())
+    }
+
+    /// Let the persona pin messages in a chat, or stop it
+    pub async fn set_pins(pool: &SqlitePool, account_id: i64, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, pins_enabled)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                pins_enabled = excluded.pins_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update chat pins")?;
+
+        Ok(())
+    }
+
+    /// Set a chat's quiet hours; None switches them off
+    pub async fn set_quiet_hours(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        quiet_hours: Option<&str>,
+        allow_mentions: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, quiet_hours, quiet_allow_mentions)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                quiet_hours = excluded.quiet_hours,
+                quiet_allow_mentions = excluded.quiet_allow_mentions,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(quiet_hours)
+        .bind(allow_mentions)
+        .execute(pool)
+        .await
+        .context("Failed to update quiet hours")?;
+
+        Ok(())
+    }
+
+    /// Set how replies write numbers, dates, money and units in a chat
+    pub async fn set_locale(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        locale: Option<&str>,
+        units: Option<&str>,
+        transliteration: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, locale, units, transliteration)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                locale = excluded.locale,
+                units = excluded.units,
+                transliteration = excluded.transliteration,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(locale)
+        .bind(units)
+        .bind(transliteration)
+        .execute(pool)
+        .await
+        .context("Failed to update chat locale")?;
+
+        Ok(())
+    }
+
+    /// Set what happens to messages a chat missed while the account was offline
+    pub async fn set_catchup_mode(pool: &SqlitePool, account_id: i64, chat_id: i64, mode: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, catchup_mode)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                catchup_mode = excluded.catchup_mode,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(mode)
+        .execute(pool)
+        .await
+        .context("Failed to update catch-up mode")?;
+
+        Ok(())
+    }
+
+    /// Set (`Some((min, max))`) or clear the reply probability auto-tuning bounds of a chat
+    pub async fn set_tuning_bounds(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        bounds: Option<(i64, i64)>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, tune_min_probability, tune_max_probability)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                tune_min_probability = excluded.tune_min_probability,
+                tune_max_probability = excluded.tune_max_probability,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(bounds.map(|b| b.0))
+        .bind(bounds.map(|b| b.1))
+        .execute(pool)
+        .await
+        .context("Failed to update tuning bounds")?;
+
+        Ok(())
+    }
+
+    /// Chats whose reply probability is auto-tuned
+    pub async fn list_tuned(pool: &SqlitePool) -> Result<Vec<AccountChat>> {
+        let chats = sqlx::query_as::<_, AccountChat>(
+            "SELECT * FROM account_chats WHERE tune_min_probability IS NOT NULL AND tune_max_probability IS NOT NULL",
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch tuned chats")?;
+
+        Ok(chats)
+    }
+
+    /// Turn the answer cache of a chat on or off
+    pub async fn set_answer_cache(pool: &SqlitePool, account_id: i64, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, answer_cache_enabled)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                answer_cache_enabled = excluded.answer_cache_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update answer cache setting")?;
+
+        Ok(())
+    }
+
+    /// Mark a chat as onboarded. Returns false if it already was.
+    pub async fn mark_onboarded(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE account_chats SET onboarded_at = CURRENT_TIMESTAMP
+            WHERE account_id = ? AND chat_id = ? AND onboarded_at IS NULL
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to mark chat as onboarded")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Enable or disable proactive conversation starters in a chat
+    pub async fn set_initiative(pool: &SqlitePool, account_id: i64, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, initiative_enabled)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                initiative_enabled = excluded.initiative_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update chat initiative flag")?;
+
+        tracing::info!("Set initiative={} for chat {} on account {}", enabled, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Set the memory retrieval filters of a chat
+    pub async fn set_rag_scope(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        prefer_sender: bool,
+        exclude_bots: bool,
+        max_age_days: Option<i64>,
+        private_recall: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, rag_prefer_sender, rag_exclude_bots, rag_max_age_days, rag_private_recall)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                rag_prefer_sender = excluded.rag_prefer_sender,
+                rag_exclude_bots = excluded.rag_exclude_bots,
+                rag_max_age_days = excluded.rag_max_age_days,
+                rag_private_recall = excluded.rag_private_recall,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(prefer_sender)
+        .bind(exclude_bots)
+        .bind(max_age_days)
+        .bind(private_recall)
+        .execute(pool)
+        .await
+        .context("Failed to update chat retrieval filters")?;
+
+        tracing::info!(
+            "Set retrieval filters for chat {} on account {}: prefer_sender={}, exclude_bots={}, max_age_days={:?}, private_recall={}",
+            chat_id, account_id, prefer_sender, exclude_bots, max_age_days, private_recall
+        );
+        Ok(())
+    }
+
+    /// Override whether bot-authored messages are embedded in a chat; `None` follows the global setting
+    pub async fn set_embed_bot_messages(pool: &SqlitePool, account_id: i64, chat_id: i64, embed: Option<bool>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, embed_bot_messages)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                embed_bot_messages = excluded.embed_bot_messages,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(embed)
+        .execute(pool)
+        .await
+        .context("Failed to update chat bot memory setting")?;
+
+        tracing::info!("Set embed_bot_messages={:?} for chat {} on account {}", embed, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Chats of an account where conversation starters are enabled
+    pub async fn list_initiative_chats(pool: &SqlitePool, account_id: i64) -> Result<Vec<AccountChat>> {
+        let chats = sqlx::query_as::<_, AccountChat>(
+            "SELECT * FROM account_chats WHERE account_id = ? AND initiative_enabled = 1 AND is_denied = 0"
+        )
+        .bind(account_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list initiative chats")?;
+
+        Ok(chats)
+    }
+
+    /// Count a conversation starter towards today's cap
+    pub async fn record_initiative(pool: &SqlitePool, account_id: i64, chat_id: i64, day: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE account_chats SET
+                initiative_count = CASE WHEN initiative_day = ? THEN initiative_count + 1 ELSE 1 END,
+                initiative_day = ?,
+                last_initiative_at = CURRENT_TIMESTAMP
+            WHERE account_id = ? AND chat_id = ?
+            "#,
+        )
+        .bind(day)
+        .bind(day)
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to record conversation starter")?;
+
+        Ok(())
+    }
+
+    /// Set a chat's retention limits (None = default)
+    pub async fn set_retention(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        days: Option<i64>,
+        max_messages: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, retention_days, retention_max_messages)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                retention_days = excluded.retention_days,
+                retention_max_messages = excluded.retention_max_messages,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(days)
+        .bind(max_messages)
+        .execute(pool)
+        .await
+        .context("Failed to update chat retention")?;
+
+        tracing::info!(
+            "Set retention {:?} days / {:?} messages for chat {} on account {}",
+            days, max_messages, chat_id, account_id
+        );
+        Ok(())
+    }
+
+    /// Chats with their own retention limits
+    pub async fn list_with_retention(pool: &SqlitePool) -> Result<Vec<AccountChat>> {
+        let chats = sqlx::query_as::<_, AccountChat>(
+            "SELECT * FROM account_chats WHERE retention_days IS NOT NULL OR retention_max_messages IS NOT NULL"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list chats with retention")?;
+
+        Ok(chats)
+    }
+
+    /// Set a chat's IANA timezone (None falls back to the default)
+    pub async fn set_timezone(pool: &SqlitePool, account_id: i64, chat_id: i64, timezone: Option<&str>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, timezone)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                timezone = excluded.timezone,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(timezone)
+        .execute(pool)
+        .await
+        .context("Failed to update chat timezone")?;
+
+        tracing::info!("Set timezone {:?} for chat {} on account {}", timezone, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Configure the weekly digest for a chat
+    pub async fn set_digest(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        enabled: bool,
+        weekday: i64,
+        hour: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, digest_enabled, digest_weekday, digest_hour)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                digest_enabled = excluded.digest_enabled,
+                digest_weekday = excluded.digest_weekday,
+                digest_hour = excluded.digest_hour,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .bind(weekday)
+        .bind(hour)
+        .execute(pool)
+        .await
+        .context("Failed to update chat digest settings")?;
+
+        tracing::info!("Set digest={} for chat {} on account {}", enabled, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Chats of an account with the weekly digest enabled
+    pub async fn list_digest_chats(pool: &SqlitePool, account_id: i64) -> Result<Vec<AccountChat>> {
+        let chats = sqlx::query_as::<_, AccountChat>(
+            "SELECT * FROM account_chats WHERE account_id = ? AND digest_enabled = 1 AND is_denied = 0"
+        )
+        .bind(account_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list digest chats")?;
+
+        Ok(chats)
+    }
+
+    /// Mark a digest as posted
+    pub async fn record_digest(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE account_chats SET last_digest_at = CURRENT_TIMESTAMP WHERE account_id = ? AND chat_id = ?"
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to record digest")?;
+
+        Ok(())
+    }
+
+    /// Configure persona rotation for a chat ('off', 'schedule' or 'conversation')
+    pub async fn set_rotation(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        mode: &str,
+        tag: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, rotation_mode, rotation_tag)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                rotation_mode = excluded.rotation_mode,
+                rotation_tag = excluded.rotation_tag,
+                active_persona_id = NULL,
+                persona_since = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(mode)
+        .bind(tag)
+        .execute(pool)
+        .await
+        .context("Failed to update chat rotation")?;
+
+        tracing::info!("Set persona rotation for chat {} on account {} to {} ({:?})", chat_id, account_id, mode, tag);
+        Ok(())
+    }
+
+    /// Switch the persona currently answering in a chat
+    pub async fn set_active_persona(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        persona_id: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE account_chats SET active_persona_id = ?, persona_since = CURRENT_TIMESTAMP
+            WHERE account_id = ? AND chat_id = ?
+            "#,
+        )
+        .bind(persona_id)
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to switch active persona")?;
+
+        Ok(())
+    }
+
+    /// Set how replies are rendered in a chat ('markdown', 'html' or 'plain')
+    pub async fn set_format_mode(pool: &SqlitePool, account_id: i64, chat_id: i64, mode: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, format_mode)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                format_mode = excluded.format_mode,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(mode)
+        .execute(pool)
+        .await
+        .context("Failed to update chat format mode")?;
+
+        tracing::info!("Set format mode for chat {} on account {} to {}", chat_id, account_id, mode);
+        Ok(())
+    }
+}
+
+/// Repository for per-contact relationship profiles
+pub struct RelationshipRepository;
+
+impl RelationshipRepository {
+    /// Familiarity gained per message, scaled by the remaining distance to 1.0
+    const FAMILIARITY_STEP: f64 = 0.02;
+
+    /// Get the relationship between an account and a user
+    pub async fn get(pool: &SqlitePool, account_id: i64, peer_user_id: i64) -> Result<Option<Relationship>> {
+        let relationship = sqlx::query_as::<_, Relationship>(
+            "SELECT * FROM relationships WHERE account_id = ? AND peer_user_id = ?"
+        )
+        .bind(account_id)
+        .bind(peer_user_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch relationship")?;
+
+        Ok(relationship)
+    }
+
+    /// List relationships of an account, closest first
+    pub async fn list_for_account(pool: &SqlitePool, account_id: i64) -> Result<Vec<Relationship>> {
+        let relationships = sqlx::query_as::<_, Relationship>(
+            "SELECT * FROM relationships WHERE account_id = ? ORDER BY familiarity DESC"
+        )
+        .bind(account_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list relationships")?;
+
+        Ok(relationships)
+    }
+
+    /// Count a message from the user and grow familiarity
+    pub async fn record_interaction(
+        pool: &SqlitePool,
+        account_id: i64,
+        peer_user_id: i64,
+    ) -> Result<Relationship> {
+        let relationship = sqlx::query_as::<_, Relationship>(
+            r#"
+            INSERT INTO relationships (account_id, peer_user_id, familiarity, message_count)
+            VALUES (?, ?, ?, 1)
+            ON CONFLICT(account_id, peer_user_id) DO UPDATE SET
+                familiarity = MIN(1.0, familiarity + (1.0 - familiarity) * ?),
+                message_count = message_count + 1,
+                last_interaction_at = CURRENT_TIMESTAMP,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+        )
+        .bind(account_id)
+        .bind(peer_user_id)
+        .bind(Self::FAMILIARITY_STEP)
+        .bind(Self::FAMILIARITY_STEP)
+        .fetch_one(pool)
+        .await
+        .context("Failed to record interaction")?;
+
+        Ok(relationship)
+    }
+
+    /// Store an extracted profile; an empty how_known keeps the current one
+    pub async fn update_profile(
+        pool: &SqlitePool,
+        account_id: i64,
+        peer_user_id: i64,
+        how_known: &str,
+        inside_jokes: &[String],
+        refreshed_at_count: i64,
+    ) -> Result<()> {
+        let jokes_json = serde_json::to_string(inside_jokes)?;
+
+        sqlx::query(
+            r#"
+            UPDATE relationships SET
+                how_known = CASE WHEN ? = '' THEN how_known ELSE ? END,
+                inside_jokes = ?,
+                refreshed_at_count = ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE account_id = ? AND peer_user_id = ?
+            "#,
+        )
+        .bind(how_known)
+        .bind(how_known)
+        .bind(jokes_json)
+        .bind(refreshed_at_count)
+        .bind(account_id)
+        .bind(peer_user_id)
+        .execute(pool)
+        .await
+        .context("Failed to update relationship profile")?;
+
+        Ok(())
+    }
+
+    /// Manually set how the account knows a user
+    pub async fn set_how_known(
+        pool: &SqlitePool,
+        account_id: i64,
+        peer_user_id: i64,
+        how_known: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO relationships (account_id, peer_user_id, how_known)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, peer_user_id) DO UPDATE SET
+                how_known = excluded.how_known,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(peer_user_id)
+        .bind(how_known)
+        .execute(pool)
+        .await
+        .context("Failed to set relationship")?;
+
+        tracing::info!("Set relationship of account {} with user {}", account_id, crate::logging::user_ref(peer_user_id));
+        Ok(())
+    }
+
+    /// Fade familiarity for contacts not heard from in `idle_days`
+    pub async fn decay_inactive(pool: &SqlitePool, idle_days: i64, factor: f64) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE relationships SET familiarity = familiarity * ?
+            WHERE last_interaction_at < datetime('now', '-' || ? || ' days')
+            AND familiarity > 0.01
+            "#,
+        )
+        .bind(factor)
+        .bind(idle_days)
+        .execute(pool)
+        .await
+        .context("Failed to decay relationships")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Grant a feature for `days` (NULL = forever); extends an active time-limited grant,
+/// never shortens a lifetime one. Binds: user_id, feature, source, days, days, days
+const GRANT_ENTITLEMENT: &str = r#"
+    INSERT INTO entitlements (user_id, feature, source, expires_at)
+    VALUES (?, ?, ?, CASE WHEN ? IS NULL THEN NULL ELSE datetime('now', '+' || ? || ' days') END)
+    ON CONFLICT(user_id, feature) DO UPDATE SET
+        source = excluded.source,
+        expires_at = CASE
+            WHEN excluded.expires_at IS NULL OR entitlements.expires_at IS NULL THEN NULL
+            WHEN entitlements.expires_at > CURRENT_TIMESTAMP
+                THEN datetime(entitlements.expires_at, '+' || ? || ' days')
+            ELSE excluded.expires_at
+        END,
+        granted_at = CURRENT_TIMESTAMP
+"#;
+
+/// Repository for Stars payments and premium entitlements
+pub struct PaymentRepository;
+
+impl PaymentRepository {
+    /// Record a payment and grant its feature in one transaction, so a charge is never
+    /// stored without its entitlement; returns false if this charge was already processed
+    pub async fn record_payment(
+        pool: &SqlitePool,
+        user_id: i64,
+        feature: &str,
+        stars: i64,
+        days: Option<i64>,
+        telegram_charge_id: &str,
+    ) -> Result<bool> {
+        let mut tx = pool.begin().await.context("Failed to start transaction")?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO payments (user_id, feature, stars, days, telegram_charge_id)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(telegram_charge_id) DO NOTHING
+            "#,
+        )
+        .bind(user_id)
+        .bind(feature)
+        .bind(stars)
+        .bind(days)
+        .bind(telegram_charge_id)
+        .execute(&mut *tx)
+        .await
+        .context("Failed to record payment")?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query(GRANT_ENTITLEMENT)
+            .bind(user_id)
+            .bind(feature)
+            .bind("payment")
+            .bind(days)
+            .bind(days)
+            .bind(days)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to grant entitlement")?;
+
+        tx.commit().await.context("Failed to commit payment")?;
+
+        tracing::info!(
+            "Payment of {} stars from user {} for '{}', granted for {:?} days",
+            stars,
+            crate::logging::user_ref(user_id),
+            feature,
+            days
+        );
+        Ok(true)
+    }
+
+    /// Grant a feature for `days` (None = forever); extends an active time-limited grant,
+    /// never shortens a lifetime one
+    pub async fn grant(pool: &SqlitePool, user_id: i64, feature: &str, days: Option<i64>, source: &str) -> Result<()> {
+        sqlx::query(GRANT_ENTITLEMENT)
+            .bind(user_id)
+            .bind(feature)
+            .bind(source)
+            .bind(days)
+            .bind(days)
+            .bind(days)
+            .execute(pool)
+            .await
+            .context("Failed to grant entitlement")?;
+
+        tracing::info!("Granted '{}' to user {} for {:?} days ({})", feature, crate::logging::user_ref(user_id), days, source);
+        Ok(())
+    }
+
+    /// Remove a feature from a user
+    pub async fn revoke(pool: &SqlitePool, user_id: i64, feature: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM entitlements WHERE user_id = ? AND feature = ?")
+            .bind(user_id)
+            .bind(feature)
+            .execute(pool)
+            .await
+            .context("Failed to revoke entitlement")?;
+
+        tracing::info!("Revoked '{}' from user {}", feature, crate::logging::user_ref(user_id));
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// All entitlements of a user, including expired ones
+    pub async fn list_for_user(pool: &SqlitePool, user_id: i64) -> Result<Vec<Entitlement>> {
+        let entitlements = sqlx::query_as::<_, Entitlement>(
+            "SELECT * FROM entitlements WHERE user_id = ? ORDER BY feature"
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list entitlements")?;
+
+        Ok(entitlements)
+    }
+
+    /// Whether a user currently has a feature
+    pub async fn has_entitlement(pool: &SqlitePool, user_id: i64, feature: &str) -> Result<bool> {
+        let (count,): (i64,) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*) FROM entitlements
+            WHERE user_id = ? AND feature = ?
+            AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(user_id)
+        .bind(feature)
+        .fetch_one(pool)
+        .await
+        .context("Failed to check entitlement")?;
+
+        Ok(count > 0)
+    }
+
+    /// Stars received in total and number of payments
+    pub async fn totals(pool: &SqlitePool) -> Result<(i64, i64)> {
+        let totals: (i64, i64) = sqlx::query_as("SELECT COALESCE(SUM(stars), 0), COUNT(*) FROM payments")
+            .fetch_one(pool)
+            .await
+            .context("Failed to sum payments")?;
+
+        Ok(totals)
+    }
+}
+
+/// Repository for per-chat member karma
+pub struct KarmaRepository;
+
+impl KarmaRepository {
+    /// Enable or disable karma in a chat, optionally with a monthly reset
+    pub async fn configure(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        enabled: bool,
+        monthly_reset: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, karma_enabled, karma_monthly_reset)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                karma_enabled = excluded.karma_enabled,
+                karma_monthly_reset = excluded.karma_monthly_reset,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .bind(monthly_reset)
+        .execute(pool)
+        .await
+        .context("Failed to update karma settings")?;
+
+        tracing::info!("Set karma={} (monthly reset {}) for chat {} on account {}", enabled, monthly_reset, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Add points to a member (negative to take them away)
+    pub async fn add(pool: &SqlitePool, account_id: i64, chat_id: i64, user_id: i64, points: i64) -> Result<i64> {
+        let (total,): (i64,) = sqlx::query_as(
+            r#"
+            INSERT INTO karma (account_id, chat_id, user_id, points)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id, user_id) DO UPDATE SET
+                points = points + excluded.points,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING points
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(points)
+        .fetch_one(pool)
+        .await
+        .context("Failed to update karma")?;
+
+        Ok(total)
+    }
+
+    /// Note that a member thanked a message; false if they already did
+    pub async fn record_thanks(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        sender_id: i64,
+        message_id: i64,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO karma_thanks (account_id, chat_id, sender_id, message_id)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id, sender_id, message_id) DO NOTHING
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(sender_id)
+        .bind(message_id)
+        .execute(pool)
+        .await
+        .context("Failed to record thanks")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// A member's points in a chat
+    pub async fn get(pool: &SqlitePool, account_id: i64, chat_id: i64, user_id: i64) -> Result<i64> {
+        let points: Option<(i64,)> = sqlx::query_as(
+            "SELECT points FROM karma WHERE account_id = ? AND chat_id = ? AND user_id = ?"
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch karma")?;
+
+        Ok(points.map(|(p,)| p).unwrap_or(0))
+    }
+
+    /// Highest karma in a chat
+    pub async fn leaderboard(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<Karma>> {
+        let top = sqlx::query_as::<_, Karma>(
+            r#"
+            SELECT * FROM karma
+            WHERE account_id = ? AND chat_id = ? AND points > 0
+            ORDER BY points DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch leaderboard")?;
+
+        Ok(top)
+    }
+
+    /// Clear all karma in a chat and start a new period
+    pub async fn reset(pool: &SqlitePool, account_id: i64, chat_id: i64, period: &str) -> Result<()> {
+        let mut tx = pool.begin().await.context("Failed to start transaction")?;
+
+        sqlx::query("DELETE FROM karma WHERE account_id = ? AND chat_id = ?")
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to reset karma")?;
+
+        sqlx::query("DELETE FROM karma_thanks WHERE account_id = ? AND chat_id = ?")
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to reset karma thanks")?;
+
+        sqlx::query("UPDATE account_chats SET karma_period = ? WHERE account_id = ? AND chat_id = ?")
+            .bind(period)
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update karma period")?;
+
+        tx.commit().await.context("Failed to commit karma reset")?;
+
+        tracing::info!("Reset karma in chat {} on account {} (period {})", chat_id, account_id, period);
+        Ok(())
+    }
+}
+
+/// Repository for chat games: rounds and per-member points
+pub struct GameRepository;
+
+impl GameRepository {
+    /// Allow or forbid /game in a chat
+    pub async fn set_enabled(pool: &SqlitePool, account_id: i64, chat_id: i64, enabled: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats (account_id, chat_id, games_enabled)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                games_enabled = excluded.games_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(enabled)
+        .execute(pool)
+        .await
+        .context("Failed to update chat games")?;
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub async fn start_round(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        kind: &str,
+        question: &str,
+        answer: &str,
+        alternatives: Option<&str>,
+        deadline_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<GameRound> {
+        let round = sqlx::query_as::<_, GameRound>(
+            r#"
+            INSERT INTO game_rounds (account_id, chat_id, kind, question, answer, alternatives, deadline_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(kind)
+        .bind(question)
+        .bind(answer)
+        .bind(alternatives)
+        .bind(deadline_at)
+        .fetch_one(pool)
+        .await
+        .context("Failed to start game round")?;
+
+        Ok(round)
+    }
+
+    /// Questions are recorded under TDLib's temporary message ID until the send succeeds
+    pub async fn confirm_sent(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        old_message_id: i64,
+        message_id: i64,
+    ) -> Result<()> {
+        sqlx::query("UPDATE game_rounds SET message_id = ? WHERE account_id = ? AND chat_id = ? AND message_id = ?")
+            .bind(message_id)
+            .bind(account_id)
+            .bind(chat_id)
+            .bind(old_message_id)
+            .execute(pool)
+            .await
+            .context("Failed to confirm game question")?;
+
+        Ok(())
+    }
+
+    /// Remember the sent question, so the reveal can reply to it
+    pub async fn set_message_id(pool: &SqlitePool, id: i64, message_id: i64) -> Result<()> {
+        sqlx::query("UPDATE game_rounds SET message_id = ? WHERE id = ?")
+            .bind(message_id)
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update game round")?;
+
+        Ok(())
+    }
+
+    /// The open round of a chat, if any
+    pub async fn active(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<GameRound>> {
+        let round = sqlx::query_as::<_, GameRound>(
+            r#"
+            SELECT * FROM game_rounds
+            WHERE account_id = ? AND chat_id = ? AND finished_at IS NULL
+            ORDER BY id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch active game round")?;
+
+        Ok(round)
+    }
+
+    /// Close a round; false if it was already closed (someone else answered first)
+    pub async fn finish(pool: &SqlitePool, id: i64, winner_id: Option<i64>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE game_rounds SET finished_at = ?, winner_id = ? WHERE id = ? AND finished_at IS NULL",
+        )
+        .bind(chrono::Utc::now())
+        .bind(winner_id)
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to finish game round")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Open rounds whose time is up
+    pub async fn expired(pool: &SqlitePool) -> Result<Vec<GameRound>> {
+        let rounds = sqlx::query_as::<_, GameRound>(
+            "SELECT * FROM game_rounds WHERE finished_at IS NULL AND deadline_at <= ? ORDER BY deadline_at",
+        )
+        .bind(chrono::Utc::now())
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch expired game rounds")?;
+
+        Ok(rounds)
+    }
+
+    /// Latest questions of a chat, so new rounds don't repeat them
+    pub async fn recent_questions(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<String>> {
+        let questions: Vec<(String,)> = sqlx::query_as(
+            "SELECT question FROM game_rounds WHERE account_id = ? AND chat_id = ? ORDER BY id DESC LIMIT ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch recent game questions")?;
+
+        Ok(questions.into_iter().map(|(q,)| q).collect())
+    }
+
+    /// Give a member a point, returning their new total
+    pub async fn add_point(pool: &SqlitePool, account_id: i64, chat_id: i64, user_id: i64) -> Result<i64> {
+        let (total,): (i64,) = sqlx::query_as(
+            r#"
+            INSERT INTO game_scores (account_id, chat_id, user_id, points)
+            VALUES (?, ?, ?, 1)
+            ON CONFLICT(account_id, chat_id, user_id) DO UPDATE SET
+                points = points + 1,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING points
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to update game score")?;
+
+        Ok(total)
+    }
+
+    /// Best players of a chat
+    pub async fn leaderboard(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<GameScore>> {
+        let top = sqlx::query_as::<_, GameScore>(
+            r#"
+            SELECT * FROM game_scores
+            WHERE account_id = ? AND chat_id = ? AND points > 0
+            ORDER BY points DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch game leaderboard")?;
+
+        Ok(top)
+    }
+
+    /// Clear a chat's scores
+    pub async fn reset_scores(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM game_scores WHERE account_id = ? AND chat_id = ?")
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(pool)
+            .await
+            .context("Failed to reset game scores")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct ModelRepository;
+
+impl ModelRepository {
+    /// Count a use of a model, recording its embedding dimension
+    pub async fn record_use(pool: &SqlitePool, kind: &str, name: &str, dimension: Option<i64>) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO models (kind, name, dimension, uses)
+            VALUES (?, ?, ?, 1)
+            ON CONFLICT(kind, name) DO UPDATE SET
+                dimension = COALESCE(excluded.dimension, models.dimension),
+                uses = models.uses + 1,
+                last_used_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(kind)
+        .bind(name)
+        .bind(dimension)
+        .execute(pool)
+        .await
+        .context("Failed to record model use")?;
+
+        Ok(())
+    }
+
+    /// All recorded models, most recently used first
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<ModelInfo>> {
+        let models = sqlx::query_as::<_, ModelInfo>(
+            "SELECT * FROM models ORDER BY kind, last_used_at DESC"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list models")?;
+
+        Ok(models)
+    }
+
+    /// Embedding models that produced vectors of the given size
+    pub async fn embedding_models_with_dimension(pool: &SqlitePool, dimension: i64) -> Result<Vec<String>> {
+        let names: Vec<(String,)> = sqlx::query_as(
+            "SELECT name FROM models WHERE kind = 'embedding' AND dimension = ? ORDER BY last_used_at DESC"
+        )
+        .bind(dimension)
+        .fetch_all(pool)
+        .await
+        .context("Failed to look up embedding models")?;
+
+        Ok(names.into_iter().map(|(n,)| n).collect())
+    }
+}
+
+pub struct VisionPromptRepository;
+
+impl VisionPromptRepository {
+    /// Template for a media type: the persona's override, then the global one
+    pub async fn resolve(pool: &SqlitePool, media_type: &str, persona_id: Option<i64>) -> Result<Option<String>> {
+        let template: Option<(String,)> = sqlx::query_as(
+            r#"
+            SELECT template FROM vision_prompts
+            WHERE media_type = ? AND persona_id IN (?, 0)
+            ORDER BY persona_id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(media_type)
+        .bind(persona_id.unwrap_or(0))
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch vision prompt")?;
+
+        Ok(template.map(|(t,)| t))
+    }
+
+    /// Set the template for a media type (persona_id None = global)
+    pub async fn set(pool: &SqlitePool, media_type: &str, persona_id: Option<i64>, template: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO vision_prompts (media_type, persona_id, template)
+            VALUES (?, ?, ?)
+            ON CONFLICT(media_type, persona_id) DO UPDATE SET
+                template = excluded.template,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(media_type)
+        .bind(persona_id.unwrap_or(0))
+        .bind(template)
+        .execute(pool)
+        .await
+        .context("Failed to save vision prompt")?;
+
+        Ok(())
+    }
+
+    /// Drop an override; returns false if there was none
+    pub async fn reset(pool: &SqlitePool, media_type: &str, persona_id: Option<i64>) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM vision_prompts WHERE media_type = ? AND persona_id = ?")
+            .bind(media_type)
+            .bind(persona_id.unwrap_or(0))
+            .execute(pool)
+            .await
+            .context("Failed to reset vision prompt")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// All overrides
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<VisionPrompt>> {
+        let prompts = sqlx::query_as::<_, VisionPrompt>(
+            "SELECT * FROM vision_prompts ORDER BY media_type, persona_id"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list vision prompts")?;
+
+        Ok(prompts)
+    }
+}
+
+pub struct ProfileRepository;
+
+impl ProfileRepository {
+    /// Create or replace a profile
+    pub async fn save(pool: &SqlitePool, profile: &ChatProfile) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_profiles
+                (name, persona_id, reply_probability, format_mode, initiative_enabled, reply_cooldown_secs, style_notes)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                persona_id = excluded.persona_id,
+                reply_probability = excluded.reply_probability,
+                format_mode = excluded.format_mode,
+                initiative_enabled = excluded.initiative_enabled,
+                reply_cooldown_secs = excluded.reply_cooldown_secs,
+                style_notes = excluded.style_notes,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(&profile.name)
+        .bind(profile.persona_id)
+        .bind(profile.reply_probability)
+        .bind(&profile.format_mode)
+        .bind(profile.initiative_enabled)
+        .bind(profile.reply_cooldown_secs)
+        .bind(&profile.style_notes)
+        .execute(pool)
+        .await
+        .context("Failed to save chat profile")?;
+
+        tracing::info!("Saved chat profile '{}'", profile.name);
+        Ok(())
+    }
+
+    pub async fn get(pool: &SqlitePool, name: &str) -> Result<Option<ChatProfile>> {
+        let profile = sqlx::query_as::<_, ChatProfile>("SELECT * FROM chat_profiles WHERE name = ?")
+            .bind(name)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch chat profile")?;
+
+        Ok(profile)
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<ChatProfile>> {
+        let profiles = sqlx::query_as::<_, ChatProfile>("SELECT * FROM chat_profiles ORDER BY name")
+            .fetch_all(pool)
+            .await
+            .context("Failed to list chat profiles")?;
+
+        Ok(profiles)
+    }
+
+    /// Delete a profile; chats keep the settings it applied
+    pub async fn delete(pool: &SqlitePool, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM chat_profiles WHERE name = ?")
+            .bind(name)
+            .execute(pool)
+            .await
+            .context("Failed to delete chat profile")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Apply every setting of a profile to a chat in a single statement
+    pub async fn apply(pool: &SqlitePool, account_id: i64, chat_id: i64, profile: &ChatProfile) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO account_chats
+                (account_id, chat_id, pinned_persona_id, reply_probability, format_mode,
+                 initiative_enabled, reply_cooldown_secs, style_notes, profile_name)
+            VALUES (?, ?, ?, ?, COALESCE(?, 'plain'), COALESCE(?, 0), ?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                pinned_persona_id = COALESCE(?3, account_chats.pinned_persona_id),
+                reply_probability = COALESCE(?4, account_chats.reply_probability),
+                format_mode = COALESCE(?5, account_chats.format_mode),
+                initiative_enabled = COALESCE(?6, account_chats.initiative_enabled),
+                reply_cooldown_secs = COALESCE(?7, account_chats.reply_cooldown_secs),
+                style_notes = COALESCE(?8, account_chats.style_notes),
+                profile_name = excluded.profile_name,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(profile.persona_id)
+        .bind(profile.reply_probability)
+        .bind(&profile.format_mode)
+        .bind(profile.initiative_enabled)
+        .bind(profile.reply_cooldown_secs)
+        .bind(&profile.style_notes)
+        .bind(&profile.name)
+        .execute(pool)
+        .await
+        .context("Failed to apply chat profile")?;
+
+        tracing::info!("Applied profile '{}' to chat {} on account {}", profile.name, chat_id, account_id);
+        Ok(())
+    }
+
+    /// Apply the set fields of a patch to many chats at once; all or none are updated.
+    /// Each changed chat gets a settings history entry in the same transaction.
+    pub async fn apply_bulk(pool: &SqlitePool, chats: &[(i64, i64)], patch: &ChatProfile, actor_id: i64) -> Result<u64> {
+        let mut patched = serde_json::Map::new();
+        if let Some(id) = patch.persona_id {
+            patched.insert("pinned_persona_id".to_string(), id.into());
+        }
+        if let Some(prob) = patch.reply_probability {
+            patched.insert("reply_probability".to_string(), prob.into());
+        }
+        if let Some(format) = &patch.format_mode {
+            patched.insert("format_mode".to_string(), format.clone().into());
+        }
+        if let Some(initiative) = patch.initiative_enabled {
+            patched.insert("initiative_enabled".to_string(), initiative.into());
+        }
+        if let Some(secs) = patch.reply_cooldown_secs {
+            patched.insert("reply_cooldown_secs".to_string(), secs.into());
+        }
+        if let Some(style) = &patch.style_notes {
+            patched.insert("style_notes".to_string(), style.clone().into());
+        }
+
+        let mut tx = pool.begin().await.context("Failed to start transaction")?;
+        let mut updated = 0;
+
+        for (account_id, chat_id) in chats {
+            let before = sqlx::query_as::<_, AccountChat>(
+                "SELECT * FROM account_chats WHERE account_id = ? AND chat_id = ?",
+            )
+            .bind(account_id)
+            .bind(chat_id)
+            .fetch_optional(&mut *tx)
+            .await
+            .context("Failed to fetch chat settings")?;
+            let before = match before {
+                Some(chat) => match serde_json::to_value(&chat).context("Failed to serialize chat settings")? {
+                    serde_json::Value::Object(fields) => fields,
+                    _ => anyhow::bail!("Chat settings are not an object"),
+                },
+                None => continue,
+            };
+
+            let result = sqlx::query(
+                r#"
+                UPDATE account_chats SET
+                    pinned_persona_id = COALESCE(?, pinned_persona_id),
+                    reply_probability = COALESCE(?, reply_probability),
+                    format_mode = COALESCE(?, format_mode),
+                    initiative_enabled = COALESCE(?, initiative_enabled),
+                    reply_cooldown_secs = COALESCE(?, reply_cooldown_secs),
+                    style_notes = COALESCE(?, style_notes),
+                    updated_at = CURRENT_TIMESTAMP
+                WHERE account_id = ? AND chat_id = ?
+                "#,
+            )
+            .bind(patch.persona_id)
+            .bind(patch.reply_probability)
+            .bind(&patch.format_mode)
+            .bind(patch.initiative_enabled)
+            .bind(patch.reply_cooldown_secs)
+            .bind(&patch.style_notes)
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to update chat settings")?;
+            updated += result.rows_affected();
+
+            let mut old_values = serde_json::Map::new();
+            let mut new_values = serde_json::Map::new();
+            for (field, value) in &patched {
+                let previous = before.get(field).cloned().unwrap_or(serde_json::Value::Null);
+                if &previous != value {
+                    old_values.insert(field.clone(), previous);
+                    new_values.insert(field.clone(), value.clone());
+                }
+            }
+            if new_values.is_empty() {
+                continue;
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO chat_setting_changes (account_id, chat_id, actor_id, command, old_values, new_values)
+                VALUES (?, ?, ?, '/set_all', ?, ?)
+                "#,
+            )
+            .bind(account_id)
+            .bind(chat_id)
+            .bind(actor_id)
+            .bind(serde_json::Value::Object(old_values).to_string())
+            .bind(serde_json::Value::Object(new_values).to_string())
+            .execute(&mut *tx)
+            .await
+            .context("Failed to record setting change")?;
+        }
+
+        tx.commit().await.context("Failed to commit bulk chat settings")?;
+
+        tracing::info!("Bulk-updated settings of {} chats by {}", updated, crate::logging::user_ref(actor_id));
+        Ok(updated)
+    }
+}
+
+pub struct CorrectionRepository;
+
+impl CorrectionRepository {
+    /// Record a bad → good pair (good is None for deleted replies)
+    pub async fn record(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        message_id: i64,
+        prompt: Option<&str>,
+        rejected: &str,
+        corrected: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO reply_corrections (account_id, chat_id, message_id, prompt, rejected, corrected)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(message_id)
+        .bind(prompt)
+        .bind(rejected)
+        .bind(corrected)
+        .execute(pool)
+        .await
+        .context("Failed to record reply correction")?;
+
+        Ok(())
+    }
+
+    /// Latest corrections of an account
+    pub async fn list(pool: &SqlitePool, account_id: i64, limit: i64) -> Result<Vec<ReplyCorrection>> {
+        let corrections = sqlx::query_as::<_, ReplyCorrection>(
+            "SELECT * FROM reply_corrections WHERE account_id = ? ORDER BY created_at DESC, id DESC LIMIT ?"
+        )
+        .bind(account_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list reply corrections")?;
+
+        Ok(corrections)
+    }
+
+    /// (edited, deleted) counts of an account
+    pub async fn counts(pool: &SqlitePool, account_id: i64) -> Result<(i64, i64)> {
+        let counts: (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT COALESCE(SUM(corrected IS NOT NULL), 0), COALESCE(SUM(corrected IS NULL), 0)
+            FROM reply_corrections WHERE account_id = ?
+            "#,
+        )
+        .bind(account_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to count reply corrections")?;
+
+        Ok(counts)
+    }
+}
+
+pub struct ExampleRepository;
+
+impl ExampleRepository {
+    pub async fn add(
+        pool: &SqlitePool,
+        persona_id: i64,
+        user_text: &str,
+        reply: &str,
+        embedding: &[u8],
+        source: &str,
+    ) -> Result<i64> {
+        let id: i64 = sqlx::query_scalar(
+            r#"
+            INSERT INTO persona_examples (persona_id, user_text, reply, embedding, source)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING id
+            "#,
+        )
+        .bind(persona_id)
+        .bind(user_text)
+        .bind(reply)
+        .bind(embedding)
+        .bind(source)
+        .fetch_one(pool)
+        .await
+        .context("Failed to add persona example")?;
+
+        tracing::info!("Added {} example {} to persona {}", source, id, persona_id);
+        Ok(id)
+    }
+
+    pub async fn list(pool: &SqlitePool, persona_id: i64) -> Result<Vec<PersonaExample>> {
+        let examples = sqlx::query_as::<_, PersonaExample>(
+            "SELECT * FROM persona_examples WHERE persona_id = ? ORDER BY created_at DESC, id DESC"
+        )
+        .bind(persona_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list persona examples")?;
+
+        Ok(examples)
+    }
+
+    /// Examples of one persona or all, created within an optional date range (YYYY-MM-DD, inclusive)
+    pub async fn list_between(
+        pool: &SqlitePool,
+        persona_id: Option<i64>,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<PersonaExample>> {
+        let examples = sqlx::query_as::<_, PersonaExample>(
+            r#"
+            SELECT * FROM persona_examples
+            WHERE (?1 IS NULL OR persona_id = ?1)
+              AND (?2 IS NULL OR date(created_at) >= ?2)
+              AND (?3 IS NULL OR date(created_at) <= ?3)
+            ORDER BY persona_id, id
+            "#,
+        )
+        .bind(persona_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list persona examples")?;
+
+        Ok(examples)
+    }
+
+    pub async fn delete(pool: &SqlitePool, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM persona_examples WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to delete persona example")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+pub struct TopicRepository;
+
+impl TopicRepository {
+    /// Record the topic a chat is discussing now
+    pub async fn add(pool: &SqlitePool, account_id: i64, chat_id: i64, topic: &str, embedding: &[u8]) -> Result<()> {
+        sqlx::query("INSERT INTO chat_topics (account_id, chat_id, topic, embedding) VALUES (?, ?, ?, ?)")
+            .bind(account_id)
+            .bind(chat_id)
+            .bind(topic)
+            .bind(embedding)
+            .execute(pool)
+            .await
+            .context("Failed to store chat topic")?;
+
+        // Only a short trail of past topics is kept
+        sqlx::query(
+            r#"
+            DELETE FROM chat_topics
+            WHERE account_id = ? AND chat_id = ?
+            AND id NOT IN (
+                SELECT id FROM chat_topics WHERE account_id = ? AND chat_id = ?
+                ORDER BY created_at DESC, id DESC LIMIT 20
+            )
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to trim chat topics")?;
+
+        Ok(())
+    }
+
+    /// Latest topics of a chat, newest first
+    pub async fn recent(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<ChatTopic>> {
+        let topics = sqlx::query_as::<_, ChatTopic>(
+            r#"
+            SELECT * FROM chat_topics WHERE account_id = ? AND chat_id = ?
+            ORDER BY created_at DESC, id DESC LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch chat topics")?;
+
+        Ok(topics)
+    }
+}
+
+pub struct BusinessRepository;
+
+impl BusinessRepository {
+    /// Record a new or changed connection; our own settings are kept
+    pub async fn upsert_connection(
+        pool: &SqlitePool,
+        connection_id: &str,
+        user_id: i64,
+        user_chat_id: i64,
+        can_reply: bool,
+        is_enabled: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO business_connections (connection_id, user_id, user_chat_id, can_reply, is_enabled)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(connection_id) DO UPDATE SET
+                can_reply = excluded.can_reply,
+                is_enabled = excluded.is_enabled,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(connection_id)
+        .bind(user_id)
+        .bind(user_chat_id)
+        .bind(can_reply)
+        .bind(is_enabled)
+        .execute(pool)
+        .await
+        .context("Failed to store business connection")?;
+
+        Ok(())
+    }
+
+    pub async fn get(pool: &SqlitePool, connection_id: &str) -> Result<Option<BusinessConnection>> {
+        let connection = sqlx::query_as::<_, BusinessConnection>(
+            "SELECT * FROM business_connections WHERE connection_id = ?"
+        )
+        .bind(connection_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch business connection")?;
+
+        Ok(connection)
+    }
+
+    /// Newest connection of a business account
+    pub async fn get_by_user(pool: &SqlitePool, user_id: i64) -> Result<Option<BusinessConnection>> {
+        let connection = sqlx::query_as::<_, BusinessConnection>(
+            "SELECT * FROM business_connections WHERE user_id = ? ORDER BY created_at DESC LIMIT 1"
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch business connection")?;
+
+        Ok(connection)
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<BusinessConnection>> {
+        let connections = sqlx::query_as::<_, BusinessConnection>(
+            "SELECT * FROM business_connections ORDER BY created_at DESC"
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list business connections")?;
+
+        Ok(connections)
+    }
+
+    /// Save the auto-responder settings of a connection
+    pub async fn update_settings(pool: &SqlitePool, connection: &BusinessConnection) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE business_connections SET
+                auto_reply = ?, persona_id = ?, reply_mode = ?, work_days = ?,
+                work_start_hour = ?, work_end_hour = ?, timezone = ?, updated_at = CURRENT_TIMESTAMP
+            WHERE connection_id = ?
+            "#,
+        )
+        .bind(connection.auto_reply)
+        .bind(connection.persona_id)
+        .bind(&connection.reply_mode)
+        .bind(&connection.work_days)
+        .bind(connection.work_start_hour)
+        .bind(connection.work_end_hour)
+        .bind(&connection.timezone)
+        .bind(&connection.connection_id)
+        .execute(pool)
+        .await
+        .context("Failed to update business settings")?;
+
+        Ok(())
+    }
+
+    pub async fn add_message(pool: &SqlitePool, connection_id: &str, chat_id: i64, role: MessageRole, content: &str) -> Result<()> {
+        sqlx::query("INSERT INTO business_messages (connection_id, chat_id, role, content) VALUES (?, ?, ?, ?)")
+            .bind(connection_id)
+            .bind(chat_id)
+            .bind(role.as_str())
+            .bind(content)
+            .execute(pool)
+            .await
+            .context("Failed to save business message")?;
+
+        Ok(())
+    }
+
+    /// Latest messages of a business chat, oldest first
+    pub async fn recent_messages(
+        pool: &SqlitePool,
+        connection_id: &str,
+        chat_id: i64,
+        limit: i64,
+    ) -> Result<Vec<BusinessMessage>> {
+        let messages = sqlx::query_as::<_, BusinessMessage>(
+            r#"
+            SELECT * FROM business_messages WHERE connection_id = ? AND chat_id = ?
+            ORDER BY created_at DESC, id DESC LIMIT ?
+            "#,
+        )
+        .bind(connection_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch business messages")?;
+
+        Ok(messages.into_iter().rev().collect())
+    }
+}
+
+pub struct EphemeralRepository;
+
+impl EphemeralRepository {
+    /// Schedule sent messages for deletion
+    pub async fn schedule(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        message_ids: &[i64],
+        delete_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        for message_id in message_ids {
+            sqlx::query("INSERT INTO ephemeral_messages (account_id, chat_id, message_id, delete_at) VALUES (?, ?, ?, ?)")
+                .bind(account_id)
+                .bind(chat_id)
+                .bind(message_id)
+                .bind(delete_at)
+                .execute(pool)
+                .await
+                .context("Failed to schedule message deletion")?;
+        }
+
+        Ok(())
+    }
+
+    /// Swap TDLib's temporary message ID for the final one once a send succeeds
+    pub async fn confirm_sent(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        old_message_id: i64,
+        message_id: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE ephemeral_messages SET message_id = ? WHERE account_id = ? AND chat_id = ? AND message_id = ?",
+        )
+        .bind(message_id)
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(old_message_id)
+        .execute(pool)
+        .await
+        .context("Failed to confirm ephemeral message")?;
+
+        Ok(())
+    }
+
+    /// An account's messages whose time is up, oldest first
+    pub async fn due(pool: &SqlitePool, account_id: i64, limit: i64) -> Result<Vec<EphemeralMessage>> {
+        let messages = sqlx::query_as::<_, EphemeralMessage>(
+            "SELECT * FROM ephemeral_messages WHERE account_id = ? AND delete_at <= ? ORDER BY delete_at LIMIT ?",
+        )
+        .bind(account_id)
+        .bind(chrono::Utc::now())
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch due ephemeral messages")?;
+
+        Ok(messages)
+    }
+
+    /// Drop deletions that were due before `before`; returns how many
+    pub async fn remove_overdue(pool: &SqlitePool, before: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM ephemeral_messages WHERE delete_at < ?")
+            .bind(before)
+            .execute(pool)
+            .await
+            .context("Failed to remove overdue ephemeral messages")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM ephemeral_messages WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to remove ephemeral message")?;
+
+        Ok(())
+    }
+
+    /// Pending deletions in a chat
+    pub async fn count_pending(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<i64> {
+        let (count,): (i64,) =
+            sqlx::query_as("SELECT COUNT(*) FROM ephemeral_messages WHERE account_id = ? AND chat_id = ?")
+                .bind(account_id)
+                .bind(chat_id)
+                .fetch_one(pool)
+                .await
+                .context("Failed to count ephemeral messages")?;
+
+        Ok(count)
+    }
+}
+
+pub struct AnswerCacheRepository;
+
+impl AnswerCacheRepository {
+    /// Cached answers of a chat generated after `since`
+    pub async fn fresh(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<CachedAnswer>> {
+        let answers = sqlx::query_as::<_, CachedAnswer>(
+            "SELECT * FROM answer_cache WHERE account_id = ? AND chat_id = ? AND updated_at >= ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch cached answers")?;
+
+        Ok(answers)
+    }
+
+    /// All cached answers of a chat, stale ones included
+    pub async fn list(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Vec<CachedAnswer>> {
+        let answers = sqlx::query_as::<_, CachedAnswer>(
+            "SELECT * FROM answer_cache WHERE account_id = ? AND chat_id = ? ORDER BY hits DESC, updated_at DESC",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list cached answers")?;
+
+        Ok(answers)
+    }
+
+    pub async fn add(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        persona_id: Option<i64>,
+        question: &str,
+        embedding: &[u8],
+        answer: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO answer_cache (account_id, chat_id, persona_id, question, embedding, answer)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(persona_id)
+        .bind(question)
+        .bind(embedding)
+        .bind(answer)
+        .execute(pool)
+        .await
+        .context("Failed to cache answer")?;
+
+        Ok(())
+    }
+
+    /// Replace a stale answer with a freshly generated one
+    pub async fn refresh(pool: &SqlitePool, id: i64, persona_id: Option<i64>, answer: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE answer_cache SET answer = ?, persona_id = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(answer)
+        .bind(persona_id)
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to refresh cached answer")?;
+
+        Ok(())
+    }
+
+    pub async fn record_hit(pool: &SqlitePool, id: i64) -> Result<()> {
+        sqlx::query("UPDATE answer_cache SET hits = hits + 1 WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to record cache hit")?;
+
+        Ok(())
+    }
+
+    /// Drop a chat's cached answers. Returns how many were removed.
+    pub async fn clear(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM answer_cache WHERE account_id = ? AND chat_id = ?")
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(pool)
+            .await
+            .context("Failed to clear answer cache")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct MediaQuotaRepository;
+
+impl MediaQuotaRepository {
+    /// Count one use unless the day's limit is reached, in a single statement.
+    /// Returns false when the quota is exhausted.
+    pub async fn consume(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        kind: &str,
+        day: &str,
+        limit: Option<i64>,
+    ) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO media_quotas (account_id, chat_id, kind, day, used)
+            VALUES (?1, ?2, ?3, ?4, 1)
+            ON CONFLICT(account_id, chat_id, kind) DO UPDATE SET
+                used = CASE WHEN media_quotas.day = excluded.day THEN media_quotas.used + 1 ELSE 1 END,
+                day = excluded.day
+            WHERE ?5 IS NULL
+                OR (CASE WHEN media_quotas.day = excluded.day THEN media_quotas.used ELSE 0 END) < ?5
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(kind)
+        .bind(day)
+        .bind(limit)
+        .execute(pool)
+        .await
+        .context("Failed to consume media quota")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get(pool: &SqlitePool, account_id: i64, chat_id: i64, kind: &str) -> Result<Option<MediaQuota>> {
+        let quota = sqlx::query_as::<_, MediaQuota>(
+            "SELECT * FROM media_quotas WHERE account_id = ? AND chat_id = ? AND kind = ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(kind)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch media quota")?;
+
+        Ok(quota)
+    }
+
+    /// Quotas of all chats of an account
+    pub async fn list_for_account(pool: &SqlitePool, account_id: i64) -> Result<Vec<MediaQuota>> {
+        let quotas = sqlx::query_as::<_, MediaQuota>("SELECT * FROM media_quotas WHERE account_id = ?")
+            .bind(account_id)
+            .fetch_all(pool)
+            .await
+            .context("Failed to list media quotas")?;
+
+        Ok(quotas)
+    }
+
+    /// Override the daily limit of a chat (None = back to the default)
+    pub async fn set_limit(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        kind: &str,
+        limit: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO media_quotas (account_id, chat_id, kind, daily_limit)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id, kind) DO UPDATE SET
+                daily_limit = excluded.daily_limit
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(kind)
+        .bind(limit)
+        .execute(pool)
+        .await
+        .context("Failed to set media quota")?;
+
+        Ok(())
+    }
+}
+
+pub struct FeedRepository;
+
+impl FeedRepository {
+    /// Subscribe a chat to a feed; returns None if it already is
+    pub async fn add(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        url: &str,
+        title: &str,
+        seen_ids: &[String],
+    ) -> Result<Option<i64>> {
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO feeds (account_id, chat_id, url, title, seen_ids, last_checked_at)
+            VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(url)
+        .bind(title)
+        .bind(serde_json::to_string(seen_ids)?)
+        .execute(pool)
+        .await
+        .context("Failed to add feed")?;
+
+        Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+    }
+
+    pub async fn list_all(pool: &SqlitePool) -> Result<Vec<Feed>> {
+        let feeds = sqlx::query_as::<_, Feed>("SELECT * FROM feeds ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .context("Failed to list feeds")?;
+
+        Ok(feeds)
+    }
+
+    pub async fn list_for_account(pool: &SqlitePool, account_id: i64) -> Result<Vec<Feed>> {
+        let feeds = sqlx::query_as::<_, Feed>("SELECT * FROM feeds WHERE account_id = ? ORDER BY chat_id, id")
+            .bind(account_id)
+            .fetch_all(pool)
+            .await
+            .context("Failed to list feeds")?;
+
+        Ok(feeds)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM feeds WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to remove feed")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Store the result of a successful check
+    pub async fn record_check(
+        pool: &SqlitePool,
+        id: i64,
+        title: &str,
+        etag: Option<&str>,
+        last_modified: Option<&str>,
+        seen_ids: &[String],
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE feeds SET
+                title = ?, etag = ?, last_modified = ?, seen_ids = ?,
+                last_checked_at = CURRENT_TIMESTAMP, last_error = NULL
+            WHERE id = ?
+            "#,
+        )
+        .bind(title)
+        .bind(etag)
+        .bind(last_modified)
+        .bind(serde_json::to_string(seen_ids)?)
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to update feed")?;
+
+        Ok(())
+    }
+
+    /// A check that got nothing new (304) or failed
+    pub async fn record_unchanged(pool: &SqlitePool, id: i64, error: Option<&str>) -> Result<()> {
+        sqlx::query("UPDATE feeds SET last_checked_at = CURRENT_TIMESTAMP, last_error = ? WHERE id = ?")
+            .bind(error)
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update feed")?;
+
+        Ok(())
+    }
+}
+
+pub struct WebhookRepository;
+
+impl WebhookRepository {
+    pub async fn add(pool: &SqlitePool, url: &str, events: &str, secret: &str, keywords: Option<&str>) -> Result<i64> {
+        let result = sqlx::query("INSERT INTO webhooks (url, events, secret, keywords) VALUES (?, ?, ?, ?)")
+            .bind(url)
+            .bind(events)
+            .bind(secret)
+            .bind(keywords)
+            .execute(pool)
+            .await
+            .context("Failed to add webhook")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &SqlitePool, id: i64) -> Result<Option<Webhook>> {
+        let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch webhook")?;
+
+        Ok(webhook)
+    }
+
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<Webhook>> {
+        let webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .context("Failed to list webhooks")?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn list_active(pool: &SqlitePool) -> Result<Vec<Webhook>> {
+        let webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE is_active = 1 ORDER BY id")
+            .fetch_all(pool)
+            .await
+            .context("Failed to list webhooks")?;
+
+        Ok(webhooks)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to remove webhook")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn set_status(pool: &SqlitePool, id: i64, status: &str) -> Result<()> {
+        sqlx::query("UPDATE webhooks SET last_status = ? WHERE id = ?")
+            .bind(status)
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update webhook status")?;
+
+        Ok(())
+    }
+
+    /// Queue a delivery for the next worker pass
+    pub async fn enqueue(pool: &SqlitePool, webhook_id: i64, event: &str, payload: &str) -> Result<()> {
+        sqlx::query("INSERT INTO webhook_deliveries (webhook_id, event, payload) VALUES (?, ?, ?)")
+            .bind(webhook_id)
+            .bind(event)
+            .bind(payload)
+            .execute(pool)
+            .await
+            .context("Failed to queue webhook delivery")?;
+
+        Ok(())
+    }
+
+    pub async fn due_deliveries(pool: &SqlitePool, limit: i64) -> Result<Vec<WebhookDelivery>> {
+        let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+            "SELECT * FROM webhook_deliveries WHERE next_attempt_at <= CURRENT_TIMESTAMP ORDER BY id LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch due webhook deliveries")?;
+
+        Ok(deliveries)
+    }
+
+    pub async fn retry_later(pool: &SqlitePool, delivery_id: i64, delay_secs: i64) -> Result<()> {
+        sqlx::query(
+            "UPDATE webhook_deliveries SET attempts = attempts + 1, \
+             next_attempt_at = datetime('now', '+' || ? || ' seconds') WHERE id = ?",
+        )
+        .bind(delay_secs)
+        .bind(delivery_id)
+        .execute(pool)
+        .await
+        .context("Failed to reschedule webhook delivery")?;
+
+        Ok(())
+    }
+
+    pub async fn remove_delivery(pool: &SqlitePool, delivery_id: i64) -> Result<()> {
+        sqlx::query("DELETE FROM webhook_deliveries WHERE id = ?")
+            .bind(delivery_id)
+            .execute(pool)
+            .await
+            .context("Failed to remove webhook delivery")?;
+
+        Ok(())
+    }
+
+    pub async fn count_pending(pool: &SqlitePool, webhook_id: i64) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM webhook_deliveries WHERE webhook_id = ?")
+            .bind(webhook_id)
+            .fetch_one(pool)
+            .await
+            .context("Failed to count webhook deliveries")?;
+
+        Ok(count.0)
+    }
+}
+
+pub struct EntityRepository;
+
+impl EntityRepository {
+    /// Add an entity or count another mention of it; a known kind or description is never erased
+    pub async fn upsert(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        name: &str,
+        kind: &str,
+        description: Option<&str>,
+        message_id: Option<i64>,
+    ) -> Result<ChatEntity> {
+        let entity = sqlx::query_as::<_, ChatEntity>(
+            r#"
+            INSERT INTO chat_entities (account_id, chat_id, name, name_key, kind, description, last_message_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(account_id, chat_id, name_key) DO UPDATE SET
+                mentions = mentions + 1,
+                kind = CASE WHEN excluded.kind != 'other' THEN excluded.kind ELSE kind END,
+                description = COALESCE(excluded.description, description),
+                last_message_id = COALESCE(excluded.last_message_id, last_message_id),
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING *
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(name)
+        .bind(name.to_lowercase())
+        .bind(kind)
+        .bind(description)
+        .bind(message_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to store entity")?;
+
+        Ok(entity)
+    }
+
+    pub async fn add_relation(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        subject_id: i64,
+        relation: &str,
+        object_id: i64,
+        message_id: Option<i64>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO entity_relations (account_id, chat_id, subject_id, relation, object_id, message_id)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(subject_id, relation, object_id) DO UPDATE SET
+                message_id = COALESCE(excluded.message_id, message_id)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(subject_id)
+        .bind(relation)
+        .bind(object_id)
+        .bind(message_id)
+        .execute(pool)
+        .await
+        .context("Failed to store entity relation")?;
+
+        Ok(())
+    }
+
+    /// Entities of a chat, most mentioned first
+    pub async fn list_for_chat(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<ChatEntity>> {
+        let entities = sqlx::query_as::<_, ChatEntity>(
+            r#"
+            SELECT * FROM chat_entities WHERE account_id = ? AND chat_id = ?
+            ORDER BY mentions DESC, updated_at DESC LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch chat entities")?;
+
+        Ok(entities)
+    }
+
+    pub async fn find(pool: &SqlitePool, account_id: i64, chat_id: i64, name: &str) -> Result<Option<ChatEntity>> {
+        let entity = sqlx::query_as::<_, ChatEntity>(
+            "SELECT * FROM chat_entities WHERE account_id = ? AND chat_id = ? AND name_key = ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(name.trim().to_lowercase())
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch entity")?;
+
+        Ok(entity)
+    }
+
+    /// Relations an entity takes part in, either side
+    pub async fn relations(pool: &SqlitePool, entity_id: i64, limit: i64) -> Result<Vec<EntityRelation>> {
+        let relations = sqlx::query_as::<_, EntityRelation>(
+            r#"
+            SELECT r.id, s.name AS subject, r.relation, o.name AS object, r.message_id
+            FROM entity_relations r
+            JOIN chat_entities s ON s.id = r.subject_id
+            JOIN chat_entities o ON o.id = r.object_id
+            WHERE r.subject_id = ? OR r.object_id = ?
+            ORDER BY r.created_at DESC LIMIT ?
+            "#,
+        )
+        .bind(entity_id)
+        .bind(entity_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch entity relations")?;
+
+        Ok(relations)
+    }
+}
+
+pub struct TuningRepository;
+
+impl TuningRepository {
+    pub async fn get_epoch(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<TuningEpoch>> {
+        let epoch = sqlx::query_as::<_, TuningEpoch>(
+            "SELECT * FROM reply_tuning_epochs WHERE account_id = ? AND chat_id = ?",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch tuning epoch")?;
+
+        Ok(epoch)
+    }
+
+    /// Start counting engagement at a probability, dropping what the previous epoch collected
+    pub async fn start_epoch(pool: &SqlitePool, account_id: i64, chat_id: i64, probability: i64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO reply_tuning_epochs (account_id, chat_id, probability)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, chat_id) DO UPDATE SET
+                probability = excluded.probability,
+                replies_to_bot = 0,
+                negatives = 0,
+                started_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(probability)
+        .execute(pool)
+        .await
+        .context("Failed to start tuning epoch")?;
+
+        Ok(())
+    }
+
+    /// Count a reply to the account or a negative reaction in the running epoch
+    pub async fn record_signal(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        reply_to_bot: bool,
+        negative: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE reply_tuning_epochs
+            SET replies_to_bot = replies_to_bot + ?, negatives = negatives + ?
+            WHERE account_id = ? AND chat_id = ?
+            "#,
+        )
+        .bind(reply_to_bot as i64)
+        .bind(negative as i64)
+        .bind(account_id)
+        .bind(chat_id)
+        .execute(pool)
+        .await
+        .context("Failed to record tuning signal")?;
+
+        Ok(())
+    }
+
+    /// Replies the account sent since the epoch started
+    pub async fn epoch_activity(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<i64> {
+        let replies: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT
+                (SELECT COUNT(*) FROM messages_history m
+                 WHERE m.account_id = e.account_id AND m.chat_id = e.chat_id
+                 AND m.role = 'assistant' AND m.created_at >= e.started_at)
+            FROM reply_tuning_epochs e
+            WHERE e.account_id = ? AND e.chat_id = ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to count tuning epoch activity")?;
+
+        Ok(replies.unwrap_or(0))
+    }
+
+    /// Whether the running epoch is at least this old
+    pub async fn epoch_is_older_than(pool: &SqlitePool, account_id: i64, chat_id: i64, hours: i64) -> Result<bool> {
+        let old: Option<bool> = sqlx::query_scalar(
+            r#"
+            SELECT started_at <= datetime('now', '-' || ? || ' hours') FROM reply_tuning_epochs
+            WHERE account_id = ? AND chat_id = ?
+            "#,
+        )
+        .bind(hours)
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to check tuning epoch age")?;
+
+        Ok(old.unwrap_or(false))
+    }
+
+    pub async fn arms(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Vec<TuningArm>> {
+        let arms = sqlx::query_as::<_, TuningArm>(
+            r#"
+            SELECT probability, pulls, reward_sum FROM reply_tuning_arms
+            WHERE account_id = ? AND chat_id = ?
+            ORDER BY probability
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch tuning arms")?;
+
+        Ok(arms)
+    }
+
+    pub async fn record_pull(pool: &SqlitePool, account_id: i64, chat_id: i64, probability: i64, reward: f64) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO reply_tuning_arms (account_id, chat_id, probability, pulls, reward_sum)
+            VALUES (?, ?, ?, 1, ?)
+            ON CONFLICT(account_id, chat_id, probability) DO UPDATE SET
+                pulls = pulls + 1,
+                reward_sum = reward_sum + excluded.reward_sum
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(probability)
+        .bind(reward)
+        .execute(pool)
+        .await
+        .context("Failed to record tuning result")?;
+
+        Ok(())
+    }
+
+    pub async fn log_decision(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        old_probability: i64,
+        new_probability: i64,
+        reward: f64,
+        reason: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO reply_tuning_log (account_id, chat_id, old_probability, new_probability, reward, reason)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(old_probability)
+        .bind(new_probability)
+        .bind(reward)
+        .bind(reason)
+        .execute(pool)
+        .await
+        .context("Failed to log tuning decision")?;
+
+        Ok(())
+    }
+
+    /// Latest decisions for a chat, newest first
+    pub async fn recent_decisions(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<TuningDecision>> {
+        let decisions = sqlx::query_as::<_, TuningDecision>(
+            r#"
+            SELECT * FROM reply_tuning_log WHERE account_id = ? AND chat_id = ?
+            ORDER BY created_at DESC, id DESC LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch tuning decisions")?;
+
+        Ok(decisions)
+    }
+
+    /// Forget what was learned, e.g. when the bounds change
+    pub async fn reset(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<()> {
+        let mut tx = pool.begin().await.context("Failed to start transaction")?;
+
+        sqlx::query("DELETE FROM reply_tuning_epochs WHERE account_id = ? AND chat_id = ?")
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to reset tuning epoch")?;
+
+        sqlx::query("DELETE FROM reply_tuning_arms WHERE account_id = ? AND chat_id = ?")
+            .bind(account_id)
+            .bind(chat_id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to reset tuning arms")?;
+
+        tx.commit().await.context("Failed to commit tuning reset")?;
+
+        Ok(())
+    }
+}
+
+pub struct SettingChangeRepository;
+
+impl SettingChangeRepository {
+    pub async fn record(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        actor_id: i64,
+        command: &str,
+        old_values: Option<&str>,
+        new_values: &str,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO chat_setting_changes (account_id, chat_id, actor_id, command, old_values, new_values)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(actor_id)
+        .bind(command)
+        .bind(old_values)
+        .bind(new_values)
+        .execute(pool)
+        .await
+        .context("Failed to record setting change")?;
+
+        Ok(())
+    }
+
+    /// The newest change of a chat that wasn't undone yet
+    pub async fn last_active(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<ChatSettingChange>> {
+        let change = sqlx::query_as::<_, ChatSettingChange>(
+            r#"
+            SELECT * FROM chat_setting_changes
+            WHERE account_id = ? AND chat_id = ? AND undone = 0
+            ORDER BY id DESC LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch last setting change")?;
+
+        Ok(change)
+    }
+
+    /// A chat's changes, newest first, undone ones included
+    pub async fn history(pool: &SqlitePool, account_id: i64, chat_id: i64, limit: i64) -> Result<Vec<ChatSettingChange>> {
+        let changes = sqlx::query_as::<_, ChatSettingChange>(
+            r#"
+            SELECT * FROM chat_setting_changes
+            WHERE account_id = ? AND chat_id = ?
+            ORDER BY id DESC LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch settings history")?;
+
+        Ok(changes)
+    }
+
+    /// Changes of a chat from `from_id` on that are still in effect, newest first
+    pub async fn active_since(pool: &SqlitePool, account_id: i64, chat_id: i64, from_id: i64) -> Result<Vec<ChatSettingChange>> {
+        let changes = sqlx::query_as::<_, ChatSettingChange>(
+            r#"
+            SELECT * FROM chat_setting_changes
+            WHERE account_id = ? AND chat_id = ? AND id >= ? AND undone = 0
+            ORDER BY id DESC
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(from_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch setting changes")?;
+
+        Ok(changes)
+    }
+
+    /// Seconds since the chat's settings last changed, if they ever did
+    pub async fn seconds_since_last(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Option<i64>> {
+        let seconds: Option<i64> = sqlx::query_scalar(
+            r#"
+            SELECT CAST(strftime('%s', 'now') - strftime('%s', MAX(created_at)) AS INTEGER)
+            FROM chat_setting_changes WHERE account_id = ? AND chat_id = ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to fetch last setting change time")?;
+
+        Ok(seconds)
+    }
+
+    /// Put the fields of a change back and take it off the undo stack.
+    /// The field names are interpolated: callers pass only known account_chats columns.
+    pub async fn revert(
+        pool: &SqlitePool,
+        change: &ChatSettingChange,
+        old_values: Option<&serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<()> {
+        let mut tx = pool.begin().await.context("Failed to start transaction")?;
+
+        match old_values {
+            Some(fields) => {
+                for (field, value) in fields {
+                    let sql = format!("UPDATE account_chats SET {} = ? WHERE account_id = ? AND chat_id = ?", field);
+                    let query = sqlx::query(&sql);
+                    let query = match value {
+                        serde_json::Value::Null => query.bind(None::<i64>),
+                        serde_json::Value::Bool(b) => query.bind(*b),
+                        serde_json::Value::Number(n) if n.is_i64() => query.bind(n.as_i64()),
+                        serde_json::Value::Number(n) => query.bind(n.as_f64()),
+                        // Timestamps went through serde as RFC 3339; store them the way SQLite does
+                        serde_json::Value::String(s) => match chrono::DateTime::parse_from_rfc3339(s) {
+                            Ok(at) => query.bind(at.naive_utc().format("%Y-%m-%d %H:%M:%S").to_string()),
+                            Err(_) => query.bind(s.clone()),
+                        },
+                        other => query.bind(other.to_string()),
+                    };
+                    query
+                        .bind(change.account_id)
+                        .bind(change.chat_id)
+                        .execute(&mut *tx)
+                        .await
+                        .with_context(|| format!("Failed to restore {}", field))?;
+                }
+            }
+            // The command created the row; before it the chat ran on defaults
+            None => {
+                sqlx::query("DELETE FROM account_chats WHERE account_id = ? AND chat_id = ?")
+                    .bind(change.account_id)
+                    .bind(change.chat_id)
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to remove chat settings")?;
+            }
+        }
+
+        sqlx::query("UPDATE chat_setting_changes SET undone = 1 WHERE id = ?")
+            .bind(change.id)
+            .execute(&mut *tx)
+            .await
+            .context("Failed to mark setting change undone")?;
+
+        tx.commit().await.context("Failed to commit setting undo")?;
+
+        Ok(())
+    }
+}
+
+pub struct TraceRepository;
+
+impl TraceRepository {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        correlation_id: &str,
+        incoming_message_id: i64,
+        reply_message_id: Option<i64>,
+        stages: &str,
+        total_ms: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO reply_traces (account_id, chat_id, correlation_id, incoming_message_id, reply_message_id, stages, total_ms)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(correlation_id)
+        .bind(incoming_message_id)
+        .bind(reply_message_id)
+        .bind(stages)
+        .bind(total_ms)
+        .execute(pool)
+        .await
+        .context("Failed to record reply trace")?;
+
+        Ok(())
+    }
+
+    /// Swap TDLib's temporary message ID for the final one once a send succeeds
+    pub async fn confirm_sent(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        old_message_id: i64,
+        message_id: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE reply_traces SET reply_message_id = ? WHERE account_id = ? AND chat_id = ? AND reply_message_id = ?",
+        )
+        .bind(message_id)
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(old_message_id)
+        .execute(pool)
+        .await
+        .context("Failed to confirm traced reply")?;
+
+        Ok(())
+    }
+
+    /// Traces of a reply, or of the reply to a message, newest first; any chat when `chat_id` is None
+    pub async fn find(pool: &SqlitePool, chat_id: Option<i64>, message_id: i64, limit: i64) -> Result<Vec<ReplyTraceRecord>> {
+        let traces = sqlx::query_as::<_, ReplyTraceRecord>(
+            r#"
+            SELECT * FROM reply_traces
+            WHERE (reply_message_id = ? OR incoming_message_id = ?) AND (? IS NULL OR chat_id = ?)
+            ORDER BY id DESC LIMIT ?
+            "#,
+        )
+        .bind(message_id)
+        .bind(message_id)
+        .bind(chat_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch reply traces")?;
+
+        Ok(traces)
+    }
+
+    pub async fn delete_older_than(pool: &SqlitePool, days: i64) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM reply_traces WHERE created_at < datetime('now', '-' || ? || ' days')")
+            .bind(days)
+            .execute(pool)
+            .await
+            .context("Failed to delete old reply traces")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct EmbedBacklogRepository;
+
+impl EmbedBacklogRepository {
+    pub async fn create(pool: &SqlitePool, chat_id: Option<i64>, report_chat_id: i64) -> Result<EmbedBacklogJob> {
+        let job = sqlx::query_as::<_, EmbedBacklogJob>(
+            "INSERT INTO embed_backlog_jobs (chat_id, report_chat_id) VALUES (?, ?) RETURNING *",
+        )
+        .bind(chat_id)
+        .bind(report_chat_id)
+        .fetch_one(pool)
+        .await
+        .context("Failed to create embed backlog job")?;
+
+        Ok(job)
+    }
+
+    pub async fn list_running(pool: &SqlitePool) -> Result<Vec<EmbedBacklogJob>> {
+        let jobs = sqlx::query_as::<_, EmbedBacklogJob>(
+            "SELECT * FROM embed_backlog_jobs WHERE status = 'running' ORDER BY id",
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to list embed backlog jobs")?;
+
+        Ok(jobs)
+    }
+
+    /// A running job covering the same chats, so a command isn't started twice
+    pub async fn find_running(pool: &SqlitePool, chat_id: Option<i64>) -> Result<Option<EmbedBacklogJob>> {
+        let job = sqlx::query_as::<_, EmbedBacklogJob>(
+            "SELECT * FROM embed_backlog_jobs WHERE status = 'running' AND chat_id IS ? LIMIT 1",
+        )
+        .bind(chat_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to find embed backlog job")?;
+
+        Ok(job)
+    }
+
+    pub async fn save_progress(
+        pool: &SqlitePool,
+        id: i64,
+        last_message_id: i64,
+        scanned: i64,
+        embedded: i64,
+        failed: i64,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE embed_backlog_jobs
+            SET last_message_id = ?, scanned = scanned + ?, embedded = embedded + ?, failed = failed + ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(last_message_id)
+        .bind(scanned)
+        .bind(embedded)
+        .bind(failed)
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to save embed backlog progress")?;
+
+        Ok(())
+    }
+
+    pub async fn set_status(pool: &SqlitePool, id: i64, status: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE embed_backlog_jobs SET status = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND status = 'running'",
+        )
+        .bind(status)
+        .bind(id)
+        .execute(pool)
+        .await
+        .context("Failed to update embed backlog job")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn get(pool: &SqlitePool, id: i64) -> Result<Option<EmbedBacklogJob>> {
+        let job = sqlx::query_as::<_, EmbedBacklogJob>("SELECT * FROM embed_backlog_jobs WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch embed backlog job")?;
+
+        Ok(job)
+    }
+}
+
+pub struct HandoffRepository;
+
+impl HandoffRepository {
+    pub async fn create(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        user_id: Option<i64>,
+        reason: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Handoff> {
+        let handoff = sqlx::query_as::<_, Handoff>(
+            r#"
+            INSERT INTO handoffs (account_id, chat_id, user_id, reason, expires_at)
+            VALUES (?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(reason)
+        .bind(expires_at)
+        .fetch_one(pool)
+        .await
+        .context("Failed to create handoff")?;
+
+        tracing::info!("Handed chat {} (account {}) over to a human until {}: {}", chat_id, account_id, expires_at, reason);
+        Ok(handoff)
+    }
+
+    /// The running handoff covering a user in a chat, whether for them or the whole chat
+    pub async fn active_for(pool: &SqlitePool, account_id: i64, chat_id: i64, user_id: i64) -> Result<Option<Handoff>> {
+        let handoff = sqlx::query_as::<_, Handoff>(
+            r#"
+            SELECT * FROM handoffs
+            WHERE account_id = ? AND chat_id = ? AND (user_id IS NULL OR user_id = ?)
+              AND resumed_at IS NULL AND expires_at > ?
+            ORDER BY id DESC LIMIT 1
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .fetch_optional(pool)
+        .await
+        .context("Failed to look up handoff")?;
 
-        query.execute(pool)
+        Ok(handoff)
+    }
+
+    pub async fn list_active(pool: &SqlitePool) -> Result<Vec<Handoff>> {
+        let handoffs = sqlx::query_as::<_, Handoff>(
+            "SELECT * FROM handoffs WHERE resumed_at IS NULL AND expires_at > ? ORDER BY expires_at",
+        )
+        .bind(chrono::Utc::now())
+        .fetch_all(pool)
+        .await
+        .context("Failed to list handoffs")?;
+
+        Ok(handoffs)
+    }
+
+    /// Hand a chat (or one user in it; None = all of the chat's handoffs) back to the bot
+    pub async fn resume(pool: &SqlitePool, account_id: i64, chat_id: i64, user_id: Option<i64>) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            UPDATE handoffs SET resumed_at = ?
+            WHERE account_id = ? AND chat_id = ? AND (? IS NULL OR user_id = ?)
+              AND resumed_at IS NULL AND expires_at > ?
+            "#,
+        )
+        .bind(chrono::Utc::now())
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(user_id)
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await
+        .context("Failed to resume handoff")?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+pub struct ChatRuleRepository;
+
+impl ChatRuleRepository {
+    pub async fn add(pool: &SqlitePool, rule: &NewChatRule) -> Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO chat_rules (account_id, chat_id, sender_id, pattern, persona_id, temperature, reply, priority)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(rule.account_id)
+        .bind(rule.chat_id)
+        .bind(rule.sender_id)
+        .bind(&rule.pattern)
+        .bind(rule.persona_id)
+        .bind(rule.temperature)
+        .bind(&rule.reply)
+        .bind(rule.priority)
+        .execute(pool)
+        .await
+        .context("Failed to add chat rule")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &SqlitePool, id: i64) -> Result<Option<ChatRule>> {
+        let rule = sqlx::query_as::<_, ChatRule>("SELECT * FROM chat_rules WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
             .await
-            .context("Failed to update campaign status")?;
+            .context("Failed to fetch chat rule")?;
+
+        Ok(rule)
+    }
+
+    /// Every rule in evaluation order
+    pub async fn list(pool: &SqlitePool) -> Result<Vec<ChatRule>> {
+        let rules = sqlx::query_as::<_, ChatRule>("SELECT * FROM chat_rules ORDER BY priority DESC, id")
+            .fetch_all(pool)
+            .await
+            .context("Failed to list chat rules")?;
+
+        Ok(rules)
+    }
+
+    /// Rules that can apply to an account's messages, in evaluation order
+    pub async fn list_for_account(pool: &SqlitePool, account_id: i64) -> Result<Vec<ChatRule>> {
+        let rules = sqlx::query_as::<_, ChatRule>(
+            "SELECT * FROM chat_rules WHERE account_id IS NULL OR account_id = ? ORDER BY priority DESC, id",
+        )
+        .bind(account_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to list chat rules")?;
+
+        Ok(rules)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM chat_rules WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to remove chat rule")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+pub struct ChatBridgeRepository;
+
+impl ChatBridgeRepository {
+    pub async fn add(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_a: i64,
+        chat_b: i64,
+        lang_a: &str,
+        lang_b: &str,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO chat_bridges (account_id, chat_a, chat_b, lang_a, lang_b)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_a)
+        .bind(chat_b)
+        .bind(lang_a)
+        .bind(lang_b)
+        .execute(pool)
+        .await
+        .context("Failed to add chat bridge")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn list(pool: &SqlitePool, account_id: i64) -> Result<Vec<ChatBridge>> {
+        let bridges = sqlx::query_as::<_, ChatBridge>("SELECT * FROM chat_bridges WHERE account_id = ? ORDER BY id")
+            .bind(account_id)
+            .fetch_all(pool)
+            .await
+            .context("Failed to list chat bridges")?;
+
+        Ok(bridges)
+    }
+
+    /// Bridges with the chat on either side
+    pub async fn for_chat(pool: &SqlitePool, account_id: i64, chat_id: i64) -> Result<Vec<ChatBridge>> {
+        let bridges = sqlx::query_as::<_, ChatBridge>(
+            "SELECT * FROM chat_bridges WHERE account_id = ? AND (chat_a = ? OR chat_b = ?)",
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(chat_id)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch chat bridges")?;
+
+        Ok(bridges)
+    }
+
+    pub async fn remove(pool: &SqlitePool, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM chat_bridges WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to remove chat bridge")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Repository for the audit log of moderation actions
+pub struct AuditRepository;
+
+impl AuditRepository {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn record(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        action: &str,
+        message_id: Option<i64>,
+        actor: &str,
+        ok: bool,
+        details: Option<&str>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (account_id, chat_id, action, message_id, actor, ok, details)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(action)
+        .bind(message_id)
+        .bind(actor)
+        .bind(ok)
+        .bind(details)
+        .execute(pool)
+        .await
+        .context("Failed to write audit log")?;
 
-        tracing::info!("Updated campaign {} status to {}", campaign_id, status);
         Ok(())
     }
 
-    /// Delete a spam campaign
-    pub async fn delete(pool: &SqlitePool, campaign_id: i64) -> Result<()> {
-        sqlx::query("DELETE FROM spam_campaigns WHERE id = ?")
-            .bind(campaign_id)
+    /// Newest actions of an account, optionally in one chat
+    pub async fn recent(pool: &SqlitePool, account_id: i64, chat_id: Option<i64>, limit: i64) -> Result<Vec<AuditEntry>> {
+        let entries = sqlx::query_as::<_, AuditEntry>(
+            r#"
+            SELECT * FROM audit_log
+            WHERE account_id = ? AND (? IS NULL OR chat_id = ?)
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(chat_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch audit log")?;
+
+        Ok(entries)
+    }
+}
+
+/// Repository for users' consent to recalling their private chats in groups
+pub struct PrivacyRepository;
+
+impl PrivacyRepository {
+    /// Whether the user allowed the account to bring up their private chat in groups
+    pub async fn shares_private(pool: &SqlitePool, account_id: i64, user_id: i64) -> Result<bool> {
+        let share: Option<bool> = sqlx::query_scalar(
+            "SELECT share_private FROM privacy_consents WHERE account_id = ? AND user_id = ?",
+        )
+        .bind(account_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch privacy consent")?;
+
+        Ok(share.unwrap_or(false))
+    }
+
+    pub async fn set_share_private(pool: &SqlitePool, account_id: i64, user_id: i64, share: bool) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO privacy_consents (account_id, user_id, share_private)
+            VALUES (?, ?, ?)
+            ON CONFLICT(account_id, user_id) DO UPDATE SET
+                share_private = excluded.share_private,
+                updated_at = CURRENT_TIMESTAMP
+            "#,
+        )
+        .bind(account_id)
+        .bind(user_id)
+        .bind(share)
+        .execute(pool)
+        .await
+        .context("Failed to update privacy consent")?;
+
+        tracing::info!(
+            "{} {} private chat recall on account {}",
+            crate::logging::user_ref(user_id),
+            if share { "allowed" } else { "revoked" },
+            account_id
+        );
+        Ok(())
+    }
+}
+
+/// Repository for persona sticker packs
+pub struct StickerPackRepository;
+
+impl StickerPackRepository {
+    pub async fn get(pool: &SqlitePool, persona_id: i64) -> Result<Option<StickerPack>> {
+        let pack = sqlx::query_as::<_, StickerPack>("SELECT * FROM sticker_packs WHERE persona_id = ?")
+            .bind(persona_id)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch sticker pack")?;
+
+        Ok(pack)
+    }
+
+    pub async fn create(pool: &SqlitePool, persona_id: i64, account_id: i64, name: &str, title: &str, phrases: &[String]) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO sticker_packs (persona_id, account_id, name, title, phrases)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(persona_id)
+        .bind(account_id)
+        .bind(name)
+        .bind(title)
+        .bind(serde_json::to_string(phrases)?)
+        .execute(pool)
+        .await
+        .context("Failed to create sticker pack")?;
+
+        tracing::info!("Created sticker pack {} for persona {} on account {}", name, persona_id, account_id);
+        Ok(())
+    }
+
+    /// Record a refresh and the phrases the pack holds now
+    pub async fn set_phrases(pool: &SqlitePool, persona_id: i64, phrases: &[String]) -> Result<()> {
+        sqlx::query("UPDATE sticker_packs SET phrases = ?, refreshed_at = CURRENT_TIMESTAMP WHERE persona_id = ?")
+            .bind(serde_json::to_string(phrases)?)
+            .bind(persona_id)
             .execute(pool)
             .await
-            .context("Failed to delete spam campaign")?;
+            .context("Failed to update sticker pack")?;
+
+        Ok(())
+    }
+
+    /// Packs last refreshed before `before`
+    pub async fn list_due(pool: &SqlitePool, before: chrono::DateTime<chrono::Utc>) -> Result<Vec<StickerPack>> {
+        let packs = sqlx::query_as::<_, StickerPack>("SELECT * FROM sticker_packs WHERE refreshed_at < ?")
+            .bind(before)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch sticker packs due for a refresh")?;
+
+        Ok(packs)
+    }
+
+    /// Stop refreshing a pack; the set itself stays on Telegram
+    pub async fn delete(pool: &SqlitePool, persona_id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM sticker_packs WHERE persona_id = ?")
+            .bind(persona_id)
+            .execute(pool)
+            .await
+            .context("Failed to delete sticker pack")?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+pub struct ChatSummaryRepository;
+
+impl ChatSummaryRepository {
+    pub async fn record(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        kind: &str,
+        first_message_id: i64,
+        last_message_id: i64,
+        summary: &str,
+    ) -> Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO chat_summaries (account_id, chat_id, kind, first_message_id, last_message_id, summary)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(kind)
+        .bind(first_message_id)
+        .bind(last_message_id)
+        .bind(summary)
+        .execute(pool)
+        .await
+        .context("Failed to record chat summary")?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &SqlitePool, id: i64) -> Result<Option<ChatSummary>> {
+        let summary = sqlx::query_as::<_, ChatSummary>("SELECT * FROM chat_summaries WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .context("Failed to fetch chat summary")?;
+
+        Ok(summary)
+    }
+
+    /// A chat's summaries older than `before_id` (all when None), newest first
+    pub async fn list(
+        pool: &SqlitePool,
+        account_id: i64,
+        chat_id: i64,
+        before_id: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<ChatSummary>> {
+        let summaries = sqlx::query_as::<_, ChatSummary>(
+            r#"
+            SELECT * FROM chat_summaries
+            WHERE account_id = ? AND chat_id = ? AND (? IS NULL OR id < ?)
+            ORDER BY id DESC LIMIT ?
+            "#,
+        )
+        .bind(account_id)
+        .bind(chat_id)
+        .bind(before_id)
+        .bind(before_id)
+        .bind(limit)
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch chat summaries")?;
+
+        Ok(summaries)
+    }
+
+    pub async fn update_text(pool: &SqlitePool, id: i64, summary: &str) -> Result<()> {
+        sqlx::query("UPDATE chat_summaries SET summary = ? WHERE id = ?")
+            .bind(summary)
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to update chat summary")?;
 
-        tracing::info!("Deleted spam campaign {}", campaign_id);
         Ok(())
     }
+
+    pub async fn delete(pool: &SqlitePool, id: i64) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM chat_summaries WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .context("Failed to delete chat summary")?;
+
+        Ok(result.rows_affected() > 0)
+    }
 }
@@ -1,9 +1,17 @@
 pub mod ai;
+pub mod archive;
 pub mod bot;
+pub mod business;
 pub mod config;
 pub mod db;
+pub mod logging;
+pub mod payments;
+pub mod retention;
+pub mod security;
+pub mod settings_history;
 pub mod state;
 pub mod userbot;
+pub mod webhooks;
 
 pub use config::Config;
 pub use state::AppState;
@@ -0,0 +1,151 @@
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+const DEFAULT_FILTER: &str = "info,puppeteer=debug";
+const LOG_FILE_PREFIX: &str = "puppeteer.log";
+
+/// Handle used by /loglevel to swap the filter at runtime
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Whether user ids in log lines are replaced by pseudonyms (safe mode)
+static REDACT_USER_IDS: AtomicBool = AtomicBool::new(false);
+
+/// Output format for console and file logs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines
+    Text,
+    /// One JSON object per line (Loki/ELK)
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text" | "pretty" => Some(LogFormat::Text),
+            "json" => Some(LogFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Initialize the global subscriber.
+///
+/// Logs always go to stdout; when `log_dir` is set they are also written to
+/// a daily-rotated file there, and with `otlp_endpoint` spans are exported to
+/// an OpenTelemetry collector. The returned guard flushes the file writer
+/// and must be kept alive until shutdown.
+pub fn init(format: LogFormat, log_dir: Option<&str>, otlp_endpoint: Option<&str>) -> Result<Option<WorkerGuard>> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_FILTER.into());
+    let (filter, handle) = reload::Layer::new(filter);
+
+    let (file_writer, guard) = match log_dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create log directory {}", dir))?;
+            let appender = tracing_appender::rolling::daily(dir, LOG_FILE_PREFIX);
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    let json = format == LogFormat::Json;
+
+    #[cfg(feature = "otlp")]
+    let otlp = match otlp_endpoint {
+        Some(endpoint) => Some(tracing_opentelemetry::layer().with_tracer(otlp_tracer(endpoint)?)),
+        None => None,
+    };
+    #[cfg(not(feature = "otlp"))]
+    let otlp: Option<tracing_subscriber::layer::Identity> = {
+        if let Some(endpoint) = otlp_endpoint {
+            eprintln!("OTEL_EXPORTER_OTLP_ENDPOINT={} is ignored: this build has no `otlp` feature", endpoint);
+        }
+        None
+    };
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(otlp)
+        .with((!json).then(fmt::layer))
+        .with(json.then(|| fmt::layer().json()))
+        .with(
+            file_writer
+                .clone()
+                .filter(|_| !json)
+                .map(|w| fmt::layer().with_ansi(false).with_writer(w)),
+        )
+        .with(
+            file_writer
+                .filter(|_| json)
+                .map(|w| fmt::layer().json().with_writer(w)),
+        )
+        .try_init()
+        .context("Failed to initialize logging")?;
+
+    let _ = FILTER_HANDLE.set(handle);
+
+    Ok(guard)
+}
+
+#[cfg(feature = "otlp")]
+fn otlp_tracer(endpoint: &str) -> Result<opentelemetry_sdk::trace::Tracer> {
+    use opentelemetry_otlp::WithExportConfig;
+
+    let resource = opentelemetry_sdk::Resource::new(vec![opentelemetry::KeyValue::new("service.name", "puppeteer")]);
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(endpoint))
+        .with_trace_config(opentelemetry_sdk::trace::config().with_resource(resource))
+        .install_batch(opentelemetry_sdk::runtime::Tokio)
+        .with_context(|| format!("Failed to start OTLP export to {}", endpoint))
+}
+
+/// Flush spans still waiting for export
+pub fn shutdown() {
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Current filter directives, e.g. `info,puppeteer=debug`
+pub fn current_level() -> Option<String> {
+    FILTER_HANDLE
+        .get()?
+        .with_current(|filter| filter.to_string())
+        .ok()
+}
+
+/// Replace the filter with new directives (`debug`, `info,puppeteer=trace`, ...)
+pub fn set_level(directives: &str) -> Result<()> {
+    let filter = EnvFilter::try_new(directives)
+        .with_context(|| format!("Invalid log filter '{}'", directives))?;
+
+    FILTER_HANDLE
+        .get()
+        .context("Logging is not initialized")?
+        .reload(filter)
+        .context("Failed to reload log filter")?;
+
+    tracing::info!("Log filter changed to '{}'", directives);
+    Ok(())
+}
+
+pub fn set_redact_user_ids(redact: bool) {
+    REDACT_USER_IDS.store(redact, Ordering::Relaxed);
+}
+
+/// A user id for a log line: as is, or a stable pseudonym when ids are redacted
+pub fn user_ref(user_id: i64) -> String {
+    if !REDACT_USER_IDS.load(Ordering::Relaxed) {
+        return user_id.to_string();
+    }
+    let digest = Sha256::digest(user_id.to_le_bytes());
+    format!("user#{}", digest.iter().take(4).map(|b| format!("{:02x}", b)).collect::<String>())
+}
@@ -1,22 +1,34 @@
 use anyhow::Result;
-use puppeteer::{bot, db::AccountRepository, userbot, AppState, Config};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use puppeteer::{bot, db::AccountRepository, logging, userbot, AppState, Config};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "info,puppeteer=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Load configuration
+    let mut config = Config::from_env()?;
 
-    tracing::info!("Starting Puppeteer...");
+    // `simulate [options]` load-tests the reply pipeline against a scratch database,
+    // `bench [options]` measures RAG retrieval, queue waits and the pipeline into a report
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (simulate, bench) = match args.first().map(String::as_str) {
+        Some("simulate") => {
+            let options = userbot::simulate::SimulateOptions::parse(&args[1..])?;
+            config.database_url = options.database_url.clone();
+            (Some(options), None)
+        }
+        Some("bench") => {
+            let options = userbot::bench::BenchOptions::parse(&args[1..])?;
+            config.database_url = options.database_url.clone();
+            (None, Some(options))
+        }
+        Some(other) => anyhow::bail!("Unknown subcommand '{}' (available: simulate, bench)", other),
+        None => (None, None),
+    };
 
-    // Load configuration
-    let config = Config::from_env()?;
+    // Initialize logging (the guard flushes the log file on exit)
+    let _log_guard = logging::init(config.log_format, config.log_dir.as_deref(), config.otlp_endpoint.as_deref())?;
+    logging::set_redact_user_ids(config.safe_mode);
+
+    tracing::info!("Starting Puppeteer...");
     tracing::info!("Configuration loaded. Owners: {:?}", config.owner_ids);
 
     // Initialize database
@@ -26,9 +38,37 @@ async fn main() -> Result<()> {
     // Create application state
     let state = AppState::new(config, db_pool);
 
+    // Fail fast on missing models or an embedding model that doesn't match stored memories
+    puppeteer::ai::validate_models(&state).await?;
+
+    if let Some(options) = simulate {
+        return userbot::simulate::run(state, options).await;
+    }
+    if let Some(options) = bench {
+        return userbot::bench::run(state, options).await;
+    }
+
+    // Personas kept in git and shipped with the container
+    if let Some(dir) = state.config.personas_dir.clone() {
+        match puppeteer::ai::persona_files::import_dir(&state.db_pool, std::path::Path::new(&dir)).await {
+            Ok(report) => {
+                tracing::info!("Persona files in {}: {}", dir, report.summary());
+                for (file, error) in &report.failed {
+                    tracing::warn!("Persona file {} not imported: {}", file, error);
+                }
+            }
+            Err(e) => tracing::error!("Failed to import personas from {}: {}", dir, e),
+        }
+    }
+
     // Load and spawn existing active accounts from database
     tracing::info!("Loading active accounts from database...");
-    let active_accounts = AccountRepository::list_active(&state.db_pool).await?;
+    let active_accounts = if state.config.safe_mode {
+        tracing::warn!("Safe mode: userbots, ghost mode, spam campaigns and web search are disabled");
+        Vec::new()
+    } else {
+        AccountRepository::list_active(&state.db_pool).await?
+    };
     
     for account in active_accounts {
         tracing::info!("Spawning userbot for account {} ({})", account.id, account.phone_number);
@@ -38,11 +78,125 @@ async fn main() -> Result<()> {
     }
 
     // Start spam campaign worker
-    let state_spam = state.clone();
+    if !state.config.safe_mode {
+        let state_spam = state.clone();
+        tokio::spawn(async move {
+            userbot::spam_campaign_worker(state_spam).await;
+        });
+        tracing::info!("Spam campaign worker started");
+    }
+
+    // Start conversation starter worker
+    let state_initiative = state.clone();
     tokio::spawn(async move {
-        userbot::spam_campaign_worker(state_spam).await;
+        userbot::initiative_worker(state_initiative).await;
     });
-    tracing::info!("Spam campaign worker started");
+
+    // Start persona tag rotation worker
+    let state_rotation = state.clone();
+    tokio::spawn(async move {
+        userbot::persona_rotation_worker(state_rotation).await;
+    });
+
+    // Start weekly digest worker
+    let state_digest = state.clone();
+    tokio::spawn(async move {
+        userbot::digest_worker(state_digest).await;
+    });
+
+    // Start LLM error budget worker (global auto-pause)
+    let state_health = state.clone();
+    tokio::spawn(async move {
+        puppeteer::ai::health::health_worker(state_health).await;
+    });
+
+    // Tell the owner when a persona's reply style drifts
+    let state_style = state.clone();
+    tokio::spawn(async move {
+        puppeteer::ai::style::style_drift_worker(state_style).await;
+    });
+
+    // Degrade generation while the host is under load
+    let state_throttle = state.clone();
+    tokio::spawn(async move {
+        puppeteer::ai::throttle::throttle_worker(state_throttle).await;
+    });
+
+    // Add new catchphrases to persona sticker packs monthly
+    let state_stickers = state.clone();
+    tokio::spawn(async move {
+        userbot::sticker_worker(state_stickers).await;
+    });
+
+    // Start ephemeral reply deletion worker
+    let state_ephemeral = state.clone();
+    tokio::spawn(async move {
+        userbot::ephemeral_worker(state_ephemeral).await;
+    });
+
+    // Reveal game answers nobody got in time
+    let state_games = state.clone();
+    tokio::spawn(async move {
+        userbot::game_worker(state_games).await;
+    });
+
+    // Start RSS/Atom feed worker
+    let state_feeds = state.clone();
+    tokio::spawn(async move {
+        userbot::feed_worker(state_feeds).await;
+    });
+
+    // Start outbound webhook delivery worker
+    let state_webhooks = state.clone();
+    tokio::spawn(async move {
+        puppeteer::webhooks::webhook_worker(state_webhooks).await;
+    });
+
+    // Start memory consolidation worker
+    let state_consolidation = state.clone();
+    tokio::spawn(async move {
+        puppeteer::ai::consolidation_worker(state_consolidation).await;
+    });
+
+    // Resume memory embedding backfills started with /embed_backlog
+    let state_embed_backlog = state.clone();
+    tokio::spawn(async move {
+        puppeteer::ai::embed_backlog::embed_backlog_worker(state_embed_backlog).await;
+    });
+
+    // Start relationship decay worker
+    let state_relationships = state.clone();
+    tokio::spawn(async move {
+        puppeteer::ai::relationship_decay_worker(state_relationships).await;
+    });
+
+    // Start reply probability tuning worker
+    let state_tuning = state.clone();
+    tokio::spawn(async move {
+        userbot::tuning::tuning_worker(state_tuning).await;
+    });
+
+    // Start retention worker
+    let state_retention = state.clone();
+    tokio::spawn(async move {
+        puppeteer::retention::retention_worker(state_retention).await;
+    });
+
+    // Start the nightly archiving of old messages (ARCHIVE_AFTER_DAYS)
+    if state.config.archive_after_days.is_some() {
+        let state_archive = state.clone();
+        tokio::spawn(async move {
+            puppeteer::archive::archive_worker(state_archive).await;
+        });
+    }
+
+    // Start the Telegram Business auto-responder (BUSINESS_BOT_TOKEN)
+    if state.config.business_bot_token.is_some() {
+        let state_business = state.clone();
+        tokio::spawn(async move {
+            puppeteer::business::business_worker(state_business).await;
+        });
+    }
 
     tracing::info!("Puppeteer is ready! Starting admin bot...");
 
@@ -54,5 +208,6 @@ async fn main() -> Result<()> {
     state.shutdown_all_userbots().await?;
 
     tracing::info!("Puppeteer stopped");
+    logging::shutdown();
     Ok(())
 }
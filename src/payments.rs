@@ -0,0 +1,99 @@
+use crate::{db::PaymentRepository, AppState};
+
+/// Reply length (chars) for users without the long-replies feature when it is gated
+pub const SHORT_REPLY_CHARS: usize = 500;
+
+/// Features that can be put behind a Stars payment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    /// Image, GIF and video-note analysis
+    Vision,
+    /// Voice note transcription
+    Voice,
+    /// Replies longer than SHORT_REPLY_CHARS
+    LongReplies,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 3] = [Feature::Vision, Feature::Voice, Feature::LongReplies];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Feature::Vision => "vision",
+            Feature::Voice => "voice",
+            Feature::LongReplies => "long_replies",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "vision" => Some(Feature::Vision),
+            "voice" => Some(Feature::Voice),
+            "long_replies" | "long" => Some(Feature::LongReplies),
+            _ => None,
+        }
+    }
+
+    /// Human-readable name used in invoices
+    pub fn title(&self) -> &'static str {
+        match self {
+            Feature::Vision => "Image understanding",
+            Feature::Voice => "Voice message transcription",
+            Feature::LongReplies => "Long-form answers",
+        }
+    }
+}
+
+/// Invoice payload: "premium:<feature>:<days>" (0 days = lifetime)
+pub fn invoice_payload(feature: Feature, days: Option<i64>) -> String {
+    format!("premium:{}:{}", feature.as_str(), days.unwrap_or(0))
+}
+
+/// Parse an invoice payload back into the feature and duration
+pub fn parse_payload(payload: &str) -> Option<(Feature, Option<i64>)> {
+    let mut parts = payload.split(':');
+    if parts.next()? != "premium" {
+        return None;
+    }
+    let feature = Feature::parse(parts.next()?)?;
+    let days: i64 = parts.next()?.parse().ok()?;
+    Some((feature, (days > 0).then_some(days)))
+}
+
+/// Whether a feature is gated at all (PREMIUM_FEATURES)
+pub fn is_gated(state: &AppState, feature: Feature) -> bool {
+    state.config.premium_features.contains(&feature)
+}
+
+/// Whether a sender may use a feature: ungated features are free for everyone.
+/// Lookup errors fail open so a database hiccup doesn't silently degrade replies.
+pub async fn is_allowed(state: &AppState, feature: Feature, user_id: i64) -> bool {
+    if !is_gated(state, feature) {
+        return true;
+    }
+    if user_id == 0 {
+        return false;
+    }
+
+    match PaymentRepository::has_entitlement(&state.db_pool, user_id, feature.as_str()).await {
+        Ok(allowed) => allowed,
+        Err(e) => {
+            tracing::warn!("Entitlement check failed for user {}: {}", crate::logging::user_ref(user_id), e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_roundtrip() {
+        let payload = invoice_payload(Feature::Voice, Some(30));
+        assert_eq!(payload, "premium:voice:30");
+        assert_eq!(parse_payload(&payload), Some((Feature::Voice, Some(30))));
+        assert_eq!(parse_payload("premium:long_replies:0"), Some((Feature::LongReplies, None)));
+        assert_eq!(parse_payload("other:voice:1"), None);
+    }
+}
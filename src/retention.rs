@@ -0,0 +1,103 @@
+use crate::{
+    ai::rag,
+    db::{ChatRepository, MessageRepository, TraceRepository},
+    AppState,
+};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+/// How often retention limits are enforced
+const RETENTION_INTERVAL_SECS: u64 = 6 * 60 * 60;
+/// Reply timings kept for /trace
+const TRACE_RETENTION_DAYS: i64 = 7;
+/// Messages hashed per batch, so a large backlog doesn't hold the database for long
+const HASH_BATCH_SIZE: i64 = 500;
+
+/// Stored form of a message body after hashing
+pub fn hashed_body(content: &str) -> String {
+    let digest = Sha256::digest(content.as_bytes());
+    let hex: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", hex)
+}
+
+/// Periodically enforce retention: per-chat limits, the global RETENTION_DAYS,
+/// and hashing of old message bodies (RETENTION_HASH_AFTER_DAYS)
+pub async fn retention_worker(state: AppState) {
+    tracing::info!("Retention worker started");
+
+    loop {
+        if let Err(e) = enforce_retention(&state).await {
+            tracing::error!("Retention run failed: {}", e);
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(RETENTION_INTERVAL_SECS)).await;
+    }
+}
+
+/// One retention pass; returns (deleted, hashed) message counts
+pub async fn enforce_retention(state: &AppState) -> Result<(u64, u64)> {
+    let mut deleted = 0;
+    let mut memories = 0;
+
+    // A chat's memories go back no further than its messages do
+    for chat in ChatRepository::list_with_retention(&state.db_pool).await? {
+        let (account_id, chat_id) = (chat.account_id, chat.chat_id);
+        if let Some(days) = chat.retention_days {
+            deleted += MessageRepository::purge_chat_older_than(&state.db_pool, account_id, chat_id, days).await?;
+            let before = chrono::Utc::now().timestamp() - days * 86400;
+            memories += rag::purge_chat_memories_before(&state.db_pool, account_id, chat_id, before).await?;
+        }
+        if let Some(keep) = chat.retention_max_messages {
+            deleted += MessageRepository::purge_chat_excess(&state.db_pool, account_id, chat_id, keep).await?;
+            // No messages left (purged, or memories imported on their own): nothing to measure against
+            if let Some(before) = MessageRepository::oldest_message_at(&state.db_pool, account_id, chat_id).await? {
+                memories += rag::purge_chat_memories_before(&state.db_pool, account_id, chat_id, before).await?;
+            }
+        }
+    }
+
+    if let Some(days) = state.config.retention_days {
+        deleted += MessageRepository::delete_older_than(&state.db_pool, days).await?;
+        memories += rag::purge_memories_older_than(&state.db_pool, days).await?;
+    }
+
+    TraceRepository::delete_older_than(&state.db_pool, TRACE_RETENTION_DAYS).await?;
+
+    let mut hashed = 0;
+    let mut hashed_memories = 0;
+    if let Some(days) = state.config.retention_hash_after_days {
+        loop {
+            let batch = MessageRepository::list_unhashed_older_than(&state.db_pool, days, HASH_BATCH_SIZE).await?;
+            if batch.is_empty() {
+                break;
+            }
+            for (id, content) in &batch {
+                MessageRepository::mark_hashed(&state.db_pool, *id, &hashed_body(content)).await?;
+            }
+            hashed += batch.len() as u64;
+        }
+
+        hashed_memories = rag::hash_memories_older_than(&state.db_pool, days).await?;
+    }
+
+    if memories > 0 || hashed_memories > 0 {
+        tracing::info!("Retention: deleted {} memories, hashed {}", memories, hashed_memories);
+    }
+    if deleted > 0 || hashed > 0 {
+        tracing::info!("Retention: deleted {} messages, hashed {}", deleted, hashed);
+    }
+    Ok((deleted, hashed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_bodies() {
+        let hashed = hashed_body("привет");
+        assert!(hashed.starts_with("sha256:"));
+        assert_eq!(hashed.len(), "sha256:".len() + 64);
+        assert_eq!(hashed, hashed_body("привет"));
+    }
+}
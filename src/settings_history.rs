@@ -0,0 +1,256 @@
+use crate::{
+    db::{AccountChat, ChatRepository, ChatSettingChange, SettingChangeRepository},
+    AppState,
+};
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+use std::future::Future;
+
+/// Columns maintained by workers or Telegram rather than by admin commands
+const UNTRACKED_FIELDS: &[&str] = &[
+    "account_id",
+    "chat_id",
+    "title",
+    "chat_type",
+    "updated_at",
+    "initiative_day",
+    "initiative_count",
+    "last_initiative_at",
+    "persona_since",
+    "last_digest_at",
+    "karma_period",
+    "onboarded_at",
+];
+
+/// (old values, new values) of changed settings.
+/// Old values are `None` when the row didn't exist before.
+pub type SettingsDiff = (Option<Map<String, Value>>, Map<String, Value>);
+
+/// Changed settings between two versions of a chat's fields
+pub fn diff(
+    before: Option<&Map<String, Value>>,
+    after: &Map<String, Value>,
+) -> Option<SettingsDiff> {
+    let mut old_values = Map::new();
+    let mut new_values = Map::new();
+    for (field, value) in after {
+        if UNTRACKED_FIELDS.contains(&field.as_str()) {
+            continue;
+        }
+        let previous = before.map_or(&Value::Null, |b| b.get(field).unwrap_or(&Value::Null));
+        if previous != value {
+            old_values.insert(field.clone(), previous.clone());
+            new_values.insert(field.clone(), value.clone());
+        }
+    }
+
+    if new_values.is_empty() {
+        return None;
+    }
+    Some((before.map(|_| old_values), new_values))
+}
+
+fn to_fields(chat: &AccountChat) -> Result<Map<String, Value>> {
+    let mut fields = match serde_json::to_value(chat).context("Failed to serialize chat settings")? {
+        Value::Object(fields) => fields,
+        _ => anyhow::bail!("Chat settings are not an object"),
+    };
+    fields.retain(|field, _| !UNTRACKED_FIELDS.contains(&field.as_str()));
+    Ok(fields)
+}
+
+/// The (account, chat) a command is about, when its first two arguments are ids
+pub fn chat_scope(args: &[String]) -> Option<(i64, i64)> {
+    match (args.first()?.parse::<i64>(), args.get(1)?.parse::<i64>()) {
+        (Ok(account_id), Ok(chat_id)) => Some((account_id, chat_id)),
+        _ => None,
+    }
+}
+
+/// Record what a command changed in a chat's settings, given the row from before it ran
+pub async fn record_change(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    actor_id: i64,
+    command: &str,
+    before: Option<&AccountChat>,
+) -> Result<()> {
+    let after = match ChatRepository::get(&state.db_pool, account_id, chat_id).await? {
+        Some(after) => after,
+        None => return Ok(()),
+    };
+    let before = before.map(to_fields).transpose()?;
+    let (old_values, new_values) = match diff(before.as_ref(), &to_fields(&after)?) {
+        Some(changes) => changes,
+        None => return Ok(()),
+    };
+
+    SettingChangeRepository::record(
+        &state.db_pool,
+        account_id,
+        chat_id,
+        actor_id,
+        command,
+        old_values.map(|v| Value::Object(v).to_string()).as_deref(),
+        &Value::Object(new_values).to_string(),
+    )
+    .await?;
+    tracing::info!(
+        "Settings of chat {} (account {}) changed by {} via {}",
+        chat_id,
+        account_id,
+        crate::logging::user_ref(actor_id),
+        command
+    );
+    Ok(())
+}
+
+/// Seconds left before the chat's settings may change again, under CHAT_SETTINGS_COOLDOWN_SECS
+pub async fn cooldown_left(state: &AppState, account_id: i64, chat_id: i64) -> Result<Option<i64>> {
+    let cooldown = state.config.chat_settings_cooldown_secs;
+    if cooldown <= 0 {
+        return Ok(None);
+    }
+    let since = SettingChangeRepository::seconds_since_last(&state.db_pool, account_id, chat_id).await?;
+    Ok(since.filter(|s| *s < cooldown).map(|s| cooldown - s))
+}
+
+/// Outcome of a settings change run through [`guarded`]
+pub enum Guarded<T> {
+    Applied(T),
+    /// Refused under the cooldown, with the seconds left
+    CoolingDown(i64),
+}
+
+/// Run a change of a chat's settings under the cooldown and record what it changed.
+/// Every path that changes chat settings goes through here so /undo_last_setting sees it.
+pub async fn guarded<T>(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    actor_id: i64,
+    label: &str,
+    change: impl Future<Output = T>,
+) -> Result<Guarded<T>> {
+    if let Some(left) = cooldown_left(state, account_id, chat_id).await? {
+        return Ok(Guarded::CoolingDown(left));
+    }
+    let before = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+
+    let output = change.await;
+
+    if let Err(e) = record_change(state, account_id, chat_id, actor_id, label, before.as_ref()).await {
+        tracing::warn!("Failed to record settings change of chat {}: {}", chat_id, e);
+    }
+    Ok(Guarded::Applied(output))
+}
+
+/// Reply for a change refused under the cooldown
+pub fn cooldown_message(account_id: i64, chat_id: i64, left: i64) -> String {
+    format!(
+        "⏳ Settings of chat {} changed moments ago. Try again in {}s, or revert with /undo_last_setting {} {}",
+        chat_id, left, account_id, chat_id
+    )
+}
+
+/// Revert the newest change of a chat that is still in effect
+pub async fn undo_last(state: &AppState, account_id: i64, chat_id: i64) -> Result<Option<ChatSettingChange>> {
+    let change = match SettingChangeRepository::last_active(&state.db_pool, account_id, chat_id).await? {
+        Some(change) => change,
+        None => return Ok(None),
+    };
+
+    let old_values: Option<Map<String, Value>> = change
+        .old_values
+        .as_deref()
+        .map(serde_json::from_str)
+        .transpose()
+        .context("Stored setting change is not valid JSON")?;
+
+    // Only restore columns the chat row actually has
+    if let (Some(fields), Some(current)) =
+        (old_values.as_ref(), ChatRepository::get(&state.db_pool, account_id, chat_id).await?)
+    {
+        let known = to_fields(&current)?;
+        if let Some(unknown) = fields.keys().find(|field| !known.contains_key(*field)) {
+            anyhow::bail!("Change {} touches unknown setting {}", change.id, unknown);
+        }
+    }
+
+    SettingChangeRepository::revert(&state.db_pool, &change, old_values.as_ref()).await?;
+    Ok(Some(change))
+}
+
+/// Put a chat's settings back to how they were just before a change, undoing it and every
+/// later change still in effect, newest first. Returns the reverted changes.
+pub async fn rollback_to(state: &AppState, account_id: i64, chat_id: i64, change_id: i64) -> Result<Vec<ChatSettingChange>> {
+    let pending = SettingChangeRepository::active_since(&state.db_pool, account_id, chat_id, change_id).await?;
+    if pending.last().map(|c| c.id) != Some(change_id) {
+        anyhow::bail!("Change {} is not an active change of chat {}", change_id, chat_id);
+    }
+
+    let mut reverted = Vec::new();
+    for _ in 0..pending.len() {
+        match undo_last(state, account_id, chat_id).await? {
+            Some(change) => reverted.push(change),
+            None => break,
+        }
+    }
+    Ok(reverted)
+}
+
+/// `field: old → new` lines of a change
+pub fn describe(change: &ChatSettingChange) -> Vec<String> {
+    let new_values: Map<String, Value> = serde_json::from_str(&change.new_values).unwrap_or_default();
+    let old_values: Option<Map<String, Value>> = change.old_values.as_deref().and_then(|v| serde_json::from_str(v).ok());
+
+    new_values
+        .iter()
+        .map(|(field, new)| {
+            let old = old_values.as_ref().and_then(|o| o.get(field)).map_or("default".to_string(), |v| v.to_string());
+            format!("{}: {} → {}", field, old, new)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(fields) => fields,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn diffs_only_changed_settings() {
+        let before = fields(serde_json::json!({ "title": "Chat", "reply_probability": null, "karma_enabled": false }));
+        let after = fields(serde_json::json!({ "title": "Renamed", "reply_probability": 40, "karma_enabled": false }));
+
+        let (old_values, new_values) = diff(Some(&before), &after).unwrap();
+        assert_eq!(new_values.len(), 1);
+        assert_eq!(new_values["reply_probability"], 40);
+        assert_eq!(old_values.unwrap()["reply_probability"], Value::Null);
+        assert!(diff(Some(&before), &before).is_none());
+    }
+
+    #[test]
+    fn created_rows_have_no_old_values() {
+        let after = fields(serde_json::json!({ "karma_enabled": true, "timezone": null }));
+        let (old_values, new_values) = diff(None, &after).unwrap();
+        assert!(old_values.is_none());
+        assert_eq!(new_values.len(), 1);
+        assert_eq!(new_values["karma_enabled"], true);
+    }
+
+    #[test]
+    fn scopes_commands_by_leading_ids() {
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(chat_scope(&args(&["1", "-100", "40"])), Some((1, -100)));
+        assert_eq!(chat_scope(&args(&["1"])), None);
+        assert_eq!(chat_scope(&args(&["all", "-100"])), None);
+    }
+}
@@ -30,18 +30,40 @@ pub struct AppState {
     /// Registry of active MTProto clients (userbots)
     /// Key: account_id, Value: UserbotHandle
     pub userbots: Arc<RwLock<HashMap<i64, UserbotHandle>>>,
+
+    /// Free Whisper slots (WHISPER_CONCURRENCY); voice notes queue here
+    pub transcription_slots: Arc<tokio::sync::Semaphore>,
+
+    /// Recent chat model calls and the auto-pause they may trigger
+    pub llm_health: Arc<crate::ai::health::LlmHealth>,
+
+    /// How far generation is degraded because of host load
+    pub throttle: Arc<crate::ai::throttle::Throttle>,
 }
 
 impl AppState {
     /// Create a new application state
     pub fn new(config: Config, db_pool: SqlitePool) -> Self {
         Self {
+            transcription_slots: Arc::new(tokio::sync::Semaphore::new(config.whisper_concurrency)),
             config: Arc::new(config),
             db_pool,
             userbots: Arc::new(RwLock::new(HashMap::new())),
+            llm_health: Arc::new(crate::ai::health::LlmHealth::default()),
+            throttle: Arc::new(crate::ai::throttle::Throttle::default()),
         }
     }
 
+    /// Whether auto-replies are paused globally
+    pub fn is_paused(&self) -> bool {
+        self.llm_health.paused().is_some()
+    }
+
+    /// Pause (`Some(reason)`) or resume (`None`) auto-replies globally
+    pub fn set_paused(&self, reason: Option<String>) {
+        self.llm_health.set_paused(reason);
+    }
+
     /// Add a userbot to the active pool
     pub async fn add_userbot(&self, handle: UserbotHandle) {
         let account_id = handle.account_id;
@@ -0,0 +1,405 @@
+use super::simulate::{self, SimulateOptions};
+use crate::{
+    ai::{memory_editor, rag, whisper},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+
+/// Database used unless --database is given; memories are seeded into it
+pub const DEFAULT_DATABASE_URL: &str = "sqlite:data/bench.db";
+
+/// Chats the synthetic memories are seeded into, one per chunk count, counting down from here
+const FIRST_RAG_CHAT_ID: i64 = -1_000_000_900_001;
+
+/// Embedding size when nothing is stored yet (nomic-embed-text)
+const DEFAULT_DIMENSION: usize = 768;
+
+/// Memories asked for per retrieval, as replies do
+const RAG_TOP_N: usize = 5;
+
+const USAGE: &str = "Usage: bench [--chunks 100,1000,10000] [--queries N] [--jobs N] [--service-ms MS] [--load 0.9] \
+[--chats N] [--rate MSG_PER_SEC] [--duration SECS] [--report FILE] [--database URL]";
+
+/// Options of `puppeteer bench`
+#[derive(Debug, Clone)]
+pub struct BenchOptions {
+    /// Memories per chat RAG retrieval is timed at
+    pub chunks: Vec<usize>,
+    /// Retrievals per chunk count
+    pub queries: usize,
+    /// Synthetic jobs sent through the transcription queue
+    pub jobs: usize,
+    /// Average time a queued job holds its slot
+    pub service_ms: u64,
+    /// Offered queue load: 1.0 keeps every slot exactly busy, above that the queue grows
+    pub load: f64,
+    /// Chats the queue jobs and pipeline messages come from
+    pub chats: u32,
+    /// Incoming messages per second for the pipeline run
+    pub rate: f64,
+    /// Length of the pipeline run; 0 skips it
+    pub duration_secs: u64,
+    /// Where the JSON report is written
+    pub report: Option<String>,
+    pub database_url: String,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            chunks: vec![100, 1000, 10000],
+            queries: 50,
+            jobs: 200,
+            service_ms: 200,
+            load: 0.9,
+            chats: 10,
+            rate: 1.0,
+            duration_secs: 30,
+            report: None,
+            database_url: DEFAULT_DATABASE_URL.to_string(),
+        }
+    }
+}
+
+impl BenchOptions {
+    /// Parse `--chunks 100,5000 --queries 20 --duration 0 --report bench.json`
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .with_context(|| format!("{} needs a value", name))
+                    .map(|v| v.to_string())
+            };
+            match arg.as_str() {
+                "--chunks" => {
+                    options.chunks = value("--chunks")?
+                        .split(',')
+                        .map(|n| n.trim().parse::<usize>())
+                        .collect::<Result<_, _>>()
+                        .context("--chunks must be comma-separated numbers")?
+                }
+                "--queries" => options.queries = value("--queries")?.parse().context("--queries must be a number")?,
+                "--jobs" => options.jobs = value("--jobs")?.parse().context("--jobs must be a number")?,
+                "--service-ms" => {
+                    options.service_ms = value("--service-ms")?.parse().context("--service-ms must be a number")?
+                }
+                "--load" => options.load = value("--load")?.parse().context("--load must be a number")?,
+                "--chats" => options.chats = value("--chats")?.parse().context("--chats must be a number")?,
+                "--rate" => options.rate = value("--rate")?.parse().context("--rate must be a number")?,
+                "--duration" => {
+                    options.duration_secs = value("--duration")?.parse().context("--duration must be a number of seconds")?
+                }
+                "--report" => options.report = Some(value("--report")?),
+                "--database" => options.database_url = value("--database")?,
+                other => anyhow::bail!("Unknown option '{}'. {}", other, USAGE),
+            }
+        }
+
+        if options.chunks.is_empty() || options.chunks.contains(&0) || options.queries == 0 {
+            anyhow::bail!("--chunks and --queries must be positive");
+        }
+        let positive = |v: f64| v.is_finite() && v > 0.0;
+        if options.chats == 0 || !positive(options.rate) || !positive(options.load) || options.service_ms == 0 {
+            anyhow::bail!("--chats, --rate, --load and --service-ms must be positive");
+        }
+        Ok(options)
+    }
+}
+
+/// Distribution of a set of timings, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct Stats {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p95_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl Stats {
+    pub fn of(timings: &[Duration]) -> Self {
+        if timings.is_empty() {
+            return Stats::default();
+        }
+        let mut ms: Vec<f64> = timings.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        ms.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let percentile = |p: f64| ms[((ms.len() as f64 * p) as usize).min(ms.len() - 1)];
+
+        Stats {
+            count: ms.len(),
+            mean_ms: ms.iter().sum::<f64>() / ms.len() as f64,
+            p50_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: ms[ms.len() - 1],
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!(
+            "n={} mean {:.1}ms, p50 {:.1}ms, p95 {:.1}ms, p99 {:.1}ms, max {:.1}ms",
+            self.count, self.mean_ms, self.p50_ms, self.p95_ms, self.p99_ms, self.max_ms
+        )
+    }
+}
+
+/// Jain's fairness index of per-chat averages: 1.0 when every chat fares the same, 1/n when one takes it all
+pub fn fairness(values: &[f64]) -> f64 {
+    let sum: f64 = values.iter().sum();
+    let squares: f64 = values.iter().map(|v| v * v).sum();
+    if values.is_empty() || squares == 0.0 {
+        return 1.0;
+    }
+    sum * sum / (values.len() as f64 * squares)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RagResult {
+    pub chunks: usize,
+    pub seed_ms: f64,
+    pub retrieval: Stats,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueResult {
+    pub slots: usize,
+    pub jobs: usize,
+    pub load: f64,
+    pub wait: Stats,
+    /// Fairness of the average wait across chats
+    pub fairness: f64,
+    /// Average wait of the worst-off chat
+    pub worst_chat_wait_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineResult {
+    pub chats: u32,
+    pub rate: f64,
+    pub messages: usize,
+    pub failures: u64,
+    pub latency: Stats,
+}
+
+/// Everything one `bench` run measured, written as JSON with --report
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchReport {
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub chat_model: String,
+    pub embed_dimension: usize,
+    pub rag: Vec<RagResult>,
+    pub queue: QueueResult,
+    pub pipeline: Option<PipelineResult>,
+}
+
+/// Recorded as the model of synthetic memories, so the embedding cache never hands their vectors out
+const SYNTHETIC_EMBED_MODEL: &str = "bench-random";
+
+fn random_embedding(dimension: usize) -> Vec<f32> {
+    (0..dimension).map(|_| rand::random::<f32>() * 2.0 - 1.0).collect()
+}
+
+/// Time retrieval from chats seeded with synthetic memories of random embeddings
+async fn bench_rag(state: &AppState, account_id: i64, options: &BenchOptions, dimension: usize) -> Result<Vec<RagResult>> {
+    let mut results = Vec::new();
+    for (i, &chunks) in options.chunks.iter().enumerate() {
+        let chat_id = FIRST_RAG_CHAT_ID - i as i64;
+        memory_editor::delete_matching(&state.db_pool, account_id, chat_id, &memory_editor::MemoryFilter::default()).await?;
+
+        let seeding = Instant::now();
+        for n in 0..chunks {
+            rag::store_memory(
+                &state.db_pool,
+                account_id,
+                chat_id,
+                None,
+                Some(1_000_000 + (n % 50) as i64),
+                false,
+                None,
+                &format!("synthetic memory #{} about {}", n, simulate::phrase(n)),
+                &random_embedding(dimension),
+                SYNTHETIC_EMBED_MODEL,
+                1.0,
+            )
+            .await?;
+        }
+        let seed_ms = seeding.elapsed().as_secs_f64() * 1000.0;
+
+        let filter = rag::RetrievalFilter::default();
+        let mut timings = Vec::with_capacity(options.queries);
+        for _ in 0..options.queries {
+            let query = random_embedding(dimension);
+            let started = Instant::now();
+            rag::retrieve_memories(&state.db_pool, account_id, chat_id, &query, RAG_TOP_N, &filter).await?;
+            timings.push(started.elapsed());
+        }
+
+        let result = RagResult { chunks, seed_ms, retrieval: Stats::of(&timings) };
+        println!("  {:>6} chunks: {}", chunks, result.retrieval.describe());
+        results.push(result);
+        memory_editor::delete_matching(&state.db_pool, account_id, chat_id, &memory_editor::MemoryFilter::default()).await?;
+    }
+    Ok(results)
+}
+
+/// Push jobs from several chats through the transcription queue and measure how long each waited for a slot
+async fn bench_queue(state: &AppState, options: &BenchOptions) -> Result<QueueResult> {
+    let slots_count = state.config.whisper_concurrency.max(1);
+    let slots = Arc::new(Semaphore::new(slots_count));
+    let service = Duration::from_millis(options.service_ms);
+    // Arrivals spaced so the queue is offered `load` times what the slots can serve
+    let interval = service.div_f64(slots_count as f64 * options.load);
+    let waits: Arc<Mutex<Vec<(u32, Duration)>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let mut tasks = Vec::with_capacity(options.jobs);
+    for _ in 0..options.jobs {
+        let chat = rand::random::<u32>() % options.chats;
+        let (slots, waits) = (slots.clone(), waits.clone());
+        // Service times vary between half and one and a half of the average
+        let work_time = service.mul_f64(0.5 + rand::random::<f64>());
+        tasks.push(tokio::spawn(async move {
+            let queued_at = Instant::now();
+            let _ = whisper::run_queued(&slots, Duration::from_secs(3600), None, async {}, async {
+                waits.lock().await.push((chat, queued_at.elapsed()));
+                tokio::time::sleep(work_time).await;
+                Ok(())
+            })
+            .await;
+        }));
+        tokio::time::sleep(interval).await;
+    }
+    for task in tasks {
+        let _ = task.await;
+    }
+
+    let waits = waits.lock().await;
+    let mut per_chat: HashMap<u32, Vec<Duration>> = HashMap::new();
+    for (chat, wait) in waits.iter() {
+        per_chat.entry(*chat).or_default().push(*wait);
+    }
+    let chat_means: Vec<f64> = per_chat.values().map(|w| Stats::of(w).mean_ms).collect();
+    let all: Vec<Duration> = waits.iter().map(|(_, wait)| *wait).collect();
+
+    Ok(QueueResult {
+        slots: slots_count,
+        jobs: options.jobs,
+        load: options.load,
+        wait: Stats::of(&all),
+        fairness: fairness(&chat_means),
+        worst_chat_wait_ms: chat_means.iter().cloned().fold(0.0, f64::max),
+    })
+}
+
+/// Measure RAG retrieval against memory size, transcription queue waits and the reply pipeline end to end.
+/// `state` must be built on `options.database_url`.
+pub async fn run(state: AppState, options: BenchOptions) -> Result<()> {
+    let account = simulate::simulated_account(&state).await?;
+    let dimension = rag::stored_embedding_dimension(&state.db_pool).await?.unwrap_or(DEFAULT_DIMENSION);
+    let started_at = chrono::Utc::now();
+
+    println!("RAG retrieval (top {}, {}-dim embeddings, {} queries each)", RAG_TOP_N, dimension, options.queries);
+    let rag = bench_rag(&state, account.id, &options, dimension).await?;
+
+    println!("Transcription queue ({} jobs from {} chats, load {:.2})", options.jobs, options.chats, options.load);
+    let queue = bench_queue(&state, &options).await?;
+    println!(
+        "  {} slots: wait {}\n  fairness across chats {:.3}, worst chat avg. {:.1}ms",
+        queue.slots,
+        queue.wait.describe(),
+        queue.fairness,
+        queue.worst_chat_wait_ms
+    );
+
+    let pipeline = if options.duration_secs > 0 {
+        println!("Reply pipeline ({} chats at {} msg/s for {}s)", options.chats, options.rate, options.duration_secs);
+        let simulation = simulate::measure(
+            &state,
+            &SimulateOptions {
+                chats: options.chats,
+                rate: options.rate,
+                duration_secs: options.duration_secs,
+                realtime: false,
+                database_url: options.database_url.clone(),
+            },
+        )
+        .await?;
+        let result = PipelineResult {
+            chats: options.chats,
+            rate: options.rate,
+            messages: simulation.generated,
+            failures: simulation.failures,
+            latency: Stats::of(&simulation.latencies),
+        };
+        println!("  {} failed, latency {}", result.failures, result.latency.describe());
+        Some(result)
+    } else {
+        None
+    };
+
+    let report = BenchReport {
+        started_at,
+        chat_model: state.config.ollama_model.clone(),
+        embed_dimension: dimension,
+        rag,
+        queue,
+        pipeline,
+    };
+    if let Some(path) = &options.report {
+        tokio::fs::write(path, serde_json::to_string_pretty(&report)? + "\n")
+            .await
+            .with_context(|| format!("Failed to write {}", path))?;
+        println!("Report written to {}", path);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = BenchOptions::parse(&args(&["--chunks", "10, 500", "--duration", "0", "--report", "b.json"])).unwrap();
+        assert_eq!(options.chunks, vec![10, 500]);
+        assert_eq!(options.duration_secs, 0);
+        assert_eq!(options.report.as_deref(), Some("b.json"));
+        assert_eq!(options.database_url, DEFAULT_DATABASE_URL);
+
+        assert!(BenchOptions::parse(&args(&["--chunks", "10,x"])).is_err());
+        assert!(BenchOptions::parse(&args(&["--chunks", "0"])).is_err());
+        assert!(BenchOptions::parse(&args(&["--load", "0"])).is_err());
+        assert!(BenchOptions::parse(&args(&["--rate", "NaN"])).is_err());
+        assert!(BenchOptions::parse(&args(&["--load", "inf"])).is_err());
+    }
+
+    #[test]
+    fn summarizes_timings() {
+        let timings: Vec<Duration> = (1..=100).map(Duration::from_millis).collect();
+        let stats = Stats::of(&timings);
+        assert_eq!(stats.count, 100);
+        assert_eq!(stats.mean_ms, 50.5);
+        assert_eq!(stats.p50_ms, 51.0);
+        assert_eq!(stats.p99_ms, 100.0);
+        assert_eq!(Stats::of(&[]), Stats::default());
+    }
+
+    #[test]
+    fn measures_fairness() {
+        assert_eq!(fairness(&[10.0, 10.0, 10.0]), 1.0);
+        assert!((fairness(&[30.0, 0.0, 0.0]) - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(fairness(&[0.0, 0.0]), 1.0);
+    }
+}
@@ -0,0 +1,184 @@
+use super::{
+    formatting::FormatMode,
+    transport::{ChatTransport, TdTransport},
+};
+use crate::{
+    ai::translate,
+    db::{ChatBridge, ChatBridgeRepository, ChatRepository},
+    state::AppState,
+};
+use anyhow::Result;
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::{mpsc, Mutex};
+
+/// How long a chat's relay queue waits for another message before its task exits
+const RELAY_QUEUE_IDLE_SECS: u64 = 60;
+
+/// A written message waiting to be mirrored: (sender, text)
+type Relayed = (i64, String);
+
+lazy_static::lazy_static! {
+    /// Relay queue of each (account, source chat) with a running task
+    static ref RELAY_QUEUES: std::sync::Mutex<HashMap<(i64, i64), mpsc::UnboundedSender<Relayed>>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
+/// Chats a message from `chat_id` is mirrored into, with the language it is translated into there
+pub fn targets(bridges: &[ChatBridge], chat_id: i64) -> Vec<(i64, String)> {
+    bridges
+        .iter()
+        .filter_map(|bridge| {
+            if bridge.chat_a == chat_id {
+                Some((bridge.chat_b, bridge.lang_b.clone()))
+            } else if bridge.chat_b == chat_id {
+                Some((bridge.chat_a, bridge.lang_a.clone()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Who wrote a mirrored message and where, above its translation
+pub fn attribution(sender: &str, chat_title: &str, text: &str) -> String {
+    format!("💬 {} · {}:\n{}", sender, chat_title, text)
+}
+
+async fn sender_name(client: &Arc<Mutex<Client<TdJson>>>, sender_id: i64) -> String {
+    if sender_id == 0 {
+        return "Канал".to_string();
+    }
+    match client.lock().await.get_user(&GetUser::builder().user_id(sender_id).build()).await {
+        Ok(user) if user.last_name().is_empty() => user.first_name().to_string(),
+        Ok(user) => format!("{} {}", user.first_name(), user.last_name()),
+        Err(_) => format!("id{}", sender_id),
+    }
+}
+
+/// Hand a message to its chat's relay queue, starting the queue's task if the chat has none.
+/// Translations take a while; the queue keeps mirrored messages in the order they were written.
+pub fn enqueue(
+    state: &AppState,
+    account_id: i64,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat_id: i64,
+    sender_id: i64,
+    text: &str,
+) {
+    let key = (account_id, chat_id);
+    let mut queues = RELAY_QUEUES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(sender) = queues.get(&key) {
+        if sender.send((sender_id, text.to_string())).is_ok() {
+            return;
+        }
+    }
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let _ = sender.send((sender_id, text.to_string()));
+    queues.insert(key, sender);
+    tokio::spawn(drain_relay_queue(state.clone(), client.clone(), key, receiver));
+}
+
+/// Relay a chat's messages one by one; exits once the chat has been quiet for a while
+async fn drain_relay_queue(
+    state: AppState,
+    client: Arc<Mutex<Client<TdJson>>>,
+    key: (i64, i64),
+    mut receiver: mpsc::UnboundedReceiver<Relayed>,
+) {
+    let (account_id, chat_id) = key;
+    let idle = std::time::Duration::from_secs(RELAY_QUEUE_IDLE_SECS);
+    loop {
+        let (sender_id, text) = match tokio::time::timeout(idle, receiver.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => return,
+            // Senders only send under the lock, so nothing can slip in between the check and the removal
+            Err(_) => {
+                let mut queues = RELAY_QUEUES.lock().unwrap_or_else(|e| e.into_inner());
+                match receiver.try_recv() {
+                    Ok(message) => message,
+                    Err(_) => {
+                        queues.remove(&key);
+                        return;
+                    }
+                }
+            }
+        };
+
+        if let Err(e) = relay(&state, account_id, &client, chat_id, sender_id, &text).await {
+            tracing::warn!("Failed to relay message from chat {}: {}", chat_id, e);
+        }
+    }
+}
+
+/// Mirror a message into every chat bridged with its chat, translated into that chat's language
+async fn relay(
+    state: &AppState,
+    account_id: i64,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat_id: i64,
+    sender_id: i64,
+    text: &str,
+) -> Result<()> {
+    let bridges = ChatBridgeRepository::for_chat(&state.db_pool, account_id, chat_id).await?;
+    let targets = targets(&bridges, chat_id);
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let sender = sender_name(client, sender_id).await;
+    let chat_title = match ChatRepository::get(&state.db_pool, account_id, chat_id).await? {
+        Some(chat) if !chat.title.is_empty() => chat.title,
+        _ => chat_id.to_string(),
+    };
+
+    let transport = TdTransport::new(client.clone());
+    for (target_chat, language) in targets {
+        let translated = match translate::translate(state, text, &language).await {
+            Ok(translation) => translation.translation,
+            Err(e) => {
+                tracing::warn!("Failed to translate bridged message from chat {}: {}", chat_id, e);
+                continue;
+            }
+        };
+        if let Err(e) = transport
+            .send_text(target_chat, &attribution(&sender, &chat_title, &translated), FormatMode::Plain, None)
+            .await
+        {
+            tracing::warn!("Failed to mirror message from chat {} into {}: {}", chat_id, target_chat, e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bridge(chat_a: i64, chat_b: i64) -> ChatBridge {
+        ChatBridge {
+            id: 1,
+            account_id: 1,
+            chat_a,
+            chat_b,
+            lang_a: "ru".to_string(),
+            lang_b: "en".to_string(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn mirrors_both_ways() {
+        let bridges = vec![bridge(-100, -200), bridge(-300, -100)];
+        assert_eq!(
+            targets(&bridges, -100),
+            vec![(-200, "en".to_string()), (-300, "ru".to_string())]
+        );
+        assert_eq!(targets(&bridges, -200), vec![(-100, "ru".to_string())]);
+        assert!(targets(&bridges, -400).is_empty());
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{Account, ChatRepository, MessageRepository, MessageRole, NewMessage, PersonaRepository},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// TDLib delivers missed updates in a burst; the backlog is complete once it has been quiet this long
+const BACKLOG_QUIET: Duration = Duration::from_secs(20);
+
+/// Missed messages kept per chat; the oldest are dropped beyond this
+const MAX_MISSED: usize = 150;
+
+/// Characters of one missed message shown to the model
+const MESSAGE_MAX_CHARS: usize = 300;
+
+const RETURN_INSTRUCTIONS: &str = r#"[ТЫ ВЕРНУЛСЯ]
+Тебя не было в чате какое-то время. Ниже — что обсуждали без тебя.
+Напиши одно короткое сообщение в своем обычном стиле: что ты вернулся и пара слов о пропущенном (можно с мнением или вопросом).
+Не пересказывай всё подряд, не отвечай каждому. Если говорить не о чем, верни ровно `<IGNORE>`."#;
+
+/// What happens to messages that piled up in a chat while the account was offline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatchupMode {
+    /// Ignore them
+    Off,
+    /// Remember a summary of them
+    Summary,
+    /// Remember a summary and, after a long gap, post one "I'm back" message
+    Post,
+}
+
+impl CatchupMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CatchupMode::Off => "off",
+            CatchupMode::Summary => "summary",
+            CatchupMode::Post => "post",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "off" => Some(CatchupMode::Off),
+            "summary" => Some(CatchupMode::Summary),
+            "post" => Some(CatchupMode::Post),
+            _ => None,
+        }
+    }
+}
+
+/// A message that arrived too late to be answered
+#[derive(Debug, Clone)]
+pub struct MissedMessage {
+    /// Unix time it was sent
+    pub date: i64,
+    pub text: String,
+}
+
+struct Backlog {
+    messages: Vec<MissedMessage>,
+    last_at: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref BACKLOGS: std::sync::Mutex<HashMap<(i64, i64), Backlog>> = std::sync::Mutex::new(HashMap::new());
+}
+
+/// Collect a message that is too old to answer; once the backlog of its chat stops growing it is caught up on
+pub fn record_missed(state: &AppState, account: &Account, client: &Arc<Mutex<Client<TdJson>>>, chat_id: i64, missed: MissedMessage) {
+    if missed.text.trim().is_empty() {
+        return;
+    }
+
+    let key = (account.id, chat_id);
+    let is_new = {
+        let mut backlogs = BACKLOGS.lock().unwrap_or_else(|e| e.into_inner());
+        let is_new = !backlogs.contains_key(&key);
+        let backlog = backlogs.entry(key).or_insert_with(|| Backlog { messages: Vec::new(), last_at: Instant::now() });
+        backlog.messages.push(missed);
+        if backlog.messages.len() > MAX_MISSED {
+            backlog.messages.remove(0);
+        }
+        backlog.last_at = Instant::now();
+        is_new
+    };
+
+    if is_new {
+        let (state, account, client) = (state.clone(), account.clone(), client.clone());
+        tokio::spawn(async move {
+            let messages = wait_for_backlog(key).await;
+            if let Err(e) = catch_up(&state, &account, &client, chat_id, messages).await {
+                tracing::warn!("Failed to catch up on chat {} of account {}: {}", chat_id, account.id, e);
+            }
+        });
+    }
+}
+
+/// Missed messages not yet caught up on, for the status view
+pub fn pending_messages() -> usize {
+    BACKLOGS.lock().unwrap_or_else(|e| e.into_inner()).values().map(|b| b.messages.len()).sum()
+}
+
+async fn wait_for_backlog(key: (i64, i64)) -> Vec<MissedMessage> {
+    loop {
+        let deadline = {
+            let mut backlogs = BACKLOGS.lock().unwrap_or_else(|e| e.into_inner());
+            match backlogs.get(&key) {
+                Some(backlog) if backlog.last_at.elapsed() < BACKLOG_QUIET => backlog.last_at + BACKLOG_QUIET,
+                _ => return backlogs.remove(&key).map(|b| b.messages).unwrap_or_default(),
+            }
+        };
+        tokio::time::sleep_until(deadline.into()).await;
+    }
+}
+
+/// Missed messages as a transcript for the model, oldest first
+pub fn transcript(messages: &[MissedMessage]) -> String {
+    let mut sorted: Vec<&MissedMessage> = messages.iter().collect();
+    sorted.sort_by_key(|m| m.date);
+    sorted
+        .iter()
+        .map(|m| {
+            let text: String = m.text.split_whitespace().collect::<Vec<_>>().join(" ");
+            format!("- {}", text.chars().take(MESSAGE_MAX_CHARS).collect::<String>())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether the account was away long enough to say it is back
+pub fn should_post(mode: CatchupMode, messages: &[MissedMessage], now: i64, threshold_minutes: i64) -> bool {
+    let oldest = match messages.iter().map(|m| m.date).min() {
+        Some(oldest) => oldest,
+        None => return false,
+    };
+    mode == CatchupMode::Post && now - oldest >= threshold_minutes * 60
+}
+
+async fn catch_up(
+    state: &AppState,
+    account: &Account,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat_id: i64,
+    messages: Vec<MissedMessage>,
+) -> Result<()> {
+    let chat = ChatRepository::get(&state.db_pool, account.id, chat_id).await?;
+    if chat.as_ref().is_some_and(|c| c.is_denied || c.is_paused()) || !account.is_chat_allowed(chat_id) {
+        return Ok(());
+    }
+    let mode = chat
+        .as_ref()
+        .and_then(|c| CatchupMode::parse(&c.catchup_mode))
+        .unwrap_or(CatchupMode::Summary);
+    if mode == CatchupMode::Off || messages.is_empty() || state.is_paused() {
+        return Ok(());
+    }
+
+    let transcript = transcript(&messages);
+    let summary = summarize(state, &transcript).await?;
+    if summary.is_empty() {
+        return Ok(());
+    }
+
+    let (from, to) = (
+        messages.iter().map(|m| m.date).min().unwrap_or_default(),
+        messages.iter().map(|m| m.date).max().unwrap_or_default(),
+    );
+    let format_time = |ts: i64| {
+        chrono::DateTime::from_timestamp(ts, 0)
+            .map(|t| t.format("%d.%m %H:%M").to_string())
+            .unwrap_or_default()
+    };
+    let statement = format!(
+        "Пока меня не было ({} – {} UTC, {} сообщений), в чате обсуждали: {}",
+        format_time(from),
+        format_time(to),
+        messages.len(),
+        summary
+    );
+    remember_summary(state, account.id, chat_id, &statement).await?;
+    tracing::info!("Caught up on {} missed messages in chat {} of account {}", messages.len(), chat_id, account.id);
+
+    if should_post(mode, &messages, chrono::Utc::now().timestamp(), state.config.catchup_post_after_minutes) {
+        let persona_id = chat.as_ref().and_then(|c| c.active_persona_id);
+        post_return(state, account.id, client, chat_id, persona_id, &summary).await?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Summary {
+    summary: String,
+}
+
+async fn summarize(state: &AppState, transcript: &str) -> Result<String> {
+    let prompt = format!(
+        r#"Below are messages of a Telegram chat that arrived while its member was offline, oldest first.
+Summarize what happened: topics, decisions, questions left for others, news. Skip greetings and noise.
+
+Messages:
+{}
+
+Return JSON: {{"summary": "<2-5 sentences in Russian, or empty if nothing worth remembering happened>"}}"#,
+        transcript
+    );
+
+    let summary: Summary = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.2), num_predict: None }),
+        )
+        .await
+        .context("Failed to summarize missed messages")?;
+    Ok(summary.summary.trim().to_string())
+}
+
+/// Keep the summary as a semantic memory so later replies know what was missed
+async fn remember_summary(state: &AppState, account_id: i64, chat_id: i64, statement: &str) -> Result<()> {
+    let embedding = crate::ai::generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        statement,
+    )
+    .await
+    .context("Failed to embed catch-up summary")?;
+
+    crate::ai::rag::upsert_semantic_memory(&state.db_pool, account_id, chat_id, statement, 0.6, &embedding).await?;
+    Ok(())
+}
+
+/// Post one "I'm back" message as the chat's persona
+async fn post_return(
+    state: &AppState,
+    account_id: i64,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat_id: i64,
+    active_persona_id: Option<i64>,
+    summary: &str,
+) -> Result<()> {
+    let (system_prompt, persona_id) = match active_persona_id {
+        Some(id) => match crate::ai::persona_inheritance::resolve(&state.db_pool, id).await? {
+            Some(persona) => (persona.prompt, Some(persona.id)),
+            None => (PersonaRepository::effective_prompt(&state.db_pool, account_id).await?, None),
+        },
+        None => (PersonaRepository::effective_prompt(&state.db_pool, account_id).await?, None),
+    };
+
+    let text = OllamaClient::new(state.config.ollama_url.clone())
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages: vec![
+                OllamaMessage { role: "system".to_string(), content: system_prompt },
+                OllamaMessage { role: "system".to_string(), content: RETURN_INSTRUCTIONS.to_string() },
+                OllamaMessage { role: "user".to_string(), content: format!("Пропущенное: {}", summary) },
+            ],
+            stream: true,
+            options: None,
+        })
+        .await?;
+    let text = text.replace("||", " ").trim().to_string();
+    if text.is_empty() || text == "<IGNORE>" {
+        return Ok(());
+    }
+
+    let send_message = SendMessage::builder()
+        .chat_id(chat_id)
+        .input_message_content(InputMessageContent::InputMessageText(
+            InputMessageText::builder()
+                .text(FormattedText::builder().text(text.clone()).build())
+                .build(),
+        ))
+        .build();
+    client.lock().await.send_message(&send_message).await?;
+
+    MessageRepository::create(
+        &state.db_pool,
+        NewMessage {
+            account_id,
+            chat_id,
+            role: MessageRole::Assistant,
+            content: text,
+            sender_id: None,
+            sender_chat_id: None,
+            persona_id,
+        },
+    )
+    .await?;
+
+    tracing::info!("Account {} posted a catch-up message in chat {}", account_id, chat_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn missed(date: i64, text: &str) -> MissedMessage {
+        MissedMessage { date, text: text.to_string() }
+    }
+
+    #[test]
+    fn transcript_is_chronological() {
+        let messages = vec![missed(20, "второе"), missed(10, "первое\n  сообщение")];
+        assert_eq!(transcript(&messages), "- первое сообщение\n- второе");
+    }
+
+    #[test]
+    fn posts_only_after_a_long_gap() {
+        let messages = vec![missed(1_000, "a"), missed(4_000, "b")];
+        assert!(should_post(CatchupMode::Post, &messages, 1_000 + 3_600, 60));
+        assert!(!should_post(CatchupMode::Post, &messages, 1_000 + 600, 60));
+        assert!(!should_post(CatchupMode::Summary, &messages, 1_000 + 3_600, 60));
+        assert!(!should_post(CatchupMode::Post, &[], 10_000, 60));
+    }
+}
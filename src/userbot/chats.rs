@@ -0,0 +1,69 @@
+use crate::{db::ChatRepository, state::AppState};
+use anyhow::{Context, Result};
+use rust_tdlib::types::*;
+
+/// How many dialogs to pull from the main chat list
+const DISCOVERY_LIMIT: i32 = 200;
+
+/// Fetch the userbot's dialogs via TDLib and store them in `account_chats`.
+/// Returns the number of chats discovered.
+pub async fn discover_chats(state: &AppState, account_id: i64) -> Result<usize> {
+    let handle = state
+        .get_userbot(account_id)
+        .await
+        .context("Userbot is not running")?;
+
+    let client_lock = handle.client.lock().await;
+
+    let get_chats = GetChats::builder()
+        .chat_list(ChatList::Main(ChatListMain::builder().build()))
+        .limit(DISCOVERY_LIMIT)
+        .build();
+
+    let chats = client_lock
+        .get_chats(&get_chats)
+        .await
+        .context("Failed to fetch chat list")?;
+
+    let mut discovered = 0;
+
+    for chat_id in chats.chat_ids() {
+        let chat = match client_lock
+            .get_chat(&GetChat::builder().chat_id(*chat_id).build())
+            .await
+        {
+            Ok(chat) => chat,
+            Err(e) => {
+                tracing::warn!("Failed to fetch chat {}: {}", chat_id, e);
+                continue;
+            }
+        };
+
+        ChatRepository::upsert_discovered(
+            &state.db_pool,
+            account_id,
+            chat.id(),
+            chat.title(),
+            chat_type_label(chat.type_()),
+        )
+        .await?;
+
+        discovered += 1;
+    }
+
+    drop(client_lock);
+
+    tracing::info!("Discovered {} chats for account {}", discovered, account_id);
+    Ok(discovered)
+}
+
+pub(crate) fn chat_type_label(chat_type: &ChatType) -> &'static str {
+    match chat_type {
+        ChatType::Private(_) => "private",
+        ChatType::BasicGroup(_) => "group",
+        ChatType::Supergroup(s) if s.is_channel() => "channel",
+        ChatType::Supergroup(_) => "supergroup",
+        ChatType::Secret(_) => "secret",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,364 @@
+use super::{
+    transport::ChatTransport,
+    worker::{respond_to_message, IncomingMessage},
+};
+use crate::{
+    db::{Account, AccountChat, ChatRepository},
+    state::AppState,
+};
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+/// Telegram clients repeat the typing action every ~5s; older ones have expired
+const TYPING_FRESH: Duration = Duration::from_secs(6);
+
+/// A message opening with one of these starts another topic
+const TOPIC_MARKERS: &[&str] = &[
+    "кстати",
+    "а еще",
+    "а ещё",
+    "другой вопрос",
+    "другая тема",
+    "не по теме",
+    "btw",
+    "by the way",
+    "another question",
+    "unrelated",
+];
+
+/// Words shorter than this carry no topic
+const MIN_TOPIC_WORD_CHARS: usize = 4;
+
+/// Crude stemming: words sharing this prefix count as the same
+const TOPIC_STEM_CHARS: usize = 5;
+
+/// (account, chat, sender)
+type Key = (i64, i64, i64);
+
+/// Messages from one sender in one chat, waiting to be answered together
+struct Batch {
+    id: u64,
+    messages: Vec<IncomingMessage>,
+    first_at: Instant,
+    last_at: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref BATCHES: std::sync::Mutex<HashMap<Key, Batch>> = std::sync::Mutex::new(HashMap::new());
+    static ref TYPING: std::sync::Mutex<HashMap<Key, Instant>> = std::sync::Mutex::new(HashMap::new());
+}
+
+static NEXT_BATCH_ID: AtomicU64 = AtomicU64::new(1);
+
+/// How a chat batches messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebounceSettings {
+    pub window: Duration,
+    pub max_wait: Duration,
+    pub max_batch: usize,
+}
+
+impl DebounceSettings {
+    pub fn for_chat(state: &AppState, chat: Option<&AccountChat>) -> Self {
+        let window_ms = chat.and_then(|c| c.debounce_ms).map_or(state.config.debounce_ms, |ms| ms.max(0) as u64);
+        let max_batch = chat
+            .and_then(|c| c.debounce_max_batch)
+            .map_or(state.config.debounce_max_batch, |n| n.max(1) as usize);
+
+        Self {
+            window: Duration::from_millis(window_ms),
+            max_wait: Duration::from_millis(state.config.debounce_max_wait_ms.max(window_ms)),
+            max_batch,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.window.is_zero() && self.max_batch > 1
+    }
+}
+
+/// Remember a sender's typing status, from TDLib's chat action updates
+pub fn record_typing(account_id: i64, chat_id: i64, sender_id: i64, typing: bool) {
+    let mut typing_map = TYPING.lock().unwrap_or_else(|e| e.into_inner());
+    if typing {
+        typing_map.insert((account_id, chat_id, sender_id), Instant::now());
+    } else {
+        typing_map.remove(&(account_id, chat_id, sender_id));
+    }
+    typing_map.retain(|_, at| at.elapsed() < TYPING_FRESH);
+}
+
+/// Messages waiting in batches, for the status view
+pub fn pending_messages() -> usize {
+    BATCHES.lock().unwrap_or_else(|e| e.into_inner()).values().map(|b| b.messages.len()).sum()
+}
+
+fn is_typing(key: Key) -> bool {
+    TYPING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key)
+        .is_some_and(|at| at.elapsed() < TYPING_FRESH)
+}
+
+/// Queue a message for a batched reply, or answer it now when batching is off for it
+pub async fn submit<T: ChatTransport + 'static>(
+    state: &AppState,
+    account: &Account,
+    transport: &Arc<T>,
+    chat_settings: Option<&AccountChat>,
+    incoming: IncomingMessage,
+) -> Result<()> {
+    let settings = DebounceSettings::for_chat(state, chat_settings);
+
+    // Channel posts and anonymous senders aren't a conversation to wait for
+    if !settings.is_enabled() || incoming.sender_id == 0 || incoming.is_channel_post {
+        let result = respond_to_message(state, account, transport.as_ref(), chat_settings, &incoming).await;
+        transport.answered(&incoming, 1, result.is_ok());
+        return result;
+    }
+
+    let key = (account.id, incoming.chat_id, incoming.sender_id);
+    let correlation_id = incoming.correlation_id.clone();
+    let now = Instant::now();
+    let (flush, start_waiter) = {
+        let mut batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+
+        let split = batches.get(&key).map(|batch| {
+            let previous: Vec<&str> = batch.messages.iter().map(|m| m.text.as_str()).collect();
+            batch.messages.len() >= settings.max_batch || is_topic_change(&previous.join("\n"), &incoming.text)
+        });
+
+        match split {
+            Some(false) => {
+                if let Some(batch) = batches.get_mut(&key) {
+                    batch.messages.push(incoming);
+                    batch.last_at = now;
+                }
+                (None, None)
+            }
+            // A full batch or a new topic is answered now; this message starts the next batch
+            Some(true) | None => {
+                let id = NEXT_BATCH_ID.fetch_add(1, Ordering::Relaxed);
+                let batch = Batch { id, messages: vec![incoming], first_at: now, last_at: now };
+                let flush = batches.insert(key, batch).map(|old| old.messages);
+                (flush, Some(id))
+            }
+        }
+    };
+
+    if let Some(messages) = flush {
+        spawn_reply(state, account, transport, messages);
+    }
+    if let Some(id) = start_waiter {
+        let (state, account, transport) = (state.clone(), account.clone(), transport.clone());
+        let span = tracing::info_span!("debounce", cid = %correlation_id, chat = key.1);
+        tokio::spawn(
+            async move {
+                wait_and_reply(state, account, transport, key, id, settings).await;
+            }
+            .instrument(span),
+        );
+    }
+    Ok(())
+}
+
+/// Sleep until the sender has been quiet long enough (or the batch waited its maximum), then answer it
+async fn wait_and_reply<T: ChatTransport>(
+    state: AppState,
+    account: Account,
+    transport: Arc<T>,
+    key: Key,
+    id: u64,
+    settings: DebounceSettings,
+) {
+    loop {
+        let deadline = {
+            let batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+            match batches.get(&key) {
+                // Flushed early and replaced by a newer batch with its own waiter
+                Some(batch) if batch.id != id => return,
+                None => return,
+                Some(batch) => {
+                    let quiet = if is_typing(key) {
+                        Instant::now() + settings.window
+                    } else {
+                        batch.last_at + settings.window
+                    };
+                    quiet.min(batch.first_at + settings.max_wait)
+                }
+            }
+        };
+
+        if deadline > Instant::now() {
+            tokio::time::sleep_until(deadline.into()).await;
+            continue;
+        }
+
+        let messages = {
+            let mut batches = BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+            match batches.get(&key) {
+                Some(batch) if batch.id == id => batches.remove(&key).map(|b| b.messages),
+                _ => None,
+            }
+        };
+        if let Some(messages) = messages {
+            reply(&state, &account, transport.as_ref(), messages).await;
+        }
+        return;
+    }
+}
+
+fn spawn_reply<T: ChatTransport + 'static>(
+    state: &AppState,
+    account: &Account,
+    transport: &Arc<T>,
+    messages: Vec<IncomingMessage>,
+) {
+    let (state, account, transport) = (state.clone(), account.clone(), transport.clone());
+    tokio::spawn(async move {
+        reply(&state, &account, transport.as_ref(), messages).await;
+    });
+}
+
+async fn reply<T: ChatTransport>(state: &AppState, account: &Account, transport: &T, messages: Vec<IncomingMessage>) {
+    let count = messages.len();
+    let incoming = match merge(messages) {
+        Some(incoming) => incoming,
+        None => return,
+    };
+
+    // Settings may have changed while the batch waited
+    let chat_settings = match ChatRepository::get(&state.db_pool, account.id, incoming.chat_id).await {
+        Ok(chat) => chat,
+        Err(e) => {
+            tracing::warn!("Failed to load chat {} for a batched reply: {}", incoming.chat_id, e);
+            transport.answered(&incoming, count, false);
+            return;
+        }
+    };
+    if chat_settings.as_ref().is_some_and(|c| c.is_denied || c.is_paused()) {
+        transport.answered(&incoming, count, true);
+        return;
+    }
+
+    let result = respond_to_message(state, account, transport, chat_settings.as_ref(), &incoming).await;
+    if let Err(e) = &result {
+        tracing::error!("Failed to answer batch in chat {}: {}", incoming.chat_id, e);
+    }
+    transport.answered(&incoming, count, result.is_ok());
+}
+
+/// One message standing for a batch: texts in order, answering (and replying to) the last one.
+/// It is timed from the first message of the batch.
+pub fn merge(mut messages: Vec<IncomingMessage>) -> Option<IncomingMessage> {
+    // Message ids grow within a chat; order by them in case a slow message was queued late
+    messages.sort_by_key(|m| m.message_id);
+    let is_sticker = messages.iter().all(|m| m.is_sticker);
+    let mentions_us = messages.iter().any(|m| m.mentions_us);
+    let media = messages.iter().rev().find_map(|m| m.media.clone());
+    let text = messages.iter().map(|m| m.text.trim()).filter(|t| !t.is_empty()).collect::<Vec<_>>().join("\n");
+    let received_at = messages.iter().map(|m| m.received_at).min()?;
+    let queued_at = messages.iter().map(|m| m.queued_at).min()?;
+    if messages.len() > 1 {
+        let merged: Vec<&str> = messages.iter().map(|m| m.correlation_id.as_str()).collect();
+        tracing::debug!("Answering messages {} together", merged.join(", "));
+    }
+
+    let mut merged = messages.into_iter().last()?;
+    merged.is_sticker = is_sticker;
+    merged.mentions_us = mentions_us;
+    merged.media = media;
+    merged.text = text;
+    merged.received_at = received_at;
+    merged.queued_at = queued_at;
+    Some(merged)
+}
+
+/// Cheap guess whether `next` is about something else than what came before:
+/// an explicit marker, or two substantial texts without a word in common
+pub fn is_topic_change(previous: &str, next: &str) -> bool {
+    let lowered = next.trim().to_lowercase();
+    if TOPIC_MARKERS.iter().any(|marker| lowered.starts_with(marker)) {
+        return true;
+    }
+
+    let (before, after) = (topic_words(previous), topic_words(next));
+    before.len() >= 3 && after.len() >= 3 && before.is_disjoint(&after)
+}
+
+fn topic_words(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= MIN_TOPIC_WORD_CHARS)
+        .map(|w| w.chars().take(TOPIC_STEM_CHARS).collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: i64, text: &str, is_sticker: bool) -> IncomingMessage {
+        IncomingMessage {
+            chat_id: 1,
+            message_id: id,
+            sender_id: 2,
+            sender_chat_id: None,
+            sender_is_bot: false,
+            reply_to_message_id: 0,
+            mentions_us: false,
+            is_channel_post: false,
+            is_sticker,
+            text: text.to_string(),
+            media: None,
+            correlation_id: format!("{:08x}", id),
+            received_at: Instant::now(),
+            queued_at: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn merges_in_order_and_answers_the_last() {
+        let merged = merge(vec![
+            message(1, "слушай", false),
+            message(2, "[Пользователь отправил стикер]", true),
+            message(3, "ты завтра свободен?", false),
+        ])
+        .unwrap();
+
+        assert_eq!(merged.message_id, 3);
+        assert_eq!(merged.text, "слушай\n[Пользователь отправил стикер]\nты завтра свободен?");
+        assert!(!merged.is_sticker);
+        assert!(merge(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn merges_by_message_id() {
+        let merged = merge(vec![message(5, "ты завтра свободен?", false), message(4, "[Голосовое] слушай", false)]).unwrap();
+
+        assert_eq!(merged.message_id, 5);
+        assert_eq!(merged.text, "[Голосовое] слушай\nты завтра свободен?");
+    }
+
+    #[test]
+    fn detects_topic_changes() {
+        assert!(is_topic_change("я вчера смотрел матч", "кстати, ты видел новости?"));
+        assert!(is_topic_change(
+            "запушил фикс базы данных, проверь миграции",
+            "какой рецепт пиццы посоветуешь вечером"
+        ));
+        assert!(!is_topic_change(
+            "запушил фикс базы данных, проверь миграции",
+            "миграции запускаются при старте базы"
+        ));
+        // Short follow-ups are never split off
+        assert!(!is_topic_change("запушил фикс базы данных", "ок?"));
+    }
+}
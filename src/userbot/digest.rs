@@ -0,0 +1,217 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{AccountChat, AccountRepository, ChatRepository, MessageRepository, MessageRole, NewMessage},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use chrono::{Datelike, Timelike};
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// How often chats are checked for a due digest
+const CHECK_INTERVAL_SECS: u64 = 600;
+/// Members listed in the digest
+const TOP_MEMBERS: i64 = 5;
+/// Consolidated facts offered to the persona as this week's topics
+const TOP_TOPICS: i64 = 8;
+/// Days a digest looks back
+const DIGEST_DAYS: i64 = 7;
+
+const DIGEST_INSTRUCTIONS: &str = r#"[ИТОГИ НЕДЕЛИ]
+Напиши короткий итог недели для этого чата в своем обычном стиле, по данным ниже: кто был самым активным, что обсуждали и, если есть, какое твое сообщение всем зашло.
+Не выдумывай ничего, чего нет в данных. Без заголовков и списков, 3-6 коротких фраз.
+Если данных слишком мало для итога, верни ровно `<IGNORE>`."#;
+
+/// Weekday names accepted by /digest, Monday first
+pub const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+
+/// Parse a weekday name ("mon", "monday", ...) into 0 = Monday .. 6 = Sunday
+pub fn parse_weekday(value: &str) -> Option<i64> {
+    let value = value.trim().to_lowercase();
+    WEEKDAYS
+        .iter()
+        .position(|d| value.starts_with(d))
+        .map(|i| i as i64)
+}
+
+/// Post weekly digests in chats that opted in, at their configured day and hour in the chat's timezone
+pub async fn digest_worker(state: AppState) {
+    tracing::info!("Digest worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        for account_id in state.list_active_userbot_ids().await {
+            let chats = match ChatRepository::list_digest_chats(&state.db_pool, account_id).await {
+                Ok(chats) => chats,
+                Err(e) => {
+                    tracing::error!("Failed to list digest chats for account {}: {}", account_id, e);
+                    continue;
+                }
+            };
+
+            for chat in chats {
+                let now = super::timezone::chat_now(Some(&chat), state.config.default_timezone);
+                let weekday = now.weekday().num_days_from_monday() as i64;
+                let hour = now.hour() as i64;
+
+                let posted_recently = chat
+                    .last_digest_at
+                    .is_some_and(|at| chrono::Utc::now() - at < chrono::Duration::days(6));
+
+                if chat.digest_weekday != weekday || chat.digest_hour != hour || posted_recently {
+                    continue;
+                }
+
+                if let Err(e) = post_digest(&state, &chat).await {
+                    tracing::warn!(
+                        "Weekly digest failed for account {} in chat {}: {}",
+                        account_id,
+                        chat.chat_id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Generate the digest for the past week in the persona's voice and post it.
+/// Returns false if there was nothing worth posting.
+pub async fn post_digest(state: &AppState, chat: &AccountChat) -> Result<bool> {
+    let account = AccountRepository::get_by_id(&state.db_pool, chat.account_id)
+        .await?
+        .context("Account not found")?;
+    let handle = state
+        .get_userbot(chat.account_id)
+        .await
+        .context("Userbot is not running")?;
+
+    let since = chrono::Utc::now() - chrono::Duration::days(DIGEST_DAYS);
+
+    let members =
+        MessageRepository::top_senders(&state.db_pool, chat.account_id, chat.chat_id, DIGEST_DAYS, TOP_MEMBERS).await?;
+    if members.is_empty() {
+        ChatRepository::record_digest(&state.db_pool, chat.account_id, chat.chat_id).await?;
+        return Ok(false);
+    }
+
+    let mut facts = String::new();
+
+    facts.push_str("Самые активные участники:\n");
+    {
+        let client_lock = handle.client.lock().await;
+        for member in &members {
+            let name = match client_lock.get_user(&GetUser::builder().user_id(member.sender_id).build()).await {
+                Ok(user) => user.first_name().to_string(),
+                Err(_) => format!("id{}", member.sender_id),
+            };
+            facts.push_str(&format!("- {}: {} сообщений\n", name, member.messages));
+        }
+    }
+
+    let topics = crate::ai::rag::recent_facts(&state.db_pool, chat.account_id, chat.chat_id, since.timestamp(), TOP_TOPICS).await?;
+    if !topics.is_empty() {
+        facts.push_str("\nТемы недели:\n");
+        for topic in topics {
+            facts.push_str(&format!("- {}\n", topic));
+        }
+    }
+
+    if let Some(best) = MessageRepository::top_answered(&state.db_pool, chat.account_id, chat.chat_id, DIGEST_DAYS).await? {
+        facts.push_str(&format!(
+            "\nТвое самое удачное сообщение (на него ответили {} раз): {}\n",
+            best.responses, best.content
+        ));
+    }
+
+    let (system_prompt, persona_id) =
+        super::rotation::resolve_chat_persona(state, &account, Some(chat)).await?;
+    let system_prompt = super::timezone::with_local_time(
+        system_prompt,
+        super::timezone::chat_now(Some(chat), state.config.default_timezone),
+    );
+
+    let digest = OllamaClient::new(state.config.ollama_url.clone())
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages: vec![
+                OllamaMessage { role: "system".to_string(), content: system_prompt },
+                OllamaMessage { role: "system".to_string(), content: DIGEST_INSTRUCTIONS.to_string() },
+                OllamaMessage { role: "user".to_string(), content: facts },
+            ],
+            stream: true,
+            options: None,
+        })
+        .await?;
+
+    let digest = digest.trim();
+    ChatRepository::record_digest(&state.db_pool, chat.account_id, chat.chat_id).await?;
+
+    if digest.is_empty() || digest == "<IGNORE>" {
+        return Ok(false);
+    }
+
+    let transport = super::transport::TdTransport::new(handle.client.clone());
+    super::transport::send_split(&transport, state, chat.chat_id, Some(chat), digest).await?;
+
+    MessageRepository::create(
+        &state.db_pool,
+        NewMessage {
+            account_id: chat.account_id,
+            chat_id: chat.chat_id,
+            role: MessageRole::Assistant,
+            content: digest.to_string(),
+            sender_id: None,
+            sender_chat_id: None,
+            persona_id,
+        },
+    )
+    .await?;
+
+    tracing::info!("Userbot {} posted the weekly digest in chat {}", chat.account_id, chat.chat_id);
+    Ok(true)
+}
+
+/// Count a member's reply to one of our own text messages
+pub async fn record_response(
+    state: &AppState,
+    account_id: i64,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat_id: i64,
+    message_id: i64,
+) -> Result<()> {
+    let message = client
+        .lock()
+        .await
+        .get_message(&GetMessage::builder().chat_id(chat_id).message_id(message_id).build())
+        .await?;
+
+    if !message.is_outgoing() {
+        return Ok(());
+    }
+
+    let text = match message.content() {
+        MessageContent::MessageText(text) => text.text().text().to_string(),
+        _ => return Ok(()),
+    };
+
+    MessageRepository::record_response(&state.db_pool, account_id, chat_id, message_id, &text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_weekday_names() {
+        assert_eq!(parse_weekday("mon"), Some(0));
+        assert_eq!(parse_weekday("Sunday"), Some(6));
+        assert_eq!(parse_weekday("fri"), Some(4));
+        assert_eq!(parse_weekday("someday"), None);
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    db::{EphemeralMessage, EphemeralRepository, MessageRepository},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use rust_tdlib::types::*;
+
+/// How often due replies are deleted
+const CHECK_INTERVAL_SECS: u64 = 60;
+
+/// Deletions handled per account and check
+const BATCH_SIZE: i64 = 200;
+
+/// Deletions still pending this long after their time (the account stayed offline) are dropped
+const GIVE_UP_AFTER_HOURS: i64 = 24;
+
+/// Delete replies in ephemeral chats once their time is up
+pub async fn ephemeral_worker(state: AppState) {
+    tracing::info!("Ephemeral reply worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(GIVE_UP_AFTER_HOURS);
+        match EphemeralRepository::remove_overdue(&state.db_pool, cutoff).await {
+            Ok(0) => {}
+            Ok(dropped) => tracing::info!("Dropped {} ephemeral deletions of offline accounts", dropped),
+            Err(e) => tracing::error!("Failed to drop overdue ephemeral replies: {}", e),
+        }
+
+        // Only running accounts can delete; the others' rows wait without holding up anyone's batch
+        for account_id in state.list_active_userbot_ids().await {
+            let due = match EphemeralRepository::due(&state.db_pool, account_id, BATCH_SIZE).await {
+                Ok(due) => due,
+                Err(e) => {
+                    tracing::error!("Failed to fetch due ephemeral replies of account {}: {}", account_id, e);
+                    continue;
+                }
+            };
+
+            for message in due {
+                if let Err(e) = delete_if_unmarked(&state, &message).await {
+                    tracing::warn!(
+                        "Failed to delete ephemeral reply {} in chat {}: {}",
+                        message.message_id,
+                        message.chat_id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Delete a reply unless someone pinned it or answered it; either way it is done
+async fn delete_if_unmarked(state: &AppState, message: &EphemeralMessage) -> Result<()> {
+    let handle = match state.get_userbot(message.account_id).await {
+        Some(handle) => handle,
+        // Stopped mid-batch: retried when the account is back, within GIVE_UP_AFTER_HOURS
+        None => return Ok(()),
+    };
+
+    // Deleting a reply people answered would leave their answers hanging
+    let answered =
+        MessageRepository::responses_to(&state.db_pool, message.account_id, message.chat_id, message.message_id).await? > 0;
+
+    let client_lock = handle.client.lock().await;
+    let sent = client_lock
+        .get_message(&GetMessage::builder().chat_id(message.chat_id).message_id(message.message_id).build())
+        .await;
+
+    match sent {
+        // Already gone, or the send never went through
+        Err(e) if is_not_found(&e) => {}
+        // Anything else may pass; keep the row and try again next check
+        Err(e) => return Err(e).context("Failed to fetch the reply"),
+        Ok(sent) => {
+            if sent.is_pinned() || answered {
+                tracing::debug!("Keeping marked reply {} in chat {}", message.message_id, message.chat_id);
+            } else {
+                client_lock
+                    .delete_messages(
+                        &DeleteMessages::builder()
+                            .chat_id(message.chat_id)
+                            .message_ids(vec![message.message_id])
+                            .revoke(true)
+                            .build(),
+                    )
+                    .await?;
+            }
+        }
+    }
+    drop(client_lock);
+
+    EphemeralRepository::remove(&state.db_pool, message.id).await
+}
+
+/// Whether TDLib answered that the message doesn't exist
+fn is_not_found(error: &rust_tdlib::errors::Error) -> bool {
+    matches!(error, rust_tdlib::errors::Error::TDLibError(e) if e.code() == 404)
+}
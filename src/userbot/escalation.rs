@@ -0,0 +1,180 @@
+use super::trace::message_link;
+use crate::{
+    bot::handlers::html_escape,
+    db::AccountChat,
+    state::AppState,
+    webhooks::{self, Event},
+};
+use anyhow::{Context, Result};
+use rust_tdlib::client::{tdlib_client::TdJson, Client};
+use std::sync::Arc;
+use teloxide::{
+    prelude::*,
+    types::{ChatId, InputFile, ParseMode},
+};
+use tokio::sync::Mutex;
+
+/// Longest transcript quoted in the caption; Telegram captions stop at 1024 characters
+const MAX_TRANSCRIPT_CHARS: usize = 700;
+
+/// A voice or video note that may need the owner's attention
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NoteKind {
+    Voice,
+    Video,
+}
+
+impl NoteKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoteKind::Voice => "voice",
+            NoteKind::Video => "video",
+        }
+    }
+}
+
+/// The chat's keywords a transcript mentions
+pub fn matched(keywords: &[String], transcript: &str) -> Vec<String> {
+    let transcript = transcript.to_lowercase();
+    keywords.iter().filter(|k| transcript.contains(k.as_str())).cloned().collect()
+}
+
+/// Cut a transcript to fit a caption
+pub fn clip(transcript: &str) -> String {
+    match transcript.char_indices().nth(MAX_TRANSCRIPT_CHARS) {
+        Some((end, _)) => format!("{}…", &transcript[..end]),
+        None => transcript.to_string(),
+    }
+}
+
+/// Transcribe a video note's sound track, waiting for a Whisper slot like voice notes do
+async fn transcribe_video(state: &AppState, path: &str) -> Result<String> {
+    let whisper_url = state.config.whisper_url.as_ref().context("Whisper URL not configured")?;
+    let timeout = std::time::Duration::from_secs(state.config.whisper_timeout_secs);
+    let whisper = crate::ai::WhisperClient::with_timeout(whisper_url.clone(), timeout);
+    crate::ai::whisper::run_queued(
+        &state.transcription_slots,
+        timeout,
+        None,
+        async {},
+        whisper.transcribe(std::path::Path::new(path)),
+    )
+    .await
+}
+
+/// Forward a voice or video note to the owners if its transcript mentions one of the chat's keywords.
+/// Voice notes come with the transcript the worker already made; video notes are transcribed here.
+#[allow(clippy::too_many_arguments)]
+pub async fn escalate(
+    state: &AppState,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    message_id: i64,
+    sender_id: i64,
+    kind: NoteKind,
+    file_id: i32,
+    transcript: Option<String>,
+) -> Result<()> {
+    let keywords = chat.get_escalation_keywords();
+    if keywords.is_empty() {
+        return Ok(());
+    }
+
+    let path = super::worker::download_file(client, file_id).await?;
+    let transcript = match transcript {
+        Some(transcript) => transcript,
+        None => match transcribe_video(state, &path).await {
+            Ok(transcript) => transcript,
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&path).await;
+                return Err(e);
+            }
+        },
+    };
+
+    let hits = matched(&keywords, &transcript);
+    if hits.is_empty() {
+        let _ = tokio::fs::remove_file(&path).await;
+        return Ok(());
+    }
+    tracing::info!("Escalating {} note in chat {} for {}", kind.as_str(), chat.chat_id, hits.join(", "));
+
+    let title = match chat.title.is_empty() {
+        true => chat.chat_id.to_string(),
+        false => chat.title.clone(),
+    };
+    let link = message_link(chat.chat_id, message_id, sender_id);
+    let mut caption = format!(
+        "🚨 <b>{}</b> · {} note from <code>{}</code>\nKeywords: {}\n\n<i>{}</i>",
+        html_escape(&title),
+        kind.as_str(),
+        sender_id,
+        html_escape(&hits.join(", ")),
+        html_escape(&clip(&transcript))
+    );
+    if let Some(link) = &link {
+        caption.push_str(&format!("\n\n<a href=\"{}\">Open message</a>", link));
+    }
+
+    let bot = Bot::new(&state.config.bot_token);
+    for owner_id in &state.config.owner_ids {
+        let sent = match kind {
+            NoteKind::Voice => bot
+                .send_voice(ChatId(*owner_id), InputFile::file(&path))
+                .caption(caption.clone())
+                .parse_mode(ParseMode::Html)
+                .await
+                .map(|_| ()),
+            // Video notes take no caption
+            NoteKind::Video => match bot.send_video_note(ChatId(*owner_id), InputFile::file(&path)).await {
+                Ok(_) => bot
+                    .send_message(ChatId(*owner_id), caption.clone())
+                    .parse_mode(ParseMode::Html)
+                    .await
+                    .map(|_| ()),
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = sent {
+            tracing::error!("Failed to escalate {} note to owner {}: {}", kind.as_str(), owner_id, e);
+        }
+    }
+    let _ = tokio::fs::remove_file(&path).await;
+
+    webhooks::emit(
+        state,
+        Event::Escalation,
+        serde_json::json!({
+            "account_id": chat.account_id,
+            "chat_id": chat.chat_id,
+            "message_id": message_id,
+            "sender_id": sender_id,
+            "kind": kind.as_str(),
+            "keywords": hits,
+            "transcript": transcript.chars().take(1000).collect::<String>(),
+            "link": link,
+        }),
+    )
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_keywords_case_insensitively() {
+        let keywords = crate::userbot::onboarding::parse_triggers("срочно, Деньги,help");
+        assert_eq!(matched(&keywords, "Это СРОЧНО, перезвони"), vec!["срочно".to_string()]);
+        assert_eq!(matched(&keywords, "need help with money"), vec!["help".to_string()]);
+        assert!(matched(&keywords, "просто привет").is_empty());
+    }
+
+    #[test]
+    fn clips_long_transcripts() {
+        assert_eq!(clip("short"), "short");
+        let long = "я".repeat(MAX_TRANSCRIPT_CHARS + 10);
+        assert_eq!(clip(&long).chars().count(), MAX_TRANSCRIPT_CHARS + 1);
+    }
+}
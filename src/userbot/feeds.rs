@@ -0,0 +1,298 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{ChatRepository, Feed, FeedRepository, MessageRepository, MessageRole, NewMessage, PersonaRepository},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use rust_tdlib::types::*;
+
+/// Item ids remembered per feed beyond the ones currently in it
+const SEEN_KEPT: usize = 200;
+
+/// Characters of an item's summary shown to the model
+const SUMMARY_MAX_CHARS: usize = 1500;
+
+const RETELL_INSTRUCTIONS: &str = r#"[НОВОСТЬ]
+Перескажи новость ниже для чата своими словами, в своем обычном стиле и со своим мнением — 2–4 предложения.
+Не выдумывай факты, которых нет в новости. Ссылку не добавляй. Если новость неинтересна или непонятна, верни ровно `<IGNORE>`."#;
+
+/// One entry of a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedItem {
+    pub id: String,
+    pub title: String,
+    pub summary: String,
+    pub link: Option<String>,
+}
+
+/// A feed that changed since the last fetch
+#[derive(Debug)]
+pub struct Fetched {
+    pub title: String,
+    /// Newest first, as feeds list them
+    pub items: Vec<FeedItem>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+/// Download a feed; None when the server says it hasn't changed (HTTP 304)
+pub async fn fetch(url: &str, etag: Option<&str>, last_modified: Option<&str>) -> Result<Option<Fetched>> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(20))
+        .user_agent("Puppeteer feed reader")
+        .build()?;
+
+    let mut request = client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+    }
+    if let Some(last_modified) = last_modified {
+        request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+    }
+
+    let response = request.send().await.context("Failed to download feed")?;
+    if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response.error_for_status().context("Feed server returned an error")?;
+
+    let header = |name: reqwest::header::HeaderName| {
+        response.headers().get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+    };
+    let etag = header(reqwest::header::ETAG);
+    let last_modified = header(reqwest::header::LAST_MODIFIED);
+
+    let bytes = response.bytes().await.context("Failed to read feed")?;
+    let feed = feed_rs::parser::parse(&bytes[..]).context("Not an RSS or Atom feed")?;
+
+    let items = feed
+        .entries
+        .into_iter()
+        .map(|entry| FeedItem {
+            id: entry.id,
+            title: entry.title.map(|t| clean_text(&t.content)).unwrap_or_default(),
+            summary: entry
+                .summary
+                .map(|s| s.content)
+                .or_else(|| entry.content.and_then(|c| c.body))
+                .map(|s| clean_text(&s))
+                .unwrap_or_default(),
+            link: entry.links.into_iter().next().map(|l| l.href),
+        })
+        .collect();
+
+    Ok(Some(Fetched {
+        title: feed.title.map(|t| clean_text(&t.content)).unwrap_or_default(),
+        items,
+        etag,
+        last_modified,
+    }))
+}
+
+/// Plain text from an HTML fragment, on one line
+fn clean_text(html: &str) -> String {
+    super::formatting::strip_html(html)
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&nbsp;", " ")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Items not seen before, oldest first
+pub fn unseen<'a>(items: &'a [FeedItem], seen: &[String]) -> Vec<&'a FeedItem> {
+    items.iter().rev().filter(|item| !seen.contains(&item.id)).collect()
+}
+
+/// Ids to remember after a check: everything seen or just posted, current items first
+pub fn next_seen(items: &[FeedItem], seen: &[String], posted: &[String]) -> Vec<String> {
+    let mut next: Vec<String> = items
+        .iter()
+        .filter(|item| seen.contains(&item.id) || posted.contains(&item.id))
+        .map(|item| item.id.clone())
+        .collect();
+
+    // Items that dropped out of the feed stay remembered for a while, in case they come back
+    for id in seen {
+        if next.len() >= SEEN_KEPT.max(items.len()) {
+            break;
+        }
+        if !next.contains(id) {
+            next.push(id.clone());
+        }
+    }
+    next
+}
+
+/// Check subscribed feeds and retell new items in their chats
+pub async fn feed_worker(state: AppState) {
+    tracing::info!("Feed worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(state.config.feed_poll_minutes * 60)).await;
+
+        // Retellings need the chat model; items wait until it's back
+        if state.is_paused() {
+            continue;
+        }
+
+        let feeds = match FeedRepository::list_all(&state.db_pool).await {
+            Ok(feeds) => feeds,
+            Err(e) => {
+                tracing::error!("Failed to list feeds: {}", e);
+                continue;
+            }
+        };
+
+        for feed in feeds {
+            if state.get_userbot(feed.account_id).await.is_none() {
+                continue;
+            }
+            if let Err(e) = check_feed(&state, &feed).await {
+                tracing::warn!("Feed {} ({}) failed: {}", feed.id, feed.url, e);
+                let _ = FeedRepository::record_unchanged(&state.db_pool, feed.id, Some(&e.to_string())).await;
+            }
+        }
+    }
+}
+
+async fn check_feed(state: &AppState, feed: &Feed) -> Result<()> {
+    let chat = ChatRepository::get(&state.db_pool, feed.account_id, feed.chat_id).await?;
+    if chat.as_ref().is_some_and(|c| c.is_denied || c.is_paused()) {
+        return Ok(());
+    }
+
+    let fetched = match fetch(&feed.url, feed.etag.as_deref(), feed.last_modified.as_deref()).await? {
+        Some(fetched) => fetched,
+        None => return FeedRepository::record_unchanged(&state.db_pool, feed.id, None).await,
+    };
+
+    let seen = feed.get_seen_ids();
+    let mut posted = Vec::new();
+    for item in unseen(&fetched.items, &seen).into_iter().take(state.config.feed_max_items_per_poll) {
+        let persona_id = chat.as_ref().and_then(|c| c.active_persona_id);
+        post_item(state, feed, persona_id, &fetched.title, item).await?;
+        posted.push(item.id.clone());
+    }
+
+    let title = if fetched.title.is_empty() { feed.title.clone() } else { fetched.title.clone() };
+    FeedRepository::record_check(
+        &state.db_pool,
+        feed.id,
+        &title,
+        fetched.etag.as_deref(),
+        fetched.last_modified.as_deref(),
+        &next_seen(&fetched.items, &seen, &posted),
+    )
+    .await
+}
+
+/// Retell an item as the chat's persona and post it with its link
+async fn post_item(
+    state: &AppState,
+    feed: &Feed,
+    active_persona_id: Option<i64>,
+    feed_title: &str,
+    item: &FeedItem,
+) -> Result<()> {
+    let (system_prompt, persona_id) = match active_persona_id {
+        Some(id) => match crate::ai::persona_inheritance::resolve(&state.db_pool, id).await? {
+            Some(persona) => (persona.prompt, Some(persona.id)),
+            None => (PersonaRepository::effective_prompt(&state.db_pool, feed.account_id).await?, None),
+        },
+        None => (PersonaRepository::effective_prompt(&state.db_pool, feed.account_id).await?, None),
+    };
+
+    let summary: String = item.summary.chars().take(SUMMARY_MAX_CHARS).collect();
+    let news = format!("Источник: {}\nЗаголовок: {}\n{}", feed_title, item.title, summary);
+
+    let retelling = OllamaClient::new(state.config.ollama_url.clone())
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages: vec![
+                OllamaMessage { role: "system".to_string(), content: system_prompt },
+                OllamaMessage { role: "system".to_string(), content: RETELL_INSTRUCTIONS.to_string() },
+                OllamaMessage { role: "user".to_string(), content: news },
+            ],
+            stream: true,
+            options: None,
+        })
+        .await?;
+    let retelling = retelling.trim();
+    if retelling.is_empty() || retelling == "<IGNORE>" {
+        tracing::debug!("Persona skipped feed item {} of feed {}", item.id, feed.id);
+        return Ok(());
+    }
+
+    let text = match &item.link {
+        Some(link) => format!("{}\n\n{}", retelling, link),
+        None => retelling.to_string(),
+    };
+
+    let handle = match state.get_userbot(feed.account_id).await {
+        Some(h) => h,
+        None => return Ok(()),
+    };
+    let client_lock = handle.client.lock().await;
+    let send_message = SendMessage::builder()
+        .chat_id(feed.chat_id)
+        .input_message_content(InputMessageContent::InputMessageText(
+            InputMessageText::builder()
+                .text(FormattedText::builder().text(text.clone()).build())
+                .build(),
+        ))
+        .build();
+    client_lock.send_message(&send_message).await?;
+    drop(client_lock);
+
+    MessageRepository::create(
+        &state.db_pool,
+        NewMessage {
+            account_id: feed.account_id,
+            chat_id: feed.chat_id,
+            role: MessageRole::Assistant,
+            content: text,
+            sender_id: None,
+            sender_chat_id: None,
+            persona_id,
+        },
+    )
+    .await?;
+
+    tracing::info!("Posted item '{}' of feed {} to chat {}", item.title, feed.id, feed.chat_id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: &str) -> FeedItem {
+        FeedItem { id: id.to_string(), title: id.to_string(), summary: String::new(), link: None }
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn new_items_come_oldest_first() {
+        let items = vec![item("c"), item("b"), item("a")];
+        let fresh: Vec<&str> = unseen(&items, &ids(&["a"])).iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(fresh, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn unposted_items_stay_unseen() {
+        let items = vec![item("c"), item("b"), item("a")];
+        let next = next_seen(&items, &ids(&["a", "old"]), &ids(&["b"]));
+        assert_eq!(next, ids(&["b", "a", "old"]));
+        assert_eq!(unseen(&items, &next).len(), 1);
+    }
+
+    #[test]
+    fn cleans_html() {
+        assert_eq!(clean_text("<p>Hello&nbsp;<b>world</b></p>\n  &amp; more"), "Hello world & more");
+    }
+}
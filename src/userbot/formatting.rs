@@ -0,0 +1,366 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use rust_tdlib::{client::tdlib_client::TdJson, client::Client, types::*};
+
+/// How a chat wants outgoing replies rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatMode {
+    /// Convert LLM markdown into Telegram entities
+    Markdown,
+    /// Let TDLib parse the reply as Telegram HTML
+    Html,
+    /// Send the text as-is
+    Plain,
+}
+
+impl FormatMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FormatMode::Markdown => "markdown",
+            FormatMode::Html => "html",
+            FormatMode::Plain => "plain",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "markdown" | "md" => Some(FormatMode::Markdown),
+            "html" => Some(FormatMode::Html),
+            "plain" | "off" => Some(FormatMode::Plain),
+            _ => None,
+        }
+    }
+
+    /// The mode a chat's messages are sent in; plain unless the chat opted into another
+    pub fn for_chat(chat: Option<&crate::db::AccountChat>) -> Self {
+        chat.and_then(|c| Self::parse(&c.format_mode)).unwrap_or(FormatMode::Plain)
+    }
+}
+
+/// Formatting applied to a span of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EntityKind {
+    Bold,
+    Italic,
+    Strikethrough,
+    Code,
+    Pre(Option<String>),
+    TextUrl(String),
+}
+
+/// A formatted span; offsets and lengths are in UTF-16 code units, as Telegram expects
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntitySpan {
+    pub offset: usize,
+    pub length: usize,
+    pub kind: EntityKind,
+}
+
+/// Plain text plus the entities to render on top of it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FormattedMessage {
+    pub text: String,
+    pub entities: Vec<EntitySpan>,
+}
+
+impl FormattedMessage {
+    fn utf16_len(&self) -> usize {
+        self.text.encode_utf16().count()
+    }
+
+    fn push_str(&mut self, s: &str) {
+        self.text.push_str(s);
+    }
+
+    /// Drop empty or out-of-range entities so TDLib never rejects the message
+    fn validate(mut self) -> Self {
+        let len = self.utf16_len();
+        self.entities.retain(|e| e.length > 0 && e.offset < len);
+        for entity in &mut self.entities {
+            entity.length = entity.length.min(len - entity.offset);
+        }
+        self.entities.sort_by_key(|e| e.offset);
+        self
+    }
+}
+
+/// Convert LLM markdown into text + entities
+pub fn markdown_to_entities(markdown: &str) -> FormattedMessage {
+    let mut message = FormattedMessage::default();
+    let mut open: Vec<(EntityKind, usize)> = Vec::new();
+    let mut lists: Vec<Option<u64>> = Vec::new();
+
+    let parser = Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH);
+
+    for event in parser {
+        match event {
+            Event::Start(tag) => match tag {
+                Tag::Strong | Tag::Heading { .. } => open.push((EntityKind::Bold, message.utf16_len())),
+                Tag::Emphasis => open.push((EntityKind::Italic, message.utf16_len())),
+                Tag::Strikethrough => open.push((EntityKind::Strikethrough, message.utf16_len())),
+                Tag::Link { dest_url, .. } => {
+                    open.push((EntityKind::TextUrl(dest_url.to_string()), message.utf16_len()))
+                }
+                Tag::CodeBlock(kind) => {
+                    let language = match kind {
+                        CodeBlockKind::Fenced(lang) if !lang.is_empty() => Some(lang.to_string()),
+                        _ => None,
+                    };
+                    open.push((EntityKind::Pre(language), message.utf16_len()));
+                }
+                Tag::List(start) => lists.push(start),
+                Tag::Item => {
+                    let bullet = match lists.last_mut() {
+                        Some(Some(n)) => {
+                            let bullet = format!("{}. ", n);
+                            *n += 1;
+                            bullet
+                        }
+                        _ => "• ".to_string(),
+                    };
+                    message.push_str(&bullet);
+                }
+                _ => {}
+            },
+            Event::End(tag) => match tag {
+                TagEnd::Strong
+                | TagEnd::Emphasis
+                | TagEnd::Strikethrough
+                | TagEnd::Link
+                | TagEnd::CodeBlock
+                | TagEnd::Heading(_) => {
+                    if let Some((kind, start)) = open.pop() {
+                        // Code blocks end with a newline that should stay outside the entity
+                        let end = message.text.trim_end_matches('\n').encode_utf16().count();
+                        message.entities.push(EntitySpan {
+                            offset: start,
+                            length: end.saturating_sub(start),
+                            kind,
+                        });
+                    }
+                    if matches!(tag, TagEnd::Heading(_) | TagEnd::CodeBlock) {
+                        message.push_str("\n\n");
+                    }
+                }
+                TagEnd::Paragraph => message.push_str("\n\n"),
+                TagEnd::Item if !message.text.ends_with('\n') => message.push_str("\n"),
+                TagEnd::List(_) => {
+                    lists.pop();
+                    message.push_str("\n");
+                }
+                _ => {}
+            },
+            Event::Text(text) => message.push_str(&text),
+            Event::Code(code) => {
+                let start = message.utf16_len();
+                message.push_str(&code);
+                message.entities.push(EntitySpan {
+                    offset: start,
+                    length: message.utf16_len() - start,
+                    kind: EntityKind::Code,
+                });
+            }
+            Event::SoftBreak | Event::HardBreak => message.push_str("\n"),
+            Event::Rule => message.push_str("———\n\n"),
+            Event::Html(html) | Event::InlineHtml(html) => message.push_str(&html),
+            _ => {}
+        }
+    }
+
+    // Trailing separators never carry entities, so trimming keeps offsets valid
+    let trimmed_len = message.text.trim_end().len();
+    message.text.truncate(trimmed_len);
+
+    message.validate()
+}
+
+fn to_text_entity(span: &EntitySpan) -> TextEntity {
+    let entity_type = match &span.kind {
+        EntityKind::Bold => TextEntityType::Bold(TextEntityTypeBold::builder().build()),
+        EntityKind::Italic => TextEntityType::Italic(TextEntityTypeItalic::builder().build()),
+        EntityKind::Strikethrough => {
+            TextEntityType::Strikethrough(TextEntityTypeStrikethrough::builder().build())
+        }
+        EntityKind::Code => TextEntityType::Code(TextEntityTypeCode::builder().build()),
+        EntityKind::Pre(Some(language)) => TextEntityType::PreCode(
+            TextEntityTypePreCode::builder().language(language.clone()).build(),
+        ),
+        EntityKind::Pre(None) => TextEntityType::Pre(TextEntityTypePre::builder().build()),
+        EntityKind::TextUrl(url) => {
+            TextEntityType::TextUrl(TextEntityTypeTextUrl::builder().url(url.clone()).build())
+        }
+    };
+
+    TextEntity::builder()
+        .offset(span.offset as i32)
+        .length(span.length as i32)
+        .type_(entity_type)
+        .build()
+}
+
+/// Strip tags from text that TDLib refused to parse as HTML
+pub(crate) fn strip_html(text: &str) -> String {
+    lazy_static::lazy_static! {
+        static ref TAG: regex::Regex = regex::Regex::new(r"</?[a-zA-Z][^>]*>").unwrap();
+    }
+    TAG.replace_all(text, "")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Build the FormattedText to send for a reply chunk in the given mode
+pub async fn format_reply(client: &Client<TdJson>, text: &str, mode: FormatMode) -> FormattedText {
+    match mode {
+        FormatMode::Plain => FormattedText::builder().text(text).build(),
+        FormatMode::Markdown => {
+            let message = markdown_to_entities(text);
+            FormattedText::builder()
+                .text(message.text.clone())
+                .entities(message.entities.iter().map(to_text_entity).collect::<Vec<_>>())
+                .build()
+        }
+        FormatMode::Html => {
+            let parse = ParseTextEntities::builder()
+                .text(text)
+                .parse_mode(TextParseMode::HTML(TextParseModeHTML::builder().build()))
+                .build();
+
+            match client.parse_text_entities(&parse).await {
+                Ok(formatted) => formatted,
+                Err(e) => {
+                    // Invalid markup: keep the words, lose the tags
+                    tracing::warn!("Reply is not valid Telegram HTML, sending as plain text: {}", e);
+                    FormattedText::builder().text(strip_html(text)).build()
+                }
+            }
+        }
+    }
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// Longest prefix of `text` that fits in `max_len` UTF-16 units, cut at the best boundary
+fn split_point(text: &str, max_len: usize) -> usize {
+    // Byte index of the hard limit
+    let mut limit = text.len();
+    let mut units = 0;
+    for (idx, ch) in text.char_indices() {
+        units += ch.len_utf16();
+        if units > max_len {
+            limit = idx;
+            break;
+        }
+    }
+
+    let window = &text[..limit];
+
+    // Prefer paragraph, then line, then sentence, then word boundaries;
+    // ignore boundaries in the first third so chunks don't get tiny
+    let min = limit / 3;
+    for separator in ["\n\n", "\n", ". ", "! ", "? ", "… ", " "] {
+        if let Some(pos) = window.rfind(separator) {
+            if pos > min {
+                return pos + separator.len();
+            }
+        }
+    }
+
+    limit.max(text.chars().next().map_or(0, |c| c.len_utf8()))
+}
+
+/// Split a reply into messages of at most `max_len` UTF-16 units.
+/// Fenced code blocks cut in half are closed and reopened so both parts stay formatted.
+pub fn split_long_message(text: &str, max_len: usize) -> Vec<String> {
+    if utf16_len(text) <= max_len {
+        return vec![text.to_string()];
+    }
+
+    // Leave room for a closing/reopening fence
+    const FENCE_RESERVE: usize = 24;
+    let budget = max_len.saturating_sub(FENCE_RESERVE).max(1);
+
+    let mut chunks = Vec::new();
+    let mut rest = text.to_string();
+    let mut reopen_fence: Option<String> = None;
+
+    while !rest.is_empty() {
+        if let Some(fence) = reopen_fence.take() {
+            rest = format!("{}\n{}", fence, rest);
+        }
+
+        if utf16_len(&rest) <= max_len {
+            chunks.push(rest);
+            break;
+        }
+
+        let cut = split_point(&rest, budget);
+        let mut chunk = rest[..cut].trim_end().to_string();
+        rest = rest[cut..].trim_start_matches([' ', '\n']).to_string();
+
+        // An odd number of fences means the chunk ends inside a code block
+        let fences: Vec<&str> = chunk.lines().filter(|l| l.trim_start().starts_with("```")).collect();
+        if fences.len() % 2 == 1 {
+            let opening = fences.last().map(|l| l.trim().to_string()).unwrap_or_default();
+            chunk.push_str("\n```");
+            reopen_fence = Some(opening);
+        }
+
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_is_unchanged() {
+        let message = markdown_to_entities("ну да || ахах)");
+        assert_eq!(message.text, "ну да || ахах)");
+        assert!(message.entities.is_empty());
+    }
+
+    #[test]
+    fn test_nested_formatting_uses_utf16_offsets() {
+        let message = markdown_to_entities("привет **жирный _и курсив_** 👍 `code`");
+        assert_eq!(message.text, "привет жирный и курсив 👍 code");
+        assert!(message.entities.contains(&EntitySpan { offset: 7, length: 15, kind: EntityKind::Bold }));
+        assert!(message.entities.contains(&EntitySpan { offset: 14, length: 8, kind: EntityKind::Italic }));
+        // The emoji is two UTF-16 units
+        assert!(message.entities.contains(&EntitySpan { offset: 26, length: 4, kind: EntityKind::Code }));
+    }
+
+    #[test]
+    fn test_code_block_keeps_language() {
+        let message = markdown_to_entities("```rust\nfn main() {}\n```");
+        assert_eq!(message.text, "fn main() {}");
+        assert_eq!(message.entities[0].kind, EntityKind::Pre(Some("rust".to_string())));
+        assert_eq!(message.entities[0].length, 12);
+    }
+
+    #[test]
+    fn test_split_prefers_paragraphs() {
+        let text = format!("{}\n\n{}", "а".repeat(150), "б".repeat(150));
+        let chunks = split_long_message(&text, 200);
+        assert_eq!(chunks, vec!["а".repeat(150), "б".repeat(150)]);
+    }
+
+    #[test]
+    fn test_split_reopens_code_block() {
+        let body = (0..60).map(|i| format!("line {}", i)).collect::<Vec<_>>().join("\n");
+        let text = format!("```rust\n{}\n```", body);
+        let chunks = split_long_message(&text, 200);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(utf16_len(chunk) <= 200);
+            assert_eq!(chunk.matches("```").count() % 2, 0);
+        }
+        assert!(chunks[1].starts_with("```rust\n"));
+    }
+}
@@ -0,0 +1,393 @@
+use super::{
+    formatting::FormatMode,
+    transport::{ChatTransport, TdTransport},
+};
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    db::{Account, AccountChat, GameRepository, GameRound},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Members shown by /game top
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// How often rounds whose time is up are revealed
+const CHECK_INTERVAL_SECS: u64 = 5;
+
+/// Earlier questions of the chat the model is told not to repeat
+const RECENT_QUESTIONS: i64 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameKind {
+    /// A question with a short answer
+    Trivia,
+    /// A definition; the answer is the word
+    Words,
+}
+
+impl GameKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "trivia" | "викторина" | "quiz" => Some(GameKind::Trivia),
+            "words" | "словарь" | "слова" => Some(GameKind::Words),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GameKind::Trivia => "trivia",
+            GameKind::Words => "words",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            GameKind::Trivia => "Викторина",
+            GameKind::Words => "Словарь",
+        }
+    }
+
+    fn task(&self) -> &'static str {
+        match self {
+            GameKind::Trivia => {
+                "Come up with one trivia question of general knowledge with a short, unambiguous answer \
+                (a name, a number, a place, one or two words). Not too easy."
+            }
+            GameKind::Words => {
+                "Pick one real, not too common noun and write its dictionary definition without using the word \
+                or its root. Players have to guess the word from the definition."
+            }
+        }
+    }
+}
+
+/// In-chat /game command a member can send when games are enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameCommand {
+    Start(GameKind),
+    /// Reveal the answer and close the round
+    Stop,
+    /// Best players of the chat
+    Leaderboard,
+}
+
+impl GameCommand {
+    /// "/game trivia", "/game@name словарь", "/game stop", "/game top"; a bare /game starts trivia
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let command = words.next()?.split('@').next()?.to_lowercase();
+        if command != "/game" && command != "/игра" {
+            return None;
+        }
+        match words.next().map(|w| w.to_lowercase()).as_deref() {
+            None => Some(GameCommand::Start(GameKind::Trivia)),
+            Some("stop" | "стоп") => Some(GameCommand::Stop),
+            Some("top" | "топ" | "leaderboard") => Some(GameCommand::Leaderboard),
+            Some(kind) => GameKind::parse(kind).map(GameCommand::Start),
+        }
+    }
+}
+
+/// Lowercase words without punctuation, "ё" read as "е"
+pub fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .replace('ё', "е")
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { ' ' })
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Whether a guess names one of the answers, alone or among other words ("это Париж")
+pub fn is_correct(answers: &[String], guess: &str) -> bool {
+    let guess = format!(" {} ", normalize(guess));
+    answers
+        .iter()
+        .map(|a| normalize(a))
+        .filter(|a| !a.is_empty())
+        .any(|answer| guess.contains(&format!(" {} ", answer)))
+}
+
+/// Answers a round accepts
+fn accepted_answers(round: &GameRound) -> Vec<String> {
+    let mut answers = vec![round.answer.clone()];
+    if let Some(alternatives) = round.alternatives.as_deref() {
+        answers.extend(serde_json::from_str::<Vec<String>>(alternatives).unwrap_or_default());
+    }
+    answers
+}
+
+/// A round as the model wrote it
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct GeneratedRound {
+    /// The host's one-line announcement, in the persona's voice
+    pub intro: String,
+    pub question: String,
+    pub answer: String,
+    pub alternatives: Vec<String>,
+}
+
+/// Parse the model's round, refusing ones without a question or answer and questions that give the answer away
+pub fn parse_round(json: &str) -> Option<GeneratedRound> {
+    check_round(serde_json::from_str(json).ok()?)
+}
+
+/// Trim a round, refusing ones without a question or answer and questions that give the answer away
+fn check_round(mut round: GeneratedRound) -> Option<GeneratedRound> {
+    round.question = round.question.trim().to_string();
+    round.answer = round.answer.trim().to_string();
+    round.alternatives.retain(|a| !a.trim().is_empty());
+    if round.question.is_empty() || normalize(&round.answer).is_empty() {
+        return None;
+    }
+    if is_correct(std::slice::from_ref(&round.answer), &round.question) {
+        return None;
+    }
+    Some(round)
+}
+
+async fn generate_round(state: &AppState, system_prompt: &str, kind: GameKind, recent: &[String]) -> Result<GeneratedRound> {
+    let prompt = format!(
+        r#"You host a game in a Telegram group chat. {}
+Write in the language of your instructions. Do not repeat these earlier questions:
+{}
+
+Return JSON: {{"intro": "<one short line announcing the round, in your style>", "question": "<the question or definition>", "answer": "<the answer>", "alternatives": ["<other spellings or forms of the answer that count>", ...]}}"#,
+        kind.task(),
+        if recent.is_empty() { "(none)".to_string() } else { recent.join("\n") }
+    );
+
+    let round: GeneratedRound = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![
+                OllamaMessage { role: "system".to_string(), content: system_prompt.to_string() },
+                OllamaMessage { role: "user".to_string(), content: prompt },
+            ],
+            Some(ChatOptions { temperature: Some(0.9), num_predict: None }),
+        )
+        .await
+        .context("Failed to generate game round")?;
+
+    check_round(round).context("Model returned an unusable game round")
+}
+
+async fn member_name(client: &Arc<Mutex<Client<TdJson>>>, user_id: i64) -> String {
+    match client.lock().await.get_user(&GetUser::builder().user_id(user_id).build()).await {
+        Ok(user) => user.first_name().to_string(),
+        Err(_) => format!("id{}", user_id),
+    }
+}
+
+async fn send(client: &Arc<Mutex<Client<TdJson>>>, chat_id: i64, text: &str, reply_to: Option<i64>) -> Result<i64> {
+    TdTransport::new(client.clone()).send_text(chat_id, text, FormatMode::Plain, reply_to).await
+}
+
+/// Ask a new question, unless one is still open
+async fn start_round(
+    state: &AppState,
+    account: &Account,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    kind: GameKind,
+    reply_to: i64,
+) -> Result<()> {
+    if let Some(open) = GameRepository::active(&state.db_pool, account.id, chat.chat_id).await? {
+        let text = format!("Сначала ответьте на этот вопрос:\n{}", open.question);
+        send(client, chat.chat_id, &text, open.message_id.or(Some(reply_to))).await?;
+        return Ok(());
+    }
+
+    let (system_prompt, _) = super::rotation::resolve_chat_persona(state, account, Some(chat)).await?;
+    let recent = GameRepository::recent_questions(&state.db_pool, account.id, chat.chat_id, RECENT_QUESTIONS).await?;
+    let generated = generate_round(state, &system_prompt, kind, &recent).await?;
+
+    let deadline = chrono::Utc::now() + chrono::Duration::seconds(state.config.game_round_secs);
+    let alternatives = if generated.alternatives.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(&generated.alternatives)?)
+    };
+    let round = GameRepository::start_round(
+        &state.db_pool,
+        account.id,
+        chat.chat_id,
+        kind.as_str(),
+        &generated.question,
+        &generated.answer,
+        alternatives.as_deref(),
+        deadline,
+    )
+    .await?;
+
+    let intro = match generated.intro.trim() {
+        "" => format!("🎲 {}", kind.title()),
+        intro => format!("🎲 {}: {}", kind.title(), intro),
+    };
+    let text = format!(
+        "{}\n\n{}\n\n⏱ {} сек. Ответ пишите в чат.",
+        intro, generated.question, state.config.game_round_secs
+    );
+    let message_id = send(client, chat.chat_id, &text, Some(reply_to)).await?;
+    GameRepository::set_message_id(&state.db_pool, round.id, message_id).await?;
+
+    tracing::info!("Started {} round {} in chat {} of account {}", kind.as_str(), round.id, chat.chat_id, account.id);
+    Ok(())
+}
+
+/// Close the open round without a winner and say what the answer was
+async fn reveal(state: &AppState, client: &Arc<Mutex<Client<TdJson>>>, round: &GameRound, why: &str) -> Result<()> {
+    if !GameRepository::finish(&state.db_pool, round.id, None).await? {
+        return Ok(());
+    }
+    let text = format!("{} Правильный ответ: {}", why, round.answer);
+    send(client, round.chat_id, &text, round.message_id).await?;
+    Ok(())
+}
+
+/// Answer /game in a chat with games enabled
+pub async fn answer_command(
+    state: &AppState,
+    account: &Account,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    command: GameCommand,
+    reply_to: i64,
+) -> Result<()> {
+    match command {
+        GameCommand::Start(kind) => start_round(state, account, client, chat, kind, reply_to).await,
+        GameCommand::Stop => {
+            match GameRepository::active(&state.db_pool, account.id, chat.chat_id).await? {
+                Some(round) => reveal(state, client, &round, "🛑 Раунд остановлен.").await?,
+                None => {
+                    send(client, chat.chat_id, "Сейчас нет вопроса. /game trivia или /game словарь", Some(reply_to)).await?;
+                }
+            }
+            Ok(())
+        }
+        GameCommand::Leaderboard => {
+            let top = GameRepository::leaderboard(&state.db_pool, account.id, chat.chat_id, LEADERBOARD_SIZE).await?;
+            let text = if top.is_empty() {
+                "🏆 Пока никто не набрал очков. /game trivia — начать".to_string()
+            } else {
+                let mut lines = vec!["🏆 Лучшие игроки:".to_string()];
+                for (i, entry) in top.iter().enumerate() {
+                    lines.push(format!("{}. {} — {}", i + 1, member_name(client, entry.user_id).await, entry.points));
+                }
+                lines.join("\n")
+            };
+            send(client, chat.chat_id, &text, Some(reply_to)).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Treat a message as a guess while a round is open. Returns whether a round is open: the persona
+/// doesn't chat over a running game, so such messages get no other reply.
+pub async fn check_guess(
+    state: &AppState,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    sender_id: i64,
+    message_id: i64,
+    text: &str,
+) -> Result<bool> {
+    let round = match GameRepository::active(&state.db_pool, chat.account_id, chat.chat_id).await? {
+        Some(round) => round,
+        None => return Ok(false),
+    };
+    if sender_id == 0 || !is_correct(&accepted_answers(&round), text) {
+        return Ok(true);
+    }
+
+    // Two right answers at once: only the first one closes the round
+    if !GameRepository::finish(&state.db_pool, round.id, Some(sender_id)).await? {
+        return Ok(true);
+    }
+    let points = GameRepository::add_point(&state.db_pool, chat.account_id, chat.chat_id, sender_id).await?;
+    let text = format!(
+        "✅ {} угадывает: {}! Очков: {}",
+        member_name(client, sender_id).await,
+        round.answer,
+        points
+    );
+    send(client, chat.chat_id, &text, Some(message_id)).await?;
+    Ok(true)
+}
+
+/// Reveal the answers of rounds nobody got in time
+pub async fn game_worker(state: AppState) {
+    tracing::info!("Game worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let expired = match GameRepository::expired(&state.db_pool).await {
+            Ok(expired) => expired,
+            Err(e) => {
+                tracing::error!("Failed to fetch expired game rounds: {}", e);
+                continue;
+            }
+        };
+
+        for round in expired {
+            let result = match state.get_userbot(round.account_id).await {
+                Some(handle) => reveal(&state, &handle.client, &round, "⏱ Время вышло!").await,
+                // Nobody to announce it; just close the round
+                None => GameRepository::finish(&state.db_pool, round.id, None).await.map(|_| ()),
+            };
+            if let Err(e) = result {
+                tracing::warn!("Failed to close game round {} in chat {}: {}", round.id, round.chat_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(GameCommand::parse("/game"), Some(GameCommand::Start(GameKind::Trivia)));
+        assert_eq!(GameCommand::parse("/game@somebot словарь"), Some(GameCommand::Start(GameKind::Words)));
+        assert_eq!(GameCommand::parse("/game Stop"), Some(GameCommand::Stop));
+        assert_eq!(GameCommand::parse("/игра топ"), Some(GameCommand::Leaderboard));
+        assert_eq!(GameCommand::parse("/game chess"), None);
+        assert_eq!(GameCommand::parse("game trivia"), None);
+    }
+
+    #[test]
+    fn checks_guesses() {
+        let answers = vec!["Париж".to_string(), "Paris".to_string()];
+        assert!(is_correct(&answers, "париж"));
+        assert!(is_correct(&answers, "Это Париж!"));
+        assert!(is_correct(&answers, "paris?"));
+        assert!(!is_correct(&answers, "Парижский"));
+        assert!(is_correct(&["Ёлка".to_string()], "елка"));
+        assert!(!is_correct(&["".to_string()], "что угодно"));
+    }
+
+    #[test]
+    fn rejects_rounds_that_give_the_answer_away() {
+        let round = parse_round(r#"{"question": "Столица Франции?", "answer": " Париж ", "alternatives": ["Paris", ""]}"#).unwrap();
+        assert_eq!(round.answer, "Париж");
+        assert_eq!(round.alternatives, vec!["Paris"]);
+
+        assert!(parse_round(r#"{"question": "Кошка — домашнее животное", "answer": "кошка"}"#).is_none());
+        assert!(parse_round(r#"{"question": "", "answer": "x"}"#).is_none());
+        assert!(parse_round("not json").is_none());
+    }
+}
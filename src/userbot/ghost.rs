@@ -0,0 +1,187 @@
+use crate::{
+    db::{CorrectionRepository, MessageRepository},
+    state::AppState,
+};
+use anyhow::Result;
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Command the owner types from the userbot account itself, replying to one of its messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GhostCommand {
+    /// "!edit <text>": replace the replied-to message with the text
+    Edit(String),
+    /// "!del": delete the replied-to message
+    Delete,
+    /// "!save": keep the replied-to exchange as a few-shot example of the persona
+    Save,
+}
+
+impl GhostCommand {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim_start();
+        let (command, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        match command.to_lowercase().as_str() {
+            "!edit" if !rest.trim().is_empty() => Some(GhostCommand::Edit(rest.trim().to_string())),
+            "!del" | "!delete" if rest.trim().is_empty() => Some(GhostCommand::Delete),
+            "!save" if rest.trim().is_empty() => Some(GhostCommand::Save),
+            _ => None,
+        }
+    }
+}
+
+fn text_of(message: &Message) -> Option<String> {
+    match message.content() {
+        MessageContent::MessageText(text) => Some(text.text().text().to_string()),
+        _ => None,
+    }
+}
+
+/// Handle a message sent from the account itself; returns whether it was a ghost command.
+///
+/// The command message is deleted, the replied-to message edited or deleted, and the
+/// correction stored as a preference pair (original → corrected, or → nothing).
+/// Edited and saved exchanges also become few-shot examples of the persona that replied.
+pub async fn handle_outgoing(
+    state: &AppState,
+    account_id: i64,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    message: &Message,
+) -> Result<bool> {
+    if state.config.safe_mode {
+        return Ok(false);
+    }
+    let command = match text_of(message).as_deref().and_then(GhostCommand::parse) {
+        Some(command) => command,
+        None => return Ok(false),
+    };
+
+    let chat_id = message.chat_id();
+    let target_id = message.reply_to_message_id();
+
+    let client = client.lock().await;
+    client
+        .delete_messages(
+            &DeleteMessages::builder()
+                .chat_id(chat_id)
+                .message_ids(vec![message.id()])
+                .revoke(true)
+                .build(),
+        )
+        .await?;
+
+    if target_id == 0 {
+        return Ok(true);
+    }
+
+    let target = client
+        .get_message(&GetMessage::builder().chat_id(chat_id).message_id(target_id).build())
+        .await?;
+    let original = match text_of(&target) {
+        Some(text) if target.is_outgoing() => text,
+        _ => return Ok(true),
+    };
+
+    // What the reply answered, when it was sent as a reply
+    let prompt = match target.reply_to_message_id() {
+        0 => None,
+        id => client
+            .get_message(&GetMessage::builder().chat_id(chat_id).message_id(id).build())
+            .await
+            .ok()
+            .and_then(|m| text_of(&m)),
+    };
+
+    let stored = MessageRepository::find_reply(&state.db_pool, account_id, chat_id, &original).await?;
+    let persona_id = stored.as_ref().and_then(|m| m.persona_id);
+
+    let corrected = match &command {
+        GhostCommand::Edit(text) => {
+            client
+                .edit_message_text(
+                    &EditMessageText::builder()
+                        .chat_id(chat_id)
+                        .message_id(target_id)
+                        .input_message_content(InputMessageContent::InputMessageText(
+                            InputMessageText::builder()
+                                .text(FormattedText::builder().text(text.clone()).build())
+                                .build(),
+                        ))
+                        .build(),
+                )
+                .await?;
+            Some(text.as_str())
+        }
+        GhostCommand::Delete => {
+            client
+                .delete_messages(
+                    &DeleteMessages::builder()
+                        .chat_id(chat_id)
+                        .message_ids(vec![target_id])
+                        .revoke(true)
+                        .build(),
+                )
+                .await?;
+            None
+        }
+        GhostCommand::Save => {
+            drop(client);
+            save_example(state, persona_id, prompt.as_deref(), &original).await?;
+            return Ok(true);
+        }
+    };
+    drop(client);
+
+    if let Some(stored) = &stored {
+        MessageRepository::replace_in_reply(&state.db_pool, stored.id, &original, corrected).await?;
+    }
+    CorrectionRepository::record(&state.db_pool, account_id, chat_id, target_id, prompt.as_deref(), &original, corrected)
+        .await?;
+    if let Some(corrected) = corrected {
+        save_example(state, persona_id, prompt.as_deref(), corrected).await?;
+    }
+
+    tracing::info!(
+        "Owner {} reply {} in chat {} of account {}",
+        if corrected.is_some() { "edited" } else { "deleted" },
+        target_id,
+        chat_id,
+        account_id
+    );
+    Ok(true)
+}
+
+/// Keep an exchange as a few-shot example, if both sides are known
+async fn save_example(state: &AppState, persona_id: Option<i64>, prompt: Option<&str>, reply: &str) -> Result<()> {
+    match (persona_id, prompt) {
+        (Some(persona_id), Some(prompt)) => {
+            let persona = crate::db::PersonaRepository::get_by_id(&state.db_pool, persona_id).await?;
+            if !crate::ai::memory_policy::MemoryWritePolicy::of(persona.as_ref()).stores_examples() {
+                tracing::debug!("Not saving example: persona {} doesn't write memory", persona_id);
+                return Ok(());
+            }
+            crate::ai::examples::add_example(state, persona_id, prompt, reply, "ghost").await?;
+        }
+        _ => tracing::debug!("Not saving example: persona or replied-to message unknown"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(GhostCommand::parse("!edit ну да"), Some(GhostCommand::Edit("ну да".to_string())));
+        assert_eq!(GhostCommand::parse("!DEL"), Some(GhostCommand::Delete));
+        assert_eq!(GhostCommand::parse("!save"), Some(GhostCommand::Save));
+        assert_eq!(GhostCommand::parse("!edit"), None);
+        assert_eq!(GhostCommand::parse("!del this"), None);
+        assert_eq!(GhostCommand::parse("edit me"), None);
+    }
+}
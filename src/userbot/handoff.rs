@@ -0,0 +1,93 @@
+use crate::{
+    db::{Handoff, HandoffRepository, MessageRepository},
+    AppState,
+};
+use anyhow::Result;
+
+/// Reply a persona can give instead of an answer to hand the conversation to a human,
+/// when its prompt tells it which questions it must not answer
+pub const HANDOFF_MARKER: &str = "<HANDOFF>";
+
+/// Messages of recent context sent along with a handoff notice
+const CONTEXT_MESSAGES: i64 = 8;
+/// Characters of each context message shown
+const CONTEXT_MESSAGE_CHARS: usize = 300;
+
+/// Whether a generated reply asks for a human
+pub fn is_handoff_reply(reply: &str) -> bool {
+    reply.trim() == HANDOFF_MARKER
+}
+
+/// Stop auto-replies to a user (or the whole chat) for HANDOFF_MINUTES or the given minutes
+pub async fn start(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    user_id: Option<i64>,
+    reason: &str,
+    minutes: Option<i64>,
+) -> Result<Handoff> {
+    let minutes = minutes.unwrap_or(state.config.handoff_minutes);
+    let expires_at = chrono::Utc::now() + chrono::Duration::minutes(minutes);
+    HandoffRepository::create(&state.db_pool, account_id, chat_id, user_id, reason, expires_at).await
+}
+
+/// Plain-text notice for the owners: who, why, until when, the last messages and a link to the
+/// message that started it (or to the private chat)
+pub async fn notice(state: &AppState, handoff: &Handoff, message_id: Option<i64>) -> Result<String> {
+    let who = match handoff.user_id {
+        Some(user_id) => crate::logging::user_ref(user_id),
+        None => "everyone".to_string(),
+    };
+    let mut text = format!(
+        "🙋 Handoff #{}: account {} stopped replying to {} in chat {} until {} UTC\nReason: {}\n",
+        handoff.id,
+        handoff.account_id,
+        who,
+        handoff.chat_id,
+        handoff.expires_at.format("%Y-%m-%d %H:%M"),
+        handoff.reason
+    );
+
+    let recent = MessageRepository::get_recent_messages(&state.db_pool, handoff.account_id, handoff.chat_id, CONTEXT_MESSAGES)
+        .await?;
+    if !recent.is_empty() {
+        text.push_str("\nRecent messages:\n");
+        for message in &recent {
+            let author = match (message.role.as_str(), message.sender_id) {
+                ("assistant", _) => "bot".to_string(),
+                (_, Some(sender_id)) => crate::logging::user_ref(sender_id),
+                _ => "user".to_string(),
+            };
+            let body: String = message.content.chars().take(CONTEXT_MESSAGE_CHARS).collect();
+            text.push_str(&format!("• {}: {}\n", author, body));
+        }
+    }
+
+    let link = match message_id {
+        Some(message_id) => super::trace::message_link(handoff.chat_id, message_id, handoff.user_id.unwrap_or(0)),
+        None if handoff.chat_id > 0 => super::trace::message_link(handoff.chat_id, 0, handoff.chat_id),
+        None => None,
+    };
+    if let Some(link) = link {
+        text.push_str(&format!("\nOpen: {}\n", link));
+    }
+    text.push_str(&format!(
+        "\nHand it back early with /resume {} {}{}",
+        handoff.account_id,
+        handoff.chat_id,
+        handoff.user_id.map(|u| format!(" {}", u)).unwrap_or_default()
+    ));
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spots_the_handoff_marker() {
+        assert!(is_handoff_reply(" <HANDOFF>\n"));
+        assert!(!is_handoff_reply("напиши владельцу <HANDOFF>"));
+    }
+}
@@ -0,0 +1,181 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{AccountChat, AccountRepository, ChatRepository, MessageRepository, MessageRole, NewMessage, PersonaRepository},
+    state::AppState,
+};
+use anyhow::Result;
+use chrono::Timelike;
+
+/// How often chats are checked for silence
+const CHECK_INTERVAL_SECS: u64 = 300;
+
+const STARTER_INSTRUCTIONS: &str = r#"[ИНИЦИАТИВА]
+В чате давно тихо. Напиши одно короткое сообщение, чтобы оживить разговор: вернись к теме из недавней переписки ниже, спроси что-нибудь или поделись мыслью — в своем обычном стиле.
+Не упоминай, что в чате было тихо. Если сказать нечего, верни ровно `<IGNORE>`."#;
+
+/// Periodically post conversation starters in silent chats that opted in
+pub async fn initiative_worker(state: AppState) {
+    tracing::info!("Initiative worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        if state.is_paused() {
+            continue;
+        }
+
+        for account_id in state.list_active_userbot_ids().await {
+            let chats = match ChatRepository::list_initiative_chats(&state.db_pool, account_id).await {
+                Ok(chats) => chats,
+                Err(e) => {
+                    tracing::error!("Failed to list initiative chats for account {}: {}", account_id, e);
+                    continue;
+                }
+            };
+
+            for chat in chats {
+                if let Err(e) = maybe_start_conversation(&state, &chat).await {
+                    tracing::warn!(
+                        "Conversation starter failed for account {} in chat {}: {}",
+                        account_id,
+                        chat.chat_id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Post a starter if the chat has been silent long enough and the daily cap allows it
+async fn maybe_start_conversation(state: &AppState, chat: &AccountChat) -> Result<()> {
+    // Active hours and the daily cap follow the chat's own clock
+    let local_now = super::timezone::chat_now(Some(chat), state.config.default_timezone);
+    let (start, end) = state.config.initiative_active_hours;
+    if local_now.hour() < start || local_now.hour() >= end {
+        return Ok(());
+    }
+    if super::quiet_hours::of(Some(chat)).is_some_and(|quiet| quiet.contains_time(&local_now)) {
+        return Ok(());
+    }
+
+    let today = local_now.format("%Y-%m-%d").to_string();
+    let sent_today = if chat.initiative_day.as_deref() == Some(today.as_str()) {
+        chat.initiative_count
+    } else {
+        0
+    };
+
+    if sent_today >= state.config.initiative_max_per_day {
+        return Ok(());
+    }
+
+    let silence = chrono::Duration::minutes(state.config.initiative_silence_minutes);
+    let now = chrono::Utc::now();
+
+    if chat.last_initiative_at.is_some_and(|at| now - at < silence) {
+        return Ok(());
+    }
+
+    // Only chats with some history: there has to be something to come back to
+    let last_message = match MessageRepository::get_last_message(&state.db_pool, chat.account_id, chat.chat_id).await? {
+        Some(m) => m,
+        None => return Ok(()),
+    };
+
+    if now - last_message.created_at < silence {
+        return Ok(());
+    }
+
+    // Never talk into the void twice in a row
+    if last_message.role == MessageRole::Assistant.as_str() {
+        return Ok(());
+    }
+
+    let account = match AccountRepository::get_by_id(&state.db_pool, chat.account_id).await? {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+
+    if !account.is_chat_allowed(chat.chat_id) {
+        return Ok(());
+    }
+
+    let (system_prompt, persona_id) = match chat.active_persona_id {
+        Some(id) => match crate::ai::persona_inheritance::resolve(&state.db_pool, id).await? {
+            Some(persona) => (persona.prompt, Some(persona.id)),
+            None => (PersonaRepository::effective_prompt(&state.db_pool, account.id).await?, account.persona_id),
+        },
+        None => (PersonaRepository::effective_prompt(&state.db_pool, account.id).await?, account.persona_id),
+    };
+    let time_variants = match persona_id {
+        Some(id) => crate::ai::persona_inheritance::resolve(&state.db_pool, id).await?.and_then(|p| p.time_variants),
+        None => None,
+    };
+
+    let system_prompt = super::timezone::with_local_time(system_prompt, local_now);
+    let system_prompt = crate::ai::time_variants::apply(system_prompt, time_variants.as_deref(), local_now.hour());
+    let starter = generate_starter(state, chat.account_id, chat.chat_id, system_prompt).await?;
+    let starter = starter.trim();
+    if starter.is_empty() || starter == "<IGNORE>" {
+        return Ok(());
+    }
+
+    let handle = match state.get_userbot(chat.account_id).await {
+        Some(h) => h,
+        None => return Ok(()),
+    };
+
+    let transport = super::transport::TdTransport::new(handle.client.clone());
+    super::transport::send_split(&transport, state, chat.chat_id, Some(chat), starter).await?;
+
+    ChatRepository::record_initiative(&state.db_pool, chat.account_id, chat.chat_id, &today).await?;
+    MessageRepository::create(
+        &state.db_pool,
+        NewMessage {
+            account_id: chat.account_id,
+            chat_id: chat.chat_id,
+            role: MessageRole::Assistant,
+            content: starter.to_string(),
+            sender_id: None,
+            sender_chat_id: None,
+            persona_id,
+        },
+    )
+    .await?;
+
+    tracing::info!("Userbot {} started a conversation in chat {}", chat.account_id, chat.chat_id);
+    Ok(())
+}
+
+async fn generate_starter(state: &AppState, account_id: i64, chat_id: i64, system_prompt: String) -> Result<String> {
+    let history = MessageRepository::get_recent_messages(&state.db_pool, account_id, chat_id, 15).await?;
+
+    let mut messages = vec![
+        OllamaMessage {
+            role: "system".to_string(),
+            content: system_prompt,
+        },
+        OllamaMessage {
+            role: "system".to_string(),
+            content: STARTER_INSTRUCTIONS.to_string(),
+        },
+    ];
+
+    for msg in history {
+        messages.push(OllamaMessage {
+            role: msg.role,
+            content: msg.content,
+        });
+    }
+
+    let client = OllamaClient::new(state.config.ollama_url.clone());
+    client
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages,
+            stream: true,
+            options: None,
+        })
+        .await
+}
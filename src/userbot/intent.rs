@@ -0,0 +1,128 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaChatRequest, OllamaClient, OllamaMessage},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+
+/// Longest message the heuristics judge on their own; longer ones always say something
+const MAX_TRIVIAL_CHARS: usize = 12;
+
+/// Words that only acknowledge what was said
+const ACKNOWLEDGMENTS: &[&str] = &[
+    "ок", "окей", "оке", "ага", "угу", "да", "неа", "нет", "понял", "поняла", "ясно", "ясн", "пон", "норм",
+    "спс", "спасибо", "пасиб", "лол", "кек", "хах", "ахах", "ахаха", "хд", "ok", "okay", "k", "kk", "yes", "no",
+    "yep", "nope", "thx", "thanks", "lol", "lmao", "+", "++", "+1", "-", ")", "))", ")))", "(", "((",
+];
+
+/// What a message that nobody addressed to the account calls for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Intent {
+    /// Worth a real reply
+    Reply,
+    /// Only seen: a reaction at most, no words
+    React,
+    /// Nothing at all
+    Ignore,
+}
+
+impl Intent {
+    fn parse(answer: &str) -> Option<Self> {
+        let word = answer.trim().trim_matches(|c: char| !c.is_alphabetic()).to_lowercase();
+        match word.split_whitespace().next()? {
+            "reply" => Some(Intent::Reply),
+            "react" => Some(Intent::React),
+            "ignore" => Some(Intent::Ignore),
+            _ => None,
+        }
+    }
+}
+
+/// Settle the obvious cases without a model: emoji get a reaction, bare acknowledgments nothing
+pub fn heuristic(text: &str) -> Option<Intent> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Some(Intent::Ignore);
+    }
+    if text.contains('?') || text.chars().count() > MAX_TRIVIAL_CHARS {
+        return None;
+    }
+    // "))", "+" and "+1" acknowledge as written, before punctuation is trimmed off
+    let compact = text.replace(char::is_whitespace, "").to_lowercase();
+    if ACKNOWLEDGMENTS.contains(&compact.as_str()) {
+        return Some(Intent::Ignore);
+    }
+    if !text.chars().any(char::is_alphanumeric) {
+        // A row of emoji is worth a reaction
+        return Some(Intent::React);
+    }
+    let normalized = text.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    ACKNOWLEDGMENTS.contains(&normalized.as_str()).then_some(Intent::Ignore)
+}
+
+/// Whether a message needs the full reply pipeline. A failing check answers:
+/// the filter only saves work and should never cost a reply.
+pub async fn classify(state: &AppState, text: &str) -> Intent {
+    if let Some(intent) = heuristic(text) {
+        return intent;
+    }
+    let model = match &state.config.intent_model {
+        Some(model) => model,
+        None => return Intent::Reply,
+    };
+    match ask_model(state, model, text).await {
+        Ok(intent) => intent,
+        Err(e) => {
+            tracing::debug!("Intent check failed, answering: {}", e);
+            Intent::Reply
+        }
+    }
+}
+
+async fn ask_model(state: &AppState, model: &str, text: &str) -> Result<Intent> {
+    let prompt = format!(
+        "A message from a group chat. Does it call for a written reply from a chat member (reply), \
+         only an emoji reaction (react) or nothing (ignore)? Answer with one word: reply, react or ignore.\n\n\
+         Message: \"{}\"",
+        text
+    );
+    let answer = OllamaClient::new(&state.config.ollama_url)
+        .chat(OllamaChatRequest {
+            model: model.to_string(),
+            messages: vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            stream: false,
+            options: Some(ChatOptions { temperature: Some(0.0), num_predict: Some(5) }),
+        })
+        .await
+        .context("Failed to classify message intent")?;
+
+    Intent::parse(&answer).with_context(|| format!("Unexpected intent answer: {}", answer.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settles_trivial_messages() {
+        assert_eq!(heuristic("ок"), Some(Intent::Ignore));
+        assert_eq!(heuristic("Ага!"), Some(Intent::Ignore));
+        assert_eq!(heuristic(")))"), Some(Intent::Ignore));
+        assert_eq!(heuristic("+"), Some(Intent::Ignore));
+        assert_eq!(heuristic("+1"), Some(Intent::Ignore));
+        assert_eq!(heuristic("1"), None);
+        assert_eq!(heuristic("😂😂"), Some(Intent::React));
+        assert_eq!(heuristic("🔥 🔥"), Some(Intent::React));
+        assert_eq!(heuristic("ок?"), None);
+        assert_eq!(heuristic("кто идет завтра в кино"), None);
+        assert_eq!(heuristic("привет"), None);
+    }
+
+    #[test]
+    fn reads_the_models_word() {
+        assert_eq!(Intent::parse("reply"), Some(Intent::Reply));
+        assert_eq!(Intent::parse(" Ignore.\n"), Some(Intent::Ignore));
+        assert_eq!(Intent::parse("\"react\""), Some(Intent::React));
+        assert_eq!(Intent::parse("maybe"), None);
+        assert_eq!(Intent::parse(""), None);
+    }
+}
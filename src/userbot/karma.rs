@@ -0,0 +1,246 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{Account, AccountChat, KarmaRepository},
+    state::AppState,
+};
+use anyhow::Result;
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Members shown by /leaderboard
+const LEADERBOARD_SIZE: i64 = 10;
+
+/// Window of CHAT_COMMAND_LIMIT
+const COMMAND_WINDOW_SECS: i64 = 600;
+
+lazy_static::lazy_static! {
+    /// When each (chat, member) last sent in-chat commands
+    static ref COMMAND_TIMESTAMPS: std::sync::Mutex<std::collections::HashMap<(i64, i64), Vec<i64>>> =
+        std::sync::Mutex::new(std::collections::HashMap::new());
+
+    static ref THANKS: regex::Regex = regex::Regex::new(
+        r"(?i)^\s*(\+(1|реп|rep)?(\s|$)|(спасибо|спасиб|спс|сяп|благодарю|пасиб\w*|мерси|thanks?|thank you|thx|ty)\b)"
+    ).expect("invalid thanks pattern");
+}
+
+/// In-chat command a member can send when karma is enabled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KarmaCommand {
+    /// The sender's own karma
+    Karma,
+    /// Top members of the chat
+    Leaderboard,
+}
+
+impl KarmaCommand {
+    /// "/karma", "/karma@name", "/leaderboard" and "/top"
+    pub fn parse(text: &str) -> Option<Self> {
+        let command = text.split_whitespace().next()?.split('@').next()?.to_lowercase();
+        match command.as_str() {
+            "/karma" | "/карма" => Some(KarmaCommand::Karma),
+            "/leaderboard" | "/top" | "/топ" => Some(KarmaCommand::Leaderboard),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a member may send another in-chat command, counting it if so
+pub fn allow_command(chat_id: i64, user_id: i64, limit: usize) -> bool {
+    if limit == 0 {
+        return true;
+    }
+    let now = chrono::Utc::now().timestamp();
+    let mut timestamps = COMMAND_TIMESTAMPS.lock().unwrap_or_else(|e| e.into_inner());
+    // Members whose commands all left the window are forgotten, so the map doesn't grow forever
+    timestamps.retain(|_, sent| {
+        sent.retain(|&ts| now - ts < COMMAND_WINDOW_SECS);
+        !sent.is_empty()
+    });
+    let sent = timestamps.entry((chat_id, user_id)).or_default();
+    within_limit(sent, now, limit)
+}
+
+/// Drop timestamps outside the window and record `now` if fewer than `limit` remain
+fn within_limit(sent: &mut Vec<i64>, now: i64, limit: usize) -> bool {
+    sent.retain(|&ts| now - ts < COMMAND_WINDOW_SECS);
+    if sent.len() >= limit {
+        return false;
+    }
+    sent.push(now);
+    true
+}
+
+/// Whether a message reads as thanks ("спасибо", "+1", "thx", ...)
+pub fn is_thanks(text: &str) -> bool {
+    THANKS.is_match(text)
+}
+
+/// Start a new karma period if the chat resets monthly and the month changed
+pub async fn ensure_period(state: &AppState, chat: &AccountChat) -> Result<()> {
+    if !chat.karma_monthly_reset {
+        return Ok(());
+    }
+
+    let month = chrono::Utc::now()
+        .with_timezone(&super::timezone::chat_timezone(Some(chat), state.config.default_timezone))
+        .format("%Y-%m")
+        .to_string();
+
+    if chat.karma_period.as_deref() != Some(month.as_str()) {
+        KarmaRepository::reset(&state.db_pool, chat.account_id, chat.chat_id, &month).await?;
+    }
+    Ok(())
+}
+
+/// Give a point to the author of the message a "thanks" replies to
+pub async fn award_thanks(
+    state: &AppState,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    message: &Message,
+    sender_id: i64,
+) -> Result<()> {
+    let reply_to = message.reply_to_message_id();
+    if reply_to == 0 || sender_id == 0 {
+        return Ok(());
+    }
+
+    let original = client
+        .lock()
+        .await
+        .get_message(&GetMessage::builder().chat_id(chat.chat_id).message_id(reply_to).build())
+        .await?;
+
+    let author_id = match original.sender_id() {
+        MessageSender::User(user) => user.user_id(),
+        _ => return Ok(()),
+    };
+
+    // No thanking yourself
+    if author_id == sender_id {
+        return Ok(());
+    }
+
+    ensure_period(state, chat).await?;
+
+    // Each member thanks a message once, however many "+1" they reply with
+    if !KarmaRepository::record_thanks(&state.db_pool, chat.account_id, chat.chat_id, sender_id, reply_to).await? {
+        return Ok(());
+    }
+    let total = KarmaRepository::add(&state.db_pool, chat.account_id, chat.chat_id, author_id, 1).await?;
+    tracing::debug!("Karma +1 for user {} in chat {} (now {})", crate::logging::user_ref(author_id), chat.chat_id, total);
+    Ok(())
+}
+
+/// Answer /karma or /leaderboard in the persona's voice
+pub async fn answer_command(
+    state: &AppState,
+    account: &Account,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    command: KarmaCommand,
+    sender_id: i64,
+    reply_to: i64,
+) -> Result<()> {
+    ensure_period(state, chat).await?;
+
+    let (facts, fallback) = match command {
+        KarmaCommand::Karma => {
+            let points = KarmaRepository::get(&state.db_pool, chat.account_id, chat.chat_id, sender_id).await?;
+            (
+                format!("Собеседник спросил свою карму в этом чате. У него {} очков.", points),
+                format!("карма: {}", points),
+            )
+        }
+        KarmaCommand::Leaderboard => {
+            let top = KarmaRepository::leaderboard(&state.db_pool, chat.account_id, chat.chat_id, LEADERBOARD_SIZE).await?;
+            if top.is_empty() {
+                ("Собеседник спросил таблицу кармы, но у всех пока ноль.".to_string(), "пока у всех ноль".to_string())
+            } else {
+                let mut lines = Vec::new();
+                let client_lock = client.lock().await;
+                for (i, entry) in top.iter().enumerate() {
+                    let name = match client_lock.get_user(&GetUser::builder().user_id(entry.user_id).build()).await {
+                        Ok(user) => user.first_name().to_string(),
+                        Err(_) => format!("id{}", entry.user_id),
+                    };
+                    lines.push(format!("{}. {} — {}", i + 1, name, entry.points));
+                }
+                let table = lines.join("\n");
+                (format!("Собеседник спросил таблицу кармы чата:\n{}", table), table)
+            }
+        }
+    };
+
+    let (system_prompt, _) = super::rotation::resolve_chat_persona(state, account, Some(chat)).await?;
+    let instructions = "[КАРМА]\nОтветь на запрос кармы одним коротким сообщением в своем стиле. \
+        Все цифры и имена из данных должны быть в ответе без изменений, таблицу сохрани построчно.";
+
+    let text = OllamaClient::new(state.config.ollama_url.clone())
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages: vec![
+                OllamaMessage { role: "system".to_string(), content: system_prompt },
+                OllamaMessage { role: "system".to_string(), content: instructions.to_string() },
+                OllamaMessage { role: "user".to_string(), content: facts },
+            ],
+            stream: true,
+            options: None,
+        })
+        .await
+        .map(|t| t.trim().to_string())
+        .ok()
+        .filter(|t| !t.is_empty() && t != "<IGNORE>")
+        .unwrap_or(fallback);
+
+    let send_message = SendMessage::builder()
+        .chat_id(chat.chat_id)
+        .reply_to_message_id(reply_to)
+        .input_message_content(InputMessageContent::InputMessageText(
+            InputMessageText::builder()
+                .text(FormattedText::builder().text(text).build())
+                .build(),
+        ))
+        .build();
+    client.lock().await.send_message(&send_message).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_thanks() {
+        assert!(is_thanks("спасибо большое"));
+        assert!(is_thanks("Спс"));
+        assert!(is_thanks("+1"));
+        assert!(is_thanks("+"));
+        assert!(is_thanks("thanks!"));
+        assert!(!is_thanks("tyrant"));
+        assert!(!is_thanks("а спасибо где"));
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(KarmaCommand::parse("/karma"), Some(KarmaCommand::Karma));
+        assert_eq!(KarmaCommand::parse("/leaderboard@somebot"), Some(KarmaCommand::Leaderboard));
+        assert_eq!(KarmaCommand::parse("/start"), None);
+        assert_eq!(KarmaCommand::parse("karma"), None);
+    }
+
+    #[test]
+    fn limits_commands_per_window() {
+        let mut sent = Vec::new();
+        assert!(within_limit(&mut sent, 0, 2));
+        assert!(within_limit(&mut sent, 10, 2));
+        assert!(!within_limit(&mut sent, 20, 2));
+        assert!(within_limit(&mut sent, COMMAND_WINDOW_SECS + 5, 2));
+        assert_eq!(sent, vec![10, COMMAND_WINDOW_SECS + 5]);
+    }
+}
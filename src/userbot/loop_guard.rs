@@ -0,0 +1,130 @@
+use super::transport::ChatTransport;
+use crate::{db::ChatRepository, state::AppState};
+use anyhow::Result;
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+/// Replies within this window are considered for loop detection
+const LOOP_WINDOW_SECS: i64 = 120;
+/// Replies in the window that make a loop suspicious
+const LOOP_REPLIES: usize = 8;
+/// A loop is a back-and-forth with at most this many other participants
+const LOOP_MAX_PARTNERS: usize = 2;
+
+lazy_static::lazy_static! {
+    static ref REPLY_LOGS: Mutex<HashMap<(i64, i64), ReplyLog>> = Mutex::new(HashMap::new());
+}
+
+/// Recent replies in one chat: when we answered and to whom
+#[derive(Debug, Default)]
+pub struct ReplyLog {
+    replies: VecDeque<(i64, i64)>,
+}
+
+impl ReplyLog {
+    /// Record a reply to `sender_id` at `now` (unix seconds).
+    /// Returns true when the recent replies look like a conversation loop:
+    /// many rapid answers exchanged with one or two participants.
+    pub fn record(&mut self, now: i64, sender_id: i64) -> bool {
+        self.replies.push_back((now, sender_id));
+        while self.replies.front().is_some_and(|(at, _)| now - at > LOOP_WINDOW_SECS) {
+            self.replies.pop_front();
+        }
+
+        if self.replies.len() < LOOP_REPLIES {
+            return false;
+        }
+
+        let mut partners: Vec<i64> = self.replies.iter().map(|(_, sender)| *sender).collect();
+        partners.sort_unstable();
+        partners.dedup();
+        partners.len() <= LOOP_MAX_PARTNERS
+    }
+}
+
+/// Whether a user is a Telegram bot
+pub async fn is_bot(client: &Arc<tokio::sync::Mutex<Client<TdJson>>>, user_id: i64) -> Result<bool> {
+    let user = client
+        .lock()
+        .await
+        .get_user(&GetUser::builder().user_id(user_id).build())
+        .await?;
+    Ok(matches!(user.type_(), UserType::Bot(_)))
+}
+
+/// Whether auto-replies to this bot are allowed (BOT_REPLY_ALLOWLIST)
+pub fn is_allowed_bot(state: &AppState, user_id: i64) -> bool {
+    state.config.bot_reply_allowlist.contains(&user_id)
+}
+
+/// Log a reply in a group and pause the chat with an owner alert if it looks like a loop
+pub async fn record_reply<T: ChatTransport>(
+    state: &AppState,
+    transport: &T,
+    account_id: i64,
+    chat_id: i64,
+    sender_id: i64,
+) -> Result<()> {
+    // Private chats are one-on-one by nature
+    if chat_id > 0 {
+        return Ok(());
+    }
+
+    let suspected = {
+        let mut logs = REPLY_LOGS.lock().unwrap_or_else(|e| e.into_inner());
+        let log = logs.entry((account_id, chat_id)).or_default();
+        let suspected = log.record(chrono::Utc::now().timestamp(), sender_id);
+        if suspected {
+            logs.remove(&(account_id, chat_id));
+        }
+        suspected
+    };
+
+    if !suspected {
+        return Ok(());
+    }
+
+    let minutes = state.config.loop_pause_minutes;
+    let until = chrono::Utc::now() + chrono::Duration::minutes(minutes);
+    ChatRepository::pause(&state.db_pool, account_id, chat_id, until, "conversation loop suspected").await?;
+
+    tracing::warn!("Conversation loop suspected in chat {} of account {}, paused", chat_id, account_id);
+    transport
+        .notify_owner(
+            state,
+            &format!(
+                "🔁 Userbot {} replied {}+ times in 2 minutes in chat {} with the same participants. \
+                Looks like a bot loop, replies there are paused for {} min. /resume_chat {} {} to lift it.",
+                account_id, LOOP_REPLIES, chat_id, minutes, account_id, chat_id
+            ),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_rapid_back_and_forth() {
+        let mut log = ReplyLog::default();
+        let suspected: Vec<bool> = (0..LOOP_REPLIES as i64).map(|i| log.record(1000 + i * 5, 42)).collect();
+        assert!(!suspected[..LOOP_REPLIES - 1].iter().any(|s| *s));
+        assert!(suspected[LOOP_REPLIES - 1]);
+    }
+
+    #[test]
+    fn ignores_busy_groups_and_slow_chats() {
+        let mut log = ReplyLog::default();
+        assert!(!(0..20).any(|i| log.record(1000 + i, i)));
+
+        let mut log = ReplyLog::default();
+        assert!(!(0..20).any(|i| log.record(1000 + i * 60, 42)));
+    }
+}
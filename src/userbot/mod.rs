@@ -1,5 +1,45 @@
 pub mod worker;
 pub mod spam;
+pub mod chats;
+pub mod formatting;
+pub mod initiative;
+pub mod rotation;
+pub mod digest;
+pub mod timezone;
+pub mod karma;
+pub mod transport;
+pub mod simulate;
+pub mod bench;
+pub mod loop_guard;
+pub mod profiles;
+pub mod ghost;
+pub mod onboarding;
+pub mod ephemeral;
+pub mod quotas;
+pub mod feeds;
+pub mod debounce;
+pub mod catchup;
+pub mod tuning;
+pub mod quiet_hours;
+pub mod handoff;
+pub mod persona_switch;
+pub mod rules;
+pub mod games;
+pub mod bridge;
+pub mod escalation;
+pub mod pin;
+pub mod privacy;
+pub mod stickers;
+pub mod intent;
 
 pub use worker::{spawn_userbot, DEFAULT_SYSTEM_PROMPT};
 pub use spam::{execute_spam_campaign, spam_campaign_worker};
+pub use chats::discover_chats;
+pub use initiative::initiative_worker;
+pub use rotation::persona_rotation_worker;
+pub use digest::digest_worker;
+pub use ephemeral::ephemeral_worker;
+pub use feeds::feed_worker;
+pub use games::game_worker;
+pub use stickers::sticker_worker;
+pub mod trace;
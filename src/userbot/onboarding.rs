@@ -0,0 +1,342 @@
+use crate::{
+    ai::ollama::{OllamaChatRequest, OllamaClient, OllamaMessage},
+    db::{models::Account, ChatRepository, MessageRepository, MessageRole, NewMessage, PersonaRepository},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::{collections::HashMap, sync::Arc};
+use tokio::sync::Mutex;
+
+const INTRO_INSTRUCTIONS: &str = r#"[ЗНАКОМСТВО]
+Тебя только что добавили в этот групповой чат. Напиши одно короткое приветствие (1-2 предложения) в своем обычном стиле.
+Не рассказывай подробно о себе и не перечисляй, что умеешь."#;
+
+lazy_static::lazy_static! {
+    /// Telegram user ID of each account, fetched once per process
+    static ref OWN_USER_IDS: std::sync::Mutex<HashMap<i64, i64>> = std::sync::Mutex::new(HashMap::new());
+}
+
+/// How an account replies in a new group, picked from the owners' setup menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetupChoice {
+    Active,
+    Default,
+    Quiet,
+    TriggersOnly,
+    Silent,
+}
+
+impl SetupChoice {
+    pub const ALL: [SetupChoice; 5] = [
+        SetupChoice::Active,
+        SetupChoice::Default,
+        SetupChoice::Quiet,
+        SetupChoice::TriggersOnly,
+        SetupChoice::Silent,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SetupChoice::Active => "active",
+            SetupChoice::Default => "default",
+            SetupChoice::Quiet => "quiet",
+            SetupChoice::TriggersOnly => "triggers",
+            SetupChoice::Silent => "silent",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SetupChoice::Active => "🗣 Active (80%)",
+            SetupChoice::Default => "🙂 Account default",
+            SetupChoice::Quiet => "🤫 Quiet (10%)",
+            SetupChoice::TriggersOnly => "🏷 Triggers only",
+            SetupChoice::Silent => "⛔ Stay silent",
+        }
+    }
+
+    /// Per-chat reply probability override (`None` = account default)
+    pub fn reply_probability(&self) -> Option<i64> {
+        match self {
+            SetupChoice::Active => Some(80),
+            SetupChoice::Default => None,
+            SetupChoice::Quiet => Some(10),
+            SetupChoice::TriggersOnly | SetupChoice::Silent => Some(0),
+        }
+    }
+}
+
+/// Parse a comma-separated trigger list into lowercase words
+pub fn parse_triggers(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// Whether a message mentions any of the chat's trigger words
+pub fn matches_trigger(triggers: &[String], text: &str) -> bool {
+    if triggers.is_empty() {
+        return false;
+    }
+    let text = text.to_lowercase();
+    triggers.iter().any(|t| text.contains(t.as_str()))
+}
+
+/// Apply a setup menu choice to a chat
+pub async fn apply_choice(state: &AppState, account_id: i64, chat_id: i64, choice: SetupChoice) -> Result<()> {
+    ChatRepository::set_reply_probability(&state.db_pool, account_id, chat_id, choice.reply_probability()).await?;
+    ChatRepository::set_denied(&state.db_pool, account_id, chat_id, choice == SetupChoice::Silent).await?;
+
+    // Triggers-only without triggers would never answer: default to the persona's name
+    if choice == SetupChoice::TriggersOnly {
+        let chat = ChatRepository::get(&state.db_pool, account_id, chat_id).await?;
+        if chat.map_or(true, |c| c.get_triggers().is_empty()) {
+            let account = crate::db::AccountRepository::get_by_id(&state.db_pool, account_id)
+                .await?
+                .context("Account not found")?;
+            if let Some(persona) = super::rotation::current_persona(state, &account, chat_id).await? {
+                ChatRepository::set_triggers(&state.db_pool, account_id, chat_id, Some(&persona.name.to_lowercase()))
+                    .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Handle a service message about new members: if the account itself was added,
+/// register the chat, greet it and send the owners a setup menu
+pub async fn handle_members_added(
+    state: &AppState,
+    account: &Account,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    message: &Message,
+    added: &MessageChatAddMembers,
+) -> Result<()> {
+    let own_id = own_user_id(account.id, client).await?;
+    if !added.member_user_ids().contains(&own_id) {
+        return Ok(());
+    }
+
+    let chat_id = message.chat_id();
+    let adder_id = match message.sender_id() {
+        MessageSender::User(user) => user.user_id(),
+        _ => 0,
+    };
+
+    let client_lock = client.lock().await;
+    let chat = client_lock
+        .get_chat(&GetChat::builder().chat_id(chat_id).build())
+        .await
+        .context("Failed to fetch the new chat")?;
+    let member_count = match chat.type_() {
+        ChatType::Supergroup(s) => client_lock
+            .get_supergroup_full_info(&GetSupergroupFullInfo::builder().supergroup_id(s.supergroup_id()).build())
+            .await
+            .map(|info| info.member_count() as usize)
+            .ok(),
+        ChatType::BasicGroup(b) => client_lock
+            .get_basic_group_full_info(&GetBasicGroupFullInfo::builder().basic_group_id(b.basic_group_id()).build())
+            .await
+            .map(|info| info.members().len())
+            .ok(),
+        _ => None,
+    };
+    let adder_name = match client_lock.get_user(&GetUser::builder().user_id(adder_id).build()).await {
+        Ok(user) => user.first_name().to_string(),
+        Err(_) => adder_id.to_string(),
+    };
+    drop(client_lock);
+
+    ChatRepository::upsert_discovered(
+        &state.db_pool,
+        account.id,
+        chat_id,
+        chat.title(),
+        super::chats::chat_type_label(chat.type_()),
+    )
+    .await?;
+
+    // Being re-added to a chat we already greeted gets the menu, not a second intro
+    let first_time = ChatRepository::mark_onboarded(&state.db_pool, account.id, chat_id).await?;
+    let allowed = account.is_chat_allowed(chat_id);
+    if first_time && allowed && state.config.onboarding_intro {
+        if let Err(e) = post_intro(state, account, client, chat_id).await {
+            tracing::warn!("Failed to post intro in chat {}: {}", chat_id, e);
+        }
+    }
+
+    tracing::info!("Userbot {} was added to chat {} by {}", account.id, chat_id, crate::logging::user_ref(adder_id));
+
+    let mut text = format!(
+        "👋 Account {} was added to <b>{}</b> (<code>{}</code>) by {} (<code>{}</code>){}.",
+        account.id,
+        crate::bot::handlers::html_escape(chat.title()),
+        chat_id,
+        crate::bot::handlers::html_escape(&adder_name),
+        adder_id,
+        member_count.map(|n| format!(", {} members", n)).unwrap_or_default()
+    );
+    if !allowed {
+        text.push_str("\n⚠️ The chat isn't in the account's whitelist (/allow_chat), so it won't reply there.");
+    }
+    text.push_str(&format!(
+        "\n\nHow should it reply here? Custom triggers: /chat_triggers {} {} word, word",
+        account.id, chat_id
+    ));
+
+    send_setup_menu(state, adder_id, &text, account.id, chat_id).await
+}
+
+/// Send the setup menu to the owner who added the account, or to all owners
+async fn send_setup_menu(state: &AppState, adder_id: i64, text: &str, account_id: i64, chat_id: i64) -> Result<()> {
+    use teloxide::{
+        prelude::*,
+        types::{ChatId, InlineKeyboardButton, InlineKeyboardMarkup, ParseMode},
+    };
+
+    let button = |choice: SetupChoice| {
+        InlineKeyboardButton::callback(
+            choice.label(),
+            format!("onb:{}:{}:{}", choice.as_str(), account_id, chat_id),
+        )
+    };
+    let keyboard = InlineKeyboardMarkup::new(vec![
+        vec![button(SetupChoice::Active), button(SetupChoice::Default), button(SetupChoice::Quiet)],
+        vec![button(SetupChoice::TriggersOnly), button(SetupChoice::Silent)],
+    ]);
+
+    // The admin bot only talks to owners; anyone else adding the account is reported to them
+    let recipients = if state.config.is_owner(adder_id) {
+        vec![adder_id]
+    } else {
+        state.config.owner_ids.clone()
+    };
+
+    let bot = Bot::new(&state.config.bot_token);
+    for owner_id in recipients {
+        if let Err(e) = bot
+            .send_message(ChatId(owner_id), text)
+            .parse_mode(ParseMode::Html)
+            .reply_markup(keyboard.clone())
+            .await
+        {
+            tracing::error!("Failed to send onboarding menu to {}: {}", owner_id, e);
+        }
+    }
+
+    Ok(())
+}
+
+/// Greet a new chat as the persona answering there
+async fn post_intro(
+    state: &AppState,
+    account: &Account,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat_id: i64,
+) -> Result<()> {
+    let (system_prompt, persona_id) = match super::rotation::current_persona(state, account, chat_id).await? {
+        Some(persona) => (persona.prompt, Some(persona.id)),
+        None => (PersonaRepository::effective_prompt(&state.db_pool, account.id).await?, account.persona_id),
+    };
+
+    let chat = ChatRepository::get(&state.db_pool, account.id, chat_id).await?;
+    let local_now = super::timezone::chat_now(chat.as_ref(), state.config.default_timezone);
+    let messages = vec![
+        OllamaMessage {
+            role: "system".to_string(),
+            content: super::timezone::with_local_time(system_prompt, local_now),
+        },
+        OllamaMessage {
+            role: "system".to_string(),
+            content: INTRO_INSTRUCTIONS.to_string(),
+        },
+    ];
+
+    let intro = OllamaClient::new(state.config.ollama_url.clone())
+        .chat(OllamaChatRequest {
+            model: state.config.ollama_model.clone(),
+            messages,
+            stream: true,
+            options: None,
+        })
+        .await?;
+    let intro = intro.trim();
+    if intro.is_empty() {
+        return Ok(());
+    }
+
+    let send_message = SendMessage::builder()
+        .chat_id(chat_id)
+        .input_message_content(InputMessageContent::InputMessageText(
+            InputMessageText::builder()
+                .text(FormattedText::builder().text(intro).build())
+                .build(),
+        ))
+        .build();
+    client.lock().await.send_message(&send_message).await?;
+
+    MessageRepository::create(
+        &state.db_pool,
+        NewMessage {
+            account_id: account.id,
+            chat_id,
+            role: MessageRole::Assistant,
+            content: intro.to_string(),
+            sender_id: None,
+            sender_chat_id: None,
+            persona_id,
+        },
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// The account's own Telegram user ID
+async fn own_user_id(account_id: i64, client: &Arc<Mutex<Client<TdJson>>>) -> Result<i64> {
+    if let Some(id) = OWN_USER_IDS.lock().unwrap().get(&account_id) {
+        return Ok(*id);
+    }
+
+    let me = client
+        .lock()
+        .await
+        .get_me(&GetMe::builder().build())
+        .await
+        .context("Failed to fetch own user")?;
+    OWN_USER_IDS.lock().unwrap().insert(account_id, me.id());
+    Ok(me.id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn triggers_match_case_insensitively() {
+        let triggers = parse_triggers(" Маша, bot ,,");
+        assert_eq!(triggers, vec!["маша", "bot"]);
+        assert!(matches_trigger(&triggers, "МАША, ты тут?"));
+        assert!(!matches_trigger(&triggers, "привет всем"));
+        assert!(!matches_trigger(&[], "маша"));
+    }
+
+    #[test]
+    fn setup_choices_round_trip() {
+        for choice in SetupChoice::ALL {
+            assert_eq!(SetupChoice::parse(choice.as_str()), Some(choice));
+        }
+        assert_eq!(SetupChoice::TriggersOnly.reply_probability(), Some(0));
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    ai::ollama::{ChatOptions, OllamaClient, OllamaMessage},
+    db::{AccountChat, ChatRepository, ChatSummary, ChatSummaryRepository, MessageHistory, MessageRepository},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+/// Messages of the old persona's conversation that get summarized
+const SUMMARY_MESSAGES: i64 = 20;
+
+/// Added to the prompt of the first reply after a switch in chats that announce it
+pub const ANNOUNCE_NOTE: &str = "[СМЕНА]\nРаньше в этом чате отвечал кто-то другой, теперь отвечаешь ты. \
+Коротко и в своём стиле дай понять, что теперь здесь ты, не называя себя ботом или персоной.";
+
+/// Persona that wrote the latest reply of a context; None when nobody replied yet
+fn previous_persona(buffer: &[MessageHistory]) -> Option<Option<i64>> {
+    buffer.iter().rev().find(|m| m.role == "assistant").map(|m| m.persona_id)
+}
+
+fn transcript(buffer: &[MessageHistory]) -> String {
+    buffer
+        .iter()
+        .map(|m| {
+            let author = if m.role == "assistant" { "я" } else { "собеседник" };
+            format!("{}: {}", author, m.content)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// When another persona now answers the chat than the one that wrote the last reply, close the old
+/// conversation: summarize it and keep only the summary for the new persona. Returns whether it did.
+pub async fn guard(state: &AppState, account_id: i64, chat_id: i64, persona_id: Option<i64>) -> Result<bool> {
+    let buffer = MessageRepository::get_context_messages(&state.db_pool, account_id, chat_id, SUMMARY_MESSAGES).await?;
+    match previous_persona(&buffer) {
+        Some(previous) if previous != persona_id => {}
+        _ => return Ok(false),
+    }
+
+    let summary = match summarize(state, &transcript(&buffer)).await {
+        Ok(summary) => Some(summary).filter(|s| !s.is_empty()),
+        Err(e) => {
+            tracing::warn!("Failed to summarize conversation before persona switch in chat {}: {}", chat_id, e);
+            None
+        }
+    };
+    ChatRepository::set_context_boundary(&state.db_pool, account_id, chat_id, summary.as_deref()).await?;
+    if let (Some(summary), Some(first), Some(last)) = (&summary, buffer.first(), buffer.last()) {
+        ChatSummaryRepository::record(&state.db_pool, account_id, chat_id, "persona_switch", first.id, last.id, summary)
+            .await?;
+    }
+    tracing::info!(
+        "Persona switch in chat {} of account {}: started a fresh context{}",
+        chat_id,
+        account_id,
+        if summary.is_some() { " with a summary" } else { "" }
+    );
+    Ok(true)
+}
+
+/// The summary of what was said before the chat's context boundary, for the prompt
+pub fn context_note(chat: Option<&AccountChat>) -> Option<String> {
+    chat.and_then(|c| c.context_summary.as_deref())
+        .filter(|s| !s.trim().is_empty())
+        .map(|summary| format!("[РАНЕЕ В ЭТОМ ЧАТЕ]\n{}", summary.trim()))
+}
+
+/// Summarize a stored summary's messages again; the chat's context picks up the new text
+/// if it was still using the old one. Returns the new summary.
+pub async fn regenerate_summary(state: &AppState, stored: &ChatSummary) -> Result<String> {
+    let messages: Vec<MessageHistory> = MessageRepository::get_range(
+        &state.db_pool,
+        stored.account_id,
+        stored.chat_id,
+        stored.first_message_id,
+        stored.last_message_id,
+    )
+    .await?
+    .into_iter()
+    .filter(|m| !m.is_hashed)
+    .collect();
+    if messages.is_empty() {
+        anyhow::bail!("the summarized messages are no longer stored");
+    }
+
+    let summary = summarize(state, &transcript(&messages)).await?;
+    if summary.is_empty() {
+        anyhow::bail!("the model found nothing worth keeping");
+    }
+    ChatSummaryRepository::update_text(&state.db_pool, stored.id, &summary).await?;
+    if in_context(state, stored).await? {
+        ChatRepository::set_context_summary(&state.db_pool, stored.account_id, stored.chat_id, Some(&summary)).await?;
+    }
+    Ok(summary)
+}
+
+/// Delete a stored summary, and drop it from the chat's context if it is still passed on there
+pub async fn delete_summary(state: &AppState, stored: &ChatSummary) -> Result<()> {
+    if in_context(state, stored).await? {
+        ChatRepository::set_context_summary(&state.db_pool, stored.account_id, stored.chat_id, None).await?;
+    }
+    ChatSummaryRepository::delete(&state.db_pool, stored.id).await?;
+    Ok(())
+}
+
+/// Whether the chat's prompt still carries this summary
+async fn in_context(state: &AppState, stored: &ChatSummary) -> Result<bool> {
+    let chat = ChatRepository::get(&state.db_pool, stored.account_id, stored.chat_id).await?;
+    Ok(chat.and_then(|c| c.context_summary).as_deref() == Some(stored.summary.as_str()))
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Summary {
+    summary: String,
+}
+
+async fn summarize(state: &AppState, transcript: &str) -> Result<String> {
+    let prompt = format!(
+        r#"Below is the end of a Telegram conversation, oldest first. "я" is the account, "собеседник" the other side.
+Summarize it for someone who continues the conversation: topics, facts about the people, open questions and promises. Skip greetings and noise.
+
+Conversation:
+{}
+
+Return JSON: {{"summary": "<2-4 sentences in Russian, or empty if nothing worth keeping was said>"}}"#,
+        transcript
+    );
+
+    let summary: Summary = OllamaClient::new(state.config.ollama_url.clone())
+        .generate_structured(
+            &state.config.ollama_model,
+            vec![OllamaMessage { role: "user".to_string(), content: prompt }],
+            Some(ChatOptions { temperature: Some(0.2), num_predict: None }),
+        )
+        .await
+        .context("Failed to summarize the conversation")?;
+    Ok(summary.summary.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, persona_id: Option<i64>) -> MessageHistory {
+        MessageHistory {
+            id: 0,
+            account_id: 1,
+            chat_id: 2,
+            role: role.to_string(),
+            content: "привет".to_string(),
+            created_at: chrono::Utc::now(),
+            sender_id: None,
+            sender_chat_id: None,
+            persona_id,
+            is_hashed: false,
+        }
+    }
+
+    #[test]
+    fn finds_the_persona_of_the_last_reply() {
+        assert_eq!(previous_persona(&[]), None);
+        assert_eq!(previous_persona(&[message("user", None)]), None);
+        assert_eq!(
+            previous_persona(&[message("assistant", Some(3)), message("assistant", Some(5)), message("user", None)]),
+            Some(Some(5))
+        );
+        assert_eq!(previous_persona(&[message("assistant", None), message("user", None)]), Some(None));
+    }
+}
@@ -0,0 +1,100 @@
+use super::transport::ChatTransport;
+use crate::{db::AuditRepository, state::AppState};
+use anyhow::Result;
+
+/// Marker a reply can carry to pin the message it answers
+pub const PIN_MARKER: &str = "<PIN>";
+
+/// Added to the system prompt in chats where the persona may pin
+pub const PROMPT_NOTE: &str = "[ЗАКРЕПЛЕНИЕ]\n\
+Если сообщение, на которое ты отвечаешь, — действительно важное объявление для всего чата \
+(встреча, дедлайн, правила, смена ссылки), можешь закрепить его: добавь в ответ `<PIN>`. \
+Не закрепляй обычную болтовню, шутки и вопросы.";
+
+lazy_static::lazy_static! {
+    static ref OWNER_REQUEST: regex::Regex = regex::Regex::new(
+        r"(?i)^\s*(?P<unpin>/unpin\b|открепи|unpin\b)|^\s*(/pin\b|закрепи|запинь|pin\b)"
+    ).expect("invalid pin request pattern");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PinAction {
+    Pin,
+    Unpin,
+}
+
+impl PinAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PinAction::Pin => "pin",
+            PinAction::Unpin => "unpin",
+        }
+    }
+}
+
+/// Strip the pin marker from a generated reply, returning whether it was there
+pub fn take_marker(reply: &str) -> (String, bool) {
+    if !reply.contains(PIN_MARKER) {
+        return (reply.to_string(), false);
+    }
+    (reply.replace(PIN_MARKER, "").trim().to_string(), true)
+}
+
+/// "закрепи это", "/pin", "открепи" and the like, as the owner says them in reply to a message
+pub fn owner_request(text: &str) -> Option<PinAction> {
+    let captures = OWNER_REQUEST.captures(text)?;
+    Some(if captures.name("unpin").is_some() { PinAction::Unpin } else { PinAction::Pin })
+}
+
+/// Pin or unpin a message and write it to the audit log, whether Telegram allowed it or not
+pub async fn apply<T: ChatTransport>(
+    state: &AppState,
+    transport: &T,
+    account_id: i64,
+    chat_id: i64,
+    message_id: i64,
+    action: PinAction,
+    actor: &str,
+) -> Result<()> {
+    let result = transport.pin_message(chat_id, message_id, action == PinAction::Pin).await;
+    let error = result.as_ref().err().map(|e| e.to_string());
+    match &error {
+        None => tracing::info!("{} {}ned message {} in chat {} (account {})", actor, action.as_str(), message_id, chat_id, account_id),
+        Some(e) => tracing::warn!("Failed to {} message {} in chat {}: {}", action.as_str(), message_id, chat_id, e),
+    }
+
+    AuditRepository::record(
+        &state.db_pool,
+        account_id,
+        chat_id,
+        action.as_str(),
+        Some(message_id),
+        actor,
+        error.is_none(),
+        error.as_deref(),
+    )
+    .await?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_the_marker() {
+        assert_eq!(take_marker("Важно! <PIN>"), ("Важно!".to_string(), true));
+        assert_eq!(take_marker("<PIN>"), (String::new(), true));
+        assert_eq!(take_marker("просто ответ"), ("просто ответ".to_string(), false));
+    }
+
+    #[test]
+    fn recognizes_owner_requests() {
+        assert_eq!(owner_request("Закрепи это"), Some(PinAction::Pin));
+        assert_eq!(owner_request("/pin"), Some(PinAction::Pin));
+        assert_eq!(owner_request("открепи, уже неактуально"), Some(PinAction::Unpin));
+        assert_eq!(owner_request("/unpin"), Some(PinAction::Unpin));
+        assert_eq!(owner_request("pinned message?"), None);
+        assert_eq!(owner_request("не надо закрепи"), None);
+    }
+}
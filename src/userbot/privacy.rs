@@ -0,0 +1,88 @@
+use super::{formatting::FormatMode, transport::ChatTransport};
+use crate::{db::PrivacyRepository, state::AppState};
+use anyhow::Result;
+
+/// Private-chat memories recalled per group reply
+pub const RECALLED: usize = 2;
+
+/// Added above recalled private-chat memories in a group
+pub const PROMPT_HEADER: &str = "[ИЗ ЛИЧНОЙ ПЕРЕПИСКИ С СОБЕСЕДНИКОМ]\n\
+Это собеседник писал тебе в личке и разрешил вспоминать это в группах. \
+Можешь сослаться на это («ты же в личке говорил…»), если это к месту, \
+но не пересказывай подробности, которые он вряд ли хотел бы раскрыть при всех.\n\n";
+
+/// In-chat /privacy, sent to the account in a private chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivacyCommand {
+    /// Show the current choice
+    Status,
+    /// Allow recalling the private chat in groups
+    Share,
+    /// Keep the private chat private
+    Keep,
+}
+
+impl PrivacyCommand {
+    /// "/privacy", "/privacy on|off", "/приватность да|нет"
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut words = text.split_whitespace();
+        let command = words.next()?.split('@').next()?.to_lowercase();
+        if command != "/privacy" && command != "/приватность" {
+            return None;
+        }
+        Some(match words.next().map(|w| w.to_lowercase()).as_deref() {
+            Some("on" | "share" | "да" | "вкл") => PrivacyCommand::Share,
+            Some("off" | "keep" | "нет" | "выкл") => PrivacyCommand::Keep,
+            _ => PrivacyCommand::Status,
+        })
+    }
+}
+
+fn describe(shares: bool) -> &'static str {
+    if shares {
+        "Сейчас я могу вспоминать то, что ты пишешь мне в личке, когда мы общаемся в общих чатах. \
+         Выключить: /privacy off"
+    } else {
+        "Сейчас то, что ты пишешь мне в личке, остается в личке: в общих чатах я это не вспоминаю. \
+         Разрешить: /privacy on"
+    }
+}
+
+/// Record or show a user's choice, answering with a fixed text rather than the persona:
+/// consent should read the same for everyone
+pub async fn answer_command<T: ChatTransport>(
+    state: &AppState,
+    transport: &T,
+    account_id: i64,
+    user_id: i64,
+    command: PrivacyCommand,
+    reply_to: i64,
+) -> Result<()> {
+    let text = match command {
+        PrivacyCommand::Status => {
+            describe(PrivacyRepository::shares_private(&state.db_pool, account_id, user_id).await?).to_string()
+        }
+        PrivacyCommand::Share | PrivacyCommand::Keep => {
+            let share = command == PrivacyCommand::Share;
+            PrivacyRepository::set_share_private(&state.db_pool, account_id, user_id, share).await?;
+            format!("Готово. {}", describe(share))
+        }
+    };
+    transport.send_text(user_id, &text, FormatMode::Plain, Some(reply_to)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_privacy_commands() {
+        assert_eq!(PrivacyCommand::parse("/privacy"), Some(PrivacyCommand::Status));
+        assert_eq!(PrivacyCommand::parse("/privacy ON"), Some(PrivacyCommand::Share));
+        assert_eq!(PrivacyCommand::parse("/приватность нет"), Some(PrivacyCommand::Keep));
+        assert_eq!(PrivacyCommand::parse("/privacy@somebot off"), Some(PrivacyCommand::Keep));
+        assert_eq!(PrivacyCommand::parse("/privacy maybe"), Some(PrivacyCommand::Status));
+        assert_eq!(PrivacyCommand::parse("privacy on"), None);
+    }
+}
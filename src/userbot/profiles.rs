@@ -0,0 +1,222 @@
+use super::formatting::FormatMode;
+use crate::db::{AccountChat, ChatProfile};
+
+/// Parse the settings of a profile from "key=value" arguments.
+///
+/// Keys: persona=<id>, prob=<0-100>, format=<markdown|html|plain>,
+/// initiative=<on|off>, cooldown=<seconds>, style=<text until the end>.
+/// Keys that are not given stay unset and leave the chat's value alone.
+/// Every bad setting is reported, one error per setting.
+pub fn parse_settings(name: &str, args: &[String]) -> Result<ChatProfile, Vec<String>> {
+    let mut profile = ChatProfile { name: name.to_string(), ..Default::default() };
+    let mut errors = Vec::new();
+
+    for (i, arg) in args.iter().enumerate() {
+        let Some((key, value)) = arg.split_once('=') else {
+            errors.push(format!("expected key=value, got '{}'", arg));
+            continue;
+        };
+
+        let parsed: Result<(), String> = match key.to_lowercase().as_str() {
+            "persona" => value
+                .parse()
+                .map(|id| profile.persona_id = Some(id))
+                .map_err(|_| format!("persona: invalid id '{}'", value)),
+            "prob" | "probability" => match value.parse::<i64>() {
+                Ok(prob) if (0..=100).contains(&prob) => {
+                    profile.reply_probability = Some(prob);
+                    Ok(())
+                }
+                Ok(_) => Err("prob: must be 0-100".to_string()),
+                Err(_) => Err(format!("prob: invalid number '{}'", value)),
+            },
+            "format" => FormatMode::parse(value)
+                .map(|mode| profile.format_mode = Some(mode.as_str().to_string()))
+                .ok_or_else(|| format!("format: unknown mode '{}'", value)),
+            "initiative" => match value.to_lowercase().as_str() {
+                "on" | "yes" | "1" => {
+                    profile.initiative_enabled = Some(true);
+                    Ok(())
+                }
+                "off" | "no" | "0" => {
+                    profile.initiative_enabled = Some(false);
+                    Ok(())
+                }
+                _ => Err(format!("initiative: must be on or off, got '{}'", value)),
+            },
+            "cooldown" => match value.parse::<i64>() {
+                Ok(secs) if secs >= 0 => {
+                    profile.reply_cooldown_secs = Some(secs);
+                    Ok(())
+                }
+                Ok(_) => Err("cooldown: must not be negative".to_string()),
+                Err(_) => Err(format!("cooldown: invalid number '{}'", value)),
+            },
+            "style" => {
+                // The style takes the rest of the line
+                let rest = std::iter::once(value.to_string())
+                    .chain(args[i + 1..].iter().cloned())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                profile.style_notes = Some(rest.trim().to_string()).filter(|s| !s.is_empty());
+                break;
+            }
+            _ => Err(format!("unknown setting '{}'", key)),
+        };
+        if let Err(e) = parsed {
+            errors.push(e);
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    Ok(profile)
+}
+
+/// Which known chats a bulk settings change touches
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatFilter {
+    All,
+    /// Basic groups and supergroups, not channels
+    Groups,
+    Private,
+    Ids(Vec<i64>),
+}
+
+impl ChatFilter {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "all" => Some(Self::All),
+            "groups" => Some(Self::Groups),
+            "private" => Some(Self::Private),
+            other => {
+                let ids = other.strip_prefix("ids=")?;
+                let ids = ids
+                    .split(',')
+                    .filter(|id| !id.trim().is_empty())
+                    .map(|id| id.trim().parse().ok())
+                    .collect::<Option<Vec<i64>>>()?;
+                (!ids.is_empty()).then_some(Self::Ids(ids))
+            }
+        }
+    }
+
+    pub fn matches(&self, chat: &AccountChat) -> bool {
+        match self {
+            Self::All => true,
+            Self::Groups => matches!(chat.chat_type.as_str(), "group" | "supergroup"),
+            Self::Private => chat.chat_type == "private",
+            Self::Ids(ids) => ids.contains(&chat.chat_id),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::All => "all chats".to_string(),
+            Self::Groups => "groups".to_string(),
+            Self::Private => "private chats".to_string(),
+            Self::Ids(ids) => format!("{} listed chat(s)", ids.len()),
+        }
+    }
+}
+
+/// Parse "[filter] key=value..." for a bulk change; the filter defaults to all chats.
+/// A single setting may also be written as "key value", e.g. "cooldown 10".
+pub fn parse_bulk(args: &[String]) -> Result<(ChatFilter, ChatProfile), Vec<String>> {
+    let (filter, rest) = match args.first().and_then(|a| ChatFilter::parse(a)) {
+        Some(filter) => (filter, &args[1..]),
+        None => (ChatFilter::All, args),
+    };
+
+    let settings = match rest {
+        [key, value] if !key.contains('=') => vec![format!("{}={}", key, value)],
+        _ => rest.to_vec(),
+    };
+    if settings.is_empty() {
+        return Err(vec!["no settings given".to_string()]);
+    }
+
+    Ok((filter, parse_settings("", &settings)?))
+}
+
+/// One-line summary of what a profile sets
+pub fn describe(profile: &ChatProfile) -> String {
+    let mut parts = Vec::new();
+    if let Some(id) = profile.persona_id {
+        parts.push(format!("persona #{}", id));
+    }
+    if let Some(prob) = profile.reply_probability {
+        parts.push(format!("prob {}%", prob));
+    }
+    if let Some(format) = &profile.format_mode {
+        parts.push(format!("format {}", format));
+    }
+    if let Some(initiative) = profile.initiative_enabled {
+        parts.push(format!("initiative {}", if initiative { "on" } else { "off" }));
+    }
+    if let Some(cooldown) = profile.reply_cooldown_secs {
+        parts.push(format!("cooldown {}s", cooldown));
+    }
+    if profile.style_notes.is_some() {
+        parts.push("style notes".to_string());
+    }
+
+    if parts.is_empty() {
+        "no settings".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_settings() {
+        let profile = parse_settings("quiet", &args("prob=10 format=plain initiative=off cooldown=600 style=Коротко и по делу")).unwrap();
+        assert_eq!(profile.reply_probability, Some(10));
+        assert_eq!(profile.format_mode.as_deref(), Some("plain"));
+        assert_eq!(profile.initiative_enabled, Some(false));
+        assert_eq!(profile.reply_cooldown_secs, Some(600));
+        assert_eq!(profile.style_notes.as_deref(), Some("Коротко и по делу"));
+        assert_eq!(profile.persona_id, None);
+    }
+
+    #[test]
+    fn parses_bulk_changes() {
+        let (filter, patch) = parse_bulk(&args("cooldown 10")).unwrap();
+        assert_eq!(filter, ChatFilter::All);
+        assert_eq!(patch.reply_cooldown_secs, Some(10));
+
+        let (filter, patch) = parse_bulk(&args("ids=-100,-200 prob=5 initiative=off")).unwrap();
+        assert_eq!(filter, ChatFilter::Ids(vec![-100, -200]));
+        assert_eq!(patch.reply_probability, Some(5));
+        assert_eq!(patch.initiative_enabled, Some(false));
+
+        assert!(parse_bulk(&args("groups")).is_err());
+        assert!(parse_bulk(&args("ids=abc prob=5")).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_settings() {
+        assert!(parse_settings("x", &args("prob=150")).is_err());
+        assert!(parse_settings("x", &args("format=rtf")).is_err());
+        assert!(parse_settings("x", &args("mood=happy")).is_err());
+        assert!(parse_settings("x", &args("prob")).is_err());
+    }
+
+    #[test]
+    fn reports_every_bad_setting() {
+        let errors = parse_settings("x", &args("prob=150 format=plain cooldown=-5 mood=happy")).unwrap_err();
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].starts_with("prob:"));
+        assert!(errors[1].starts_with("cooldown:"));
+        assert_eq!(errors[2], "unknown setting 'mood'");
+    }
+}
@@ -0,0 +1,96 @@
+use crate::db::AccountChat;
+use chrono::{DateTime, Timelike};
+use chrono_tz::Tz;
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// A daily window of local time without auto-replies; it may wrap past midnight
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    /// Minutes after local midnight
+    start: u32,
+    end: u32,
+}
+
+impl QuietHours {
+    /// Parse "23:00-08:00" (or "23-8")
+    pub fn parse(value: &str) -> Option<Self> {
+        let (start, end) = value.trim().split_once(['-', '–'])?;
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        (start != end).then_some(Self { start, end })
+    }
+
+    pub fn contains(&self, minute_of_day: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute_of_day)
+        } else {
+            minute_of_day >= self.start || minute_of_day < self.end
+        }
+    }
+
+    pub fn contains_time(&self, local: &DateTime<Tz>) -> bool {
+        self.contains(local.hour() * 60 + local.minute())
+    }
+
+    /// Stored and shown form, "23:00-08:00"
+    pub fn format(&self) -> String {
+        format!(
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+fn parse_time(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let (hours, minutes) = match value.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (value.parse::<u32>().ok()?, 0),
+    };
+    // "24:00" is the end of the day
+    let minute = hours * 60 + minutes;
+    (minutes < 60 && minute <= MINUTES_PER_DAY).then_some(minute % MINUTES_PER_DAY)
+}
+
+/// The chat's quiet hours, when it has any
+pub fn of(chat: Option<&AccountChat>) -> Option<QuietHours> {
+    chat.and_then(|c| c.quiet_hours.as_deref()).and_then(QuietHours::parse)
+}
+
+/// Whether a message must go unanswered: inside the chat's quiet hours and not a direct
+/// mention the chat lets through
+pub fn holds_back(chat: Option<&AccountChat>, local_now: &DateTime<Tz>, mentions_us: bool) -> bool {
+    match of(chat) {
+        Some(quiet) if quiet.contains_time(local_now) => {
+            !(mentions_us && chat.is_some_and(|c| c.quiet_allow_mentions))
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_wraps_past_midnight() {
+        let night = QuietHours::parse("23:00-08:00").unwrap();
+        assert_eq!(night.format(), "23:00-08:00");
+        assert!(night.contains(23 * 60 + 30));
+        assert!(night.contains(3 * 60));
+        assert!(!night.contains(8 * 60));
+        assert!(!night.contains(12 * 60));
+
+        let lunch = QuietHours::parse("13-14:30").unwrap();
+        assert!(lunch.contains(14 * 60));
+        assert!(!lunch.contains(14 * 60 + 30));
+
+        assert_eq!(QuietHours::parse("22:00-24:00").unwrap().format(), "22:00-00:00");
+        assert!(QuietHours::parse("8:00-8:00").is_none());
+        assert!(QuietHours::parse("25:00-08:00").is_none());
+        assert!(QuietHours::parse("late").is_none());
+    }
+}
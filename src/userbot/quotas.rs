@@ -0,0 +1,129 @@
+use crate::{
+    db::{MediaQuota, MediaQuotaRepository},
+    state::AppState,
+};
+
+/// Expensive media processing limited per chat per day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MediaKind {
+    /// Photos, GIFs and video notes described by the vision model
+    Vision,
+    /// Voice notes transcribed by Whisper
+    Voice,
+}
+
+impl MediaKind {
+    pub const ALL: [MediaKind; 2] = [MediaKind::Vision, MediaKind::Voice];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vision => "vision",
+            Self::Voice => "voice",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "vision" | "photo" => Some(Self::Vision),
+            "voice" => Some(Self::Voice),
+            _ => None,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Vision => "📷",
+            Self::Voice => "🎙",
+        }
+    }
+
+    /// Limit from MEDIA_QUOTA_*_PER_DAY
+    pub fn default_limit(&self, state: &AppState) -> Option<i64> {
+        match self {
+            Self::Vision => state.config.media_quota_vision,
+            Self::Voice => state.config.media_quota_voice,
+        }
+    }
+}
+
+/// Quotas count UTC days
+pub fn today() -> String {
+    chrono::Utc::now().date_naive().to_string()
+}
+
+/// The limit that applies: the chat's override, else the default
+pub fn effective_limit(quota: Option<&MediaQuota>, default: Option<i64>) -> Option<i64> {
+    quota.and_then(|q| q.daily_limit).or(default)
+}
+
+/// "3/20 today" or "3 today (unlimited)"
+pub fn describe(used: i64, limit: Option<i64>) -> String {
+    match limit {
+        Some(limit) => format!("{}/{} today", used, limit),
+        None => format!("{} today (unlimited)", used),
+    }
+}
+
+/// Count one media processing in a chat; false once the day's quota is used up.
+/// Owners are exempt, and lookup errors fail open like entitlement checks.
+pub async fn try_consume(state: &AppState, account_id: i64, chat_id: i64, sender_id: i64, kind: MediaKind) -> bool {
+    if state.config.is_owner(sender_id) {
+        return true;
+    }
+
+    let quota = match MediaQuotaRepository::get(&state.db_pool, account_id, chat_id, kind.as_str()).await {
+        Ok(quota) => quota,
+        Err(e) => {
+            tracing::warn!("Media quota lookup failed in chat {}: {}", chat_id, e);
+            return true;
+        }
+    };
+
+    let limit = effective_limit(quota.as_ref(), kind.default_limit(state));
+    if limit == Some(0) {
+        return false;
+    }
+
+    match MediaQuotaRepository::consume(&state.db_pool, account_id, chat_id, kind.as_str(), &today(), limit).await {
+        Ok(true) => true,
+        Ok(false) => {
+            tracing::debug!("Daily {} quota exhausted in chat {}", kind.as_str(), chat_id);
+            false
+        }
+        Err(e) => {
+            tracing::warn!("Failed to count media quota in chat {}: {}", chat_id, e);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quota(daily_limit: Option<i64>, used: i64, day: &str) -> MediaQuota {
+        MediaQuota {
+            account_id: 1,
+            chat_id: -1,
+            kind: "vision".to_string(),
+            daily_limit,
+            used,
+            day: day.to_string(),
+        }
+    }
+
+    #[test]
+    fn chat_override_wins_over_default() {
+        assert_eq!(effective_limit(Some(&quota(Some(5), 0, "")), Some(50)), Some(5));
+        assert_eq!(effective_limit(Some(&quota(None, 0, "")), Some(50)), Some(50));
+        assert_eq!(effective_limit(None, None), None);
+    }
+
+    #[test]
+    fn usage_resets_each_day() {
+        let q = quota(Some(5), 4, "2026-03-01");
+        assert_eq!(q.used_on("2026-03-01"), 4);
+        assert_eq!(q.used_on("2026-03-02"), 0);
+        assert_eq!(describe(4, Some(5)), "4/5 today");
+    }
+}
@@ -0,0 +1,230 @@
+use crate::{
+    ai::persona_inheritance,
+    db::{Account, AccountChat, ChatRepository, MessageRepository, Persona, PersonaRepository},
+    state::AppState,
+    webhooks,
+};
+use anyhow::Result;
+use chrono::Timelike;
+use rand::{distributions::WeightedIndex, prelude::Distribution, seq::SliceRandom};
+use serde_json::json;
+
+/// Local hour at which tag rotations run
+const ROTATION_HOUR: u32 = 4;
+
+/// Nightly: give every account bound to a rotating tag a random persona from that tag
+pub async fn persona_rotation_worker(state: AppState) {
+    tracing::info!("Persona rotation worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(15 * 60)).await;
+
+        if chrono::Local::now().hour() != ROTATION_HOUR {
+            continue;
+        }
+
+        let rotations = match PersonaRepository::list_tag_rotations(&state.db_pool).await {
+            Ok(r) => r,
+            Err(e) => {
+                tracing::error!("Failed to load tag rotations: {}", e);
+                continue;
+            }
+        };
+
+        for rotation in rotations {
+            // Once per night
+            let rotated_recently = rotation
+                .last_rotated_at
+                .is_some_and(|at| chrono::Utc::now() - at < chrono::Duration::hours(20));
+            if rotated_recently {
+                continue;
+            }
+
+            if let Err(e) = rotate_tag(&state, &rotation.tag).await {
+                tracing::error!("Rotation of tag '{}' failed: {}", rotation.tag, e);
+            }
+        }
+    }
+}
+
+/// Rebind every account of a tag group to a random persona of the same tag
+pub async fn rotate_tag(state: &AppState, tag: &str) -> Result<usize> {
+    let personas = PersonaRepository::list_by_tag(&state.db_pool, tag).await?;
+    let accounts = PersonaRepository::accounts_with_tag(&state.db_pool, tag).await?;
+    let mut rotated = 0;
+
+    for account in accounts {
+        // Prefer a different persona than the current one
+        let choices: Vec<_> = personas
+            .iter()
+            .filter(|p| personas.len() == 1 || Some(p.id) != account.persona_id)
+            .collect();
+
+        let picked = match choices.choose(&mut rand::thread_rng()) {
+            Some(p) => p,
+            None => continue,
+        };
+
+        PersonaRepository::bind_account(&state.db_pool, account.id, Some(picked.id)).await?;
+        webhooks::emit(
+            state,
+            webhooks::Event::PersonaActivated,
+            json!({ "account_id": account.id, "persona_id": picked.id, "persona": picked.name, "reason": "tag_rotation" }),
+        )
+        .await;
+        rotated += 1;
+    }
+
+    PersonaRepository::mark_tag_rotated(&state.db_pool, tag).await?;
+    tracing::info!("Rotated {} accounts within persona tag '{}'", rotated, tag);
+    Ok(rotated)
+}
+
+/// How the persona answering in a chat changes over time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RotationMode {
+    /// Always the account's own persona/prompt
+    Off,
+    /// Switch every PERSONA_ROTATION_HOURS
+    Schedule,
+    /// Switch whenever a new conversation starts
+    Conversation,
+}
+
+impl RotationMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RotationMode::Off => "off",
+            RotationMode::Schedule => "schedule",
+            RotationMode::Conversation => "conversation",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "off" => Some(RotationMode::Off),
+            "schedule" => Some(RotationMode::Schedule),
+            "conversation" => Some(RotationMode::Conversation),
+            _ => None,
+        }
+    }
+}
+
+/// Pick the persona that answers in a chat, rotating it when due.
+/// Returns the system prompt to use and the persona it came from.
+pub async fn resolve_chat_persona(
+    state: &AppState,
+    account: &Account,
+    chat: Option<&AccountChat>,
+) -> Result<(String, Option<i64>)> {
+    // A persona pinned by a chat profile wins over rotation
+    if let Some(pinned) = chat.and_then(|c| c.pinned_persona_id) {
+        if let Some(persona) = persona_inheritance::resolve(&state.db_pool, pinned).await? {
+            return Ok((persona.prompt, Some(persona.id)));
+        }
+    }
+
+    let chat = match chat {
+        Some(c) if RotationMode::parse(&c.rotation_mode).unwrap_or(RotationMode::Off) != RotationMode::Off => c,
+        _ => return account_prompt(state, account).await,
+    };
+    let mode = RotationMode::parse(&chat.rotation_mode).unwrap_or(RotationMode::Off);
+
+    let now = chrono::Utc::now();
+    let sticky = chrono::Duration::minutes(state.config.persona_sticky_minutes);
+
+    let mid_conversation = MessageRepository::get_last_message(&state.db_pool, account.id, chat.chat_id)
+        .await?
+        .is_some_and(|m| now - m.created_at < sticky);
+
+    let active = match chat.active_persona_id {
+        Some(id) => persona_inheritance::resolve(&state.db_pool, id).await?,
+        None => None,
+    };
+
+    let due = match (&active, mode) {
+        (None, _) => true,
+        (Some(_), RotationMode::Schedule) => chat.persona_since.map_or(true, |since| {
+            now - since >= chrono::Duration::hours(state.config.persona_rotation_hours)
+        }),
+        (Some(_), RotationMode::Conversation) => true,
+        (Some(_), RotationMode::Off) => false,
+    };
+
+    // Sticky period: never switch persona in the middle of a conversation
+    if due && (active.is_none() || !mid_conversation) {
+        let current = active.as_ref().map(|p| p.id);
+        if let Some(picked) = pick_weighted(state, chat.rotation_tag.as_deref(), current).await? {
+            if Some(picked.id) != current {
+                ChatRepository::set_active_persona(&state.db_pool, account.id, chat.chat_id, picked.id).await?;
+                webhooks::emit(
+                    state,
+                    webhooks::Event::PersonaActivated,
+                    json!({
+                        "account_id": account.id,
+                        "chat_id": chat.chat_id,
+                        "persona_id": picked.id,
+                        "persona": picked.name,
+                        "reason": "chat_rotation",
+                    }),
+                )
+                .await;
+                tracing::info!(
+                    "Chat {} of account {} now answered by persona '{}'",
+                    chat.chat_id,
+                    account.id,
+                    picked.name
+                );
+            }
+            let picked = persona_inheritance::resolve_loaded(&state.db_pool, picked).await?;
+            return Ok((picked.prompt, Some(picked.id)));
+        }
+    }
+
+    match active {
+        Some(persona) => Ok((persona.prompt, Some(persona.id))),
+        None => account_prompt(state, account).await,
+    }
+}
+
+/// The persona currently answering in a chat, without rotating it
+pub async fn current_persona(state: &AppState, account: &Account, chat_id: i64) -> Result<Option<Persona>> {
+    let chat = ChatRepository::get(&state.db_pool, account.id, chat_id).await?;
+    let pinned = chat.as_ref().and_then(|c| c.pinned_persona_id);
+    let rotating = chat
+        .filter(|c| RotationMode::parse(&c.rotation_mode).unwrap_or(RotationMode::Off) != RotationMode::Off)
+        .and_then(|c| c.active_persona_id);
+
+    match pinned.or(rotating).or(account.persona_id) {
+        Some(id) => persona_inheritance::resolve(&state.db_pool, id).await,
+        None => Ok(None),
+    }
+}
+
+/// The account's own prompt (bound persona or system prompt), without rotation
+async fn account_prompt(state: &AppState, account: &Account) -> Result<(String, Option<i64>)> {
+    let prompt = PersonaRepository::effective_prompt(&state.db_pool, account.id).await?;
+    Ok((prompt, account.persona_id))
+}
+
+/// Weighted random persona from the pool, avoiding the current one when possible
+async fn pick_weighted(state: &AppState, tag: Option<&str>, current: Option<i64>) -> Result<Option<Persona>> {
+    let pool = match tag {
+        Some(tag) => PersonaRepository::list_by_tag(&state.db_pool, tag).await?,
+        None => PersonaRepository::list_all(&state.db_pool).await?,
+    };
+
+    let mut candidates: Vec<Persona> = pool.into_iter().filter(|p| p.rotation_weight > 0).collect();
+    if candidates.len() > 1 {
+        candidates.retain(|p| Some(p.id) != current);
+    }
+
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+
+    let weights = WeightedIndex::new(candidates.iter().map(|p| p.rotation_weight))?;
+    let idx = weights.sample(&mut rand::thread_rng());
+
+    Ok(Some(candidates.swap_remove(idx)))
+}
@@ -0,0 +1,221 @@
+use crate::{
+    db::{ChatRule, ChatRuleRepository, NewChatRule},
+    state::AppState,
+};
+use regex::Regex;
+
+/// Highest temperature a rule may set
+const MAX_TEMPERATURE: f64 = 2.0;
+
+/// What a rule says about answering at all
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplyRule {
+    /// Answer regardless of the reply probability
+    Always,
+    /// Never answer
+    Never,
+}
+
+impl ReplyRule {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "always" => Some(ReplyRule::Always),
+            "never" => Some(ReplyRule::Never),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplyRule::Always => "always",
+            ReplyRule::Never => "never",
+        }
+    }
+}
+
+/// A rule as typed in /rule_add; the persona is still a name or an ID
+#[derive(Debug, Default, PartialEq)]
+pub struct RuleDraft {
+    pub rule: NewChatRule,
+    pub persona: Option<String>,
+}
+
+/// Parse `chat=-100123 match=(?i)crypto persona=Trader temperature=1.2`: conditions
+/// (account, chat, user, match) and actions (persona, temperature, reply, priority) in any order
+pub fn parse(args: &[String]) -> Result<RuleDraft, String> {
+    let mut draft = RuleDraft::default();
+    for arg in args {
+        let (key, value) = arg
+            .split_once('=')
+            .filter(|(_, v)| !v.is_empty())
+            .ok_or_else(|| format!("'{}' is not key=value", arg))?;
+        let id = || value.parse::<i64>().map_err(|_| format!("{} must be a number", key));
+        match key {
+            "account" => draft.rule.account_id = Some(id()?),
+            "chat" => draft.rule.chat_id = Some(id()?),
+            "user" => draft.rule.sender_id = Some(id()?),
+            "match" => {
+                Regex::new(value).map_err(|e| format!("bad regex: {}", e))?;
+                draft.rule.pattern = Some(value.to_string());
+            }
+            "persona" => draft.persona = Some(value.to_string()),
+            "temperature" => {
+                let temperature = value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|t| (0.0..=MAX_TEMPERATURE).contains(t))
+                    .ok_or_else(|| format!("temperature must be between 0 and {}", MAX_TEMPERATURE))?;
+                draft.rule.temperature = Some(temperature);
+            }
+            "reply" => {
+                let reply = ReplyRule::parse(value).ok_or("reply must be always or never")?;
+                draft.rule.reply = Some(reply.as_str().to_string());
+            }
+            "priority" => draft.rule.priority = id()?,
+            _ => return Err(format!("unknown key '{}'", key)),
+        }
+    }
+
+    if draft.persona.is_none() && draft.rule.temperature.is_none() && draft.rule.reply.is_none() {
+        return Err("a rule needs an action: persona=, temperature= or reply=".to_string());
+    }
+    Ok(draft)
+}
+
+/// What the matching rules decided for a message; unset fields leave the default logic alone
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RuleOutcome {
+    pub persona_id: Option<i64>,
+    pub temperature: Option<f64>,
+    pub reply: Option<ReplyRule>,
+    /// IDs of the rules that matched, for logs
+    pub matched: Vec<i64>,
+}
+
+fn matches(rule: &ChatRule, account_id: i64, chat_id: i64, sender_id: i64, text: &str) -> bool {
+    rule.account_id.map_or(true, |id| id == account_id)
+        && rule.chat_id.map_or(true, |id| id == chat_id)
+        && rule.sender_id.map_or(true, |id| id == sender_id)
+        && rule.pattern.as_deref().map_or(true, |pattern| {
+            Regex::new(pattern).map(|re| re.is_match(text)).unwrap_or(false)
+        })
+}
+
+/// Apply rules in order (priority first): for each action the first matching rule that sets it wins
+pub fn evaluate(rules: &[ChatRule], account_id: i64, chat_id: i64, sender_id: i64, text: &str) -> RuleOutcome {
+    let mut outcome = RuleOutcome::default();
+    for rule in rules.iter().filter(|r| matches(r, account_id, chat_id, sender_id, text)) {
+        outcome.persona_id = outcome.persona_id.or(rule.persona_id);
+        outcome.temperature = outcome.temperature.or(rule.temperature);
+        outcome.reply = outcome.reply.or_else(|| rule.reply.as_deref().and_then(ReplyRule::parse));
+        outcome.matched.push(rule.id);
+    }
+    outcome
+}
+
+/// The rules' decision for an incoming message; a broken rules table never stops replies
+pub async fn for_message(state: &AppState, account_id: i64, incoming: &super::worker::IncomingMessage) -> RuleOutcome {
+    let rules = match ChatRuleRepository::list_for_account(&state.db_pool, account_id).await {
+        Ok(rules) => rules,
+        Err(e) => {
+            tracing::warn!("Failed to load chat rules: {}", e);
+            return RuleOutcome::default();
+        }
+    };
+    let outcome = evaluate(&rules, account_id, incoming.chat_id, incoming.sender_id, &incoming.text);
+    if !outcome.matched.is_empty() {
+        tracing::debug!("Chat rules {:?} matched in chat {}", outcome.matched, incoming.chat_id);
+    }
+    outcome
+}
+
+/// One line per rule for /rules
+pub fn describe(rule: &ChatRule, persona_name: Option<&str>) -> String {
+    let mut conditions = Vec::new();
+    if let Some(id) = rule.account_id {
+        conditions.push(format!("account={}", id));
+    }
+    if let Some(id) = rule.chat_id {
+        conditions.push(format!("chat={}", id));
+    }
+    if let Some(id) = rule.sender_id {
+        conditions.push(format!("user={}", id));
+    }
+    if let Some(pattern) = &rule.pattern {
+        conditions.push(format!("match={}", pattern));
+    }
+
+    let mut actions = Vec::new();
+    if let Some(id) = rule.persona_id {
+        actions.push(format!("persona={}", persona_name.map_or(id.to_string(), str::to_string)));
+    }
+    if let Some(temperature) = rule.temperature {
+        actions.push(format!("temperature={}", temperature));
+    }
+    if let Some(reply) = &rule.reply {
+        actions.push(format!("reply={}", reply));
+    }
+
+    format!(
+        "if {} → {}",
+        if conditions.is_empty() { "any message".to_string() } else { conditions.join(" ") },
+        actions.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(str::to_string).collect()
+    }
+
+    fn rule(id: i64, chat_id: Option<i64>, pattern: Option<&str>, persona_id: Option<i64>, reply: Option<&str>) -> ChatRule {
+        ChatRule {
+            id,
+            account_id: None,
+            chat_id,
+            sender_id: None,
+            pattern: pattern.map(str::to_string),
+            persona_id,
+            temperature: None,
+            reply: reply.map(str::to_string),
+            priority: 0,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn parses_rules() {
+        let draft = parse(&args("chat=-100 match=(?i)crypto persona=Trader temperature=1.2")).unwrap();
+        assert_eq!(draft.rule.chat_id, Some(-100));
+        assert_eq!(draft.rule.pattern.as_deref(), Some("(?i)crypto"));
+        assert_eq!(draft.persona.as_deref(), Some("Trader"));
+        assert_eq!(draft.rule.temperature, Some(1.2));
+
+        assert_eq!(parse(&args("user=42 reply=never")).unwrap().rule.reply.as_deref(), Some("never"));
+        assert!(parse(&args("chat=-100")).is_err());
+        assert!(parse(&args("match=( reply=never")).is_err());
+        assert!(parse(&args("temperature=5")).is_err());
+        assert!(parse(&args("color=red reply=never")).is_err());
+    }
+
+    #[test]
+    fn first_matching_rule_wins_per_action() {
+        let rules = vec![
+            rule(1, Some(-100), Some("(?i)crypto"), Some(7), None),
+            rule(2, Some(-100), None, Some(8), Some("always")),
+            rule(3, Some(-200), None, None, Some("never")),
+        ];
+
+        let outcome = evaluate(&rules, 1, -100, 5, "Crypto is up");
+        assert_eq!(outcome.persona_id, Some(7));
+        assert_eq!(outcome.reply, Some(ReplyRule::Always));
+        assert_eq!(outcome.matched, vec![1, 2]);
+
+        assert_eq!(evaluate(&rules, 1, -100, 5, "hello").persona_id, Some(8));
+        assert_eq!(evaluate(&rules, 1, -200, 5, "hello").reply, Some(ReplyRule::Never));
+        assert_eq!(evaluate(&rules, 1, -300, 5, "hello"), RuleOutcome::default());
+    }
+}
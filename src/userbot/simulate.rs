@@ -0,0 +1,352 @@
+use super::{
+    formatting::FormatMode,
+    transport::ChatTransport,
+    worker::IncomingMessage,
+};
+use crate::{
+    ai::{media_memory::MediaRef, polls::PollDraft},
+    db::{Account, AccountRepository, NewAccount},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Phone number of the throwaway account simulated messages are answered by
+const SIMULATED_PHONE: &str = "+simulated";
+/// First fake group chat id; simulated chats count down from here
+const FIRST_CHAT_ID: i64 = -1_000_000_000_001;
+/// Fake senders per simulated chat
+const SENDERS_PER_CHAT: i64 = 5;
+/// Give up waiting for the last replies when none arrive for this long
+const DRAIN_STALL_SECS: u64 = 300;
+
+const PHRASES: &[&str] = &[
+    "привет, как дела?",
+    "кто-нибудь смотрел вчерашний матч?",
+    "слушай, а ты в базах данных шаришь?",
+    "ахаха",
+    "ну и погода сегодня",
+    "что думаете про новый айфон",
+    "го вечером в доту",
+    "спасибо, помогло",
+    "а где все?",
+    "у меня опять прод упал, что делать",
+    "посоветуйте сериал на вечер",
+    "ок",
+];
+
+/// One of the canned chat phrases, cycling through them
+pub fn phrase(n: usize) -> &'static str {
+    PHRASES[n % PHRASES.len()]
+}
+
+/// Database used unless --database is given, so simulated chats never mix with real history
+pub const DEFAULT_DATABASE_URL: &str = "sqlite:data/simulate.db";
+
+/// Options of `puppeteer simulate`
+#[derive(Debug, Clone)]
+pub struct SimulateOptions {
+    /// Number of simulated chats
+    pub chats: u32,
+    /// Incoming messages per second across all chats
+    pub rate: f64,
+    /// How long to generate messages
+    pub duration_secs: u64,
+    /// Keep the humanization delays instead of skipping them
+    pub realtime: bool,
+    /// Database the simulation writes to
+    pub database_url: String,
+}
+
+impl Default for SimulateOptions {
+    fn default() -> Self {
+        Self {
+            chats: 10,
+            rate: 1.0,
+            duration_secs: 60,
+            realtime: false,
+            database_url: DEFAULT_DATABASE_URL.to_string(),
+        }
+    }
+}
+
+impl SimulateOptions {
+    /// Parse `--chats 20 --rate 5 --duration 120 --realtime --database sqlite:data/load.db`
+    pub fn parse(args: &[String]) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .with_context(|| format!("{} needs a value", name))
+                    .map(|v| v.to_string())
+            };
+            match arg.as_str() {
+                "--chats" => options.chats = value("--chats")?.parse().context("--chats must be a number")?,
+                "--rate" => options.rate = value("--rate")?.parse().context("--rate must be a number")?,
+                "--duration" => {
+                    options.duration_secs = value("--duration")?.parse().context("--duration must be a number of seconds")?
+                }
+                "--realtime" => options.realtime = true,
+                "--database" => options.database_url = value("--database")?,
+                other => anyhow::bail!(
+                    "Unknown option '{}'. Usage: simulate [--chats N] [--rate MSG_PER_SEC] [--duration SECS] [--realtime] [--database URL]",
+                    other
+                ),
+            }
+        }
+
+        if options.chats == 0 || !options.rate.is_finite() || options.rate <= 0.0 || options.duration_secs == 0 {
+            anyhow::bail!("--chats, --rate and --duration must be positive");
+        }
+        Ok(options)
+    }
+}
+
+/// Transport that counts what would have been sent instead of talking to Telegram
+#[derive(Default)]
+pub struct SimulatedTransport {
+    realtime: bool,
+    reads: AtomicU64,
+    typing: AtomicU64,
+    sent: AtomicU64,
+    owner_alerts: AtomicU64,
+    /// Incoming messages the pipeline is done with, batched ones included
+    answered: AtomicU64,
+    failures: AtomicU64,
+    latencies: Mutex<Vec<Duration>>,
+}
+
+impl ChatTransport for SimulatedTransport {
+    async fn mark_read(&self, _chat_id: i64, _message_id: i64) -> Result<()> {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn set_typing(&self, _chat_id: i64, _typing: bool) -> Result<()> {
+        self.typing.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn send_text(&self, chat_id: i64, text: &str, _mode: FormatMode, _reply_to: Option<i64>) -> Result<i64> {
+        let id = self.sent.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("[simulate] chat {} <- {}", chat_id, text);
+        Ok(id as i64 + 1)
+    }
+
+    async fn send_poll(&self, chat_id: i64, poll: &PollDraft, _reply_to: Option<i64>) -> Result<i64> {
+        let id = self.sent.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("[simulate] chat {} <- poll {}", chat_id, poll.question);
+        Ok(id as i64 + 1)
+    }
+
+    async fn send_media(&self, chat_id: i64, media: &MediaRef, _reply_to: Option<i64>) -> Result<i64> {
+        let id = self.sent.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!("[simulate] chat {} <- {} {}", chat_id, media.kind.as_str(), media.remote_file_id);
+        Ok(id as i64 + 1)
+    }
+
+    async fn pin_message(&self, chat_id: i64, message_id: i64, pin: bool) -> Result<()> {
+        tracing::debug!("[simulate] chat {} {} message {}", chat_id, if pin { "pin" } else { "unpin" }, message_id);
+        Ok(())
+    }
+
+    async fn pause(&self, duration: Duration) {
+        if self.realtime {
+            tokio::time::sleep(duration).await;
+        }
+    }
+
+    async fn notify_owner(&self, _state: &AppState, message: &str) -> Result<()> {
+        self.owner_alerts.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!("[simulate] owner alert: {}", message);
+        Ok(())
+    }
+
+    fn answered(&self, incoming: &IncomingMessage, messages: usize, ok: bool) {
+        if !ok {
+            self.failures.fetch_add(messages as u64, Ordering::Relaxed);
+        }
+        self.latencies.lock().unwrap_or_else(|e| e.into_inner()).push(incoming.received_at.elapsed());
+        self.answered.fetch_add(messages as u64, Ordering::Relaxed);
+    }
+}
+
+/// What a simulation run did
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub elapsed: Duration,
+    pub generated: usize,
+    pub failures: u64,
+    pub reads: u64,
+    pub sent: u64,
+    pub typing: u64,
+    pub owner_alerts: u64,
+    /// Latency of each reply from the first message it answers, sorted; a debounced batch counts once
+    pub latencies: Vec<Duration>,
+}
+
+/// The throwaway "+simulated" account, created inactive on first use so it is never started as a real userbot
+pub async fn simulated_account(state: &AppState) -> Result<Account> {
+    match AccountRepository::get_by_phone(&state.db_pool, SIMULATED_PHONE).await? {
+        Some(account) => Ok(account),
+        None => {
+            let account = AccountRepository::create(
+                &state.db_pool,
+                NewAccount {
+                    phone_number: SIMULATED_PHONE.to_string(),
+                    session_data: Vec::new(),
+                    system_prompt: state.config.default_system_prompt.clone(),
+                },
+            )
+            .await?;
+            AccountRepository::set_active(&state.db_pool, account.id, false).await?;
+            Ok(account)
+        }
+    }
+}
+
+/// Feed synthetic messages through the reply pipeline and print throughput and latency.
+/// `state` must be built on `options.database_url`.
+pub async fn run(state: AppState, options: SimulateOptions) -> Result<()> {
+    let report = measure(&state, &options).await?;
+    let latencies = &report.latencies;
+    let percentile = |p: f64| {
+        latencies
+            .get(((latencies.len() as f64 * p) as usize).min(latencies.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    };
+
+    println!("Simulation finished in {:.1}s", report.elapsed.as_secs_f64());
+    println!("  messages generated: {}", report.generated);
+    println!("  pipeline failures:  {}", report.failures);
+    println!("  messages read:      {}", report.reads);
+    println!("  chunks sent:        {}", report.sent);
+    println!("  typing indicators:  {}", report.typing);
+    println!("  owner alerts:       {}", report.owner_alerts);
+    println!(
+        "  latency p50/p95/max: {:.2}s / {:.2}s / {:.2}s",
+        percentile(0.5).as_secs_f64(),
+        percentile(0.95).as_secs_f64(),
+        latencies.last().copied().unwrap_or_default().as_secs_f64()
+    );
+
+    Ok(())
+}
+
+/// Feed synthetic messages through the debouncer and the reply pipeline, answered by the
+/// simulated account, and wait until every message has been answered
+pub async fn measure(state: &AppState, options: &SimulateOptions) -> Result<SimulationReport> {
+    let account = simulated_account(state).await?;
+
+    tracing::info!(
+        "Simulating {} chats at {} msg/s for {}s on account {}{}",
+        options.chats,
+        options.rate,
+        options.duration_secs,
+        account.id,
+        if options.realtime { " (realtime delays)" } else { "" }
+    );
+
+    let transport = Arc::new(SimulatedTransport { realtime: options.realtime, ..Default::default() });
+    let interval = Duration::from_secs_f64(1.0 / options.rate);
+    let started = Instant::now();
+    let mut generated = 0;
+    let mut next_message_id = 1;
+
+    while started.elapsed() < Duration::from_secs(options.duration_secs) {
+        let chat_index = rand::random::<u32>() % options.chats;
+        let incoming = IncomingMessage {
+            chat_id: FIRST_CHAT_ID - chat_index as i64,
+            message_id: next_message_id,
+            sender_id: 1_000_000 + chat_index as i64 * SENDERS_PER_CHAT + rand::random::<i64>().rem_euclid(SENDERS_PER_CHAT),
+            sender_chat_id: None,
+            sender_is_bot: false,
+            reply_to_message_id: 0,
+            mentions_us: false,
+            is_channel_post: false,
+            is_sticker: false,
+            text: PHRASES[rand::random::<usize>() % PHRASES.len()].to_string(),
+            media: None,
+            correlation_id: super::trace::correlation_id(),
+            received_at: Instant::now(),
+            queued_at: Instant::now(),
+        };
+        next_message_id += 1;
+        generated += 1;
+
+        // With debouncing off submit answers inline, so each message gets its own task
+        let (state, account, transport) = (state.clone(), account.clone(), transport.clone());
+        tokio::spawn(async move {
+            let chat_id = incoming.chat_id;
+            if let Err(e) = super::debounce::submit(&state, &account, &transport, None, incoming).await {
+                tracing::warn!("[simulate] pipeline failed in chat {}: {}", chat_id, e);
+            }
+        });
+
+        tokio::time::sleep(interval).await;
+    }
+
+    // Batches still waiting out their debounce window are answered in the background
+    let mut last_progress = (0, Instant::now());
+    loop {
+        let answered = transport.answered.load(Ordering::Relaxed);
+        if answered >= generated as u64 {
+            break;
+        }
+        if answered != last_progress.0 {
+            last_progress = (answered, Instant::now());
+        } else if last_progress.1.elapsed() >= Duration::from_secs(DRAIN_STALL_SECS) {
+            tracing::warn!("[simulate] {} messages still unanswered, giving up", generated as u64 - answered);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    let mut latencies = transport.latencies.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    latencies.sort();
+
+    Ok(SimulationReport {
+        elapsed: started.elapsed(),
+        generated,
+        failures: transport.failures.load(Ordering::Relaxed),
+        reads: transport.reads.load(Ordering::Relaxed),
+        sent: transport.sent.load(Ordering::Relaxed),
+        typing: transport.typing.load(Ordering::Relaxed),
+        owner_alerts: transport.owner_alerts.load(Ordering::Relaxed),
+        latencies,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = SimulateOptions::parse(&args(&["--chats", "20", "--rate", "5"])).unwrap();
+        assert_eq!(options.chats, 20);
+        assert_eq!(options.rate, 5.0);
+        assert_eq!(options.duration_secs, 60);
+        assert!(!options.realtime);
+        assert_eq!(options.database_url, DEFAULT_DATABASE_URL);
+
+        assert!(SimulateOptions::parse(&args(&["--chats"])).is_err());
+        assert!(SimulateOptions::parse(&args(&["--rate", "0"])).is_err());
+        assert!(SimulateOptions::parse(&args(&["--rate", "NaN"])).is_err());
+        assert!(SimulateOptions::parse(&args(&["--rate", "inf"])).is_err());
+        assert!(SimulateOptions::parse(&args(&["--bogus"])).is_err());
+    }
+}
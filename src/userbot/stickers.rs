@@ -0,0 +1,297 @@
+use crate::{
+    db::{AnsweredReply, MessageRepository, Persona, StickerPack, StickerPackRepository},
+    state::AppState,
+};
+use ab_glyph::{FontArc, PxScale};
+use anyhow::{Context, Result};
+use image::{ImageFormat, Rgba, RgbaImage};
+use imageproc::drawing::{draw_text_mut, text_size};
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Telegram wants static stickers to fit 512x512
+const SIZE: u32 = 512;
+/// Room left around the text
+const MARGIN: u32 = 40;
+/// Replies longer than this aren't catchphrases
+const MAX_PHRASE_CHARS: usize = 40;
+/// Stickers added per creation or refresh
+const BATCH: usize = 20;
+/// Telegram's limit for a static sticker set
+const MAX_STICKERS: usize = 120;
+/// Packs are refreshed this often
+const REFRESH_DAYS: i64 = 30;
+const CHECK_INTERVAL_SECS: u64 = 6 * 3600;
+/// Emoji every sticker is filed under
+const STICKER_EMOJI: &str = "💬";
+
+/// Backgrounds, picked per phrase so a refresh doesn't repaint old stickers
+const PALETTE: [[u8; 3]; 6] = [
+    [0xF9, 0xC7, 0x4F],
+    [0x90, 0xBE, 0x6D],
+    [0x43, 0xAA, 0x8B],
+    [0x57, 0x75, 0x90],
+    [0xF3, 0x72, 0x2C],
+    [0xB5, 0x83, 0x8D],
+];
+
+/// Short, answered replies of a persona, most answers first, without ones already in the pack
+pub fn catchphrases(replies: &[AnsweredReply], skip: &[String], limit: usize) -> Vec<String> {
+    let skip: Vec<String> = skip.iter().map(|p| p.to_lowercase()).collect();
+    let mut answers: HashMap<String, (String, i64)> = HashMap::new();
+    for reply in replies {
+        let phrase = reply.reply.replace("||", " ").split_whitespace().collect::<Vec<_>>().join(" ");
+        if phrase.is_empty() || phrase.chars().count() > MAX_PHRASE_CHARS || skip.contains(&phrase.to_lowercase()) {
+            continue;
+        }
+        answers.entry(phrase.to_lowercase()).or_insert_with(|| (phrase, 0)).1 += reply.responses;
+    }
+
+    let mut phrases: Vec<(String, i64)> = answers.into_values().collect();
+    phrases.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    phrases.into_iter().take(limit).map(|(phrase, _)| phrase).collect()
+}
+
+/// Break a phrase into lines that `fits` accepts; a word too long for any line gets one of its own
+pub fn wrap(phrase: &str, fits: impl Fn(&str) -> bool) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in phrase.split_whitespace() {
+        let candidate = if line.is_empty() { word.to_string() } else { format!("{} {}", line, word) };
+        if line.is_empty() || fits(&candidate) {
+            line = candidate;
+        } else {
+            lines.push(std::mem::replace(&mut line, word.to_string()));
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// The largest text size the phrase fits the sticker at
+fn layout(font: &FontArc, phrase: &str) -> (PxScale, Vec<String>) {
+    let room = SIZE - 2 * MARGIN;
+    let mut fallback = None;
+    for size in (32..=112).rev().step_by(8) {
+        let scale = PxScale::from(size as f32);
+        let lines = wrap(phrase, |line| text_size(scale, font, line).0 <= room);
+        let widest = lines.iter().map(|l| text_size(scale, font, l).0).max().unwrap_or(0);
+        let height = lines.len() as u32 * line_height(scale);
+        if widest <= room && height <= room {
+            return (scale, lines);
+        }
+        fallback = Some((scale, lines));
+    }
+    fallback.unwrap_or_else(|| (PxScale::from(32.0), vec![phrase.to_string()]))
+}
+
+fn line_height(scale: PxScale) -> u32 {
+    (scale.y * 1.2) as u32
+}
+
+/// A 512x512 PNG of the phrase on a colored background
+pub fn render(font: &FontArc, phrase: &str) -> Result<Vec<u8>> {
+    let [r, g, b] = PALETTE[phrase.bytes().map(usize::from).sum::<usize>() % PALETTE.len()];
+    let mut image = RgbaImage::from_pixel(SIZE, SIZE, Rgba([r, g, b, 255]));
+
+    let (scale, lines) = layout(font, phrase);
+    let height = lines.len() as u32 * line_height(scale);
+    let mut y = SIZE.saturating_sub(height) / 2;
+    for line in &lines {
+        let width = text_size(scale, font, line).0;
+        let x = SIZE.saturating_sub(width) / 2;
+        // A soft shadow keeps light text readable on every background
+        draw_text_mut(&mut image, Rgba([0, 0, 0, 110]), x as i32 + 3, y as i32 + 3, scale, font, line);
+        draw_text_mut(&mut image, Rgba([255, 255, 255, 255]), x as i32, y as i32, scale, font, line);
+        y += line_height(scale);
+    }
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)
+        .context("Failed to encode sticker")?;
+    Ok(png)
+}
+
+fn load_font(state: &AppState) -> Result<FontArc> {
+    let bytes = std::fs::read(&state.config.sticker_font)
+        .with_context(|| format!("Failed to read sticker font {}", state.config.sticker_font))?;
+    FontArc::try_from_vec(bytes).context("Sticker font is not a TrueType font")
+}
+
+/// Render the phrases to files TDLib can upload; the caller removes them
+async fn sticker_files(state: &AppState, persona_id: i64, phrases: &[String]) -> Result<Vec<String>> {
+    let font = load_font(state)?;
+    let dir = "data/stickers";
+    tokio::fs::create_dir_all(dir).await.context("Failed to create sticker directory")?;
+
+    let mut paths = Vec::new();
+    for (i, phrase) in phrases.iter().enumerate() {
+        let path = format!("{}/{}_{}_{}.png", dir, persona_id, chrono::Utc::now().timestamp(), i);
+        tokio::fs::write(&path, render(&font, phrase)?).await.context("Failed to write sticker")?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+fn input_sticker(path: &str) -> InputSticker {
+    InputSticker::Static(
+        InputStickerStatic::builder()
+            .sticker(InputFile::Local(InputFileLocal::builder().path(path).build()))
+            .emojis(STICKER_EMOJI)
+            .build(),
+    )
+}
+
+async fn remove_files(paths: &[String]) {
+    for path in paths {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+}
+
+/// Create a sticker set of the persona's catchphrases, owned by the account; returns its short name
+pub async fn create_pack(
+    state: &AppState,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    account_id: i64,
+    persona: &Persona,
+) -> Result<String> {
+    let replies = MessageRepository::answered_replies(&state.db_pool, Some(persona.id), 1, None, None).await?;
+    let phrases = catchphrases(&replies, &[], BATCH);
+    if phrases.is_empty() {
+        anyhow::bail!("No short answered replies of this persona yet");
+    }
+
+    // Short names are global on Telegram, so a random suffix keeps them from colliding
+    let name = format!("persona{}_{:08x}", persona.id, rand::random::<u32>());
+    let title: String = format!("{} — фразочки", persona.name).chars().take(64).collect();
+
+    let paths = sticker_files(state, persona.id, &phrases).await?;
+    let result = async {
+        let client = client.lock().await;
+        let me = client.get_me(&GetMe::builder().build()).await.context("Failed to get own user")?;
+        let request = CreateNewStickerSet::builder()
+            .user_id(me.id())
+            .title(title.clone())
+            .name(name.clone())
+            .stickers(paths.iter().map(|p| input_sticker(p)).collect::<Vec<_>>())
+            .source("puppeteer")
+            .build();
+        client.create_new_sticker_set(&request).await.context("Telegram refused to create the sticker set")
+    }
+    .await;
+    remove_files(&paths).await;
+    result?;
+
+    StickerPackRepository::create(&state.db_pool, persona.id, account_id, &name, &title, &phrases).await?;
+    Ok(name)
+}
+
+/// Add the persona's newest catchphrases to its pack; returns how many were added
+pub async fn refresh_pack(state: &AppState, client: &Arc<Mutex<Client<TdJson>>>, pack: &StickerPack) -> Result<usize> {
+    let mut phrases = pack.get_phrases();
+    let room = MAX_STICKERS.saturating_sub(phrases.len()).min(BATCH);
+    let replies = MessageRepository::answered_replies(&state.db_pool, Some(pack.persona_id), 1, None, None).await?;
+    let new = catchphrases(&replies, &phrases, room);
+    if new.is_empty() {
+        StickerPackRepository::set_phrases(&state.db_pool, pack.persona_id, &phrases).await?;
+        return Ok(0);
+    }
+
+    let paths = sticker_files(state, pack.persona_id, &new).await?;
+    let mut added = 0;
+    {
+        let client = client.lock().await;
+        let me = client.get_me(&GetMe::builder().build()).await;
+        match me {
+            Ok(me) => {
+                for (phrase, path) in new.iter().zip(&paths) {
+                    let request = AddStickerToSet::builder()
+                        .user_id(me.id())
+                        .name(pack.name.clone())
+                        .sticker(input_sticker(path))
+                        .build();
+                    match client.add_sticker_to_set(&request).await {
+                        Ok(_) => {
+                            phrases.push(phrase.clone());
+                            added += 1;
+                        }
+                        Err(e) => tracing::warn!("Failed to add sticker to pack {}: {}", pack.name, e),
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Failed to get own user to refresh pack {}: {}", pack.name, e),
+        }
+    }
+    remove_files(&paths).await;
+
+    StickerPackRepository::set_phrases(&state.db_pool, pack.persona_id, &phrases).await?;
+    tracing::info!("Added {} stickers to pack {}", added, pack.name);
+    Ok(added)
+}
+
+/// Add new catchphrases to every pack once a month
+pub async fn sticker_worker(state: AppState) {
+    tracing::info!("Sticker pack worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let due = chrono::Utc::now() - chrono::Duration::days(REFRESH_DAYS);
+        let packs = match StickerPackRepository::list_due(&state.db_pool, due).await {
+            Ok(packs) => packs,
+            Err(e) => {
+                tracing::error!("Failed to fetch sticker packs: {}", e);
+                continue;
+            }
+        };
+
+        for pack in packs {
+            // Waits for the account to come back online
+            let handle = match state.get_userbot(pack.account_id).await {
+                Some(handle) => handle,
+                None => continue,
+            };
+            if let Err(e) = refresh_pack(&state, &handle.client, &pack).await {
+                tracing::warn!("Failed to refresh sticker pack {}: {}", pack.name, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(text: &str, responses: i64) -> AnsweredReply {
+        AnsweredReply { persona_id: Some(1), responses, reply: text.to_string(), user_text: None }
+    }
+
+    #[test]
+    fn picks_short_answered_replies() {
+        let replies = [
+            reply("ну такое", 3),
+            reply("Ну  такое", 4),
+            reply("база", 5),
+            reply("это очень длинный ответ, который никак не поместится на стикер", 50),
+            reply("кек||лол", 1),
+        ];
+        assert_eq!(catchphrases(&replies, &[], 10), vec!["ну такое", "база", "кек лол"]);
+        assert_eq!(catchphrases(&replies, &["БАЗА".to_string()], 1), vec!["ну такое"]);
+    }
+
+    #[test]
+    fn wraps_words_into_lines() {
+        let fits = |line: &str| line.chars().count() <= 10;
+        assert_eq!(wrap("ну это вообще база", fits), vec!["ну это", "вообще", "база"]);
+        assert_eq!(wrap("сверхдлинноеслово ок", fits), vec!["сверхдлинноеслово", "ок"]);
+        assert!(wrap("   ", fits).is_empty());
+    }
+}
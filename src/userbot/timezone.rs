@@ -0,0 +1,99 @@
+use crate::db::AccountChat;
+use chrono::{DateTime, Datelike, Timelike};
+use chrono_tz::Tz;
+
+/// Country calling code prefixes and their most common timezone, longest prefixes first
+const PHONE_TIMEZONES: &[(&str, &str)] = &[
+    ("375", "Europe/Minsk"),
+    ("380", "Europe/Kyiv"),
+    ("998", "Asia/Tashkent"),
+    ("995", "Asia/Tbilisi"),
+    ("994", "Asia/Baku"),
+    ("374", "Asia/Yerevan"),
+    ("371", "Europe/Riga"),
+    ("370", "Europe/Vilnius"),
+    ("372", "Europe/Tallinn"),
+    ("373", "Europe/Chisinau"),
+    ("77", "Asia/Almaty"),
+    ("49", "Europe/Berlin"),
+    ("44", "Europe/London"),
+    ("48", "Europe/Warsaw"),
+    ("90", "Europe/Istanbul"),
+    ("33", "Europe/Paris"),
+    ("39", "Europe/Rome"),
+    ("34", "Europe/Madrid"),
+    ("81", "Asia/Tokyo"),
+    ("86", "Asia/Shanghai"),
+    ("91", "Asia/Kolkata"),
+    ("7", "Europe/Moscow"),
+    ("1", "America/New_York"),
+];
+
+const WEEKDAYS_RU: [&str; 7] = ["понедельник", "вторник", "среда", "четверг", "пятница", "суббота", "воскресенье"];
+
+/// Parse an IANA timezone name ("Europe/Moscow")
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.trim().parse().ok()
+}
+
+/// Guess a timezone from an account's phone number
+pub fn suggest_timezone(phone: &str) -> Option<&'static str> {
+    let digits: String = phone.chars().filter(|c| c.is_ascii_digit()).collect();
+    PHONE_TIMEZONES
+        .iter()
+        .find(|(prefix, _)| digits.starts_with(prefix))
+        .map(|(_, tz)| *tz)
+}
+
+/// Timezone of a chat, falling back to the configured default
+pub fn chat_timezone(chat: Option<&AccountChat>, default: Tz) -> Tz {
+    chat.and_then(|c| c.timezone.as_deref())
+        .and_then(parse_timezone)
+        .unwrap_or(default)
+}
+
+/// Current time in a chat's timezone
+pub fn chat_now(chat: Option<&AccountChat>, default: Tz) -> DateTime<Tz> {
+    chrono::Utc::now().with_timezone(&chat_timezone(chat, default))
+}
+
+/// Fill `{date}`, `{time}` and `{weekday}` in a prompt. Prompts without any of
+/// them get the local time appended so the persona always knows what time it is.
+pub fn with_local_time(prompt: String, now: DateTime<Tz>) -> String {
+    let date = now.format("%d.%m.%Y").to_string();
+    let time = format!("{:02}:{:02}", now.hour(), now.minute());
+    let weekday = WEEKDAYS_RU[now.weekday().num_days_from_monday() as usize];
+
+    if ["{date}", "{time}", "{weekday}"].iter().any(|v| prompt.contains(v)) {
+        return prompt
+            .replace("{date}", &date)
+            .replace("{time}", &time)
+            .replace("{weekday}", weekday);
+    }
+
+    format!("{}\n\n[ВРЕМЯ]\nСейчас {}, {}, {} по местному времени.", prompt, weekday, date, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn suggests_from_phone_prefix() {
+        assert_eq!(suggest_timezone("+7 999 123-45-67"), Some("Europe/Moscow"));
+        assert_eq!(suggest_timezone("+77011234567"), Some("Asia/Almaty"));
+        assert_eq!(suggest_timezone("+375291234567"), Some("Europe/Minsk"));
+        assert_eq!(suggest_timezone("+0000"), None);
+    }
+
+    #[test]
+    fn fills_time_variables() {
+        let now = chrono_tz::Europe::Moscow.with_ymd_and_hms(2026, 3, 2, 9, 5, 0).unwrap();
+        assert_eq!(
+            with_local_time("сегодня {weekday}, {time}".to_string(), now),
+            "сегодня понедельник, 09:05"
+        );
+        assert!(with_local_time("ты бот".to_string(), now).ends_with("понедельник, 02.03.2026, 09:05 по местному времени."));
+    }
+}
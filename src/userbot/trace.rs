@@ -0,0 +1,164 @@
+use super::worker::IncomingMessage;
+use crate::{db::TraceRepository, state::AppState};
+use std::time::Instant;
+
+/// Telegram links carry server message ids; TDLib ids are those shifted by 20 bits
+const SERVER_ID_SHIFT: u32 = 20;
+
+/// Supergroup and channel ids in links drop this prefix
+const CHANNEL_ID_OFFSET: i64 = 1_000_000_000_000;
+
+/// A random id tying together the log lines and spans of one message's way through the pipeline
+pub fn correlation_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+/// How long each step of answering a message took
+#[derive(Debug)]
+pub struct ReplyTrace {
+    pub correlation_id: String,
+    received_at: Instant,
+    last: Instant,
+    stages: Vec<(&'static str, u64)>,
+}
+
+impl ReplyTrace {
+    /// Starts when the pipeline picks the message up: reading it (media included) and waiting
+    /// for the rest of a batch are its first two stages
+    pub fn start(incoming: &IncomingMessage) -> Self {
+        let mut trace = Self {
+            correlation_id: incoming.correlation_id.clone(),
+            received_at: incoming.received_at,
+            last: incoming.queued_at,
+            stages: vec![("receive", incoming.queued_at.duration_since(incoming.received_at).as_millis() as u64)],
+        };
+        trace.mark("debounce");
+        trace
+    }
+
+    /// Close the stage running since the previous mark
+    pub fn mark(&mut self, stage: &'static str) {
+        let now = Instant::now();
+        self.stages.push((stage, now.duration_since(self.last).as_millis() as u64));
+        self.last = now;
+    }
+
+    pub fn total_ms(&self) -> u64 {
+        self.last.duration_since(self.received_at).as_millis() as u64
+    }
+
+    pub fn stages(&self) -> &[(&'static str, u64)] {
+        &self.stages
+    }
+
+    /// "debounce 1200ms, rag 85ms, ..." for log lines
+    pub fn summary(&self) -> String {
+        self.stages
+            .iter()
+            .map(|(stage, ms)| format!("{} {}ms", stage, ms))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Store the timings of a sent reply for /trace
+pub async fn save(state: &AppState, account_id: i64, incoming: &IncomingMessage, trace: &ReplyTrace, sent_ids: &[i64]) {
+    let stages = serde_json::Value::Array(
+        trace
+            .stages()
+            .iter()
+            .map(|(stage, ms)| serde_json::json!([stage, ms]))
+            .collect(),
+    );
+    if let Err(e) = TraceRepository::record(
+        &state.db_pool,
+        account_id,
+        incoming.chat_id,
+        &trace.correlation_id,
+        incoming.message_id,
+        sent_ids.first().copied(),
+        &stages.to_string(),
+        trace.total_ms() as i64,
+    )
+    .await
+    {
+        tracing::warn!("Failed to store reply trace {}: {}", trace.correlation_id, e);
+    }
+}
+
+/// Stage timings stored by `save`
+pub fn parse_stages(stages: &str) -> Vec<(String, u64)> {
+    serde_json::from_str(stages).unwrap_or_default()
+}
+
+/// Chat (when the link names it by id) and TDLib message id of a message link:
+/// t.me/c/<chat>/<message>, t.me/c/<chat>/<thread>/<message> or t.me/<username>/<message>
+pub fn parse_message_link(link: &str) -> Option<(Option<i64>, i64)> {
+    let path = link
+        .trim()
+        .trim_start_matches("https://")
+        .trim_start_matches("http://")
+        .trim_start_matches("www.")
+        .strip_prefix("t.me/")?;
+    let path = path.split(['?', '#']).next()?;
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+
+    let server_id: i64 = parts.last()?.parse().ok()?;
+    let message_id = server_id << SERVER_ID_SHIFT;
+    match parts.as_slice() {
+        ["c", chat, ..] if parts.len() >= 3 => {
+            let chat: i64 = chat.parse().ok()?;
+            Some((Some(-(CHANNEL_ID_OFFSET + chat)), message_id))
+        }
+        [_username, _] | [_username, _, _] => Some((None, message_id)),
+        _ => None,
+    }
+}
+
+/// A link opening a message: t.me/c/... for supergroups and channels, the sender's profile in private chats.
+/// Basic groups have no message links.
+pub fn message_link(chat_id: i64, message_id: i64, sender_id: i64) -> Option<String> {
+    if chat_id > 0 {
+        return Some(format!("tg://user?id={}", if sender_id > 0 { sender_id } else { chat_id }));
+    }
+    let channel = -chat_id - CHANNEL_ID_OFFSET;
+    (channel > 0).then(|| format!("https://t.me/c/{}/{}", channel, message_id >> SERVER_ID_SHIFT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_private_and_public_links() {
+        assert_eq!(
+            parse_message_link("https://t.me/c/1234567890/42"),
+            Some((Some(-1_001_234_567_890), 42 << 20))
+        );
+        assert_eq!(
+            parse_message_link("t.me/c/1234567890/7/42?single"),
+            Some((Some(-1_001_234_567_890), 42 << 20))
+        );
+        assert_eq!(parse_message_link("https://t.me/somegroup/42"), Some((None, 42 << 20)));
+        assert_eq!(parse_message_link("https://example.com/c/1/2"), None);
+        assert_eq!(parse_message_link("https://t.me/somegroup"), None);
+    }
+
+    #[test]
+    fn links_back_to_messages() {
+        let link = message_link(-1_001_234_567_890, 42 << 20, 7).unwrap();
+        assert_eq!(parse_message_link(&link), Some((Some(-1_001_234_567_890), 42 << 20)));
+        assert_eq!(link, "https://t.me/c/1234567890/42");
+        assert_eq!(message_link(55, 1 << 20, 55).as_deref(), Some("tg://user?id=55"));
+        assert_eq!(message_link(-4567, 1 << 20, 7), None);
+    }
+
+    #[test]
+    fn round_trips_stages() {
+        assert_eq!(
+            parse_stages(r#"[["debounce", 1200], ["llm", 3400]]"#),
+            vec![("debounce".to_string(), 1200), ("llm".to_string(), 3400)]
+        );
+        assert!(parse_stages("not json").is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+use super::{
+    formatting::{self, FormatMode},
+    worker::IncomingMessage,
+};
+use crate::{
+    ai::{
+        media_memory::{MediaKind, MediaRef},
+        polls::PollDraft,
+    },
+    db::AccountChat,
+    state::AppState,
+};
+use anyhow::Result;
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::*,
+};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+/// Where the reply pipeline sends its output. Userbots talk to TDLib;
+/// `puppeteer simulate` swaps in a mock so the pipeline runs without Telegram.
+pub trait ChatTransport: Send + Sync {
+    /// Mark a message as read
+    fn mark_read(&self, chat_id: i64, message_id: i64) -> impl Future<Output = Result<()>> + Send;
+
+    /// Show (true) or cancel (false) the typing indicator
+    fn set_typing(&self, chat_id: i64, typing: bool) -> impl Future<Output = Result<()>> + Send;
+
+    /// Send one chunk of a reply, optionally as a reply to a message.
+    /// Returns the sent message's ID (TDLib's temporary one until the send succeeds).
+    fn send_text(
+        &self,
+        chat_id: i64,
+        text: &str,
+        mode: FormatMode,
+        reply_to: Option<i64>,
+    ) -> impl Future<Output = Result<i64>> + Send;
+
+    /// Send a native poll or quiz; returns the TDLib message id
+    fn send_poll(
+        &self,
+        chat_id: i64,
+        poll: &PollDraft,
+        reply_to: Option<i64>,
+    ) -> impl Future<Output = Result<i64>> + Send;
+
+    /// Send a photo or GIF Telegram already has, by its remote file id
+    fn send_media(
+        &self,
+        chat_id: i64,
+        media: &MediaRef,
+        reply_to: Option<i64>,
+    ) -> impl Future<Output = Result<i64>> + Send;
+
+    /// Pin (silently) or unpin a message
+    fn pin_message(&self, chat_id: i64, message_id: i64, pin: bool) -> impl Future<Output = Result<()>> + Send;
+
+    /// Wait out a humanization delay
+    fn pause(&self, duration: Duration) -> impl Future<Output = ()> + Send;
+
+    /// Tell the owners about a failure
+    fn notify_owner(&self, state: &AppState, message: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Called once the reply pipeline is done with a message, or with the `messages`
+    /// the debouncer merged into it; `ok` is false when the pipeline failed
+    fn answered(&self, _incoming: &IncomingMessage, _messages: usize, _ok: bool) {}
+}
+
+/// Send a whole text the way replies go out: in the chat's format mode, split into
+/// messages of at most MAX_MESSAGE_LENGTH. Returns the sent message IDs.
+pub async fn send_split<T: ChatTransport>(
+    transport: &T,
+    state: &AppState,
+    chat_id: i64,
+    chat: Option<&AccountChat>,
+    text: &str,
+) -> Result<Vec<i64>> {
+    let mode = FormatMode::for_chat(chat);
+    let mut sent_ids = Vec::new();
+    for part in formatting::split_long_message(text, state.config.max_message_length) {
+        sent_ids.push(transport.send_text(chat_id, &part, mode, None).await?);
+    }
+    Ok(sent_ids)
+}
+
+/// The real transport: a userbot's TDLib client
+pub struct TdTransport {
+    client: Arc<Mutex<Client<TdJson>>>,
+}
+
+impl TdTransport {
+    pub fn new(client: Arc<Mutex<Client<TdJson>>>) -> Self {
+        Self { client }
+    }
+}
+
+impl ChatTransport for TdTransport {
+    async fn mark_read(&self, chat_id: i64, message_id: i64) -> Result<()> {
+        let view_messages = ViewMessages::builder()
+            .chat_id(chat_id)
+            .message_ids(vec![message_id])
+            .force_read(true)
+            .build();
+
+        self.client.lock().await.view_messages(&view_messages).await?;
+        Ok(())
+    }
+
+    async fn set_typing(&self, chat_id: i64, typing: bool) -> Result<()> {
+        let action = if typing {
+            ChatAction::Typing(ChatActionTyping::builder().build())
+        } else {
+            ChatAction::Cancel(ChatActionCancel::builder().build())
+        };
+        let send_action = SendChatAction::builder().chat_id(chat_id).action(action).build();
+
+        self.client.lock().await.send_chat_action(&send_action).await?;
+        Ok(())
+    }
+
+    async fn send_text(&self, chat_id: i64, text: &str, mode: FormatMode, reply_to: Option<i64>) -> Result<i64> {
+        let client_lock = self.client.lock().await;
+
+        let formatted = formatting::format_reply(&client_lock, text, mode).await;
+        let input_message = InputMessageContent::InputMessageText(
+            InputMessageText::builder()
+                .text(formatted)
+                .build()
+        );
+
+        let mut send_message_builder = SendMessage::builder();
+        send_message_builder
+            .chat_id(chat_id)
+            .input_message_content(input_message);
+
+        if let Some(message_id) = reply_to {
+            send_message_builder.reply_to_message_id(message_id);
+        }
+
+        let sent = client_lock.send_message(&send_message_builder.build()).await?;
+        Ok(sent.id())
+    }
+
+    async fn send_poll(&self, chat_id: i64, poll: &PollDraft, reply_to: Option<i64>) -> Result<i64> {
+        let poll_type = match poll.correct_option {
+            Some(correct) => PollType::Quiz(PollTypeQuiz::builder().correct_option_id(correct as i32).build()),
+            None => PollType::Regular(PollTypeRegular::builder().allow_multiple_answers(false).build()),
+        };
+        let input_message = InputMessageContent::InputMessagePoll(
+            InputMessagePoll::builder()
+                .question(poll.question.clone())
+                .options(poll.options.clone())
+                .is_anonymous(false)
+                .type_(poll_type)
+                .build(),
+        );
+
+        let mut send_message_builder = SendMessage::builder();
+        send_message_builder
+            .chat_id(chat_id)
+            .input_message_content(input_message);
+
+        if let Some(message_id) = reply_to {
+            send_message_builder.reply_to_message_id(message_id);
+        }
+
+        let sent = self.client.lock().await.send_message(&send_message_builder.build()).await?;
+        Ok(sent.id())
+    }
+
+    async fn send_media(&self, chat_id: i64, media: &MediaRef, reply_to: Option<i64>) -> Result<i64> {
+        let file = InputFile::Remote(InputFileRemote::builder().id(media.remote_file_id.clone()).build());
+        let input_message = match media.kind {
+            MediaKind::Photo => InputMessageContent::InputMessagePhoto(InputMessagePhoto::builder().photo(file).build()),
+            MediaKind::Animation => {
+                InputMessageContent::InputMessageAnimation(InputMessageAnimation::builder().animation(file).build())
+            }
+        };
+
+        let mut send_message_builder = SendMessage::builder();
+        send_message_builder
+            .chat_id(chat_id)
+            .input_message_content(input_message);
+
+        if let Some(message_id) = reply_to {
+            send_message_builder.reply_to_message_id(message_id);
+        }
+
+        let sent = self.client.lock().await.send_message(&send_message_builder.build()).await?;
+        Ok(sent.id())
+    }
+
+    async fn pin_message(&self, chat_id: i64, message_id: i64, pin: bool) -> Result<()> {
+        let client_lock = self.client.lock().await;
+        if pin {
+            let request = PinChatMessage::builder()
+                .chat_id(chat_id)
+                .message_id(message_id)
+                .disable_notification(true)
+                .only_for_self(false)
+                .build();
+            client_lock.pin_chat_message(&request).await?;
+        } else {
+            let request = UnpinChatMessage::builder().chat_id(chat_id).message_id(message_id).build();
+            client_lock.unpin_chat_message(&request).await?;
+        }
+        Ok(())
+    }
+
+    async fn pause(&self, duration: Duration) {
+        tokio::time::sleep(duration).await;
+    }
+
+    async fn notify_owner(&self, state: &AppState, message: &str) -> Result<()> {
+        super::worker::notify_owner(state, message).await
+    }
+}
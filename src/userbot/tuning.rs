@@ -0,0 +1,284 @@
+use crate::{
+    db::{AccountChat, AccountRepository, ChatRepository, TuningArm, TuningRepository},
+    state::AppState,
+};
+use anyhow::{Context, Result};
+use rust_tdlib::{
+    client::{tdlib_client::TdJson, Client},
+    types::GetMessage,
+};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// How often tuned chats are checked for a finished epoch
+const CHECK_INTERVAL_SECS: u64 = 30 * 60;
+
+/// Probabilities tried between the owner's bounds
+const ARM_COUNT: i64 = 5;
+
+/// Share of epochs spent on a random probability instead of the best one
+const EPSILON: f64 = 0.2;
+
+/// An epoch with fewer replies says nothing about engagement
+const MIN_EPOCH_REPLIES: i64 = 3;
+
+/// One "shut up" outweighs a reply
+const NEGATIVE_WEIGHT: f64 = 2.0;
+
+/// An epoch that never gets enough replies is restarted after this many epoch lengths
+const STALE_EPOCHS: i64 = 4;
+
+/// Messages telling the account to be quiet
+const NEGATIVE_MARKERS: &[&str] = &[
+    "заткнись",
+    "замолчи",
+    "помолчи",
+    "отстань",
+    "хватит спамить",
+    "хватит флудить",
+    "не флуди",
+    "завали",
+    "уймись",
+    "бот, хватит",
+    "shut up",
+    "stfu",
+    "stop spamming",
+];
+
+/// Engagement collected during one epoch
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Signals {
+    /// Replies the account sent
+    pub replies: i64,
+    pub replies_to_bot: i64,
+    pub negatives: i64,
+}
+
+impl Signals {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} replies sent, {} answered, {} negative",
+            self.replies, self.replies_to_bot, self.negatives
+        )
+    }
+}
+
+/// Engagement per reply sent; `None` when too few replies went out to judge
+pub fn reward(signals: &Signals) -> Option<f64> {
+    if signals.replies < MIN_EPOCH_REPLIES {
+        return None;
+    }
+    let engagement = signals.replies_to_bot as f64 - NEGATIVE_WEIGHT * signals.negatives as f64;
+    Some(engagement / signals.replies as f64)
+}
+
+/// Probabilities the bandit chooses from, evenly spread between the bounds
+pub fn arms(min: i64, max: i64) -> Vec<i64> {
+    let (min, max) = (min.min(max), min.max(max));
+    let mut arms: Vec<i64> = (0..ARM_COUNT).map(|i| min + (max - min) * i / (ARM_COUNT - 1)).collect();
+    arms.dedup();
+    arms
+}
+
+pub fn is_negative(text: &str) -> bool {
+    let lowered = text.to_lowercase();
+    NEGATIVE_MARKERS.iter().any(|marker| lowered.contains(marker))
+}
+
+/// Epsilon-greedy: untried probabilities first (nearest to the current one), then mostly the best average,
+/// sometimes a random one. Returns the next probability and why.
+pub fn choose(arms: &[i64], stats: &[TuningArm], current: i64, explore_roll: f64, random_pick: usize) -> (i64, String) {
+    let stats_of = |arm: i64| stats.iter().find(|s| s.probability == arm && s.pulls > 0);
+
+    if let Some(untried) = arms
+        .iter()
+        .filter(|arm| stats_of(**arm).is_none())
+        .min_by_key(|arm| (**arm - current).abs())
+    {
+        return (*untried, format!("trying {}%, not measured yet", untried));
+    }
+
+    if arms.is_empty() {
+        return (current, "no probabilities to choose from".to_string());
+    }
+
+    if explore_roll < EPSILON {
+        let arm = arms[random_pick % arms.len()];
+        return (
+            arm,
+            format!("exploring {}% (a random pick in {:.0}% of epochs)", arm, EPSILON * 100.0),
+        );
+    }
+
+    let mean = |s: &TuningArm| s.reward_sum / s.pulls as f64;
+    let best = arms
+        .iter()
+        .filter_map(|arm| stats_of(*arm))
+        .max_by(|a, b| mean(a).partial_cmp(&mean(b)).unwrap_or(std::cmp::Ordering::Equal));
+    match best {
+        Some(best) => (
+            best.probability,
+            format!(
+                "{}% has the best average engagement: {:.2} over {} epochs",
+                best.probability,
+                mean(best),
+                best.pulls
+            ),
+        ),
+        None => (current, "keeping the current probability".to_string()),
+    }
+}
+
+/// Count a message of a tuned chat towards the running epoch
+pub async fn observe(
+    state: &AppState,
+    account_id: i64,
+    client: &Arc<Mutex<Client<TdJson>>>,
+    chat: &AccountChat,
+    reply_to_message_id: i64,
+    text: &str,
+) {
+    let reply_to_bot = reply_to_message_id != 0 && is_own_message(client, chat.chat_id, reply_to_message_id).await;
+    let negative = is_negative(text);
+    if !reply_to_bot && !negative {
+        return;
+    }
+
+    if let Err(e) = TuningRepository::record_signal(&state.db_pool, account_id, chat.chat_id, reply_to_bot, negative).await {
+        tracing::debug!("Failed to record tuning signal in chat {}: {}", chat.chat_id, e);
+    }
+}
+
+async fn is_own_message(client: &Arc<Mutex<Client<TdJson>>>, chat_id: i64, message_id: i64) -> bool {
+    let request = GetMessage::builder().chat_id(chat_id).message_id(message_id).build();
+    match client.lock().await.get_message(&request).await {
+        Ok(message) => message.is_outgoing(),
+        Err(_) => false,
+    }
+}
+
+/// Close finished epochs of tuned chats and move them to the next probability
+pub async fn tuning_worker(state: AppState) {
+    tracing::info!("Reply probability tuning worker started");
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let chats = match ChatRepository::list_tuned(&state.db_pool).await {
+            Ok(chats) => chats,
+            Err(e) => {
+                tracing::error!("Failed to fetch tuned chats: {}", e);
+                continue;
+            }
+        };
+
+        for chat in chats {
+            if let Err(e) = tune_chat(&state, &chat).await {
+                tracing::warn!("Failed to tune reply probability of chat {}: {}", chat.chat_id, e);
+            }
+        }
+    }
+}
+
+async fn tune_chat(state: &AppState, chat: &AccountChat) -> Result<()> {
+    let (min, max) = match (chat.tune_min_probability, chat.tune_max_probability) {
+        (Some(min), Some(max)) => (min, max),
+        _ => return Ok(()),
+    };
+    let (account_id, chat_id) = (chat.account_id, chat.chat_id);
+    let epoch_hours = state.config.tuning_epoch_hours;
+
+    let epoch = match TuningRepository::get_epoch(&state.db_pool, account_id, chat_id).await? {
+        Some(epoch) => epoch,
+        None => {
+            let account = AccountRepository::get_by_id(&state.db_pool, account_id)
+                .await?
+                .context("Account not found")?;
+            let current = chat.reply_probability.unwrap_or(account.reply_probability);
+            let start = current.clamp(min, max);
+            if start != current {
+                ChatRepository::set_reply_probability(&state.db_pool, account_id, chat_id, Some(start)).await?;
+                TuningRepository::log_decision(
+                    &state.db_pool,
+                    account_id,
+                    chat_id,
+                    current,
+                    start,
+                    0.0,
+                    &format!("moved into the bounds {}–{}%", min, max),
+                )
+                .await?;
+            }
+            TuningRepository::start_epoch(&state.db_pool, account_id, chat_id, start).await?;
+            return Ok(());
+        }
+    };
+
+    if !TuningRepository::epoch_is_older_than(&state.db_pool, account_id, chat_id, epoch_hours).await? {
+        return Ok(());
+    }
+
+    let replies = TuningRepository::epoch_activity(&state.db_pool, account_id, chat_id).await?;
+    let signals = Signals { replies, replies_to_bot: epoch.replies_to_bot, negatives: epoch.negatives };
+    let reward = match reward(&signals) {
+        Some(reward) => reward,
+        None => {
+            // A quiet chat: wait for more replies, but don't keep counting forever
+            if TuningRepository::epoch_is_older_than(&state.db_pool, account_id, chat_id, epoch_hours * STALE_EPOCHS)
+                .await?
+            {
+                TuningRepository::start_epoch(&state.db_pool, account_id, chat_id, epoch.probability).await?;
+            }
+            return Ok(());
+        }
+    };
+
+    TuningRepository::record_pull(&state.db_pool, account_id, chat_id, epoch.probability, reward).await?;
+    let stats = TuningRepository::arms(&state.db_pool, account_id, chat_id).await?;
+    let (next, why) = choose(&arms(min, max), &stats, epoch.probability, rand::random::<f64>(), rand::random::<usize>());
+    let reason = format!("{:.2} engagement at {}% ({}); {}", reward, epoch.probability, signals.describe(), why);
+
+    ChatRepository::set_reply_probability(&state.db_pool, account_id, chat_id, Some(next)).await?;
+    TuningRepository::start_epoch(&state.db_pool, account_id, chat_id, next).await?;
+    TuningRepository::log_decision(&state.db_pool, account_id, chat_id, epoch.probability, next, reward, &reason).await?;
+
+    tracing::info!("Tuned reply probability of chat {} (account {}): {}", chat_id, account_id, reason);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn arm(probability: i64, pulls: i64, reward_sum: f64) -> TuningArm {
+        TuningArm { probability, pulls, reward_sum }
+    }
+
+    #[test]
+    fn spreads_arms_between_bounds() {
+        assert_eq!(arms(10, 50), vec![10, 20, 30, 40, 50]);
+        assert_eq!(arms(30, 30), vec![30]);
+        assert_eq!(arms(5, 7), vec![5, 6, 7]);
+    }
+
+    #[test]
+    fn rewards_engagement_and_punishes_negatives() {
+        let signals = Signals { replies: 10, replies_to_bot: 6, negatives: 1 };
+        assert_eq!(reward(&signals), Some(0.4));
+        assert_eq!(reward(&Signals { replies: 2, ..signals }), None);
+        assert!(is_negative("Бот, заткнись уже"));
+        assert!(!is_negative("ну ты даешь"));
+    }
+
+    #[test]
+    fn tries_unmeasured_arms_first_then_exploits() {
+        let arms = arms(10, 30);
+        let (next, why) = choose(&arms, &[arm(20, 1, 0.5)], 20, 0.9, 0);
+        assert_eq!(next, 15);
+        assert!(why.contains("not measured"));
+
+        let stats = [arm(10, 2, 0.2), arm(15, 1, 0.1), arm(20, 3, 1.8), arm(25, 1, 0.3), arm(30, 1, 0.0)];
+        assert_eq!(choose(&arms, &stats, 10, 0.9, 0).0, 20);
+        assert_eq!(choose(&arms, &stats, 10, 0.1, 4).0, 30);
+    }
+}
@@ -1,3 +1,4 @@
+use super::transport::ChatTransport;
 use crate::{
     db::{AccountRepository, MessageRole, NewMessage},
     state::{AppState, UserbotHandle},
@@ -9,7 +10,8 @@ use rust_tdlib::{
 };
 use std::sync::Arc;
 use std::collections::HashMap;
-use tokio::sync::{Mutex, RwLock};
+use tokio::sync::{mpsc, Mutex, RwLock};
+use tracing::Instrument;
 
 type TdClient = Client<TdJson>;
 type TdWorker = Worker<ConsoleAuthStateHandler, TdJson>;
@@ -23,6 +25,40 @@ lazy_static::lazy_static! {
 // Casual responses for stickers
 const STICKER_RESPONSES: &[&str] = &["ахах", "жиза", "норм", "кек", "лол", "хд"];
 
+// Sent while a voice note waits for a free Whisper slot
+const VOICE_PLACEHOLDERS: &[&str] = &["сек, щас послушаю", "ща послушаю", "погоди, наушники найду"];
+
+/// At most one voice placeholder per chat in this window
+const VOICE_PLACEHOLDER_COOLDOWN_SECS: u64 = 600;
+
+lazy_static::lazy_static! {
+    static ref VOICE_PLACEHOLDER_SENT: std::sync::Mutex<HashMap<(i64, i64), std::time::Instant>> =
+        std::sync::Mutex::new(HashMap::new());
+    /// Progress messages of long transcriptions per (account, chat, temporary id)
+    static ref VOICE_PROGRESS_IDS: std::sync::Mutex<HashMap<(i64, i64, i64), VoiceProgress>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
+/// Where a transcription's progress message stands with TDLib
+#[derive(Debug, Clone, Copy)]
+enum VoiceProgress {
+    /// Sent, TDLib hasn't confirmed it yet
+    Sending,
+    /// Confirmed under this id
+    Sent(i64),
+    /// The transcription ended before the confirmation; delete it once it arrives
+    Abandoned,
+}
+
+/// How long a chat's message queue waits for another message before its task exits
+const CHAT_QUEUE_IDLE_SECS: u64 = 60;
+
+lazy_static::lazy_static! {
+    /// Incoming messages per (account, chat), handled one at a time in arrival order
+    static ref CHAT_QUEUES: std::sync::Mutex<HashMap<(i64, i64), mpsc::UnboundedSender<Message>>> =
+        std::sync::Mutex::new(HashMap::new());
+}
+
 /// Default advanced adaptive system prompt for human-like behavior with extreme dryness
 /// NOTE: This is a fallback. For diverse "horde" behavior, use personas::generate_random_persona()
 /// or personas::generate_persona_by_name() when creating new accounts.
@@ -52,7 +88,36 @@ Assistant: <IGNORE>
 User: ну и че мы делать будем с этой базой данных?
 Assistant: хз вообще || надо думать || я бы снес ее нахрен и заново поднял"#;
 
+/// Bounds of the exponential backoff used when restarting a crashed client
+const RESTART_BACKOFF_MIN_SECS: u64 = 2;
+const RESTART_BACKOFF_MAX_SECS: u64 = 300;
+/// A client that stayed up this long is considered healthy and resets the backoff
+const HEALTHY_UPTIME_SECS: u64 = 600;
+/// Buffered TDLib updates per client
+const UPDATE_CHANNEL_CAPACITY: usize = 1000;
+
+/// A bound TDLib client together with its worker and update stream
+struct ClientSession {
+    worker: TdWorker,
+    client: Arc<Mutex<TdClient>>,
+    updates: mpsc::Receiver<Box<Update>>,
+}
+
+/// Why the event loop returned
+enum LoopExit {
+    /// Shutdown was requested via the handle
+    Shutdown,
+    /// TDLib closed the client or the update stream ended; worth restarting
+    Closed,
+    /// The session was revoked; restarting would need a new login
+    LoggedOut,
+}
+
 pub async fn spawn_userbot(state: AppState, account_id: i64) -> Result<()> {
+    if state.config.safe_mode {
+        anyhow::bail!("Userbots are disabled in safe mode");
+    }
+
     if state.is_userbot_running(account_id).await {
         tracing::warn!("Userbot {} is already running", account_id);
         return Ok(());
@@ -64,6 +129,25 @@ pub async fn spawn_userbot(state: AppState, account_id: i64) -> Result<()> {
 
     tracing::info!("Starting userbot for account {}: {}", account_id, account.phone_number);
 
+    let shutdown_tx = Arc::new(tokio::sync::Notify::new());
+
+    // The first start happens inline so callers see auth/config errors right away
+    let session = start_client(&state, &account, shutdown_tx.clone()).await?;
+
+    tokio::spawn(supervise_userbot(state, account_id, shutdown_tx, session));
+
+    tracing::info!("Userbot {} started successfully", account_id);
+    Ok(())
+}
+
+/// Create a TDLib client wired to an update channel and register its handle
+async fn start_client(
+    state: &AppState,
+    account: &crate::db::models::Account,
+    shutdown_tx: Arc<tokio::sync::Notify>,
+) -> Result<ClientSession> {
+    let (updates_tx, updates_rx) = mpsc::channel::<Box<Update>>(UPDATE_CHANNEL_CAPACITY);
+
     let mut worker: TdWorker = Worker::builder().build()?;
     worker.start();
 
@@ -80,84 +164,231 @@ pub async fn spawn_userbot(state: AppState, account_id: i64) -> Result<()> {
 
     let client: TdClient = Client::builder()
         .with_tdlib_parameters(tdlib_params)
+        .with_updates_sender(updates_tx)
         .build()?;
 
-    let client = worker.bind_client(client).await?;
+    let client = match worker.bind_client(client).await {
+        Ok(client) => client,
+        Err(e) => {
+            worker.stop();
+            return Err(e).context("Failed to authorize TDLib client");
+        }
+    };
     let client = Arc::new(Mutex::new(client));
 
-    let shutdown_tx = Arc::new(tokio::sync::Notify::new());
+    state
+        .add_userbot(UserbotHandle {
+            client: client.clone(),
+            account_id: account.id,
+            phone_number: account.phone_number.clone(),
+            shutdown_tx,
+        })
+        .await;
+
+    Ok(ClientSession {
+        worker,
+        client,
+        updates: updates_rx,
+    })
+}
 
-    let handle = UserbotHandle {
-        client: client.clone(),
-        account_id,
-        phone_number: account.phone_number.clone(),
-        shutdown_tx: shutdown_tx.clone(),
-    };
+/// Run the event loop and restart the client with exponential backoff when it dies
+async fn supervise_userbot(
+    state: AppState,
+    account_id: i64,
+    shutdown: Arc<tokio::sync::Notify>,
+    session: ClientSession,
+) {
+    let mut session = Some(session);
+    let mut backoff = RESTART_BACKOFF_MIN_SECS;
 
-    state.add_userbot(handle).await;
+    loop {
+        if let Some(mut current) = session.take() {
+            let started = std::time::Instant::now();
 
-    let state_clone = state.clone();
-    let account_clone = account.clone();
-    tokio::spawn(async move {
-        if let Err(e) = run_userbot_loop(state_clone.clone(), account_clone, client, shutdown_tx).await {
-            tracing::error!("Userbot {} error: {}", account_id, e);
-            
-            // Notify owner about error
-            if let Err(notify_err) = notify_owner(&state_clone, &format!("❌ Userbot {} error: {}", account_id, e)).await {
-                tracing::error!("Failed to notify owner: {}", notify_err);
+            // Reload settings on every (re)start so restarts pick up admin changes
+            let exit = match AccountRepository::get_by_id(&state.db_pool, account_id).await {
+                Ok(Some(account)) => {
+                    run_userbot_loop(&state, &account, &current.client, &mut current.updates, &shutdown).await
+                }
+                Ok(None) => Ok(LoopExit::Shutdown),
+                Err(e) => Err(e),
+            };
+            current.worker.stop();
+
+            match exit {
+                Ok(LoopExit::Shutdown) => break,
+                Ok(LoopExit::LoggedOut) => {
+                    tracing::warn!("Userbot {} was logged out, not restarting", account_id);
+                    let _ = AccountRepository::set_active(&state.db_pool, account_id, false).await;
+                    if let Err(e) = notify_owner(
+                        &state,
+                        &format!("⚠️ Userbot {} was logged out. Re-add the account to log in again.", account_id),
+                    )
+                    .await
+                    {
+                        tracing::error!("Failed to notify owner: {}", e);
+                    }
+                    break;
+                }
+                Ok(LoopExit::Closed) => {
+                    tracing::warn!("Userbot {} client closed unexpectedly", account_id);
+                }
+                Err(e) => {
+                    tracing::error!("Userbot {} error: {}", account_id, e);
+                }
+            }
+
+            if started.elapsed().as_secs() >= HEALTHY_UPTIME_SECS {
+                backoff = RESTART_BACKOFF_MIN_SECS;
+            }
+
+            if let Err(e) = notify_owner(
+                &state,
+                &format!("❌ Userbot {} crashed, restarting in {}s", account_id, backoff),
+            )
+            .await
+            {
+                tracing::error!("Failed to notify owner: {}", e);
             }
         }
-    });
 
-    tracing::info!("Userbot {} started successfully", account_id);
-    Ok(())
+        tokio::select! {
+            _ = shutdown.notified() => break,
+            _ = tokio::time::sleep(tokio::time::Duration::from_secs(backoff)) => {}
+        }
+
+        let account = match AccountRepository::get_by_id(&state.db_pool, account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => break,
+            Err(e) => {
+                tracing::error!("Failed to load account {} for restart: {}", account_id, e);
+                backoff = (backoff * 2).min(RESTART_BACKOFF_MAX_SECS);
+                continue;
+            }
+        };
+
+        match start_client(&state, &account, shutdown.clone()).await {
+            Ok(restarted) => {
+                tracing::info!("Userbot {} restarted", account_id);
+                session = Some(restarted);
+            }
+            Err(e) => {
+                tracing::error!("Failed to restart userbot {}: {}", account_id, e);
+            }
+        }
+
+        backoff = (backoff * 2).min(RESTART_BACKOFF_MAX_SECS);
+    }
+
+    // A shutdown racing with a restart could have re-registered the handle
+    state.remove_userbot(account_id).await;
+    tracing::info!("Userbot {} supervisor stopped", account_id);
 }
 
 async fn run_userbot_loop(
-    state: AppState,
-    account: crate::db::models::Account,
-    client: Arc<Mutex<TdClient>>,
-    shutdown: Arc<tokio::sync::Notify>,
-) -> Result<()> {
+    state: &AppState,
+    account: &crate::db::models::Account,
+    client: &Arc<Mutex<TdClient>>,
+    updates: &mut mpsc::Receiver<Box<Update>>,
+    shutdown: &tokio::sync::Notify,
+) -> Result<LoopExit> {
     tracing::info!("Userbot {} event loop started", account.id);
 
-    // Create a channel to receive updates from the worker
-    let (_tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<Box<Update>>();
-    
-    // Spawn a task to receive updates from TDLib
-    let client_clone = client.clone();
-    let _account_id = account.id;
-    tokio::spawn(async move {
-        loop {
-            let client_lock = client_clone.lock().await;
-            // Note: In rust-tdlib 0.4, updates are typically received through the Worker
-            // For now, we'll use a simple polling approach
-            // The actual implementation depends on the specific rust-tdlib API
-            drop(client_lock);
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
-    });
-
-    loop {
+    let exit = loop {
         tokio::select! {
             _ = shutdown.notified() => {
                 tracing::info!("Userbot {} received shutdown signal", account.id);
-                break;
+                break LoopExit::Shutdown;
             }
-            Some(update) = rx.recv() => {
-                // Process the update
-                if let Err(e) = process_update(&state, &account, &client, update).await {
-                    tracing::error!("Error processing update for userbot {}: {}", account.id, e);
+            update = updates.recv() => {
+                let update = match update {
+                    Some(update) => update,
+                    None => break LoopExit::Closed,
+                };
+
+                // Auth and connection state drive the supervisor, everything else is content
+                match update.as_ref() {
+                    Update::AuthorizationState(auth_state) => match auth_state.authorization_state() {
+                        AuthorizationState::Ready(_) => {
+                            tracing::info!("Userbot {} is authorized and ready", account.id);
+                        }
+                        AuthorizationState::LoggingOut(_) => break LoopExit::LoggedOut,
+                        AuthorizationState::Closing(_) | AuthorizationState::Closed(_) => {
+                            break LoopExit::Closed;
+                        }
+                        _ => {}
+                    },
+                    Update::ConnectionState(connection) => {
+                        // TDLib reconnects on its own; just make outages visible in logs
+                        tracing::info!("Userbot {} connection state: {:?}", account.id, connection.state());
+                    }
+                    _ => {
+                        if let Err(e) = process_update(state, account, client, update).await {
+                            tracing::error!("Error processing update for userbot {}: {}", account.id, e);
+                        }
+                    }
                 }
             }
-            _ = tokio::time::sleep(tokio::time::Duration::from_secs(1)) => {
-                // Keep alive
-            }
         }
-    }
+    };
 
     tracing::info!("Userbot {} event loop stopped", account.id);
-    Ok(())
+    Ok(exit)
+}
+
+/// Hand a message to its chat's queue, starting the queue's task if the chat has none.
+/// Messages of one chat are handled in order, so slow media never lets a later text overtake it.
+fn enqueue_incoming(
+    state: &AppState,
+    account: &crate::db::models::Account,
+    client: &Arc<Mutex<TdClient>>,
+    message: &Message,
+) {
+    let key = (account.id, message.chat_id());
+    let mut queues = CHAT_QUEUES.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(sender) = queues.get(&key) {
+        if sender.send(message.clone()).is_ok() {
+            return;
+        }
+    }
+
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let _ = sender.send(message.clone());
+    queues.insert(key, sender);
+    tokio::spawn(drain_chat_queue(state.clone(), account.clone(), client.clone(), key, receiver));
+}
+
+/// Handle a chat's messages one by one; exits once the chat has been quiet for a while
+async fn drain_chat_queue(
+    state: AppState,
+    account: crate::db::models::Account,
+    client: Arc<Mutex<TdClient>>,
+    key: (i64, i64),
+    mut receiver: mpsc::UnboundedReceiver<Message>,
+) {
+    let idle = std::time::Duration::from_secs(CHAT_QUEUE_IDLE_SECS);
+    loop {
+        let message = match tokio::time::timeout(idle, receiver.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => return,
+            // Senders only send under the lock, so nothing can slip in between the check and the removal
+            Err(_) => {
+                let mut queues = CHAT_QUEUES.lock().unwrap_or_else(|e| e.into_inner());
+                match receiver.try_recv() {
+                    Ok(message) => message,
+                    Err(_) => {
+                        queues.remove(&key);
+                        return;
+                    }
+                }
+            }
+        };
+
+        if let Err(e) = handle_incoming_message(&state, &account, &client, &message).await {
+            tracing::error!("Error handling message for userbot {}: {}", account.id, e);
+        }
+    }
 }
 
 /// Process a TDLib update
@@ -171,30 +402,87 @@ async fn process_update(
         Update::NewMessage(new_message) => {
             let message = new_message.message();
             
-            // Ignore outgoing messages (sent by this userbot)
+            // Outgoing messages are ours; the owner may be correcting a reply from the account itself
             if message.is_outgoing() {
+                if let Err(e) = super::ghost::handle_outgoing(state, account.id, client, message).await {
+                    tracing::warn!("Failed to apply ghost command in chat {}: {}", message.chat_id(), e);
+                }
+                return Ok(());
+            }
+
+            // Being added to a group starts its onboarding
+            if let MessageContent::MessageChatAddMembers(added) = message.content() {
+                if let Err(e) = super::onboarding::handle_members_added(state, account, client, message, added).await {
+                    tracing::warn!("Failed to onboard chat {}: {}", message.chat_id(), e);
+                }
                 return Ok(());
             }
             
-            // Handle incoming message with humanization
-            handle_incoming_message(state, account, client, message).await?;
+            // Handle incoming message with humanization; replies wait out delays and typing,
+            // so they run on a per-chat queue and auth or connection updates never wait behind them
+            enqueue_incoming(state, account, client, message);
+        }
+        Update::MessageSendSucceeded(succeeded) => {
+            let key = (account.id, succeeded.message().chat_id(), succeeded.old_message_id());
+            let abandoned = {
+                let mut progress = VOICE_PROGRESS_IDS.lock().unwrap_or_else(|e| e.into_inner());
+                match progress.get(&key).copied() {
+                    Some(VoiceProgress::Abandoned) => progress.remove(&key).is_some(),
+                    Some(_) => {
+                        progress.insert(key, VoiceProgress::Sent(succeeded.message().id()));
+                        false
+                    }
+                    None => false,
+                }
+            };
+            if abandoned {
+                delete_voice_progress(client, succeeded.message().chat_id(), succeeded.message().id()).await;
+            }
+
+            // Scheduled deletions were recorded under the temporary ID
+            crate::db::EphemeralRepository::confirm_sent(
+                &state.db_pool,
+                account.id,
+                succeeded.message().chat_id(),
+                succeeded.old_message_id(),
+                succeeded.message().id(),
+            )
+            .await?;
+            crate::db::GameRepository::confirm_sent(
+                &state.db_pool,
+                account.id,
+                succeeded.message().chat_id(),
+                succeeded.old_message_id(),
+                succeeded.message().id(),
+            )
+            .await?;
+            crate::db::TraceRepository::confirm_sent(
+                &state.db_pool,
+                account.id,
+                succeeded.message().chat_id(),
+                succeeded.old_message_id(),
+                succeeded.message().id(),
+            )
+            .await?;
+        }
+        Update::MessageSendFailed(failed) => {
+            // A progress message that never made it has nothing left to delete
+            VOICE_PROGRESS_IDS
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&(account.id, failed.message().chat_id(), failed.old_message_id()));
+        }
+        Update::ChatAction(action) => {
+            // Someone still typing holds back the reply to what they already sent
+            if let MessageSender::User(user) = action.sender_id() {
+                let typing = matches!(action.action(), ChatAction::Typing(_) | ChatAction::RecordingVoiceNote(_));
+                super::debounce::record_typing(account.id, action.chat_id(), user.user_id(), typing);
+            }
         }
         Update::MessageContent(msg_content) => {
             // Message content was edited - we can ignore this for now
             tracing::debug!("Message content updated in chat {}", msg_content.chat_id());
         }
-        Update::AuthorizationState(auth_state) => {
-            // Handle authorization state changes
-            match auth_state.authorization_state() {
-                AuthorizationState::Ready(_) => {
-                    tracing::info!("Userbot {} is authorized and ready", account.id);
-                }
-                AuthorizationState::Closed(_) => {
-                    tracing::warn!("Userbot {} authorization closed", account.id);
-                }
-                _ => {}
-            }
-        }
         _ => {
             // Ignore other update types for now
         }
@@ -205,23 +493,41 @@ async fn process_update(
 
 /// Handle incoming message with humanization
 /// Handle incoming message with extreme humanization
+#[tracing::instrument(
+    name = "receive",
+    skip_all,
+    fields(cid = tracing::field::Empty, account = account.id, chat = message.chat_id())
+)]
 async fn handle_incoming_message(
     state: &AppState,
     account: &crate::db::models::Account,
     client: &Arc<Mutex<TdClient>>,
     message: &Message,
 ) -> Result<()> {
+    let received_at = std::time::Instant::now();
+    let correlation_id = super::trace::correlation_id();
+    tracing::Span::current().record("cid", correlation_id.as_str());
+
     // Extract message details using getters
     let chat_id = message.chat_id();
     let message_id = message.id();
     let message_date = message.date();
 
-    // Get sender user ID for rate limiting
-    let sender_id = match message.sender_id() {
-        MessageSender::User(user) => user.user_id(),
-        _ => 0,
+    // Get sender user ID for rate limiting; channels and anonymous admins send as a chat
+    let (sender_id, sender_chat_id) = match message.sender_id() {
+        MessageSender::User(user) => (user.user_id(), None),
+        MessageSender::Chat(sender_chat) => (0, Some(sender_chat.chat_id())),
+        _ => (0, None),
     };
 
+    // Channel posts auto-forwarded into a linked discussion group arrive with the
+    // channel as sender and carry the original post in forward_info
+    let is_channel_post = sender_chat_id.is_some_and(|id| id != chat_id)
+        && message
+            .forward_info()
+            .as_ref()
+            .is_some_and(|info| info.from_chat_id() != 0);
+
     // Rate limiting: check if user is spamming (>5 messages per minute)
     if sender_id != 0 {
         let now = chrono::Utc::now().timestamp();
@@ -233,7 +539,7 @@ async fn handle_incoming_message(
         
         // Check if user exceeded rate limit
         if user_timestamps.len() >= 5 {
-            tracing::debug!("Rate limit exceeded for user {} in chat {}", sender_id, chat_id);
+            tracing::debug!("Rate limit exceeded for user {} in chat {}", crate::logging::user_ref(sender_id), chat_id);
             return Ok(());
         }
         
@@ -241,15 +547,131 @@ async fn handle_incoming_message(
         user_timestamps.push(now);
     }
 
+    let sender_is_bot = sender_id != 0
+        && match super::loop_guard::is_bot(client, sender_id).await {
+            Ok(is_bot) => is_bot,
+            Err(e) => {
+                tracing::debug!("Failed to check whether {} is a bot: {}", crate::logging::user_ref(sender_id), e);
+                false
+            }
+        };
+
+    // Never auto-reply to bots (unless allowlisted): two bots answering each other never stop
+    if sender_is_bot && !super::loop_guard::is_allowed_bot(state, sender_id) {
+        tracing::debug!("Ignoring message from bot {} in chat {}", crate::logging::user_ref(sender_id), chat_id);
+        return Ok(());
+    }
+
+    let chat_settings = crate::db::ChatRepository::get(&state.db_pool, account.id, chat_id).await?;
+
+    // Check if message is too old
+    let now = chrono::Utc::now().timestamp();
+    let message_age = now - message_date as i64;
+    if message_age > account.ignore_old_messages_sec {
+        tracing::debug!("Not answering old message ({}s old) in chat {}", message_age, chat_id);
+        // Piled up while we were offline: summarized once the backlog is in, per the chat's catch-up mode.
+        // Media stays away from the vision model and Whisper here, that it was sent is enough
+        if let Some(text) = missed_text(message.content(), is_channel_post) {
+            super::catchup::record_missed(
+                state,
+                account,
+                client,
+                chat_id,
+                super::catchup::MissedMessage { date: message_date as i64, text },
+            );
+        }
+        return Ok(());
+    }
+
+    // Check if chat is allowed (and not explicitly denied)
+    if !account.is_chat_allowed(chat_id) {
+        return Ok(());
+    }
+
+    if chat_settings.as_ref().map(|c| c.is_denied).unwrap_or(false) {
+        tracing::debug!("Ignoring message in denied chat {}", chat_id);
+        return Ok(());
+    }
+    if chat_settings.as_ref().map(|c| c.is_paused()).unwrap_or(false) {
+        tracing::debug!("Ignoring message in paused chat {}", chat_id);
+        return Ok(());
+    }
+
+    // A chat may keep its media away from the vision model or Whisper; premium features
+    // (PREMIUM_FEATURES) are only processed for entitled senders
+    use crate::payments::Feature;
+    let media_allowed = match message.content() {
+        MessageContent::MessagePhoto(_)
+        | MessageContent::MessageAnimation(_)
+        | MessageContent::MessageVideoNote(_) => {
+            chat_settings.as_ref().map_or(true, |c| c.vision_enabled)
+                && crate::payments::is_allowed(state, Feature::Vision, sender_id).await
+        }
+        MessageContent::MessageVoiceNote(_) => {
+            chat_settings.as_ref().map_or(true, |c| c.voice_enabled)
+                && crate::payments::is_allowed(state, Feature::Voice, sender_id).await
+        }
+        _ => true,
+    };
+
+    // Daily per-chat media quotas; too long voice notes aren't transcribed and don't count
+    use super::quotas::{self, MediaKind};
+    let quota_kind = match message.content() {
+        MessageContent::MessagePhoto(_)
+        | MessageContent::MessageAnimation(_)
+        | MessageContent::MessageVideoNote(_) => Some(MediaKind::Vision),
+        MessageContent::MessageVoiceNote(voice)
+            if voice.voice_note().duration() as u32 <= state.config.voice_max_duration_secs =>
+        {
+            Some(MediaKind::Voice)
+        }
+        _ => None,
+    };
+    let quota_left = match quota_kind {
+        Some(kind) if media_allowed => quotas::try_consume(state, account.id, chat_id, sender_id, kind).await,
+        _ => true,
+    };
+
     // Process message content and get text + optional media description
+    use crate::ai::media_memory::{MediaKind as MemoryKind, MediaRef};
+    let mut described_media = None;
     let (text, is_sticker) = match message.content() {
+        MessageContent::MessageText(msg_text) if is_channel_post => {
+            (format!("[Пост канала]: {}", msg_text.text().text()), false)
+        }
         MessageContent::MessageText(msg_text) => {
             (msg_text.text().text().to_string(), false)
         }
+        MessageContent::MessagePhoto(_) if !quota_left => {
+            ("[Пользователь отправил фото; дневной лимит распознавания исчерпан]".to_string(), false)
+        }
+        MessageContent::MessageAnimation(_) if !quota_left => {
+            ("[Пользователь отправил GIF; дневной лимит распознавания исчерпан]".to_string(), false)
+        }
+        MessageContent::MessageVideoNote(_) if !quota_left => {
+            ("[Пользователь отправил видеосообщение; дневной лимит распознавания исчерпан]".to_string(), false)
+        }
+        MessageContent::MessageVoiceNote(_) if !quota_left => {
+            ("[Пользователь отправил голосовое сообщение; дневной лимит расшифровки исчерпан]".to_string(), false)
+        }
+        MessageContent::MessagePhoto(_) if !media_allowed => ("[Пользователь отправил фото]".to_string(), false),
+        MessageContent::MessageAnimation(_) if !media_allowed => ("[Пользователь отправил GIF]".to_string(), false),
+        MessageContent::MessageVideoNote(_) if !media_allowed => ("[Пользователь отправил видеосообщение]".to_string(), false),
+        MessageContent::MessageVoiceNote(_) if !media_allowed => {
+            ("[Пользователь отправил голосовое сообщение]".to_string(), false)
+        }
         MessageContent::MessagePhoto(photo) => {
             // Process photo with vision
-            match process_photo(state, client, photo).await {
-                Ok(description) => (format!("[Изображение]: {}", description), false),
+            match process_photo(state, account, client, chat_id, photo).await {
+                Ok(description) => {
+                    described_media = photo
+                        .photo()
+                        .sizes()
+                        .iter()
+                        .max_by_key(|s| s.width() * s.height())
+                        .map(|s| MediaRef { kind: MemoryKind::Photo, remote_file_id: s.photo().remote().id().clone() });
+                    (format!("[Изображение]: {}", description), false)
+                }
                 Err(e) => {
                     tracing::warn!("Failed to process photo: {}", e);
                     ("[Пользователь отправил фото]".to_string(), false)
@@ -258,8 +680,14 @@ async fn handle_incoming_message(
         }
         MessageContent::MessageAnimation(animation) => {
             // Process GIF/animation with vision (extract 3 frames)
-            match process_animation(state, client, animation).await {
-                Ok(description) => (format!("[GIF/Анимация]: {}", description), false),
+            match process_animation(state, account, client, chat_id, animation).await {
+                Ok(description) => {
+                    described_media = Some(MediaRef {
+                        kind: MemoryKind::Animation,
+                        remote_file_id: animation.animation().animation().remote().id().clone(),
+                    });
+                    (format!("[GIF/Анимация]: {}", description), false)
+                }
                 Err(e) => {
                     tracing::warn!("Failed to process animation: {}", e);
                     ("[Пользователь отправил GIF]".to_string(), false)
@@ -270,9 +698,21 @@ async fn handle_incoming_message(
             // Stickers get casual responses with low probability
             ("[Пользователь отправил стикер]".to_string(), true)
         }
+        MessageContent::MessageVoiceNote(voice)
+            if voice.voice_note().duration() as u32 > state.config.voice_max_duration_secs =>
+        {
+            // Too long to transcribe; react to the fact instead of the content
+            (
+                format!(
+                    "[Пользователь отправил длинное голосовое сообщение на {} мин]",
+                    (voice.voice_note().duration() + 59) / 60
+                ),
+                false,
+            )
+        }
         MessageContent::MessageVoiceNote(voice) => {
             // Process voice with Whisper
-            match process_voice(state, client, voice).await {
+            match process_voice(state, account, client, chat_id, voice).await {
                 Ok(transcription) => (format!("[Голосовое сообщение]: {}", transcription), false),
                 Err(e) => {
                     tracing::warn!("Failed to process voice: {}", e);
@@ -282,7 +722,7 @@ async fn handle_incoming_message(
         }
         MessageContent::MessageVideoNote(video_note) => {
             // Process video circle (extract 3 frames)
-            match process_video_note(state, client, video_note).await {
+            match process_video_note(state, account, client, chat_id, video_note).await {
                 Ok(description) => (format!("[Видео кружок]: {}", description), false),
                 Err(e) => {
                     tracing::warn!("Failed to process video note: {}", e);
@@ -299,31 +739,311 @@ async fn handle_incoming_message(
         }
     };
 
-    // Check if message is too old
-    let now = chrono::Utc::now().timestamp();
-    let message_age = now - message_date as i64;
-    if message_age > account.ignore_old_messages_sec {
-        tracing::debug!("Ignoring old message ({}s old) in chat {}", message_age, chat_id);
+    // Who talks here, answered or not, and which of our messages they answer: the digest,
+    // sticker packs and fine-tuning exports go by that (TDLib doesn't give us reactions)
+    if sender_id != 0 && !sender_is_bot {
+        if let Err(e) =
+            crate::db::MessageRepository::record_member_message(&state.db_pool, account.id, chat_id, sender_id).await
+        {
+            tracing::debug!("Failed to record member activity in chat {}: {}", chat_id, e);
+        }
+        if message.reply_to_message_id() != 0 {
+            if let Err(e) =
+                super::digest::record_response(state, account.id, client, chat_id, message.reply_to_message_id()).await
+            {
+                tracing::debug!("Failed to record response in chat {}: {}", chat_id, e);
+            }
+        }
+    }
+
+    // Escalation: voice and video notes mentioning the chat's keywords go to the owner with their transcript
+    if let Some(chat) = chat_settings.as_ref().filter(|c| c.escalation_keywords.is_some()) {
+        use super::escalation::NoteKind;
+        let note = match message.content() {
+            MessageContent::MessageVoiceNote(voice) => text
+                .strip_prefix("[Голосовое сообщение]: ")
+                .map(|transcript| (NoteKind::Voice, voice.voice_note().voice().id(), Some(transcript.to_string()))),
+            // Transcribing a video note is Whisper work: it needs voice allowed here and a voice quota left
+            MessageContent::MessageVideoNote(video_note) => {
+                let transcribe = chat.voice_enabled
+                    && crate::payments::is_allowed(state, Feature::Voice, sender_id).await
+                    && quotas::try_consume(state, account.id, chat_id, sender_id, MediaKind::Voice).await;
+                transcribe.then(|| (NoteKind::Video, video_note.video_note().video().id(), None))
+            }
+            _ => None,
+        };
+        if let Some((kind, file_id, transcript)) = note {
+            let (state, client, chat) = (state.clone(), client.clone(), chat.clone());
+            tokio::spawn(async move {
+                if let Err(e) = super::escalation::escalate(
+                    &state, &client, &chat, message_id, sender_id, kind, file_id, transcript,
+                )
+                .await
+                {
+                    tracing::warn!("Failed to escalate {} note in chat {}: {}", kind.as_str(), chat_id, e);
+                }
+            });
+        }
+    }
+
+    // Bridges: mirror written messages into the chats bridged with this one, translated, in order
+    if matches!(message.content(), MessageContent::MessageText(_)) && !sender_is_bot {
+        super::bridge::enqueue(state, account.id, client, chat_id, sender_id, &text);
+    }
+
+    // Karma: in-chat /karma and /leaderboard, points for "thanks" replies
+    if let Some(chat) = chat_settings.as_ref().filter(|c| c.karma_enabled) {
+        if let Some(command) = super::karma::KarmaCommand::parse(&text) {
+            if !super::karma::allow_command(chat_id, sender_id, state.config.chat_command_limit) {
+                tracing::debug!("Command limit reached by {} in chat {}", crate::logging::user_ref(sender_id), chat_id);
+                return Ok(());
+            }
+            if let Err(e) = super::karma::answer_command(state, account, client, chat, command, sender_id, message_id).await {
+                tracing::warn!("Failed to answer karma command in chat {}: {}", chat_id, e);
+            }
+            return Ok(());
+        }
+
+        if super::karma::is_thanks(&text) {
+            if let Err(e) = super::karma::award_thanks(state, client, chat, message, sender_id).await {
+                tracing::debug!("Failed to award karma in chat {}: {}", chat_id, e);
+            }
+        }
+    }
+
+    // Games: in-chat /game commands, and guesses while a round is open
+    if let Some(chat) = chat_settings.as_ref().filter(|c| c.games_enabled) {
+        if let Some(command) = super::games::GameCommand::parse(&text) {
+            if !super::karma::allow_command(chat_id, sender_id, state.config.chat_command_limit) {
+                tracing::debug!("Command limit reached by {} in chat {}", crate::logging::user_ref(sender_id), chat_id);
+                return Ok(());
+            }
+            if let Err(e) = super::games::answer_command(state, account, client, chat, command, message_id).await {
+                tracing::warn!("Failed to answer game command in chat {}: {}", chat_id, e);
+            }
+            return Ok(());
+        }
+
+        match super::games::check_guess(state, client, chat, sender_id, message_id, &text).await {
+            Ok(true) => return Ok(()),
+            Ok(false) => {}
+            Err(e) => tracing::warn!("Failed to check game guess in chat {}: {}", chat_id, e),
+        }
+    }
+
+    // Groups move fast: keep track of what they are discussing, answered or not
+    if chat_id < 0 && !is_sticker {
+        crate::ai::topics::track_message(state, account.id, chat_id, &text);
+    }
+    if !is_sticker {
+        crate::ai::entities::track_message(state, account.id, chat_id, message_id, &text);
+    }
+
+    // Engagement of chats whose reply probability is auto-tuned
+    if let Some(chat) = chat_settings.as_ref().filter(|c| c.tune_min_probability.is_some()) {
+        super::tuning::observe(state, account.id, client, chat, message.reply_to_message_id(), &text).await;
+    }
+
+    let incoming = IncomingMessage {
+        chat_id,
+        message_id,
+        sender_id,
+        sender_chat_id,
+        sender_is_bot,
+        reply_to_message_id: message.reply_to_message_id(),
+        mentions_us: message.contains_unread_mention(),
+        is_channel_post,
+        is_sticker,
+        text,
+        media: described_media,
+        correlation_id,
+        received_at,
+        queued_at: std::time::Instant::now(),
+    };
+
+    // Messages written in quick succession are answered together
+    let transport = Arc::new(super::transport::TdTransport::new(client.clone()));
+    super::debounce::submit(state, account, &transport, chat_settings.as_ref(), incoming).await
+}
+
+/// An incoming message reduced to what the reply pipeline needs
+#[derive(Debug, Clone)]
+pub struct IncomingMessage {
+    pub chat_id: i64,
+    pub message_id: i64,
+    /// 0 for channels and anonymous admins
+    pub sender_id: i64,
+    pub sender_chat_id: Option<i64>,
+    /// Allowlisted bots get this far; their messages are remembered as bot-authored
+    pub sender_is_bot: bool,
+    /// 0 if the message isn't a reply
+    pub reply_to_message_id: i64,
+    /// Mentions the account or replies to it
+    pub mentions_us: bool,
+    pub is_channel_post: bool,
+    pub is_sticker: bool,
+    /// Message text, or a description of its media
+    pub text: String,
+    /// The described photo or GIF, kept with the memory when IMAGE_MEMORY is on
+    pub media: Option<crate::ai::media_memory::MediaRef>,
+    /// Ties the spans, log lines and stored timings of this message together
+    pub correlation_id: String,
+    pub received_at: std::time::Instant,
+    /// When reading it (media included) was done and it went to debouncing
+    pub queued_at: std::time::Instant,
+}
+
+/// Decide whether to answer a message and, if so, generate, send and store the reply
+#[tracing::instrument(
+    name = "reply",
+    skip_all,
+    fields(cid = %incoming.correlation_id, account = account.id, chat = incoming.chat_id)
+)]
+pub async fn respond_to_message<T: ChatTransport>(
+    state: &AppState,
+    account: &crate::db::models::Account,
+    transport: &T,
+    chat_settings: Option<&crate::db::AccountChat>,
+    incoming: &IncomingMessage,
+) -> Result<()> {
+    let IncomingMessage { chat_id, message_id, sender_id, sender_chat_id, is_channel_post, is_sticker, .. } = *incoming;
+    let text = &incoming.text;
+    let mut trace = super::trace::ReplyTrace::start(incoming);
+
+    // Keyword webhooks watch chats even while replies are paused or the sender is blocked
+    if !is_sticker {
+        crate::webhooks::match_keywords(state, account.id, chat_id, sender_id, text).await;
+    }
+
+    // /privacy in a private chat: whether what the user says here may come up in groups
+    if chat_id == sender_id {
+        if let Some(command) = super::privacy::PrivacyCommand::parse(text) {
+            if super::karma::allow_command(chat_id, sender_id, state.config.chat_command_limit) {
+                super::privacy::answer_command(state, transport, account.id, sender_id, command, message_id).await?;
+            }
+            return Ok(());
+        }
+    }
+
+    // The chat model is out of its error budget; the health worker resumes us.
+    // Until then OFFLINE_FALLBACK answers what is addressed to us from the chat's history
+    let offline = state.is_paused();
+    if offline && !state.config.offline_fallback {
+        tracing::debug!("Ignoring message in chat {}: auto-replies are paused", chat_id);
         return Ok(());
     }
 
-    // Check if chat is allowed
-    if !account.is_chat_allowed(chat_id) {
+    // "закрепи это" from the owner in reply to a message: pinned (or unpinned) without asking the model
+    if incoming.reply_to_message_id != 0 && chat_id < 0 && state.config.is_owner(sender_id) {
+        if let Some(action) = super::pin::owner_request(text) {
+            let actor = format!("owner {}", sender_id);
+            if let Err(e) =
+                super::pin::apply(state, transport, account.id, chat_id, incoming.reply_to_message_id, action, &actor).await
+            {
+                transport
+                    .notify_owner(state, &format!("📌 Couldn't {} the message in chat {}: {}", action.as_str(), chat_id, e))
+                    .await?;
+            }
+            return Ok(());
+        }
+    }
+
+    // Prompt-injection policy: skip users who ran out of strikes and suspicious messages
+    if crate::security::is_user_blocked(state, account.id, chat_id, sender_id).await? {
+        tracing::debug!("Ignoring blocked user {} in chat {}", crate::logging::user_ref(sender_id), chat_id);
+        return Ok(());
+    }
+
+    if !is_sticker {
+        let verdict = crate::security::check_message(state, account.id, chat_id, sender_id, text).await?;
+        if verdict == crate::security::SecurityVerdict::Skip {
+            return Ok(());
+        }
+    }
+
+    // Routing rules (/rule_add) come before the default logic
+    let routing = super::rules::for_message(state, account.id, incoming).await;
+    if routing.reply == Some(super::rules::ReplyRule::Never) {
+        tracing::debug!("Not answering in chat {}: rules {:?} say never", chat_id, routing.matched);
+        return Ok(());
+    }
+
+    // Track how well the account knows this person (channel posts have no personal sender)
+    let relationship = if sender_id != 0 && !is_channel_post {
+        match crate::db::RelationshipRepository::record_interaction(&state.db_pool, account.id, sender_id).await {
+            Ok(relationship) => {
+                if relationship.message_count - relationship.refreshed_at_count
+                    >= crate::ai::relationships::REFRESH_EVERY_MESSAGES
+                {
+                    let state = state.clone();
+                    let account_id = account.id;
+                    tokio::spawn(async move {
+                        if let Err(e) = crate::ai::refresh_relationship(&state, account_id, sender_id).await {
+                            tracing::warn!("Failed to refresh relationship: {}", e);
+                        }
+                    });
+                }
+                Some(relationship)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to record interaction: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // Handed over to a human: they answer until the handoff runs out or is resumed
+    if crate::db::HandoffRepository::active_for(&state.db_pool, account.id, chat_id, sender_id).await?.is_some() {
+        tracing::debug!("Not answering in chat {}: handed over to a human", chat_id);
+        record_unanswered(state, account, chat_settings, incoming).await;
+        return Ok(());
+    }
+
+    // Quiet hours: nothing is answered, but the message is kept as if it had been
+    let chat_time = super::timezone::chat_now(chat_settings, state.config.default_timezone);
+    if super::quiet_hours::holds_back(chat_settings, &chat_time, incoming.mentions_us) {
+        tracing::debug!("Not answering in chat {} during its quiet hours", chat_id);
+        record_unanswered(state, account, chat_settings, incoming).await;
         return Ok(());
     }
 
     // Determine if this is a private chat
     let is_private = chat_id > 0;
 
-    // Calculate reply probability (lower for stickers)
-    let adjusted_probability = if is_sticker {
-        account.reply_probability / 4 // Very low probability for stickers
+    let format_mode = super::formatting::FormatMode::for_chat(chat_settings);
+
+    // Per-chat override takes precedence over the account-wide probability
+    let base_probability = chat_settings
+        .and_then(|c| c.reply_probability)
+        .unwrap_or(account.reply_probability);
+
+    // Calculate reply probability (lower for stickers, dedicated setting for channel posts)
+    let adjusted_probability = if is_channel_post {
+        account.channel_post_reply_probability
+    } else if is_sticker {
+        base_probability / 4 // Very low probability for stickers
     } else {
-        account.reply_probability
+        base_probability
     };
 
+    // Trigger words always get an answer
+    let triggered = chat_settings.is_some_and(|c| super::onboarding::matches_trigger(&c.get_triggers(), text));
+    // A poll asked of us directly isn't left to chance
+    let wants_poll = !is_sticker
+        && chat_settings.is_some_and(|c| c.polls_enabled)
+        && crate::ai::polls::looks_like_poll_request(text);
+    // So is a request to send a remembered picture again
+    let wants_media = state.config.image_memory
+        && !is_sticker
+        && crate::ai::media_memory::looks_like_media_request(text);
+    let triggered = triggered
+        || ((wants_poll || wants_media) && (incoming.mentions_us || is_private))
+        || routing.reply == Some(super::rules::ReplyRule::Always);
+
     // Decide whether to respond
-    let should_respond = if is_private && account.always_respond_in_pm == 1 {
+    let should_respond = if triggered || (is_private && account.always_respond_in_pm == 1) {
         true
     } else {
         rand::random::<u8>() as i64 % 100 < adjusted_probability
@@ -333,57 +1053,236 @@ async fn handle_incoming_message(
         tracing::debug!("Skipping message in chat {} (probability check)", chat_id);
         return Ok(());
     }
+    if offline && !(triggered || incoming.mentions_us || is_private) {
+        tracing::debug!("Skipping message in chat {}: offline, only answering what is addressed to us", chat_id);
+        return Ok(());
+    }
 
-    // IMMEDIATELY mark message as read (simulate instant read receipt)
-    let client_lock = client.lock().await;
-    let view_messages = ViewMessages::builder()
-        .chat_id(chat_id)
-        .message_ids(vec![message_id])
-        .force_read(true)
-        .build();
+    // "ок", "+" and rows of emoji aren't worth a generation unless they are addressed to us
+    if state.config.intent_filter && !(triggered || incoming.mentions_us || is_sticker || offline) {
+        match super::intent::classify(state, text).await {
+            super::intent::Intent::Reply => {}
+            super::intent::Intent::React => {
+                tracing::debug!("Not answering in chat {}: the message only calls for a reaction", chat_id);
+                // No reactions through TDLib here: reading it is all the acknowledgment it gets
+                if let Err(e) = transport.mark_read(chat_id, message_id).await {
+                    tracing::warn!("Failed to mark message as read: {}", e);
+                }
+                return Ok(());
+            }
+            super::intent::Intent::Ignore => {
+                tracing::debug!("Not answering in chat {}: the message needs no reply", chat_id);
+                return Ok(());
+            }
+        }
+    }
 
-    if let Err(e) = client_lock.view_messages(&view_messages).await {
+    // Per-chat cooldown between replies (set by chat profiles, stretched while the host is under load)
+    let cooldown = state.throttle.level().cooldown(chat_settings.and_then(|c| c.reply_cooldown_secs));
+    if let Some(cooldown) = cooldown {
+        let last = crate::db::MessageRepository::last_reply_at(&state.db_pool, account.id, chat_id).await?;
+        if last.is_some_and(|t| chrono::Utc::now() - t < chrono::Duration::seconds(cooldown)) {
+            tracing::debug!("Skipping message in chat {} (reply cooldown)", chat_id);
+            return Ok(());
+        }
+    }
+
+    trace.mark("checks");
+
+    // IMMEDIATELY mark message as read (simulate instant read receipt)
+    if let Err(e) = transport.mark_read(chat_id, message_id).await {
         tracing::warn!("Failed to mark message as read: {}", e);
     }
-    drop(client_lock);
 
     // Random "Read Delay" - simulate user reading and thinking (5-60 seconds)
     let read_delay = 5 + (rand::random::<u8>() % 56) as u64; // 5-60 seconds
     tracing::debug!("Read delay: {}s for chat {}", read_delay, chat_id);
-    tokio::time::sleep(tokio::time::Duration::from_secs(read_delay)).await;
+    transport.pause(std::time::Duration::from_secs(read_delay)).await;
 
     // Calculate additional response delay based on message length
-    let response_delay = calculate_response_delay(account, &text);
-    tokio::time::sleep(tokio::time::Duration::from_secs(response_delay as u64)).await;
+    let response_delay = calculate_response_delay(account, text);
+    transport.pause(std::time::Duration::from_secs(response_delay as u64)).await;
+    trace.mark("read_delay");
+
+    // Persona answering in this chat (a rule's, per-chat rotation or the account's own)
+    let ruled_persona = match routing.persona_id {
+        Some(id) => crate::ai::persona_inheritance::resolve(&state.db_pool, id).await?,
+        None => None,
+    };
+    let (system_prompt, persona_id) = match ruled_persona {
+        Some(persona) => (persona.prompt, Some(persona.id)),
+        None => super::rotation::resolve_chat_persona(state, account, chat_settings).await?,
+    };
+    let persona = match persona_id {
+        Some(id) => crate::ai::persona_inheritance::resolve(&state.db_pool, id).await?,
+        None => None,
+    };
+    // Another persona took over: the old conversation only carries over as a summary
+    let switched = match super::persona_switch::guard(state, account.id, chat_id, persona_id).await {
+        Ok(switched) => switched,
+        Err(e) => {
+            tracing::warn!("Persona switch guard failed in chat {}: {}", chat_id, e);
+            false
+        }
+    };
+    let reloaded_settings;
+    let chat_settings = if switched {
+        reloaded_settings = crate::db::ChatRepository::get(&state.db_pool, account.id, chat_id).await?;
+        reloaded_settings.as_ref()
+    } else {
+        chat_settings
+    };
+    // Some personas (trolls, nonsense generators) shouldn't leave anything in long-term memory
+    let memory_write = crate::ai::memory_policy::MemoryWritePolicy::of(persona.as_ref());
+    // Nor can every persona search the web, send pictures or act in the chat
+    let capabilities = crate::ai::capabilities::Capabilities::of(persona.as_ref());
+    let local_now = super::timezone::chat_now(chat_settings, state.config.default_timezone);
+    let system_prompt = super::timezone::with_local_time(system_prompt, local_now);
+    // Sleepy at night, lively in the evening: the persona's variant for the chat's local hour
+    let system_prompt = crate::ai::time_variants::apply(
+        system_prompt,
+        persona.as_ref().and_then(|p| p.time_variants.as_deref()),
+        chrono::Timelike::hour(&local_now),
+    );
+    let system_prompt = match chat_settings.and_then(|c| c.style_notes.as_deref()).filter(|s| !s.trim().is_empty()) {
+        Some(notes) => format!("{}\n\n[СТИЛЬ В ЭТОМ ЧАТЕ]\n{}", system_prompt, notes.trim()),
+        None => system_prompt,
+    };
+    let system_prompt = if switched && chat_settings.is_some_and(|c| c.announce_persona_switch) {
+        format!("{}\n\n{}", system_prompt, super::persona_switch::ANNOUNCE_NOTE)
+    } else {
+        system_prompt
+    };
+    // The persona may pin announcements here
+    let may_pin = !is_private && capabilities.use_tools && chat_settings.is_some_and(|c| c.pins_enabled);
+    let system_prompt = if may_pin {
+        format!("{}\n\n{}", system_prompt, super::pin::PROMPT_NOTE)
+    } else {
+        system_prompt
+    };
+
+    // "Make a poll about Friday": a native poll instead of a text reply
+    if wants_poll && !offline && capabilities.use_tools {
+        if let Some(draft) = extract_poll_draft(state, account.id, chat_id, text).await {
+            let poll_id = transport.send_poll(chat_id, &draft, Some(message_id)).await?;
+            tracing::info!("Userbot {} sent poll {} to chat {}", account.id, poll_id, chat_id);
+            save_exchange(state, account.id, incoming, &draft.as_history(), persona_id).await;
+            return Ok(());
+        }
+    }
+
+    // "скинь тот мем с котом": the remembered picture itself, not a retelling of it
+    if wants_media && !offline {
+        if let Some(remembered) = find_remembered_media(state, account.id, chat_id, text).await {
+            transport.send_media(chat_id, &remembered.media, Some(message_id)).await?;
+            tracing::info!(
+                "Userbot {} re-sent {} from message {:?} in chat {} (similarity {:.2})",
+                account.id,
+                remembered.media.kind.as_str(),
+                remembered.message_id,
+                chat_id,
+                remembered.similarity
+            );
+            save_exchange(state, account.id, incoming, &remembered.description, persona_id).await;
+            return Ok(());
+        }
+    }
 
     // Generate AI response
     let response_text = if is_sticker {
         // Casual response for stickers
         let idx = rand::random::<usize>() % STICKER_RESPONSES.len();
         STICKER_RESPONSES[idx].to_string()
+    } else if offline {
+        match crate::ai::markov::offline_reply(state, account.id, chat_id, text).await? {
+            Some(reply) => {
+                tracing::info!("Userbot {} answering in chat {} offline (chat model down)", account.id, chat_id);
+                reply
+            }
+            None => {
+                tracing::debug!("Not answering in chat {}: too little history for an offline reply", chat_id);
+                return Ok(());
+            }
+        }
     } else {
-        match generate_ai_response(state, account, chat_id, &text).await {
+        let context = ResponseContext {
+            chat_settings,
+            system_prompt,
+            persona_id,
+            memory_write,
+            temperature: routing.temperature,
+            capabilities,
+            relationship: relationship.as_ref(),
+        };
+        match generate_ai_response(state, account, incoming, context, &mut trace).await {
             Ok(resp) => resp,
             Err(e) => {
                 tracing::error!("Failed to generate AI response: {}", e);
                 // Notify owner, but don't send error to chat
-                notify_owner(state, &format!("⚠️ Userbot {} failed to generate response: {}", account.id, e)).await?;
+                transport.notify_owner(state, &format!("⚠️ Userbot {} failed to generate response: {}", account.id, e)).await?;
                 return Ok(());
             }
         }
     };
 
+    // The persona asked to pin the message it answers (with or without saying anything)
+    let (response_text, wants_pin) = super::pin::take_marker(&response_text);
+    if wants_pin && may_pin {
+        let pin = super::pin::PinAction::Pin;
+        if let Err(e) = super::pin::apply(state, transport, account.id, chat_id, message_id, pin, "persona").await {
+            tracing::debug!("Persona couldn't pin message {} in chat {}: {}", message_id, chat_id, e);
+        }
+    }
+
     // Check if AI returned <IGNORE> - if so, don't send anything
     if response_text.trim() == "<IGNORE>" {
         tracing::info!("Userbot {} ignoring message in chat {} (AI returned <IGNORE>)", account.id, chat_id);
         return Ok(());
     }
 
-    // Split response by || for multi-texting
-    let message_chunks: Vec<&str> = response_text
+    // The persona was told not to answer this kind of question: a human takes over
+    if super::handoff::is_handoff_reply(&response_text) {
+        record_unanswered(state, account, chat_settings, incoming).await;
+        let user_id = (sender_id != 0).then_some(sender_id);
+        let handoff =
+            super::handoff::start(state, account.id, chat_id, user_id, "the persona asked for a human", None).await?;
+        let notice = super::handoff::notice(state, &handoff, Some(message_id)).await?;
+        transport.notify_owner(state, &notice).await?;
+        return Ok(());
+    }
+
+    // The answering persona's own clean-up rules
+    let response_text = match persona
+        .as_ref()
+        .and_then(|p| crate::ai::postprocess::for_persona(p.id, p.postprocess.as_deref()))
+    {
+        Some(processor) => processor.apply(&response_text),
+        None => response_text,
+    };
+
+    // The chat's way of writing numbers, dates, money and units
+    let response_text = match crate::ai::localize::Localizer::for_chat(chat_settings) {
+        Some(localizer) => localizer.apply(&response_text),
+        None => response_text,
+    };
+
+    // Without the long-replies feature (when gated) only the first part of a long answer is sent
+    let short_only = !crate::payments::is_allowed(state, crate::payments::Feature::LongReplies, sender_id).await;
+    let response_text = if short_only && response_text.chars().count() > crate::payments::SHORT_REPLY_CHARS {
+        super::formatting::split_long_message(&response_text, crate::payments::SHORT_REPLY_CHARS)
+            .into_iter()
+            .next()
+            .unwrap_or_default()
+    } else {
+        response_text
+    };
+
+    // Split response by || for multi-texting, then break up anything over the length limit
+    let message_chunks: Vec<String> = response_text
         .split("||")
         .map(|s| s.trim())
         .filter(|s| !s.is_empty() && *s != "<IGNORE>")
+        .flat_map(|s| super::formatting::split_long_message(s, state.config.max_message_length))
         .collect();
 
     // If no chunks (empty response), skip
@@ -395,11 +1294,13 @@ async fn handle_incoming_message(
     // Decide whether to use reply or regular message
     let use_reply = if is_private {
         false // Never use reply in private chats
+    } else if is_channel_post {
+        true // Comments must reply to the post to land in its thread
     } else {
         // In group chats, use reply only if:
         // 1. The message is a reply to our previous message (active dialogue)
         // 2. Or based on probability (but less often)
-        let is_reply_to_us = message.reply_to_message_id() != 0; // Check if replying to someone
+        let is_reply_to_us = incoming.reply_to_message_id != 0; // Check if replying to someone
 
         if is_reply_to_us {
             // If someone replied to us, always use reply back
@@ -417,111 +1318,135 @@ async fn handle_incoming_message(
         tracing::debug!("Distracted typist behavior triggered for userbot {}", account.id);
 
         // Start typing
-        let client_lock = client.lock().await;
-        let send_action = SendChatAction::builder()
-            .chat_id(chat_id)
-            .action(ChatAction::Typing(ChatActionTyping::builder().build()))
-            .build();
-
-        if let Err(e) = client_lock.send_chat_action(&send_action).await {
+        if let Err(e) = transport.set_typing(chat_id, true).await {
             tracing::warn!("Failed to send typing indicator: {}", e);
         }
-        drop(client_lock);
 
         // Type for a bit (2-4 seconds)
         let distracted_typing_duration = 2 + (rand::random::<u8>() % 3) as u64; // 2-4 seconds
-        tokio::time::sleep(tokio::time::Duration::from_secs(distracted_typing_duration)).await;
+        transport.pause(std::time::Duration::from_secs(distracted_typing_duration)).await;
 
         // Cancel typing (send cancel action)
-        let client_lock = client.lock().await;
-        let cancel_action = SendChatAction::builder()
-            .chat_id(chat_id)
-            .action(ChatAction::Cancel(ChatActionCancel::builder().build()))
-            .build();
-
-        if let Err(e) = client_lock.send_chat_action(&cancel_action).await {
+        if let Err(e) = transport.set_typing(chat_id, false).await {
             tracing::warn!("Failed to cancel typing: {}", e);
         }
-        drop(client_lock);
 
         // Pause (distracted - 3-10 seconds)
         let distracted_pause = 3 + (rand::random::<u8>() % 8) as u64; // 3-10 seconds
-        tokio::time::sleep(tokio::time::Duration::from_secs(distracted_pause)).await;
+        transport.pause(std::time::Duration::from_secs(distracted_pause)).await;
     }
 
     // Send each chunk as a separate message with typing indicators
+    let mut sent_ids = Vec::with_capacity(message_chunks.len());
     for (idx, chunk) in message_chunks.iter().enumerate() {
         // Calculate typing duration for this chunk
         let typing_duration = calculate_typing_duration(account, chunk);
 
         // Send typing indicator
-        let client_lock = client.lock().await;
-        let send_action = SendChatAction::builder()
-            .chat_id(chat_id)
-            .action(ChatAction::Typing(ChatActionTyping::builder().build()))
-            .build();
-
-        if let Err(e) = client_lock.send_chat_action(&send_action).await {
+        if let Err(e) = transport.set_typing(chat_id, true).await {
             tracing::warn!("Failed to send typing indicator: {}", e);
         }
-        drop(client_lock);
 
         // "Type" the response
-        tokio::time::sleep(tokio::time::Duration::from_secs(typing_duration as u64)).await;
-
-        // Send the message chunk
-        let client_lock = client.lock().await;
+        transport.pause(std::time::Duration::from_secs(typing_duration as u64)).await;
 
-        let input_message = InputMessageContent::InputMessageText(
-            InputMessageText::builder()
-                .text(FormattedText::builder().text(chunk.to_string()).build())
-                .build()
-        );
-
-        let mut send_message_builder = SendMessage::builder();
-        send_message_builder
-            .chat_id(chat_id)
-            .input_message_content(input_message);
-
-        // Add reply only to the first chunk if needed
-        if use_reply && idx == 0 {
-            send_message_builder.reply_to_message_id(message_id);
-        }
-
-        let send_message = send_message_builder.build();
-
-        if let Err(e) = client_lock.send_message(&send_message).await {
-            tracing::error!("Failed to send message chunk {}: {}", idx, e);
-            notify_owner(state, &format!("❌ Userbot {} failed to send message chunk: {}", account.id, e)).await?;
-            drop(client_lock);
-            return Ok(());
+        // Send the message chunk, as a reply only for the first chunk if needed
+        let reply_to = (use_reply && idx == 0).then_some(message_id);
+        match transport.send_text(chat_id, chunk, format_mode, reply_to).await {
+            Ok(sent_id) => sent_ids.push(sent_id),
+            Err(e) => {
+                tracing::error!("Failed to send message chunk {}: {}", idx, e);
+                transport.notify_owner(state, &format!("❌ Userbot {} failed to send message chunk: {}", account.id, e)).await?;
+                return Ok(());
+            }
         }
 
-        drop(client_lock);
-
         // Add a small random pause between chunks (0.5s - 1.5s)
         if idx < message_chunks.len() - 1 {
             let pause_ms = 500 + (rand::random::<u16>() % 1001) as u64; // 500-1500ms
-            tokio::time::sleep(tokio::time::Duration::from_millis(pause_ms)).await;
+            transport.pause(std::time::Duration::from_millis(pause_ms)).await;
+        }
+    }
+
+    trace.mark("send");
+
+    // Ephemeral chats: the reply deletes itself later
+    if let Some(minutes) = chat_settings.and_then(|c| c.ephemeral_minutes).filter(|m| *m > 0) {
+        let delete_at = chrono::Utc::now() + chrono::Duration::minutes(minutes);
+        if let Err(e) = crate::db::EphemeralRepository::schedule(&state.db_pool, account.id, chat_id, &sent_ids, delete_at).await {
+            tracing::warn!("Failed to schedule reply deletion in chat {}: {}", chat_id, e);
         }
     }
 
+    // Save the incoming message together with its sender identity
+    let incoming_message = NewMessage {
+        account_id: account.id,
+        chat_id,
+        role: MessageRole::User,
+        content: text.clone(),
+        sender_id: (sender_id != 0).then_some(sender_id),
+        sender_chat_id,
+        persona_id: None,
+    };
+
+    if let Err(e) = AccountRepository::add_message(&state.db_pool, incoming_message).await {
+        tracing::warn!("Failed to save incoming message to history: {}", e);
+    }
+
     // Save to message history
     let new_message = NewMessage {
         account_id: account.id,
         chat_id,
         role: MessageRole::Assistant,
         content: response_text.clone(),
+        sender_id: None,
+        sender_chat_id: None,
+        persona_id,
     };
 
-    if let Err(e) = AccountRepository::add_message(&state.db_pool, new_message).await {
-        tracing::warn!("Failed to save message to history: {}", e);
+    // Offline replies stay out of history, or the model would read them back as things it said
+    if !offline {
+        if let Err(e) = AccountRepository::add_message(&state.db_pool, new_message).await {
+            tracing::warn!("Failed to save message to history: {}", e);
+        }
+    }
+
+    if memory_write.stores_replies() && !is_sticker && !offline {
+        remember_reply(state, account.id, chat_settings, chat_id, persona_id, &response_text).await;
+    }
+
+    // Many rapid replies to the same one or two participants look like a bot loop
+    if let Err(e) = super::loop_guard::record_reply(state, transport, account.id, chat_id, sender_id).await {
+        tracing::warn!("Loop guard failed in chat {}: {}", chat_id, e);
     }
 
-    tracing::info!("Userbot {} responded in chat {} with {} chunks", account.id, chat_id, message_chunks.len());
+    super::trace::save(state, account.id, incoming, &trace, &sent_ids).await;
+    tracing::info!(
+        "Userbot {} responded in chat {} with {} chunks in {}ms ({})",
+        account.id,
+        chat_id,
+        message_chunks.len(),
+        trace.total_ms(),
+        trace.summary()
+    );
     Ok(())
 }
 
+/// What a message too old to answer goes into the catch-up summary as, without processing its media
+fn missed_text(content: &MessageContent, is_channel_post: bool) -> Option<String> {
+    let text = match content {
+        MessageContent::MessageText(msg_text) if is_channel_post => format!("[Пост канала]: {}", msg_text.text().text()),
+        MessageContent::MessageText(msg_text) => msg_text.text().text().to_string(),
+        MessageContent::MessagePhoto(_) => "[Пользователь отправил фото]".to_string(),
+        MessageContent::MessageAnimation(_) => "[Пользователь отправил GIF]".to_string(),
+        MessageContent::MessageVoiceNote(_) => "[Пользователь отправил голосовое сообщение]".to_string(),
+        MessageContent::MessageVideoNote(_) => "[Пользователь отправил видеосообщение]".to_string(),
+        MessageContent::MessageVideo(_) => "[Пользователь отправил видео]".to_string(),
+        _ => return None,
+    };
+    Some(text)
+}
+
 /// Calculate response delay based on message length and account settings
 fn calculate_response_delay(account: &crate::db::models::Account, text: &str) -> i64 {
     let min = account.min_response_delay_sec;
@@ -551,22 +1476,77 @@ fn calculate_typing_duration(account: &crate::db::models::Account, response: &st
     final_duration.max(1).min(30)
 }
 
+/// What a reply is generated with besides the message itself
+struct ResponseContext<'a> {
+    chat_settings: Option<&'a crate::db::AccountChat>,
+    system_prompt: String,
+    persona_id: Option<i64>,
+    memory_write: crate::ai::memory_policy::MemoryWritePolicy,
+    temperature: Option<f64>,
+    capabilities: crate::ai::capabilities::Capabilities,
+    relationship: Option<&'a crate::db::Relationship>,
+}
+
 /// Generate AI response using Ollama
+#[tracing::instrument(name = "generate", skip_all)]
 async fn generate_ai_response(
     state: &AppState,
     account: &crate::db::models::Account,
-    chat_id: i64,
-    user_message: &str,
+    incoming: &IncomingMessage,
+    context: ResponseContext<'_>,
+    trace: &mut super::trace::ReplyTrace,
 ) -> Result<String> {
+    let ResponseContext { chat_settings, system_prompt, persona_id, memory_write, temperature, capabilities, relationship } = context;
+    let (chat_id, user_message) = (incoming.chat_id, incoming.text.as_str());
     let http_client = reqwest::Client::new();
     
-    // Check if web search is needed
-    let search_context = match crate::ai::should_search(
+    // Generate embedding for current message for RAG retrieval
+    let query_embedding = match crate::ai::generate_embedding_cached(
         &http_client,
+        &state.db_pool,
         &state.config.ollama_url,
-        &state.config.ollama_model,
+        &state.config.ollama_embed_model,
         user_message,
     ).await {
+        Ok(emb) => Some(emb),
+        Err(e) => {
+            tracing::warn!("Failed to generate query embedding: {}", e);
+            None
+        }
+    };
+    
+    trace.mark("embedding");
+
+    // A question answered recently in this chat gets the same answer without the LLM
+    let cacheable = chat_settings.is_some_and(|c| c.answer_cache_enabled) && crate::ai::answer_cache::is_question(user_message);
+    if let (true, Some(embedding)) = (cacheable, &query_embedding) {
+        match crate::ai::answer_cache::lookup(state, account.id, chat_id, persona_id, embedding).await {
+            Ok(Some(answer)) => {
+                if memory_write.stores_incoming() {
+                    remember_message(state, account.id, chat_settings, persona_id, incoming, embedding).await;
+                }
+                trace.mark("answer_cache");
+                return Ok(answer);
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!("Failed to look up cached answer: {}", e),
+        }
+    }
+
+    // Check if web search is needed (never in safe mode, nor in chats or for personas that opted out)
+    let search_query = if state.config.safe_mode
+        || !capabilities.search_web
+        || !chat_settings.map_or(true, |c| c.web_search_enabled)
+    {
+        Ok(None)
+    } else {
+        crate::ai::should_search(
+            &state.config.ollama_url,
+            &state.config.ollama_model,
+            user_message,
+        ).await
+    };
+    let search_context = match search_query {
         Ok(Some(query)) => {
             tracing::info!("Web search triggered for query: {}", query);
             
@@ -592,31 +1572,36 @@ async fn generate_ai_response(
         }
     };
     
-    // Generate embedding for current message for RAG retrieval
-    let query_embedding = match crate::ai::generate_embedding(
-        &http_client,
-        &state.config.ollama_url,
-        &state.config.ollama_model,
-        user_message,
-    ).await {
-        Ok(emb) => Some(emb),
-        Err(e) => {
-            tracing::warn!("Failed to generate query embedding: {}", e);
-            None
-        }
-    };
-    
+    trace.mark("search");
+
     // Retrieve relevant memories if embedding was successful
     let memory_context = if let Some(ref embedding) = query_embedding {
-        match crate::ai::retrieve_memories(&state.db_pool, account.id, chat_id, embedding, 3).await {
+        let candidates = if state.config.rag_rerank_enabled { crate::ai::RERANK_CANDIDATES } else { 3 };
+        let filter = crate::ai::RetrievalFilter::for_chat(chat_settings, Some(incoming.sender_id))
+            .with_topic(crate::ai::topics::current_topic_embedding(state, account.id, chat_id).await);
+        let retrieved = crate::ai::retrieve_memories(&state.db_pool, account.id, chat_id, embedding, candidates, &filter).await;
+        let retrieved = match retrieved {
+            Ok(memories) if state.config.rag_rerank_enabled => {
+                Ok(crate::ai::rerank_memories(state, user_message, memories, 3).await)
+            }
+            other => other,
+        };
+
+        match retrieved {
             Ok(memories) => {
                 if !memories.is_empty() {
                     let mut context = String::from("[ВСПЛЫВШИЕ ВОСПОМИНАНИЯ О ПРОШЛЫХ ДИАЛОГАХ]\n\n");
+                    let mut used = Vec::new();
                     for (i, memory) in memories.iter().enumerate() {
                         if memory.similarity > 0.5 { // Only include relevant memories
-                            context.push_str(&format!("{}. {}\n", i + 1, memory.content));
+                            let marker = if memory.tier == crate::ai::MemoryTier::Semantic { "[факт] " } else { "" };
+                            context.push_str(&format!("{}. {}{}\n", i + 1, marker, memory.content));
+                            used.push(memory);
                         }
                     }
+                    if let Err(e) = crate::ai::record_hits(&state.db_pool, &used).await {
+                        tracing::warn!("Failed to record memory hits: {}", e);
+                    }
                     Some(context)
                 } else {
                     None
@@ -630,19 +1615,111 @@ async fn generate_ai_response(
     } else {
         None
     };
+
+    // What the sender told us in private, in groups that recall it and only if they allowed it with /privacy
+    let private_context = match (&query_embedding, chat_settings) {
+        (Some(embedding), Some(chat)) if chat_id < 0 && chat.rag_private_recall && incoming.sender_id > 0 => {
+            match crate::db::PrivacyRepository::shares_private(&state.db_pool, account.id, incoming.sender_id).await {
+                Ok(true) => {
+                    match crate::ai::retrieve_private_memories(
+                        &state.db_pool,
+                        account.id,
+                        incoming.sender_id,
+                        embedding,
+                        super::privacy::RECALLED,
+                    )
+                    .await
+                    {
+                        Ok(memories) => {
+                            let recalled: Vec<_> = memories.iter().filter(|m| m.similarity > 0.5).collect();
+                            if recalled.is_empty() {
+                                None
+                            } else {
+                                let mut context = String::from(super::privacy::PROMPT_HEADER);
+                                for (i, memory) in recalled.iter().enumerate() {
+                                    context.push_str(&format!("{}. {}\n", i + 1, memory.content));
+                                }
+                                if let Err(e) = crate::ai::record_hits(&state.db_pool, &recalled).await {
+                                    tracing::warn!("Failed to record memory hits: {}", e);
+                                }
+                                Some(context)
+                            }
+                        }
+                        Err(e) => {
+                            tracing::warn!("Failed to retrieve private chat memories: {}", e);
+                            None
+                        }
+                    }
+                }
+                Ok(false) => None,
+                Err(e) => {
+                    tracing::warn!("Failed to check privacy consent: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
     
-    // Get recent message history
-    let history = AccountRepository::get_recent_messages(&state.db_pool, account.id, chat_id, 10).await?;
+    // How this persona answered similar messages before
+    let examples_context = match (persona_id, &query_embedding) {
+        (Some(persona_id), Some(embedding)) if state.config.few_shot_examples > 0 => {
+            match crate::ai::examples::relevant_examples(state, persona_id, embedding, state.config.few_shot_examples).await {
+                Ok(examples) => crate::ai::examples::format_examples(&examples),
+                Err(e) => {
+                    tracing::warn!("Failed to fetch persona examples: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // People, places and projects the message mentions
+    let entity_context = match crate::ai::entities::entity_context(state, account.id, chat_id, user_message).await {
+        Ok(context) => context,
+        Err(e) => {
+            tracing::warn!("Failed to look up entities: {}", e);
+            None
+        }
+    };
+
+    trace.mark("rag");
+
+    // Recent history since the last persona switch; what came before is summarized
+    let history = crate::db::MessageRepository::get_context_messages(&state.db_pool, account.id, chat_id, 10).await?;
     
     // Build conversation context
     let mut messages = vec![];
     
-    // Add system prompt
+    // Add system prompt (resolved by the caller: rotated, bound persona or the account's own)
     messages.push(crate::ai::ollama::OllamaMessage {
         role: "system".to_string(),
-        content: account.system_prompt.clone(),
+        content: system_prompt,
     });
     
+    // Talk differently to strangers and long-time contacts
+    if let Some(relationship) = relationship {
+        messages.push(crate::ai::ollama::OllamaMessage {
+            role: "system".to_string(),
+            content: crate::ai::relationship_context(relationship),
+        });
+    }
+
+    if let Some(earlier) = super::persona_switch::context_note(chat_settings) {
+        messages.push(crate::ai::ollama::OllamaMessage {
+            role: "system".to_string(),
+            content: earlier,
+        });
+    }
+
+    if let Some(examples) = examples_context {
+        messages.push(crate::ai::ollama::OllamaMessage {
+            role: "system".to_string(),
+            content: examples,
+        });
+    }
+
     // Add memory context if available
     if let Some(ref mem_ctx) = memory_context {
         messages.push(crate::ai::ollama::OllamaMessage {
@@ -650,7 +1727,21 @@ async fn generate_ai_response(
             content: mem_ctx.clone(),
         });
     }
-    
+
+    if let Some(private) = private_context {
+        messages.push(crate::ai::ollama::OllamaMessage {
+            role: "system".to_string(),
+            content: private,
+        });
+    }
+
+    if let Some(entities) = entity_context {
+        messages.push(crate::ai::ollama::OllamaMessage {
+            role: "system".to_string(),
+            content: entities,
+        });
+    }
+
     // Add search results if available
     if let Some(ref search_ctx) = search_context {
         messages.push(crate::ai::ollama::OllamaMessage {
@@ -673,44 +1764,290 @@ async fn generate_ai_response(
         content: user_message.to_string(),
     });
     
-    // Generate response
+    // Generate response, shorter and on the fallback model while the host is under load
+    let throttle_level = state.throttle.level();
+    let model = throttle_level
+        .model(&state.config.ollama_model, state.config.ollama_fallback_model.as_deref())
+        .to_string();
+    let num_predict = throttle_level.num_predict(state.config.throttle_max_tokens);
     let ollama_client = crate::ai::ollama::OllamaClient::new(state.config.ollama_url.clone());
     let request = crate::ai::ollama::OllamaChatRequest {
-        model: state.config.ollama_model.clone(),
+        model: model.clone(),
         messages,
         stream: true,
+        options: (temperature.is_some() || num_predict.is_some())
+            .then_some(crate::ai::ollama::ChatOptions { temperature, num_predict }),
     };
     
-    let response = ollama_client.chat(request).await?;
+    trace.mark("prompt");
+
+    let started = std::time::Instant::now();
+    let in_flight = state.throttle.start_generation();
+    let response = ollama_client
+        .chat(request)
+        .instrument(tracing::info_span!("llm", model = %model, throttle = throttle_level.as_str()))
+        .await;
+    drop(in_flight);
+    state.llm_health.record(response.is_ok(), started.elapsed());
+    trace.mark("llm");
+    let response = response?;
+    crate::ai::models::track(&state.db_pool, crate::ai::ModelKind::Chat, &model, None).await;
     
-    // Store significant messages in long-term memory
     if let Some(embedding) = query_embedding {
-        // Only store if message is substantial (>10 chars)
-        if user_message.len() > 10 {
-            if let Err(e) = crate::ai::store_memory(
-                &state.db_pool,
-                account.id,
-                chat_id,
-                user_message,
-                &embedding,
-            ).await {
-                tracing::warn!("Failed to store memory: {}", e);
-            }
-            
-            // Cleanup old memories periodically (every 100th message)
-            if rand::random::<u8>() % 100 == 0 {
-                if let Err(e) = crate::ai::cleanup_old_memories(&state.db_pool, account.id, chat_id).await {
-                    tracing::warn!("Failed to cleanup old memories: {}", e);
-                }
+        if cacheable && !response.trim().is_empty() && !response.contains("<IGNORE>") {
+            if let Err(e) = crate::ai::answer_cache::store(state, account.id, chat_id, persona_id, user_message, &embedding, &response).await {
+                tracing::warn!("Failed to cache answer: {}", e);
             }
         }
+
+        if memory_write.stores_incoming() {
+            remember_message(state, account.id, chat_settings, persona_id, incoming, &embedding).await;
+        }
     }
     
     Ok(response)
 }
 
+/// Store a message and the account's non-text answer to it (a poll, a re-sent picture) in history
+async fn save_exchange(state: &AppState, account_id: i64, incoming: &IncomingMessage, answer: &str, persona_id: Option<i64>) {
+    let asked = NewMessage {
+        account_id,
+        chat_id: incoming.chat_id,
+        role: MessageRole::User,
+        content: incoming.text.clone(),
+        sender_id: (incoming.sender_id != 0).then_some(incoming.sender_id),
+        sender_chat_id: incoming.sender_chat_id,
+        persona_id: None,
+    };
+    let answered = NewMessage {
+        account_id,
+        chat_id: incoming.chat_id,
+        role: MessageRole::Assistant,
+        content: answer.to_string(),
+        sender_id: None,
+        sender_chat_id: None,
+        persona_id,
+    };
+    for message in [asked, answered] {
+        if let Err(e) = AccountRepository::add_message(&state.db_pool, message).await {
+            tracing::warn!("Failed to save message to history: {}", e);
+        }
+    }
+}
+
+/// The remembered picture a request means; failures just mean a text reply
+async fn find_remembered_media(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    text: &str,
+) -> Option<crate::ai::media_memory::RememberedMedia> {
+    let embedding = match crate::ai::generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        text,
+    )
+    .await
+    {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            tracing::warn!("Failed to embed media request in chat {}: {}", chat_id, e);
+            return None;
+        }
+    };
+    match crate::ai::media_memory::find(&state.db_pool, account_id, chat_id, &embedding).await {
+        Ok(found) => found,
+        Err(e) => {
+            tracing::warn!("Failed to look up remembered media in chat {}: {}", chat_id, e);
+            None
+        }
+    }
+}
+
+/// The poll the message asks for, when the model agrees it asks for one; failures fall back to a text reply
+async fn extract_poll_draft(
+    state: &AppState,
+    account_id: i64,
+    chat_id: i64,
+    text: &str,
+) -> Option<crate::ai::polls::PollDraft> {
+    let recent = match AccountRepository::get_recent_messages(&state.db_pool, account_id, chat_id, 15).await {
+        Ok(history) => history
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Err(e) => {
+            tracing::warn!("Failed to load history for poll in chat {}: {}", chat_id, e);
+            String::new()
+        }
+    };
+    match crate::ai::polls::extract_poll(state, &recent, text).await {
+        Ok(draft) => draft,
+        Err(e) => {
+            tracing::warn!("Poll extraction failed in chat {}: {}", chat_id, e);
+            None
+        }
+    }
+}
+
+/// Log and remember a message that is deliberately left unanswered
+async fn record_unanswered(
+    state: &AppState,
+    account: &crate::db::models::Account,
+    chat_settings: Option<&crate::db::AccountChat>,
+    incoming: &IncomingMessage,
+) {
+    let message = NewMessage {
+        account_id: account.id,
+        chat_id: incoming.chat_id,
+        role: MessageRole::User,
+        content: incoming.text.clone(),
+        sender_id: (incoming.sender_id != 0).then_some(incoming.sender_id),
+        sender_chat_id: incoming.sender_chat_id,
+        persona_id: None,
+    };
+    if let Err(e) = AccountRepository::add_message(&state.db_pool, message).await {
+        tracing::warn!("Failed to save unanswered message to history: {}", e);
+    }
+
+    if incoming.is_sticker || !crate::ai::is_memorable(&incoming.text, state.config.rag_min_memory_chars) {
+        return;
+    }
+    // The persona that would have answered decides whether anything is remembered
+    let persona = match super::rotation::resolve_chat_persona(state, account, chat_settings).await {
+        Ok((_, Some(persona_id))) => crate::ai::persona_inheritance::resolve(&state.db_pool, persona_id).await.ok().flatten(),
+        _ => None,
+    };
+    if !crate::ai::memory_policy::MemoryWritePolicy::of(persona.as_ref()).stores_incoming() {
+        return;
+    }
+
+    match crate::ai::generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        &incoming.text,
+    )
+    .await
+    {
+        Ok(embedding) => {
+            remember_message(state, account.id, chat_settings, persona.as_ref().map(|p| p.id), incoming, &embedding).await
+        }
+        Err(e) => tracing::warn!("Failed to embed unanswered message: {}", e),
+    }
+}
+
+/// Store a significant incoming message in long-term memory
+async fn remember_message(
+    state: &AppState,
+    account_id: i64,
+    chat_settings: Option<&crate::db::AccountChat>,
+    persona_id: Option<i64>,
+    incoming: &IncomingMessage,
+    embedding: &[f32],
+) {
+    // Only store messages that carry some information ("ок", "привет" don't)
+    if !crate::ai::is_memorable(&incoming.text, state.config.rag_min_memory_chars) {
+        return;
+    }
+    // Other bots' messages weigh less, or aren't remembered at all
+    let importance = if incoming.sender_is_bot {
+        match crate::ai::rag::bot_memory_importance(&state.config, chat_settings) {
+            Some(importance) => importance,
+            None => return,
+        }
+    } else {
+        1.0
+    };
+
+    if let Err(e) = crate::ai::store_memory(
+        &state.db_pool,
+        account_id,
+        incoming.chat_id,
+        Some(incoming.message_id),
+        Some(incoming.sender_id).filter(|id| *id != 0),
+        incoming.sender_is_bot,
+        persona_id,
+        &incoming.text,
+        embedding,
+        &state.config.ollama_embed_model,
+        importance,
+    ).await {
+        tracing::warn!("Failed to store memory: {}", e);
+    } else if let Some(media) = incoming.media.as_ref().filter(|_| state.config.image_memory) {
+        if let Err(e) = crate::ai::media_memory::attach(&state.db_pool, account_id, incoming.chat_id, &incoming.text, media).await {
+            tracing::warn!("Failed to remember media: {}", e);
+        }
+    }
+    
+    // Cleanup old memories periodically (every 100th message)
+    if rand::random::<u8>() % 100 == 0 {
+        if let Err(e) = crate::ai::cleanup_old_memories(&state.db_pool, account_id, incoming.chat_id).await {
+            tracing::warn!("Failed to cleanup old memories: {}", e);
+        }
+    }
+}
+
+/// Store the persona's own reply in long-term memory, for personas whose policy allows it
+async fn remember_reply(
+    state: &AppState,
+    account_id: i64,
+    chat_settings: Option<&crate::db::AccountChat>,
+    chat_id: i64,
+    persona_id: Option<i64>,
+    reply: &str,
+) {
+    let reply = reply.replace("||", "\n");
+    if !crate::ai::is_memorable(&reply, state.config.rag_min_memory_chars) {
+        return;
+    }
+    // Our own phrasing shouldn't dominate what retrieval brings back
+    let importance = match crate::ai::rag::bot_memory_importance(&state.config, chat_settings) {
+        Some(importance) => importance,
+        None => return,
+    };
+
+    let embedding = match crate::ai::generate_embedding_cached(
+        &reqwest::Client::new(),
+        &state.db_pool,
+        &state.config.ollama_url,
+        &state.config.ollama_embed_model,
+        &reply,
+    )
+    .await
+    {
+        Ok(embedding) => embedding,
+        Err(e) => {
+            tracing::warn!("Failed to embed reply for memory: {}", e);
+            return;
+        }
+    };
+
+    if let Err(e) = crate::ai::store_memory(
+        &state.db_pool,
+        account_id,
+        chat_id,
+        None,
+        None,
+        true,
+        persona_id,
+        &reply,
+        &embedding,
+        &state.config.ollama_embed_model,
+        importance,
+    )
+    .await
+    {
+        tracing::warn!("Failed to store reply memory: {}", e);
+    }
+}
+
 /// Notify owner about system events (errors, warnings, etc.)
-async fn notify_owner(state: &AppState, message: &str) -> Result<()> {
+pub(crate) async fn notify_owner(state: &AppState, message: &str) -> Result<()> {
     use teloxide::prelude::*;
     use teloxide::types::ChatId;
     
@@ -729,7 +2066,9 @@ async fn notify_owner(state: &AppState, message: &str) -> Result<()> {
 /// Process photo with vision model
 async fn process_photo(
     state: &AppState,
+    account: &crate::db::models::Account,
     client: &Arc<Mutex<TdClient>>,
+    chat_id: i64,
     photo: &MessagePhoto,
 ) -> Result<String> {
     // Get the largest photo size
@@ -747,13 +2086,15 @@ async fn process_photo(
     use base64::Engine;
     let base64_image = base64::engine::general_purpose::STANDARD.encode(&image_bytes);
     
-    // Analyze with vision model
-    let ollama_client = crate::ai::ollama::OllamaClient::new(state.config.ollama_url.clone());
-    let description = ollama_client.vision(
-        "llava", // or minicpm-v
-        "Опиши что на этом изображении. Будь кратким, 1-2 предложения.",
-        vec![base64_image],
-    ).await?;
+    // Prompt for this media type, as overridden for the answering persona
+    use crate::ai::vision_prompts::{prompt_for, MediaType};
+    let persona = super::rotation::current_persona(state, account, chat_id).await.unwrap_or(None);
+    let prompt = prompt_for(&state.db_pool, MediaType::Photo, persona.as_ref(), Some(photo.caption().text().as_str())).await;
+
+    // Screenshots may go to a model that reads text better
+    use crate::ai::vision_routing::classify_photo;
+    let category = classify_photo(&state.config.vision_routing, photo_size.width(), photo_size.height(), &file_path).await;
+    let description = describe_media(state, category, &prompt, vec![base64_image]).await?;
     
     // Clean up temp file
     let _ = tokio::fs::remove_file(file_path).await;
@@ -764,7 +2105,9 @@ async fn process_photo(
 /// Process animation/GIF with vision model (extract 3 frames)
 async fn process_animation(
     state: &AppState,
+    account: &crate::db::models::Account,
     client: &Arc<Mutex<TdClient>>,
+    chat_id: i64,
     animation: &MessageAnimation,
 ) -> Result<String> {
     let file_id = animation.animation().animation().id();
@@ -783,13 +2126,13 @@ async fn process_animation(
         base64_frames.push(base64::engine::general_purpose::STANDARD.encode(&frame_bytes));
     }
     
+    // Prompt for this media type, as overridden for the answering persona
+    use crate::ai::vision_prompts::{prompt_for, MediaType};
+    let persona = super::rotation::current_persona(state, account, chat_id).await.unwrap_or(None);
+    let prompt = prompt_for(&state.db_pool, MediaType::Animation, persona.as_ref(), Some(animation.caption().text().as_str())).await;
+
     // Analyze with vision model
-    let ollama_client = crate::ai::ollama::OllamaClient::new(state.config.ollama_url.clone());
-    let description = ollama_client.vision(
-        "llava",
-        "Опиши что происходит в этой гифке/анимации. Будь кратким, 1-2 предложения.",
-        base64_frames,
-    ).await?;
+    let description = describe_media(state, crate::ai::vision_routing::VisionCategory::Animation, &prompt, base64_frames).await?;
     
     // Clean up temp files
     let _ = tokio::fs::remove_file(file_path).await;
@@ -803,7 +2146,9 @@ async fn process_animation(
 /// Process voice message with Whisper
 async fn process_voice(
     state: &AppState,
+    account: &crate::db::models::Account,
     client: &Arc<Mutex<TdClient>>,
+    chat_id: i64,
     voice: &MessageVoiceNote,
 ) -> Result<String> {
     let file_id = voice.voice_note().voice().id();
@@ -815,21 +2160,183 @@ async fn process_voice(
     let whisper_url = state.config.whisper_url.as_ref()
         .context("Whisper URL not configured")?;
     
-    let transcription = crate::ai::whisper::transcribe_audio(
-        whisper_url,
-        std::path::Path::new(&file_path),
-    ).await?;
-    
+    let duration = voice.voice_note().duration();
+    let started = std::time::Instant::now();
+    let (progress_tx, mut progress_rx) = tokio::sync::watch::channel((0, 0));
+
+    // Whisper runs WHISPER_CONCURRENCY transcriptions at a time; the rest wait here
+    let transcription = crate::ai::whisper::run_queued(
+        &state.transcription_slots,
+        std::time::Duration::from_secs(state.config.whisper_timeout_secs),
+        Some(state.config.whisper_placeholder_after_secs)
+            .filter(|s| *s > 0)
+            .map(std::time::Duration::from_secs),
+        send_voice_placeholder(client, account.id, chat_id),
+        crate::ai::whisper::transcribe_chunked(
+            whisper_url,
+            std::path::Path::new(&file_path),
+            duration.max(0) as u32,
+            state.config.voice_chunk_secs,
+            std::time::Duration::from_secs(state.config.whisper_timeout_secs),
+            move |done, total| {
+                tracing::info!(
+                    "Transcribing {}s voice note {}: {}/{} segments ({:.0}s elapsed)",
+                    duration,
+                    file_id,
+                    done,
+                    total,
+                    started.elapsed().as_secs_f64()
+                );
+                let _ = progress_tx.send((done, total));
+            },
+        ),
+    );
+
+    // Long notes show how far they got; the channel closes once the transcription ends
+    let progress = async {
+        let mut status = None;
+        while progress_rx.changed().await.is_ok() {
+            let (done, total) = *progress_rx.borrow_and_update();
+            if done < total {
+                status = show_voice_progress(client, account.id, chat_id, status, done, total).await;
+            }
+        }
+        status
+    };
+    let (transcription, status) = tokio::join!(transcription, progress);
+    if let Some(temporary_id) = status {
+        remove_voice_progress(client, account.id, chat_id, temporary_id).await;
+    }
+
     // Clean up temp file
     let _ = tokio::fs::remove_file(file_path).await;
     
-    Ok(transcription)
+    transcription
+}
+
+/// Send or update the "listening" status of a long transcription; returns its temporary message id
+async fn show_voice_progress(
+    client: &Arc<Mutex<TdClient>>,
+    account_id: i64,
+    chat_id: i64,
+    status: Option<i64>,
+    done: usize,
+    total: usize,
+) -> Option<i64> {
+    let content = InputMessageContent::InputMessageText(
+        InputMessageText::builder()
+            .text(FormattedText::builder().text(format!("слушаю голосовое… {}/{}", done, total)).build())
+            .build(),
+    );
+
+    let temporary_id = match status {
+        Some(temporary_id) => temporary_id,
+        None => {
+            let send_message = SendMessage::builder().chat_id(chat_id).input_message_content(content).build();
+            return match client.lock().await.send_message(&send_message).await {
+                Ok(message) => {
+                    VOICE_PROGRESS_IDS
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert((account_id, chat_id, message.id()), VoiceProgress::Sending);
+                    Some(message.id())
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to send transcription progress to chat {}: {}", chat_id, e);
+                    None
+                }
+            };
+        }
+    };
+
+    // Until TDLib confirms the send, the message can't be edited yet; the next segment tries again
+    let sent_id = match VOICE_PROGRESS_IDS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&(account_id, chat_id, temporary_id))
+    {
+        Some(VoiceProgress::Sent(id)) => Some(*id),
+        _ => None,
+    };
+    if let Some(message_id) = sent_id {
+        let edit = EditMessageText::builder()
+            .chat_id(chat_id)
+            .message_id(message_id)
+            .input_message_content(content)
+            .build();
+        if let Err(e) = client.lock().await.edit_message_text(&edit).await {
+            tracing::debug!("Failed to update transcription progress in chat {}: {}", chat_id, e);
+        }
+    }
+    Some(temporary_id)
+}
+
+/// Delete the status message once the transcription is over, or once TDLib confirms it if it hasn't yet
+async fn remove_voice_progress(client: &Arc<Mutex<TdClient>>, account_id: i64, chat_id: i64, temporary_id: i64) {
+    let key = (account_id, chat_id, temporary_id);
+    let sent_id = {
+        let mut progress = VOICE_PROGRESS_IDS.lock().unwrap_or_else(|e| e.into_inner());
+        match progress.get(&key).copied() {
+            Some(VoiceProgress::Sent(id)) => {
+                progress.remove(&key);
+                id
+            }
+            Some(_) => {
+                progress.insert(key, VoiceProgress::Abandoned);
+                return;
+            }
+            None => return,
+        }
+    };
+    delete_voice_progress(client, chat_id, sent_id).await;
+}
+
+async fn delete_voice_progress(client: &Arc<Mutex<TdClient>>, chat_id: i64, message_id: i64) {
+    let delete = DeleteMessages::builder()
+        .chat_id(chat_id)
+        .message_ids(vec![message_id])
+        .revoke(true)
+        .build();
+    if let Err(e) = client.lock().await.delete_messages(&delete).await {
+        tracing::warn!("Failed to delete transcription progress in chat {}: {}", chat_id, e);
+    }
+}
+
+/// Let the chat know a voice note will be answered, once per chat per cooldown
+async fn send_voice_placeholder(client: &Arc<Mutex<TdClient>>, account_id: i64, chat_id: i64) {
+    {
+        let mut sent = VOICE_PLACEHOLDER_SENT.lock().unwrap_or_else(|e| e.into_inner());
+        let now = std::time::Instant::now();
+        let recent = sent
+            .get(&(account_id, chat_id))
+            .is_some_and(|at| now.duration_since(*at).as_secs() < VOICE_PLACEHOLDER_COOLDOWN_SECS);
+        if recent {
+            return;
+        }
+        sent.insert((account_id, chat_id), now);
+    }
+
+    let text = VOICE_PLACEHOLDERS[rand::random::<usize>() % VOICE_PLACEHOLDERS.len()];
+    let send_message = SendMessage::builder()
+        .chat_id(chat_id)
+        .input_message_content(InputMessageContent::InputMessageText(
+            InputMessageText::builder()
+                .text(FormattedText::builder().text(text).build())
+                .build(),
+        ))
+        .build();
+
+    if let Err(e) = client.lock().await.send_message(&send_message).await {
+        tracing::warn!("Failed to send voice placeholder to chat {}: {}", chat_id, e);
+    }
 }
 
 /// Process video note/circle (extract 3 frames)
 async fn process_video_note(
     state: &AppState,
+    account: &crate::db::models::Account,
     client: &Arc<Mutex<TdClient>>,
+    chat_id: i64,
     video_note: &MessageVideoNote,
 ) -> Result<String> {
     let file_id = video_note.video_note().video().id();
@@ -848,13 +2355,13 @@ async fn process_video_note(
         base64_frames.push(base64::engine::general_purpose::STANDARD.encode(&frame_bytes));
     }
     
+    // Prompt for this media type, as overridden for the answering persona
+    use crate::ai::vision_prompts::{prompt_for, MediaType};
+    let persona = super::rotation::current_persona(state, account, chat_id).await.unwrap_or(None);
+    let prompt = prompt_for(&state.db_pool, MediaType::VideoNote, persona.as_ref(), None).await;
+
     // Analyze with vision model
-    let ollama_client = crate::ai::ollama::OllamaClient::new(state.config.ollama_url.clone());
-    let description = ollama_client.vision(
-        "llava",
-        "Опиши что происходит в этом видео кружке. Будь кратким, 1-2 предложения.",
-        base64_frames,
-    ).await?;
+    let description = describe_media(state, crate::ai::vision_routing::VisionCategory::VideoNote, &prompt, base64_frames).await?;
     
     // Clean up temp files
     let _ = tokio::fs::remove_file(file_path).await;
@@ -865,8 +2372,25 @@ async fn process_video_note(
     Ok(description)
 }
 
+/// Ask the vision model routed for this kind of media, with its settings
+async fn describe_media(
+    state: &AppState,
+    category: crate::ai::vision_routing::VisionCategory,
+    prompt: &str,
+    images: Vec<String>,
+) -> Result<String> {
+    let routing = &state.config.vision_routing;
+    let model = routing.model_for(category, &state.config.ollama_vision_model);
+    tracing::debug!("Describing {} with {}", category.as_str(), model);
+
+    let ollama_client = crate::ai::ollama::OllamaClient::new(state.config.ollama_url.clone());
+    let description = ollama_client.vision(model, prompt, images, routing.options_for(model)).await?;
+    crate::ai::models::track(&state.db_pool, crate::ai::ModelKind::Vision, model, None).await;
+    Ok(description)
+}
+
 /// Download file from TDLib
-async fn download_file(client: &Arc<Mutex<TdClient>>, file_id: i32) -> Result<String> {
+pub(super) async fn download_file(client: &Arc<Mutex<TdClient>>, file_id: i32) -> Result<String> {
     let client_lock = client.lock().await;
     
     let download_file = DownloadFile::builder()
@@ -0,0 +1,300 @@
+use crate::{
+    db::{MessageRepository, Webhook, WebhookDelivery, WebhookRepository},
+    AppState,
+};
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How often due deliveries are sent
+const CHECK_INTERVAL_SECS: u64 = 15;
+
+/// Deliveries sent per pass
+const BATCH_SIZE: i64 = 50;
+
+/// Wait before each retry; a delivery is dropped after the last one
+const RETRY_BACKOFF_SECS: &[i64] = &[60, 5 * 60, 30 * 60, 2 * 60 * 60, 6 * 60 * 60];
+
+/// Keyword webhooks are re-read this often, not on every chat message
+const KEYWORD_CACHE_SECS: u64 = 60;
+
+/// Something external systems can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A persona started answering for an account or in a chat
+    PersonaActivated,
+    /// A message was blocked as a prompt injection
+    SecurityBlock,
+    /// Yesterday's activity per account, once a day after midnight UTC
+    DailyStats,
+    /// A chat message contained one of the webhook's keywords
+    Keyword,
+    /// A voice or video note mentioning a chat's escalation keywords was forwarded to the owner
+    Escalation,
+    /// Sent by /webhook_test
+    Ping,
+}
+
+impl Event {
+    pub const ALL: [Event; 6] = [
+        Event::PersonaActivated,
+        Event::SecurityBlock,
+        Event::DailyStats,
+        Event::Keyword,
+        Event::Escalation,
+        Event::Ping,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Event::PersonaActivated => "persona_activated",
+            Event::SecurityBlock => "security_block",
+            Event::DailyStats => "daily_stats",
+            Event::Keyword => "keyword",
+            Event::Escalation => "escalation",
+            Event::Ping => "ping",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|e| e.as_str() == s.trim())
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref KEYWORD_HOOKS: Mutex<Option<(Instant, Vec<Webhook>)>> = Mutex::new(None);
+}
+
+/// Forget cached keyword webhooks after they were changed
+pub fn invalidate_cache() {
+    *KEYWORD_HOOKS.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+/// `sha256=<hex>` of the body, sent as X-Puppeteer-Signature
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", hex)
+}
+
+/// A random signing secret
+pub fn generate_secret() -> String {
+    (0..32).map(|_| format!("{:02x}", rand::random::<u8>())).collect()
+}
+
+/// Seconds to wait before retry number `attempts + 1`, or None to give up
+pub fn retry_delay(attempts: i64) -> Option<i64> {
+    RETRY_BACKOFF_SECS.get(attempts as usize).copied()
+}
+
+/// The JSON body of an event
+fn envelope(event: Event, data: serde_json::Value) -> String {
+    json!({
+        "event": event.as_str(),
+        "sent_at": chrono::Utc::now().to_rfc3339(),
+        "data": data,
+    })
+    .to_string()
+}
+
+/// Queue an event for every webhook subscribed to it. Never fails the caller.
+pub async fn emit(state: &AppState, event: Event, data: serde_json::Value) {
+    let webhooks = match WebhookRepository::list_active(&state.db_pool).await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            tracing::warn!("Failed to list webhooks for {}: {}", event.as_str(), e);
+            return;
+        }
+    };
+
+    let payload = envelope(event, data);
+    for webhook in webhooks.iter().filter(|w| w.wants(event.as_str())) {
+        if let Err(e) = WebhookRepository::enqueue(&state.db_pool, webhook.id, event.as_str(), &payload).await {
+            tracing::warn!("Failed to queue {} for webhook {}: {}", event.as_str(), webhook.id, e);
+        }
+    }
+}
+
+/// Queue a test event for one webhook
+pub async fn ping(state: &AppState, webhook_id: i64) -> Result<()> {
+    let payload = envelope(Event::Ping, json!({ "webhook_id": webhook_id }));
+    WebhookRepository::enqueue(&state.db_pool, webhook_id, Event::Ping.as_str(), &payload).await
+}
+
+/// Fire `keyword` webhooks whose words appear in a chat message
+pub async fn match_keywords(state: &AppState, account_id: i64, chat_id: i64, sender_id: i64, text: &str) {
+    let cached = KEYWORD_HOOKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .filter(|(at, _)| at.elapsed() < Duration::from_secs(KEYWORD_CACHE_SECS))
+        .map(|(_, hooks)| hooks.clone());
+
+    let hooks = match cached {
+        Some(hooks) => hooks,
+        None => {
+            let hooks: Vec<Webhook> = match WebhookRepository::list_active(&state.db_pool).await {
+                Ok(all) => all.into_iter().filter(|w| w.wants(Event::Keyword.as_str())).collect(),
+                Err(e) => {
+                    tracing::warn!("Failed to list keyword webhooks: {}", e);
+                    return;
+                }
+            };
+            *KEYWORD_HOOKS.lock().unwrap_or_else(|e| e.into_inner()) = Some((Instant::now(), hooks.clone()));
+            hooks
+        }
+    };
+
+    let lowered = text.to_lowercase();
+    for hook in hooks {
+        let matched: Vec<String> = hook.get_keywords().into_iter().filter(|k| lowered.contains(k.as_str())).collect();
+        if matched.is_empty() {
+            continue;
+        }
+
+        let payload = envelope(
+            Event::Keyword,
+            json!({
+                "account_id": account_id,
+                "chat_id": chat_id,
+                "sender_id": sender_id,
+                "keywords": matched,
+                "text": text.chars().take(1000).collect::<String>(),
+            }),
+        );
+        if let Err(e) = WebhookRepository::enqueue(&state.db_pool, hook.id, Event::Keyword.as_str(), &payload).await {
+            tracing::warn!("Failed to queue keyword event for webhook {}: {}", hook.id, e);
+        }
+    }
+}
+
+/// Send queued deliveries with retries, and emit daily stats after midnight UTC
+pub async fn webhook_worker(state: AppState) {
+    tracing::info!("Webhook worker started");
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    let mut stats_day = chrono::Utc::now().date_naive();
+
+    loop {
+        tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+
+        let today = chrono::Utc::now().date_naive();
+        if today != stats_day {
+            stats_day = today;
+            emit_daily_stats(&state).await;
+        }
+
+        let due = match WebhookRepository::due_deliveries(&state.db_pool, BATCH_SIZE).await {
+            Ok(due) => due,
+            Err(e) => {
+                tracing::error!("Failed to fetch webhook deliveries: {}", e);
+                continue;
+            }
+        };
+
+        for delivery in due {
+            if let Err(e) = deliver(&state, &client, &delivery).await {
+                tracing::warn!("Webhook delivery {} failed: {}", delivery.id, e);
+            }
+        }
+    }
+}
+
+async fn deliver(state: &AppState, client: &reqwest::Client, delivery: &WebhookDelivery) -> Result<()> {
+    let webhook = match WebhookRepository::get(&state.db_pool, delivery.webhook_id).await? {
+        Some(w) if w.is_active => w,
+        _ => return WebhookRepository::remove_delivery(&state.db_pool, delivery.id).await,
+    };
+
+    let result = client
+        .post(&webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header("X-Puppeteer-Event", &delivery.event)
+        .header("X-Puppeteer-Delivery", delivery.id.to_string())
+        .header("X-Puppeteer-Signature", sign(&webhook.secret, &delivery.payload))
+        .body(delivery.payload.clone())
+        .send()
+        .await;
+
+    let failure = match result {
+        Ok(response) if response.status().is_success() => None,
+        Ok(response) => Some(format!("HTTP {}", response.status())),
+        Err(e) => Some(e.to_string()),
+    };
+
+    let failure = match failure {
+        None => {
+            WebhookRepository::set_status(&state.db_pool, webhook.id, &format!("ok ({})", delivery.event)).await?;
+            return WebhookRepository::remove_delivery(&state.db_pool, delivery.id).await;
+        }
+        Some(failure) => failure,
+    };
+
+    match retry_delay(delivery.attempts) {
+        Some(delay) => {
+            WebhookRepository::retry_later(&state.db_pool, delivery.id, delay).await?;
+            WebhookRepository::set_status(&state.db_pool, webhook.id, &format!("retrying: {}", failure)).await?;
+        }
+        None => {
+            tracing::warn!("Giving up on {} for webhook {}: {}", delivery.event, webhook.id, failure);
+            WebhookRepository::remove_delivery(&state.db_pool, delivery.id).await?;
+            WebhookRepository::set_status(&state.db_pool, webhook.id, &format!("dropped {}: {}", delivery.event, failure))
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Messages received and replies sent per account over the last day
+async fn emit_daily_stats(state: &AppState) {
+    let rows = match MessageRepository::activity_since(&state.db_pool, 24).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::warn!("Failed to collect daily stats: {}", e);
+            return;
+        }
+    };
+
+    let mut accounts: std::collections::BTreeMap<i64, serde_json::Map<String, serde_json::Value>> = Default::default();
+    for (account_id, role, count) in rows {
+        accounts.entry(account_id).or_default().insert(role, json!(count));
+    }
+    let accounts: Vec<serde_json::Value> = accounts
+        .into_iter()
+        .map(|(account_id, counts)| json!({ "account_id": account_id, "messages": counts }))
+        .collect();
+
+    emit(state, Event::DailyStats, json!({ "hours": 24, "accounts": accounts })).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_like_rfc_4231() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn retries_then_gives_up() {
+        assert_eq!(retry_delay(0), Some(60));
+        assert!(retry_delay(RETRY_BACKOFF_SECS.len() as i64).is_none());
+    }
+
+    #[test]
+    fn parses_event_names() {
+        assert_eq!(Event::parse("security_block"), Some(Event::SecurityBlock));
+        assert!(Event::parse("everything").is_none());
+    }
+}